@@ -4,10 +4,26 @@
 //! トークン数を削減しつつ重要なコンテキストを保持する。
 
 use super::conversation::{Conversation, Message, Role};
+use crate::llm::{LlmBackend, RequestOptions};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
-/// 圧縮設定
+/// LLM 要約のシステムプロンプト
+const SUMMARY_SYSTEM_PROMPT: &str = "You summarize earlier parts of a conversation between a user and a coding assistant \
+so the assistant can continue the work without the original messages. \
+Write a concise summary in plain text: the user's goals and requests, decisions made, \
+files and code that were read or changed, tool results that still matter, and open tasks. \
+Do not invent details. Do not address the user.";
+
+/// LLM 要約の温度（要約は言い換えより正確さを優先）
+const SUMMARY_TEMPERATURE: f32 = 0.2;
+
+/// 要約に渡す1メッセージあたりの最大文字数
+const SUMMARY_INPUT_CHARS: usize = 2000;
+
+/// 圧縮設定（`[agent.compression]`、省略した項目は既定値）
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CompressionConfig {
     /// 圧縮を開始するトークン使用率の閾値 (0.0-1.0)
     pub threshold: f32,
@@ -19,16 +35,22 @@ pub struct CompressionConfig {
     pub preserve_code_blocks: bool,
     /// ツール結果を保持するか
     pub preserve_tool_results: bool,
+    /// 要約をモデルに書かせる（失敗・タイムアウト時は簡易要約）
+    pub use_llm_summary: bool,
+    /// LLM 要約のタイムアウト（秒）
+    pub llm_summary_timeout_secs: u64,
 }
 
 impl Default for CompressionConfig {
     fn default() -> Self {
         Self {
             threshold: 0.5,      // 50%で圧縮開始
-            max_tokens: 128000,  // 128K tokens
+            max_tokens: crate::llm::context_window::DEFAULT_MAX_NUM_CTX as usize, // num_ctx の上限と同じ
             preserve_recent: 10, // 直近10メッセージは保持
             preserve_code_blocks: true,
             preserve_tool_results: true,
+            use_llm_summary: false,
+            llm_summary_timeout_secs: 60,
         }
     }
 }
@@ -104,6 +126,11 @@ impl ContextCompressor {
         Self { config }
     }
 
+    /// 現在の設定
+    pub fn config(&self) -> &CompressionConfig {
+        &self.config
+    }
+
     /// 閾値を設定
     pub fn with_threshold(mut self, threshold: f32) -> Self {
         self.config.threshold = threshold.clamp(0.0, 1.0);
@@ -126,40 +153,108 @@ impl ContextCompressor {
 
     /// 会話を圧縮
     pub fn compress(&self, conversation: &Conversation) -> CompressedConversation {
+        let (system_message, old_messages, recent_messages) = self.partition(conversation);
+        let summary = (!old_messages.is_empty()).then(|| self.heuristic_summary(&old_messages));
+        self.assemble(conversation, system_message, old_messages, recent_messages, summary)
+    }
+
+    /// 会話を圧縮（要約はモデルに書かせ、失敗やタイムアウト時は簡易要約にする）
+    pub async fn compress_with_llm(&self, conversation: &Conversation, client: &dyn LlmBackend) -> CompressedConversation {
+        let (system_message, old_messages, recent_messages) = self.partition(conversation);
+        let summary = if old_messages.is_empty() {
+            None
+        } else {
+            Some(match self.llm_summary(&old_messages, client).await {
+                Ok(summary) => self.fit_summary(summary),
+                Err(e) => {
+                    tracing::warn!("LLM summary failed, using heuristic summary: {}", e);
+                    self.heuristic_summary(&old_messages)
+                }
+            })
+        };
+        self.assemble(conversation, system_message, old_messages, recent_messages, summary)
+    }
+
+    /// 先頭のシステムメッセージ、要約する古いメッセージ、保持する直近のメッセージに分ける
+    fn partition(&self, conversation: &Conversation) -> (Option<Message>, Vec<Message>, Vec<Message>) {
         let messages = conversation.messages();
-        let original_count = messages.len();
 
-        // システムメッセージを抽出
-        let system_message = messages.iter().find(|m| m.role == Role::System).cloned();
+        // システムメッセージ（先頭のもの）を抽出
+        let system_index = messages.iter().position(|m| m.role == Role::System);
+        let system_message = system_index.map(|i| messages[i].clone());
 
-        // 非システムメッセージを取得
-        let non_system: Vec<_> = messages
+        // それ以外（以前の要約も含む）
+        let mut non_system: Vec<_> = messages
             .iter()
-            .filter(|m| m.role != Role::System)
-            .cloned()
+            .enumerate()
+            .filter(|(i, _)| Some(*i) != system_index)
+            .map(|(_, m)| m.clone())
             .collect();
 
-        // 圧縮が不要な場合
-        if non_system.len() <= self.config.preserve_recent {
+        // 圧縮が不要な場合は古いメッセージなし
+        let split_point = non_system.len().saturating_sub(self.config.preserve_recent);
+        let recent_messages = non_system.split_off(split_point);
+        (system_message, non_system, recent_messages)
+    }
+
+    /// 簡易要約（重要なメッセージを抜き出して予算に収める）
+    fn heuristic_summary(&self, old_messages: &[Message]) -> String {
+        let important_from_old = self.extract_important_messages(old_messages);
+        self.fit_summary(self.summarize_messages(old_messages, &important_from_old))
+    }
+
+    /// 古いメッセージをモデルに要約させる
+    async fn llm_summary(&self, old_messages: &[Message], client: &dyn LlmBackend) -> anyhow::Result<String> {
+        let transcript = old_messages
+            .iter()
+            .map(|m| {
+                let role = match m.role {
+                    Role::System => "Earlier summary",
+                    Role::User => "User",
+                    Role::Assistant => "Assistant",
+                    Role::Tool => "Tool result",
+                };
+                format!("{}: {}", role, truncate_chars(&m.content, SUMMARY_INPUT_CHARS))
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let prompt = format!("Summarize this conversation:\n\n{}\n\nSummary:", transcript);
+        let options = RequestOptions {
+            temperature: Some(SUMMARY_TEMPERATURE),
+            num_predict: Some(self.summary_budget() as u32),
+            ..RequestOptions::default()
+        };
+
+        let timeout = Duration::from_secs(self.config.llm_summary_timeout_secs);
+        let summary = tokio::time::timeout(timeout, client.generate_with_options(&prompt, Some(SUMMARY_SYSTEM_PROMPT), options))
+            .await
+            .map_err(|_| anyhow::anyhow!("timed out after {} seconds", timeout.as_secs()))??;
+        let summary = summary.trim();
+        if summary.is_empty() {
+            anyhow::bail!("model returned an empty summary");
+        }
+        Ok(summary.to_string())
+    }
+
+    /// 分割結果と要約から圧縮された会話を作る
+    fn assemble(
+        &self,
+        conversation: &Conversation,
+        system_message: Option<Message>,
+        old_messages: Vec<Message>,
+        recent_messages: Vec<Message>,
+        summary: Option<String>,
+    ) -> CompressedConversation {
+        let original_count = conversation.messages().len();
+        let Some(summary) = summary else {
             return CompressedConversation {
                 system_message,
                 compressed_history: None,
-                preserved_messages: non_system,
+                preserved_messages: recent_messages,
                 original_message_count: original_count,
                 estimated_tokens_saved: 0,
             };
-        }
-
-        // 古いメッセージと保持するメッセージを分離
-        let split_point = non_system.len() - self.config.preserve_recent;
-        let old_messages: Vec<_> = non_system[..split_point].to_vec();
-        let recent_messages: Vec<_> = non_system[split_point..].to_vec();
-
-        // 重要なメッセージを抽出
-        let important_from_old = self.extract_important_messages(&old_messages);
-
-        // 古いメッセージを要約
-        let summary = self.summarize_messages(&old_messages, &important_from_old);
+        };
 
         // 推定トークン削減数を計算
         let old_tokens: usize = old_messages
@@ -191,9 +286,10 @@ impl ContextCompressor {
     }
 
     /// メッセージのトークン数を推定
-    fn estimate_message_tokens(&self, message: &Message) -> usize {
+    pub fn estimate_message_tokens(&self, message: &Message) -> usize {
         // 簡易推定: 4文字 = 1トークン（日本語は2文字 = 1トークン）
-        self.estimate_text_tokens(&message.content) + 4 // role分のオーバーヘッド
+        let pinned = message.pinned.as_deref().map(|p| self.estimate_text_tokens(p)).unwrap_or(0);
+        self.estimate_text_tokens(&message.content) + pinned + 4 // role分のオーバーヘッド
     }
 
     /// テキストのトークン数を推定
@@ -239,6 +335,12 @@ impl ContextCompressor {
 
         for msg in messages {
             match msg.role {
+                Role::System => {
+                    // 以前の要約は見出しを除いて引き継ぐ
+                    let previous = msg.content.split_once('\n').map(|(_, body)| body).unwrap_or(&msg.content);
+                    summary.push_str(previous.trim_end());
+                    summary.push('\n');
+                }
                 Role::User => {
                     // ユーザーの質問/リクエストを抽出
                     if let Some(topic) = self.extract_topic(&msg.content) {
@@ -295,36 +397,46 @@ impl ContextCompressor {
         summary
     }
 
+    /// 要約のトークン予算（閾値の半分）
+    fn summary_budget(&self) -> usize {
+        ((self.config.max_tokens as f32 * self.config.threshold / 2.0) as usize).max(1)
+    }
+
+    /// 要約を予算に収める
+    ///
+    /// 以前の要約を引き継ぐため、繰り返し圧縮すると古い行から落とす
+    fn fit_summary(&self, summary: String) -> String {
+        let budget = self.summary_budget();
+        let mut lines: Vec<&str> = summary.lines().collect();
+        let mut dropped = false;
+        while lines.len() > 1 && self.estimate_text_tokens(&lines.join("\n")) > budget {
+            lines.remove(0);
+            dropped = true;
+        }
+        let mut fitted = lines.join("\n");
+        if dropped {
+            fitted.insert_str(0, "(older history omitted)\n");
+        }
+        fitted
+    }
+
     /// トピックを抽出
     fn extract_topic(&self, content: &str) -> Option<String> {
         // 最初の1文または100文字を抽出
         let first_line = content.lines().next()?;
-        let truncated = if first_line.len() > 100 {
-            format!("{}...", &first_line[..97])
-        } else {
-            first_line.to_string()
-        };
-        Some(truncated)
+        Some(truncate_chars(first_line, 100))
     }
 
     /// アクションを抽出
     fn extract_action(&self, content: &str) -> Option<String> {
         // 最初の1文を抽出
         let first_sentence = content.split('.').next()?;
-        if first_sentence.len() > 100 {
-            Some(format!("{}...", &first_sentence[..97]))
-        } else {
-            Some(first_sentence.to_string())
-        }
+        Some(truncate_chars(first_sentence, 100))
     }
 
     /// コンテンツを短縮
     fn truncate_content(&self, content: &str, max_len: usize) -> String {
-        if content.len() <= max_len {
-            content.to_string()
-        } else {
-            format!("{}...", &content[..max_len.saturating_sub(3)])
-        }
+        truncate_chars(content, max_len)
     }
 
     /// コードブロックを抽出
@@ -361,9 +473,18 @@ impl Default for ContextCompressor {
     }
 }
 
+/// 文字数で切り詰める（マルチバイト文字の途中で切らない）
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars.saturating_sub(3)) {
+        Some((idx, _)) if text.chars().count() > max_chars => format!("{}...", &text[..idx]),
+        _ => text.to_string(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::llm::OllamaClient;
 
     #[test]
     fn test_should_compress() {
@@ -379,11 +500,11 @@ mod tests {
 
         // 多くのメッセージを追加
         for i in 0..50 {
-            conv.add_user(&format!(
+            conv.add_user(format!(
                 "Message {}: This is a longer message to increase token count",
                 i
             ));
-            conv.add_assistant(&format!(
+            conv.add_assistant(format!(
                 "Response {}: This is a longer response to increase token count",
                 i
             ));
@@ -421,8 +542,8 @@ mod tests {
 
         let mut conv = Conversation::new();
         for i in 0..10 {
-            conv.add_user(&format!("User message {}", i));
-            conv.add_assistant(&format!("Assistant message {}", i));
+            conv.add_user(format!("User message {}", i));
+            conv.add_assistant(format!("Assistant message {}", i));
         }
 
         let compressed = compressor.compress(&conv);
@@ -449,8 +570,8 @@ mod tests {
         let mut conv = Conversation::new();
         conv.set_system("System prompt");
         for i in 0..20 {
-            conv.add_user(&format!("User {}", i));
-            conv.add_assistant(&format!("Assistant {}", i));
+            conv.add_user(format!("User {}", i));
+            conv.add_assistant(format!("Assistant {}", i));
         }
 
         let compressed = compressor.compress(&conv);
@@ -459,4 +580,98 @@ mod tests {
         // 復元された会話はシステムメッセージを含む
         assert!(restored.messages().iter().any(|m| m.role == Role::System));
     }
+    #[test]
+    fn test_recompression_keeps_system_and_previous_summary() {
+        let config = CompressionConfig {
+            preserve_recent: 2,
+            ..Default::default()
+        };
+        let compressor = ContextCompressor::with_config(config);
+
+        let mut conv = Conversation::new();
+        conv.set_system("System prompt");
+        for i in 0..6 {
+            // マルチバイト文字の途中で切り詰めない
+            conv.add_user(format!("{}番目の質問{}", i, "あ".repeat(120)));
+            conv.add_assistant(format!("Answer {}", i));
+        }
+        let once = compressor.compress(&conv).to_conversation();
+        let mut twice_input = once.clone();
+        twice_input.add_user("follow-up");
+        twice_input.add_assistant("done");
+        let twice = compressor.compress(&twice_input).to_conversation();
+
+        assert_eq!(twice.messages()[0].content, "System prompt");
+        let summary = &twice.messages()[1];
+        assert_eq!(summary.role, Role::System);
+        assert!(summary.content.contains("0番目の質問"), "{}", summary.content);
+        assert!(summary.content.contains("Answer 4"));
+    }
+
+    fn long_conversation() -> Conversation {
+        let mut conv = Conversation::new();
+        conv.set_system("System prompt");
+        for i in 0..6 {
+            conv.add_user(format!("Question {} about the parser", i));
+            conv.add_assistant(format!("Answer {}", i));
+        }
+        conv
+    }
+
+    #[tokio::test]
+    async fn test_llm_summary_flows_into_restored_conversation() {
+        use crate::llm::mock_server::{http_response, spawn_mock_handler};
+        use std::sync::{Arc, Mutex};
+
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&requests);
+        let url = spawn_mock_handler(move |request| {
+            seen.lock().unwrap().push(request.to_string());
+            let body = serde_json::json!({
+                "model": "mock",
+                "response": "The user is refactoring the parser; answers 0-3 covered the tokenizer.",
+                "done": true
+            });
+            http_response("200 OK", &[], &body.to_string())
+        })
+        .await;
+        let client = OllamaClient::new(&url, "mock");
+        let compressor = ContextCompressor::with_config(CompressionConfig {
+            preserve_recent: 4,
+            use_llm_summary: true,
+            ..Default::default()
+        });
+
+        let restored = compressor.compress_with_llm(&long_conversation(), &client).await.to_conversation();
+        assert_eq!(restored.messages()[0].content, "System prompt");
+        assert_eq!(
+            restored.messages()[1].content,
+            "[Previous conversation summary (8 messages)]\nThe user is refactoring the parser; answers 0-3 covered the tokenizer."
+        );
+        assert_eq!(restored.messages().len(), 6);
+
+        // 古いメッセージだけを低い温度と小さい予算で送る
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].contains("Question 3 about the parser"));
+        assert!(!requests[0].contains("Question 4 about the parser"));
+        assert!(requests[0].contains("\"temperature\":0.2"));
+        assert!(requests[0].contains("\"num_predict\""));
+    }
+
+    #[tokio::test]
+    async fn test_llm_summary_falls_back_to_heuristic_on_error() {
+        use crate::llm::mock_server::{http_response, spawn_mock_handler};
+
+        let url = spawn_mock_handler(|_| http_response("400 Bad Request", &[], r#"{"error": "bad request"}"#)).await;
+        let client = OllamaClient::new(&url, "mock");
+        let compressor = ContextCompressor::with_config(CompressionConfig {
+            preserve_recent: 4,
+            use_llm_summary: true,
+            ..Default::default()
+        });
+
+        let compressed = compressor.compress_with_llm(&long_conversation(), &client).await;
+        let summary = compressed.compressed_history.unwrap().summary;
+        assert!(summary.contains("User discussed: Question 0 about the parser"), "{}", summary);
+    }
 }
@@ -0,0 +1,231 @@
+//! ツール実行前の確認
+//!
+//! 副作用のある呼び出しは実行前に `ConfirmationPolicy` に尋ねる。
+//! 「このツールは常に許可」「この呼び出しは常に許可」の選択はセッションの許可リストに記録し、
+//! 同じ確認を繰り返さない（/clear・/load・/cd で忘れる）
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use super::mode::Mode;
+use super::session::{ResetAction, ResetTarget, SessionResettable};
+use crate::cli::ConfirmResult;
+use crate::tools::ToolEffects;
+
+/// 実行してよいかの尋ね方（対話ならダイアログ、非対話なら固定の答え）
+pub trait ConfirmationPolicy: Send + Sync {
+    /// `tool` の実行を許可するか（`details` は確認に出す内容）
+    fn decide(&self, tool: &str, details: &str) -> ConfirmResult;
+
+    /// スキルが frontmatter の `extra_paths` で求めるパスへのアクセスを許可するか
+    ///
+    /// `always_choices` が false（書き込みの要求）なら「常に許可」の選択肢は出さない
+    fn decide_grant(&self, skill: &str, details: &str, _always_choices: bool) -> ConfirmResult {
+        self.decide(skill, details)
+    }
+}
+
+/// 尋ねずに許可する（非対話モードの --yes）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AutoApprove;
+
+impl ConfirmationPolicy for AutoApprove {
+    fn decide(&self, _tool: &str, _details: &str) -> ConfirmResult {
+        ConfirmResult::Approved
+    }
+}
+
+/// 尋ねずに拒否する（非対話モードで --yes がないとき）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AutoDeny;
+
+impl ConfirmationPolicy for AutoDeny {
+    fn decide(&self, _tool: &str, _details: &str) -> ConfirmResult {
+        ConfirmResult::Denied
+    }
+}
+
+/// セッション中は確認しないツールと呼び出し
+#[derive(Debug, Default)]
+pub struct SessionAllowSet {
+    /// ツール名
+    tools: HashSet<String>,
+    /// ツール名と確認内容の組（同じ内容の呼び出しだけ）
+    exact: HashSet<(String, String)>,
+}
+
+impl SessionAllowSet {
+    /// 確認せずに許可するか
+    pub fn allows(&self, tool: &str, details: &str) -> bool {
+        self.tools.contains(tool) || self.exact.contains(&(tool.to_string(), details.to_string()))
+    }
+
+    /// 「常に許可」の選択を記録する（それ以外の結果は何もしない）
+    pub fn record(&mut self, tool: &str, details: &str, result: ConfirmResult) {
+        match result {
+            ConfirmResult::AlwaysAllowTool => {
+                self.tools.insert(tool.to_string());
+            }
+            ConfirmResult::AlwaysAllowExact => {
+                self.exact.insert((tool.to_string(), details.to_string()));
+            }
+            ConfirmResult::Approved | ConfirmResult::Denied => {}
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.tools.clear();
+        self.exact.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty() && self.exact.is_empty()
+    }
+}
+
+/// 確認ポリシーとセッションの許可リスト
+///
+/// ツール自身の確認（上書きの差分、push するコミットなど）もここを通し、許可リストを共有する
+pub struct ToolConfirmation {
+    policy: Arc<dyn ConfirmationPolicy>,
+    allowed: Mutex<SessionAllowSet>,
+}
+
+impl ToolConfirmation {
+    pub fn new(policy: Arc<dyn ConfirmationPolicy>) -> Self {
+        Self {
+            policy,
+            allowed: Mutex::new(SessionAllowSet::default()),
+        }
+    }
+
+    /// 実行してよいか（確認が要らない、セッション中の許可済み、またはポリシーが許可）
+    ///
+    /// ダイアログはブロックするので、非同期コンテキストからは `spawn_blocking` で呼ぶ
+    pub fn confirm(&self, tool: &str, effects: ToolEffects, mode: &Mode, details: &str) -> bool {
+        if !mode.requires_confirmation(effects) {
+            return true;
+        }
+        if self.allowed.lock().is_ok_and(|allowed| allowed.allows(tool, details)) {
+            return true;
+        }
+        let result = self.policy.decide(tool, details);
+        if let Ok(mut allowed) = self.allowed.lock() {
+            allowed.record(tool, details, result);
+        }
+        result.is_approved()
+    }
+
+    /// スキルの `extra_paths` を許可するか
+    ///
+    /// 読み取りの「常に許可」は許可リストに `skill:<name>` として記録し、次からは尋ねない。
+    /// 書き込みは毎回尋ね、許可リストも使わない
+    pub fn confirm_grant(&self, skill: &str, details: &str, write: bool) -> bool {
+        let subject = format!("skill:{}", skill);
+        if !write && self.allowed.lock().is_ok_and(|allowed| allowed.allows(&subject, details)) {
+            return true;
+        }
+        let result = self.policy.decide_grant(skill, details, !write);
+        if !write {
+            if let Ok(mut allowed) = self.allowed.lock() {
+                allowed.record(&subject, details, result);
+            }
+        }
+        result.is_approved()
+    }
+}
+
+impl SessionResettable for ToolConfirmation {
+    fn reset_target(&self) -> ResetTarget {
+        ResetTarget::ToolApprovals
+    }
+
+    fn reset(&self, _action: ResetAction) {
+        if let Ok(mut allowed) = self.allowed.lock() {
+            allowed.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 決まった答えを返し、尋ねられた回数を数える
+    struct Scripted {
+        answer: ConfirmResult,
+        asked: Mutex<usize>,
+    }
+
+    impl ConfirmationPolicy for Scripted {
+        fn decide(&self, _tool: &str, _details: &str) -> ConfirmResult {
+            *self.asked.lock().unwrap() += 1;
+            self.answer
+        }
+    }
+
+    fn scripted(answer: ConfirmResult) -> (ToolConfirmation, Arc<Scripted>) {
+        let policy = Arc::new(Scripted { answer, asked: Mutex::new(0) });
+        (ToolConfirmation::new(policy.clone()), policy)
+    }
+
+    #[test]
+    fn test_decision_matrix() {
+        use ConfirmResult::*;
+        use ToolEffects::*;
+        // (副作用, モード, ポリシーの答え) → 実行するか、尋ねたか
+        let cases = [
+            (ReadOnly, Mode::Execute, Denied, true, false),
+            (Writes, Mode::Execute, Denied, false, true),
+            (Writes, Mode::Execute, Approved, true, true),
+            (Writes, Mode::AcceptEdits, Denied, true, false),
+            (Executes, Mode::AcceptEdits, Denied, false, true),
+            (Executes, Mode::Execute, AlwaysAllowTool, true, true),
+            (Executes, Mode::Execute, AlwaysAllowExact, true, true),
+        ];
+        for (effects, mode, answer, approved, asked) in cases {
+            let (confirmation, policy) = scripted(answer);
+            assert_eq!(confirmation.confirm("tool", effects, &mode, "details"), approved, "{:?} {:?} {:?}", effects, mode, answer);
+            assert_eq!(*policy.asked.lock().unwrap() == 1, asked, "{:?} {:?} {:?}", effects, mode, answer);
+        }
+        assert!(ToolConfirmation::new(Arc::new(AutoApprove)).confirm("bash", Executes, &Mode::Execute, "rm x"));
+    }
+
+    #[test]
+    fn test_always_allow_tool_covers_every_call() {
+        let (confirmation, policy) = scripted(ConfirmResult::AlwaysAllowTool);
+        assert!(confirmation.confirm("edit", ToolEffects::Writes, &Mode::Execute, "a.rs"));
+        assert!(confirmation.confirm("edit", ToolEffects::Writes, &Mode::Execute, "b.rs"));
+        assert_eq!(*policy.asked.lock().unwrap(), 1);
+        // ほかのツールは尋ねる
+        confirmation.confirm("bash", ToolEffects::Executes, &Mode::Execute, "ls");
+        assert_eq!(*policy.asked.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_always_allow_exact_covers_identical_calls_only() {
+        let (confirmation, policy) = scripted(ConfirmResult::AlwaysAllowExact);
+        confirmation.confirm("bash", ToolEffects::Executes, &Mode::Execute, "cargo test");
+        confirmation.confirm("bash", ToolEffects::Executes, &Mode::Execute, "cargo test");
+        assert_eq!(*policy.asked.lock().unwrap(), 1);
+        confirmation.confirm("bash", ToolEffects::Executes, &Mode::Execute, "cargo build");
+        assert_eq!(*policy.asked.lock().unwrap(), 2);
+
+        // セッションが変われば忘れる
+        confirmation.reset(ResetAction::Full);
+        confirmation.confirm("bash", ToolEffects::Executes, &Mode::Execute, "cargo test");
+        assert_eq!(*policy.asked.lock().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_allow_set_ignores_one_off_answers() {
+        let mut allowed = SessionAllowSet::default();
+        allowed.record("bash", "ls", ConfirmResult::Approved);
+        allowed.record("bash", "ls", ConfirmResult::Denied);
+        assert!(allowed.is_empty());
+        allowed.record("bash", "ls", ConfirmResult::AlwaysAllowExact);
+        assert!(allowed.allows("bash", "ls"));
+        assert!(!allowed.allows("bash", "ls -la"));
+        assert!(!allowed.allows("write", "ls"));
+    }
+}
@@ -1,60 +1,628 @@
 use anyhow::Result;
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
-use tokio::fs;
+
+use super::memory::Memory;
+use crate::tools::git::find_nested_repos;
+use crate::tools::{Workspace, WorkspaceRoot};
+
+/// ワークスペースの説明に使う文字数の上限（全ルートで分け合う）
+pub const WORKSPACE_SUMMARY_BUDGET: usize = 2000;
+
+/// プロジェクトルートで探すコンテキストファイル（最初に見つかったもの）
+pub const PROJECT_CONTEXT_FILES: &[&str] = &["agent.md", "AGENT.md", "CLAUDE.md", "claude.md", "AGENTS.md"];
+
+/// グローバル・サブディレクトリで探すコンテキストファイル
+pub const DIRECTORY_CONTEXT_FILES: &[&str] = &["agent.md", "AGENT.md"];
+
+/// 読み込むコンテキストの合計バイト数の上限（超えた分は広い範囲のものから削る）
+pub const MAX_CONTEXT_BYTES: usize = 32 * 1024;
+
+/// グローバルなコンテキストファイルを置くディレクトリ（~/.local-code）
+pub fn global_context_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".local-code"))
+}
+
+/// コンテキストファイルの適用範囲
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextScope {
+    /// ~/.local-code/agent.md
+    Global,
+    /// プロジェクトルート（複数ルートなら各ルート）の agent.md / CLAUDE.md / AGENTS.md
+    Project,
+    /// ツールが触れたサブディレクトリの agent.md
+    Directory,
+}
+
+impl ContextScope {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ContextScope::Global => "global",
+            ContextScope::Project => "project",
+            ContextScope::Directory => "directory",
+        }
+    }
+}
+
+/// 読み込んだコンテキストファイル1つ
+#[derive(Debug, Clone)]
+pub struct ContextSource {
+    pub scope: ContextScope,
+    pub path: PathBuf,
+    /// `@path` の取り込みを展開した内容
+    pub content: String,
+    /// 取り込んだファイル
+    pub imports: Vec<PathBuf>,
+    /// 上限のため末尾を削った
+    pub truncated: bool,
+}
 
 /// プロジェクトコンテキスト（agent.md, CLAUDE.md等）
 #[derive(Default)]
 pub struct AgentContext {
-    /// 読み込まれたコンテキストファイルの内容
+    /// 読み込まれたコンテキストを見出し付きでつなげた内容
     pub content: Option<String>,
-    /// 読み込み元ファイルパス
+    /// プロジェクトのコンテキストファイルのパス
     pub source_path: Option<PathBuf>,
+    /// プロジェクト内のネストしたリポジトリ（プロジェクトルートからの相対パス）
+    pub nested_repos: Vec<PathBuf>,
+    /// 読み込んだファイル（優先度の低い順: グローバル、プロジェクト、サブディレクトリ）
+    pub sources: Vec<ContextSource>,
+    /// 読み込み中の警告（見つからない取り込み、上限による切り詰めなど）
+    pub warnings: Vec<String>,
+    /// /remember で覚えた事柄
+    pub memory: Memory,
+    project_root: Option<PathBuf>,
+    /// 複数ルートのワークスペースの、プロジェクトルート以外のルート
+    other_roots: Vec<WorkspaceRoot>,
 }
 
 impl AgentContext {
-    /// プロジェクトルートからコンテキストファイルを探索・読み込み
-    pub async fn load_from_project(project_root: &Path) -> Result<Self> {
-        let candidates = [
-            "agent.md",
-            "AGENT.md",
-            "CLAUDE.md",
-            "claude.md",
-        ];
-
-        for filename in candidates {
-            let path = project_root.join(filename);
-            if path.exists() {
-                let content = fs::read_to_string(&path).await?;
-                tracing::info!("Loaded context from: {}", path.display());
-                return Ok(Self {
-                    content: Some(content),
-                    source_path: Some(path),
-                });
+    /// プロジェクトルート（とワークスペースのほかのルート）とグローバル設定からコンテキストファイルを探索・読み込み
+    pub async fn load_from_project(project_root: &Path, roots: &[WorkspaceRoot]) -> Result<Self> {
+        Self::load_with_roots(project_root, roots, global_context_dir().as_deref()).await
+    }
+
+    /// `global_dir` の agent.md とプロジェクトルートのコンテキストファイルを読み込む
+    pub async fn load(project_root: &Path, global_dir: Option<&Path>) -> Result<Self> {
+        Self::load_with_roots(project_root, &[], global_dir).await
+    }
+
+    /// `load` に加えて、複数ルートのワークスペースのほかのルートのコンテキストファイルも読み込む
+    ///
+    /// ルートの順に並べ、サブディレクトリの agent.md は触れたファイルを含むルートの中で探す
+    pub async fn load_with_roots(project_root: &Path, roots: &[WorkspaceRoot], global_dir: Option<&Path>) -> Result<Self> {
+        let nested_repos: Vec<PathBuf> = find_nested_repos(project_root)
+            .into_iter()
+            .filter_map(|p| p.strip_prefix(project_root).ok().map(Path::to_path_buf))
+            .collect();
+        for repo in &nested_repos {
+            tracing::info!("Nested repository detected: {}", repo.display());
+        }
+
+        let mut context = Self {
+            nested_repos,
+            memory: Memory::load(Some(project_root), global_dir),
+            project_root: Some(project_root.to_path_buf()),
+            other_roots: roots.iter().filter(|root| !same_file(&root.path, project_root)).cloned().collect(),
+            ..Self::default()
+        };
+        let mut found = Vec::new();
+        if let Some(path) = global_dir.and_then(|dir| find_file(dir, DIRECTORY_CONTEXT_FILES)) {
+            found.push((ContextScope::Global, path));
+        }
+        if let Some(path) = find_file(project_root, PROJECT_CONTEXT_FILES) {
+            context.source_path = Some(path.clone());
+            found.push((ContextScope::Project, path));
+        }
+        for root in &context.other_roots {
+            if let Some(path) = find_file(&root.path, PROJECT_CONTEXT_FILES) {
+                found.push((ContextScope::Project, path));
             }
         }
+        for (scope, path) in found {
+            let source = context.read_source(scope, &path).await?;
+            tracing::info!("Loaded {} context from: {}", scope.label(), path.display());
+            context.sources.push(source);
+        }
+        if context.sources.is_empty() {
+            tracing::info!("No agent context file found in {}", project_root.display());
+        }
 
-        tracing::info!("No agent context file found in {}", project_root.display());
-        Ok(Self {
-            content: None,
-            source_path: None,
-        })
+        let (sources, warnings) = (&mut context.sources, &mut context.warnings);
+        cap_sources(sources, MAX_CONTEXT_BYTES, warnings);
+        context.content = (!context.sources.is_empty()).then(|| {
+            context.sources.iter().map(|s| context.section(s)).collect::<Vec<_>>().join("\n")
+        });
+        Ok(context)
+    }
+
+    /// `path` に触れたときに加わるサブディレクトリのコンテキスト（まだ読み込んでいないものだけ）
+    ///
+    /// プロジェクトルートと `path` の間にある agent.md を外側から順に読み込む。
+    /// 残りの上限を超える分は削る
+    pub async fn context_for(&mut self, path: &Path) -> Option<String> {
+        let project_root = self.project_root.clone()?;
+        let path = if path.is_absolute() { path.to_path_buf() } else { project_root.join(path) };
+        let dir = if path.is_dir() { path.as_path() } else { path.parent()? };
+        let root = self.root_of(dir)?;
+        if dir == root {
+            return None;
+        }
+
+        let mut found: Vec<PathBuf> = dir
+            .ancestors()
+            .take_while(|d| *d != root)
+            .filter_map(|d| find_file(d, DIRECTORY_CONTEXT_FILES))
+            .filter(|p| !self.is_loaded(p))
+            .collect();
+        found.reverse();
+        let mut added = Vec::new();
+        for file in found {
+            match self.read_source(ContextScope::Directory, &file).await {
+                Ok(source) => added.push(source),
+                Err(e) => tracing::warn!("Failed to read {}: {}", file.display(), e),
+            }
+        }
+        if added.is_empty() {
+            return None;
+        }
+
+        let used: usize = self.sources.iter().map(|s| s.content.len()).sum();
+        let mut warnings = Vec::new();
+        cap_sources(&mut added, MAX_CONTEXT_BYTES.saturating_sub(used), &mut warnings);
+        self.warnings.extend(warnings);
+        let text = added.iter().map(|s| self.section(s)).collect::<Vec<_>>().join("\n");
+        for source in &added {
+            tracing::info!("Loaded directory context from: {}", source.path.display());
+        }
+        self.sources.extend(added);
+        Some(text)
+    }
+
+    /// メモリファイルだけを読み直す
+    pub fn refresh_memory(&mut self) {
+        self.memory.refresh();
+    }
+
+    /// サブディレクトリのコンテキストを忘れる（会話を消したとき、次に触れたら読み直す）
+    pub fn forget_directories(&mut self) {
+        self.sources.retain(|s| s.scope != ContextScope::Directory);
+    }
+
+    /// 読み込んだファイルと取り込み元の一覧（/context）
+    pub fn describe(&self) -> String {
+        if self.sources.is_empty() {
+            return "Context files: none (looked for agent.md, CLAUDE.md, AGENTS.md)".to_string();
+        }
+        let mut lines = vec!["Context files:".to_string()];
+        for source in &self.sources {
+            let mut line = format!(
+                "  {:<9} {} ({} bytes{})",
+                source.scope.label(),
+                self.display_path(&source.path),
+                source.content.len(),
+                if source.truncated { ", truncated" } else { "" }
+            );
+            if !source.imports.is_empty() {
+                let imports: Vec<String> = source.imports.iter().map(|p| self.display_path(p)).collect();
+                line.push_str(&format!(", imports {}", imports.join(", ")));
+            }
+            lines.push(line);
+        }
+        for warning in &self.warnings {
+            lines.push(format!("  warning: {}", warning));
+        }
+        lines.join("\n")
     }
 
     /// システムプロンプト用にフォーマット
     pub fn as_system_prompt(&self) -> Option<String> {
-        self.content.as_ref().map(|c| {
-            format!(
+        let mut sections = Vec::new();
+
+        if let Some(c) = &self.content {
+            sections.push(format!(
                 "# Project Context\n\
-                 The following instructions are from the project's agent configuration file:\n\n\
+                 The following instructions are from the agent configuration files, \
+                 from the most general to the most specific (later sections take precedence):\n\n\
                  {}\n",
                 c
-            )
-        })
+            ));
+        }
+
+        if !self.nested_repos.is_empty() {
+            let lines: Vec<String> = self
+                .nested_repos
+                .iter()
+                .map(|p| format!("- contains nested repo at {} — its files are treated as opaque", p.display()))
+                .collect();
+            sections.push(format!("# Nested Repositories\n{}\n", lines.join("\n")));
+        }
+
+        if let Some(memory) = self.memory.as_system_prompt() {
+            sections.push(memory);
+        }
+
+        if sections.is_empty() {
+            None
+        } else {
+            Some(sections.join("\n"))
+        }
     }
 
     /// コンテキストが存在するかチェック
     pub fn has_context(&self) -> bool {
         self.content.is_some()
     }
+
+    fn is_loaded(&self, path: &Path) -> bool {
+        self.sources.iter().any(|s| same_file(&s.path, path) || s.imports.iter().any(|i| same_file(i, path)))
+    }
+
+    /// ファイルを読み、`@path` の行を取り込んだ内容に置き換える（1段のみ）
+    ///
+    /// 取り込んだファイルの中の `@path` は展開しない。読み込み済みのファイルや自分自身は取り込まない
+    async fn read_source(&mut self, scope: ContextScope, path: &Path) -> Result<ContextSource> {
+        let text = tokio::fs::read_to_string(path).await?;
+        let base = path.parent().unwrap_or(Path::new("."));
+        let mut seen: HashSet<PathBuf> = HashSet::from([canonical(path)]);
+        let mut imports = Vec::new();
+        let mut content = String::new();
+        for line in text.lines() {
+            let Some(target) = import_target(line) else {
+                content.push_str(line);
+                content.push('\n');
+                continue;
+            };
+            let import = base.join(target);
+            if !seen.insert(canonical(&import)) || self.is_loaded(&import) {
+                self.warnings.push(format!("skipped repeated import of {} in {}", target, self.display_path(path)));
+                continue;
+            }
+            match tokio::fs::read_to_string(&import).await {
+                Ok(imported) => {
+                    content.push_str(imported.trim_end());
+                    content.push('\n');
+                    imports.push(import);
+                }
+                Err(e) => {
+                    self.warnings.push(format!("could not import {} in {}: {}", target, self.display_path(path), e));
+                    content.push_str(line);
+                    content.push('\n');
+                }
+            }
+        }
+        Ok(ContextSource {
+            scope,
+            path: path.to_path_buf(),
+            content: content.trim_end().to_string(),
+            imports,
+            truncated: false,
+        })
+    }
+
+    /// 見出し付きの1ファイル分
+    fn section(&self, source: &ContextSource) -> String {
+        let title = match source.scope {
+            ContextScope::Global => "Global instructions",
+            ContextScope::Project => "Project instructions",
+            ContextScope::Directory => "Directory instructions",
+        };
+        format!("## {} ({})\n\n{}\n", title, self.display_path(&source.path), source.content)
+    }
+
+    /// `dir` を含むルート（入れ子なら深い方）
+    fn root_of(&self, dir: &Path) -> Option<PathBuf> {
+        self.project_root
+            .iter()
+            .chain(self.other_roots.iter().map(|root| &root.path))
+            .filter(|root| dir.starts_with(root))
+            .max_by_key(|root| root.components().count())
+            .cloned()
+    }
+
+    /// プロジェクト内ならルートからの相対パス（ほかのルートなら `ラベル:相対パス`）
+    fn display_path(&self, path: &Path) -> String {
+        if let Some(relative) = self.project_root.as_deref().and_then(|root| path.strip_prefix(root).ok()) {
+            return relative.display().to_string();
+        }
+        self.other_roots
+            .iter()
+            .find_map(|root| path.strip_prefix(&root.path).ok().map(|rel| format!("{}:{}", root.label, rel.display())))
+            .unwrap_or_else(|| path.display().to_string())
+    }
+}
+
+/// `dir` にある最初の候補ファイル
+fn find_file(dir: &Path, candidates: &[&str]) -> Option<PathBuf> {
+    candidates.iter().map(|name| dir.join(name)).find(|path| path.is_file())
+}
+
+/// `@relative/path.md` だけの行なら取り込むパス
+fn import_target(line: &str) -> Option<&str> {
+    let target = line.trim().strip_prefix('@')?;
+    let looks_like_path = !target.is_empty()
+        && !target.contains(char::is_whitespace)
+        && !Path::new(target).is_absolute()
+        && target.contains('.');
+    looks_like_path.then_some(target)
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    a == b || canonical(a) == canonical(b)
+}
+
+/// 合計が `max` バイトを超えたら、前（より広い範囲）のものから末尾を削る
+fn cap_sources(sources: &mut [ContextSource], max: usize, warnings: &mut Vec<String>) {
+    let total: usize = sources.iter().map(|s| s.content.len()).sum();
+    let mut excess = total.saturating_sub(max);
+    if excess == 0 {
+        return;
+    }
+    tracing::warn!("Agent context is {} bytes; truncating to {} bytes", total, max);
+    warnings.push(format!("context files total {} bytes; truncated to the {} byte limit", total, max));
+    for source in sources.iter_mut() {
+        if excess == 0 {
+            break;
+        }
+        let mut keep = source.content.len().saturating_sub(excess);
+        while !source.content.is_char_boundary(keep) {
+            keep -= 1;
+        }
+        excess -= source.content.len() - keep;
+        source.content.truncate(keep);
+        source.truncated = true;
+    }
+}
+
+/// 複数ルートのワークスペースの説明（ルートごとにラベル・エコシステム・トップレベルの構成）
+pub fn describe_workspace(workspace: &Workspace, budget: usize) -> String {
+    let per_root = budget / workspace.roots().len().max(1);
+    let mut out = String::from(
+        "# Workspace\nThis session spans several project roots. Refer to files as `label:path` \
+         (e.g. `backend:src/main.rs`); paths without a label resolve against the current root. \
+         glob and grep search the current root unless given `root` (a label or \"all\"). \
+         bash runs in the first root; each root has its own language server.\n",
+    );
+    for root in workspace.roots() {
+        out.push_str(&describe_root(root, per_root));
+        out.push('\n');
+    }
+    out
+}
+
+/// 1ルート分の説明（上限を超えるエントリは件数だけ示す）
+fn describe_root(root: &WorkspaceRoot, budget: usize) -> String {
+    let mut line = format!("- {}: {}", root.label, root.path.display());
+    if let Some(ecosystem) = ecosystem(&root.path) {
+        line.push_str(&format!(" ({})", ecosystem));
+    }
+    let entries = top_level_entries(&root.path);
+    let mut shown = 0;
+    for entry in &entries {
+        let sep = if shown == 0 { " — " } else { ", " };
+        if line.len() + sep.len() + entry.len() > budget {
+            break;
+        }
+        line.push_str(sep);
+        line.push_str(entry);
+        shown += 1;
+    }
+    if shown < entries.len() {
+        line.push_str(&format!(" … {} more", entries.len() - shown));
+    }
+    line
 }
 
+/// マニフェストからエコシステムを推定
+pub(crate) fn ecosystem(root: &Path) -> Option<&'static str> {
+    let markers: [(&str, &str); 6] = [
+        ("Cargo.toml", "Rust"),
+        ("package.json", "Node.js"),
+        ("go.mod", "Go"),
+        ("pyproject.toml", "Python"),
+        ("requirements.txt", "Python"),
+        ("setup.py", "Python"),
+    ];
+    markers
+        .iter()
+        .find(|(file, _)| root.join(file).exists())
+        .map(|(_, name)| *name)
+}
+
+/// トップレベルのエントリ（ディレクトリを先に、隠しファイルは除く）
+fn top_level_entries(root: &Path) -> Vec<String> {
+    let Ok(read_dir) = std::fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut entries: Vec<(bool, String)> = read_dir
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                return None;
+            }
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            Some((!is_dir, if is_dir { format!("{}/", name) } else { name }))
+        })
+        .collect();
+    entries.sort();
+    entries.into_iter().map(|(_, name)| name).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace() -> (tempfile::TempDir, Workspace) {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = dir.path().join("backend");
+        let web = dir.path().join("web");
+        std::fs::create_dir_all(backend.join("src")).unwrap();
+        std::fs::create_dir_all(web.join("components")).unwrap();
+        std::fs::write(backend.join("Cargo.toml"), "[package]\n").unwrap();
+        std::fs::write(web.join("package.json"), "{}").unwrap();
+        for i in 0..40 {
+            std::fs::write(web.join(format!("page{:02}.tsx", i)), "").unwrap();
+        }
+        let ws = Workspace::from_paths(&[backend, web]).unwrap();
+        (dir, ws)
+    }
+
+    #[test]
+    fn test_describe_workspace_lists_each_root() {
+        let (_dir, ws) = workspace();
+        let text = describe_workspace(&ws, WORKSPACE_SUMMARY_BUDGET);
+        assert!(text.contains("- backend: "), "{}", text);
+        assert!(text.contains("(Rust) — src/, Cargo.toml"), "{}", text);
+        assert!(text.contains("(Node.js) — components/, package.json"), "{}", text);
+    }
+
+    #[test]
+    fn test_describe_workspace_stays_within_budget() {
+        let (_dir, ws) = workspace();
+        let text = describe_workspace(&ws, 400);
+        let web = text.lines().find(|l| l.starts_with("- web:")).unwrap();
+        assert!(web.len() <= 200 + " … 40 more".len(), "{}", web);
+        assert!(web.contains(" more"), "{}", web);
+    }
+
+    /// グローバル設定のディレクトリとプロジェクトルート
+    fn dirs_with(files: &[(&str, &str)]) -> (tempfile::TempDir, PathBuf, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let global = dir.path().join("global");
+        let project = dir.path().join("project");
+        std::fs::create_dir_all(&global).unwrap();
+        std::fs::create_dir_all(&project).unwrap();
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        (dir, global, project)
+    }
+
+    #[tokio::test]
+    async fn test_global_then_project_with_headers() {
+        let (_dir, global, project) = dirs_with(&[
+            ("global/agent.md", "Answer in Japanese."),
+            ("project/CLAUDE.md", "Use cargo nextest."),
+            ("project/AGENTS.md", "ignored: CLAUDE.md comes first"),
+        ]);
+        let context = AgentContext::load(&project, Some(&global)).await.unwrap();
+        let content = context.content.as_deref().unwrap();
+        let global_at = content.find("## Global instructions (").unwrap();
+        let project_at = content.find("## Project instructions (CLAUDE.md)\n\nUse cargo nextest.").unwrap();
+        assert!(global_at < project_at, "{}", content);
+        assert!(!content.contains("ignored"), "{}", content);
+        assert_eq!(context.source_path, Some(project.join("CLAUDE.md")));
+        assert!(context.describe().contains("project   CLAUDE.md (18 bytes)"), "{}", context.describe());
+
+        // AGENTS.md だけでも読む
+        std::fs::remove_file(project.join("CLAUDE.md")).unwrap();
+        let context = AgentContext::load(&project, None).await.unwrap();
+        assert_eq!(context.sources.len(), 1);
+        assert!(context.content.unwrap().contains("## Project instructions (AGENTS.md)"));
+    }
+
+    #[tokio::test]
+    async fn test_imports_expand_one_level_and_skip_cycles() {
+        let (_dir, global, project) = dirs_with(&[
+            ("global/agent.md", "global rules"),
+            ("project/agent.md", "intro\n@docs/style.md\n@agent.md\n@../global/agent.md\n@missing.md\nemail @someone"),
+            ("project/docs/style.md", "Prefer iterators.\n@nested.md"),
+            ("project/docs/nested.md", "never inlined"),
+        ]);
+        let context = AgentContext::load(&project, Some(&global)).await.unwrap();
+        let source = &context.sources[1];
+        assert_eq!(
+            source.content,
+            "intro\nPrefer iterators.\n@nested.md\n@missing.md\nemail @someone"
+        );
+        assert_eq!(source.imports, vec![project.join("docs/style.md")]);
+        // 自分自身と読み込み済みのグローバルは取り込まない
+        assert_eq!(context.warnings.iter().filter(|w| w.contains("skipped repeated import")).count(), 2, "{:?}", context.warnings);
+        assert!(context.warnings.iter().any(|w| w.contains("could not import missing.md")), "{:?}", context.warnings);
+        assert!(context.describe().contains("imports docs/style.md"), "{}", context.describe());
+    }
+
+    #[tokio::test]
+    async fn test_size_cap_trims_general_context_first() {
+        let big = "g".repeat(MAX_CONTEXT_BYTES);
+        let (_dir, global, project) = dirs_with(&[("global/agent.md", &big), ("project/agent.md", "project rules")]);
+        let context = AgentContext::load(&project, Some(&global)).await.unwrap();
+        let total: usize = context.sources.iter().map(|s| s.content.len()).sum();
+        assert_eq!(total, MAX_CONTEXT_BYTES);
+        assert!(context.sources[0].truncated);
+        assert_eq!(context.sources[1].content, "project rules");
+        assert!(context.warnings[0].contains("truncated"), "{:?}", context.warnings);
+        assert!(context.describe().contains("truncated"));
+    }
+
+    #[tokio::test]
+    async fn test_memory_is_part_of_the_prompt() {
+        use crate::agent::memory::MemoryScope;
+        let (_dir, global, project) = dirs_with(&[("project/agent.md", "rules")]);
+        let mut context = AgentContext::load(&project, Some(&global)).await.unwrap();
+        assert!(!context.as_system_prompt().unwrap().contains("# Memory"));
+
+        // 別のハンドルで書いた内容も refresh_memory で入る
+        let mut other = Memory::load(Some(&project), Some(&global));
+        other.remember("we use thiserror", MemoryScope::Project).unwrap();
+        context.refresh_memory();
+        let prompt = context.as_system_prompt().unwrap();
+        let memory_at = prompt.find("# Memory\n").unwrap();
+        assert!(prompt.find("# Project Context").unwrap() < memory_at, "{}", prompt);
+        assert!(prompt[memory_at..].contains("- we use thiserror\n"), "{}", prompt);
+    }
+
+    #[tokio::test]
+    async fn test_context_for_adds_subdirectory_guidance_once() {
+        let (_dir, _global, project) = dirs_with(&[
+            ("project/agent.md", "root"),
+            ("project/crates/agent.md", "crates rules"),
+            ("project/crates/core/agent.md", "core rules"),
+            ("project/crates/core/src/lib.rs", ""),
+        ]);
+        let mut context = AgentContext::load(&project, None).await.unwrap();
+        assert_eq!(context.context_for(Path::new("agent.md")).await, None);
+
+        let text = context.context_for(Path::new("crates/core/src/lib.rs")).await.unwrap();
+        let outer = text.find("## Directory instructions (crates/agent.md)").unwrap();
+        let inner = text.find("## Directory instructions (crates/core/agent.md)").unwrap();
+        assert!(outer < inner, "{}", text);
+        assert!(!text.contains("root"), "{}", text);
+        assert_eq!(context.context_for(&project.join("crates/core")).await, None);
+        assert!(context.describe().contains("directory crates/core/agent.md"), "{}", context.describe());
+
+        context.forget_directories();
+        assert!(context.context_for(Path::new("crates/core/src/lib.rs")).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_every_workspace_root_contributes_its_agent_md() {
+        let (dir, global, project) = dirs_with(&[
+            ("project/agent.md", "backend rules"),
+            ("web/AGENTS.md", "web rules"),
+            ("web/components/agent.md", "component rules"),
+            ("web/components/button.tsx", ""),
+        ]);
+        let web = dir.path().join("web");
+        let ws = Workspace::from_paths(&[project.clone(), web.clone()]).unwrap();
+        let mut context = AgentContext::load_with_roots(&project, ws.roots(), Some(&global)).await.unwrap();
+
+        let prompt = context.as_system_prompt().unwrap();
+        let backend = prompt.find("## Project instructions (agent.md)\n\nbackend rules").unwrap();
+        let frontend = prompt.find("## Project instructions (web:AGENTS.md)\n\nweb rules").unwrap();
+        assert!(backend < frontend, "{}", prompt);
+
+        // サブディレクトリの agent.md はそのファイルを含むルートの中で探す
+        let text = context.context_for(&web.join("components/button.tsx")).await.unwrap();
+        assert!(text.contains("## Directory instructions (web:components/agent.md)\n\ncomponent rules"), "{}", text);
+        assert_eq!(context.context_for(&web.join("AGENTS.md")).await, None);
+    }
+}
@@ -11,6 +11,30 @@ pub enum Role {
     Tool,
 }
 
+/// ユーザーメッセージの出どころ
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageOrigin {
+    /// ユーザーが入力した
+    #[default]
+    Typed,
+    /// スキルが組み立てたプロンプト（スキルのコマンドや自動実行）
+    Skill,
+    /// 検証に失敗したコードの修正依頼
+    Verification,
+}
+
+impl MessageOrigin {
+    /// /undo・/retry で取り除くやり取りの始まりになるか（検証の修正依頼は前のやり取りに含める）
+    pub fn starts_exchange(self) -> bool {
+        !matches!(self, MessageOrigin::Verification)
+    }
+
+    pub(crate) fn is_typed(&self) -> bool {
+        *self == MessageOrigin::Typed
+    }
+}
+
 /// 会話メッセージ
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
@@ -20,6 +44,18 @@ pub struct Message {
     pub tool_name: Option<String>,
     #[serde(skip)]
     pub timestamp: Option<SystemTime>,
+    /// 生成途中で中断されたか（Ctrl+C）
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub interrupted: bool,
+    /// このターンだけ添付する固定セクション（/use、切り詰めの対象外）
+    #[serde(skip)]
+    pub pinned: Option<String>,
+    /// ユーザーメッセージの出どころ
+    #[serde(default, skip_serializing_if = "MessageOrigin::is_typed")]
+    pub origin: MessageOrigin,
+    /// 入力した行そのまま（スキルの通知や固定結果の注記を付ける前。/retry で送り直す）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<String>,
 }
 
 impl Message {
@@ -29,6 +65,10 @@ impl Message {
             content: content.into(),
             tool_name: None,
             timestamp: Some(SystemTime::now()),
+            interrupted: false,
+            pinned: None,
+            origin: MessageOrigin::Typed,
+            input: None,
         }
     }
 
@@ -38,6 +78,10 @@ impl Message {
             content: content.into(),
             tool_name: None,
             timestamp: Some(SystemTime::now()),
+            interrupted: false,
+            pinned: None,
+            origin: MessageOrigin::Typed,
+            input: None,
         }
     }
 
@@ -47,6 +91,10 @@ impl Message {
             content: content.into(),
             tool_name: None,
             timestamp: Some(SystemTime::now()),
+            interrupted: false,
+            pinned: None,
+            origin: MessageOrigin::Typed,
+            input: None,
         }
     }
 
@@ -56,6 +104,10 @@ impl Message {
             content: content.into(),
             tool_name: Some(name.into()),
             timestamp: Some(SystemTime::now()),
+            interrupted: false,
+            pinned: None,
+            origin: MessageOrigin::Typed,
+            input: None,
         }
     }
 }
@@ -96,12 +148,34 @@ impl Conversation {
         self.messages.insert(0, Message::system(content));
     }
 
+    /// 先頭のシステムプロンプトだけを置き換える（圧縮の要約など、後ろのシステムメッセージは残す）
+    pub fn replace_system_prompt(&mut self, content: impl Into<String>) {
+        match self.messages.first_mut() {
+            Some(first) if first.role == Role::System => first.content = content.into(),
+            _ => self.messages.insert(0, Message::system(content)),
+        }
+    }
+
     /// メッセージを追加
     pub fn add(&mut self, message: Message) {
         self.messages.push(message);
         self.truncate_if_needed();
     }
 
+    /// 固定セクション付きのユーザーメッセージを追加
+    pub fn add_pinned_user(&mut self, content: impl Into<String>, pinned: impl Into<String>) {
+        let mut message = Message::user(content);
+        message.pinned = Some(pinned.into());
+        self.add(message);
+    }
+
+    /// 固定セクションを外す（次のターン以降は送らない）
+    pub fn release_pins(&mut self) {
+        for message in &mut self.messages {
+            message.pinned = None;
+        }
+    }
+
     /// ユーザーメッセージを追加
     pub fn add_user(&mut self, content: impl Into<String>) {
         self.add(Message::user(content));
@@ -112,6 +186,13 @@ impl Conversation {
         self.add(Message::assistant(content));
     }
 
+    /// 中断されたアシスタントメッセージ（部分応答）を追加
+    pub fn add_interrupted_assistant(&mut self, content: impl Into<String>) {
+        let mut message = Message::assistant(content);
+        message.interrupted = true;
+        self.add(message);
+    }
+
     /// ツール結果を追加
     pub fn add_tool_result(&mut self, tool_name: impl Into<String>, content: impl Into<String>) {
         self.add(Message::tool(tool_name, content));
@@ -148,6 +229,24 @@ impl Conversation {
         }
     }
 
+    /// 最後のやり取り（最後に入力したユーザーメッセージ以降のメッセージ）を取り除く
+    ///
+    /// 検証の修正依頼とその応答は、元のやり取りに含めて取り除く。
+    /// システムメッセージは途中にあっても残す。ユーザーメッセージがなければ何もせず None
+    pub fn pop_last_exchange(&mut self) -> Option<Exchange> {
+        let is_user = |m: &Message| m.role == Role::User;
+        // 始まりのメッセージが圧縮で消えていれば、最後のユーザーメッセージから
+        let start = self
+            .messages
+            .iter()
+            .rposition(|m| is_user(m) && m.origin.starts_exchange())
+            .or_else(|| self.messages.iter().rposition(is_user))?;
+        let (system, messages): (Vec<Message>, Vec<Message>) =
+            self.messages.drain(start..).partition(|m| m.role == Role::System);
+        self.messages.extend(system);
+        Some(Exchange { messages })
+    }
+
     /// プロンプト形式に変換（OLLAMA用）
     pub fn to_prompt(&self) -> String {
         let mut prompt = String::new();
@@ -157,8 +256,12 @@ impl Conversation {
                 Role::System => {
                     prompt.push_str(&format!("System: {}\n\n", msg.content));
                 }
-                Role::User => {
-                    prompt.push_str(&format!("User: {}\n\n", msg.content));
+                Role::User => match &msg.pinned {
+                    Some(pinned) => prompt.push_str(&format!("User: {}\n\n{}\n\n", msg.content, pinned)),
+                    None => prompt.push_str(&format!("User: {}\n\n", msg.content)),
+                },
+                Role::Assistant if msg.interrupted => {
+                    prompt.push_str(&format!("Assistant: {}\n[interrupted by user]\n\n", msg.content));
                 }
                 Role::Assistant => {
                     prompt.push_str(&format!("Assistant: {}\n\n", msg.content));
@@ -174,26 +277,20 @@ impl Conversation {
         prompt
     }
 
-    /// 必要に応じて古いメッセージを削除
+    /// 必要に応じて古いメッセージを削除（システムメッセージと固定中のメッセージは保持）
     fn truncate_if_needed(&mut self) {
-        if self.messages.len() > self.max_messages {
-            // システムメッセージは保持
-            let system_msgs: Vec<_> = self.messages.iter()
-                .filter(|m| m.role == Role::System)
-                .cloned()
-                .collect();
-
-            let non_system: Vec<_> = self.messages.iter()
-                .filter(|m| m.role != Role::System)
-                .cloned()
-                .collect();
-
-            let keep_count = self.max_messages - system_msgs.len();
-            let skip = non_system.len().saturating_sub(keep_count);
-
-            self.messages = system_msgs;
-            self.messages.extend(non_system.into_iter().skip(skip));
+        let mut excess = self.messages.len().saturating_sub(self.max_messages);
+        if excess == 0 {
+            return;
         }
+        self.messages.retain(|m| {
+            if excess > 0 && m.role != Role::System && m.pinned.is_none() {
+                excess -= 1;
+                false
+            } else {
+                true
+            }
+        });
     }
 
     /// コンテキスト圧縮を適用して新しいConversationを返す
@@ -232,13 +329,133 @@ impl Conversation {
 
     /// 推定トークン数を取得
     pub fn estimated_tokens(&self) -> usize {
+        self.estimated_tokens_by_role().total()
+    }
+
+    /// ロールごとの推定トークン数（先頭のシステムメッセージはシステムプロンプトとして分ける）
+    pub fn estimated_tokens_by_role(&self) -> TokenBreakdown {
         use super::compression::ContextCompressor;
 
         let compressor = ContextCompressor::new();
-        compressor.estimate_tokens(self)
+        let mut breakdown = TokenBreakdown::default();
+        let system_prompt = self.messages.iter().position(|m| m.role == Role::System);
+        for (i, message) in self.messages.iter().enumerate() {
+            let tokens = compressor.estimate_message_tokens(message);
+            let slot = match message.role {
+                Role::System if Some(i) == system_prompt => &mut breakdown.system_prompt,
+                Role::System => &mut breakdown.system,
+                Role::User => &mut breakdown.user,
+                Role::Assistant => &mut breakdown.assistant,
+                Role::Tool => &mut breakdown.tool,
+            };
+            *slot += tokens;
+        }
+        breakdown
     }
 }
 
+/// 入力したユーザーメッセージ1つと、それに続くメッセージ（/undo・/retry）
+#[derive(Debug, Clone)]
+pub struct Exchange {
+    /// 先頭が入力したユーザーメッセージ（後ろに検証の修正依頼が続くことがある）
+    messages: Vec<Message>,
+}
+
+impl Exchange {
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    /// ユーザーが入力したテキスト（入力した行がなければ送った内容）
+    pub fn user_text(&self) -> &str {
+        let first = &self.messages[0];
+        first.input.as_deref().unwrap_or(&first.content)
+    }
+
+    /// やり直しで送るテキスト（指示があれば元のテキストの後ろに添える）
+    pub fn retry_input(&self, guidance: Option<&str>) -> String {
+        match guidance.map(str::trim).filter(|g| !g.is_empty()) {
+            Some(guidance) => format!("{}\n\n{}", self.user_text(), guidance),
+            None => self.user_text().to_string(),
+        }
+    }
+
+    /// 取り除いた内容の要約（`1 user message, 2 tool results, 2 assistant messages`）
+    pub fn summary(&self) -> String {
+        let count = |role: Role| self.messages.iter().filter(|m| m.role == role).count();
+        let plural = |n: usize, noun: &str| format!("{} {}{}", n, noun, if n == 1 { "" } else { "s" });
+        let mut parts = vec![plural(count(Role::User), "user message")];
+        let tools = count(Role::Tool);
+        if tools > 0 {
+            parts.push(plural(tools, "tool result"));
+        }
+        parts.push(plural(count(Role::Assistant), "assistant message"));
+        parts.join(", ")
+    }
+}
+
+/// これを超えたらプロンプトに警告を出す使用率（%）
+pub const CONTEXT_WARNING_PERCENT: usize = 80;
+
+/// ロールごとの推定トークン数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenBreakdown {
+    pub system_prompt: usize,
+    /// システムプロンプト以外のシステムメッセージ（圧縮の要約など）
+    pub system: usize,
+    pub user: usize,
+    pub assistant: usize,
+    pub tool: usize,
+}
+
+impl TokenBreakdown {
+    /// システムプロンプトを除いた会話部分
+    pub fn conversation(&self) -> usize {
+        self.system + self.user + self.assistant + self.tool
+    }
+
+    pub fn total(&self) -> usize {
+        self.system_prompt + self.conversation()
+    }
+
+    /// コンテキストウィンドウに対する使用率（%、切り捨て）
+    pub fn usage_percent(&self, window: usize) -> usize {
+        (self.total() * 100).checked_div(window).unwrap_or(100)
+    }
+
+    /// /tokens の表示
+    pub fn report(&self, window: usize) -> String {
+        let rows = [
+            ("System prompt", self.system_prompt),
+            ("Conversation", self.conversation()),
+            ("  user", self.user),
+            ("  assistant", self.assistant),
+            ("  tool", self.tool),
+            ("  summaries", self.system),
+        ];
+        let mut lines: Vec<String> = rows
+            .iter()
+            .map(|(label, tokens)| format!("{:<15}{:>8}", label, tokens))
+            .collect();
+        lines.push(format!(
+            "{:<15}{:>8} / {} ({}%)",
+            "Total",
+            self.total(),
+            window,
+            self.usage_percent(window)
+        ));
+        lines.push(format!("{:<15}{:>8}", "Remaining", window.saturating_sub(self.total())));
+        lines.push("(estimated: ~4 ASCII or ~2 other characters per token)".to_string());
+        lines.join("\n")
+    }
+}
+
+/// 使用率が閾値を超えていればプロンプトに添える警告（`⚠ 85% ctx`）
+pub fn context_warning(used: usize, window: usize) -> Option<String> {
+    let percent = (used * 100).checked_div(window).unwrap_or(100);
+    (percent > CONTEXT_WARNING_PERCENT).then(|| format!("⚠ {}% ctx", percent))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -255,6 +472,15 @@ mod tests {
         assert_eq!(conv.messages()[1].role, Role::User);
     }
 
+    #[test]
+    fn test_interrupted_assistant_in_prompt() {
+        let mut conv = Conversation::new();
+        conv.add_user("Hello");
+        conv.add_interrupted_assistant("Partial ans");
+        assert!(conv.last().unwrap().interrupted);
+        assert!(conv.to_prompt().contains("Partial ans\n[interrupted by user]"));
+    }
+
     #[test]
     fn test_to_prompt() {
         let mut conv = Conversation::new();
@@ -263,4 +489,144 @@ mod tests {
         assert!(prompt.contains("User: Hello"));
         assert!(prompt.ends_with("Assistant: "));
     }
+
+    #[test]
+    fn test_pinned_message_survives_truncation_for_the_turn() {
+        let mut conv = Conversation::with_max_messages(3);
+        conv.set_system("system");
+        conv.add_pinned_user("look at t3", "<pinned_tool_results>full output</pinned_tool_results>");
+        for i in 0..4 {
+            conv.add_tool_result("read", format!("result {}", i));
+        }
+
+        assert_eq!(conv.len(), 3);
+        assert!(conv.to_prompt().contains("User: look at t3\n\n<pinned_tool_results>full output"));
+
+        // 次のターンでは固定が外れ、通常どおり切り詰められる
+        conv.release_pins();
+        assert!(!conv.to_prompt().contains("full output"));
+        conv.add_user("next");
+        assert!(!conv.messages().iter().any(|m| m.content == "look at t3"));
+    }
+
+    #[test]
+    fn test_pop_last_exchange_with_tool_messages() {
+        let mut conv = Conversation::new();
+        conv.set_system("system");
+        conv.add_user("first");
+        conv.add_assistant("first answer");
+        conv.add_user("read main.rs");
+        conv.add_assistant("<tool_call>read</tool_call>");
+        conv.add_tool_result("read", "fn main() {}");
+        conv.add(Message::system("summary of older messages"));
+        conv.add_tool_result("grep", "no matches");
+        conv.add_assistant("main.rs is empty");
+
+        let exchange = conv.pop_last_exchange().unwrap();
+        assert_eq!(exchange.user_text(), "read main.rs");
+        assert_eq!(exchange.messages().len(), 5);
+        assert_eq!(exchange.summary(), "1 user message, 2 tool results, 2 assistant messages");
+        let left: Vec<&str> = conv.messages().iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(left, ["system", "first", "first answer", "summary of older messages"]);
+
+        // 応答を待たずに中断したターンも1つのやり取り
+        conv.add_user("unanswered");
+        assert_eq!(conv.pop_last_exchange().unwrap().summary(), "1 user message, 0 assistant messages");
+
+        conv.pop_last_exchange().unwrap();
+        assert!(conv.pop_last_exchange().is_none());
+        assert_eq!(conv.len(), 2);
+        assert!(conv.messages().iter().all(|m| m.role == Role::System));
+    }
+
+    #[test]
+    fn test_pop_last_exchange_includes_verification_fixes() {
+        let tagged = |content: &str, origin: MessageOrigin, input: Option<&str>| {
+            let mut message = Message::user(content);
+            message.origin = origin;
+            message.input = input.map(str::to_string);
+            message
+        };
+        let mut conv = Conversation::new();
+        conv.add_user("earlier question");
+        conv.add_assistant("earlier answer");
+        conv.add(tagged(
+            "<skill_hint>\nRelevant skills detected: tdd.\n</skill_hint>\n\nwrite a parser\n\n[Pinned tool results: grep]",
+            MessageOrigin::Typed,
+            Some("write a parser"),
+        ));
+        conv.add_assistant("```rust\nfn parse( {}\n```");
+        for attempt in 1..=2 {
+            conv.add(tagged(&format!("The code failed to compile (attempt {})", attempt), MessageOrigin::Verification, None));
+            conv.add_assistant("```rust\nfn parse() {}\n```");
+        }
+
+        let exchange = conv.pop_last_exchange().unwrap();
+        assert_eq!(exchange.messages().len(), 6);
+        assert_eq!(exchange.summary(), "3 user messages, 3 assistant messages");
+        assert_eq!(exchange.user_text(), "write a parser");
+        assert_eq!(exchange.retry_input(Some("use nom")), "write a parser\n\nuse nom");
+        let left: Vec<&str> = conv.messages().iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(left, ["earlier question", "earlier answer"]);
+
+        // スキルのプロンプトもやり取りの始まり
+        conv.add(tagged("Follow the debug skill: ...", MessageOrigin::Skill, Some("/debug crash on start")));
+        conv.add_assistant("found it");
+        assert_eq!(conv.pop_last_exchange().unwrap().user_text(), "/debug crash on start");
+
+        // 出どころと入力した行は保存しても残る
+        let message = tagged("with hint", MessageOrigin::Verification, Some("raw"));
+        let restored: Message = serde_json::from_str(&serde_json::to_string(&message).unwrap()).unwrap();
+        assert_eq!(restored.origin, MessageOrigin::Verification);
+        assert_eq!(restored.input.as_deref(), Some("raw"));
+    }
+
+    #[test]
+    fn test_retry_input_keeps_user_text() {
+        let mut conv = Conversation::new();
+        conv.add_user("explain  the parser\nin detail");
+        conv.add_assistant("long answer");
+        let exchange = conv.pop_last_exchange().unwrap();
+        assert_eq!(exchange.retry_input(None), "explain  the parser\nin detail");
+        assert_eq!(exchange.retry_input(Some("  ")), "explain  the parser\nin detail");
+        assert_eq!(exchange.retry_input(Some("but shorter")), "explain  the parser\nin detail\n\nbut shorter");
+    }
+
+    #[test]
+    fn test_token_breakdown_by_role() {
+        use super::super::compression::ContextCompressor;
+
+        let mut conv = Conversation::new();
+        conv.set_system("You are a helpful assistant.");
+        conv.messages.push(Message::system("[Summary] earlier turns"));
+        conv.add_user("Hello");
+        conv.add_assistant("Hi there!");
+        conv.add_tool_result("read", "fn main() {}");
+
+        let compressor = ContextCompressor::new();
+        let tokens: Vec<usize> = conv.messages().iter().map(|m| compressor.estimate_message_tokens(m)).collect();
+        let breakdown = conv.estimated_tokens_by_role();
+        assert_eq!(breakdown.system_prompt, tokens[0]);
+        assert_eq!(breakdown.system, tokens[1]);
+        assert_eq!(breakdown.user, tokens[2]);
+        assert_eq!(breakdown.assistant, tokens[3]);
+        assert_eq!(breakdown.tool, tokens[4]);
+        assert_eq!(breakdown.conversation(), tokens[1..].iter().sum::<usize>());
+        assert_eq!(breakdown.total(), conv.estimated_tokens());
+
+        let breakdown = TokenBreakdown { system_prompt: 100, user: 200, assistant: 300, ..Default::default() };
+        assert_eq!(breakdown.usage_percent(1000), 60);
+        assert_eq!(breakdown.usage_percent(0), 100);
+        let report = breakdown.report(1000);
+        assert!(report.contains("600 / 1000 (60%)"));
+        assert!(report.lines().any(|l| l.starts_with("Remaining") && l.ends_with("400")));
+    }
+
+    #[test]
+    fn test_context_warning_threshold() {
+        assert_eq!(context_warning(800, 1000), None);
+        assert_eq!(context_warning(850, 1000).as_deref(), Some("⚠ 85% ctx"));
+        assert_eq!(context_warning(1200, 1000).as_deref(), Some("⚠ 120% ctx"));
+        assert_eq!(context_warning(10, 0).as_deref(), Some("⚠ 100% ctx"));
+    }
 }
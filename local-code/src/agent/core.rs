@@ -1,18 +1,38 @@
 use anyhow::Result;
 use std::sync::Arc;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
 
-use crate::config::{OllamaConfig, RetryConfig};
-use crate::llm::{OllamaClient, ToolCallParser};
-use crate::tools::ToolRegistry;
+use crate::config::{LlmProvider, OllamaConfig, RetryConfig};
+use crate::llm::{LlmBackend, OllamaClient, OpenAiCompatClient, RequestOptions, RetryEvent, ToolCallFormat, ToolCallParser};
+use crate::tools::{DryRun, Tool, ToolRegistry, ToolResult, Workspace};
 use crate::skills::SkillRegistry;
-use crate::cli::output::StreamingWriter;
-use super::context::AgentContext;
-use super::conversation::Conversation;
+use crate::cli::output::{OutputPostProcessor, StreamingWriter};
+use super::compression::{CompressionConfig, ContextCompressor};
+use super::context::{describe_workspace, AgentContext, WORKSPACE_SUMMARY_BUDGET};
+use super::memory::{Memory, MemoryEntry, MemoryScope};
+use super::debug::PromptDebugger;
+use super::environment::{EnvFingerprint, EnvProber};
+use super::conversation::{Conversation, Exchange, Message, MessageOrigin, Role, TokenBreakdown};
+use super::history::{autosave_name, ConversationMetadata, HistoryManager};
 use super::mode::ModeManager;
+use super::session::{ResetAction, ResetTarget, SessionResetHub, SessionResettable, SessionTransition};
+use super::prompt::SystemPrompt;
+use super::turn::{ToolCallRecord, TurnRecord};
+use super::event::AgentEvent;
+use super::confirmation::ToolConfirmation;
+use super::tool_output::ToolOutputManager;
+use super::tool_stats::{SessionStats, ToolStats};
+use super::tool_results::{pin_labels, pinned_section, PinnedResult, ToolResultRecord, ToolResultStore};
 
 /// エージェント設定
 pub struct AgentConfig {
+    /// 使用する LLM バックエンド
+    pub provider: LlmProvider,
+    /// LLM サーバーの URL（provider が openai なら OpenAI 互換サーバーの `/v1` まで）
     pub ollama_url: String,
+    /// OpenAI 互換サーバーの API キー
+    pub api_key: Option<String>,
     pub model: String,
     pub initial_mode: super::mode::Mode,
     /// 会話履歴の最大メッセージ数
@@ -21,20 +41,46 @@ pub struct AgentConfig {
     pub connect_timeout: u64,
     /// 読み取りタイムアウト（秒）
     pub read_timeout: u64,
+    /// num_ctx（未指定ならサーバーのデフォルト）
+    pub num_ctx: Option<u32>,
+    /// num_ctx を引き上げる上限
+    pub max_num_ctx: u32,
     /// リトライ設定
     pub retry_config: RetryConfig,
+    /// コンテキスト圧縮の設定
+    pub compression: CompressionConfig,
+    /// /tokens で使うコンテキストウィンドウ（トークン）
+    pub context_window: usize,
+    /// 応答の THOUGHT・`<think>` を会話履歴に残すか（false なら取り除いて記録）
+    pub keep_thoughts: bool,
+    /// 生成オプション（temperature など）
+    pub options: RequestOptions,
+    /// モデルをメモリに残す時間（Ollama の keep_alive、None なら送らない）
+    pub keep_alive: Option<String>,
+    /// 受け付けるツール呼び出しの書式（システムプロンプトにも載せる）
+    pub tool_call_format: ToolCallFormat,
 }
 
 impl Default for AgentConfig {
     fn default() -> Self {
         Self {
+            provider: LlmProvider::Ollama,
             ollama_url: "http://localhost:11434".to_string(),
+            api_key: None,
             model: "Rnj-1".to_string(),
             initial_mode: super::mode::Mode::Execute,
             max_messages: 100,
             connect_timeout: 30,
             read_timeout: 300,
+            num_ctx: None,
+            max_num_ctx: crate::llm::context_window::DEFAULT_MAX_NUM_CTX,
             retry_config: RetryConfig::default(),
+            compression: CompressionConfig::default(),
+            context_window: DEFAULT_CONTEXT_WINDOW,
+            keep_thoughts: false,
+            options: RequestOptions::default(),
+            keep_alive: None,
+            tool_call_format: ToolCallFormat::default(),
         }
     }
 }
@@ -47,21 +93,34 @@ impl AgentConfig {
         max_messages: usize,
     ) -> Self {
         Self {
+            provider: LlmProvider::Ollama,
             ollama_url: ollama_config.url.clone(),
+            api_key: None,
             model: ollama_config.model.clone(),
             initial_mode,
             max_messages,
             connect_timeout: ollama_config.connect_timeout,
             read_timeout: ollama_config.read_timeout,
+            num_ctx: ollama_config.num_ctx,
+            max_num_ctx: ollama_config.max_num_ctx,
             retry_config: ollama_config.retry.clone(),
+            compression: CompressionConfig::default(),
+            context_window: DEFAULT_CONTEXT_WINDOW,
+            keep_thoughts: false,
+            options: ollama_config.options.clone(),
+            keep_alive: Some(ollama_config.keep_alive.clone()).filter(|k| !k.trim().is_empty()),
+            tool_call_format: ToolCallFormat::default(),
         }
     }
 }
 
+/// 既定のコンテキストウィンドウ（`agent.context_window`）
+pub const DEFAULT_CONTEXT_WINDOW: usize = 8192;
+
 /// メインエージェント
 pub struct Agent {
-    /// LLMクライアント
-    llm: OllamaClient,
+    /// LLMクライアント（`[llm] provider` で選んだバックエンド）
+    llm: Box<dyn LlmBackend>,
     /// ツールレジストリ
     tools: Arc<ToolRegistry>,
     /// スキルレジストリ
@@ -78,6 +137,75 @@ pub struct Agent {
     max_messages: usize,
     /// 作業ディレクトリ（プロジェクトルート）
     project_root: Option<std::path::PathBuf>,
+    /// 複数ルートのワークスペース（ルートが1つなら None）
+    workspace: Option<Arc<Workspace>>,
+    /// プロンプトデバッグ出力（--debug-prompt）
+    prompt_debugger: Option<PromptDebugger>,
+    /// 環境フィンガープリントのプローブ
+    env_prober: Option<Arc<EnvProber>>,
+    /// セッション遷移時のリセット先
+    session_hub: SessionResetHub,
+    /// 直近のツール結果（/use で参照）
+    tool_results: Arc<ToolResultStore>,
+    /// 長いツール結果の切り詰め（全文は read_tool_output で読む）
+    tool_output: Arc<ToolOutputManager>,
+    /// 受け付けるツール呼び出しの書式
+    tool_call_format: ToolCallFormat,
+    /// 次のユーザーメッセージに添付する固定結果
+    pending_pins: Vec<PinnedResult>,
+    /// 次のユーザーメッセージの出どころと入力した行（/undo・/retry 用）
+    pending_origin: (MessageOrigin, Option<String>),
+    /// システムプロンプトの静的な先頭部分のバイト数
+    static_prefix_len: usize,
+    /// 長くなった会話の圧縮
+    compressor: ContextCompressor,
+    /// 直近の圧縮結果（表示はCLI層、取り出すまで保持）
+    compression_notice: Option<CompressionNotice>,
+    /// /tokens で使うコンテキストウィンドウ
+    context_window: usize,
+    /// 応答の思考部分を会話履歴に残すか
+    keep_thoughts: bool,
+    /// 確認が必要なツールを実行せずに拒否する（確認できない非対話モード）
+    deny_confirmations: bool,
+    /// 実行前の確認（未設定なら確認しない）
+    confirmation: Option<Arc<ToolConfirmation>>,
+    /// ドライラン（有効時は変更系のツールを模擬する）
+    dry_run: Arc<DryRun>,
+    /// ターンごとの自動保存先（REPL のみ）
+    autosave: Option<HistoryManager>,
+    /// セッションの開始日時（保存するメタデータの created_at）
+    created_at: u64,
+    /// ターンの進行の通知先（スピナー表示用）
+    events: Option<UnboundedSender<AgentEvent>>,
+    /// ツールの使用統計（/stats）
+    tool_stats: Arc<ToolStats>,
+}
+
+/// 会話を圧縮した結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressionNotice {
+    /// 要約に置き換えたメッセージ数
+    pub compressed_messages: usize,
+    /// 推定トークン削減数
+    pub tokens_saved: usize,
+}
+
+/// 1回の生成の結果
+enum Generated {
+    /// 応答と統計情報（取れた場合）
+    Complete(String, Option<crate::llm::StreamStats>),
+    /// 中断された（ストリーミングでは受信済みの部分応答）
+    Interrupted(String),
+}
+
+impl std::fmt::Display for CompressionNotice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Context compressed: {} messages → summary (~{} tokens saved)",
+            self.compressed_messages, self.tokens_saved
+        )
+    }
 }
 
 impl Agent {
@@ -88,14 +216,44 @@ impl Agent {
         skills: Arc<SkillRegistry>,
         mode: ModeManager,
     ) -> Self {
+        let llm: Box<dyn LlmBackend> = match config.provider {
+            LlmProvider::Ollama => Box::new(
+                OllamaClient::with_timeout(
+                    &config.ollama_url,
+                    &config.model,
+                    config.connect_timeout,
+                    config.read_timeout,
+                )
+                .with_retry_config(config.retry_config.clone())
+                .with_context_limits(config.num_ctx, config.max_num_ctx)
+                .with_options(config.options.clone())
+                .with_keep_alive(config.keep_alive.clone()),
+            ),
+            LlmProvider::OpenAi => Box::new(
+                OpenAiCompatClient::with_timeout(
+                    &config.ollama_url,
+                    &config.model,
+                    config.connect_timeout,
+                    config.read_timeout,
+                )
+                .with_api_key(config.api_key.clone())
+                .with_retry_config(config.retry_config.clone())
+                .with_options(config.options.clone()),
+            ),
+        };
+        let mut session_hub = SessionResetHub::new();
+        if let Some(state) = llm.session_state() {
+            session_hub.register(state);
+        }
+        let tool_stats = Arc::new(ToolStats::new());
+        session_hub.register(Arc::clone(&tool_stats) as Arc<dyn SessionResettable>);
+        let tool_results = Arc::new(ToolResultStore::new());
+        session_hub.register(Arc::clone(&tool_results) as Arc<dyn SessionResettable>);
+        let tool_output = Arc::new(ToolOutputManager::default());
+        session_hub.register(Arc::clone(&tool_output) as Arc<dyn SessionResettable>);
+
         Self {
-            llm: OllamaClient::with_timeout(
-                &config.ollama_url,
-                &config.model,
-                config.connect_timeout,
-                config.read_timeout,
-            )
-            .with_retry_config(config.retry_config.clone()),
+            llm,
             tools: Arc::new(tools),
             skills,
             conversation: Conversation::with_max_messages(config.max_messages),
@@ -104,6 +262,30 @@ impl Agent {
             system_extra: None,
             max_messages: config.max_messages,
             project_root: None,
+            workspace: None,
+            prompt_debugger: None,
+            env_prober: None,
+            session_hub,
+            tool_results,
+            tool_output,
+            tool_call_format: config.tool_call_format,
+            pending_pins: Vec::new(),
+            pending_origin: (MessageOrigin::Typed, None),
+            static_prefix_len: 0,
+            compressor: ContextCompressor::with_config(config.compression.clone()),
+            compression_notice: None,
+            context_window: config.context_window,
+            keep_thoughts: config.keep_thoughts,
+            deny_confirmations: false,
+            confirmation: None,
+            dry_run: Arc::new(DryRun::default()),
+            autosave: None,
+            created_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            events: None,
+            tool_stats,
         }
     }
 
@@ -112,116 +294,505 @@ impl Agent {
         // 作業ディレクトリを保存
         self.project_root = Some(project_root.to_path_buf());
 
-        self.context = AgentContext::load_from_project(project_root).await?;
+        // 複数ルートなら、ほかのルートの agent.md も読み込む
+        let roots = self.workspace.as_ref().map(|w| w.roots().to_vec()).unwrap_or_default();
+        self.context = AgentContext::load_from_project(project_root, &roots).await?;
 
         // システムプロンプトを設定
-        let mut system_prompt = self.build_system_prompt();
-        if let Some(extra) = &self.system_extra {
-            system_prompt.push_str("\n\n");
-            system_prompt.push_str(extra);
-        }
-        if let Some(ctx) = self.context.as_system_prompt() {
-            system_prompt.push_str("\n\n");
-            system_prompt.push_str(&ctx);
-        }
-        self.conversation.set_system(system_prompt);
+        let system_prompt = self.build_system_prompt();
+        let rendered = system_prompt.render();
+        // 同じ状態からは同じプロンプトになること（サーバー側のプレフィックスキャッシュのため）
+        debug_assert_eq!(rendered, self.build_system_prompt().render());
+        tracing::debug!("Working directory in system prompt: {:?}", self.project_root);
+        self.static_prefix_len = system_prompt.static_prefix_len();
+        self.conversation.set_system(rendered);
 
         Ok(())
     }
 
     /// ユーザー入力を処理
     pub async fn process(&mut self, input: &str) -> Result<String> {
-        self.conversation.add_user(input);
+        self.process_with_cancel(input, &CancellationToken::new()).await
+    }
+
+    /// ユーザー入力を処理し、ツール呼び出しや統計を含む記録を返す
+    pub async fn process_detailed(&mut self, input: &str) -> Result<TurnRecord> {
+        self.process_detailed_with_cancel(input, &CancellationToken::new()).await
+    }
+
+    /// ユーザー入力を処理（キャンセル対応）
+    ///
+    /// トークンがキャンセルされた場合は応答待ちを打ち切り、
+    /// 中断されたアシスタントメッセージとして会話に記録する
+    pub async fn process_with_cancel(&mut self, input: &str, cancel: &CancellationToken) -> Result<String> {
+        Ok(self.process_detailed_with_cancel(input, cancel).await?.response)
+    }
+
+    /// ユーザー入力を処理し、記録を返す（キャンセル対応）
+    pub async fn process_detailed_with_cancel(
+        &mut self,
+        input: &str,
+        cancel: &CancellationToken,
+    ) -> Result<TurnRecord> {
+        self.process_turn(input, cancel, false).await
+    }
+
+    async fn process_turn(&mut self, input: &str, cancel: &CancellationToken, streaming: bool) -> Result<TurnRecord> {
+        // リトライの待機中でもすぐに中断できるよう、ターンの間だけトークンを渡す
+        self.llm.set_cancel_token(Some(cancel.clone()));
+        let result = self.run_turn(input, cancel, streaming).await;
+        self.llm.set_cancel_token(None);
+        self.emit(AgentEvent::Done);
+        if let Ok(record) = &result {
+            self.tool_stats.record_turn(record.stats.as_ref());
+            self.autosave();
+        }
+        result
+    }
+
+    /// ツールを実行し（設定されたタイムアウト付き）、使用統計に記録する
+    async fn execute_tool(&self, name: &str, tool: &dyn Tool, params: serde_json::Value) -> Result<ToolResult> {
+        let started = std::time::Instant::now();
+        let result = self.tools.execute_with_timeout(name, self.dry_run.execute(tool, params)).await;
+        let (success, output_bytes) = match &result {
+            Ok(result) if result.success => (true, result.output.len()),
+            Ok(result) => (false, result.error.as_deref().map_or(0, str::len)),
+            Err(e) => (false, e.to_string().len()),
+        };
+        self.tool_stats.record_tool(name, started.elapsed(), success, output_bytes);
+        result
+    }
+
+    async fn run_turn(&mut self, input: &str, cancel: &CancellationToken, streaming: bool) -> Result<TurnRecord> {
+        let started = std::time::Instant::now();
+        let mut record = TurnRecord::new(input);
+        self.add_user_input(input).await;
 
         // LLMに送信
         let prompt = self.conversation.to_prompt();
-        let response = self.llm.generate(&prompt, None).await?;
+        self.dump_debug("prompt", &prompt);
+        self.emit(AgentEvent::GenerationStarted);
+        let generated = if streaming {
+            self.generate_streaming(&prompt, cancel).await?
+        } else {
+            tokio::select! {
+                _ = cancel.cancelled() => Generated::Interrupted(String::new()),
+                response = self.llm.generate_with_stats(&prompt, None) => match response {
+                    Ok((response, stats)) => Generated::Complete(response, stats),
+                    // リトライ待機の打ち切りによる失敗も中断として扱う
+                    Err(_) if cancel.is_cancelled() => Generated::Interrupted(String::new()),
+                    Err(e) => return Err(e),
+                },
+            }
+        };
+        let response = match generated {
+            Generated::Complete(response, stats) => {
+                record.stats = stats;
+                response
+            }
+            Generated::Interrupted(partial) => {
+                // 部分応答は中断済みとして残す（ツールは実行しない）
+                self.dump_debug("response", &partial);
+                self.conversation.add_interrupted_assistant(&partial);
+                record.assistant = partial.clone();
+                record.response = partial;
+                record.interrupted = true;
+                record.elapsed_ms = started.elapsed().as_millis() as u64;
+                return Ok(record);
+            }
+        };
+        self.dump_debug("response", &response);
 
         // ツール呼び出しをパース
-        let tool_calls = ToolCallParser::parse(&response)?;
+        let tool_calls = ToolCallParser::parse_with(&response, self.tool_call_format)?;
 
         if tool_calls.is_empty() {
             // ツール呼び出しなし - テキスト応答
-            self.conversation.add_assistant(&response);
-            return Ok(response);
+            self.conversation.add_assistant(self.recorded_text(&response));
+            record.assistant = response.clone();
+            record.response = response;
+            record.elapsed_ms = started.elapsed().as_millis() as u64;
+            return Ok(record);
         }
 
-        // ツールを実行
-        let mut full_response = String::new();
-        let (text_part, _) = ToolCallParser::split_response(&response);
-        if !text_part.is_empty() {
-            full_response.push_str(&text_part);
-            full_response.push_str("\n\n");
-        }
+        // ツールを実行（結果は応答の本文と別に組み立て、会話には思考を除いた本文と合わせて記録する）
+        let mut results = String::new();
+        let (text_part, _) = ToolCallParser::split_response_with(&response, self.tool_call_format);
+        let recorded_text = self.recorded_text(&text_part);
 
         for call in tool_calls {
-            // モード制限をチェック
-            if !self.mode.is_tool_allowed(&call.tool).await {
-                let error_msg = format!(
-                    "Tool '{}' is not allowed in {} mode",
-                    call.tool,
-                    self.mode.current().await
-                );
+            // 中断された場合は残りのツールを実行しない
+            if cancel.is_cancelled() {
+                record.interrupted = true;
+                break;
+            }
+
+            // モード制限と確認の可否をチェックし、ユーザーに確認する
+            let denial = match self.tool_denial(&call.tool, &call.params).await {
+                Some(error_msg) => Some(error_msg),
+                None => self.confirmation_denial(&call.tool, &call.params).await,
+            };
+            if let Some(error_msg) = denial {
                 self.conversation.add_tool_result(&call.tool, &error_msg);
-                full_response.push_str(&format!("[{}] {}\n", call.tool, error_msg));
+                results.push_str(&format!("[{}] {}\n", call.tool, error_msg));
+                record.tool_calls.push(ToolCallRecord {
+                    id: None,
+                    tool: call.tool,
+                    params: call.params,
+                    output: error_msg,
+                    success: false,
+                    data: None,
+                });
                 continue;
             }
 
             // ツールを実行
-            if let Some(tool) = self.tools.get(&call.tool) {
-                match tool.execute(call.params).await {
-                    Ok(result) => {
-                        let output = if result.success {
+            self.emit(AgentEvent::ToolStarted(call.tool.clone()));
+            self.observe_tool_call(&call.tool, &call.params);
+            let (id, output, success, data) = if let Some(tool) = self.tools.get(&call.tool) {
+                match self.execute_tool(&call.tool, tool.as_ref(), call.params.clone()).await {
+                    Ok(mut result) => {
+                        let data = result.data.take();
+                        let success = result.success;
+                        let mut output = if result.success {
                             result.output
                         } else {
                             result.error.unwrap_or_else(|| "Unknown error".to_string())
                         };
-                        self.conversation.add_tool_result(&call.tool, &output);
-                        full_response.push_str(&format!("[{}]\n{}\n\n", call.tool, output));
+                        if success {
+                            if let Some(guidance) = self.directory_context(&call.params).await {
+                                output.push_str(&format!("\n\n{}", guidance));
+                            }
+                        }
+                        let (id, shown) = self.record_tool_result(&call.tool, &call.params, &output, data.clone());
+                        results.push_str(&format!("[{} {}]\n{}\n\n", call.tool, id, shown));
+                        (Some(id), output, success, data)
                     }
                     Err(e) => {
                         let error = format!("Error: {}", e);
                         self.conversation.add_tool_result(&call.tool, &error);
-                        full_response.push_str(&format!("[{}] {}\n\n", call.tool, error));
+                        results.push_str(&format!("[{}] {}\n\n", call.tool, error));
+                        (None, error, false, None)
                     }
                 }
             } else {
                 let error = format!("Unknown tool: {}", call.tool);
                 self.conversation.add_tool_result(&call.tool, &error);
-                full_response.push_str(&format!("{}\n\n", error));
-            }
+                results.push_str(&format!("{}\n\n", error));
+                (None, error, false, None)
+            };
+            self.emit(AgentEvent::ToolFinished(call.tool.clone(), success));
+            record.tool_calls.push(ToolCallRecord {
+                id,
+                tool: call.tool,
+                params: call.params,
+                output,
+                success,
+                data,
+            });
         }
 
-        self.conversation.add_assistant(&full_response);
-        Ok(full_response)
+        let with_text = |text: &str| {
+            if text.is_empty() {
+                results.clone()
+            } else {
+                format!("{}\n\n{}", text, results)
+            }
+        };
+        self.conversation.add_assistant(with_text(&recorded_text));
+        record.response = with_text(&text_part);
+        record.assistant = text_part;
+        record.elapsed_ms = started.elapsed().as_millis() as u64;
+        Ok(record)
     }
 
-    /// システムプロンプトを構築
-    fn build_system_prompt(&self) -> String {
-        let tools_prompt = self.tools.to_prompt_format();
+    /// 会話履歴に記録する応答本文（設定により思考部分を取り除く）
+    fn recorded_text(&self, text: &str) -> String {
+        if self.keep_thoughts {
+            text.to_string()
+        } else {
+            OutputPostProcessor::remove_thought_blocks(text)
+        }
+    }
 
-        // 作業ディレクトリ情報を追加
-        let working_dir_info = if let Some(ref root) = self.project_root {
+    /// ツールを実行できない理由（モードで禁止、または確認が必要だが確認できない）
+    async fn tool_denial(&self, tool: &str, params: &serde_json::Value) -> Option<String> {
+        if self.tools.is_disabled(tool) {
+            return Some(format!("Tool '{}' is disabled by configuration ([tools] disabled)", tool));
+        }
+        // 一覧表示などの呼び出しは、ツールが宣言した最も強い副作用ではなく呼び出しごとの副作用で判定する
+        let effects = match self.tools.get(tool) {
+            Some(t) => t.call_effects(params),
+            None => self.mode.effects_of(tool),
+        };
+        if !self.mode.is_call_allowed(tool, effects).await {
+            return Some(self.mode.call_denial_message(tool, effects).await);
+        }
+        // ドライランでは何も実行しないので確認は要らない
+        if self.deny_confirmations && !self.dry_run.is_enabled() && self.mode.call_requires_confirmation(effects) {
+            return Some(format!(
+                "Tool '{}' requires confirmation, which is not available in non-interactive mode (re-run with --yes to allow)",
+                tool
+            ));
+        }
+        None
+    }
+
+    /// 実行前にユーザーに確認し、拒否されたらモデルに返す結果
+    ///
+    /// ツール自身が確認するもの（上書きの差分など）とドライランでは確認しない
+    async fn confirmation_denial(&self, tool_name: &str, params: &serde_json::Value) -> Option<String> {
+        let confirmation = Arc::clone(self.confirmation.as_ref()?);
+        let tool = self.tools.get(tool_name)?;
+        if tool.confirms_itself() || self.dry_run.is_enabled() {
+            return None;
+        }
+        let effects = tool.call_effects(params);
+        let mode = self.mode.current().await;
+        let name = tool_name.to_string();
+        let details = serde_json::to_string_pretty(params).unwrap_or_else(|_| params.to_string());
+        let approved = tokio::task::spawn_blocking(move || confirmation.confirm(&name, effects, &mode, &details))
+            .await
+            .unwrap_or(false);
+        (!approved).then(|| {
             format!(
-                "\n\n# Working Directory\nYou are working in: {}\nAll file operations (read, write, glob, grep, bash) are relative to this directory.\nWhen using tools, you can use relative paths from this directory, or omit the path parameter to use the current directory.",
-                root.display()
+                "User denied execution of {}. Do not retry the same call; adjust the approach or ask the user",
+                tool_name
             )
+        })
+    }
+
+    /// ユーザー入力を会話に追加（/use の固定結果があれば添付）
+    ///
+    /// 追加する前に、長くなった会話を圧縮する（今回の入力と固定結果は圧縮の対象外）
+    async fn add_user_input(&mut self, input: &str) {
+        // 前のターンの固定は外す
+        self.conversation.release_pins();
+        self.compress_if_needed().await;
+        let pins = std::mem::take(&mut self.pending_pins);
+        let (origin, typed) = std::mem::take(&mut self.pending_origin);
+        let mut message = if pins.is_empty() {
+            Message::user(input)
+        } else {
+            let mut message = Message::user(format!("{}\n\n[Pinned tool results: {}]", input, pin_labels(&pins)));
+            message.pinned = Some(pinned_section(&pins));
+            message
+        };
+        message.origin = origin;
+        message.input = typed;
+        self.conversation.add(message);
+    }
+
+    /// 閾値を超えていれば古いメッセージを要約に置き換える（システムプロンプトは保持）
+    async fn compress_if_needed(&mut self) {
+        if !self.compressor.should_compress(&self.conversation) {
+            return;
+        }
+        let compressed = if self.compressor.config().use_llm_summary {
+            self.compressor.compress_with_llm(&self.conversation, self.llm.as_ref()).await
         } else {
-            String::new()
+            self.compressor.compress(&self.conversation)
+        };
+        let Some(history) = &compressed.compressed_history else {
+            return;
         };
+        tracing::info!(
+            "Compressed {} messages into a summary, ~{} tokens saved",
+            history.original_count,
+            compressed.estimated_tokens_saved
+        );
+        self.compression_notice = Some(CompressionNotice {
+            compressed_messages: history.original_count,
+            tokens_saved: compressed.estimated_tokens_saved,
+        });
+        self.replace_conversation(compressed.to_conversation());
+    }
+
+    /// 直近の圧縮結果を取り出す
+    pub fn take_compression_notice(&mut self) -> Option<CompressionNotice> {
+        self.compression_notice.take()
+    }
+
+    /// ツールが触れたパスのサブディレクトリにある agent.md（初めて触れたときだけ）
+    ///
+    /// `ラベル:パス` は複数ルートのワークスペースで解決してから探す
+    async fn directory_context(&mut self, params: &serde_json::Value) -> Option<String> {
+        let path = ["path", "file_path"].iter().find_map(|key| params.get(key).and_then(|v| v.as_str()))?;
+        let path = match &self.workspace {
+            Some(workspace) => workspace.resolve(path),
+            None => std::path::PathBuf::from(path),
+        };
+        let guidance = self.context.context_for(&path).await?;
+        Some(format!(
+            "[Additional instructions apply to this directory]\n{}",
+            guidance.trim_end()
+        ))
+    }
 
+    /// ツール結果を会話と記録に追加し、ID と会話に入れたテキストを返す
+    ///
+    /// 会話にはテキストだけを渡し、構造化された結果は記録にのみ残す。
+    /// 長い出力は先頭と末尾だけを会話に入れる（記録には全文が残る）
+    fn record_tool_result(
+        &mut self,
+        tool_name: &str,
+        params: &serde_json::Value,
+        output: &str,
+        data: Option<serde_json::Value>,
+    ) -> (String, String) {
+        let effects = self
+            .tools
+            .get(tool_name)
+            .map(|t| t.effects())
+            .unwrap_or(crate::tools::ToolEffects::Executes);
+        let id = self.tool_results.record(tool_name, params, effects, output, data);
+        let shown = self.tool_output.limit(&id, output);
+        self.conversation.add_tool_result(tool_name, &shown);
+        (id, shown)
+    }
+
+    /// ID でツール呼び出しの記録を取得（構造化された結果を含む）
+    pub fn tool_result(&self, id: &str) -> Option<ToolResultRecord> {
+        self.tool_results.get(id)
+    }
+
+    /// ID で過去のツール結果を取得（/use）
+    pub async fn resolve_tool_result(&self, id: &str) -> Result<PinnedResult> {
+        self.tool_results.resolve(id, &self.tools).await
+    }
+
+    /// 次のユーザーメッセージに固定結果を添付する
+    pub fn pin_for_next_turn(&mut self, pins: Vec<PinnedResult>) {
+        self.pending_pins = pins;
+    }
+
+    /// 次のユーザーメッセージの出どころと入力した行を記録する（指定しなければ入力そのもの）
+    pub fn tag_next_turn(&mut self, origin: MessageOrigin, input: Option<String>) {
+        self.pending_origin = (origin, input);
+    }
+
+    /// システムプロンプトを構築
+    ///
+    /// 静的なセクションを固定順で並べる。ターンごとに変わる情報は `push_dynamic` で末尾に置くこと
+    fn build_system_prompt(&self) -> SystemPrompt {
+        let mut prompt = SystemPrompt::new();
+        prompt.push_static(
+            "instructions",
+            format!(
+                "You are a coding assistant. You can use tools to help the user.\n\n{}",
+                self.tool_call_format.instructions()
+            ),
+        );
+        prompt.push_static("tools", self.tools.to_prompt_format());
+
+        // 作業ディレクトリ情報を追加
+        if let Some(workspace) = &self.workspace {
+            prompt.push_static("workspace", describe_workspace(workspace, WORKSPACE_SUMMARY_BUDGET));
+        } else if let Some(ref root) = self.project_root {
+            prompt.push_static(
+                "working_directory",
+                format!(
+                    "# Working Directory\nYou are working in: {}\nAll file operations (read, write, glob, grep, tree, bash) are relative to this directory.\nFor an overview of the project layout, call tree once instead of chaining glob calls.\nWhen using tools, you can use relative paths from this directory, or omit the path parameter to use the current directory.",
+                    root.display()
+                ),
+            );
+        }
+        if let Some(extra) = &self.system_extra {
+            prompt.push_static("system_extra", extra.clone());
+        }
+        if let Some(ctx) = self.context.as_system_prompt() {
+            prompt.push_static("project_context", ctx);
+        }
+        if let Some(workspace) = &self.workspace {
+            let current = workspace.current();
+            prompt.push_dynamic(
+                "current_root",
+                format!("# Current Root\n{} ({})", current.label, current.path.display()),
+            );
+        }
+        prompt
+    }
+
+    /// 複数ルートのワークスペースを設定（load_context の前に呼ぶ）
+    pub fn set_workspace(&mut self, workspace: Option<Arc<Workspace>>) {
+        self.workspace = workspace.filter(|w| w.is_multi());
+    }
+
+    /// 現在のルートを変えたあとにシステムプロンプトを作り直す（会話は残す）
+    pub fn refresh_system_prompt(&mut self) {
+        let system_prompt = self.build_system_prompt();
+        self.static_prefix_len = system_prompt.static_prefix_len();
+        self.conversation.replace_system_prompt(system_prompt.render());
+    }
+
+    /// メモリファイルだけを読み直してシステムプロンプトに反映する（会話は残す）
+    pub fn refresh_memory(&mut self) {
+        self.context.refresh_memory();
+        self.refresh_system_prompt();
+    }
+
+    /// 覚えている事柄（/memory）
+    pub fn memory(&self) -> &Memory {
+        &self.context.memory
+    }
+
+    /// 事柄を覚えてすぐにシステムプロンプトへ入れる（同じ内容がすでにあれば false）
+    pub fn remember(&mut self, text: &str, scope: MemoryScope) -> Result<bool> {
+        let added = self.context.memory.remember(text, scope)?;
+        self.refresh_system_prompt();
+        Ok(added)
+    }
+
+    /// /memory の番号で忘れる
+    pub fn forget_memory(&mut self, index: usize) -> Result<MemoryEntry> {
+        let removed = self.context.memory.forget(index)?;
+        self.refresh_system_prompt();
+        Ok(removed)
+    }
+
+    /// システムプロンプトのうちセッション中に変わらない先頭部分のバイト数
+    pub fn static_prefix_len(&self) -> usize {
+        self.static_prefix_len
+    }
+
+    /// /context の表示内容
+    pub fn context_summary(&self) -> String {
+        let system_len = self
+            .conversation
+            .messages()
+            .iter()
+            .find(|m| m.role == super::conversation::Role::System)
+            .map(|m| m.content.len())
+            .unwrap_or(0);
         format!(
-            r#"You are a coding assistant. You can use tools to help the user.
+            "Messages: {} (~{} tokens)\nSystem prompt: {} bytes, static prefix {} bytes\nThe static prefix stays byte-identical between turns so the server can reuse its prompt cache.",
+            self.conversation.len(),
+            self.conversation.estimated_tokens(),
+            system_len,
+            self.static_prefix_len
+        ) + "\n\n" + &self.context.describe()
+    }
 
-To use a tool, output a JSON block like this:
-```json
-{{"tool": "tool_name", "params": {{"param1": "value1"}}}}
-```
+    /// ロールごとの推定トークン数
+    pub fn token_breakdown(&self) -> TokenBreakdown {
+        self.conversation.estimated_tokens_by_role()
+    }
 
-{}{}"#,
-            tools_prompt,
-            working_dir_info
-        )
+    /// コンテキストウィンドウ（トークン）
+    pub fn context_window(&self) -> usize {
+        self.context_window
+    }
+
+    /// セッションの使用統計（/stats、`--output json`）
+    pub fn session_stats(&self) -> SessionStats {
+        let mut stats = self.tool_stats.snapshot();
+        stats.context_tokens = self.token_breakdown().total();
+        stats
+    }
+
+    /// 使用統計を捨てる（/stats reset）
+    pub fn reset_stats(&self) {
+        self.tool_stats.reset();
     }
 
     /// モードマネージャーへの参照を取得
@@ -234,9 +805,15 @@ To use a tool, output a JSON block like this:
         &self.skills
     }
 
+    /// 読み込み直したスキルレジストリに差し替える（/reload）
+    pub fn set_skills(&mut self, skills: Arc<SkillRegistry>) {
+        self.skills = skills;
+    }
+
     /// 会話をクリア
     pub fn clear_conversation(&mut self) {
         self.conversation.clear();
+        self.context.forget_directories();
     }
 
     /// 会話履歴を取得
@@ -244,12 +821,59 @@ To use a tool, output a JSON block like this:
         &self.conversation
     }
 
+    /// 最後のやり取りを会話から取り除く（/undo・/retry）。自動保存にも反映する
+    pub fn undo_last_exchange(&mut self) -> Option<Exchange> {
+        let exchange = self.conversation.pop_last_exchange()?;
+        self.autosave();
+        Some(exchange)
+    }
+
     /// 会話履歴を置き換え
     pub fn replace_conversation(&mut self, mut conversation: Conversation) {
         conversation.set_max_messages(self.max_messages);
         self.conversation = conversation;
     }
 
+    /// 保存した会話を読み込む（/load、/resume、--resume）
+    ///
+    /// 保存時のシステムプロンプトは古い可能性があるため、現在のものに置き換える
+    pub fn restore_conversation(&mut self, mut conversation: Conversation) {
+        let current = self.conversation.messages().first().filter(|m| m.role == Role::System);
+        if let Some(system) = current {
+            conversation.replace_system_prompt(system.content.clone());
+        }
+        self.replace_conversation(conversation);
+        self.context.forget_directories();
+    }
+
+    /// ターンごとに会話を自動保存する（None で無効）
+    pub fn set_autosave(&mut self, manager: Option<HistoryManager>) {
+        self.autosave = manager;
+    }
+
+    /// 会話の保存に付けるメタデータ（モデル、プロジェクト、使用ツールのバージョン）
+    pub fn session_metadata(&self) -> ConversationMetadata {
+        ConversationMetadata {
+            created_at: Some(self.created_at),
+            model: Some(self.llm.model().to_string()),
+            project_path: self.project_root.as_ref().map(|root| root.display().to_string()),
+            environment: self.env_fingerprint().filter(|f| !f.is_empty()),
+        }
+    }
+
+    /// プロジェクトの自動保存を上書きする（失敗してもターンは止めない）
+    fn autosave(&self) {
+        let (Some(manager), Some(root)) = (&self.autosave, &self.project_root) else {
+            return;
+        };
+        if !self.conversation.messages().iter().any(|m| m.role == Role::User) {
+            return;
+        }
+        if let Err(e) = manager.save_with_metadata(&autosave_name(root), &self.conversation, self.session_metadata()) {
+            tracing::warn!("Failed to autosave conversation: {:#}", e);
+        }
+    }
+
     /// 会話履歴の最大メッセージ数を更新
     pub fn set_max_messages(&mut self, max_messages: usize) {
         self.max_messages = max_messages;
@@ -261,112 +885,155 @@ To use a tool, output a JSON block like this:
         self.system_extra = extra;
     }
 
+    /// プロンプトデバッグ出力を設定
+    pub fn set_prompt_debugger(&mut self, debugger: Option<PromptDebugger>) {
+        self.prompt_debugger = debugger;
+    }
+
+    /// 確認が必要なツールを拒否するか（非対話モードで `--yes` がないとき）
+    pub fn set_deny_confirmations(&mut self, deny: bool) {
+        self.deny_confirmations = deny;
+    }
+
+    /// 副作用のあるツールを実行前に確認する（ツール自身の確認と同じものを渡し、許可リストを共有する）
+    pub fn set_confirmation(&mut self, confirmation: Arc<ToolConfirmation>) {
+        self.session_hub.replace(Arc::clone(&confirmation) as Arc<dyn SessionResettable>);
+        self.confirmation = Some(confirmation);
+    }
+
+    /// ツール結果の切り詰め設定（read_tool_output と共有する）
+    pub fn set_tool_output(&mut self, tool_output: Arc<ToolOutputManager>) {
+        self.session_hub.replace(Arc::clone(&tool_output) as Arc<dyn SessionResettable>);
+        self.tool_output = tool_output;
+    }
+
+    /// エージェントの外で持つ状態をセッション遷移でリセットする（同じコンポーネントは置き換える）
+    pub fn register_session_state(&mut self, state: Arc<dyn SessionResettable>) {
+        self.session_hub.replace(state);
+    }
+
+    /// ドライランの状態を共有する（CLI の /dryrun と同じものを渡す）
+    pub fn set_dry_run(&mut self, dry_run: Arc<DryRun>) {
+        self.dry_run = dry_run;
+    }
+
+    /// プロジェクトルート（load_context で設定）
+    pub fn project_root(&self) -> Option<&std::path::Path> {
+        self.project_root.as_deref()
+    }
+
+    pub fn dry_run(&self) -> &Arc<DryRun> {
+        &self.dry_run
+    }
+
+    /// 環境フィンガープリントのプローブを設定
+    pub fn set_env_prober(&mut self, prober: Option<Arc<EnvProber>>) {
+        if let Some(prober) = &prober {
+            self.session_hub.replace(Arc::clone(prober) as Arc<dyn SessionResettable>);
+        }
+        self.env_prober = prober;
+    }
+
+    /// セッション遷移を各コンポーネントに伝播
+    pub fn reset_session(&self, transition: SessionTransition) -> Vec<(ResetTarget, ResetAction)> {
+        self.session_hub.emit(transition)
+    }
+
+    /// 現在までに取得した環境フィンガープリント
+    pub fn env_fingerprint(&self) -> Option<EnvFingerprint> {
+        self.env_prober.as_ref().map(|p| p.snapshot())
+    }
+
+    /// 使用ツールのバージョンをバックグラウンドで取得
+    fn observe_tool_call(&self, tool_name: &str, params: &serde_json::Value) {
+        if let Some(prober) = &self.env_prober {
+            prober.observe_tool(tool_name, params);
+        }
+    }
+
+    /// デバッグ出力が有効ならプロンプト/レスポンスを保存してパスを表示
+    fn dump_debug(&self, kind: &str, content: &str) {
+        if let Some(debugger) = &self.prompt_debugger {
+            match debugger.dump(kind, content) {
+                Ok(path) => crate::cli::output::print_debug(&format!("{} written to {}", kind, path.display())),
+                Err(e) => tracing::warn!("Failed to write debug {}: {}", kind, e),
+            }
+        }
+    }
+
     /// モデルを切り替え
     pub fn set_model(&mut self, model: impl Into<String>) {
-        self.llm.set_model(model);
+        let model = model.into();
+        if model != self.llm.model() {
+            self.llm.set_model(model);
+            self.session_hub.emit(SessionTransition::ModelChange);
+        }
+    }
+
+    /// ターンの進行（生成の開始、ツールの実行）の通知先を設定
+    pub fn set_event_sender(&mut self, tx: Option<UnboundedSender<AgentEvent>>) {
+        self.events = tx;
+    }
+
+    fn emit(&self, event: AgentEvent) {
+        if let Some(tx) = &self.events {
+            let _ = tx.send(event);
+        }
+    }
+
+    /// LLMリクエストのリトライ状況の通知先を設定
+    pub fn set_retry_status_sender(&mut self, tx: Option<UnboundedSender<RetryEvent>>) {
+        self.llm.set_status_sender(tx);
     }
 
     /// ストリーミングでユーザー入力を処理
     ///
-    /// トークンを受信するたびにリアルタイムで出力する
-    pub async fn process_streaming(&mut self, input: &str) -> Result<String> {
-        self.conversation.add_user(input);
+    /// トークンを受信するたびにリアルタイムで出力する。ツールの実行以降は `process_detailed_with_cancel` と同じ。
+    /// トークンがキャンセルされた場合はストリームを破棄し、部分応答を返す
+    pub async fn process_streaming(&mut self, input: &str, cancel: &CancellationToken) -> Result<TurnRecord> {
+        self.process_turn(input, cancel, true).await
+    }
 
-        // LLMにストリーミングリクエストを送信
-        let prompt = self.conversation.to_prompt();
-        let mut stream = self.llm.generate_streaming(&prompt, None).await?;
+    /// ストリーミングで生成し、受け取ったトークンをそのまま表示する
+    async fn generate_streaming(&self, prompt: &str, cancel: &CancellationToken) -> Result<Generated> {
+        let mut stream = tokio::select! {
+            _ = cancel.cancelled() => return Ok(Generated::Interrupted(String::new())),
+            stream = self.llm.generate_streaming(prompt, None) => stream?,
+        };
 
-        // ストリーミングライターを初期化
         let mut writer = StreamingWriter::new();
+        writer.set_note(self.llm.last_ctx_decision().and_then(|d| d.note()));
         writer.start(None);
 
-        // ストリーミングで受信
+        // 思考部分は表示せず、目印に置き換える（蓄積する応答はそのまま）
+        let mut filter = OutputPostProcessor::stream_filter().with_indicator(crate::cli::color::enabled());
         let mut last_stats: Option<crate::llm::StreamStats> = None;
-
-        while let Some(chunk) = stream.next().await {
-            // テキストを即座に出力
-            writer.write(&chunk.text);
-
-            // 統計情報を保存
+        while let Some(chunk) = stream.next_or_cancel(cancel).await {
+            let visible = filter.feed(&chunk.text);
+            if !visible.is_empty() {
+                writer.write(&visible);
+            }
             if chunk.done {
                 last_stats = chunk.stats;
             }
         }
+        let rest = filter.finish();
+        if !rest.is_empty() {
+            writer.write(&rest);
+        }
 
         // 統計情報付きで終了（利用可能な場合）
-        if let Some(stats) = last_stats {
-            writer.finish_with_stats(stats.tokens_per_second, stats.eval_count);
-        } else {
-            writer.finish();
+        match &last_stats {
+            Some(stats) => writer.finish_with_stats(stats.tokens_per_second, stats.eval_count, stats.prompt_eval_count),
+            None => writer.finish(),
         }
 
-        // 累積されたテキストを取得
         let response = stream.accumulated().to_string();
-
-        // ツール呼び出しをパース
-        let tool_calls = ToolCallParser::parse(&response)?;
-
-        if tool_calls.is_empty() {
-            // ツール呼び出しなし - テキスト応答
-            self.conversation.add_assistant(&response);
-            return Ok(response);
-        }
-
-        // ツールを実行
-        let mut full_response = String::new();
-        let (text_part, _) = ToolCallParser::split_response(&response);
-        if !text_part.is_empty() {
-            full_response.push_str(&text_part);
-            full_response.push_str("\n\n");
-        }
-
-        for call in tool_calls {
-            // モード制限をチェック
-            if !self.mode.is_tool_allowed(&call.tool).await {
-                let error_msg = format!(
-                    "Tool '{}' is not allowed in {} mode",
-                    call.tool,
-                    self.mode.current().await
-                );
-                self.conversation.add_tool_result(&call.tool, &error_msg);
-                full_response.push_str(&format!("[{}] {}\n", call.tool, error_msg));
-                continue;
-            }
-
-            // ツールを実行
-            println!(); // ツール実行前に改行
-            crate::cli::output::print_tool(&call.tool, "executing...");
-
-            if let Some(tool) = self.tools.get(&call.tool) {
-                match tool.execute(call.params).await {
-                    Ok(result) => {
-                        let output = if result.success {
-                            result.output
-                        } else {
-                            result.error.unwrap_or_else(|| "Unknown error".to_string())
-                        };
-                        self.conversation.add_tool_result(&call.tool, &output);
-                        full_response.push_str(&format!("[{}]\n{}\n\n", call.tool, output));
-                        // ツール結果を表示
-                        crate::cli::output::print_success(&format!("[{}] completed", call.tool));
-                    }
-                    Err(e) => {
-                        let error = format!("Error: {}", e);
-                        self.conversation.add_tool_result(&call.tool, &error);
-                        full_response.push_str(&format!("[{}] {}\n\n", call.tool, error));
-                        crate::cli::output::print_error(&format!("[{}] {}", call.tool, error));
-                    }
-                }
-            } else {
-                let error = format!("Unknown tool: {}", call.tool);
-                self.conversation.add_tool_result(&call.tool, &error);
-                full_response.push_str(&format!("{}\n\n", error));
-                crate::cli::output::print_error(&error);
-            }
+        if cancel.is_cancelled() {
+            Ok(Generated::Interrupted(response))
+        } else {
+            Ok(Generated::Complete(response, last_stats))
         }
-
-        self.conversation.add_assistant(&full_response);
-        Ok(full_response)
     }
 
     /// ストリーミングでユーザー入力を処理（コールバック版）
@@ -380,53 +1047,66 @@ To use a tool, output a JSON block like this:
     where
         F: FnMut(&str),
     {
-        self.conversation.add_user(input);
+        self.add_user_input(input).await;
 
         // LLMにストリーミングリクエストを送信
         let prompt = self.conversation.to_prompt();
+        self.dump_debug("prompt", &prompt);
         let mut stream = self.llm.generate_streaming(&prompt, None).await?;
 
-        // コールバック付きで処理
+        // コールバック付きで処理（思考部分は渡さない）
+        let mut filter = OutputPostProcessor::stream_filter();
         while let Some(chunk) = stream.next().await {
-            on_token(&chunk.text);
+            let visible = filter.feed(&chunk.text);
+            if !visible.is_empty() {
+                on_token(&visible);
+            }
+        }
+        let rest = filter.finish();
+        if !rest.is_empty() {
+            on_token(&rest);
         }
 
         // 累積されたテキストを取得
         let response = stream.accumulated().to_string();
+        self.dump_debug("response", &response);
 
         // ツール呼び出しをパース（ストリーミング後に処理）
-        let tool_calls = ToolCallParser::parse(&response)?;
+        let tool_calls = ToolCallParser::parse_with(&response, self.tool_call_format)?;
 
         if tool_calls.is_empty() {
-            self.conversation.add_assistant(&response);
+            self.conversation.add_assistant(self.recorded_text(&response));
+            self.tool_stats.record_turn(None);
             return Ok(response);
         }
 
         // ツールを実行（非ストリーミング部分）
         let mut full_response = response.clone();
+        let text_len = full_response.len();
 
         for call in tool_calls {
-            if !self.mode.is_tool_allowed(&call.tool).await {
-                let error_msg = format!(
-                    "Tool '{}' is not allowed in {} mode",
-                    call.tool,
-                    self.mode.current().await
-                );
+            let denial = match self.tool_denial(&call.tool, &call.params).await {
+                Some(error_msg) => Some(error_msg),
+                None => self.confirmation_denial(&call.tool, &call.params).await,
+            };
+            if let Some(error_msg) = denial {
                 self.conversation.add_tool_result(&call.tool, &error_msg);
                 full_response.push_str(&format!("\n[{}] {}", call.tool, error_msg));
                 continue;
             }
 
+            self.observe_tool_call(&call.tool, &call.params);
             if let Some(tool) = self.tools.get(&call.tool) {
-                match tool.execute(call.params).await {
-                    Ok(result) => {
+                match self.execute_tool(&call.tool, tool.as_ref(), call.params.clone()).await {
+                    Ok(mut result) => {
+                        let data = result.data.take();
                         let output = if result.success {
                             result.output
                         } else {
                             result.error.unwrap_or_else(|| "Unknown error".to_string())
                         };
-                        self.conversation.add_tool_result(&call.tool, &output);
-                        full_response.push_str(&format!("\n[{}]\n{}", call.tool, output));
+                        let (id, shown) = self.record_tool_result(&call.tool, &call.params, &output, data);
+                        full_response.push_str(&format!("\n[{} {}]\n{}", call.tool, id, shown));
                     }
                     Err(e) => {
                         let error = format!("Error: {}", e);
@@ -441,12 +1121,605 @@ To use a tool, output a JSON block like this:
             }
         }
 
-        self.conversation.add_assistant(&full_response);
+        let recorded = format!("{}{}", self.recorded_text(&response), &full_response[text_len..]);
+        self.conversation.add_assistant(&recorded);
+        self.tool_stats.record_turn(None);
         Ok(full_response)
     }
 
     /// LLMクライアントへの参照を取得
-    pub fn llm(&self) -> &OllamaClient {
-        &self.llm
+    pub fn llm(&self) -> &dyn LlmBackend {
+        self.llm.as_ref()
+    }
+
+    /// モデルを読み込んでおく（起動時と /warm、読み込みを制御できないバックエンドでは false）
+    pub async fn warm_up(&self) -> Result<bool> {
+        self.llm.warm_up().await
+    }
+
+    /// 生成オプションを1項目変更（/set、次のリクエストから有効）
+    pub fn set_generation_option(&mut self, key: &str, value: &str) -> std::result::Result<(), String> {
+        self.llm.options_mut().set(key, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::conversation::Message;
+    use crate::agent::mode::Mode;
+    use crate::llm::mock_server::{http_response, spawn_mock_handler};
+    use crate::tools::bash::BashTool;
+    use crate::tools::file::ReadTool;
+    use std::collections::BTreeMap;
+
+    #[tokio::test]
+    async fn test_custom_mode_blocks_disallowed_tool() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = spawn_mock_handler(|_| {
+            let body = serde_json::json!({
+                "model": "mock",
+                "response": "```json\n{\"tool\": \"bash\", \"params\": {\"command\": \"touch created.txt\"}}\n```",
+                "done": true
+            });
+            http_response("200 OK", &[], &body.to_string())
+        })
+        .await;
+
+        let mut tools = ToolRegistry::new();
+        tools.register(Arc::new(ReadTool::new()));
+        tools.register(Arc::new(BashTool::with_timeout(10, dir.path())));
+        let modes = BTreeMap::from([("review".to_string(), vec!["read".to_string()])]);
+        let mode = ModeManager::new(Mode::Custom("review".to_string()))
+            .with_allowlists(&modes)
+            .with_tool_effects(tools.effects());
+        let config = AgentConfig {
+            ollama_url: url,
+            model: "mock".to_string(),
+            ..AgentConfig::default()
+        };
+        let mut agent = Agent::new(config, tools, Arc::new(SkillRegistry::new()), mode);
+
+        let response = agent.process("create a file").await.unwrap();
+        assert!(response.contains("not in the allowed tools of review mode"), "{}", response);
+        assert!(!dir.path().join("created.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_disabled_tool_is_refused_with_config_message() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = spawn_mock_handler(|_| {
+            let body = serde_json::json!({
+                "model": "mock",
+                "response": "```json\n{\"tool\": \"bash\", \"params\": {\"command\": \"touch created.txt\"}}\n```",
+                "done": true
+            });
+            http_response("200 OK", &[], &body.to_string())
+        })
+        .await;
+
+        let mut tools = ToolRegistry::new().with_disabled(["bash"]);
+        tools.register(Arc::new(ReadTool::new()));
+        tools.register(Arc::new(BashTool::with_timeout(10, dir.path())));
+        let mode = ModeManager::new(Mode::Execute).with_tool_effects(tools.effects());
+        let config = AgentConfig {
+            ollama_url: url,
+            model: "mock".to_string(),
+            ..AgentConfig::default()
+        };
+        let mut agent = Agent::new(config, tools, Arc::new(SkillRegistry::new()), mode);
+
+        let response = agent.process("create a file").await.unwrap();
+        assert!(response.contains("disabled by configuration"), "{}", response);
+        assert!(!response.contains("Unknown tool"), "{}", response);
+        assert!(!dir.path().join("created.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_file_path_in_a_labelled_root_adds_directory_guidance() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path();
+        std::fs::create_dir_all(project.join("web/src")).unwrap();
+        std::fs::create_dir_all(project.join("api")).unwrap();
+        std::fs::write(project.join("web/agent.md"), "web rules: use tabs").unwrap();
+        std::fs::write(project.join("web/src/lib.rs"), "fn main() {}\n").unwrap();
+        let url = spawn_mock_handler(|_| {
+            let body = serde_json::json!({
+                "model": "mock",
+                "response": "```json\n{\"tool\": \"read\", \"params\": {\"file_path\": \"web:src/lib.rs\"}}\n```",
+                "done": true
+            });
+            http_response("200 OK", &[], &body.to_string())
+        })
+        .await;
+
+        let workspace = Arc::new(Workspace::from_paths(&[project.join("api"), project.join("web")]).unwrap());
+        let mut tools = ToolRegistry::new();
+        tools.register(Arc::new(ReadTool::new().with_workspace(Arc::clone(&workspace))));
+        let mode = ModeManager::new(Mode::Execute).with_tool_effects(tools.effects());
+        let config = AgentConfig {
+            ollama_url: url,
+            model: "mock".to_string(),
+            ..AgentConfig::default()
+        };
+        let mut agent = Agent::new(config, tools, Arc::new(SkillRegistry::new()), mode);
+        agent.load_context(project).await.unwrap();
+        agent.set_workspace(Some(workspace));
+
+        // read の file_path（ラベル付き）で触れたディレクトリの agent.md が結果に添えられる
+        let response = agent.process("look at the web crate").await.unwrap();
+        assert!(response.contains("## Directory instructions (web/agent.md)\n\nweb rules: use tabs"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn test_plan_mode_allows_list_only_git_calls() {
+        use crate::tools::git::GitStashTool;
+
+        let dir = tempfile::tempdir().unwrap();
+        assert!(std::process::Command::new("git")
+            .args(["init", "-q"])
+            .current_dir(dir.path())
+            .status()
+            .unwrap()
+            .success());
+        let url = spawn_mock_handler(|_| {
+            let body = serde_json::json!({
+                "model": "mock",
+                "response": "```json\n{\"tool\": \"git_stash\", \"params\": {\"action\": \"list\"}}\n```\n```json\n{\"tool\": \"git_stash\", \"params\": {\"action\": \"pop\"}}\n```",
+                "done": true
+            });
+            http_response("200 OK", &[], &body.to_string())
+        })
+        .await;
+
+        let mut tools = ToolRegistry::new();
+        tools.register(Arc::new(GitStashTool::new().with_root(dir.path())));
+        let mode = ModeManager::new(Mode::Plan).with_tool_effects(tools.effects());
+        let config = AgentConfig {
+            ollama_url: url,
+            model: "mock".to_string(),
+            ..AgentConfig::default()
+        };
+        let mut agent = Agent::new(config, tools, Arc::new(SkillRegistry::new()), mode);
+
+        let response = agent.process("check the stashes").await.unwrap();
+        assert!(response.contains("No stashes"), "{}", response);
+        assert!(response.contains("not allowed in plan mode"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn test_skill_allowed_tools_block_other_tools() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = spawn_mock_handler(|_| {
+            let body = serde_json::json!({
+                "model": "mock",
+                "response": "```json\n{\"tool\": \"bash\", \"params\": {\"command\": \"touch created.txt\"}}\n```",
+                "done": true
+            });
+            http_response("200 OK", &[], &body.to_string())
+        })
+        .await;
+
+        let mut tools = ToolRegistry::new();
+        tools.register(Arc::new(ReadTool::new()));
+        tools.register(Arc::new(BashTool::with_timeout(10, dir.path())));
+        let mode = ModeManager::new(Mode::Execute).with_tool_effects(tools.effects());
+        let config = AgentConfig {
+            ollama_url: url,
+            model: "mock".to_string(),
+            ..AgentConfig::default()
+        };
+        let mut agent = Agent::new(config, tools, Arc::new(SkillRegistry::new()), mode.clone());
+
+        let allowed = vec!["read".to_string(), "grep".to_string()];
+        mode.restrict_to_skill("review", Some(&allowed)).await;
+        let response = agent.process("review the code").await.unwrap();
+        assert!(response.contains("not in the allowed tools of skill 'review'"), "{}", response);
+        assert!(!dir.path().join("created.txt").exists());
+
+        // スキルが終われば元に戻る
+        mode.clear_skill_restriction().await;
+        agent.process("now run it").await.unwrap();
+        assert!(dir.path().join("created.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_turn_emits_progress_events_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "hello\n").unwrap();
+        let path = dir.path().join("a.txt").display().to_string();
+        let url = spawn_mock_handler(move |_| {
+            let call = serde_json::json!({"tool": "read", "params": {"file_path": path}});
+            let missing = serde_json::json!({"tool": "nope", "params": {}});
+            let body = serde_json::json!({
+                "model": "mock",
+                "response": format!("```json\n{}\n```\n```json\n{}\n```", call, missing),
+                "done": true
+            });
+            http_response("200 OK", &[], &body.to_string())
+        })
+        .await;
+
+        let mut tools = ToolRegistry::new();
+        tools.register(Arc::new(ReadTool::new()));
+        let mode = ModeManager::new(Mode::Execute).with_tool_effects(tools.effects());
+        let config = AgentConfig {
+            ollama_url: url,
+            model: "mock".to_string(),
+            ..AgentConfig::default()
+        };
+        let mut agent = Agent::new(config, tools, Arc::new(SkillRegistry::new()), mode);
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        agent.set_event_sender(Some(tx));
+
+        agent.process("read it").await.unwrap();
+        let mut events = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            events.push(event);
+        }
+        assert_eq!(
+            events,
+            vec![
+                AgentEvent::GenerationStarted,
+                AgentEvent::ToolStarted("read".to_string()),
+                AgentEvent::ToolFinished("read".to_string(), true),
+                AgentEvent::ToolStarted("nope".to_string()),
+                AgentEvent::ToolFinished("nope".to_string(), false),
+                AgentEvent::Done,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_streaming_turn_matches_blocking_turn() {
+        let text = "Let me check.\n```json\n{\"tool\": \"nope\", \"params\": {}}\n```";
+        let url = spawn_mock_handler(move |request| {
+            if request.contains("\"stream\":true") {
+                // 数文字ずつのチャンクに分けて返す
+                let chars: Vec<char> = text.chars().collect();
+                let mut body: String = chars
+                    .chunks(7)
+                    .map(|c| serde_json::json!({"model": "mock", "response": c.iter().collect::<String>(), "done": false}).to_string() + "\n")
+                    .collect();
+                body.push_str(&serde_json::json!({"model": "mock", "response": "", "done": true, "eval_count": 9}).to_string());
+                body.push('\n');
+                http_response("200 OK", &[], &body)
+            } else {
+                let body = serde_json::json!({"model": "mock", "response": text, "done": true});
+                http_response("200 OK", &[], &body.to_string())
+            }
+        })
+        .await;
+        let agent = || {
+            let config = AgentConfig {
+                ollama_url: url.clone(),
+                model: "mock".to_string(),
+                ..AgentConfig::default()
+            };
+            Agent::new(config, ToolRegistry::new(), Arc::new(SkillRegistry::new()), ModeManager::new(Mode::Execute))
+        };
+
+        let mut blocking_agent = agent();
+        let blocking = blocking_agent.process_detailed("check").await.unwrap();
+        let mut streaming_agent = agent();
+        let streamed = streaming_agent.process_streaming("check", &CancellationToken::new()).await.unwrap();
+
+        assert_eq!(streamed.response, blocking.response);
+        assert_eq!(streamed.assistant, "Let me check.");
+        assert_eq!(streamed.tool_calls.len(), 1);
+        assert_eq!(streamed.stats.map(|s| s.eval_count), Some(9));
+        assert_eq!(streaming_agent.conversation().len(), blocking_agent.conversation().len());
+    }
+
+    #[tokio::test]
+    async fn test_thoughts_are_dropped_from_history_unless_kept() {
+        let text = "<think>\nWhat does the user want?\n</think>\n\nHello!";
+        let url = spawn_mock_handler(move |_| {
+            let body = serde_json::json!({"model": "mock", "response": text, "done": true});
+            http_response("200 OK", &[], &body.to_string())
+        })
+        .await;
+        for keep_thoughts in [false, true] {
+            let config = AgentConfig {
+                ollama_url: url.clone(),
+                model: "mock".to_string(),
+                keep_thoughts,
+                ..AgentConfig::default()
+            };
+            let mut agent = Agent::new(config, ToolRegistry::new(), Arc::new(SkillRegistry::new()), ModeManager::new(Mode::Execute));
+            let record = agent.process_detailed("hi").await.unwrap();
+
+            assert_eq!(record.response, text);
+            let stored = &agent.conversation().messages().last().unwrap().content;
+            assert_eq!(stored.contains("<think>"), keep_thoughts);
+            assert!(stored.ends_with("Hello!"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deny_confirmations_blocks_side_effects() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = spawn_mock_handler(|_| {
+            let body = serde_json::json!({
+                "model": "mock",
+                "response": "```json\n{\"tool\": \"bash\", \"params\": {\"command\": \"touch created.txt\"}}\n```",
+                "done": true
+            });
+            http_response("200 OK", &[], &body.to_string())
+        })
+        .await;
+
+        let mut tools = ToolRegistry::new();
+        tools.register(Arc::new(BashTool::with_timeout(10, dir.path())));
+        let mode = ModeManager::new(Mode::Execute).with_tool_effects(tools.effects());
+        let config = AgentConfig {
+            ollama_url: url,
+            model: "mock".to_string(),
+            ..AgentConfig::default()
+        };
+        let mut agent = Agent::new(config, tools, Arc::new(SkillRegistry::new()), mode);
+        agent.set_deny_confirmations(true);
+
+        let response = agent.process("create a file").await.unwrap();
+        assert!(response.contains("requires confirmation"), "{}", response);
+        assert!(!dir.path().join("created.txt").exists());
+
+        agent.set_deny_confirmations(false);
+        agent.process("create a file").await.unwrap();
+        assert!(dir.path().join("created.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_confirmation_denial_is_returned_to_model() {
+        use crate::agent::confirmation::{AutoDeny, ConfirmationPolicy};
+        use crate::cli::ConfirmResult;
+
+        /// 「同じ呼び出しは常に許可」と答え、尋ねられた回数を数える
+        struct AlwaysExact(std::sync::Mutex<usize>);
+        impl ConfirmationPolicy for AlwaysExact {
+            fn decide(&self, _tool: &str, _details: &str) -> ConfirmResult {
+                *self.0.lock().unwrap() += 1;
+                ConfirmResult::AlwaysAllowExact
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let url = spawn_mock_handler(|_| {
+            let body = serde_json::json!({
+                "model": "mock",
+                "response": "```json\n{\"tool\": \"bash\", \"params\": {\"command\": \"touch created.txt\"}}\n```",
+                "done": true
+            });
+            http_response("200 OK", &[], &body.to_string())
+        })
+        .await;
+
+        let mut tools = ToolRegistry::new();
+        tools.register(Arc::new(BashTool::with_timeout(10, dir.path())));
+        let mode = ModeManager::new(Mode::Execute).with_tool_effects(tools.effects());
+        let config = AgentConfig {
+            ollama_url: url,
+            model: "mock".to_string(),
+            ..AgentConfig::default()
+        };
+        let mut agent = Agent::new(config, tools, Arc::new(SkillRegistry::new()), mode);
+        agent.set_confirmation(Arc::new(ToolConfirmation::new(Arc::new(AutoDeny))));
+
+        let record = agent.process_detailed("create a file").await.unwrap();
+        assert!(record.response.contains("User denied execution of bash"), "{}", record.response);
+        assert!(!record.tool_calls[0].success);
+        assert!(!dir.path().join("created.txt").exists());
+
+        let policy = Arc::new(AlwaysExact(std::sync::Mutex::new(0)));
+        agent.set_confirmation(Arc::new(ToolConfirmation::new(policy.clone())));
+        agent.process("create a file").await.unwrap();
+        assert!(dir.path().join("created.txt").exists());
+        agent.process("create a file").await.unwrap();
+        assert_eq!(*policy.0.lock().unwrap(), 1);
+
+        // /clear で許可を忘れる
+        agent.reset_session(SessionTransition::Clear);
+        agent.process("create a file").await.unwrap();
+        assert_eq!(*policy.0.lock().unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_long_session_is_compressed() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = spawn_mock_handler(|_| {
+            let body = serde_json::json!({
+                "model": "mock",
+                "response": "Sure. Here is a fairly long answer that keeps the conversation growing over time.",
+                "done": true
+            });
+            http_response("200 OK", &[], &body.to_string())
+        })
+        .await;
+        let config = AgentConfig {
+            ollama_url: url,
+            model: "mock".to_string(),
+            compression: CompressionConfig {
+                threshold: 0.5,
+                max_tokens: 800,
+                preserve_recent: 4,
+                ..CompressionConfig::default()
+            },
+            ..AgentConfig::default()
+        };
+        let tools = ToolRegistry::new();
+        let mode = ModeManager::new(Mode::Execute);
+        let mut agent = Agent::new(config, tools, Arc::new(SkillRegistry::new()), mode);
+        agent.load_context(dir.path()).await.unwrap();
+        let system_prompt = agent.conversation().messages()[0].content.clone();
+
+        let mut notices = Vec::new();
+        for i in 0..30 {
+            agent
+                .process(&format!("Question {}: please explain the next step of the refactoring in detail.", i))
+                .await
+                .unwrap();
+            notices.extend(agent.take_compression_notice());
+        }
+
+        assert!(!notices.is_empty());
+        assert!(notices[0].to_string().starts_with("Context compressed: "));
+        let messages = agent.conversation().messages();
+        assert_eq!(messages[0].content, system_prompt);
+        assert!(messages[1].content.starts_with("[Previous conversation summary"));
+        assert!(messages.len() < 30);
+        // 圧縮後も閾値の範囲内に収まっている
+        assert!(agent.conversation().estimated_tokens() <= 800);
+    }
+
+    #[tokio::test]
+    async fn test_system_prompt_is_byte_identical_across_builds() {
+        let dir = tempfile::tempdir().unwrap();
+        let new_agent = || {
+            let mut tools = ToolRegistry::new();
+            tools.register(Arc::new(ReadTool::new()));
+            tools.register(Arc::new(BashTool::with_timeout(10, dir.path())));
+            let mode = ModeManager::new(Mode::Execute).with_tool_effects(tools.effects());
+            Agent::new(AgentConfig::default(), tools, Arc::new(SkillRegistry::new()), mode)
+        };
+
+        let mut first = new_agent();
+        first.load_context(dir.path()).await.unwrap();
+        let mut second = new_agent();
+        second.load_context(dir.path()).await.unwrap();
+
+        let system = |agent: &Agent| agent.conversation().messages()[0].content.clone();
+        assert_eq!(system(&first), system(&second));
+        assert_eq!(first.static_prefix_len(), system(&first).len());
+        assert!(system(&first).find("## bash").unwrap() < system(&first).find("## read").unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_pinned_result_is_sent_with_next_message_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes.txt");
+        std::fs::write(&file, "pinned content\n").unwrap();
+        let call = format!(
+            "```json\n{{\"tool\": \"read\", \"params\": {{\"file_path\": \"{}\"}}}}\n```",
+            file.display()
+        );
+        let requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = Arc::clone(&requests);
+        let url = spawn_mock_handler(move |request| {
+            let mut seen = seen.lock().unwrap();
+            seen.push(request.to_string());
+            let response = if seen.len() == 1 { call.clone() } else { "ok".to_string() };
+            let body = serde_json::json!({"model": "mock", "response": response, "done": true});
+            http_response("200 OK", &[], &body.to_string())
+        })
+        .await;
+
+        let mut tools = ToolRegistry::new();
+        tools.register(Arc::new(ReadTool::new()));
+        let mode = ModeManager::new(Mode::Execute).with_tool_effects(tools.effects());
+        let config = AgentConfig {
+            ollama_url: url,
+            model: "mock".to_string(),
+            ..AgentConfig::default()
+        };
+        let mut agent = Agent::new(config, tools, Arc::new(SkillRegistry::new()), mode);
+
+        let response = agent.process("read the notes").await.unwrap();
+        assert!(response.contains("[read t1]"), "{}", response);
+        // 構造化された結果は記録にだけ残り、会話にはテキストだけが入る
+        let data = agent.tool_result("t1").unwrap().data.clone().unwrap();
+        assert_eq!(data["total_lines"], serde_json::json!(1));
+        assert!(agent.conversation().messages().iter().all(|m| !m.content.contains("total_lines")));
+
+        let mut pin = agent.resolve_tool_result("t1").await.unwrap();
+        pin.instruction = Some("quote it".to_string());
+        agent.pin_for_next_turn(vec![pin]);
+        agent.process("use it").await.unwrap();
+        agent.process("and again").await.unwrap();
+
+        {
+            let requests = requests.lock().unwrap();
+            assert!(requests[1].contains("pinned_tool_results"));
+            assert!(requests[1].contains("quote it"));
+            // 固定は1ターンだけ
+            assert!(!requests[2].contains("pinned_tool_results"));
+            assert!(requests[2].contains("[Pinned tool results: t1 (read "));
+        }
+
+        // 作業ディレクトリを変えたら前の結果は /use で選べない（モデルの切り替えでは残す）
+        agent.reset_session(SessionTransition::ModelChange);
+        assert!(agent.tool_result("t1").is_some());
+        agent.reset_session(SessionTransition::ChangeDirectory);
+        assert!(agent.tool_result("t1").is_none());
+        assert!(agent.resolve_tool_result("t1").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_autosave_after_turn_and_resume_keeps_current_system_prompt() {
+        let project = tempfile::tempdir().unwrap();
+        let history = tempfile::tempdir().unwrap();
+        let url = spawn_mock_handler(|_| {
+            let body = serde_json::json!({"model": "mock", "response": "hello", "done": true});
+            http_response("200 OK", &[], &body.to_string())
+        })
+        .await;
+        let config = AgentConfig {
+            ollama_url: url,
+            model: "mock".to_string(),
+            ..AgentConfig::default()
+        };
+        let tools = ToolRegistry::new();
+        let mode = ModeManager::new(Mode::Execute).with_tool_effects(tools.effects());
+        let mut agent = Agent::new(config, tools, Arc::new(SkillRegistry::new()), mode);
+        agent.load_context(project.path()).await.unwrap();
+        agent.set_autosave(Some(HistoryManager::with_directory(history.path().to_path_buf()).unwrap()));
+
+        agent.process("hi").await.unwrap();
+
+        let manager = HistoryManager::with_directory(history.path().to_path_buf()).unwrap();
+        let entries = manager.list_for_project(project.path()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, autosave_name(project.path()));
+        let persisted: crate::agent::history::PersistedConversation =
+            serde_json::from_slice(&std::fs::read(&entries[0].path).unwrap()).unwrap();
+        assert_eq!(persisted.metadata.model.as_deref(), Some("mock"));
+        assert_eq!(persisted.metadata.project_path, Some(project.path().display().to_string()));
+        assert!(persisted.metadata.created_at.is_some());
+
+        // 読み込んだ会話の古いシステムプロンプトは現在のものに置き換わる
+        let current = agent.conversation().messages()[0].content.clone();
+        let mut saved = manager.load(&entries[0].name).unwrap();
+        saved.replace_system_prompt("stale prompt");
+        saved.add(Message::system("[Previous conversation summary (2 messages)]"));
+        agent.restore_conversation(saved);
+        let messages = agent.conversation().messages();
+        assert_eq!(messages[0].content, current);
+        assert_eq!(messages.iter().filter(|m| m.role == Role::System).count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_openai_provider_drives_the_turn() {
+        let url = spawn_mock_handler(|request| {
+            assert!(request.starts_with("POST /chat/completions"), "{}", request);
+            let body = serde_json::json!({
+                "choices": [{"message": {"role": "assistant", "content": "Done."}}],
+                "usage": {"prompt_tokens": 40, "completion_tokens": 2}
+            });
+            http_response("200 OK", &[], &body.to_string())
+        })
+        .await;
+        let tools = ToolRegistry::new();
+        let mode = ModeManager::new(Mode::Execute).with_tool_effects(tools.effects());
+        let config = AgentConfig {
+            provider: LlmProvider::OpenAi,
+            ollama_url: url,
+            model: "mock".to_string(),
+            ..AgentConfig::default()
+        };
+        let mut agent = Agent::new(config, tools, Arc::new(SkillRegistry::new()), mode);
+
+        assert_eq!(agent.llm().provider(), "openai");
+        assert!(agent.llm().context_status_provider().is_none());
+        assert_eq!(agent.process("hi").await.unwrap(), "Done.");
+        assert_eq!(agent.llm().token_usage().completion_tokens, 2);
     }
 }
@@ -0,0 +1,206 @@
+//! プロンプトデバッグ出力
+//!
+//! --debug-prompt または LOCAL_CODE_DEBUG_PROMPT が有効な場合、
+//! LLMに送信するプロンプトと生のレスポンスを .local-code/debug/ に保存する
+//! （[storage] encrypt が有効なら暗号化して保存）
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use super::encryption::StorageCipher;
+
+/// デバッグ出力を有効化する環境変数
+pub const DEBUG_PROMPT_ENV: &str = "LOCAL_CODE_DEBUG_PROMPT";
+
+/// 1ファイルあたりの最大バイト数
+const DEFAULT_MAX_FILE_BYTES: usize = 1024 * 1024;
+
+/// 保持する最大ファイル数（古いものから削除）
+const DEFAULT_MAX_FILES: usize = 50;
+
+/// プロンプトデバッガー
+pub struct PromptDebugger {
+    /// 出力ディレクトリ
+    dir: PathBuf,
+    /// 1ファイルあたりの最大バイト数
+    max_file_bytes: usize,
+    /// 保持する最大ファイル数
+    max_files: usize,
+    /// 連番（同一ミリ秒内の衝突回避）
+    sequence: AtomicU64,
+    /// 設定されていれば内容を暗号化して保存
+    cipher: Option<Arc<StorageCipher>>,
+}
+
+impl PromptDebugger {
+    /// 出力ディレクトリを指定して作成
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            max_file_bytes: DEFAULT_MAX_FILE_BYTES,
+            max_files: DEFAULT_MAX_FILES,
+            sequence: AtomicU64::new(0),
+            cipher: None,
+        }
+    }
+
+    /// プロジェクトルート配下の .local-code/debug/ を使用して作成
+    pub fn for_project(project_root: &Path) -> Self {
+        Self::new(project_root.join(".local-code").join("debug"))
+    }
+
+    /// 環境変数でデバッグ出力が有効化されているか
+    pub fn env_enabled() -> bool {
+        std::env::var(DEBUG_PROMPT_ENV)
+            .map(|v| !v.is_empty() && v != "0" && !v.eq_ignore_ascii_case("false"))
+            .unwrap_or(false)
+    }
+
+    /// 1ファイルあたりの最大バイト数を設定
+    pub fn with_max_file_bytes(mut self, max_file_bytes: usize) -> Self {
+        self.max_file_bytes = max_file_bytes;
+        self
+    }
+
+    /// 保持する最大ファイル数を設定
+    pub fn with_max_files(mut self, max_files: usize) -> Self {
+        self.max_files = max_files;
+        self
+    }
+
+    /// 保存内容を暗号化する
+    pub fn with_cipher(mut self, cipher: Arc<StorageCipher>) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    /// 出力ディレクトリを取得
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// 内容を秘匿化・サイズ制限してタイムスタンプ付きファイルに保存
+    ///
+    /// # Arguments
+    /// * `kind` - ファイル種別（"prompt" / "response" 等）
+    /// * `content` - 保存する内容
+    pub fn dump(&self, kind: &str, content: &str) -> Result<PathBuf> {
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create debug directory: {}", self.dir.display()))?;
+
+        let seq = self.sequence.fetch_add(1, Ordering::SeqCst);
+        let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S%.3f");
+        let path = self.dir.join(format!("{}-{:04}-{}.txt", timestamp, seq, kind));
+
+        let redacted = redact_secrets(content);
+        let body = truncate_bytes(&redacted, self.max_file_bytes);
+
+        let bytes = match &self.cipher {
+            Some(cipher) => cipher.encrypt(body.as_bytes())?,
+            None => body.into_bytes(),
+        };
+        std::fs::write(&path, bytes)
+            .with_context(|| format!("Failed to write debug file: {}", path.display()))?;
+
+        if let Err(e) = self.cleanup() {
+            tracing::warn!("Failed to clean up debug directory: {}", e);
+        }
+
+        Ok(path)
+    }
+
+    /// 古いデバッグファイルを削除して max_files 件以内に保つ
+    pub fn cleanup(&self) -> Result<usize> {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("txt"))
+            .collect();
+
+        if files.len() <= self.max_files {
+            return Ok(0);
+        }
+
+        // ファイル名はタイムスタンプ始まりなので名前順 = 作成順
+        files.sort();
+        let excess = files.len() - self.max_files;
+        let mut removed = 0;
+        for path in files.into_iter().take(excess) {
+            if std::fs::remove_file(&path).is_ok() {
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+/// APIキーやトークンらしき値を伏せ字にする
+pub fn redact_secrets(text: &str) -> String {
+    let patterns = [
+        (r"(?i)\b(api[_-]?key|access[_-]?token|secret|password|passwd|token)(\s*[:=]\s*)\S+", "${1}${2}[REDACTED]"),
+        (r"(?i)\b(bearer\s+)[A-Za-z0-9\-._~+/]+=*", "${1}[REDACTED]"),
+        (r"\bsk-[A-Za-z0-9]{16,}", "[REDACTED]"),
+    ];
+
+    let mut result = text.to_string();
+    for (pattern, replacement) in patterns {
+        if let Ok(re) = Regex::new(pattern) {
+            result = re.replace_all(&result, replacement).into_owned();
+        }
+    }
+    result
+}
+
+/// UTF-8境界を保ったままバイト数で切り詰める
+fn truncate_bytes(text: &str, max_bytes: usize) -> String {
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+
+    let mut end = max_bytes;
+    while end > 0 && !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!(
+        "{}\n\n[truncated: {} of {} bytes shown]\n",
+        &text[..end],
+        end,
+        text.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_redact_secrets() {
+        let redacted = redact_secrets("api_key=abc123 and Authorization: Bearer xyz.789");
+        assert!(!redacted.contains("abc123"));
+        assert!(!redacted.contains("xyz.789"));
+        assert!(redacted.contains("api_key=[REDACTED]"));
+    }
+
+    #[test]
+    fn test_dump_truncates_and_cleans_up() {
+        let temp_dir = tempdir().unwrap();
+        let debugger = PromptDebugger::new(temp_dir.path().to_path_buf())
+            .with_max_file_bytes(16)
+            .with_max_files(3);
+
+        let path = debugger.dump("prompt", &"x".repeat(100)).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(written.contains("[truncated: 16 of 100 bytes shown]"));
+
+        for _ in 0..5 {
+            debugger.dump("response", "hello").unwrap();
+        }
+        let count = std::fs::read_dir(temp_dir.path()).unwrap().count();
+        assert_eq!(count, 3);
+    }
+}
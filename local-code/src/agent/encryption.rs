@@ -0,0 +1,337 @@
+//! 会話ファイルの暗号化
+//!
+//! パスフレーズから Argon2id で鍵を導出し、ChaCha20-Poly1305 で暗号化する。
+//!
+//! ファイル形式（整数はリトルエンディアン）:
+//! `MAGIC(6) | version(1) | m_cost(4) | t_cost(4) | p_cost(4) | salt(16) | nonce(12) | ciphertext`
+//!
+//! ヘッダー全体を AAD として認証するため、ヘッダーの改ざんも復号失敗になる
+
+use anyhow::{Context, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use zeroize::Zeroizing;
+
+/// 暗号化ファイルの先頭バイト列
+pub const MAGIC: &[u8; 6] = b"LCENC\0";
+
+/// ヘッダーのフォーマットバージョン
+pub const FORMAT_VERSION: u8 = 1;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+const HEADER_LEN: usize = MAGIC.len() + 1 + 12 + SALT_LEN + NONCE_LEN;
+
+/// 導出済みの鍵（salt と KDF パラメータごと）
+type KeyCache = HashMap<([u8; SALT_LEN], KdfParams), Zeroizing<[u8; KEY_LEN]>>;
+
+/// 暗号化されたデータか（マジックバイトで判定）
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// 復号エラー
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecryptError {
+    /// パスフレーズが違う、またはファイルが改ざん・破損している
+    WrongPassphrase,
+    /// 未対応のフォーマットバージョン
+    UnsupportedVersion(u8),
+    /// ヘッダーが不完全
+    Truncated,
+}
+
+impl fmt::Display for DecryptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecryptError::WrongPassphrase => {
+                write!(f, "wrong passphrase (or the file is corrupted)")
+            }
+            DecryptError::UnsupportedVersion(v) => {
+                write!(f, "unsupported encrypted file version {}", v)
+            }
+            DecryptError::Truncated => write!(f, "encrypted file header is truncated"),
+        }
+    }
+}
+
+impl std::error::Error for DecryptError {}
+
+/// Argon2id のパラメータ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KdfParams {
+    /// メモリコスト（KiB）
+    pub m_cost: u32,
+    /// 反復回数
+    pub t_cost: u32,
+    /// 並列度
+    pub p_cost: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// パスフレーズの取得方法
+#[derive(Clone)]
+pub enum PassphraseSource {
+    /// 初回に問い合わせる（結果はセッション中メモリに保持）
+    Prompt(Arc<dyn Fn() -> Result<String> + Send + Sync>),
+    /// ファイルから読む（前後の空白は除く）
+    File(PathBuf),
+    /// 環境変数から読む
+    Env(String),
+    /// 直接指定
+    Value(String),
+}
+
+impl fmt::Debug for PassphraseSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PassphraseSource::Prompt(_) => write!(f, "Prompt"),
+            PassphraseSource::File(path) => write!(f, "File({})", path.display()),
+            PassphraseSource::Env(var) => write!(f, "Env({})", var),
+            PassphraseSource::Value(_) => write!(f, "Value(..)"),
+        }
+    }
+}
+
+impl PassphraseSource {
+    fn resolve(&self) -> Result<String> {
+        let passphrase = match self {
+            PassphraseSource::Prompt(prompt) => prompt()?,
+            PassphraseSource::File(path) => std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read passphrase file: {}", path.display()))?
+                .trim()
+                .to_string(),
+            PassphraseSource::Env(var) => std::env::var(var)
+                .with_context(|| format!("Passphrase environment variable {} is not set", var))?,
+            PassphraseSource::Value(value) => value.clone(),
+        };
+        if passphrase.is_empty() {
+            anyhow::bail!("Passphrase is empty");
+        }
+        Ok(passphrase)
+    }
+}
+
+/// ファイル内容の暗号化・復号
+///
+/// 導出した鍵は (salt, パラメータ) ごとにキャッシュし、
+/// 同一セッションで保存するファイルは同じ salt を使う
+pub struct StorageCipher {
+    source: PassphraseSource,
+    params: KdfParams,
+    session_salt: [u8; SALT_LEN],
+    passphrase: Mutex<Option<Zeroizing<String>>>,
+    keys: Mutex<KeyCache>,
+}
+
+impl StorageCipher {
+    /// パスフレーズの取得方法を指定して作成
+    pub fn new(source: PassphraseSource) -> Self {
+        let mut session_salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut session_salt);
+        Self {
+            source,
+            params: KdfParams::default(),
+            session_salt,
+            passphrase: Mutex::new(None),
+            keys: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 暗号化時の KDF パラメータを設定
+    pub fn with_kdf_params(mut self, params: KdfParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// 暗号化してヘッダー付きのバイト列を返す
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let key = self.key_for(self.session_salt, self.params)?;
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+
+        let mut out = Vec::with_capacity(HEADER_LEN + plaintext.len() + 16);
+        out.extend_from_slice(MAGIC);
+        out.push(FORMAT_VERSION);
+        out.extend_from_slice(&self.params.m_cost.to_le_bytes());
+        out.extend_from_slice(&self.params.t_cost.to_le_bytes());
+        out.extend_from_slice(&self.params.p_cost.to_le_bytes());
+        out.extend_from_slice(&self.session_salt);
+        out.extend_from_slice(&nonce);
+
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(key.as_slice()));
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad: &out })
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt data"))?;
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+
+    /// ヘッダー付きのバイト列を復号
+    ///
+    /// パスフレーズ違いは [`DecryptError::WrongPassphrase`] になる
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        if !is_encrypted(data) || data.len() < HEADER_LEN {
+            return Err(DecryptError::Truncated.into());
+        }
+        let version = data[MAGIC.len()];
+        if version != FORMAT_VERSION {
+            return Err(DecryptError::UnsupportedVersion(version).into());
+        }
+
+        let mut pos = MAGIC.len() + 1;
+        let mut read_u32 = || {
+            let value = u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap());
+            pos += 4;
+            value
+        };
+        let params = KdfParams {
+            m_cost: read_u32(),
+            t_cost: read_u32(),
+            p_cost: read_u32(),
+        };
+        let mut salt = [0u8; SALT_LEN];
+        salt.copy_from_slice(&data[pos..pos + SALT_LEN]);
+        pos += SALT_LEN;
+        let nonce = &data[pos..pos + NONCE_LEN];
+        pos += NONCE_LEN;
+
+        let key = self.key_for(salt, params)?;
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(key.as_slice()));
+        match cipher.decrypt(Nonce::from_slice(nonce), Payload { msg: &data[pos..], aad: &data[..pos] }) {
+            Ok(plaintext) => Ok(plaintext),
+            Err(_) => {
+                // 入力ミスの可能性があるので、問い合わせ式なら次回聞き直す
+                if matches!(self.source, PassphraseSource::Prompt(_)) {
+                    self.forget();
+                }
+                Err(DecryptError::WrongPassphrase.into())
+            }
+        }
+    }
+
+    /// 保持しているパスフレーズと鍵を破棄
+    pub fn forget(&self) {
+        *self.passphrase.lock().unwrap() = None;
+        self.keys.lock().unwrap().clear();
+    }
+
+    fn key_for(&self, salt: [u8; SALT_LEN], params: KdfParams) -> Result<Zeroizing<[u8; KEY_LEN]>> {
+        if let Some(key) = self.keys.lock().unwrap().get(&(salt, params)) {
+            return Ok(key.clone());
+        }
+
+        let passphrase = self.passphrase()?;
+        let argon_params = Params::new(params.m_cost, params.t_cost, params.p_cost, Some(KEY_LEN))
+            .map_err(|e| anyhow::anyhow!("Invalid key derivation parameters: {}", e))?;
+        let mut key = Zeroizing::new([0u8; KEY_LEN]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, argon_params)
+            .hash_password_into(passphrase.as_bytes(), &salt, key.as_mut())
+            .map_err(|e| anyhow::anyhow!("Failed to derive key: {}", e))?;
+
+        self.keys.lock().unwrap().insert((salt, params), key.clone());
+        Ok(key)
+    }
+
+    fn passphrase(&self) -> Result<Zeroizing<String>> {
+        let mut cached = self.passphrase.lock().unwrap();
+        if let Some(passphrase) = cached.as_ref() {
+            return Ok(passphrase.clone());
+        }
+        let passphrase = Zeroizing::new(self.source.resolve()?);
+        *cached = Some(passphrase.clone());
+        Ok(passphrase)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// テスト用の軽量パラメータ
+    fn fast_params() -> KdfParams {
+        KdfParams { m_cost: 64, t_cost: 1, p_cost: 1 }
+    }
+
+    fn cipher(passphrase: &str) -> StorageCipher {
+        StorageCipher::new(PassphraseSource::Value(passphrase.to_string())).with_kdf_params(fast_params())
+    }
+
+    #[test]
+    fn test_round_trip_and_header() {
+        let data = cipher("correct horse").encrypt(b"secret transcript").unwrap();
+        assert!(is_encrypted(&data));
+        assert_eq!(data[MAGIC.len()], FORMAT_VERSION);
+        assert!(!data.windows(6).any(|w| w == b"secret"));
+
+        // 別インスタンス（別の session salt）でも同じパスフレーズなら復号できる
+        let plain = cipher("correct horse").decrypt(&data).unwrap();
+        assert_eq!(plain, b"secret transcript");
+    }
+
+    #[test]
+    fn test_wrong_passphrase_is_reported() {
+        let data = cipher("right").encrypt(b"{}").unwrap();
+        let err = cipher("wrong").decrypt(&data).unwrap_err();
+        assert_eq!(err.downcast_ref::<DecryptError>(), Some(&DecryptError::WrongPassphrase));
+    }
+
+    #[test]
+    fn test_tampered_header_fails() {
+        let mut data = cipher("pw").encrypt(b"hello").unwrap();
+        data[MAGIC.len() + 1 + 12] ^= 1; // salt
+        assert!(cipher("pw").decrypt(&data).is_err());
+
+        let mut data = cipher("pw").encrypt(b"hello").unwrap();
+        data[MAGIC.len()] = 9;
+        let err = cipher("pw").decrypt(&data).unwrap_err();
+        assert_eq!(err.downcast_ref::<DecryptError>(), Some(&DecryptError::UnsupportedVersion(9)));
+    }
+
+    #[test]
+    fn test_prompt_is_asked_once_and_again_after_failure() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let source = PassphraseSource::Prompt(Arc::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok("typo".to_string())
+        }));
+        let prompting = StorageCipher::new(source).with_kdf_params(fast_params());
+
+        prompting.encrypt(b"a").unwrap();
+        prompting.encrypt(b"b").unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let other = cipher("real").encrypt(b"c").unwrap();
+        assert!(prompting.decrypt(&other).is_err());
+        prompting.encrypt(b"d").unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_passphrase_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let keyfile = dir.path().join("key");
+        std::fs::write(&keyfile, "from-file\n").unwrap();
+        let from_file = StorageCipher::new(PassphraseSource::File(keyfile)).with_kdf_params(fast_params());
+        let data = from_file.encrypt(b"x").unwrap();
+        assert_eq!(cipher("from-file").decrypt(&data).unwrap(), b"x");
+    }
+}
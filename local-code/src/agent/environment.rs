@@ -0,0 +1,361 @@
+//! 環境フィンガープリント
+//!
+//! セッションで実際に使われたツール（rustc/cargo, node, python3, git 等）の
+//! バージョンを初回使用時にバックグラウンドで取得し、会話メタデータ等に添付する
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsString;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::process::Command;
+use tokio::task::JoinHandle;
+
+use super::session::{ResetAction, ResetTarget, SessionResettable};
+use crate::config::{EnvironmentConfig, ProbeConfig};
+
+/// 取得に失敗したツールの表示値
+const UNAVAILABLE: &str = "unavailable";
+
+/// 環境フィンガープリント
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EnvFingerprint {
+    /// ツール名 → バージョン文字列
+    pub tools: BTreeMap<String, String>,
+    /// 最後に更新した日時（Unix timestamp）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub captured_at: Option<u64>,
+}
+
+impl EnvFingerprint {
+    /// 何も取得していないか
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    /// 1行ずつ "name: version" 形式で整形
+    pub fn to_display(&self) -> String {
+        if self.tools.is_empty() {
+            return "No tool versions captured yet.".to_string();
+        }
+        self.tools
+            .iter()
+            .map(|(name, version)| format!("{}: {}", name, version))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// デフォルトのプローブ一覧
+pub fn default_probes() -> Vec<ProbeConfig> {
+    let probe = |name: &str, command: &str, triggers: &[&str]| ProbeConfig {
+        name: name.to_string(),
+        command: command.to_string(),
+        args: vec!["--version".to_string()],
+        triggers: triggers.iter().map(|s| s.to_string()).collect(),
+    };
+    vec![
+        probe("rustc", "rustc", &["rustc", "cargo"]),
+        probe("cargo", "cargo", &["cargo"]),
+        probe("node", "node", &["node", "npm", "npx"]),
+        probe("python3", "python3", &["python3", "python", "pip", "pip3"]),
+        probe("git", "git", &["git"]),
+    ]
+}
+
+#[derive(Default)]
+struct ProberState {
+    fingerprint: EnvFingerprint,
+    /// 取得済みまたは取得中のプローブ名
+    scheduled: HashSet<String>,
+    /// 実行中のプローブタスク
+    pending: Vec<JoinHandle<()>>,
+}
+
+/// 遅延プローブスケジューラ
+///
+/// ツール呼び出しを観測し、関係するプローブを初回のみバックグラウンドで実行する。
+/// 取得処理はターンをブロックせず、失敗してもツール呼び出しには影響しない
+pub struct EnvProber {
+    probes: Vec<ProbeConfig>,
+    timeout: Duration,
+    /// プローブ実行時のPATH（未指定ならプロセスのPATH）
+    path: Option<OsString>,
+    state: Arc<Mutex<ProberState>>,
+}
+
+impl EnvProber {
+    /// プローブ一覧を指定して作成
+    pub fn new(probes: Vec<ProbeConfig>) -> Self {
+        Self {
+            probes,
+            timeout: Duration::from_secs(2),
+            path: None,
+            state: Arc::new(Mutex::new(ProberState::default())),
+        }
+    }
+
+    /// 設定から作成（デフォルトのプローブに設定分を追加）
+    pub fn from_config(config: &EnvironmentConfig) -> Self {
+        let mut probes = default_probes();
+        for extra in &config.probes {
+            probes.retain(|p| p.name != extra.name);
+            probes.push(extra.clone());
+        }
+        Self::new(probes).with_timeout(Duration::from_millis(config.probe_timeout_ms))
+    }
+
+    /// プローブ1件あたりのタイムアウトを設定
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// プローブ実行時のPATHを設定
+    pub fn with_path(mut self, path: impl Into<OsString>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// ツール呼び出しを観測して必要なプローブを起動
+    pub fn observe_tool(&self, tool_name: &str, params: &Value) {
+        if tool_name == "bash" {
+            if let Some(command) = params.get("command").and_then(|v| v.as_str()) {
+                self.observe_command(command);
+            }
+        } else if tool_name.starts_with("git_") {
+            self.observe_command("git");
+        }
+    }
+
+    /// コマンドラインを観測して必要なプローブを起動
+    ///
+    /// PATH を変更するコマンドの場合は取得済みのプローブも再取得する
+    pub fn observe_command(&self, command_line: &str) {
+        let words: HashSet<&str> = command_line
+            .split(|c: char| c.is_whitespace() || matches!(c, ';' | '|' | '&' | '(' | ')' | '`'))
+            .filter(|w| !w.is_empty())
+            .map(|w| w.rsplit('/').next().unwrap_or(w))
+            .collect();
+
+        let modifies_path = command_line.contains("PATH=");
+
+        let to_run: Vec<ProbeConfig> = {
+            let Ok(mut state) = self.state.lock() else {
+                return;
+            };
+            if modifies_path {
+                state.scheduled.clear();
+            }
+            let previously_captured: HashSet<String> = if modifies_path {
+                state.fingerprint.tools.keys().cloned().collect()
+            } else {
+                HashSet::new()
+            };
+
+            let selected: Vec<ProbeConfig> = self
+                .probes
+                .iter()
+                .filter(|p| !state.scheduled.contains(&p.name))
+                .filter(|p| {
+                    previously_captured.contains(&p.name)
+                        || p.triggers.iter().any(|t| words.contains(t.as_str()))
+                })
+                .cloned()
+                .collect();
+            for probe in &selected {
+                state.scheduled.insert(probe.name.clone());
+            }
+            selected
+        };
+
+        for probe in to_run {
+            self.spawn_probe(probe);
+        }
+    }
+
+    /// 現在のフィンガープリントを取得（取得中のものは含まない）
+    pub fn snapshot(&self) -> EnvFingerprint {
+        self.state
+            .lock()
+            .map(|s| s.fingerprint.clone())
+            .unwrap_or_default()
+    }
+
+    /// 実行中のプローブの完了を待つ
+    pub async fn flush(&self) {
+        let pending = match self.state.lock() {
+            Ok(mut state) => std::mem::take(&mut state.pending),
+            Err(_) => return,
+        };
+        for handle in pending {
+            let _ = handle.await;
+        }
+    }
+
+    /// 取得済みの結果を破棄（実行中のプローブは中止）
+    pub fn reset(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            for handle in state.pending.drain(..) {
+                handle.abort();
+            }
+            *state = ProberState::default();
+        }
+    }
+
+    fn spawn_probe(&self, probe: ProbeConfig) {
+        let state = Arc::clone(&self.state);
+        let timeout = self.timeout;
+        let path = self.path.clone();
+
+        let handle = tokio::spawn(async move {
+            let version = run_probe(&probe, timeout, path).await;
+            if let Ok(mut state) = state.lock() {
+                state.fingerprint.tools.insert(probe.name.clone(), version);
+                state.fingerprint.captured_at = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .ok()
+                    .map(|d| d.as_secs());
+            }
+        });
+
+        if let Ok(mut state) = self.state.lock() {
+            state.pending.retain(|h| !h.is_finished());
+            state.pending.push(handle);
+        }
+    }
+}
+
+impl SessionResettable for EnvProber {
+    fn reset_target(&self) -> ResetTarget {
+        ResetTarget::EnvFingerprint
+    }
+
+    fn reset(&self, _action: ResetAction) {
+        EnvProber::reset(self);
+    }
+}
+
+/// プローブを実行して出力の1行目を返す
+async fn run_probe(probe: &ProbeConfig, timeout: Duration, path: Option<OsString>) -> String {
+    let mut command = Command::new(&probe.command);
+    command.args(&probe.args).kill_on_drop(true);
+    if let Some(path) = path {
+        command.env("PATH", path);
+    }
+
+    let output = match tokio::time::timeout(timeout, command.output()).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            tracing::debug!("Probe '{}' failed: {}", probe.name, e);
+            return UNAVAILABLE.to_string();
+        }
+        Err(_) => {
+            tracing::debug!("Probe '{}' timed out", probe.name);
+            return format!("{} (timed out)", UNAVAILABLE);
+        }
+    };
+
+    // python2 等は stderr にバージョンを出力する
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    stdout
+        .lines()
+        .chain(stderr.lines())
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| UNAVAILABLE.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    fn write_stub(dir: &std::path::Path, name: &str, script: &str) {
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_probes_only_tools_in_use() {
+        let dir = tempdir().unwrap();
+        write_stub(dir.path(), "rustc", "echo 'rustc 9.9.9-stub'");
+        write_stub(dir.path(), "cargo", "echo 'cargo 9.9.9-stub'");
+        write_stub(dir.path(), "git", "echo 'git version 1.0-stub'");
+
+        let prober = EnvProber::new(default_probes()).with_path(dir.path());
+        prober.observe_tool("bash", &json!({"command": "cd crate && cargo test"}));
+        prober.flush().await;
+
+        let fingerprint = prober.snapshot();
+        assert_eq!(fingerprint.tools.get("rustc").unwrap(), "rustc 9.9.9-stub");
+        assert_eq!(fingerprint.tools.get("cargo").unwrap(), "cargo 9.9.9-stub");
+        assert!(!fingerprint.tools.contains_key("git"));
+        assert!(!fingerprint.tools.contains_key("node"));
+
+        prober.observe_tool("git_status", &json!({}));
+        prober.flush().await;
+        assert_eq!(prober.snapshot().tools.get("git").unwrap(), "git version 1.0-stub");
+    }
+
+    #[tokio::test]
+    async fn test_missing_and_slow_tools_do_not_fail() {
+        let dir = tempdir().unwrap();
+        write_stub(dir.path(), "node", "/bin/sleep 5");
+
+        let prober = EnvProber::new(default_probes())
+            .with_path(dir.path())
+            .with_timeout(Duration::from_millis(100));
+        prober.observe_command("node script.js; python3 main.py");
+        prober.flush().await;
+
+        let fingerprint = prober.snapshot();
+        assert_eq!(fingerprint.tools.get("node").unwrap(), "unavailable (timed out)");
+        assert_eq!(fingerprint.tools.get("python3").unwrap(), "unavailable");
+    }
+
+    #[tokio::test]
+    async fn test_path_change_reprobes() {
+        let dir = tempdir().unwrap();
+        write_stub(dir.path(), "python3", "echo 'Python 3.0.0'");
+
+        let prober = EnvProber::new(default_probes()).with_path(dir.path());
+        prober.observe_command("python3 -c 'print(1)'");
+        prober.flush().await;
+        assert_eq!(prober.snapshot().tools.get("python3").unwrap(), "Python 3.0.0");
+
+        // 同じツールは再取得しない
+        write_stub(dir.path(), "python3", "echo 'Python 3.1.0'");
+        prober.observe_command("python3 other.py");
+        prober.flush().await;
+        assert_eq!(prober.snapshot().tools.get("python3").unwrap(), "Python 3.0.0");
+
+        // PATH 変更後は取得済みのツールを再取得
+        prober.observe_command("export PATH=/opt/bin:$PATH");
+        prober.flush().await;
+        assert_eq!(prober.snapshot().tools.get("python3").unwrap(), "Python 3.1.0");
+    }
+
+    #[test]
+    fn test_custom_probe_from_config() {
+        let config = EnvironmentConfig {
+            probe_timeout_ms: 500,
+            probes: vec![ProbeConfig {
+                name: "go".to_string(),
+                command: "go".to_string(),
+                args: vec!["version".to_string()],
+                triggers: vec!["go".to_string()],
+            }],
+        };
+        let prober = EnvProber::from_config(&config);
+        assert!(prober.probes.iter().any(|p| p.name == "go"));
+        assert!(prober.probes.iter().any(|p| p.name == "git"));
+        assert_eq!(prober.timeout, Duration::from_millis(500));
+    }
+}
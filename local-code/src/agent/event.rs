@@ -0,0 +1,41 @@
+//! ターン中の進行イベント
+//!
+//! `Agent::set_event_sender` で通知先を設定すると、生成の開始やツールの実行を順に送る。
+//! CLI はこれを受け取ってスピナーの表示を切り替える
+
+/// ターンの進行
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AgentEvent {
+    /// LLM への生成リクエストを送った
+    GenerationStarted,
+    /// ツールの実行を始めた（ツール名）
+    ToolStarted(String),
+    /// ツールの実行が終わった（ツール名と成否）
+    ToolFinished(String, bool),
+    /// ターンが終わった（失敗・中断を含む）
+    Done,
+    /// 応答内のコードブロックの検証を始めた（`block` は1始まり、`total` はブロック数）
+    VerificationStarted { language: String, block: usize, total: usize },
+    /// 検証に失敗したコードブロックの修正を頼んだ（`attempt` は1始まり）
+    FixRequested { language: String, block: usize, total: usize, attempt: usize, max_attempts: usize },
+    /// コードブロックの検証（と修正）が終わった
+    VerificationFinished { language: String, block: usize, total: usize, success: bool },
+}
+
+impl AgentEvent {
+    /// スピナーに出す文言（表示を変えないイベントは None）
+    pub fn status_text(&self) -> Option<String> {
+        match self {
+            AgentEvent::GenerationStarted => Some("Generating…".to_string()),
+            AgentEvent::ToolStarted(name) => Some(format!("Running {}…", name)),
+            AgentEvent::VerificationStarted { language, block, total } => {
+                Some(format!("Verifying {} block {}/{}…", language, block, total))
+            }
+            AgentEvent::FixRequested { language, block, total, attempt, max_attempts } => Some(format!(
+                "Fixing {} block {}/{} (attempt {}/{})…",
+                language, block, total, attempt, max_attempts
+            )),
+            AgentEvent::ToolFinished(..) | AgentEvent::Done | AgentEvent::VerificationFinished { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,453 @@
+//! 会話の書き出し（/export）
+//!
+//! 先頭にセッションの情報（モデル・プロジェクト・開始日時・ツールのバージョン）を置き、
+//! ロールを見出し、ツール結果を `<details>` の折りたたみにした Markdown を作る。
+//! HTML はその Markdown を最小限の変換でインライン CSS 付きの1ページにしたもの
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::conversation::{Conversation, Message, Role};
+use super::history::ConversationMetadata;
+
+/// 書き出しの形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+}
+
+impl ExportFormat {
+    /// `md`・`markdown`・`html`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Some(ExportFormat::Markdown),
+            "html" | "htm" => Some(ExportFormat::Html),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "Markdown",
+            ExportFormat::Html => "HTML",
+        }
+    }
+}
+
+/// HTML のページに入れる CSS
+const HTML_STYLE: &str = "\
+body { font-family: -apple-system, \"Segoe UI\", sans-serif; max-width: 52rem; margin: 2rem auto; padding: 0 1rem; line-height: 1.5; color: #222; }
+h2 { border-bottom: 1px solid #ddd; padding-bottom: 0.2rem; margin-top: 2rem; }
+pre { background: #f6f8fa; padding: 0.8rem; overflow-x: auto; border-radius: 4px; }
+code { font-family: ui-monospace, Menlo, Consolas, monospace; font-size: 0.9em; }
+details { margin: 0.5rem 0; padding: 0 0.8rem; border-left: 3px solid #ddd; }
+summary { cursor: pointer; color: #555; }
+.time { color: #888; font-size: 0.85rem; margin-top: -0.5rem; }
+.session { color: #555; font-size: 0.9rem; }";
+
+/// 会話を Markdown・HTML にする
+#[derive(Debug, Clone)]
+pub struct ConversationExporter {
+    /// システムメッセージとスキルのヒントを含めるか
+    include_system: bool,
+    /// 先頭の見出し（HTML では title にも使う）
+    title: String,
+    /// 見出しの下に置くセッションの情報
+    metadata: Option<ConversationMetadata>,
+}
+
+impl Default for ConversationExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConversationExporter {
+    pub fn new() -> Self {
+        Self {
+            include_system: true,
+            title: "Conversation".to_string(),
+            metadata: None,
+        }
+    }
+
+    /// システムメッセージ（システムプロンプト・圧縮の要約）とスキルのヒントを含めるか
+    pub fn with_system(mut self, include: bool) -> Self {
+        self.include_system = include;
+        self
+    }
+
+    /// 先頭の見出し
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// 見出しの下に置くセッションの情報（`Agent::session_metadata`）
+    pub fn with_metadata(mut self, metadata: ConversationMetadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    pub fn render(&self, conversation: &Conversation, format: ExportFormat) -> String {
+        match format {
+            ExportFormat::Markdown => self.to_markdown(conversation),
+            ExportFormat::Html => self.to_html(conversation),
+        }
+    }
+
+    /// 書き出す対象のメッセージ
+    pub fn exported_messages<'a>(&self, conversation: &'a Conversation) -> Vec<&'a Message> {
+        conversation
+            .messages()
+            .iter()
+            .filter(|m| self.include_system || m.role != Role::System)
+            .collect()
+    }
+
+    pub fn to_markdown(&self, conversation: &Conversation) -> String {
+        let mut out = format!("# {}\n", self.title);
+        let (fields, environment) = (self.session_fields(), self.tool_versions());
+        if !fields.is_empty() || !environment.is_empty() {
+            out.push('\n');
+            for (label, value) in &fields {
+                out.push_str(&format!("- {}: {}\n", label, value));
+            }
+            if !environment.is_empty() {
+                out.push_str("- Environment:\n");
+                for (name, version) in &environment {
+                    out.push_str(&format!("  - {}: {}\n", name, version));
+                }
+            }
+        }
+        out.push_str(&self.messages_markdown(conversation));
+        out
+    }
+
+    /// 見出しとセッションの情報を除いた、メッセージの部分
+    fn messages_markdown(&self, conversation: &Conversation) -> String {
+        let mut out = String::new();
+        for message in self.exported_messages(conversation) {
+            out.push('\n');
+            if message.role == Role::Tool {
+                let name = message.tool_name.as_deref().unwrap_or("unknown");
+                out.push_str(&format!("<details>\n<summary>Tool result: {}</summary>\n\n", name));
+                push_timestamp(&mut out, message.timestamp);
+                out.push_str(&fenced(message.content.trim_end(), "text"));
+                out.push_str("\n</details>\n");
+                continue;
+            }
+            let heading = match message.role {
+                Role::System => "System",
+                Role::User => "User",
+                Role::Assistant if message.interrupted => "Assistant (interrupted)",
+                Role::Assistant | Role::Tool => "Assistant",
+            };
+            out.push_str(&format!("## {}\n\n", heading));
+            push_timestamp(&mut out, message.timestamp);
+            let content = if self.include_system {
+                message.content.clone()
+            } else {
+                strip_skill_hint(&message.content)
+            };
+            let content = close_open_fence(content.trim());
+            if !content.is_empty() {
+                out.push_str(&content);
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    pub fn to_html(&self, conversation: &Conversation) -> String {
+        let title = escape_html(&self.title);
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n<h1>{}</h1>\n{}{}</body>\n</html>\n",
+            title,
+            HTML_STYLE,
+            title,
+            self.session_html(),
+            markdown_to_html(&self.messages_markdown(conversation))
+        )
+    }
+
+    /// セッションの情報を `<ul class="session">` にする（なければ空）
+    fn session_html(&self) -> String {
+        let (fields, environment) = (self.session_fields(), self.tool_versions());
+        if fields.is_empty() && environment.is_empty() {
+            return String::new();
+        }
+        let mut out = String::from("<ul class=\"session\">\n");
+        for (label, value) in &fields {
+            out.push_str(&format!("<li>{}: {}</li>\n", label, escape_html(value)));
+        }
+        if !environment.is_empty() {
+            out.push_str("<li>Environment:\n<ul>\n");
+            for (name, version) in &environment {
+                out.push_str(&format!("<li>{}: {}</li>\n", escape_html(name), escape_html(version)));
+            }
+            out.push_str("</ul>\n</li>\n");
+        }
+        out.push_str("</ul>\n");
+        out
+    }
+
+    /// 表示するセッションの情報（モデル・プロジェクト・開始日時）
+    fn session_fields(&self) -> Vec<(&'static str, String)> {
+        let Some(metadata) = &self.metadata else {
+            return Vec::new();
+        };
+        let mut fields = Vec::new();
+        if let Some(model) = &metadata.model {
+            fields.push(("Model", model.clone()));
+        }
+        if let Some(project) = &metadata.project_path {
+            fields.push(("Project", project.clone()));
+        }
+        if let Some(started) = metadata.created_at.and_then(|t| chrono::DateTime::from_timestamp(t as i64, 0)) {
+            fields.push(("Started", started.format("%Y-%m-%d %H:%M:%S UTC").to_string()));
+        }
+        fields
+    }
+
+    /// 取得済みのツールのバージョン（`--version` の1行目）
+    fn tool_versions(&self) -> Vec<(String, String)> {
+        self.metadata
+            .iter()
+            .filter_map(|m| m.environment.as_ref())
+            .flat_map(|f| f.tools.iter())
+            .map(|(name, version)| (name.clone(), version.lines().next().unwrap_or_default().trim().to_string()))
+            .collect()
+    }
+}
+
+/// `_2026-01-31 12:00:00 UTC_` の行（日時がなければ何もしない）
+fn push_timestamp(out: &mut String, timestamp: Option<SystemTime>) {
+    if let Some(timestamp) = timestamp {
+        let time = chrono::DateTime::<chrono::Utc>::from(timestamp);
+        out.push_str(&format!("_{}_\n\n", time.format("%Y-%m-%d %H:%M:%S UTC")));
+    }
+}
+
+/// 中身のバッククォートより長いフェンスで囲む
+fn fenced(content: &str, language: &str) -> String {
+    let longest = content
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat((longest + 1).max(3));
+    format!("{}{}\n{}\n{}\n", fence, language, content, fence)
+}
+
+/// 閉じていないコードフェンスを閉じる（後ろの見出しがコードに飲み込まれないように）
+fn close_open_fence(content: &str) -> String {
+    let mut open: Option<String> = None;
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        let ticks = trimmed.len() - trimmed.trim_start_matches('`').len();
+        if ticks < 3 {
+            continue;
+        }
+        match &open {
+            Some(fence) if ticks >= fence.len() && trimmed[ticks..].trim().is_empty() => open = None,
+            Some(_) => {}
+            None => open = Some("`".repeat(ticks)),
+        }
+    }
+    match open {
+        Some(fence) => format!("{}\n{}", content, fence),
+        None => content.to_string(),
+    }
+}
+
+/// 関連スキルの通知（`<skill_hint>`）を取り除く
+fn strip_skill_hint(content: &str) -> String {
+    let Some(start) = content.find("<skill_hint>") else {
+        return content.to_string();
+    };
+    let Some(end) = content[start..].find("</skill_hint>").map(|i| start + i + "</skill_hint>".len()) else {
+        return content.to_string();
+    };
+    format!("{}{}", &content[..start], content[end..].trim_start())
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// 対になったバッククォートを `<code>` にする（対にならなければそのまま）
+fn inline_code(line: &str) -> String {
+    let parts: Vec<&str> = line.split('`').collect();
+    if parts.len().is_multiple_of(2) {
+        return line.to_string();
+    }
+    parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| if i % 2 == 1 { format!("<code>{}</code>", part) } else { part.to_string() })
+        .collect()
+}
+
+/// 書き出した Markdown を HTML にする（見出し・フェンス・折りたたみ・日時・段落だけを扱う）
+fn markdown_to_html(markdown: &str) -> String {
+    let mut out = String::new();
+    let mut paragraph: Vec<String> = Vec::new();
+    let mut fence: Option<usize> = None;
+
+    let flush = |paragraph: &mut Vec<String>, out: &mut String| {
+        if !paragraph.is_empty() {
+            out.push_str(&format!("<p>{}</p>\n", paragraph.join("<br>\n")));
+            paragraph.clear();
+        }
+    };
+
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        let ticks = trimmed.len() - trimmed.trim_start_matches('`').len();
+        if let Some(open) = fence {
+            if ticks >= open && trimmed[ticks..].trim().is_empty() {
+                out.push_str("</code></pre>\n");
+                fence = None;
+            } else {
+                out.push_str(&escape_html(line));
+                out.push('\n');
+            }
+            continue;
+        }
+        if ticks >= 3 {
+            flush(&mut paragraph, &mut out);
+            let language = trimmed[ticks..].trim();
+            if language.is_empty() {
+                out.push_str("<pre><code>");
+            } else {
+                out.push_str(&format!("<pre><code class=\"language-{}\">", escape_html(language)));
+            }
+            fence = Some(ticks);
+            continue;
+        }
+        let level = line.len() - line.trim_start_matches('#').len();
+        if (1..=6).contains(&level) && line[level..].starts_with(' ') {
+            flush(&mut paragraph, &mut out);
+            out.push_str(&format!("<h{0}>{1}</h{0}>\n", level, escape_html(line[level..].trim())));
+        } else if line == "<details>" || line == "</details>" {
+            flush(&mut paragraph, &mut out);
+            out.push_str(line);
+            out.push('\n');
+        } else if let Some(summary) = line.strip_prefix("<summary>").and_then(|s| s.strip_suffix("</summary>")) {
+            out.push_str(&format!("<summary>{}</summary>\n", escape_html(summary)));
+        } else if let Some(time) = line.strip_prefix('_').and_then(|s| s.strip_suffix(" UTC_")) {
+            flush(&mut paragraph, &mut out);
+            out.push_str(&format!("<p class=\"time\">{} UTC</p>\n", escape_html(time)));
+        } else if line.trim().is_empty() {
+            flush(&mut paragraph, &mut out);
+        } else {
+            paragraph.push(inline_code(&escape_html(line)));
+        }
+    }
+    flush(&mut paragraph, &mut out);
+    if fence.is_some() {
+        out.push_str("</code></pre>\n");
+    }
+    out
+}
+
+/// 書き出し先（相対パスはプロジェクトルートから）
+pub fn export_target(project_root: &Path, path: &str) -> PathBuf {
+    project_root.join(path)
+}
+
+/// 書き出す（親ディレクトリがなければ作る）
+pub fn write_export(path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    std::fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::environment::EnvFingerprint;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    const SESSION_MD: &str = include_str!("../../tests/fixtures/export/session.md");
+    const SESSION_HTML: &str = include_str!("../../tests/fixtures/export/session.html");
+
+    /// 2026-01-31 12:00:00 UTC に始めたセッションの情報
+    fn metadata() -> ConversationMetadata {
+        let tools = [("cargo", "cargo 1.80.0 (376290515 2024-07-16)"), ("rustc", "rustc 1.80.0 (051478957 2024-07-21)")];
+        ConversationMetadata {
+            created_at: Some(1_769_860_800),
+            model: Some("qwen2.5-coder:14b".to_string()),
+            project_path: Some("/home/dev/<parser> & co".to_string()),
+            environment: Some(EnvFingerprint {
+                tools: tools.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect(),
+                captured_at: None,
+            }),
+        }
+    }
+
+    /// 2026-01-31 12:00:00 UTC から1秒ずつ進む日時を付けた会話
+    fn fixture() -> Conversation {
+        let messages = [
+            Message::system("You are a coding assistant."),
+            Message::user(
+                "<skill_hint>\nRelevant skills detected: debug. Consider using `/debug` if applicable.\n</skill_hint>\n\nWhy does `parse` panic on empty input?",
+            ),
+            Message::assistant("Let me read it.\n\n[read t1]"),
+            Message::tool("read", "fn parse(s: &str) -> u32 {\n    s.parse().unwrap()\n}"),
+            Message::assistant("`unwrap` panics on `\"\"`. Return a `Result` instead:\n\n```rust\nfn parse(s: &str) -> Result<u32, ParseIntError> {\n    s.parse()\n}\n```"),
+            Message::user("Show <T> generics & tests"),
+            Message::assistant("Partial answer:\n\n```rust\nfn f<T>() {}"),
+        ];
+        let mut conversation = Conversation::new();
+        for (i, mut message) in messages.into_iter().enumerate() {
+            message.timestamp = Some(UNIX_EPOCH + Duration::from_secs(1_769_860_800 + i as u64));
+            if i == 6 {
+                message.interrupted = true;
+            }
+            conversation.add(message);
+        }
+        conversation
+    }
+
+    #[test]
+    fn test_markdown_matches_golden_file() {
+        let markdown = ConversationExporter::new().with_metadata(metadata()).render(&fixture(), ExportFormat::Markdown);
+        assert_eq!(markdown, SESSION_MD, "rendered:\n{}", markdown);
+    }
+
+    #[test]
+    fn test_html_matches_golden_file() {
+        let html = ConversationExporter::new()
+            .with_title("Session")
+            .with_metadata(metadata())
+            .render(&fixture(), ExportFormat::Html);
+        assert_eq!(html, SESSION_HTML, "rendered:\n{}", html);
+    }
+
+    #[test]
+    fn test_exclude_system_messages_and_skill_hints() {
+        let markdown = ConversationExporter::new().with_system(false).to_markdown(&fixture());
+        assert!(!markdown.contains("## System"), "{}", markdown);
+        assert!(!markdown.contains("skill_hint"), "{}", markdown);
+        assert!(markdown.contains("## User\n\n_2026-01-31 12:00:01 UTC_\n\nWhy does `parse` panic"), "{}", markdown);
+        // セッションの情報を渡さなければ見出しの直後から会話が始まる
+        assert!(markdown.starts_with("# Conversation\n\n## User\n"), "{}", markdown);
+    }
+
+    #[test]
+    fn test_fences_outlast_backticks_in_content() {
+        assert_eq!(fenced("a ``` b", "text"), "````text\na ``` b\n````\n");
+        assert_eq!(close_open_fence("```rust\nfn f() {}\n```"), "```rust\nfn f() {}\n```");
+        assert_eq!(close_open_fence("x\n````\ny"), "x\n````\ny\n````");
+        assert_eq!(ExportFormat::parse("MD"), Some(ExportFormat::Markdown));
+        assert_eq!(ExportFormat::parse("pdf"), None);
+    }
+}
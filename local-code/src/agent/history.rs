@@ -1,13 +1,39 @@
 //! 会話履歴の永続化管理
 //!
 //! ~/.local-code/history/ に会話をJSON形式で保存・読み込みする
+//!
+//! 暗号化が有効な場合はファイル内容を暗号化して保存する。
+//! 一覧表示は復号せずに済むよう、サイドカーの索引ファイルを参照する
+//!
+//! 各ターンのあとには `autosave-<プロジェクトのハッシュ>` に自動保存する（プロジェクトごとに1件を上書き）
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::SystemTime;
 
-use super::conversation::{Conversation, Message, Role};
+use super::conversation::{Conversation, Message, MessageOrigin, Role};
+use super::encryption::{is_encrypted, StorageCipher};
+use super::environment::EnvFingerprint;
+
+/// 一覧用の索引ファイル（会話ファイルは常に .json なので衝突しない）
+const INDEX_FILE: &str = ".index";
+
+/// 自動保存の会話名の接頭辞
+pub const AUTOSAVE_PREFIX: &str = "autosave-";
+
+/// プロジェクトの自動保存の会話名（パスの FNV-1a ハッシュ、Rust のバージョンによらず同じ値）
+pub fn autosave_name(project_root: &Path) -> String {
+    let hash = project_root
+        .to_string_lossy()
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        });
+    format!("{}{:016x}", AUTOSAVE_PREFIX, hash)
+}
 
 /// 永続化用の会話データ
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,6 +58,12 @@ pub struct PersistedMessage {
     pub tool_name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub timestamp: Option<u64>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub interrupted: bool,
+    #[serde(default, skip_serializing_if = "MessageOrigin::is_typed")]
+    pub origin: MessageOrigin,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<String>,
 }
 
 /// 会話メタデータ
@@ -46,6 +78,9 @@ pub struct ConversationMetadata {
     /// プロジェクトパス
     #[serde(skip_serializing_if = "Option::is_none")]
     pub project_path: Option<String>,
+    /// 使用したツールのバージョン
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<EnvFingerprint>,
 }
 
 /// 会話履歴一覧のエントリ
@@ -55,16 +90,52 @@ pub struct HistoryEntry {
     pub name: String,
     /// 保存日時
     pub saved_at: u64,
-    /// メッセージ数
-    pub message_count: usize,
+    /// メッセージ数（索引のない暗号化ファイルは不明）
+    pub message_count: Option<usize>,
+    /// 暗号化されているか
+    #[serde(default)]
+    pub encrypted: bool,
+    /// 保存したプロジェクト（索引のない暗号化ファイルや古いファイルは不明）
+    #[serde(default)]
+    pub project_path: Option<String>,
+    /// 使っていたモデル（同上）
+    #[serde(default)]
+    pub model: Option<String>,
     /// ファイルパス
     pub path: PathBuf,
 }
 
+impl HistoryEntry {
+    /// 自動保存された会話か
+    pub fn is_autosave(&self) -> bool {
+        self.name.starts_with(AUTOSAVE_PREFIX)
+    }
+}
+
+/// 索引ファイルのエントリ
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexEntry {
+    name: String,
+    saved_at: u64,
+    message_count: usize,
+    #[serde(default)]
+    encrypted: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    project_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created_at: Option<u64>,
+}
+
 /// 会話履歴マネージャー
 pub struct HistoryManager {
     /// 履歴保存ディレクトリ
     history_dir: PathBuf,
+    /// 暗号化ファイルの復号・保存時の暗号化に使う
+    cipher: Option<Arc<StorageCipher>>,
+    /// 保存時に暗号化するか
+    encrypt: bool,
 }
 
 impl HistoryManager {
@@ -87,7 +158,25 @@ impl HistoryManager {
                 .context("Failed to create history directory")?;
         }
 
-        Ok(Self { history_dir })
+        Ok(Self {
+            history_dir,
+            cipher: None,
+            encrypt: false,
+        })
+    }
+
+    /// 暗号化ファイルの読み込みに使う cipher を設定
+    ///
+    /// `encrypt` が true の場合は保存時にも暗号化する
+    pub fn with_cipher(mut self, cipher: Arc<StorageCipher>, encrypt: bool) -> Self {
+        self.cipher = Some(cipher);
+        self.encrypt = encrypt;
+        self
+    }
+
+    /// 保存時に暗号化するか
+    pub fn encrypts(&self) -> bool {
+        self.encrypt && self.cipher.is_some()
     }
 
     /// 会話を保存
@@ -96,6 +185,18 @@ impl HistoryManager {
     /// * `name` - 保存名（ファイル名として使用）
     /// * `conversation` - 保存する会話
     pub fn save(&self, name: &str, conversation: &Conversation) -> Result<PathBuf> {
+        self.save_with_metadata(name, conversation, ConversationMetadata::default())
+    }
+
+    /// メタデータ付きで会話を保存
+    ///
+    /// 同じ名前で保存し直した場合は最初の保存の作成日時を引き継ぐ
+    pub fn save_with_metadata(
+        &self,
+        name: &str,
+        conversation: &Conversation,
+        mut metadata: ConversationMetadata,
+    ) -> Result<PathBuf> {
         let sanitized_name = Self::sanitize_filename(name);
         let file_path = self.history_dir.join(format!("{}.json", sanitized_name));
 
@@ -103,21 +204,42 @@ impl HistoryManager {
             .duration_since(SystemTime::UNIX_EPOCH)
             .map(|d| d.as_secs())
             .unwrap_or(0);
+        metadata.created_at = self
+            .created_at(&sanitized_name, &file_path)
+            .or(metadata.created_at)
+            .or(Some(now));
 
         let persisted = PersistedConversation {
             name: name.to_string(),
             saved_at: now,
             messages: conversation.messages().iter().map(Self::message_to_persisted).collect(),
-            metadata: ConversationMetadata::default(),
+            metadata,
         };
+        self.write_persisted(&file_path, &sanitized_name, &persisted, self.encrypts())?;
+
+        Ok(file_path)
+    }
 
-        let json = serde_json::to_string_pretty(&persisted)
+    /// 会話ファイルを書き込み、索引を更新
+    fn write_persisted(
+        &self,
+        file_path: &Path,
+        sanitized_name: &str,
+        persisted: &PersistedConversation,
+        encrypted: bool,
+    ) -> Result<()> {
+        let json = serde_json::to_string_pretty(persisted)
             .context("Failed to serialize conversation")?;
 
-        std::fs::write(&file_path, json)
+        let bytes = match (&self.cipher, encrypted) {
+            (Some(cipher), true) => cipher.encrypt(json.as_bytes())?,
+            _ => json.into_bytes(),
+        };
+        std::fs::write(file_path, bytes)
             .context("Failed to write history file")?;
 
-        Ok(file_path)
+        self.update_index(sanitized_name, Some(Self::index_entry(persisted, encrypted)));
+        Ok(())
     }
 
     /// 会話を読み込み
@@ -132,11 +254,8 @@ impl HistoryManager {
             anyhow::bail!("History '{}' not found", name);
         }
 
-        let json = std::fs::read_to_string(&file_path)
-            .context("Failed to read history file")?;
-
-        let persisted: PersistedConversation = serde_json::from_str(&json)
-            .context("Failed to parse history file")?;
+        let persisted = self.read_persisted(&file_path)
+            .with_context(|| format!("Failed to read history '{}'", name))?;
 
         let mut conversation = Conversation::new();
         for msg in persisted.messages {
@@ -147,6 +266,8 @@ impl HistoryManager {
     }
 
     /// 保存された会話一覧を取得
+    ///
+    /// 索引にある会話は復号せずに一覧化する
     pub fn list(&self) -> Result<Vec<HistoryEntry>> {
         let mut entries = Vec::new();
 
@@ -154,14 +275,22 @@ impl HistoryManager {
             return Ok(entries);
         }
 
-        let read_dir = std::fs::read_dir(&self.history_dir)
-            .context("Failed to read history directory")?;
-
-        for entry in read_dir {
-            let entry = entry?;
-            let path = entry.path();
-
-            if path.extension().and_then(|s| s.to_str()) != Some("json") {
+        let index = self.read_index();
+        for path in self.conversation_files()? {
+            let indexed = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|stem| index.get(stem));
+            if let Some(indexed) = indexed {
+                entries.push(HistoryEntry {
+                    name: indexed.name.clone(),
+                    saved_at: indexed.saved_at,
+                    message_count: Some(indexed.message_count),
+                    encrypted: indexed.encrypted,
+                    project_path: indexed.project_path.clone(),
+                    model: indexed.model.clone(),
+                    path,
+                });
                 continue;
             }
 
@@ -174,11 +303,21 @@ impl HistoryManager {
         }
 
         // 保存日時で降順ソート（新しいものが先頭）
-        entries.sort_by(|a, b| b.saved_at.cmp(&a.saved_at));
+        entries.sort_by_key(|e| std::cmp::Reverse(e.saved_at));
 
         Ok(entries)
     }
 
+    /// 指定したプロジェクトで保存された会話の一覧（新しい順、/resume 用）
+    pub fn list_for_project(&self, project_root: &Path) -> Result<Vec<HistoryEntry>> {
+        let project = project_root.display().to_string();
+        Ok(self
+            .list()?
+            .into_iter()
+            .filter(|entry| entry.project_path.as_deref() == Some(project.as_str()))
+            .collect())
+    }
+
     /// 会話を削除
     ///
     /// # Arguments
@@ -194,9 +333,88 @@ impl HistoryManager {
         std::fs::remove_file(&file_path)
             .context("Failed to delete history file")?;
 
+        self.update_index(&sanitized_name, None);
+
         Ok(())
     }
 
+    /// 会話の名前を変える（ファイル内の名前も書き換え、メッセージとメタデータはそのまま）
+    ///
+    /// 暗号化されたファイルは暗号化したまま書き直す
+    pub fn rename(&self, from: &str, to: &str) -> Result<PathBuf> {
+        let from_name = Self::sanitize_filename(from);
+        let to_name = Self::sanitize_filename(to);
+        let from_path = self.history_dir.join(format!("{}.json", from_name));
+        let to_path = self.history_dir.join(format!("{}.json", to_name));
+
+        if !from_path.exists() {
+            anyhow::bail!("History '{}' not found", from);
+        }
+        if from_name == to_name {
+            anyhow::bail!("'{}' and '{}' are the same name", from, to);
+        }
+        if to_path.exists() {
+            anyhow::bail!("History '{}' already exists", to);
+        }
+
+        let encrypted = is_encrypted(&std::fs::read(&from_path).context("Failed to read history file")?);
+        let mut persisted = self.read_persisted(&from_path)
+            .with_context(|| format!("Failed to read history '{}'", from))?;
+        persisted.name = to.to_string();
+        self.write_persisted(&to_path, &to_name, &persisted, encrypted)?;
+
+        std::fs::remove_file(&from_path)
+            .context("Failed to remove the old history file")?;
+        self.update_index(&from_name, None);
+
+        Ok(to_path)
+    }
+
+    /// 保存された会話の名前（補完用、新しい順）
+    pub fn names(&self) -> Vec<String> {
+        self.list()
+            .map(|entries| entries.into_iter().map(|e| e.name).collect())
+            .unwrap_or_default()
+    }
+
+    /// 平文の会話ファイルをすべて暗号化し、暗号化した件数を返す
+    pub fn encrypt_existing(&self) -> Result<usize> {
+        let cipher = self
+            .cipher
+            .as_ref()
+            .context("Encryption is not configured")?;
+
+        let mut count = 0;
+        for path in self.conversation_files()? {
+            let bytes = std::fs::read(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            if is_encrypted(&bytes) {
+                continue;
+            }
+            // 壊れたファイルは暗号化せずに残す
+            let persisted: PersistedConversation = match serde_json::from_slice(&bytes) {
+                Ok(persisted) => persisted,
+                Err(e) => {
+                    tracing::warn!("Skipping unparseable history file {:?}: {}", path, e);
+                    continue;
+                }
+            };
+
+            let tmp = path.with_extension("json.tmp");
+            std::fs::write(&tmp, cipher.encrypt(&bytes)?)
+                .with_context(|| format!("Failed to write {}", tmp.display()))?;
+            std::fs::rename(&tmp, &path)
+                .with_context(|| format!("Failed to replace {}", path.display()))?;
+
+            if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                self.update_index(stem, Some(Self::index_entry(&persisted, true)));
+            }
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
     /// 会話が存在するかチェック
     pub fn exists(&self, name: &str) -> bool {
         let sanitized_name = Self::sanitize_filename(name);
@@ -239,6 +457,9 @@ impl HistoryManager {
             content: msg.content.clone(),
             tool_name: msg.tool_name.clone(),
             timestamp,
+            interrupted: msg.interrupted,
+            origin: msg.origin,
+            input: msg.input.clone(),
         }
     }
 
@@ -261,24 +482,136 @@ impl HistoryManager {
             content: persisted.content.clone(),
             tool_name: persisted.tool_name.clone(),
             timestamp,
+            interrupted: persisted.interrupted,
+            pinned: None,
+            origin: persisted.origin,
+            input: persisted.input.clone(),
         }
     }
 
-    /// ファイルからHistoryEntryを読み込み
-    fn read_entry(&self, path: &PathBuf) -> Result<HistoryEntry> {
-        let json = std::fs::read_to_string(path)
+    /// 会話ファイル（*.json）の一覧
+    fn conversation_files(&self) -> Result<Vec<PathBuf>> {
+        let read_dir = std::fs::read_dir(&self.history_dir)
+            .context("Failed to read history directory")?;
+
+        let mut files = Vec::new();
+        for entry in read_dir {
+            let path = entry?.path();
+            if path.extension().and_then(|s| s.to_str()) == Some("json") {
+                files.push(path);
+            }
+        }
+        Ok(files)
+    }
+
+    /// ファイルを読み込み、暗号化されていれば復号してパース
+    fn read_persisted(&self, path: &Path) -> Result<PersistedConversation> {
+        let bytes = std::fs::read(path)
+            .context("Failed to read history file")?;
+
+        let json = if is_encrypted(&bytes) {
+            let cipher = self
+                .cipher
+                .as_ref()
+                .context("File is encrypted but no passphrase is configured (see [storage])")?;
+            cipher.decrypt(&bytes)?
+        } else {
+            bytes
+        };
+
+        serde_json::from_slice(&json)
+            .context("Failed to parse history file")
+    }
+
+    /// 索引のないファイルからHistoryEntryを読み込み
+    ///
+    /// 暗号化ファイルは復号せず、ファイル名と更新日時のみ返す
+    fn read_entry(&self, path: &Path) -> Result<HistoryEntry> {
+        let bytes = std::fs::read(path)
             .context("Failed to read history file")?;
 
-        let persisted: PersistedConversation = serde_json::from_str(&json)
+        if is_encrypted(&bytes) {
+            let saved_at = std::fs::metadata(path)?
+                .modified()?
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            return Ok(HistoryEntry {
+                name: path.file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_string(),
+                saved_at,
+                message_count: None,
+                encrypted: true,
+                project_path: None,
+                model: None,
+                path: path.to_path_buf(),
+            });
+        }
+
+        let persisted: PersistedConversation = serde_json::from_slice(&bytes)
             .context("Failed to parse history file")?;
 
         Ok(HistoryEntry {
             name: persisted.name,
             saved_at: persisted.saved_at,
-            message_count: persisted.messages.len(),
-            path: path.clone(),
+            message_count: Some(persisted.messages.len()),
+            encrypted: false,
+            project_path: persisted.metadata.project_path,
+            model: persisted.metadata.model,
+            path: path.to_path_buf(),
         })
     }
+
+    fn index_entry(persisted: &PersistedConversation, encrypted: bool) -> IndexEntry {
+        IndexEntry {
+            name: persisted.name.clone(),
+            saved_at: persisted.saved_at,
+            message_count: persisted.messages.len(),
+            encrypted,
+            project_path: persisted.metadata.project_path.clone(),
+            model: persisted.metadata.model.clone(),
+            created_at: persisted.metadata.created_at,
+        }
+    }
+
+    /// 既存の保存の作成日時（索引になければファイルから）
+    fn created_at(&self, sanitized_name: &str, file_path: &Path) -> Option<u64> {
+        if let Some(created_at) = self.read_index().get(sanitized_name).and_then(|e| e.created_at) {
+            return Some(created_at);
+        }
+        if !file_path.exists() {
+            return None;
+        }
+        self.read_persisted(file_path).ok()?.metadata.created_at
+    }
+
+    /// 索引を読み込み（存在しない・壊れている場合は空）
+    fn read_index(&self) -> BTreeMap<String, IndexEntry> {
+        std::fs::read(self.history_dir.join(INDEX_FILE))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    /// 索引のエントリを更新（None で削除）
+    ///
+    /// 索引は一覧表示の高速化のためだけなので、失敗しても警告にとどめる
+    fn update_index(&self, stem: &str, entry: Option<IndexEntry>) {
+        let mut index = self.read_index();
+        match entry {
+            Some(entry) => {
+                index.insert(stem.to_string(), entry);
+            }
+            None => {
+                index.remove(stem);
+            }
+        }
+        let result = serde_json::to_vec_pretty(&index)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| Ok(std::fs::write(self.history_dir.join(INDEX_FILE), json)?));
+        if let Err(e) = result {
+            tracing::warn!("Failed to update history index: {}", e);
+        }
+    }
 }
 
 impl Default for HistoryManager {
@@ -290,6 +623,7 @@ impl Default for HistoryManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::agent::encryption::{KdfParams, PassphraseSource};
     use tempfile::tempdir;
 
     #[test]
@@ -314,6 +648,76 @@ mod tests {
         assert_eq!(loaded.messages()[2].role, Role::Assistant);
     }
 
+    #[test]
+    fn test_save_with_environment_metadata() {
+        let temp_dir = tempdir().unwrap();
+        let manager = HistoryManager::with_directory(temp_dir.path().to_path_buf()).unwrap();
+
+        let mut fingerprint = EnvFingerprint::default();
+        fingerprint.tools.insert("rustc".to_string(), "rustc 1.80.0".to_string());
+        let metadata = ConversationMetadata {
+            environment: Some(fingerprint.clone()),
+            ..Default::default()
+        };
+
+        let path = manager
+            .save_with_metadata("env-test", &Conversation::new(), metadata)
+            .unwrap();
+        let persisted: PersistedConversation =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(persisted.metadata.environment, Some(fingerprint));
+    }
+
+    #[test]
+    fn test_resave_keeps_created_at() {
+        let temp_dir = tempdir().unwrap();
+        let manager = HistoryManager::with_directory(temp_dir.path().to_path_buf()).unwrap();
+        let metadata = |created_at: u64| ConversationMetadata {
+            created_at: Some(created_at),
+            model: Some("qwen2.5-coder".to_string()),
+            ..Default::default()
+        };
+
+        manager.save_with_metadata("work", &Conversation::new(), metadata(100)).unwrap();
+        let path = manager.save_with_metadata("work", &Conversation::new(), metadata(200)).unwrap();
+        let persisted: PersistedConversation =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(persisted.metadata.created_at, Some(100));
+
+        // 索引がなくてもファイルから引き継ぐ
+        std::fs::remove_file(temp_dir.path().join(INDEX_FILE)).unwrap();
+        manager.save_with_metadata("work", &Conversation::new(), metadata(300)).unwrap();
+        let persisted: PersistedConversation =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(persisted.metadata.created_at, Some(100));
+
+        // 未指定なら保存した時刻
+        let path = manager.save("fresh", &Conversation::new()).unwrap();
+        let persisted: PersistedConversation =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert!(persisted.metadata.created_at.is_some());
+
+        let entry = manager.list().unwrap().into_iter().find(|e| e.name == "work").unwrap();
+        assert_eq!(entry.model.as_deref(), Some("qwen2.5-coder"));
+    }
+
+    #[test]
+    fn test_file_without_metadata_still_loads() {
+        let temp_dir = tempdir().unwrap();
+        let manager = HistoryManager::with_directory(temp_dir.path().to_path_buf()).unwrap();
+        std::fs::write(
+            temp_dir.path().join("old.json"),
+            r#"{"name": "old", "saved_at": 1700000000, "messages": [{"role": "user", "content": "hi"}]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(manager.load("old").unwrap().len(), 1);
+        let entry = manager.list().unwrap().remove(0);
+        assert_eq!(entry.name, "old");
+        assert_eq!(entry.model, None);
+        assert_eq!(entry.project_path, None);
+    }
+
     #[test]
     fn test_list() {
         let temp_dir = tempdir().unwrap();
@@ -346,6 +750,47 @@ mod tests {
         assert!(!manager.exists("to-delete"));
     }
 
+    #[test]
+    fn test_rename_preserves_messages_and_metadata() {
+        let temp_dir = tempdir().unwrap();
+        let manager = HistoryManager::with_directory(temp_dir.path().to_path_buf()).unwrap();
+
+        let mut conversation = Conversation::new();
+        conversation.add_user("Hello");
+        conversation.add_assistant("Hi there!");
+        let metadata = ConversationMetadata {
+            model: Some("qwen2.5-coder".to_string()),
+            ..Default::default()
+        };
+        manager.save_with_metadata("draft", &conversation, metadata).unwrap();
+        manager.save("taken", &Conversation::new()).unwrap();
+
+        let path = manager.rename("draft", "final").unwrap();
+        assert!(!manager.exists("draft"));
+        let loaded = manager.load("final").unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded.messages()[1].content, "Hi there!");
+
+        let persisted: PersistedConversation =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(persisted.name, "final");
+        assert_eq!(persisted.metadata.model.as_deref(), Some("qwen2.5-coder"));
+        let mut names = manager.names();
+        names.sort();
+        assert_eq!(names, vec!["final", "taken"]);
+
+        assert!(manager.rename("final", "taken").unwrap_err().to_string().contains("already exists"));
+        assert!(manager.rename("missing", "other").unwrap_err().to_string().contains("not found"));
+    }
+
+    #[test]
+    fn test_delete_nonexistent() {
+        let temp_dir = tempdir().unwrap();
+        let manager = HistoryManager::with_directory(temp_dir.path().to_path_buf()).unwrap();
+        let err = manager.delete("nope").unwrap_err();
+        assert!(err.to_string().contains("History 'nope' not found"));
+    }
+
     #[test]
     fn test_sanitize_filename() {
         assert_eq!(HistoryManager::sanitize_filename("normal"), "normal");
@@ -354,6 +799,104 @@ mod tests {
         assert_eq!(HistoryManager::sanitize_filename("multi<>chars"), "multi__chars");
     }
 
+    fn cipher(passphrase: &str) -> Arc<StorageCipher> {
+        Arc::new(
+            StorageCipher::new(PassphraseSource::Value(passphrase.to_string()))
+                .with_kdf_params(KdfParams { m_cost: 64, t_cost: 1, p_cost: 1 }),
+        )
+    }
+
+    #[test]
+    fn test_encrypted_round_trip_in_mixed_directory() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().to_path_buf();
+
+        let mut conversation = Conversation::new();
+        conversation.add_user("my api key is hunter2");
+        HistoryManager::with_directory(dir.clone()).unwrap().save("plain", &conversation).unwrap();
+
+        let manager = HistoryManager::with_directory(dir.clone())
+            .unwrap()
+            .with_cipher(cipher("pw"), true);
+        let path = manager.save("secret", &conversation).unwrap();
+        let raw = std::fs::read(&path).unwrap();
+        assert!(is_encrypted(&raw));
+        assert!(!String::from_utf8_lossy(&raw).contains("hunter2"));
+
+        assert_eq!(manager.load("secret").unwrap().len(), 1);
+        assert_eq!(manager.load("plain").unwrap().len(), 1);
+
+        let entries = manager.list().unwrap();
+        assert_eq!(entries.len(), 2);
+        let secret = entries.iter().find(|e| e.name == "secret").unwrap();
+        assert!(secret.encrypted);
+        assert_eq!(secret.message_count, Some(1));
+    }
+
+    #[test]
+    fn test_wrong_passphrase_is_not_a_parse_error() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().to_path_buf();
+        HistoryManager::with_directory(dir.clone())
+            .unwrap()
+            .with_cipher(cipher("right"), true)
+            .save("conv", &Conversation::new())
+            .unwrap();
+
+        let wrong = HistoryManager::with_directory(dir.clone())
+            .unwrap()
+            .with_cipher(cipher("wrong"), false);
+        let message = format!("{:#}", wrong.load("conv").unwrap_err());
+        assert!(message.contains("wrong passphrase"), "{}", message);
+        assert!(!message.contains("parse"), "{}", message);
+
+        let without = HistoryManager::with_directory(dir).unwrap();
+        let message = format!("{:#}", without.load("conv").unwrap_err());
+        assert!(message.contains("encrypted"), "{}", message);
+    }
+
+    #[test]
+    fn test_list_does_not_decrypt() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().to_path_buf();
+        let manager = HistoryManager::with_directory(dir.clone())
+            .unwrap()
+            .with_cipher(cipher("pw"), true);
+        manager.save("indexed", &Conversation::new()).unwrap();
+
+        // 索引を消しても、パスフレーズなしで一覧できる
+        std::fs::remove_file(dir.join(INDEX_FILE)).unwrap();
+        let entries = HistoryManager::with_directory(dir).unwrap().list().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "indexed");
+        assert!(entries[0].encrypted);
+        assert_eq!(entries[0].message_count, None);
+    }
+
+    #[test]
+    fn test_encrypt_existing() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().to_path_buf();
+        let mut conversation = Conversation::new();
+        conversation.add_user("Hello");
+        let plain = HistoryManager::with_directory(dir.clone()).unwrap();
+        plain.save("a", &conversation).unwrap();
+        plain.save("b", &conversation).unwrap();
+        std::fs::write(dir.join("broken.json"), "{").unwrap();
+
+        let manager = HistoryManager::with_directory(dir.clone())
+            .unwrap()
+            .with_cipher(cipher("pw"), true);
+        assert_eq!(manager.encrypt_existing().unwrap(), 2);
+        assert_eq!(manager.encrypt_existing().unwrap(), 0);
+
+        assert!(is_encrypted(&std::fs::read(dir.join("a.json")).unwrap()));
+        assert_eq!(std::fs::read_to_string(dir.join("broken.json")).unwrap(), "{");
+        assert_eq!(manager.load("b").unwrap().len(), 1);
+        let entries = manager.list().unwrap();
+        assert!(entries.iter().filter(|e| e.name != "broken").all(|e| e.encrypted));
+    }
+
     #[test]
     fn test_load_nonexistent() {
         let temp_dir = tempdir().unwrap();
@@ -362,4 +905,47 @@ mod tests {
         let result = manager.load("nonexistent");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_autosave_name_is_stable_per_project() {
+        let name = autosave_name(Path::new("/home/user/project"));
+        assert!(name.starts_with(AUTOSAVE_PREFIX));
+        assert_eq!(name.len(), AUTOSAVE_PREFIX.len() + 16);
+        assert_eq!(name, autosave_name(Path::new("/home/user/project")));
+        assert_ne!(name, autosave_name(Path::new("/home/user/other")));
+        // ファイル名として安全（サニタイズで変わらない）
+        assert_eq!(HistoryManager::sanitize_filename(&name), name);
+    }
+
+    #[test]
+    fn test_list_for_project() {
+        let temp_dir = tempdir().unwrap();
+        let manager = HistoryManager::with_directory(temp_dir.path().to_path_buf()).unwrap();
+        let metadata = |project: &str| ConversationMetadata {
+            project_path: Some(project.to_string()),
+            ..Default::default()
+        };
+
+        let mut conversation = Conversation::new();
+        conversation.add_user("Hello");
+        let here = Path::new("/work/here");
+        manager
+            .save_with_metadata(&autosave_name(here), &conversation, metadata("/work/here"))
+            .unwrap();
+        manager.save_with_metadata("named", &conversation, metadata("/work/here")).unwrap();
+        manager
+            .save_with_metadata(&autosave_name(Path::new("/work/there")), &conversation, metadata("/work/there"))
+            .unwrap();
+        manager.save("legacy", &conversation).unwrap();
+
+        let mut names: Vec<String> = manager.list_for_project(here).unwrap().into_iter().map(|e| e.name).collect();
+        names.sort();
+        assert_eq!(names, vec![autosave_name(here), "named".to_string()]);
+
+        // 索引がなくてもファイルのメタデータから分かる
+        std::fs::remove_file(temp_dir.path().join(INDEX_FILE)).unwrap();
+        let entries = manager.list_for_project(here).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries.iter().filter(|e| e.is_autosave()).count(), 1);
+    }
 }
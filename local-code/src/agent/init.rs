@@ -0,0 +1,301 @@
+//! /init: プロジェクトを調べて agent.md を作る
+//!
+//! マニフェスト・ビルドコマンド・トップレベルの構成・README の冒頭を手元で集め（LLM は使わない）、
+//! それを渡して LLM に agent.md の下書きを書かせる
+
+use anyhow::{bail, Context, Result};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+use super::context::ecosystem;
+use crate::llm::LlmBackend;
+use crate::tools::search::TreeTool;
+use crate::tools::Tool;
+
+/// /init が書き出すファイル名
+pub const AGENT_MD: &str = "agent.md";
+
+/// 事実として集めるマニフェスト
+const MANIFESTS: &[&str] = &[
+    "Cargo.toml", "package.json", "go.mod", "pyproject.toml", "requirements.txt", "setup.py", "Makefile",
+];
+
+/// README の冒頭として渡す文字数の上限
+const README_BUDGET: usize = 1200;
+
+/// ツリーの深さと件数
+const TREE_DEPTH: u64 = 2;
+const TREE_ENTRIES: u64 = 80;
+
+/// 下書きを頼むときのシステムプロンプト
+const INIT_SYSTEM_PROMPT: &str = "You write agent.md files: short instructions that a coding agent reads \
+before working in a repository. Use only the facts you are given; do not invent commands, paths or tools. \
+Reply with the Markdown content of the file only.";
+
+/// プロジェクトについて手元で集めた事実
+#[derive(Debug, Clone, Default)]
+pub struct ProjectFacts {
+    /// 主な言語（マニフェストから推定）
+    pub language: Option<&'static str>,
+    /// 見つかったマニフェスト
+    pub manifests: Vec<String>,
+    /// (用途, コマンド)
+    pub commands: Vec<(String, String)>,
+    /// トップレベルのディレクトリツリー
+    pub tree: String,
+    /// README の最初の段落
+    pub readme: Option<String>,
+}
+
+impl ProjectFacts {
+    /// `root` を調べる
+    pub async fn gather(root: &Path) -> Self {
+        let manifests = MANIFESTS
+            .iter()
+            .filter(|name| root.join(name).is_file())
+            .map(|name| name.to_string())
+            .collect();
+        Self {
+            language: ecosystem(root),
+            manifests,
+            commands: guess_commands(root),
+            tree: top_level_tree(root).await,
+            readme: readme_intro(root),
+        }
+    }
+
+    /// LLM に渡す依頼文
+    pub fn to_prompt(&self) -> String {
+        let mut out = String::from("Write an agent.md for this project from the facts below.\n\n# Facts\n");
+        out.push_str(&format!("Language: {}\n", self.language.unwrap_or("unknown")));
+        if !self.manifests.is_empty() {
+            out.push_str(&format!("Manifests: {}\n", self.manifests.join(", ")));
+        }
+        if !self.commands.is_empty() {
+            out.push_str("Commands:\n");
+            for (purpose, command) in &self.commands {
+                out.push_str(&format!("- {}: `{}`\n", purpose, command));
+            }
+        }
+        out.push_str(&format!("\nDirectory tree:\n```\n{}\n```\n", self.tree));
+        if let Some(readme) = &self.readme {
+            out.push_str(&format!("\nREADME (opening):\n{}\n", readme));
+        }
+        out.push_str(
+            "\n# Format\n\
+             Keep it under 60 lines, with these sections:\n\
+             - `## Build and test`: the commands above, one per line\n\
+             - `## Architecture`: what the main directories contain, in a few bullets\n\
+             - `## Conventions`: style and workflow rules a contributor should follow\n\
+             Start with a one-line `# <project name>` title.\n",
+        );
+        out
+    }
+}
+
+/// ビルド・テストなどのコマンドをマニフェストから推測する
+fn guess_commands(root: &Path) -> Vec<(String, String)> {
+    let mut commands = Vec::new();
+    let mut add = |purpose: &str, command: String| commands.push((purpose.to_string(), command));
+
+    if let Ok(cargo) = std::fs::read_to_string(root.join("Cargo.toml")) {
+        let workspace = if cargo.contains("[workspace]") { " --workspace" } else { "" };
+        add("build", format!("cargo build{}", workspace));
+        add("test", format!("cargo test{}", workspace));
+        add("lint", format!("cargo clippy{} --all-targets -- -D warnings", workspace));
+    }
+    if let Some(scripts) = std::fs::read_to_string(root.join("package.json"))
+        .ok()
+        .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok())
+        .and_then(|json| json.get("scripts").cloned())
+    {
+        let runner = node_runner(root);
+        for script in ["build", "test", "lint", "dev", "start"] {
+            if scripts.get(script).is_some() {
+                add(script, format!("{} run {}", runner, script));
+            }
+        }
+    }
+    if root.join("go.mod").is_file() {
+        add("build", "go build ./...".to_string());
+        add("test", "go test ./...".to_string());
+    }
+    let python = root.join("pyproject.toml").is_file() || root.join("requirements.txt").is_file();
+    if python && (root.join("tests").is_dir() || root.join("pytest.ini").is_file()) {
+        add("test", "pytest".to_string());
+    }
+    if let Ok(makefile) = std::fs::read_to_string(root.join("Makefile")) {
+        for target in makefile_targets(&makefile) {
+            if ["build", "test", "lint", "check", "fmt", "run"].contains(&target.as_str()) {
+                add(&target, format!("make {}", target));
+            }
+        }
+    }
+    commands
+}
+
+/// ロックファイルから Node.js のパッケージマネージャーを選ぶ
+fn node_runner(root: &Path) -> &'static str {
+    [("pnpm-lock.yaml", "pnpm"), ("yarn.lock", "yarn"), ("bun.lockb", "bun")]
+        .iter()
+        .find(|(lock, _)| root.join(lock).exists())
+        .map(|(_, runner)| *runner)
+        .unwrap_or("npm")
+}
+
+/// Makefile のターゲット名（変数代入や特殊ターゲットは除く）
+fn makefile_targets(makefile: &str) -> Vec<String> {
+    makefile
+        .lines()
+        .filter(|line| !line.starts_with(['\t', ' ', '#', '.']))
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(_, rest)| !rest.starts_with('='))
+        .map(|(target, _)| target.trim())
+        .filter(|target| !target.is_empty() && !target.contains(['$', '%', ' ', '=']))
+        .map(str::to_string)
+        .collect()
+}
+
+/// トップレベルの構成（tree ツールと同じ表示）
+async fn top_level_tree(root: &Path) -> String {
+    let params = json!({"path": root.to_string_lossy(), "max_depth": TREE_DEPTH, "max_entries": TREE_ENTRIES});
+    match TreeTool::new().execute(params).await {
+        Ok(result) if result.success => {
+            let shown = root.to_string_lossy();
+            let shown = shown.trim_end_matches('/');
+            match result.output.strip_prefix(shown) {
+                Some(rest) => format!(".{}", rest),
+                None => result.output,
+            }
+        }
+        _ => String::new(),
+    }
+}
+
+/// README の最初の段落（見出し・バッジ・画像だけの段落は飛ばす）
+fn readme_intro(root: &Path) -> Option<String> {
+    let text = ["README.md", "README", "README.rst", "readme.md"]
+        .iter()
+        .find_map(|name| std::fs::read_to_string(root.join(name)).ok())?;
+    let mut intro = String::new();
+    for paragraph in text.split("\n\n").map(str::trim) {
+        let decorative = paragraph
+            .lines()
+            .all(|line| line.starts_with('#') || line.starts_with("[![") || line.starts_with("![") || line.starts_with('='));
+        if paragraph.is_empty() || decorative {
+            continue;
+        }
+        if !intro.is_empty() && intro.len() + paragraph.len() > README_BUDGET {
+            break;
+        }
+        if !intro.is_empty() {
+            intro.push_str("\n\n");
+        }
+        intro.push_str(paragraph);
+        if intro.len() >= README_BUDGET / 2 {
+            break;
+        }
+    }
+    if intro.len() > README_BUDGET {
+        let mut end = README_BUDGET;
+        while !intro.is_char_boundary(end) {
+            end -= 1;
+        }
+        intro.truncate(end);
+        intro.push('…');
+    }
+    (!intro.is_empty()).then_some(intro)
+}
+
+/// LLM に agent.md の下書きを書かせる（全体を囲むコードフェンスは外す）
+pub async fn draft_agent_md(llm: &dyn LlmBackend, facts: &ProjectFacts) -> Result<String> {
+    let reply = llm.generate(&facts.to_prompt(), Some(INIT_SYSTEM_PROMPT)).await?;
+    let draft = strip_outer_fence(reply.trim());
+    if draft.is_empty() {
+        bail!("The model returned an empty agent.md");
+    }
+    Ok(format!("{}\n", draft))
+}
+
+fn strip_outer_fence(text: &str) -> &str {
+    let Some(rest) = text.strip_prefix("```") else {
+        return text;
+    };
+    let Some((_, body)) = rest.split_once('\n') else {
+        return text;
+    };
+    body.strip_suffix("```").map(str::trim).unwrap_or(text)
+}
+
+/// 書き出し先（既存のファイルがあり force でなければエラー）
+pub fn agent_md_target(root: &Path, force: bool) -> Result<PathBuf> {
+    let path = root.join(AGENT_MD);
+    if path.exists() && !force {
+        bail!("{} already exists; run /init --force to overwrite it", path.display());
+    }
+    Ok(path)
+}
+
+/// agent.md を書く（既存のファイルは force のときだけ上書き）
+pub fn write_agent_md(root: &Path, content: &str, force: bool) -> Result<PathBuf> {
+    let path = agent_md_target(root, force)?;
+    std::fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_gather_facts_from_manifests_and_readme() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("src/bin")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "").unwrap();
+        std::fs::write(root.join("Cargo.toml"), "[package]\nname = \"demo\"\n").unwrap();
+        std::fs::write(root.join("package.json"), r#"{"scripts": {"test": "vitest", "deploy": "x"}}"#).unwrap();
+        std::fs::write(root.join("pnpm-lock.yaml"), "").unwrap();
+        std::fs::write(root.join("Makefile"), ".PHONY: test\nCC := gcc\ntest: build\n\tcargo test\nbuild:\n\tcargo build\n%.o: %.c\n").unwrap();
+        std::fs::write(
+            root.join("README.md"),
+            "# demo\n\n[![ci](badge.svg)](ci)\n\nDemo is a tool\nfor demos.\n\nIt has parts.\n",
+        )
+        .unwrap();
+
+        let facts = ProjectFacts::gather(root).await;
+        assert_eq!(facts.language, Some("Rust"));
+        assert_eq!(facts.manifests, ["Cargo.toml", "package.json", "Makefile"]);
+        let commands: Vec<&str> = facts.commands.iter().map(|(_, c)| c.as_str()).collect();
+        assert_eq!(
+            commands,
+            ["cargo build", "cargo test", "cargo clippy --all-targets -- -D warnings", "pnpm run test", "make test", "make build"]
+        );
+        assert_eq!(facts.readme.as_deref(), Some("Demo is a tool\nfor demos.\n\nIt has parts."));
+        assert!(facts.tree.starts_with("./\n  src/\n    bin/"), "{}", facts.tree);
+
+        let prompt = facts.to_prompt();
+        assert!(prompt.contains("- test: `cargo test`\n"), "{}", prompt);
+        assert!(prompt.contains("## Build and test"), "{}", prompt);
+    }
+
+    #[test]
+    fn test_refuses_to_overwrite_without_force() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_agent_md(dir.path(), "# first\n", false).unwrap();
+        assert_eq!(path, dir.path().join(AGENT_MD));
+
+        let error = write_agent_md(dir.path(), "# second\n", false).unwrap_err();
+        assert!(error.to_string().contains("--force"), "{}", error);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "# first\n");
+
+        write_agent_md(dir.path(), "# second\n", true).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "# second\n");
+    }
+
+    #[test]
+    fn test_strip_outer_fence() {
+        assert_eq!(strip_outer_fence("```markdown\n# demo\n```"), "# demo");
+        assert_eq!(strip_outer_fence("# demo\n```sh\nls\n```"), "# demo\n```sh\nls\n```");
+    }
+}
@@ -0,0 +1,256 @@
+//! 覚えておく事柄（/remember・/memory・/forget）
+//!
+//! `<project>/.local-code/memory.md` と `~/.local-code/memory.md` に
+//! `- [2026-01-31 12:00] 内容` の箇条書きで保存し、システムプロンプトの「# Memory」に入れる
+
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+/// メモリファイルの名前（`.local-code/` の下）
+pub const MEMORY_FILE: &str = "memory.md";
+
+/// 保存先
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryScope {
+    /// ~/.local-code/memory.md（すべてのプロジェクト）
+    Global,
+    /// <project>/.local-code/memory.md
+    Project,
+}
+
+impl MemoryScope {
+    pub fn label(&self) -> &'static str {
+        match self {
+            MemoryScope::Global => "global",
+            MemoryScope::Project => "project",
+        }
+    }
+}
+
+/// 覚えている事柄1つ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryEntry {
+    pub scope: MemoryScope,
+    /// 追加した日時（手で書いた行にはない）
+    pub timestamp: Option<String>,
+    pub text: String,
+}
+
+impl MemoryEntry {
+    /// `- [日時] 内容` の1行を読む（箇条書きでない行は None）
+    fn parse(scope: MemoryScope, line: &str) -> Option<Self> {
+        let item = line.trim().strip_prefix("- ")?.trim();
+        let (timestamp, text) = match item.strip_prefix('[').and_then(|rest| rest.split_once("] ")) {
+            Some((timestamp, text)) => (Some(timestamp.to_string()), text.trim()),
+            None => (None, item),
+        };
+        (!text.is_empty()).then(|| Self { scope, timestamp, text: text.to_string() })
+    }
+
+    fn to_line(&self) -> String {
+        match &self.timestamp {
+            Some(timestamp) => format!("- [{}] {}", timestamp, self.text),
+            None => format!("- {}", self.text),
+        }
+    }
+}
+
+/// メモリファイル1つ
+#[derive(Debug, Clone)]
+struct MemoryFile {
+    scope: MemoryScope,
+    path: PathBuf,
+}
+
+impl MemoryFile {
+    /// ファイルの箇条書き（ファイルがなければ空）
+    fn read(&self) -> Vec<MemoryEntry> {
+        std::fs::read_to_string(&self.path)
+            .map(|text| text.lines().filter_map(|line| MemoryEntry::parse(self.scope, line)).collect())
+            .unwrap_or_default()
+    }
+
+    fn write(&self, entries: &[MemoryEntry]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let mut text = String::from("# Memory\n\n");
+        for entry in entries {
+            text.push_str(&entry.to_line());
+            text.push('\n');
+        }
+        std::fs::write(&self.path, text).with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
+
+/// グローバルとプロジェクトのメモリ（一覧の番号はグローバル、プロジェクトの順）
+#[derive(Debug, Clone, Default)]
+pub struct Memory {
+    files: Vec<MemoryFile>,
+    entries: Vec<MemoryEntry>,
+}
+
+impl Memory {
+    /// `global_dir/memory.md` と `project_root/.local-code/memory.md` を読み込む
+    pub fn load(project_root: Option<&Path>, global_dir: Option<&Path>) -> Self {
+        let mut files = Vec::new();
+        if let Some(dir) = global_dir {
+            files.push(MemoryFile { scope: MemoryScope::Global, path: dir.join(MEMORY_FILE) });
+        }
+        if let Some(root) = project_root {
+            files.push(MemoryFile {
+                scope: MemoryScope::Project,
+                path: root.join(".local-code").join(MEMORY_FILE),
+            });
+        }
+        let mut memory = Self { files, entries: Vec::new() };
+        memory.refresh();
+        memory
+    }
+
+    /// ファイルを読み直す（ほかのセッションや手での編集を反映）
+    pub fn refresh(&mut self) {
+        self.entries = self.files.iter().flat_map(MemoryFile::read).collect();
+    }
+
+    pub fn entries(&self) -> &[MemoryEntry] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 保存先のパス
+    pub fn path(&self, scope: MemoryScope) -> Option<&Path> {
+        self.file(scope).map(|file| file.path.as_path())
+    }
+
+    fn file(&self, scope: MemoryScope) -> Option<&MemoryFile> {
+        self.files.iter().find(|file| file.scope == scope)
+    }
+
+    /// 日時を付けて追加する（同じ内容がすでにあれば追加せず false）
+    pub fn remember(&mut self, text: &str, scope: MemoryScope) -> Result<bool> {
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if text.is_empty() {
+            bail!("Nothing to remember");
+        }
+        let Some(file) = self.file(scope).cloned() else {
+            bail!("No {} memory location available", scope.label());
+        };
+        let mut entries = file.read();
+        if entries.iter().any(|entry| entry.text == text) {
+            self.refresh();
+            return Ok(false);
+        }
+        entries.push(MemoryEntry {
+            scope,
+            timestamp: Some(chrono::Local::now().format("%Y-%m-%d %H:%M").to_string()),
+            text,
+        });
+        file.write(&entries)?;
+        self.refresh();
+        Ok(true)
+    }
+
+    /// 一覧の番号（1始まり）で削除する
+    pub fn forget(&mut self, index: usize) -> Result<MemoryEntry> {
+        self.refresh();
+        let Some(target) = index.checked_sub(1).and_then(|i| self.entries.get(i)).cloned() else {
+            bail!("No memory entry {} (see /memory)", index);
+        };
+        let Some(file) = self.file(target.scope).cloned() else {
+            bail!("No {} memory location available", target.scope.label());
+        };
+        let mut entries = file.read();
+        let Some(position) = entries.iter().position(|entry| *entry == target) else {
+            bail!("Memory entry {} changed on disk; run /memory and try again", index);
+        };
+        entries.remove(position);
+        file.write(&entries)?;
+        self.refresh();
+        Ok(target)
+    }
+
+    /// 番号付きの一覧（/memory）
+    pub fn list(&self) -> String {
+        if self.entries.is_empty() {
+            let hint = self
+                .path(MemoryScope::Project)
+                .map(|path| format!(" ({})", path.display()))
+                .unwrap_or_default();
+            return format!("No memory entries{}. Add one with /remember <text>.", hint);
+        }
+        let mut lines = Vec::new();
+        for (i, entry) in self.entries.iter().enumerate() {
+            let when = entry.timestamp.as_deref().map(|t| format!(" ({})", t)).unwrap_or_default();
+            lines.push(format!("{:>3}. [{}] {}{}", i + 1, entry.scope.label(), entry.text, when));
+        }
+        lines.push("Remove one with /forget <n>.".to_string());
+        lines.join("\n")
+    }
+
+    /// システムプロンプトの「# Memory」（何もなければ None）
+    pub fn as_system_prompt(&self) -> Option<String> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        let items: Vec<String> = self.entries.iter().map(|entry| format!("- {}", entry.text)).collect();
+        Some(format!(
+            "# Memory\nThe user asked you to remember the following. Follow it unless told otherwise:\n{}\n",
+            items.join("\n")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remember_forget_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("project");
+        let global = dir.path().join("global");
+        let mut memory = Memory::load(Some(&project), Some(&global));
+        assert!(memory.is_empty());
+
+        assert!(memory.remember("we use thiserror,\n never anyhow in lib code", MemoryScope::Project).unwrap());
+        assert!(memory.remember("answer in Japanese", MemoryScope::Global).unwrap());
+        assert!(!memory.remember("we use thiserror, never anyhow in lib code", MemoryScope::Project).unwrap());
+        assert!(memory.remember("  ", MemoryScope::Project).is_err());
+
+        let saved = std::fs::read_to_string(project.join(".local-code/memory.md")).unwrap();
+        assert!(saved.starts_with("# Memory\n\n- ["), "{}", saved);
+        assert!(saved.ends_with("] we use thiserror, never anyhow in lib code\n"), "{}", saved);
+
+        // 別のセッションから見ても同じ一覧になる
+        let reloaded = Memory::load(Some(&project), Some(&global));
+        let texts: Vec<&str> = reloaded.entries().iter().map(|e| e.text.as_str()).collect();
+        assert_eq!(texts, ["answer in Japanese", "we use thiserror, never anyhow in lib code"]);
+        assert!(reloaded.list().contains("  2. [project] we use thiserror"), "{}", reloaded.list());
+
+        let removed = memory.forget(1).unwrap();
+        assert_eq!((removed.scope, removed.text.as_str()), (MemoryScope::Global, "answer in Japanese"));
+        assert!(memory.forget(2).is_err());
+        assert_eq!(memory.entries().len(), 1);
+        assert!(Memory::load(None, Some(&global)).is_empty());
+    }
+
+    #[test]
+    fn test_hand_written_lines_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join(".local-code")).unwrap();
+        std::fs::write(dir.path().join(".local-code/memory.md"), "# Notes\n\n- run cargo fmt\nfree text\n").unwrap();
+        let mut memory = Memory::load(Some(dir.path()), None);
+        assert_eq!(memory.entries()[0].timestamp, None);
+
+        memory.remember("prefer iterators", MemoryScope::Project).unwrap();
+        assert_eq!(memory.entries()[0].text, "run cargo fmt");
+        let prompt = memory.as_system_prompt().unwrap();
+        assert!(prompt.ends_with("- run cargo fmt\n- prefer iterators\n"), "{}", prompt);
+        assert!(memory.remember("x", MemoryScope::Global).is_err());
+    }
+}
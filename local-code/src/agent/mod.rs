@@ -5,11 +5,40 @@ pub mod conversation;
 pub mod history;
 pub mod compression;
 pub mod verification;
+pub mod debug;
+pub mod environment;
+pub mod session;
+pub mod encryption;
+pub mod status;
+pub mod prompt;
+pub mod tool_results;
+pub mod tool_output;
+pub mod tool_stats;
+pub mod turn;
+pub mod event;
+pub mod confirmation;
+pub mod memory;
+pub mod init;
+pub mod export;
 
 pub use context::AgentContext;
 pub use mode::{Mode, ModeManager};
-pub use core::{Agent, AgentConfig};
-pub use conversation::{Conversation, Message, Role};
+pub use core::{Agent, AgentConfig, CompressionNotice, DEFAULT_CONTEXT_WINDOW};
+pub use conversation::{context_warning, Conversation, Exchange, Message, MessageOrigin, Role, TokenBreakdown};
 pub use history::{HistoryManager, HistoryEntry};
 pub use compression::{ContextCompressor, CompressionConfig, CompressedConversation};
-pub use verification::{CodeVerifier, VerificationResult};
+pub use verification::{BlockReport, CodeFixer, CodeVerifier, VerificationPipeline, VerificationReport, VerificationResult};
+pub use debug::PromptDebugger;
+pub use environment::{EnvFingerprint, EnvProber};
+pub use session::{ResetAction, ResetTarget, SessionResetHub, SessionResettable, SessionTransition};
+pub use encryption::{PassphraseSource, StorageCipher};
+pub use status::{HealthState, StatusProvider, StatusRegistry, SubsystemStatus};
+pub use prompt::SystemPrompt;
+pub use tool_results::{PinSource, PinStage, PinnedResult, ToolResultRecord, ToolResultStore};
+pub use tool_output::{OutputRange, ReadToolOutputTool, ToolOutputManager};
+pub use tool_stats::{SessionStats, ToolStats, ToolUsage};
+pub use turn::{ToolCallRecord, TurnRecord, VerificationRecord};
+pub use event::AgentEvent;
+pub use memory::{Memory, MemoryEntry, MemoryScope};
+pub use export::{ConversationExporter, ExportFormat};
+pub use confirmation::{AutoApprove, AutoDeny, ConfirmationPolicy, SessionAllowSet, ToolConfirmation};
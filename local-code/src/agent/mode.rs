@@ -1,77 +1,75 @@
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+use super::status::{StatusProvider, SubsystemStatus};
+use crate::tools::ToolEffects;
+
 /// エージェントの動作モード
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub enum Mode {
-    /// 計画モード: 読み取り専用ツールのみ使用可能
+    /// 計画モード: 読み取り専用（ReadOnly）のツールのみ使用可能
     Plan,
     /// 実行モード: 全ツール使用可能
     #[default]
     Execute,
+    /// 編集自動承認モード: 全ツール使用可能、ファイル書き込みの確認を省略
+    AcceptEdits,
+    /// 設定ファイル（`[agent.modes]`）で定義したモード
+    Custom(String),
 }
 
 impl Mode {
-    /// モードごとに許可されるツール名のリスト
-    pub fn allowed_tools(&self) -> Vec<&'static str> {
+    /// 副作用の種類がこのモードで許可されるか（許可リストがない場合の既定）
+    pub fn allows(&self, effects: ToolEffects) -> bool {
         match self {
-            Mode::Plan => vec![
-                "read",
-                "glob",
-                "grep",
-                "git_status",
-                "git_diff",
-                "git_log",
-                "lsp_definition",
-                "lsp_references",
-                "lsp_diagnostics",
-            ],
-            Mode::Execute => vec![
-                "read",
-                "write",
-                "edit",
-                "bash",
-                "glob",
-                "grep",
-                "git_status",
-                "git_diff",
-                "git_add",
-                "git_commit",
-                "git_log",
-                "lsp_definition",
-                "lsp_references",
-                "lsp_diagnostics",
-            ],
-        }
-    }
-
-    /// 指定ツールが現在のモードで使用可能かチェック
-    pub fn is_tool_allowed(&self, tool_name: &str) -> bool {
-        self.allowed_tools().contains(&tool_name)
+            Mode::Plan => effects == ToolEffects::ReadOnly,
+            Mode::Execute | Mode::AcceptEdits => true,
+            Mode::Custom(_) => false,
+        }
     }
 
     /// モード名を文字列で取得
-    pub fn as_str(&self) -> &'static str {
+    pub fn as_str(&self) -> &str {
         match self {
             Mode::Plan => "plan",
             Mode::Execute => "execute",
+            Mode::AcceptEdits => "accept-edits",
+            Mode::Custom(name) => name,
         }
     }
 
-    /// 文字列からモードを取得
+    /// 文字列からモードを取得（組み込み以外の名前は Custom）
+    ///
+    /// 名前は英数字・`-`・`_` のみ。Custom が定義済みかは `ModeManager::resolve` で確認する
     pub fn parse_mode(s: &str) -> Option<Self> {
-        match s.to_lowercase().as_str() {
+        let name = s.trim().to_lowercase();
+        match name.as_str() {
             "plan" => Some(Mode::Plan),
             "execute" | "exec" => Some(Mode::Execute),
+            "accept-edits" | "accept_edits" | "accept" => Some(Mode::AcceptEdits),
+            "" => None,
+            _ if name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') => {
+                Some(Mode::Custom(name))
+            }
             _ => None,
         }
     }
 
-    /// 次のモードを取得（サイクル: Plan → Execute → Plan）
+    /// 次のモードを取得（サイクル: Plan → Execute → AcceptEdits → Plan、Custom からは Plan）
     pub fn next(&self) -> Self {
         match self {
             Mode::Plan => Mode::Execute,
-            Mode::Execute => Mode::Plan,
+            Mode::Execute => Mode::AcceptEdits,
+            Mode::AcceptEdits | Mode::Custom(_) => Mode::Plan,
+        }
+    }
+
+    /// 実行前の確認が必要か（AcceptEdits ではファイル書き込みのみ省略）
+    pub fn requires_confirmation(&self, effects: ToolEffects) -> bool {
+        match self {
+            Mode::AcceptEdits if effects == ToolEffects::Writes => false,
+            _ => effects.requires_confirmation(),
         }
     }
 
@@ -80,6 +78,32 @@ impl Mode {
         match self {
             Mode::Plan => "📋",
             Mode::Execute => "⏵⏵",
+            Mode::AcceptEdits => "✎",
+            Mode::Custom(_) => "⚙",
+        }
+    }
+}
+
+/// 許可リストの1項目（`lsp_*` のような glob パターンも可）
+#[derive(Debug, Clone)]
+struct ToolPattern {
+    source: String,
+    /// パターンとして不正な場合は None（完全一致で扱う）
+    pattern: Option<glob::Pattern>,
+}
+
+impl ToolPattern {
+    fn new(source: &str) -> Self {
+        Self {
+            source: source.to_string(),
+            pattern: glob::Pattern::new(source).ok(),
+        }
+    }
+
+    fn matches(&self, tool_name: &str) -> bool {
+        match &self.pattern {
+            Some(pattern) => pattern.matches(tool_name),
+            None => self.source == tool_name,
         }
     }
 }
@@ -91,21 +115,117 @@ impl std::fmt::Display for Mode {
 }
 
 /// モードマネージャー - スレッドセーフなモード管理
+///
+/// ツールの可否は各ツールが宣言した `ToolEffects` で判定する
+/// モードごとの許可リスト（`[agent.modes]`）があればそちらを優先する
 #[derive(Clone)]
 pub struct ModeManager {
     current: Arc<RwLock<Mode>>,
+    /// 登録済みツールの副作用（未登録のツールは Executes 扱い）
+    tool_effects: Arc<HashMap<String, ToolEffects>>,
+    /// モード名 → 許可するツールのパターン
+    allowlists: Arc<BTreeMap<String, Vec<ToolPattern>>>,
+    /// 設定のうちモード名として使えなかったもの
+    invalid_modes: Arc<Vec<String>>,
+    /// 実行中のスキルの `allowed-tools`（モードの判定に加えて絞り込む）
+    skill_restriction: Arc<RwLock<Option<SkillRestriction>>>,
+}
+
+/// スキルによるツールの絞り込み
+#[derive(Debug, Clone)]
+struct SkillRestriction {
+    skill: String,
+    patterns: Vec<ToolPattern>,
+}
+
+impl SkillRestriction {
+    fn allows(&self, tool_name: &str) -> bool {
+        self.patterns.iter().any(|p| p.matches(tool_name))
+    }
 }
 
 impl ModeManager {
     pub fn new(initial_mode: Mode) -> Self {
         Self {
             current: Arc::new(RwLock::new(initial_mode)),
+            tool_effects: Arc::new(HashMap::new()),
+            allowlists: Arc::new(BTreeMap::new()),
+            invalid_modes: Arc::new(Vec::new()),
+            skill_restriction: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// モードごとの許可リストを設定（組み込みモード名なら上書き、それ以外は新しいモード）
+    pub fn with_allowlists(mut self, modes: &BTreeMap<String, Vec<String>>) -> Self {
+        let mut allowlists = BTreeMap::new();
+        let mut invalid = Vec::new();
+        for (name, patterns) in modes {
+            match Mode::parse_mode(name) {
+                Some(mode) => {
+                    let patterns = patterns.iter().map(|p| ToolPattern::new(p)).collect();
+                    allowlists.insert(mode.as_str().to_string(), patterns);
+                }
+                None => invalid.push(name.clone()),
+            }
         }
+        self.allowlists = Arc::new(allowlists);
+        self.invalid_modes = Arc::new(invalid);
+        self
+    }
+
+    /// 名前からモードを取得（Custom は設定で定義されている場合のみ）
+    pub fn resolve(&self, name: &str) -> Option<Mode> {
+        match Mode::parse_mode(name)? {
+            Mode::Custom(name) if !self.allowlists.contains_key(&name) => None,
+            mode => Some(mode),
+        }
+    }
+
+    /// 使用できるモード名（組み込み + 設定で定義したもの）
+    pub fn mode_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = [Mode::Plan, Mode::Execute, Mode::AcceptEdits]
+            .iter()
+            .map(|m| m.as_str().to_string())
+            .collect();
+        for name in self.allowlists.keys() {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        names
+    }
+
+    /// 設定の問題（不正なモード名、どのツールにも一致しないパターン）
+    ///
+    /// `with_tool_effects` の後に呼ぶ
+    pub fn config_warnings(&self) -> Vec<String> {
+        let mut warnings: Vec<String> = self
+            .invalid_modes
+            .iter()
+            .map(|name| format!("Invalid mode name in [agent.modes]: '{}'", name))
+            .collect();
+        for (mode, patterns) in self.allowlists.iter() {
+            for pattern in patterns {
+                if !self.tool_effects.keys().any(|tool| pattern.matches(tool)) {
+                    warnings.push(format!(
+                        "[agent.modes] {}: '{}' does not match any tool",
+                        mode, pattern.source
+                    ));
+                }
+            }
+        }
+        warnings
+    }
+
+    /// ツールの副作用を設定（`ToolRegistry::effects` の結果を渡す）
+    pub fn with_tool_effects(mut self, effects: HashMap<String, ToolEffects>) -> Self {
+        self.tool_effects = Arc::new(effects);
+        self
     }
 
     /// 現在のモードを取得
     pub async fn current(&self) -> Mode {
-        *self.current.read().await
+        self.current.read().await.clone()
     }
 
     /// モードを切り替え
@@ -123,14 +243,126 @@ impl ModeManager {
         self.set(Mode::Execute).await;
     }
 
-    /// ツールが現在のモードで使用可能かチェック
+    /// 同期コンテキストから現在のモードを取得（ロック中なら None）
+    pub fn try_current(&self) -> Option<Mode> {
+        self.current.try_read().ok().map(|mode| mode.clone())
+    }
+
+    /// 次のモードへ即座に切り替え（REPL の Shift+Tab 用、ロック中なら None）
+    pub fn cycle(&self) -> Option<Mode> {
+        let mut current = self.current.try_write().ok()?;
+        *current = current.next();
+        Some(current.clone())
+    }
+
+    /// ツールの副作用
+    pub fn effects_of(&self, tool_name: &str) -> ToolEffects {
+        self.tool_effects
+            .get(tool_name)
+            .copied()
+            .unwrap_or(ToolEffects::Executes)
+    }
+
+    /// ツールの実行前に確認が必要か（現在のモードを考慮）
+    ///
+    /// 確認コールバックは同期コンテキストで動くため `try_current` を使い、
+    /// モードが読めない場合は確認する側に倒す
+    pub fn requires_confirmation(&self, tool_name: &str) -> bool {
+        self.call_requires_confirmation(self.effects_of(tool_name))
+    }
+
+    /// 副作用が `effects` の呼び出しに確認が必要か（`Tool::call_effects` の結果を渡す）
+    pub fn call_requires_confirmation(&self, effects: ToolEffects) -> bool {
+        match self.try_current() {
+            Some(mode) => mode.requires_confirmation(effects),
+            None => effects.requires_confirmation(),
+        }
+    }
+
+    /// ツールが指定モードで使用可能か
+    fn allows(&self, mode: &Mode, tool_name: &str) -> bool {
+        self.allows_call(mode, tool_name, self.effects_of(tool_name))
+    }
+
+    /// 副作用が `effects` の呼び出しが指定モードで使用可能か（許可リストがあれば名前で判定）
+    fn allows_call(&self, mode: &Mode, tool_name: &str, effects: ToolEffects) -> bool {
+        match self.allowlists.get(mode.as_str()) {
+            Some(patterns) => patterns.iter().any(|p| p.matches(tool_name)),
+            None => mode.allows(effects),
+        }
+    }
+
+    /// スキルの `allowed-tools` でツールを絞り込む（None なら絞り込まない）
+    ///
+    /// スキルのプロンプトを処理し終えたら `clear_skill_restriction` で戻す
+    pub async fn restrict_to_skill(&self, skill: &str, allowed: Option<&[String]>) {
+        *self.skill_restriction.write().await = allowed.map(|tools| SkillRestriction {
+            skill: skill.to_string(),
+            patterns: tools.iter().map(|t| ToolPattern::new(t)).collect(),
+        });
+    }
+
+    /// スキルによる絞り込みを解除
+    pub async fn clear_skill_restriction(&self) {
+        *self.skill_restriction.write().await = None;
+    }
+
+    /// ツールが現在のモード（と実行中のスキル）で使用可能かチェック
     pub async fn is_tool_allowed(&self, tool_name: &str) -> bool {
-        self.current().await.is_tool_allowed(tool_name)
+        self.is_call_allowed(tool_name, self.effects_of(tool_name)).await
+    }
+
+    /// 副作用が `effects` の呼び出しが現在のモード（と実行中のスキル）で使用可能かチェック
+    pub async fn is_call_allowed(&self, tool_name: &str, effects: ToolEffects) -> bool {
+        let restriction = self.skill_restriction.read().await;
+        self.allows_call(&self.current().await, tool_name, effects)
+            && restriction.as_ref().is_none_or(|r| r.allows(tool_name))
+    }
+
+    /// ツールが使用できない理由（LLMに返すメッセージ）
+    pub async fn denial_message(&self, tool_name: &str) -> String {
+        self.call_denial_message(tool_name, self.effects_of(tool_name)).await
+    }
+
+    /// 副作用が `effects` の呼び出しが使用できない理由
+    pub async fn call_denial_message(&self, tool_name: &str, effects: ToolEffects) -> String {
+        let mode = self.current().await;
+        if let Some(restriction) = self.skill_restriction.read().await.as_ref() {
+            if self.allows_call(&mode, tool_name, effects) && !restriction.allows(tool_name) {
+                return format!(
+                    "Tool '{}' is not in the allowed tools of skill '{}'. Use one of its allowed tools instead.",
+                    tool_name, restriction.skill
+                );
+            }
+        }
+        if self.allowlists.contains_key(mode.as_str()) {
+            return format!(
+                "Tool '{}' is not in the allowed tools of {} mode. Ask the user to switch modes if it is needed.",
+                tool_name, mode
+            );
+        }
+        match mode {
+            Mode::Plan => format!(
+                "Tool '{}' {} and is not allowed in plan mode. Plan mode is read-only; ask the user to switch with /execute.",
+                tool_name,
+                effects.describe()
+            ),
+            Mode::Execute | Mode::AcceptEdits | Mode::Custom(_) => format!("Tool '{}' is not allowed in {} mode", tool_name, mode),
+        }
     }
 
-    /// 現在許可されているツール名一覧を取得
-    pub async fn allowed_tools(&self) -> Vec<&'static str> {
-        self.current().await.allowed_tools()
+    /// 現在許可されているツール名一覧を取得（名前順）
+    pub async fn allowed_tools(&self) -> Vec<String> {
+        let mode = self.current().await;
+        let restriction = self.skill_restriction.read().await;
+        let mut tools: Vec<String> = self
+            .tool_effects
+            .keys()
+            .filter(|name| self.allows(&mode, name) && restriction.as_ref().is_none_or(|r| r.allows(name)))
+            .cloned()
+            .collect();
+        tools.sort();
+        tools
     }
 }
 
@@ -140,22 +372,46 @@ impl Default for ModeManager {
     }
 }
 
+#[async_trait::async_trait]
+impl StatusProvider for ModeManager {
+    async fn status(&self) -> SubsystemStatus {
+        let mode = self.current().await;
+        let detail = format!("{}: {}", mode, self.allowed_tools().await.join(", "));
+        let warnings = self.config_warnings();
+        match warnings.first() {
+            None => SubsystemStatus::ok("mode", detail),
+            Some(first) => SubsystemStatus::degraded("mode", format!("{} ({})", detail, first))
+                .with_hint(Some("Fix [agent.modes] in the config file")),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn effects() -> HashMap<String, ToolEffects> {
+        [
+            ("read", ToolEffects::ReadOnly),
+            ("glob", ToolEffects::ReadOnly),
+            ("write", ToolEffects::Writes),
+            ("bash", ToolEffects::Executes),
+            ("word_count", ToolEffects::Executes),
+        ]
+        .into_iter()
+        .map(|(name, effects)| (name.to_string(), effects))
+        .collect()
+    }
+
     #[test]
-    fn test_mode_allowed_tools() {
-        let plan = Mode::Plan;
-        assert!(plan.is_tool_allowed("read"));
-        assert!(plan.is_tool_allowed("glob"));
-        assert!(!plan.is_tool_allowed("write"));
-        assert!(!plan.is_tool_allowed("bash"));
+    fn test_mode_allows_effects() {
+        assert!(Mode::Plan.allows(ToolEffects::ReadOnly));
+        assert!(!Mode::Plan.allows(ToolEffects::Writes));
+        assert!(!Mode::Plan.allows(ToolEffects::Executes));
 
-        let execute = Mode::Execute;
-        assert!(execute.is_tool_allowed("read"));
-        assert!(execute.is_tool_allowed("write"));
-        assert!(execute.is_tool_allowed("bash"));
+        assert!(Mode::Execute.allows(ToolEffects::ReadOnly));
+        assert!(Mode::Execute.allows(ToolEffects::Writes));
+        assert!(Mode::Execute.allows(ToolEffects::Executes));
     }
 
     #[test]
@@ -164,19 +420,132 @@ mod tests {
         assert_eq!(Mode::parse_mode("PLAN"), Some(Mode::Plan));
         assert_eq!(Mode::parse_mode("execute"), Some(Mode::Execute));
         assert_eq!(Mode::parse_mode("exec"), Some(Mode::Execute));
-        assert_eq!(Mode::parse_mode("invalid"), None);
+        assert_eq!(Mode::parse_mode("accept-edits"), Some(Mode::AcceptEdits));
+        assert_eq!(Mode::parse_mode("accept"), Some(Mode::AcceptEdits));
+        assert_eq!(Mode::parse_mode("Review"), Some(Mode::Custom("review".to_string())));
+        assert_eq!(Mode::parse_mode("not a mode"), None);
+        assert_eq!(Mode::parse_mode(""), None);
+    }
+
+    fn modes(entries: &[(&str, &[&str])]) -> BTreeMap<String, Vec<String>> {
+        entries
+            .iter()
+            .map(|(name, tools)| (name.to_string(), tools.iter().map(|t| t.to_string()).collect()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_allowlists_override_and_add_modes() {
+        let manager = ModeManager::new(Mode::Plan)
+            .with_allowlists(&modes(&[("plan", &["read", "g*"]), ("review", &["read", "bash"])]))
+            .with_tool_effects(effects());
+
+        // 設定したリストが組み込みの判定より優先される
+        assert_eq!(manager.allowed_tools().await, vec!["glob", "read"]);
+
+        let review = manager.resolve("review").unwrap();
+        assert_eq!(review, Mode::Custom("review".to_string()));
+        manager.set(review).await;
+        assert!(manager.is_tool_allowed("bash").await);
+        assert!(!manager.is_tool_allowed("write").await);
+        assert!(manager.denial_message("write").await.contains("review mode"));
+
+        // 設定のないモードは組み込みのまま
+        manager.to_execute().await;
+        assert!(manager.is_tool_allowed("write").await);
+
+        assert_eq!(manager.mode_names(), vec!["plan", "execute", "accept-edits", "review"]);
+        assert_eq!(manager.resolve("undefined"), None);
+    }
+
+    #[test]
+    fn test_config_warnings() {
+        let manager = ModeManager::new(Mode::Execute)
+            .with_allowlists(&modes(&[("review", &["read", "lsp_*", "typo"]), ("bad name", &["read"])]))
+            .with_tool_effects(effects());
+        assert_eq!(
+            manager.config_warnings(),
+            vec![
+                "Invalid mode name in [agent.modes]: 'bad name'".to_string(),
+                "[agent.modes] review: 'lsp_*' does not match any tool".to_string(),
+                "[agent.modes] review: 'typo' does not match any tool".to_string(),
+            ]
+        );
     }
 
     #[tokio::test]
     async fn test_mode_manager() {
-        let manager = ModeManager::new(Mode::Execute);
+        let manager = ModeManager::new(Mode::Execute).with_tool_effects(effects());
         assert_eq!(manager.current().await, Mode::Execute);
 
         manager.to_plan().await;
         assert_eq!(manager.current().await, Mode::Plan);
+        assert!(manager.is_tool_allowed("read").await);
+        assert!(!manager.is_tool_allowed("write").await);
         assert!(!manager.is_tool_allowed("bash").await);
+        assert_eq!(manager.allowed_tools().await, vec!["glob", "read"]);
 
         manager.to_execute().await;
         assert!(manager.is_tool_allowed("bash").await);
+        assert!(manager.allowed_tools().await.contains(&"word_count".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_bash_denied_in_plan_suggests_execute() {
+        let manager = ModeManager::new(Mode::Plan).with_tool_effects(effects());
+        let message = manager.denial_message("bash").await;
+        assert!(message.contains("runs commands"));
+        assert!(message.contains("/execute"));
+    }
+
+    #[tokio::test]
+    async fn test_skill_restriction_narrows_mode() {
+        let manager = ModeManager::new(Mode::Plan).with_tool_effects(effects());
+        manager.restrict_to_skill("review", Some(&["read".to_string(), "bash".to_string()])).await;
+        assert_eq!(manager.allowed_tools().await, vec!["read"]);
+        assert!(!manager.is_tool_allowed("glob").await);
+        assert!(manager.denial_message("glob").await.contains("skill 'review'"));
+        // モードで禁止されているツールはモードの理由を返す
+        assert!(manager.denial_message("bash").await.contains("/execute"));
+
+        // 共有している複製にも効き、解除すれば元に戻る
+        let shared = manager.clone();
+        shared.clear_skill_restriction().await;
+        assert!(manager.is_tool_allowed("glob").await);
+        manager.restrict_to_skill("plain", None).await;
+        assert!(manager.is_tool_allowed("glob").await);
+    }
+
+    #[test]
+    fn test_confirmation_follows_effects() {
+        let manager = ModeManager::new(Mode::Execute).with_tool_effects(effects());
+        assert!(!manager.requires_confirmation("read"));
+        assert!(manager.requires_confirmation("write"));
+        assert!(manager.requires_confirmation("bash"));
+        // 未登録のツールは確認が必要な側に倒す
+        assert!(manager.requires_confirmation("unknown"));
+    }
+
+    #[test]
+    fn test_cycle_order() {
+        let manager = ModeManager::new(Mode::Plan);
+        assert_eq!(manager.cycle(), Some(Mode::Execute));
+        assert_eq!(manager.cycle(), Some(Mode::AcceptEdits));
+        assert_eq!(manager.cycle(), Some(Mode::Plan));
+        assert_eq!(manager.try_current(), Some(Mode::Plan));
+
+        assert_eq!(Mode::Custom("review".to_string()).next(), Mode::Plan);
+    }
+
+    #[test]
+    fn test_accept_edits_skips_write_confirmation_only() {
+        let manager = ModeManager::new(Mode::AcceptEdits).with_tool_effects(effects());
+        assert!(!manager.requires_confirmation("write"));
+        // コマンド実行（bash, git_commit など）は引き続き確認する
+        assert!(manager.requires_confirmation("bash"));
+        assert!(manager.requires_confirmation("unknown"));
+        assert!(!Mode::AcceptEdits.requires_confirmation(ToolEffects::Writes));
+        assert!(Mode::AcceptEdits.requires_confirmation(ToolEffects::Executes));
+        assert!(Mode::Execute.requires_confirmation(ToolEffects::Writes));
     }
 }
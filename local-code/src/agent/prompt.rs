@@ -0,0 +1,98 @@
+//! システムプロンプトの組み立て
+//!
+//! Ollama / llama.cpp はリクエスト間でプロンプトの先頭部分をキャッシュする。
+//! 静的なセクションを固定順で先に、ターンごとに変わりうるセクションを末尾に置き、
+//! 状態が変わらなければ先頭部分がバイト単位で一致するようにする
+
+/// セクション間の区切り
+const SEPARATOR: &str = "\n\n";
+
+#[derive(Debug, Clone)]
+struct Section {
+    name: &'static str,
+    body: String,
+}
+
+/// セクションに分けたシステムプロンプト
+#[derive(Debug, Clone, Default)]
+pub struct SystemPrompt {
+    /// セッション中に変わらないセクション（追加順）
+    static_sections: Vec<Section>,
+    /// ターンごとに変わりうるセクション（常に末尾）
+    dynamic_sections: Vec<Section>,
+}
+
+impl SystemPrompt {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 静的なセクションを追加（空なら無視）
+    pub fn push_static(&mut self, name: &'static str, body: impl Into<String>) {
+        if let Some(section) = Self::section(name, body) {
+            self.static_sections.push(section);
+        }
+    }
+
+    /// 変わりうるセクションを追加（静的なセクションより後に置かれる）
+    pub fn push_dynamic(&mut self, name: &'static str, body: impl Into<String>) {
+        if let Some(section) = Self::section(name, body) {
+            self.dynamic_sections.push(section);
+        }
+    }
+
+    fn section(name: &'static str, body: impl Into<String>) -> Option<Section> {
+        let body = body.into().trim().to_string();
+        (!body.is_empty()).then_some(Section { name, body })
+    }
+
+    /// セクション名（出力順）
+    pub fn section_names(&self) -> Vec<&'static str> {
+        self.static_sections
+            .iter()
+            .chain(&self.dynamic_sections)
+            .map(|s| s.name)
+            .collect()
+    }
+
+    /// プロンプト文字列
+    pub fn render(&self) -> String {
+        self.static_sections
+            .iter()
+            .chain(&self.dynamic_sections)
+            .map(|s| s.body.as_str())
+            .collect::<Vec<_>>()
+            .join(SEPARATOR)
+    }
+
+    /// 静的な先頭部分のバイト数（動的セクションとの区切りは含まない）
+    pub fn static_prefix_len(&self) -> usize {
+        let bodies: usize = self.static_sections.iter().map(|s| s.body.len()).sum();
+        bodies + SEPARATOR.len() * self.static_sections.len().saturating_sub(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dynamic_sections_follow_static_prefix() {
+        let mut prompt = SystemPrompt::new();
+        prompt.push_static("instructions", "You are a coding assistant.\n");
+        prompt.push_dynamic("todos", "- [ ] write tests");
+        prompt.push_static("tools", "Available tools:");
+        prompt.push_static("empty", "   ");
+
+        assert_eq!(prompt.section_names(), vec!["instructions", "tools", "todos"]);
+        let rendered = prompt.render();
+        assert_eq!(rendered, "You are a coding assistant.\n\nAvailable tools:\n\n- [ ] write tests");
+        assert_eq!(&rendered[..prompt.static_prefix_len()], "You are a coding assistant.\n\nAvailable tools:");
+
+        // 動的セクションが変わっても先頭部分は同じ
+        let mut changed = prompt.clone();
+        changed.dynamic_sections.clear();
+        changed.push_dynamic("todos", "- [x] write tests");
+        assert_eq!(rendered[..prompt.static_prefix_len()], changed.render()[..changed.static_prefix_len()]);
+    }
+}
@@ -0,0 +1,329 @@
+//! セッション遷移時のリセット
+//!
+//! /load やモデル切り替え等でセッションが変わったとき、
+//! 状態を持つコンポーネントに SessionReset を伝播する。
+//! 遷移ごとに何をリセットするかは `reset_action` の表で一元的に定義する
+
+use std::fmt;
+use std::sync::Arc;
+
+/// セッションの遷移
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionTransition {
+    /// 保存済み会話の読み込み（/load）
+    Load,
+    /// 作業ディレクトリの変更（/cd）
+    ChangeDirectory,
+    /// 画面と一時状態のクリア（/clear）
+    Clear,
+    /// モデルの切り替え（/model）
+    ModelChange,
+}
+
+impl fmt::Display for SessionTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SessionTransition::Load => "load",
+            SessionTransition::ChangeDirectory => "cd",
+            SessionTransition::Clear => "clear",
+            SessionTransition::ModelChange => "model change",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// コンポーネントが行うリセットの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetAction {
+    /// 状態をすべて破棄
+    Full,
+    /// キャッシュと直近の状態のみ破棄
+    Caches,
+    /// モデルに紐づく状態を新しいモデルに付け替え
+    RebindModel,
+}
+
+/// リセット対象のコンポーネント
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetTarget {
+    /// num_ctx のネゴシエーション状態（LLMクライアント）
+    ContextWindow,
+    /// 環境フィンガープリント
+    EnvFingerprint,
+    /// ツールの使用統計（/stats）
+    ToolStats,
+    /// セッション中に「常に許可」したツール実行
+    ToolApprovals,
+    /// /use で選べるツール結果
+    ToolResults,
+    /// /use で次のメッセージに添付する送信待ちのツール結果
+    StagedPins,
+    /// 切り詰めたツール結果の全文（read_tool_output）
+    ToolOutput,
+    /// 言語サーバーに didOpen したドキュメント
+    LspDocuments,
+}
+
+impl fmt::Display for ResetTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ResetTarget::ContextWindow => "context window",
+            ResetTarget::EnvFingerprint => "environment fingerprint",
+            ResetTarget::ToolStats => "tool stats",
+            ResetTarget::ToolApprovals => "tool approvals",
+            ResetTarget::ToolResults => "tool results",
+            ResetTarget::StagedPins => "staged pins",
+            ResetTarget::ToolOutput => "tool output",
+            ResetTarget::LspDocuments => "LSP documents",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// 遷移 → リセットの対応表
+///
+/// コンポーネントを追加した場合は全遷移について対応を決める必要がある
+pub fn reset_action(target: ResetTarget, transition: SessionTransition) -> Option<ResetAction> {
+    use ResetAction::*;
+    use SessionTransition::*;
+    match (target, transition) {
+        // ctx はモデルごとに保持しているため /load では直近の決定のみ破棄
+        (ResetTarget::ContextWindow, Load | ChangeDirectory) => Some(Caches),
+        (ResetTarget::ContextWindow, Clear) => None,
+        (ResetTarget::ContextWindow, ModelChange) => Some(RebindModel),
+        (ResetTarget::EnvFingerprint, Load | ChangeDirectory) => Some(Full),
+        (ResetTarget::EnvFingerprint, Clear | ModelChange) => None,
+        // 統計は会話に対応させる（/clear・/load で数え直し、モデルや cd では続ける）
+        (ResetTarget::ToolStats, Load | Clear) => Some(Full),
+        (ResetTarget::ToolStats, ChangeDirectory | ModelChange) => None,
+        // 許可は会話とプロジェクトに対応させる（モデルを替えても続ける）
+        (ResetTarget::ToolApprovals, Load | Clear | ChangeDirectory) => Some(Full),
+        (ResetTarget::ToolApprovals, ModelChange) => None,
+        // ツール結果の ID は会話の中でだけ意味を持ち、パスは作業ディレクトリに対応する
+        (ResetTarget::ToolResults | ResetTarget::StagedPins | ResetTarget::ToolOutput, Load | Clear | ChangeDirectory) => {
+            Some(Full)
+        }
+        (ResetTarget::ToolResults | ResetTarget::StagedPins | ResetTarget::ToolOutput, ModelChange) => None,
+        // 開いたドキュメントは閉じる（サーバーはそのまま、ファイルは次に使うときに開き直す）
+        (ResetTarget::LspDocuments, Load | Clear | ChangeDirectory) => Some(Full),
+        (ResetTarget::LspDocuments, ModelChange) => None,
+    }
+}
+
+/// セッション遷移時にリセットされるコンポーネント
+pub trait SessionResettable: Send + Sync {
+    /// 対応表上のコンポーネント
+    fn reset_target(&self) -> ResetTarget;
+
+    /// リセットを実行
+    fn reset(&self, action: ResetAction);
+}
+
+/// リセットハンドラーの登録先
+#[derive(Default)]
+pub struct SessionResetHub {
+    handlers: Vec<Arc<dyn SessionResettable>>,
+}
+
+impl SessionResetHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// リセットハンドラーを登録
+    pub fn register(&mut self, handler: Arc<dyn SessionResettable>) {
+        self.handlers.push(handler);
+    }
+
+    /// 同じコンポーネントのハンドラーを除いてから登録
+    pub fn replace(&mut self, handler: Arc<dyn SessionResettable>) {
+        let target = handler.reset_target();
+        self.handlers.retain(|h| h.reset_target() != target);
+        self.handlers.push(handler);
+    }
+
+    /// 遷移を伝播し、実行したリセットを返す
+    pub fn emit(&self, transition: SessionTransition) -> Vec<(ResetTarget, ResetAction)> {
+        let mut applied = Vec::new();
+        for handler in &self.handlers {
+            let target = handler.reset_target();
+            if let Some(action) = reset_action(target, transition) {
+                handler.reset(action);
+                applied.push((target, action));
+            }
+        }
+
+        let summary = applied
+            .iter()
+            .map(|(target, action)| format!("{} ({:?})", target, action))
+            .collect::<Vec<_>>()
+            .join(", ");
+        tracing::debug!(
+            "Session reset on {}: {}",
+            transition,
+            if summary.is_empty() { "nothing" } else { &summary }
+        );
+        applied
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::environment::{default_probes, EnvProber};
+    use crate::agent::tool_results::{PinSource, PinStage, PinnedResult, ToolResultStore};
+    use crate::agent::ToolOutputManager;
+    use crate::tools::ToolEffects;
+    use crate::llm::{CtxDecision, OllamaClient};
+    use std::sync::Mutex;
+
+    struct Recorder {
+        target: ResetTarget,
+        actions: Mutex<Vec<ResetAction>>,
+    }
+
+    impl SessionResettable for Recorder {
+        fn reset_target(&self) -> ResetTarget {
+            self.target
+        }
+
+        fn reset(&self, action: ResetAction) {
+            self.actions.lock().unwrap().push(action);
+        }
+    }
+
+    fn recorder(target: ResetTarget) -> Arc<Recorder> {
+        Arc::new(Recorder {
+            target,
+            actions: Mutex::new(Vec::new()),
+        })
+    }
+
+    #[test]
+    fn test_emit_follows_matrix() {
+        let ctx = recorder(ResetTarget::ContextWindow);
+        let env = recorder(ResetTarget::EnvFingerprint);
+        let mut hub = SessionResetHub::new();
+        hub.register(ctx.clone());
+        hub.register(env.clone());
+
+        assert_eq!(hub.emit(SessionTransition::Clear), vec![]);
+        assert_eq!(
+            hub.emit(SessionTransition::ModelChange),
+            vec![(ResetTarget::ContextWindow, ResetAction::RebindModel)]
+        );
+        assert_eq!(
+            hub.emit(SessionTransition::Load),
+            vec![
+                (ResetTarget::ContextWindow, ResetAction::Caches),
+                (ResetTarget::EnvFingerprint, ResetAction::Full),
+            ]
+        );
+        hub.emit(SessionTransition::ChangeDirectory);
+
+        assert_eq!(
+            *ctx.actions.lock().unwrap(),
+            vec![ResetAction::RebindModel, ResetAction::Caches, ResetAction::Caches]
+        );
+        assert_eq!(*env.actions.lock().unwrap(), vec![ResetAction::Full, ResetAction::Full]);
+    }
+
+    #[test]
+    fn test_replace_keeps_single_handler_per_target() {
+        let first = recorder(ResetTarget::EnvFingerprint);
+        let second = recorder(ResetTarget::EnvFingerprint);
+        let mut hub = SessionResetHub::new();
+        hub.replace(first.clone());
+        hub.replace(second.clone());
+
+        hub.emit(SessionTransition::Load);
+        assert!(first.actions.lock().unwrap().is_empty());
+        assert_eq!(*second.actions.lock().unwrap(), vec![ResetAction::Full]);
+    }
+
+    #[tokio::test]
+    async fn test_env_fingerprint_cleared_on_load_only() {
+        let prober = Arc::new(EnvProber::new(default_probes()).with_path("/nonexistent"));
+        prober.observe_command("git status");
+        prober.flush().await;
+        assert!(!prober.snapshot().is_empty());
+
+        let mut hub = SessionResetHub::new();
+        hub.register(prober.clone());
+
+        hub.emit(SessionTransition::ModelChange);
+        assert!(!prober.snapshot().is_empty());
+        hub.emit(SessionTransition::Load);
+        assert!(prober.snapshot().is_empty());
+
+        // リセット後は再度プローブされる
+        prober.observe_command("git log");
+        prober.flush().await;
+        assert!(prober.snapshot().tools.contains_key("git"));
+    }
+
+    #[test]
+    fn test_context_window_decision_dropped_on_model_change() {
+        let client = OllamaClient::new("http://localhost:11434", "a");
+        client.record_ctx_decision(CtxDecision::Raised { from: 2048, to: 8192 });
+
+        let mut hub = SessionResetHub::new();
+        hub.register(Arc::new(client.session_state()));
+
+        hub.emit(SessionTransition::Clear);
+        assert!(client.last_ctx_decision().is_some());
+        hub.emit(SessionTransition::ModelChange);
+        assert!(client.last_ctx_decision().is_none());
+    }
+
+    #[test]
+    fn test_tool_results_and_staged_pins_cleared_with_the_conversation() {
+        let store = Arc::new(ToolResultStore::new());
+        let stage = Arc::new(PinStage::new());
+        let id = store.record("grep", &serde_json::json!({"pattern": "fn"}), ToolEffects::ReadOnly, "a\nb", None);
+        stage.add(PinnedResult {
+            id: id.clone(),
+            tool: "grep".to_string(),
+            label: "grep, 2 lines".to_string(),
+            output: "a\nb".to_string(),
+            instruction: None,
+            source: PinSource::Retained,
+        });
+        let mut hub = SessionResetHub::new();
+        hub.register(store.clone());
+        hub.register(stage.clone());
+
+        hub.emit(SessionTransition::ModelChange);
+        assert!(store.get(&id).is_some());
+        assert!(!stage.is_empty());
+        hub.emit(SessionTransition::ChangeDirectory);
+        assert!(store.get(&id).is_none());
+        assert!(stage.is_empty());
+
+        // ID は振り直さない（前の会話の ID と取り違えない）
+        let next = store.record("grep", &serde_json::json!({}), ToolEffects::ReadOnly, "", None);
+        assert_eq!(next, "t2");
+        hub.emit(SessionTransition::Clear);
+        assert!(store.get(&next).is_none());
+    }
+
+    #[test]
+    fn test_tool_output_forgets_full_outputs_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = Arc::new(ToolOutputManager::new(2, 0).with_dir(Some(dir.path().to_path_buf())));
+        let output = "1\n2\n3\n4\n5\n";
+        assert!(manager.limit("t1", output).contains("full output id=t1"));
+        let mut hub = SessionResetHub::new();
+        hub.register(manager.clone());
+
+        hub.emit(SessionTransition::ModelChange);
+        assert_eq!(manager.full_output("t1").as_deref(), Some(output));
+        // メモリからもファイルからも読まない
+        hub.emit(SessionTransition::Load);
+        assert_eq!(manager.full_output("t1"), None);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        manager.limit("t2", output);
+        assert_eq!(manager.full_output("t2").as_deref(), Some(output));
+    }
+}
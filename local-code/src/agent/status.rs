@@ -0,0 +1,178 @@
+//! サブシステムの状態集約（/status）
+//!
+//! 各サブシステムが `StatusProvider` を実装し、構築時に `StatusRegistry` へ登録する
+
+use async_trait::async_trait;
+use serde::Serialize;
+use std::sync::{Arc, RwLock};
+
+/// サブシステムの状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthState {
+    Ok,
+    /// 動作しているが問題がある
+    Degraded,
+    /// 使用できない
+    Failed,
+    /// 設定で無効、または未構成
+    Disabled,
+}
+
+impl HealthState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HealthState::Ok => "ok",
+            HealthState::Degraded => "degraded",
+            HealthState::Failed => "failed",
+            HealthState::Disabled => "disabled",
+        }
+    }
+}
+
+/// 1サブシステムの状態
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SubsystemStatus {
+    pub name: String,
+    pub state: HealthState,
+    /// 1行の詳細
+    pub detail: String,
+    /// degraded/failed の対処方法
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl SubsystemStatus {
+    pub fn new(name: impl Into<String>, state: HealthState, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            state,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    pub fn ok(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self::new(name, HealthState::Ok, detail)
+    }
+
+    pub fn degraded(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self::new(name, HealthState::Degraded, detail)
+    }
+
+    pub fn failed(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self::new(name, HealthState::Failed, detail)
+    }
+
+    pub fn disabled(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self::new(name, HealthState::Disabled, detail)
+    }
+
+    /// 対処方法を設定
+    pub fn with_hint(mut self, hint: Option<impl Into<String>>) -> Self {
+        self.hint = hint.map(Into::into);
+        self
+    }
+}
+
+/// 状態を報告するサブシステム
+#[async_trait]
+pub trait StatusProvider: Send + Sync {
+    /// 現在の状態（/status のたびに呼ばれる）
+    async fn status(&self) -> SubsystemStatus;
+}
+
+/// 登録された StatusProvider の集合（クローン間で共有）
+#[derive(Clone, Default)]
+pub struct StatusRegistry {
+    providers: Arc<RwLock<Vec<Arc<dyn StatusProvider>>>>,
+}
+
+impl StatusRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// プロバイダーを登録（表示は登録順）
+    pub fn register(&self, provider: Arc<dyn StatusProvider>) {
+        if let Ok(mut providers) = self.providers.write() {
+            providers.push(provider);
+        }
+    }
+
+    /// 全プロバイダーの状態を並行して取得
+    pub async fn collect(&self) -> Vec<SubsystemStatus> {
+        let providers: Vec<Arc<dyn StatusProvider>> = self
+            .providers
+            .read()
+            .map(|p| p.clone())
+            .unwrap_or_default();
+        futures::future::join_all(providers.iter().map(|p| p.status())).await
+    }
+
+    /// 最も悪い状態（disabled は除く）
+    pub fn overall(statuses: &[SubsystemStatus]) -> HealthState {
+        let states = statuses.iter().map(|s| s.state);
+        if states.clone().any(|s| s == HealthState::Failed) {
+            HealthState::Failed
+        } else if states.clone().any(|s| s == HealthState::Degraded) {
+            HealthState::Degraded
+        } else {
+            HealthState::Ok
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fake(SubsystemStatus);
+
+    #[async_trait]
+    impl StatusProvider for Fake {
+        async fn status(&self) -> SubsystemStatus {
+            self.0.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_collect_in_registration_order() {
+        let registry = StatusRegistry::new();
+        let shared = registry.clone();
+        registry.register(Arc::new(Fake(SubsystemStatus::ok("llm", "ready"))));
+        // クローンへの登録も反映される
+        shared.register(Arc::new(Fake(SubsystemStatus::disabled("lsp", "not configured"))));
+
+        let statuses = registry.collect().await;
+        let names: Vec<&str> = statuses.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["llm", "lsp"]);
+        assert_eq!(StatusRegistry::overall(&statuses), HealthState::Ok);
+    }
+
+    #[test]
+    fn test_overall_takes_worst_state() {
+        let degraded = vec![
+            SubsystemStatus::ok("a", ""),
+            SubsystemStatus::degraded("b", ""),
+            SubsystemStatus::disabled("c", ""),
+        ];
+        assert_eq!(StatusRegistry::overall(&degraded), HealthState::Degraded);
+
+        let mut failed = degraded.clone();
+        failed.push(SubsystemStatus::failed("d", ""));
+        assert_eq!(StatusRegistry::overall(&failed), HealthState::Failed);
+    }
+
+    #[test]
+    fn test_json_shape() {
+        let status = SubsystemStatus::failed("llm", "unreachable").with_hint(Some("run ollama serve"));
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"name": "llm", "state": "failed", "detail": "unreachable", "hint": "run ollama serve"})
+        );
+        let ok = serde_json::to_value(SubsystemStatus::ok("mode", "execute")).unwrap();
+        assert!(ok.get("hint").is_none());
+    }
+}
@@ -0,0 +1,526 @@
+//! 長いツール結果の切り詰めと全文の取り出し
+//!
+//! 行数・バイト数の上限を超えた出力は先頭と末尾だけを会話に入れ、間を
+//! `[... 4,812 lines omitted — full output id=t7 ...]` に置き換える。
+//! 全文は保持しておき、`read_tool_output` ツールで行範囲を指定して読み出す。
+//! ディスクにも保存する場合は新しいものから一定数だけ残し、[storage] encrypt が有効なら暗号化する
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::encryption::{is_encrypted, StorageCipher};
+use super::session::{ResetAction, ResetTarget, SessionResettable};
+use crate::tools::{Tool, ToolEffects, ToolResult};
+
+/// 会話に入れる出力の行数の既定上限
+pub const DEFAULT_MAX_OUTPUT_LINES: usize = 400;
+
+/// 会話に入れる出力のバイト数の既定上限
+pub const DEFAULT_MAX_OUTPUT_BYTES: usize = 32 * 1024;
+
+/// メモリに保持する全文の合計サイズの上限（バイト）
+const MAX_ARCHIVED_BYTES: usize = 16 * 1024 * 1024;
+
+/// ディスクに残す全文のファイル数の既定値（古いものから削除）
+const DEFAULT_MAX_SAVED_FILES: usize = 200;
+
+/// `read_tool_output` で一度に返す行数の既定値
+const DEFAULT_READ_LINES: usize = 200;
+
+/// 切り詰めた出力の全文
+#[derive(Debug)]
+struct ArchivedOutput {
+    id: String,
+    output: String,
+}
+
+/// ツール結果の切り詰めと全文の保持
+pub struct ToolOutputManager {
+    /// 行数の上限（0 で無制限）
+    max_lines: usize,
+    /// バイト数の上限（0 で無制限）
+    max_bytes: usize,
+    /// 全文の保存先（None ならメモリのみ）
+    dir: Option<PathBuf>,
+    /// 保存先に残す最大ファイル数
+    max_files: usize,
+    /// 設定されていれば保存する全文を暗号化
+    cipher: Option<Arc<StorageCipher>>,
+    /// 保存ファイル名の接頭辞（セッションごとに ID が振り直されるため）
+    session: String,
+    /// セッション遷移で破棄した回数（前の会話の全文をファイルからも読まないように名前に入れる）
+    generation: AtomicUsize,
+    archive: Mutex<VecDeque<ArchivedOutput>>,
+}
+
+impl Default for ToolOutputManager {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_OUTPUT_LINES, DEFAULT_MAX_OUTPUT_BYTES)
+    }
+}
+
+impl ToolOutputManager {
+    pub fn new(max_lines: usize, max_bytes: usize) -> Self {
+        let session = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        Self {
+            max_lines,
+            max_bytes,
+            dir: None,
+            max_files: DEFAULT_MAX_SAVED_FILES,
+            cipher: None,
+            session: format!("{}-{}", session, std::process::id()),
+            generation: AtomicUsize::new(0),
+            archive: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// 全文をディスクにも保存する（メモリから追い出された後も読める）
+    pub fn with_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.dir = dir;
+        self
+    }
+
+    /// 保存先に残す最大ファイル数を設定
+    pub fn with_max_files(mut self, max_files: usize) -> Self {
+        self.max_files = max_files;
+        self
+    }
+
+    /// 保存する全文を暗号化する
+    pub fn with_cipher(mut self, cipher: Arc<StorageCipher>) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    /// 既定の保存先（~/.local-code/tool-output/）
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(".local-code").join("tool-output"))
+    }
+
+    pub fn max_lines(&self) -> usize {
+        self.max_lines
+    }
+
+    /// 上限を超えていれば先頭と末尾だけを残し、全文を `id` で保持する
+    pub fn limit(&self, id: &str, output: &str) -> String {
+        let Some((head, tail)) = split_for_limit(output, self.max_lines, self.max_bytes) else {
+            return output.to_string();
+        };
+        let omitted = &output[head..output.len() - tail];
+        let marker = format!(
+            "[... {} lines omitted — full output id={}, read it with read_tool_output ...]",
+            group_thousands(count_lines(omitted)),
+            id
+        );
+        self.store(id, output);
+
+        let mut text = output[..head].to_string();
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push_str(&marker);
+        if tail > 0 {
+            text.push('\n');
+            text.push_str(&output[output.len() - tail..]);
+        }
+        text
+    }
+
+    fn store(&self, id: &str, output: &str) {
+        if let Some(path) = self.path_for(id) {
+            if let Err(e) = self.save(&path, output) {
+                tracing::warn!("ツール結果の保存に失敗しました: {}: {:#}", path.display(), e);
+            }
+            if let Err(e) = self.cleanup() {
+                tracing::warn!("Failed to clean up tool output directory: {}", e);
+            }
+        }
+
+        let mut archive = self.archive.lock().unwrap_or_else(|e| e.into_inner());
+        archive.retain(|entry| entry.id != id);
+        archive.push_back(ArchivedOutput { id: id.to_string(), output: output.to_string() });
+        let mut total: usize = archive.iter().map(|entry| entry.output.len()).sum();
+        while total > MAX_ARCHIVED_BYTES && archive.len() > 1 {
+            if let Some(evicted) = archive.pop_front() {
+                total -= evicted.output.len();
+            }
+        }
+    }
+
+    fn save(&self, path: &std::path::Path, output: &str) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let bytes = match &self.cipher {
+            Some(cipher) => cipher.encrypt(output.as_bytes())?,
+            None => output.as_bytes().to_vec(),
+        };
+        std::fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    /// 保存した全文を読む（暗号化されていれば復号する）
+    fn load(&self, path: &std::path::Path) -> Option<String> {
+        let bytes = std::fs::read(path).ok()?;
+        let bytes = match &self.cipher {
+            Some(cipher) if is_encrypted(&bytes) => cipher
+                .decrypt(&bytes)
+                .inspect_err(|e| tracing::warn!("Failed to decrypt {}: {:#}", path.display(), e))
+                .ok()?,
+            _ => bytes,
+        };
+        String::from_utf8(bytes).ok()
+    }
+
+    /// 古い全文のファイルを削除して max_files 件以内に保つ
+    pub fn cleanup(&self) -> Result<usize> {
+        let Some(dir) = &self.dir else {
+            return Ok(0);
+        };
+        let mut files: Vec<(std::time::SystemTime, PathBuf)> = std::fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("txt"))
+            .map(|path| (std::fs::metadata(&path).and_then(|m| m.modified()).unwrap_or(std::time::UNIX_EPOCH), path))
+            .collect();
+
+        if files.len() <= self.max_files {
+            return Ok(0);
+        }
+
+        // ID の番号は名前順に並ばないので、更新時刻の古い順に消す
+        files.sort();
+        let excess = files.len() - self.max_files;
+        let mut removed = 0;
+        for (_, path) in files.into_iter().take(excess) {
+            if std::fs::remove_file(&path).is_ok() {
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+
+    fn path_for(&self, id: &str) -> Option<PathBuf> {
+        let generation = self.generation.load(Ordering::SeqCst);
+        self.dir.as_ref().map(|dir| dir.join(format!("{}-{}-{}.txt", self.session, generation, id)))
+    }
+
+    /// 保持している全文を取得（"t7" と "7" のどちらでもよい）
+    pub fn full_output(&self, id: &str) -> Option<String> {
+        let id = normalize_id(id);
+        let archive = self.archive.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(entry) = archive.iter().find(|entry| entry.id == id) {
+            return Some(entry.output.clone());
+        }
+        drop(archive);
+        self.path_for(&id).and_then(|path| self.load(&path))
+    }
+
+    /// 全文の `offset` 行目（1始まり）から `limit` 行を返す
+    pub fn read(&self, id: &str, offset: usize, limit: usize) -> Option<OutputRange> {
+        let output = self.full_output(id)?;
+        let lines: Vec<&str> = output.lines().collect();
+        let start = offset.max(1).min(lines.len() + 1);
+        let end = (start - 1 + limit).min(lines.len());
+        Some(OutputRange {
+            start,
+            end,
+            total: lines.len(),
+            text: lines[start - 1..end].join("\n"),
+        })
+    }
+}
+
+/// 前の会話の全文は read_tool_output で読めないようにする（保存したファイルは残す）
+impl SessionResettable for ToolOutputManager {
+    fn reset_target(&self) -> ResetTarget {
+        ResetTarget::ToolOutput
+    }
+
+    fn reset(&self, _action: ResetAction) {
+        self.archive.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// `read` で取り出した行範囲
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputRange {
+    /// 先頭の行番号（1始まり）
+    pub start: usize,
+    /// 末尾の行番号（範囲が空なら start - 1）
+    pub end: usize,
+    /// 全体の行数
+    pub total: usize,
+    pub text: String,
+}
+
+fn normalize_id(id: &str) -> String {
+    let id = id.trim();
+    if id.starts_with('t') {
+        id.to_string()
+    } else {
+        format!("t{}", id)
+    }
+}
+
+fn count_lines(text: &str) -> usize {
+    text.lines().count()
+}
+
+/// 3桁ごとにカンマを入れる（4812 → "4,812"）
+fn group_thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// 残す先頭と末尾のバイト数を求める（上限内なら None）
+///
+/// 行数の上限は先頭と末尾で半分ずつ分け、さらにバイト数の上限も半分ずつに収める
+fn split_for_limit(output: &str, max_lines: usize, max_bytes: usize) -> Option<(usize, usize)> {
+    let line_count = count_lines(output);
+    let over_lines = max_lines > 0 && line_count > max_lines;
+    let over_bytes = max_bytes > 0 && output.len() > max_bytes;
+    if !over_lines && !over_bytes {
+        return None;
+    }
+
+    let (mut head, mut tail) = if over_lines {
+        let lines: Vec<&str> = output.split_inclusive('\n').collect();
+        let head_lines = max_lines.div_ceil(2);
+        let tail_lines = max_lines - head_lines;
+        (
+            lines[..head_lines].iter().map(|l| l.len()).sum(),
+            lines[lines.len() - tail_lines..].iter().map(|l| l.len()).sum(),
+        )
+    } else {
+        (output.len(), output.len())
+    };
+
+    if max_bytes > 0 && head + tail > max_bytes {
+        let budget = max_bytes / 2;
+        head = head.min(budget);
+        while !output.is_char_boundary(head) {
+            head -= 1;
+        }
+        tail = tail.min(max_bytes - head);
+        while !output.is_char_boundary(output.len() - tail) {
+            tail -= 1;
+        }
+    }
+    Some((head, tail))
+}
+
+/// 切り詰められたツール結果の全文を読むツール
+pub struct ReadToolOutputTool {
+    outputs: std::sync::Arc<ToolOutputManager>,
+}
+
+impl ReadToolOutputTool {
+    pub fn new(outputs: std::sync::Arc<ToolOutputManager>) -> Self {
+        Self { outputs }
+    }
+}
+
+#[async_trait]
+impl Tool for ReadToolOutputTool {
+    fn name(&self) -> &str {
+        "read_tool_output"
+    }
+
+    fn description(&self) -> &str {
+        "Read a range of lines from a tool result that was truncated (the id from its 'lines omitted' marker)"
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "id": {
+                    "type": "string",
+                    "description": "Id from the truncation marker, e.g. t7"
+                },
+                "offset": {
+                    "type": "integer",
+                    "description": "First line to read, starting at 1 (default 1)"
+                },
+                "limit": {
+                    "type": "integer",
+                    "description": "Number of lines to read (default 200)"
+                }
+            },
+            "required": ["id"]
+        })
+    }
+
+    fn effects(&self) -> ToolEffects {
+        // 保持済みの出力を読むだけ
+        ToolEffects::ReadOnly
+    }
+
+    async fn execute(&self, params: Value) -> Result<ToolResult> {
+        let id = params["id"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("id parameter is required"))?;
+        let offset = params["offset"].as_u64().unwrap_or(1) as usize;
+        let mut limit = params["limit"].as_u64().map_or(DEFAULT_READ_LINES, |n| n as usize);
+        // 読み出した結果がまた切り詰められないようにする
+        if self.outputs.max_lines() > 0 {
+            limit = limit.min(self.outputs.max_lines());
+        }
+
+        let Some(range) = self.outputs.read(id, offset, limit) else {
+            return Ok(ToolResult::failure(format!("No stored output for id {}", id)));
+        };
+        if range.start > range.end {
+            return Ok(ToolResult::failure(format!(
+                "Output {} has only {} lines",
+                normalize_id(id),
+                range.total
+            )));
+        }
+        Ok(ToolResult::success(format!(
+            "Lines {}-{} of {} ({}):\n{}",
+            range.start,
+            range.end,
+            range.total,
+            normalize_id(id),
+            range.text
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn numbered(n: usize) -> String {
+        (1..=n).map(|i| format!("line {}\n", i)).collect()
+    }
+
+    #[test]
+    fn test_output_within_limits_is_unchanged() {
+        let manager = ToolOutputManager::new(10, 0);
+        let output = numbered(10);
+        assert_eq!(manager.limit("t1", &output), output);
+        assert!(manager.full_output("t1").is_none());
+    }
+
+    #[test]
+    fn test_one_line_over_keeps_head_and_tail() {
+        let manager = ToolOutputManager::new(10, 0);
+        let text = manager.limit("t7", &numbered(11));
+        assert!(text.starts_with("line 1\n"));
+        assert!(text.contains("line 5\n[... 1 lines omitted — full output id=t7"));
+        assert!(text.ends_with("line 7\nline 8\nline 9\nline 10\nline 11\n"));
+        assert!(!text.contains("line 6\n"));
+        assert_eq!(manager.full_output("t7").unwrap(), numbered(11));
+    }
+
+    #[test]
+    fn test_marker_groups_thousands() {
+        let manager = ToolOutputManager::new(4, 0);
+        let text = manager.limit("t3", &numbered(4816));
+        assert!(text.contains("[... 4,812 lines omitted — full output id=t3"));
+        assert_eq!(group_thousands(999), "999");
+        assert_eq!(group_thousands(1_234_567), "1,234,567");
+    }
+
+    #[test]
+    fn test_byte_limit_cuts_long_lines() {
+        let manager = ToolOutputManager::new(0, 100);
+        let output = "é".repeat(300);
+        let text = manager.limit("t2", &output);
+        let marker = text.find("[...").unwrap();
+        assert_eq!(&text[..marker], format!("{}\n", "é".repeat(25)));
+        assert!(text.ends_with(&"é".repeat(25)));
+        assert_eq!(manager.full_output("2").unwrap(), output);
+
+        // ちょうど上限なら切り詰めない
+        let exact = "a".repeat(100);
+        assert_eq!(manager.limit("t4", &exact), exact);
+    }
+
+    #[test]
+    fn test_read_range_by_id() {
+        let manager = ToolOutputManager::new(10, 0);
+        manager.limit("t5", &numbered(50));
+
+        let range = manager.read("t5", 21, 3).unwrap();
+        assert_eq!((range.start, range.end, range.total), (21, 23, 50));
+        assert_eq!(range.text, "line 21\nline 22\nline 23");
+        // 末尾を越える範囲は詰める
+        assert_eq!(manager.read("5", 49, 10).unwrap().end, 50);
+        assert!(manager.read("t6", 1, 10).is_none());
+    }
+
+    #[test]
+    fn test_reads_from_disk_after_eviction() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = ToolOutputManager::new(2, 0).with_dir(Some(dir.path().to_path_buf()));
+        manager.limit("t1", &numbered(5));
+        manager.archive.lock().unwrap().clear();
+        assert_eq!(manager.read("t1", 4, 2).unwrap().text, "line 4\nline 5");
+    }
+
+    #[test]
+    fn test_saved_outputs_are_encrypted_and_pruned() {
+        use crate::agent::encryption::{KdfParams, PassphraseSource};
+
+        let dir = tempfile::tempdir().unwrap();
+        let cipher = StorageCipher::new(PassphraseSource::Value("pw".to_string()))
+            .with_kdf_params(KdfParams { m_cost: 64, t_cost: 1, p_cost: 1 });
+        let manager = ToolOutputManager::new(2, 0)
+            .with_dir(Some(dir.path().to_path_buf()))
+            .with_max_files(2)
+            .with_cipher(Arc::new(cipher));
+        manager.limit("t1", &numbered(5));
+        let saved: Vec<PathBuf> = std::fs::read_dir(dir.path()).unwrap().map(|e| e.unwrap().path()).collect();
+        assert!(is_encrypted(&std::fs::read(&saved[0]).unwrap()));
+        manager.archive.lock().unwrap().clear();
+        assert_eq!(manager.read("t1", 4, 2).unwrap().text, "line 4\nline 5");
+
+        // 新しいものから max_files 件だけ残る
+        for id in ["t2", "t3"] {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            manager.limit(id, &numbered(5));
+        }
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+        manager.archive.lock().unwrap().clear();
+        assert!(manager.read("t1", 1, 1).is_none());
+        assert_eq!(manager.read("t3", 1, 1).unwrap().text, "line 1");
+    }
+
+    #[tokio::test]
+    async fn test_read_tool_output_tool() {
+        let manager = Arc::new(ToolOutputManager::new(10, 0));
+        manager.limit("t9", &numbered(30));
+        let tool = ReadToolOutputTool::new(Arc::clone(&manager));
+
+        let result = tool.execute(json!({"id": "t9", "offset": 11, "limit": 50})).await.unwrap();
+        assert!(result.success);
+        assert!(result.output.starts_with("Lines 11-20 of 30 (t9):\nline 11\n"));
+
+        let result = tool.execute(json!({"id": "t9", "offset": 40})).await.unwrap();
+        assert!(!result.success);
+        let result = tool.execute(json!({"id": "t1"})).await.unwrap();
+        assert!(!result.success);
+    }
+}
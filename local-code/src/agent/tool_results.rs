@@ -0,0 +1,439 @@
+//! ツール結果の保持と /use による固定（pin）
+//!
+//! 各ツール呼び出しに `t1`, `t2`, ... の ID を振り、直近の出力を切り詰めずに保持する
+//! 保持上限を超えた出力は破棄し、読み取り専用でファイルが変わっていなければ再実行で取り戻す
+
+use anyhow::{bail, Result};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use super::session::{ResetAction, ResetTarget, SessionResettable};
+use crate::tools::{ToolEffects, ToolRegistry};
+
+/// メタデータを保持するツール呼び出しの最大数
+const DEFAULT_MAX_RECORDS: usize = 500;
+
+/// 出力全文を保持する合計サイズの上限（バイト）
+const DEFAULT_MAX_RETAINED_BYTES: usize = 4 * 1024 * 1024;
+
+/// 読み取ったファイルの状態（再実行してよいかの判定用）
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileFingerprint {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl FileFingerprint {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        if !metadata.is_file() {
+            return None;
+        }
+        Some(Self {
+            path: path.to_path_buf(),
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        })
+    }
+
+    fn is_unchanged(&self) -> bool {
+        Self::of(&self.path).as_ref() == Some(self)
+    }
+}
+
+/// 1回のツール呼び出しの記録
+#[derive(Debug, Clone)]
+pub struct ToolResultRecord {
+    pub id: String,
+    pub tool: String,
+    pub params: Value,
+    pub effects: ToolEffects,
+    /// 出力全文（保持上限を超えて破棄された場合は None）
+    pub output: Option<String>,
+    /// 出力の行数
+    pub lines: usize,
+    /// 構造化された結果（`ToolResult.data`、出力と一緒に破棄される）
+    pub data: Option<Value>,
+    fingerprint: Option<FileFingerprint>,
+}
+
+impl ToolResultRecord {
+    /// 表示用の説明（`read src/lib.rs:1-200` / `grep, 44 lines`）
+    pub fn label(&self) -> String {
+        match (self.tool.as_str(), self.params.get("file_path").and_then(|v| v.as_str())) {
+            ("read", Some(path)) => {
+                let offset = self.params.get("offset").and_then(|v| v.as_u64());
+                let limit = self.params.get("limit").and_then(|v| v.as_u64());
+                match (offset, limit) {
+                    (None, None) => format!("read {}", path),
+                    (offset, Some(limit)) => {
+                        let start = offset.unwrap_or(1);
+                        format!("read {}:{}-{}", path, start, start + limit.saturating_sub(1))
+                    }
+                    (Some(offset), None) => format!("read {}:{}-", path, offset),
+                }
+            }
+            _ => format!("{}, {} lines", self.tool, self.lines),
+        }
+    }
+}
+
+/// 固定した出力の取得元
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinSource {
+    /// 保持していた出力
+    Retained,
+    /// 読み取り専用ツールを再実行した出力
+    Reexecuted,
+}
+
+/// 次のユーザーメッセージに添付するツール結果
+#[derive(Debug, Clone)]
+pub struct PinnedResult {
+    pub id: String,
+    pub tool: String,
+    pub label: String,
+    pub output: String,
+    pub instruction: Option<String>,
+    pub source: PinSource,
+}
+
+/// 直近のツール結果（エージェントが持ち、セッション遷移で破棄する）
+#[derive(Debug)]
+pub struct ToolResultStore {
+    inner: Mutex<Records>,
+}
+
+#[derive(Debug)]
+struct Records {
+    records: VecDeque<ToolResultRecord>,
+    next_id: usize,
+    max_records: usize,
+    max_retained_bytes: usize,
+    retained_bytes: usize,
+}
+
+impl ToolResultStore {
+    pub fn new() -> Self {
+        Self::with_limits(DEFAULT_MAX_RECORDS, DEFAULT_MAX_RETAINED_BYTES)
+    }
+
+    /// 記録数と保持する出力の合計サイズの上限を指定
+    pub fn with_limits(max_records: usize, max_retained_bytes: usize) -> Self {
+        Self {
+            inner: Mutex::new(Records {
+                records: VecDeque::new(),
+                next_id: 1,
+                max_records: max_records.max(1),
+                max_retained_bytes,
+                retained_bytes: 0,
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Records> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// ツール呼び出しを記録して ID を返す
+    pub fn record(
+        &self,
+        tool: &str,
+        params: &Value,
+        effects: ToolEffects,
+        output: &str,
+        data: Option<Value>,
+    ) -> String {
+        let mut inner = self.lock();
+        let inner = &mut *inner;
+        let id = format!("t{}", inner.next_id);
+        inner.next_id += 1;
+
+        let fingerprint = match effects {
+            ToolEffects::ReadOnly => ["file_path", "path"]
+                .iter()
+                .find_map(|key| params.get(key).and_then(|v| v.as_str()))
+                .and_then(|path| FileFingerprint::of(Path::new(path))),
+            _ => None,
+        };
+        inner.records.push_back(ToolResultRecord {
+            id: id.clone(),
+            tool: tool.to_string(),
+            params: params.clone(),
+            effects,
+            output: Some(output.to_string()),
+            lines: output.lines().count(),
+            data,
+            fingerprint,
+        });
+        inner.retained_bytes += output.len();
+
+        while inner.records.len() > inner.max_records {
+            if let Some(dropped) = inner.records.pop_front() {
+                inner.retained_bytes -= dropped.output.map(|o| o.len()).unwrap_or(0);
+            }
+        }
+        // 古いものから出力を破棄（メタデータは残す）
+        for record in inner.records.iter_mut() {
+            if inner.retained_bytes <= inner.max_retained_bytes {
+                break;
+            }
+            if let Some(output) = record.output.take() {
+                inner.retained_bytes -= output.len();
+                record.data = None;
+            }
+        }
+        id
+    }
+
+    /// ID で記録を取得（`t3` と `3` のどちらでも可）
+    pub fn get(&self, id: &str) -> Option<ToolResultRecord> {
+        let id = id.trim();
+        let id = if id.starts_with('t') { id.to_string() } else { format!("t{}", id) };
+        self.lock().records.iter().find(|r| r.id == id).cloned()
+    }
+
+    /// 固定する出力を取得（保持していなければ、可能な場合は再実行）
+    pub async fn resolve(&self, id: &str, tools: &ToolRegistry) -> Result<PinnedResult> {
+        let Some(record) = self.get(id) else {
+            bail!("Unknown tool result id: {}", id);
+        };
+        let (output, source) = match &record.output {
+            Some(output) => (output.clone(), PinSource::Retained),
+            None => {
+                let reusable = record.effects == ToolEffects::ReadOnly
+                    && record.fingerprint.as_ref().is_some_and(|f| f.is_unchanged());
+                if !reusable {
+                    bail!(
+                        "The output of {} ({}) is no longer retained and cannot be re-run safely",
+                        record.id,
+                        record.label()
+                    );
+                }
+                let Some(tool) = tools.get(&record.tool) else {
+                    bail!("Tool '{}' is no longer available", record.tool);
+                };
+                let result = tool.execute(record.params.clone()).await?;
+                let output = if result.success {
+                    result.output
+                } else {
+                    bail!("Re-running {} failed: {}", record.id, result.error.unwrap_or_default());
+                };
+                (output, PinSource::Reexecuted)
+            }
+        };
+        Ok(PinnedResult {
+            id: record.id.clone(),
+            tool: record.tool.clone(),
+            label: record.label(),
+            output,
+            instruction: None,
+            source,
+        })
+    }
+
+    /// 全記録を破棄（ID は振り直さない）
+    pub fn clear(&self) {
+        let mut inner = self.lock();
+        inner.records.clear();
+        inner.retained_bytes = 0;
+    }
+}
+
+impl Default for ToolResultStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 前の会話やディレクトリのツール結果は /use で選べないようにする
+impl SessionResettable for ToolResultStore {
+    fn reset_target(&self) -> ResetTarget {
+        ResetTarget::ToolResults
+    }
+
+    fn reset(&self, _action: ResetAction) {
+        self.clear();
+    }
+}
+
+/// 送信待ちの /use（REPL 側で保持し、次のメッセージ送信時にエージェントへ渡す）
+#[derive(Debug, Default)]
+pub struct PinStage {
+    pins: Mutex<Vec<PinnedResult>>,
+}
+
+impl PinStage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<PinnedResult>> {
+        self.pins.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 追加（同じ ID は指示を更新して1つにまとめる）
+    pub fn add(&self, pin: PinnedResult) {
+        let mut pins = self.lock();
+        match pins.iter_mut().find(|p| p.id == pin.id) {
+            Some(existing) => *existing = pin,
+            None => pins.push(pin),
+        }
+    }
+
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// 送信用に取り出す
+    pub fn take(&self) -> Vec<PinnedResult> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    /// `staged: t3 (grep, 44 lines), t7 (read src/lib.rs:1-200)`
+    pub fn summary(&self) -> String {
+        format!("staged: {}", pin_labels(&self.lock()))
+    }
+}
+
+/// 送信待ちの固定は前の会話の結果なので、会話が変われば送らない
+impl SessionResettable for PinStage {
+    fn reset_target(&self) -> ResetTarget {
+        ResetTarget::StagedPins
+    }
+
+    fn reset(&self, _action: ResetAction) {
+        self.clear();
+    }
+}
+
+/// `t3 (grep, 44 lines), t7 (read src/lib.rs:1-200)`
+pub fn pin_labels(pins: &[PinnedResult]) -> String {
+    pins.iter()
+        .map(|p| format!("{} ({})", p.id, p.label))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// ユーザーメッセージに添付するセクション
+pub fn pinned_section(pins: &[PinnedResult]) -> String {
+    let mut section = String::from(
+        "<pinned_tool_results>\nThe user pinned these earlier tool results for this message. The output is complete and current.\n",
+    );
+    for pin in pins {
+        section.push_str(&format!("<tool_result id=\"{}\" tool=\"{}\">\n", pin.id, pin.tool));
+        if let Some(instruction) = &pin.instruction {
+            section.push_str(&format!("<instruction>{}</instruction>\n", instruction));
+        }
+        section.push_str(&pin.output);
+        if !pin.output.ends_with('\n') {
+            section.push('\n');
+        }
+        section.push_str("</tool_result>\n");
+    }
+    section.push_str("</pinned_tool_results>");
+    section
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::file::ReadTool;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn read_params(path: &Path) -> Value {
+        json!({"file_path": path.to_string_lossy()})
+    }
+
+    #[test]
+    fn test_ids_and_labels() {
+        let store = ToolResultStore::new();
+        let grep = store.record("grep", &json!({"pattern": "fn"}), ToolEffects::ReadOnly, "a\nb\nc", None);
+        let read = store.record(
+            "read",
+            &json!({"file_path": "src/lib.rs", "offset": 1, "limit": 200}),
+            ToolEffects::ReadOnly,
+            "",
+            None,
+        );
+        assert_eq!((grep.as_str(), read.as_str()), ("t1", "t2"));
+        assert_eq!(store.get("t1").unwrap().label(), "grep, 3 lines");
+        assert_eq!(store.get("2").unwrap().label(), "read src/lib.rs:1-200");
+    }
+
+    #[tokio::test]
+    async fn test_retained_output_is_returned_in_full() {
+        let store = ToolResultStore::new();
+        let output = "line\n".repeat(5000);
+        let id = store.record("bash", &json!({"command": "seq"}), ToolEffects::Executes, &output, None);
+
+        let pinned = store.resolve(&id, &ToolRegistry::new()).await.unwrap();
+        assert_eq!(pinned.source, PinSource::Retained);
+        assert_eq!(pinned.output, output);
+    }
+
+    #[tokio::test]
+    async fn test_evicted_read_only_call_is_reexecuted_when_file_unchanged() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes.txt");
+        std::fs::write(&file, "first\nsecond\n").unwrap();
+        let mut tools = ToolRegistry::new();
+        tools.register(Arc::new(ReadTool::new()));
+        let output = tools.get("read").unwrap().execute(read_params(&file)).await.unwrap().output;
+
+        // 上限を小さくして出力を破棄させる
+        let store = ToolResultStore::with_limits(10, 8);
+        let id = store.record("read", &read_params(&file), ToolEffects::ReadOnly, &output, Some(json!({"lines": 2})));
+        assert!(store.get(&id).unwrap().output.is_none());
+        assert!(store.get(&id).unwrap().data.is_none());
+
+        let pinned = store.resolve(&id, &tools).await.unwrap();
+        assert_eq!(pinned.source, PinSource::Reexecuted);
+        assert_eq!(pinned.output, output);
+
+        // ファイルが変わっていたら古い結果を装わない
+        std::fs::write(&file, "changed and longer\n").unwrap();
+        let err = store.resolve(&id, &tools).await.unwrap_err();
+        assert!(err.to_string().contains("no longer retained"));
+    }
+
+    #[tokio::test]
+    async fn test_evicted_side_effecting_call_is_not_rerun() {
+        let store = ToolResultStore::with_limits(10, 0);
+        let id = store.record("bash", &json!({"command": "date"}), ToolEffects::Executes, "now", None);
+        assert!(store.resolve(&id, &ToolRegistry::new()).await.is_err());
+        assert!(store.resolve("t99", &ToolRegistry::new()).await.is_err());
+    }
+
+    #[test]
+    fn test_stage_accumulates_and_formats() {
+        let pin = |id: &str, label: &str| PinnedResult {
+            id: id.to_string(),
+            tool: "grep".to_string(),
+            label: label.to_string(),
+            output: "match".to_string(),
+            instruction: Some("use these".to_string()),
+            source: PinSource::Retained,
+        };
+        let stage = PinStage::new();
+        stage.add(pin("t3", "grep, 44 lines"));
+        stage.add(pin("t7", "read src/lib.rs:1-200"));
+        stage.add(pin("t3", "grep, 44 lines"));
+        assert_eq!(stage.summary(), "staged: t3 (grep, 44 lines), t7 (read src/lib.rs:1-200)");
+
+        let pins = stage.take();
+        assert!(stage.is_empty());
+        let section = pinned_section(&pins);
+        assert!(section.contains("<tool_result id=\"t7\" tool=\"grep\">"));
+        assert!(section.contains("<instruction>use these</instruction>"));
+    }
+}
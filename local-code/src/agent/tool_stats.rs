@@ -0,0 +1,220 @@
+//! ツールの使用統計（/stats）
+//!
+//! ツールごとの実行回数・失敗数・所要時間・出力量と、セッション全体のターン数・トークン数を数える
+
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::session::{ResetAction, ResetTarget, SessionResettable};
+use crate::llm::StreamStats;
+use crate::tools::disk::format_size;
+
+/// 1ツール分の集計
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ToolUsage {
+    /// 実行回数
+    pub invocations: u64,
+    /// 失敗した回数（エラー・タイムアウトを含む）
+    pub failures: u64,
+    /// 所要時間の合計
+    #[serde(rename = "total_ms", serialize_with = "as_millis")]
+    pub total_duration: Duration,
+    /// 1回あたりの最長所要時間
+    #[serde(rename = "max_ms", serialize_with = "as_millis")]
+    pub max_duration: Duration,
+    /// 出力（失敗時はエラーメッセージ）の合計バイト数
+    pub output_bytes: u64,
+}
+
+impl ToolUsage {
+    fn record(&mut self, duration: Duration, success: bool, output_bytes: usize) {
+        self.invocations += 1;
+        if !success {
+            self.failures += 1;
+        }
+        self.total_duration += duration;
+        self.max_duration = self.max_duration.max(duration);
+        self.output_bytes += output_bytes as u64;
+    }
+}
+
+fn as_millis<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_millis() as u64)
+}
+
+/// セッションの統計（/stats の表示と `--output json` の出力）
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SessionStats {
+    /// 処理したターン数
+    pub turns: u64,
+    /// サーバーが統計を返したターン数
+    pub turns_with_token_counts: u64,
+    /// プロンプト評価トークン数の合計（サーバーの統計から）
+    pub prompt_tokens: u64,
+    /// 生成トークン数の合計（サーバーの統計から）
+    pub generated_tokens: u64,
+    /// 現在の会話の推定トークン数
+    pub context_tokens: usize,
+    /// ツール名ごとの集計
+    pub tools: BTreeMap<String, ToolUsage>,
+}
+
+impl SessionStats {
+    /// /stats の表示（実行回数の多い順）
+    pub fn report(&self, context_window: usize) -> String {
+        let mut lines = Vec::new();
+        if self.tools.is_empty() {
+            lines.push("No tools have run yet.".to_string());
+        } else {
+            let mut rows: Vec<(&String, &ToolUsage)> = self.tools.iter().collect();
+            rows.sort_by(|a, b| b.1.invocations.cmp(&a.1.invocations).then_with(|| a.0.cmp(b.0)));
+            let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0).max("Tool".len());
+            lines.push(format!(
+                "{:<width$}  {:>6}  {:>6}  {:>10}  {:>8}  {:>9}",
+                "Tool", "Calls", "Failed", "Total ms", "Max ms", "Output"
+            ));
+            for (name, usage) in rows {
+                lines.push(format!(
+                    "{:<width$}  {:>6}  {:>6}  {:>10}  {:>8}  {:>9}",
+                    name,
+                    usage.invocations,
+                    usage.failures,
+                    usage.total_duration.as_millis(),
+                    usage.max_duration.as_millis(),
+                    format_size(usage.output_bytes)
+                ));
+            }
+        }
+
+        lines.push(String::new());
+        lines.push(format!("Turns: {}", self.turns));
+        if self.turns_with_token_counts == 0 {
+            lines.push("Tokens: not reported by the server".to_string());
+        } else {
+            lines.push(format!(
+                "Tokens: {} prompt + {} generated ({} of {} turns reported)",
+                self.prompt_tokens, self.generated_tokens, self.turns_with_token_counts, self.turns
+            ));
+        }
+        lines.push(format!(
+            "Context: ~{} / {} tokens ({}%)",
+            self.context_tokens,
+            context_window,
+            (self.context_tokens * 100).checked_div(context_window).unwrap_or(100)
+        ));
+        lines.join("\n")
+    }
+}
+
+/// セッション中の統計の記録先
+#[derive(Debug, Default)]
+pub struct ToolStats {
+    inner: Mutex<SessionStats>,
+}
+
+impl ToolStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// ツール1回分の実行を記録
+    pub fn record_tool(&self, name: &str, duration: Duration, success: bool, output_bytes: usize) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.tools.entry(name.to_string()).or_default().record(duration, success, output_bytes);
+    }
+
+    /// 1ターン分を記録（サーバーの統計が取れればトークン数も加える）
+    pub fn record_turn(&self, stats: Option<&StreamStats>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.turns += 1;
+        if let Some(stats) = stats {
+            inner.turns_with_token_counts += 1;
+            inner.prompt_tokens += u64::from(stats.prompt_eval_count);
+            inner.generated_tokens += u64::from(stats.eval_count);
+        }
+    }
+
+    /// 現在の集計（context_tokens は呼び出し側で埋める）
+    pub fn snapshot(&self) -> SessionStats {
+        self.inner.lock().unwrap().clone()
+    }
+
+    /// 集計を捨てる
+    pub fn reset(&self) {
+        *self.inner.lock().unwrap() = SessionStats::default();
+    }
+}
+
+impl SessionResettable for ToolStats {
+    fn reset_target(&self) -> ResetTarget {
+        ResetTarget::ToolStats
+    }
+
+    fn reset(&self, _action: ResetAction) {
+        ToolStats::reset(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_accumulates_per_tool() {
+        let stats = ToolStats::new();
+        stats.record_tool("read", Duration::from_millis(5), true, 100);
+        stats.record_tool("read", Duration::from_millis(20), false, 30);
+        stats.record_tool("bash", Duration::from_millis(7), true, 1);
+        stats.record_turn(Some(&StreamStats {
+            total_duration: 0,
+            prompt_eval_count: 120,
+            eval_count: 30,
+            tokens_per_second: 0.0,
+        }));
+        stats.record_turn(None);
+
+        let snapshot = stats.snapshot();
+        let read = &snapshot.tools["read"];
+        assert_eq!(read.invocations, 2);
+        assert_eq!(read.failures, 1);
+        assert_eq!(read.total_duration, Duration::from_millis(25));
+        assert_eq!(read.max_duration, Duration::from_millis(20));
+        assert_eq!(read.output_bytes, 130);
+        assert_eq!(snapshot.tools["bash"].invocations, 1);
+        assert_eq!(snapshot.turns, 2);
+        assert_eq!(snapshot.turns_with_token_counts, 1);
+        assert_eq!((snapshot.prompt_tokens, snapshot.generated_tokens), (120, 30));
+
+        let json = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(json["tools"]["read"]["total_ms"], 25);
+        assert_eq!(json["tools"]["read"]["max_ms"], 20);
+
+        stats.reset();
+        assert_eq!(stats.snapshot(), SessionStats::default());
+    }
+
+    #[test]
+    fn test_report_sorts_by_invocations() {
+        let stats = ToolStats::new();
+        stats.record_tool("glob", Duration::from_millis(1), true, 10);
+        stats.record_tool("read", Duration::from_millis(1), true, 10);
+        stats.record_tool("read", Duration::from_millis(1), true, 10);
+        let report = stats.snapshot().report(8192);
+
+        let read = report.find("\nread").unwrap();
+        let glob = report.find("\nglob").unwrap();
+        assert!(read < glob, "{}", report);
+        assert!(report.starts_with("Tool"), "{}", report);
+    }
+
+    #[test]
+    fn test_report_with_empty_stats() {
+        let report = SessionStats::default().report(8192);
+        assert_eq!(
+            report,
+            "No tools have run yet.\n\nTurns: 0\nTokens: not reported by the server\nContext: ~0 / 8192 tokens (0%)"
+        );
+    }
+}
@@ -0,0 +1,166 @@
+//! 1ターン分の処理結果
+//!
+//! `Agent::process_detailed` が返す構造化された記録。`--output json` ではこれをそのまま出力する
+
+use serde::{Deserialize, Serialize};
+
+use std::path::Path;
+
+use super::verification::{VerificationPipeline, VerificationResult};
+use crate::llm::StreamStats;
+
+/// 1ターン分の記録
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TurnRecord {
+    /// ユーザーの入力
+    pub prompt: String,
+    /// モデルの応答テキスト（ツール呼び出しを除く）
+    pub assistant: String,
+    /// 表示用の応答（ツール結果を含む。`Agent::process` の戻り値）
+    pub response: String,
+    /// 実行（または拒否）したツール呼び出し
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCallRecord>,
+    /// 応答内のコードの検証結果
+    #[serde(default)]
+    pub verifications: Vec<VerificationRecord>,
+    /// サーバーが返した統計情報（取れた場合）
+    #[serde(default)]
+    pub stats: Option<StreamStats>,
+    /// ターン全体の所要時間（ミリ秒）
+    pub elapsed_ms: u64,
+    /// 中断されたか
+    #[serde(default)]
+    pub interrupted: bool,
+}
+
+impl TurnRecord {
+    pub fn new(prompt: impl Into<String>) -> Self {
+        Self {
+            prompt: prompt.into(),
+            assistant: String::new(),
+            response: String::new(),
+            tool_calls: Vec::new(),
+            verifications: Vec::new(),
+            stats: None,
+            elapsed_ms: 0,
+            interrupted: false,
+        }
+    }
+
+    /// 応答内の言語付きコードブロックを検証して記録する（修正は試みない。検証が無効なら何もしない）
+    pub async fn verify_code_blocks(&mut self, pipeline: &VerificationPipeline, project_root: Option<&Path>) {
+        let results = pipeline.check(&self.assistant, project_root).await;
+        self.verifications.extend(results.iter().map(VerificationRecord::from));
+    }
+}
+
+/// ツール呼び出し1件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCallRecord {
+    /// `/use` で参照できる結果ID（拒否・エラー時は None）
+    pub id: Option<String>,
+    pub tool: String,
+    pub params: serde_json::Value,
+    /// 出力、またはエラーメッセージ
+    pub output: String,
+    pub success: bool,
+    /// ツールが返した構造化データ（LSP の位置情報など）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+/// コード検証1件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VerificationRecord {
+    pub language: String,
+    pub success: bool,
+    /// 失敗時のエラー出力
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// 断片のため検証しなかった
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub skipped: bool,
+}
+
+impl From<&VerificationResult> for VerificationRecord {
+    fn from(result: &VerificationResult) -> Self {
+        Self {
+            language: result.language.clone(),
+            success: result.success,
+            error: (!result.success && !result.error.is_empty()).then(|| result.error.clone()),
+            skipped: result.skipped,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_turn_record_round_trip() {
+        let record = TurnRecord {
+            prompt: "list files".to_string(),
+            assistant: "Listing.".to_string(),
+            response: "Listing.\n\n[bash t1]\nsrc\n\n".to_string(),
+            tool_calls: vec![
+                ToolCallRecord {
+                    id: Some("t1".to_string()),
+                    tool: "bash".to_string(),
+                    params: serde_json::json!({ "command": "ls" }),
+                    output: "src".to_string(),
+                    success: true,
+                    data: Some(serde_json::json!({ "exit_code": 0 })),
+                },
+                ToolCallRecord {
+                    id: None,
+                    tool: "write".to_string(),
+                    params: serde_json::json!({ "path": "a.txt" }),
+                    output: "Tool 'write' is not allowed in plan mode".to_string(),
+                    success: false,
+                    data: None,
+                },
+            ],
+            verifications: vec![VerificationRecord {
+                language: "python".to_string(),
+                success: false,
+                error: Some("SyntaxError".to_string()),
+                skipped: false,
+            }],
+            stats: Some(StreamStats {
+                total_duration: 2_000_000_000,
+                prompt_eval_count: 120,
+                eval_count: 40,
+                tokens_per_second: 20.0,
+            }),
+            elapsed_ms: 2100,
+            interrupted: false,
+        };
+        let json = serde_json::to_string(&record).unwrap();
+        assert_eq!(serde_json::from_str::<TurnRecord>(&json).unwrap(), record);
+    }
+
+    #[test]
+    fn test_optional_fields_default() {
+        let json = r#"{"prompt":"hi","assistant":"hello","response":"hello","elapsed_ms":5}"#;
+        let record: TurnRecord = serde_json::from_str(json).unwrap();
+        assert!(record.tool_calls.is_empty());
+        assert!(record.stats.is_none());
+        assert!(!record.interrupted);
+        assert_eq!(serde_json::to_value(&record).unwrap()["stats"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_verification_record_keeps_error_only_on_failure() {
+        let result = VerificationResult {
+            success: true,
+            output: String::new(),
+            error: "warning: unused".to_string(),
+            language: "rust".to_string(),
+            code: String::new(),
+            skipped: false,
+        };
+        assert_eq!(VerificationRecord::from(&result).error, None);
+    }
+}
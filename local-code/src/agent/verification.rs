@@ -1,15 +1,38 @@
 //! コード検証エンジン
 //!
 //! 生成されたコードを実行して検証し、エラーがあれば修正を促す
+//!
+//! Rust は Cargo プロジェクトの中なら `cargo check` で依存クレートごと検証し、
+//! 単体で動かない断片（`fn main` がない、プロジェクトの項目を参照する）は検証しない。
+//! 検証に使うツール（tsc・go・shellcheck など）がなければ、失敗ではなく「検証しなかった」として返す。
+//! 検証コマンドはすべて非同期に、共通のタイムアウト付きで実行する（`[verification] timeout_secs`）
 
 use anyhow::Result;
-use std::process::Command;
-use std::io::Write;
-use tempfile::NamedTempFile;
-use std::time::Duration;
+use async_trait::async_trait;
+use std::ffi::OsString;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::time::Duration;
+use std::sync::Arc;
+use futures::future::join_all;
+use tokio::process::Command;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::{Mutex, Semaphore};
 use tokio::time::timeout;
-use tokio::process::Command as TokioCommand;
+
+use super::event::AgentEvent;
+use crate::cli::output::OutputPostProcessor;
+use crate::config::VerificationConfig;
+
+/// 1つのコードブロックについて修正を頼む既定の回数
+pub const DEFAULT_MAX_ATTEMPTS: usize = 3;
+
+/// 検証コマンド1回あたりの既定のタイムアウト（秒）
+pub const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// 検証できる言語（正規化した名前）
+pub const SUPPORTED_LANGUAGES: &[&str] = &["python", "rust", "javascript", "typescript", "go", "bash"];
 
 /// 検証結果
 #[derive(Debug, Clone)]
@@ -24,47 +47,301 @@ pub struct VerificationResult {
     pub language: String,
     /// 元のコード
     pub code: String,
+    /// 断片のため検証しなかった（`success` は true、理由は `output`）
+    pub skipped: bool,
+}
+
+impl VerificationResult {
+    /// コマンドの終了状態と出力から
+    fn from_output(language: &str, code: &str, output: std::process::Output) -> Self {
+        Self {
+            success: output.status.success(),
+            output: String::from_utf8_lossy(&output.stdout).to_string(),
+            error: String::from_utf8_lossy(&output.stderr).to_string(),
+            language: language.to_string(),
+            code: code.to_string(),
+            skipped: false,
+        }
+    }
+
+    /// 検証しなかった結果
+    pub fn skipped(language: &str, code: &str, reason: &str) -> Self {
+        Self {
+            success: true,
+            output: format!("skipped: {}", reason),
+            error: String::new(),
+            language: language.to_string(),
+            code: code.to_string(),
+            skipped: true,
+        }
+    }
+
+    /// 検証コマンドが時間内に終わらなかった結果（無限ループなどとして失敗扱い）
+    fn timed_out(language: &str, code: &str, limit: Duration) -> Self {
+        Self {
+            success: false,
+            output: String::new(),
+            error: format!("Execution timed out after {} seconds", limit.as_secs()),
+            language: language.to_string(),
+            code: code.to_string(),
+            skipped: false,
+        }
+    }
+}
+
+/// `cargo check --message-format=short` の診断1件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    /// error / warning
+    pub level: String,
+    pub message: String,
+}
+
+/// 短い形式の診断（`src/main.rs:3:5: error[E0425]: cannot find value `x``）を取り出す
+pub fn parse_short_diagnostics(output: &str) -> Vec<Diagnostic> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(4, ':');
+            let file = parts.next()?.trim();
+            let line_no = parts.next()?.trim().parse().ok()?;
+            let column = parts.next()?.trim().parse().ok()?;
+            let rest = parts.next()?.trim();
+            let (level, message) = rest.split_once(": ")?;
+            let level = level.split('[').next().unwrap_or(level);
+            if level != "error" && level != "warning" {
+                return None;
+            }
+            Some(Diagnostic {
+                file: file.to_string(),
+                line: line_no,
+                column,
+                level: level.to_string(),
+                message: message.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Rust のコードが単体のプログラムでない理由（断片でなければ None）
+///
+/// `fn main` がない、括弧の対応が取れていない、プロジェクトや周囲の項目（`crate::`・`super::`・
+/// impl の外の `self`）を参照しているものは、単体でコンパイルしても意味のあるエラーにならない
+pub fn rust_fragment_reason(code: &str) -> Option<&'static str> {
+    let opens = code.matches('{').count();
+    let closes = code.matches('}').count();
+    if opens != closes {
+        return Some("unbalanced braces");
+    }
+    if !code.contains("fn main(") {
+        return Some("no fn main");
+    }
+    if code.contains("crate::") || code.contains("super::") {
+        return Some("refers to project items");
+    }
+    let uses_self = code.contains("self.") || code.contains("&self") || code.contains("Self::");
+    if uses_self && !code.contains("impl ") {
+        return Some("uses self outside an impl");
+    }
+    None
+}
+
+/// TypeScript の検証用 tsconfig（型検査だけ）
+const TSCONFIG: &str = r#"{
+  "compilerOptions": {
+    "target": "es2020",
+    "module": "esnext",
+    "moduleResolution": "node",
+    "strict": true,
+    "noEmit": true,
+    "skipLibCheck": true
+  },
+  "files": ["snippet.ts"]
+}
+"#;
+
+/// Go の検証用モジュール
+const GO_MOD: &str = "module snippet\n\ngo 1.18\n";
+
+/// Cargo のパッケージとして検証できるプロジェクトルートか（ワークスペースだけの Cargo.toml は除く）
+fn is_cargo_package(root: &Path) -> bool {
+    std::fs::read_to_string(root.join("Cargo.toml")).is_ok_and(|manifest| manifest.contains("[package]"))
+}
+
+/// 検証用にプロジェクトの src/bin に置く一時ファイル（drop で消す）
+struct TempBin {
+    path: PathBuf,
+    /// src/bin を作った場合はそれも消す
+    created_dir: Option<PathBuf>,
+}
+
+impl TempBin {
+    fn create(root: &Path, name: &str, code: &str) -> std::io::Result<Self> {
+        let dir = root.join("src").join("bin");
+        let created_dir = (!dir.exists()).then(|| dir.clone());
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.rs", name));
+        std::fs::write(&path, code)?;
+        Ok(Self { path, created_dir })
+    }
+}
+
+impl Drop for TempBin {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+        if let Some(dir) = &self.created_dir {
+            let _ = std::fs::remove_dir(dir);
+        }
+    }
+}
+
+/// 応答内のコードブロック1つ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeSpan {
+    /// 言語（タグがなければ中身から推論、それもできなければ空）
+    pub language: String,
+    /// 中身（改行は \n にそろえる）
+    pub code: String,
+    /// 中身のバイト範囲（開始フェンスの次の行から、終了フェンスの前の改行の手前まで）
+    pub range: Range<usize>,
+}
+
+/// `block` の中身を `new_code` に差し替える（開始フェンスの改行が \r\n ならそれに合わせる）
+pub fn splice_code_block(content: &str, block: &CodeSpan, new_code: &str) -> String {
+    let newline = if content[..block.range.start].ends_with("\r\n") { "\r\n" } else { "\n" };
+    let mut new_code = new_code.replace("\r\n", "\n").replace('\n', newline);
+    // 空のブロックには終了フェンスの前の改行がない
+    if block.range.is_empty() && !new_code.is_empty() {
+        new_code.push_str(newline);
+    }
+    let mut spliced = String::with_capacity(content.len() + new_code.len());
+    spliced.push_str(&content[..block.range.start]);
+    spliced.push_str(&new_code);
+    spliced.push_str(&content[block.range.end..]);
+    spliced
 }
 
 /// コード検証エンジン
+#[derive(Debug, Clone)]
 pub struct CodeVerifier {
     /// 最大試行回数
     max_attempts: usize,
+    /// 検証コマンド1回あたりのタイムアウト
+    timeout: Duration,
+    /// プロジェクトルート（Cargo.toml があれば Rust は cargo check で検証する）
+    project_root: Option<PathBuf>,
+    /// 検証ツールを探す PATH（未設定なら環境変数の PATH）
+    search_path: Option<OsString>,
 }
 
-const EXECUTION_TIMEOUT: Duration = Duration::from_secs(10);
 
 impl CodeVerifier {
     pub fn new() -> Self {
-        Self { max_attempts: 3 }
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+            project_root: None,
+            search_path: None,
+        }
+    }
+
+    /// 修正を頼む最大回数を設定
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// 検証コマンド1回あたりのタイムアウトを設定
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// 検証ツールを探す PATH を指定する
+    pub fn with_search_path(mut self, path: impl Into<OsString>) -> Self {
+        self.search_path = Some(path.into());
+        self
+    }
+
+    /// インストールされている検証ツールのパス
+    fn find_program(&self, name: &str) -> Option<PathBuf> {
+        let path = self.search_path.clone().or_else(|| std::env::var_os("PATH"))?;
+        std::env::split_paths(&path)
+            .map(|dir| dir.join(name))
+            .find(|candidate| candidate.is_file())
+    }
+
+    /// プロジェクトルートを設定（Cargo プロジェクトなら依存クレートを使うコードも検証できる）
+    pub fn with_project_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.project_root = Some(root.into());
+        self
+    }
+
+    /// タイムアウト付きで実行して出力を集める（時間切れなら None、プロセスは終了させる）
+    async fn run(&self, command: &mut Command) -> Result<Option<std::process::Output>> {
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        match timeout(self.timeout, command.output()).await {
+            Ok(output) => Ok(Some(output?)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// 実行して結果にする（時間切れは失敗）
+    async fn run_to_result(&self, language: &str, code: &str, command: &mut Command) -> Result<VerificationResult> {
+        Ok(match self.run(command).await? {
+            Some(output) => VerificationResult::from_output(language, code, output),
+            None => VerificationResult::timed_out(language, code, self.timeout),
+        })
     }
 
     /// コードブロックを検出して検証
     pub fn extract_code_blocks(content: &str) -> Vec<(String, String)> {
+        Self::locate_code_blocks(content)
+            .into_iter()
+            .map(|block| (block.language, block.code))
+            .collect()
+    }
+
+    /// コードブロックを位置つきで検出する（閉じていないブロックは含まない）
+    pub fn locate_code_blocks(content: &str) -> Vec<CodeSpan> {
         let mut blocks = Vec::new();
-        let mut in_block = false;
-        let mut current_lang = String::new();
-        let mut current_code = Vec::new();
-
-        for line in content.lines() {
-            if line.trim().starts_with("```") {
-                if in_block {
-                    // ブロック終了
-                    let lang = if current_lang.is_empty() {
-                        Self::infer_language(&current_code.join("\n")).unwrap_or_default()
+        // 開いているブロックの言語タグと中身の開始位置
+        let mut open: Option<(String, usize)> = None;
+        let mut offset = 0;
+
+        for raw in content.split_inclusive('\n') {
+            let line_start = offset;
+            offset += raw.len();
+            let line = raw.trim();
+            if !line.starts_with("```") {
+                continue;
+            }
+            match open.take() {
+                Some((tag, body_start)) => {
+                    // 最後のコード行の改行（\n か \r\n）は中身に含めない
+                    let body = &content[body_start..line_start];
+                    let body = body.strip_suffix('\n').map_or(body, |b| b.strip_suffix('\r').unwrap_or(b));
+                    let body_end = body_start + body.len();
+                    let code = content[body_start..body_end]
+                        .split('\n')
+                        .map(|l| l.strip_suffix('\r').unwrap_or(l))
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    let language = if tag.is_empty() {
+                        Self::infer_language(&code).unwrap_or_default()
                     } else {
-                        current_lang.clone()
+                        tag
                     };
-                    blocks.push((lang, current_code.join("\n")));
-                    current_code.clear();
-                    in_block = false;
-                } else {
-                    // ブロック開始
-                    current_lang = line.trim()[3..].trim().to_string();
-                    in_block = true;
+                    blocks.push(CodeSpan { language, code, range: body_start..body_end });
                 }
-            } else if in_block {
-                current_code.push(line);
+                None => open = Some((line[3..].trim().to_string(), offset)),
             }
         }
 
@@ -77,7 +354,8 @@ impl CodeVerifier {
             "python" | "py" | "python3" => "python",
             "rust" | "rs" => "rust",
             "javascript" | "js" | "node" => "javascript",
-            "typescript" | "ts" => "typescript",
+            "typescript" | "ts" | "tsx" => "typescript",
+            "go" | "golang" => "go",
             "bash" | "sh" | "shell" => "bash",
             _ => lang,
         }
@@ -87,7 +365,12 @@ impl CodeVerifier {
     pub fn infer_language(code: &str) -> Option<String> {
         let first_lines: String = code.lines().take(5).collect::<Vec<_>>().join("\n");
 
-        if first_lines.contains("def ") || first_lines.contains("import ") || first_lines.contains("print(") {
+        // import を含むので Python より先に判定する
+        if first_lines.starts_with("package ") || first_lines.contains("\nfunc ") || first_lines.starts_with("func ") {
+            Some("go".to_string())
+        } else if first_lines.contains("interface ") || first_lines.contains(": string") || first_lines.contains(": number") {
+            Some("typescript".to_string())
+        } else if first_lines.contains("def ") || first_lines.contains("import ") || first_lines.contains("print(") {
             Some("python".to_string())
         } else if first_lines.contains("fn ") || first_lines.contains("let ") || first_lines.contains("use ") {
             Some("rust".to_string())
@@ -100,154 +383,175 @@ impl CodeVerifier {
         }
     }
 
-    /// コードを実行して検証
-    pub fn verify(&self, language: &str, code: &str) -> Result<VerificationResult> {
+
+    /// コードを実行して検証（コマンドは `with_timeout` の時間で打ち切る）
+    pub async fn verify(&self, language: &str, code: &str) -> Result<VerificationResult> {
         let lang = Self::normalize_language(language);
 
         match lang {
-            "python" => self.verify_python(code),
-            "rust" => self.verify_rust(code),
-            "javascript" => self.verify_javascript(code),
-            "bash" => self.verify_bash(code),
+            "python" => self.verify_python(code).await,
+            "rust" => self.verify_rust(code).await,
+            "javascript" => self.verify_javascript(code).await,
+            "typescript" => self.verify_typescript(code).await,
+            "go" => self.verify_go(code).await,
+            "bash" => self.verify_bash(code).await,
             _ => Ok(VerificationResult {
                 success: true,
                 output: format!("Verification not supported for language: {}", language),
                 error: String::new(),
                 language: language.to_string(),
                 code: code.to_string(),
+                skipped: false,
             }),
         }
     }
 
-    /// Python コードを検証
-    fn verify_python(&self, code: &str) -> Result<VerificationResult> {
-        let mut temp_file = NamedTempFile::with_suffix(".py")?;
-        temp_file.write_all(code.as_bytes())?;
-        temp_file.flush()?;
-
-        let output = Command::new("python3")
-            .arg(temp_file.path())
-            .output()?;
-
-        Ok(VerificationResult {
-            success: output.status.success(),
-            output: String::from_utf8_lossy(&output.stdout).to_string(),
-            error: String::from_utf8_lossy(&output.stderr).to_string(),
-            language: "python".to_string(),
-            code: code.to_string(),
-        })
+    /// 一時ディレクトリにコードを書く（drop で消える）
+    fn write_snippet(file_name: &str, code: &str) -> Result<(tempfile::TempDir, PathBuf)> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(file_name);
+        std::fs::write(&path, code)?;
+        Ok((dir, path))
     }
 
-    /// Python コードを検証（非同期、タイムアウト付き）
-    pub async fn verify_python_async(&self, code: &str) -> Result<VerificationResult> {
-        let mut temp_file = NamedTempFile::with_suffix(".py")?;
-        temp_file.write_all(code.as_bytes())?;
-        temp_file.flush()?;
-
-        let path = temp_file.path().to_path_buf();
-
-        let result = timeout(EXECUTION_TIMEOUT, async {
-            TokioCommand::new("python3")
-                .arg(&path)
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .output()
-                .await
-        }).await;
-
-        match result {
-            Ok(Ok(output)) => Ok(VerificationResult {
-                success: output.status.success(),
-                output: String::from_utf8_lossy(&output.stdout).to_string(),
-                error: String::from_utf8_lossy(&output.stderr).to_string(),
-                language: "python".to_string(),
-                code: code.to_string(),
-            }),
-            Ok(Err(e)) => Err(anyhow::anyhow!("Execution error: {}", e)),
-            Err(_) => Ok(VerificationResult {
-                success: false,
-                output: String::new(),
-                error: "Execution timed out after 10 seconds".to_string(),
-                language: "python".to_string(),
-                code: code.to_string(),
-            }),
-        }
+    /// Python コードを検証（実行する）
+    async fn verify_python(&self, code: &str) -> Result<VerificationResult> {
+        let (_dir, path) = Self::write_snippet("snippet.py", code)?;
+        self.run_to_result("python", code, Command::new("python3").arg(&path)).await
     }
 
-    /// コードを非同期で検証（タイムアウト付き）
-    pub async fn verify_async(&self, language: &str, code: &str) -> Result<VerificationResult> {
-        let lang = Self::normalize_language(language);
-
-        match lang {
-            "python" => self.verify_python_async(code).await,
-            // 他の言語は同期版にフォールバック
-            _ => self.verify(language, code),
+    /// Rust コードを検証（コンパイルのみ）
+    ///
+    /// 断片は検証せず、Cargo プロジェクトの中では `cargo check` を使う
+    async fn verify_rust(&self, code: &str) -> Result<VerificationResult> {
+        if let Some(reason) = rust_fragment_reason(code) {
+            return Ok(VerificationResult::skipped("rust", code, &format!("fragment ({})", reason)));
+        }
+        if let Some(root) = self.project_root.as_deref().filter(|root| is_cargo_package(root)) {
+            if let Some(result) = self.verify_rust_in_project(root, code).await? {
+                return Ok(result);
+            }
         }
+        self.verify_rust_standalone(code).await
     }
 
-    /// Rust コードを検証（コンパイルのみ）
-    fn verify_rust(&self, code: &str) -> Result<VerificationResult> {
-        let mut temp_file = NamedTempFile::with_suffix(".rs")?;
-        temp_file.write_all(code.as_bytes())?;
-        temp_file.flush()?;
+    /// プロジェクトの src/bin に一時的なバイナリとして置いて `cargo check` する
+    ///
+    /// 既存のコードのエラーは数えず、スニペットのファイルを指す診断だけを失敗とする。
+    /// バイナリとして検証できなければ None
+    async fn verify_rust_in_project(&self, root: &Path, code: &str) -> Result<Option<VerificationResult>> {
+        let name = format!("local_code_verify_{}", std::process::id());
+        let _bin = TempBin::create(root, &name, code)?;
+        let mut command = Command::new("cargo");
+        command.args(["check", "--message-format=short", "--bin", &name]).current_dir(root);
+        let Some(output) = self.run(&mut command).await? else {
+            return Ok(Some(VerificationResult::timed_out("rust", code, self.timeout)));
+        };
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        if stderr.contains("no bin target named") {
+            return Ok(None);
+        }
 
-        // rustc でコンパイルチェックのみ
-        let output = Command::new("rustc")
-            .arg("--emit=metadata")
-            .arg("-o")
-            .arg("/dev/null")
-            .arg(temp_file.path())
-            .output()?;
-
-        Ok(VerificationResult {
-            success: output.status.success(),
-            output: String::from_utf8_lossy(&output.stdout).to_string(),
-            error: String::from_utf8_lossy(&output.stderr).to_string(),
+        let file = format!("{}.rs", name);
+        let errors: Vec<String> = parse_short_diagnostics(&stderr)
+            .into_iter()
+            .filter(|d| d.level == "error" && d.file.ends_with(&file))
+            .map(|d| format!("snippet.rs:{}:{}: {}", d.line, d.column, d.message))
+            .collect();
+        let project_failed = !output.status.success() && errors.is_empty();
+        Ok(Some(VerificationResult {
+            success: errors.is_empty(),
+            output: if project_failed {
+                "cargo check reported errors outside the snippet; not attributed to it".to_string()
+            } else {
+                String::new()
+            },
+            error: errors.join("\n"),
             language: "rust".to_string(),
             code: code.to_string(),
-        })
+            skipped: false,
+        }))
+    }
+
+    /// 一時ファイルを rustc で検証（依存クレートは使えない）
+    async fn verify_rust_standalone(&self, code: &str) -> Result<VerificationResult> {
+        let (_dir, path) = Self::write_snippet("snippet.rs", code)?;
+        // rustc でコンパイルチェックのみ
+        let mut command = Command::new("rustc");
+        command.arg("--emit=metadata").arg("-o").arg("/dev/null").arg(&path);
+        self.run_to_result("rust", code, &mut command).await
     }
 
     /// JavaScript コードを検証（構文チェック）
-    fn verify_javascript(&self, code: &str) -> Result<VerificationResult> {
-        let mut temp_file = NamedTempFile::with_suffix(".js")?;
-        temp_file.write_all(code.as_bytes())?;
-        temp_file.flush()?;
+    async fn verify_javascript(&self, code: &str) -> Result<VerificationResult> {
+        let (_dir, path) = Self::write_snippet("snippet.js", code)?;
+        self.run_to_result("javascript", code, Command::new("node").arg("--check").arg(&path)).await
+    }
 
-        let output = Command::new("node")
-            .arg("--check")
-            .arg(temp_file.path())
-            .output()?;
+    /// TypeScript コードを検証（tsc --noEmit、なければ deno check）
+    async fn verify_typescript(&self, code: &str) -> Result<VerificationResult> {
+        let (dir, _) = Self::write_snippet("snippet.ts", code)?;
 
-        Ok(VerificationResult {
-            success: output.status.success(),
-            output: String::from_utf8_lossy(&output.stdout).to_string(),
-            error: String::from_utf8_lossy(&output.stderr).to_string(),
-            language: "javascript".to_string(),
-            code: code.to_string(),
-        })
+        let mut command = if let Some(tsc) = self.find_program("tsc") {
+            std::fs::write(dir.path().join("tsconfig.json"), TSCONFIG)?;
+            let mut command = Command::new(tsc);
+            command.arg("--pretty").arg("false");
+            command
+        } else if let Some(deno) = self.find_program("deno") {
+            let mut command = Command::new(deno);
+            command.args(["check", "snippet.ts"]);
+            command
+        } else {
+            return Ok(VerificationResult::skipped("typescript", code, "tsc not installed"));
+        };
+        command.current_dir(dir.path());
+        // tsc は診断を標準出力に書く
+        let mut result = self.run_to_result("typescript", code, &mut command).await?;
+        if !result.success && result.error.is_empty() {
+            result.error = std::mem::take(&mut result.output);
+        }
+        Ok(result)
     }
 
-    /// Bash コードを検証（構文チェック）
-    fn verify_bash(&self, code: &str) -> Result<VerificationResult> {
-        let mut temp_file = NamedTempFile::with_suffix(".sh")?;
-        temp_file.write_all(code.as_bytes())?;
-        temp_file.flush()?;
-
-        let output = Command::new("bash")
-            .arg("-n")
-            .arg(temp_file.path())
-            .output()?;
+    /// Go コードを検証（一時モジュールで go build、通れば go vet）
+    async fn verify_go(&self, code: &str) -> Result<VerificationResult> {
+        if !code.lines().any(|line| line.trim_start().starts_with("package ")) {
+            return Ok(VerificationResult::skipped("go", code, "fragment (no package clause)"));
+        }
+        let Some(go) = self.find_program("go") else {
+            return Ok(VerificationResult::skipped("go", code, "go not installed"));
+        };
+        let (dir, _) = Self::write_snippet("snippet.go", code)?;
+        std::fs::write(dir.path().join("go.mod"), GO_MOD)?;
 
-        Ok(VerificationResult {
-            success: output.status.success(),
-            output: String::from_utf8_lossy(&output.stdout).to_string(),
-            error: String::from_utf8_lossy(&output.stderr).to_string(),
-            language: "bash".to_string(),
-            code: code.to_string(),
-        })
+        let build = self
+            .run_to_result("go", code, Command::new(&go).args(["build", "-o", "/dev/null", "."]).current_dir(dir.path()))
+            .await?;
+        if !build.success {
+            return Ok(build);
+        }
+        self.run_to_result("go", code, Command::new(&go).args(["vet", "."]).current_dir(dir.path())).await
     }
 
+    /// Bash コードを検証（shellcheck があればそれで、なければ構文チェックのみ）
+    async fn verify_bash(&self, code: &str) -> Result<VerificationResult> {
+        let (_dir, path) = Self::write_snippet("snippet.sh", code)?;
+
+        if let Some(shellcheck) = self.find_program("shellcheck") {
+            let mut command = Command::new(shellcheck);
+            command.args(["--shell=bash", "--severity=warning", "--format=gcc"]).arg(&path);
+            // gcc 形式の指摘は標準出力に出る
+            let mut result = self.run_to_result("bash", code, &mut command).await?;
+            if !result.success && result.error.is_empty() {
+                result.error = std::mem::take(&mut result.output);
+            }
+            return Ok(result);
+        }
+        let Some(bash) = self.find_program("bash") else {
+            return Ok(VerificationResult::skipped("bash", code, "bash not installed"));
+        };
+        self.run_to_result("bash", code, Command::new(bash).arg("-n").arg(&path)).await
+    }
     /// 修正プロンプトを生成
     pub fn create_fix_prompt(&self, result: &VerificationResult) -> String {
         format!(
@@ -280,6 +584,379 @@ impl Default for CodeVerifier {
     }
 }
 
+
+/// 検証に失敗したコードの修正を頼む相手（REPL ではエージェント）
+#[async_trait]
+pub trait CodeFixer: Send {
+    /// 修正プロンプトを送り、応答テキストを返す（`attempt` は1始まり）
+    async fn fix(&mut self, prompt: &str, attempt: usize, max_attempts: usize) -> Result<String>;
+
+    /// Rust を `cargo check` で検証するプロジェクトルート
+    fn project_root(&self) -> Option<PathBuf> {
+        None
+    }
+}
+
+#[async_trait]
+impl CodeFixer for super::Agent {
+    async fn fix(&mut self, prompt: &str, _attempt: usize, _max_attempts: usize) -> Result<String> {
+        self.tag_next_turn(super::MessageOrigin::Verification, None);
+        Ok(self.process_detailed(prompt).await?.response)
+    }
+
+    fn project_root(&self) -> Option<PathBuf> {
+        super::Agent::project_root(self).map(Path::to_path_buf)
+    }
+}
+
+/// 1つのコードブロックの検証と修正の経過
+#[derive(Debug, Clone)]
+pub struct BlockReport {
+    pub language: String,
+    /// 修正を頼んだ回数
+    pub attempts: usize,
+    /// 修正したコードが検証を通った
+    pub fixed: bool,
+    /// 最後の検証結果
+    pub result: VerificationResult,
+}
+
+impl BlockReport {
+    /// REPL に出す1行
+    pub fn summary(&self) -> String {
+        let language = &self.result.language;
+        if self.result.skipped {
+            let reason = self.result.output.trim_start_matches("skipped: ");
+            format!("⏭ {} code not verified ({})", language, reason)
+        } else if self.result.success && self.attempts == 0 {
+            format!("✅ {} code verified", language)
+        } else if self.fixed {
+            let plural = if self.attempts == 1 { "" } else { "s" };
+            format!("✅ {} code fixed after {} attempt{}", language, self.attempts, plural)
+        } else if self.attempts == 0 {
+            format!("❌ {} code failed verification", language)
+        } else {
+            format!("⚠️ Could not fix {} code after {} attempts", language, self.attempts)
+        }
+    }
+}
+
+/// `VerificationPipeline::run` の結果
+#[derive(Debug, Clone, Default)]
+pub struct VerificationReport {
+    /// 修正したコードブロックを差し替えた応答
+    pub text: String,
+    /// 検証したコードブロック（応答内の順）
+    pub blocks: Vec<BlockReport>,
+}
+
+impl VerificationReport {
+    /// 修正を頼んだ回数の合計
+    pub fn attempts(&self) -> usize {
+        self.blocks.iter().map(|block| block.attempts).sum()
+    }
+}
+
+/// コードブロック1つの検証（既定は `CodeVerifier`）
+#[async_trait]
+pub trait BlockVerifier: Send + Sync {
+    async fn verify(&self, language: &str, code: &str) -> Result<VerificationResult>;
+}
+
+#[async_trait]
+impl BlockVerifier for CodeVerifier {
+    async fn verify(&self, language: &str, code: &str) -> Result<VerificationResult> {
+        CodeVerifier::verify(self, language, code).await
+    }
+}
+
+/// 既定の同時検証数（CPU 数の半分、少なくとも1）
+pub fn default_parallelism() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| (n.get() / 2).max(1))
+}
+
+/// 応答内のコードブロックを検証し、失敗したら修正を頼む（`[verification]`、`/verify on|off`）
+///
+/// ブロックは `parallelism` 個ずつ同時に検証する。修正の依頼は会話が1つなので1件ずつ送るが、
+/// 結果は応答内の順に並べ、差し替えもその順に行う
+pub struct VerificationPipeline {
+    verifier: CodeVerifier,
+    /// 検証を差し替える（未設定なら `verifier` にプロジェクトルートを付けて使う）
+    block_verifier: Option<Arc<dyn BlockVerifier>>,
+    enabled: bool,
+    /// 検証する言語（正規化した名前）
+    languages: Vec<String>,
+    /// 同時に動かす検証の数
+    parallelism: usize,
+    /// ブロックごとの進行の通知先（REPL のスピナー）
+    events: Option<UnboundedSender<AgentEvent>>,
+}
+
+impl VerificationPipeline {
+    /// すべての対応言語を検証する
+    pub fn new(verifier: CodeVerifier) -> Self {
+        Self {
+            verifier,
+            block_verifier: None,
+            enabled: true,
+            languages: SUPPORTED_LANGUAGES.iter().map(|s| s.to_string()).collect(),
+            parallelism: default_parallelism(),
+            events: None,
+        }
+    }
+
+    /// `[verification]` の設定から作成
+    pub fn from_config(config: &VerificationConfig) -> Self {
+        let verifier = CodeVerifier::new()
+            .with_max_attempts(config.max_attempts)
+            .with_timeout(Duration::from_secs(config.timeout_secs));
+        Self::new(verifier)
+            .with_languages(&config.languages)
+            .with_enabled(config.enabled)
+            .with_parallelism(config.parallelism)
+    }
+
+    /// 検証する言語を限定する（`py`・`ts` などの別名も使える）
+    pub fn with_languages<I>(mut self, languages: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.languages = languages
+            .into_iter()
+            .map(|lang| CodeVerifier::normalize_language(lang.as_ref()).to_lowercase())
+            .collect();
+        self
+    }
+
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// 同時に動かす検証の数（0 は 1 として扱う）
+    pub fn with_parallelism(mut self, parallelism: usize) -> Self {
+        self.parallelism = parallelism.max(1);
+        self
+    }
+
+    /// コードブロックの検証を差し替える
+    pub fn with_block_verifier(mut self, verifier: Arc<dyn BlockVerifier>) -> Self {
+        self.block_verifier = Some(verifier);
+        self
+    }
+
+    /// ブロックごとの進行を知らせる
+    pub fn with_events(mut self, events: UnboundedSender<AgentEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// 実行中に検証を止める・再開する（`/verify off|on`）
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// `/verify` で表示する現在の設定
+    pub fn describe(&self) -> String {
+        if !self.enabled {
+            return "Verification is off.".to_string();
+        }
+        format!(
+            "Verification is on: {} (up to {} fix attempts, {}s timeout per check, {} at a time).",
+            self.languages.join(", "),
+            self.verifier.max_attempts(),
+            self.verifier.timeout.as_secs(),
+            self.parallelism
+        )
+    }
+
+    /// 検証するコードブロック（言語が分からないもの・対象外の言語は除く）
+    fn blocks(&self, text: &str) -> Vec<CodeSpan> {
+        CodeVerifier::locate_code_blocks(text)
+            .into_iter()
+            .filter(|block| {
+                let lang = &block.language;
+                !lang.is_empty() && self.languages.iter().any(|allowed| allowed == CodeVerifier::normalize_language(lang))
+            })
+            .collect()
+    }
+
+    /// 修正したコードを応答に差し込む
+    ///
+    /// 差し込むたびに位置を検出し直し、同じ並びの `index` 番目のブロックを差し替える。
+    /// その位置の中身が元のコードでなければ（応答が書き換わっていれば）同じ中身のブロックを探し、
+    /// 見つからなければ差し替えない
+    fn splice_fix(&self, text: &str, index: usize, original: &str, fixed: &str) -> String {
+        let blocks = self.blocks(text);
+        let target = blocks
+            .get(index)
+            .filter(|block| block.code == original)
+            .or_else(|| blocks.iter().find(|block| block.code == original));
+        match target {
+            Some(block) => splice_code_block(text, block, fixed),
+            None => {
+                tracing::warn!("Fixed code block {} is no longer in the response; left as is", index + 1);
+                text.to_string()
+            }
+        }
+    }
+
+    fn verifier_for(&self, project_root: Option<PathBuf>) -> Arc<dyn BlockVerifier> {
+        if let Some(verifier) = &self.block_verifier {
+            return Arc::clone(verifier);
+        }
+        match project_root {
+            Some(root) => Arc::new(self.verifier.clone().with_project_root(root)),
+            None => Arc::new(self.verifier.clone()),
+        }
+    }
+
+    fn emit(&self, event: AgentEvent) {
+        if let Some(tx) = &self.events {
+            let _ = tx.send(event);
+        }
+    }
+
+    /// 同時実行数の枠を取ってから検証する
+    async fn verify_bounded(
+        verifier: &dyn BlockVerifier,
+        permits: &Semaphore,
+        language: &str,
+        code: &str,
+    ) -> Result<VerificationResult> {
+        let _permit = permits.acquire().await;
+        verifier.verify(language, code).await
+    }
+
+    /// 応答内のコードを検証するだけ（修正は頼まない。`--output json` 用）
+    pub async fn check(&self, text: &str, project_root: Option<&Path>) -> Vec<VerificationResult> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        let verifier = self.verifier_for(project_root.map(Path::to_path_buf));
+        let permits = Semaphore::new(self.parallelism);
+        let blocks = self.blocks(text);
+        let results = join_all(
+            blocks
+                .iter()
+                .map(|block| Self::verify_bounded(verifier.as_ref(), &permits, &block.language, &block.code)),
+        )
+        .await;
+        results
+            .into_iter()
+            .filter_map(|result| result.map_err(|e| tracing::debug!("Verification skipped: {}", e)).ok())
+            .collect()
+    }
+
+    /// 応答内のコードを検証し、失敗したブロックは `fixer` に修正を頼んで差し替える
+    ///
+    /// 無効なら何もせず応答をそのまま返す
+    pub async fn run<F: CodeFixer + ?Sized>(&self, response: &str, fixer: &mut F) -> VerificationReport {
+        let mut report = VerificationReport {
+            text: response.to_string(),
+            blocks: Vec::new(),
+        };
+        if !self.enabled {
+            return report;
+        }
+        let verifier = self.verifier_for(fixer.project_root());
+        let permits = Semaphore::new(self.parallelism);
+        let fixer = Mutex::new(fixer);
+        let blocks = self.blocks(response);
+        let total = blocks.len();
+
+        let results = join_all(blocks.iter().enumerate().map(|(i, block)| {
+            self.verify_block(verifier.as_ref(), &permits, &fixer, (i + 1, total), &block.language, &block.code)
+        }))
+        .await;
+
+        for (index, (original, block)) in blocks.iter().zip(results).enumerate() {
+            let Some(block) = block else {
+                continue;
+            };
+            if block.fixed {
+                report.text = self.splice_fix(&report.text, index, &original.code, &block.result.code);
+            }
+            report.blocks.push(block);
+        }
+        report
+    }
+
+    /// 1つのブロックを検証し、失敗したら通るか上限まで修正を頼む（検証できなければ None）
+    async fn verify_block<F: CodeFixer + ?Sized>(
+        &self,
+        verifier: &dyn BlockVerifier,
+        permits: &Semaphore,
+        fixer: &Mutex<&mut F>,
+        (block, total): (usize, usize),
+        lang: &str,
+        code: &str,
+    ) -> Option<BlockReport> {
+        let language = lang.to_string();
+        self.emit(AgentEvent::VerificationStarted { language: language.clone(), block, total });
+        let mut result = match Self::verify_bounded(verifier, permits, lang, code).await {
+            Ok(result) => result,
+            Err(e) => {
+                tracing::debug!("Verification skipped: {}", e);
+                return None;
+            }
+        };
+        let max_attempts = self.verifier.max_attempts();
+        let mut report = BlockReport {
+            language: language.clone(),
+            attempts: 0,
+            fixed: false,
+            result: result.clone(),
+        };
+
+        while !result.success && report.attempts < max_attempts {
+            report.attempts += 1;
+            self.emit(AgentEvent::FixRequested {
+                language: language.clone(),
+                block,
+                total,
+                attempt: report.attempts,
+                max_attempts,
+            });
+            let prompt = self.verifier.create_fix_prompt(&result);
+            // 会話は1つなので、ほかのブロックの修正とは順番に送る
+            let reply = fixer.lock().await.fix(&prompt, report.attempts, max_attempts).await;
+            let reply = match reply {
+                Ok(reply) => reply,
+                Err(e) => {
+                    tracing::warn!("Fix attempt failed: {}", e);
+                    break;
+                }
+            };
+            // フェンスがない応答は全体をコードとして扱う
+            let fixed = OutputPostProcessor::process(&reply, true);
+            let fixed_code = match CodeVerifier::extract_code_blocks(&fixed).into_iter().next() {
+                Some((_, code)) => code,
+                None => fixed.trim().to_string(),
+            };
+            if fixed_code.is_empty() {
+                continue;
+            }
+            match Self::verify_bounded(verifier, permits, lang, &fixed_code).await {
+                Ok(fixed_result) => result = fixed_result,
+                Err(e) => {
+                    tracing::warn!("Verification error: {}", e);
+                    break;
+                }
+            }
+            report.fixed = result.success;
+        }
+        report.result = result;
+        self.emit(AgentEvent::VerificationFinished { language, block, total, success: report.result.success });
+        Some(report)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -300,12 +977,61 @@ And more text.
         assert!(blocks[0].1.contains("def hello()"));
     }
 
+    #[test]
+    fn test_locate_and_splice_crlf_blocks() {
+        let content = "Fix:\r\n```python\r\nprint(1\r\nx = 2\r\n```\r\nDone.\r\n";
+        let blocks = CodeVerifier::locate_code_blocks(content);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].code, "print(1\nx = 2");
+        assert_eq!(&content[blocks[0].range.clone()], "print(1\r\nx = 2");
+
+        let spliced = splice_code_block(content, &blocks[0], "print(1)\nx = 2");
+        assert_eq!(spliced, "Fix:\r\n```python\r\nprint(1)\r\nx = 2\r\n```\r\nDone.\r\n");
+    }
+
+    #[test]
+    fn test_splice_targets_one_of_duplicate_blocks() {
+        let content = "```rust\nlet x = 1\n```\nand\n```rust\nlet x = 1\n```\n";
+        let blocks = CodeVerifier::locate_code_blocks(content);
+        assert_eq!(blocks.len(), 2);
+        let spliced = splice_code_block(content, &blocks[1], "let x = 1;");
+        assert_eq!(spliced, "```rust\nlet x = 1\n```\nand\n```rust\nlet x = 1;\n```\n");
+
+        let pipeline = VerificationPipeline::new(CodeVerifier::new());
+        // 同じ並びの位置が書き換わっていれば、同じ中身のブロックを探す
+        let edited = "Intro\n```rust\nlet y = 2\n```\n```rust\nlet x = 1\n```\n";
+        assert_eq!(
+            pipeline.splice_fix(edited, 0, "let x = 1", "let x = 1;"),
+            "Intro\n```rust\nlet y = 2\n```\n```rust\nlet x = 1;\n```\n"
+        );
+        assert_eq!(pipeline.splice_fix(edited, 0, "let z = 3", "let z = 3;"), edited);
+    }
+
+    #[test]
+    fn test_splice_blocks_without_language_tag() {
+        let content = "  ```\ndef f(:\n    pass\n  ```\n```\n```";
+        let blocks = CodeVerifier::locate_code_blocks(content);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].language, "python");
+        let spliced = splice_code_block(content, &blocks[0], "def f():\n    pass");
+        assert_eq!(spliced, "  ```\ndef f():\n    pass\n  ```\n```\n```");
+
+        // 空のブロックには改行を足して差し込む
+        assert!(blocks[1].range.is_empty());
+        assert_eq!(blocks[1].code, "");
+        let spliced = splice_code_block(content, &blocks[1], "echo hi");
+        assert!(spliced.ends_with("```\necho hi\n```"), "{:?}", spliced);
+    }
+
     #[test]
     fn test_normalize_language() {
         assert_eq!(CodeVerifier::normalize_language("py"), "python");
         assert_eq!(CodeVerifier::normalize_language("Python"), "python");
         assert_eq!(CodeVerifier::normalize_language("rs"), "rust");
         assert_eq!(CodeVerifier::normalize_language("js"), "javascript");
+        assert_eq!(CodeVerifier::normalize_language("TS"), "typescript");
+        assert_eq!(CodeVerifier::normalize_language("tsx"), "typescript");
+        assert_eq!(CodeVerifier::normalize_language("golang"), "go");
     }
 
     #[test]
@@ -315,6 +1041,14 @@ And more text.
         assert_eq!(CodeVerifier::infer_language("const x = 1;"), Some("javascript".to_string()));
         assert_eq!(CodeVerifier::infer_language("#!/bin/bash\necho hi"), Some("bash".to_string()));
         assert_eq!(CodeVerifier::infer_language("some random text"), None);
+        assert_eq!(
+            CodeVerifier::infer_language("package main\n\nimport \"fmt\"\n\nfunc main() {}"),
+            Some("go".to_string())
+        );
+        assert_eq!(
+            CodeVerifier::infer_language("interface User {\n  name: string;\n}"),
+            Some("typescript".to_string())
+        );
     }
 
     #[test]
@@ -324,4 +1058,276 @@ And more text.
         assert_eq!(blocks.len(), 1);
         assert_eq!(blocks[0].0, "python");
     }
+
+    #[tokio::test]
+    async fn test_rust_fragment_heuristic() {
+        assert_eq!(rust_fragment_reason("let x = 1;"), Some("no fn main"));
+        assert_eq!(rust_fragment_reason("fn helper() -> u32 { 1 }"), Some("no fn main"));
+        assert_eq!(rust_fragment_reason("fn main() {\n    if x {"), Some("unbalanced braces"));
+        assert_eq!(
+            rust_fragment_reason("fn main() { crate::config::load(); }"),
+            Some("refers to project items")
+        );
+        assert_eq!(rust_fragment_reason("fn main() { self.run(); }"), Some("uses self outside an impl"));
+        assert_eq!(rust_fragment_reason("fn main() { println!(\"hi\"); }"), None);
+        let with_impl = "struct A;\nimpl A { fn run(&self) {} }\nfn main() { A.run(); }";
+        assert_eq!(rust_fragment_reason(with_impl), None);
+
+        let result = CodeVerifier::new().verify("rust", "x.push(1);").await.unwrap();
+        assert!(result.success && result.skipped);
+        assert_eq!(result.output, "skipped: fragment (no fn main)");
+    }
+
+    #[test]
+    fn test_parse_short_diagnostics() {
+        let output = "\
+    Checking demo v0.1.0 (/tmp/demo)
+src/bin/local_code_verify_1.rs:3:5: error[E0425]: cannot find value `y` in this scope
+src/lib.rs:1:4: warning: function `unused` is never used
+error: could not compile `demo` (bin \"local_code_verify_1\") due to 1 previous error
+";
+        let diagnostics = parse_short_diagnostics(output);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0],
+            Diagnostic {
+                file: "src/bin/local_code_verify_1.rs".to_string(),
+                line: 3,
+                column: 5,
+                level: "error".to_string(),
+                message: "cannot find value `y` in this scope".to_string(),
+            }
+        );
+        assert_eq!(diagnostics[1].level, "warning");
+    }
+
+    #[tokio::test]
+    async fn test_cargo_project_snippets_can_use_the_crate() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir(root.join("src")).unwrap();
+        std::fs::write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"demo\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("src/lib.rs"), "pub fn answer() -> u32 { 42 }\n").unwrap();
+        let verifier = CodeVerifier::new().with_project_root(root);
+
+        let result = verifier.verify("rust", "fn main() { println!(\"{}\", demo::answer()); }").await.unwrap();
+        assert!(result.success, "{}", result.error);
+        assert!(!result.skipped);
+
+        let result = verifier.verify("rust", "fn main() { let n: u32 = demo::answer(); n.missing(); }").await.unwrap();
+        assert!(!result.success);
+        assert!(result.error.starts_with("snippet.rs:1:"), "{}", result.error);
+        // 一時的なバイナリは残さない
+        assert!(!root.join("src/bin").exists());
+    }
+
+    #[tokio::test]
+    async fn test_missing_toolchains_are_skipped() {
+        let empty = tempfile::tempdir().unwrap();
+        let verifier = CodeVerifier::new().with_search_path(empty.path());
+
+        let result = verifier.verify("ts", "const x: number = 1;").await.unwrap();
+        assert!(result.success && result.skipped);
+        assert_eq!(result.output, "skipped: tsc not installed");
+
+        let result = verifier.verify("go", "package main\n\nfunc main() {}").await.unwrap();
+        assert_eq!(result.output, "skipped: go not installed");
+        let result = verifier.verify("go", "x := 1").await.unwrap();
+        assert_eq!(result.output, "skipped: fragment (no package clause)");
+
+        let result = verifier.verify("bash", "echo hi").await.unwrap();
+        assert_eq!(result.output, "skipped: bash not installed");
+    }
+
+    /// インストールされているときだけ実際に検証する
+    fn installed(program: &str) -> bool {
+        CodeVerifier::new().find_program(program).is_some()
+    }
+
+    #[tokio::test]
+    async fn test_typescript_type_errors() {
+        if !installed("tsc") && !installed("deno") {
+            return;
+        }
+        let verifier = CodeVerifier::new();
+        assert!(verifier.verify("typescript", "const n: number = 1;\nconsole.log(n);").await.unwrap().success);
+        let result = verifier.verify("typescript", "const n: number = \"one\";").await.unwrap();
+        assert!(!result.success);
+        assert!(!result.error.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_go_build_errors() {
+        if !installed("go") {
+            return;
+        }
+        let verifier = CodeVerifier::new();
+        let ok = "package main\n\nimport \"fmt\"\n\nfunc main() { fmt.Println(1) }\n";
+        assert!(verifier.verify("go", ok).await.unwrap().success);
+        let result = verifier.verify("go", "package main\n\nfunc main() { x := 1 }\n").await.unwrap();
+        assert!(!result.success);
+        assert!(result.error.contains("declared and not used"), "{}", result.error);
+    }
+
+    #[tokio::test]
+    async fn test_bash_verification() {
+        if !installed("bash") {
+            return;
+        }
+        let verifier = CodeVerifier::new();
+        assert!(verifier.verify("sh", "echo \"$HOME\"").await.unwrap().success);
+        assert!(!verifier.verify("bash", "if true; then echo hi").await.unwrap().success);
+    }
+
+    #[tokio::test]
+    async fn test_infinite_loop_times_out() {
+        if !installed("python3") {
+            return;
+        }
+        let verifier = CodeVerifier::new().with_timeout(Duration::from_secs(1));
+        let started = std::time::Instant::now();
+        let result = verifier.verify("python", "while True:\n    pass").await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(!result.success);
+        assert_eq!(result.error, "Execution timed out after 1 seconds");
+    }
+
+    /// 試行回数ごとに決まった応答を返し、頼まれた回数を数える
+    struct ScriptedFixer {
+        replies: Vec<&'static str>,
+        calls: usize,
+    }
+
+    #[async_trait]
+    impl CodeFixer for ScriptedFixer {
+        async fn fix(&mut self, _prompt: &str, attempt: usize, _max_attempts: usize) -> Result<String> {
+            self.calls += 1;
+            Ok(self.replies.get(attempt - 1).copied().unwrap_or("```bash\nif true; then\n```").to_string())
+        }
+    }
+
+    const BROKEN: &str = "Try this:\n```bash\nif true; then echo hi\n```\n";
+
+    #[tokio::test]
+    async fn test_pipeline_counts_fix_attempts() {
+        if !installed("bash") {
+            return;
+        }
+        let pipeline = VerificationPipeline::new(CodeVerifier::new().with_max_attempts(3));
+
+        // 2回目の修正で通る
+        let mut fixer = ScriptedFixer {
+            replies: vec!["```bash\nif true; then\n```", "```bash\nif true; then echo hi; fi\n```"],
+            calls: 0,
+        };
+        let report = pipeline.run(BROKEN, &mut fixer).await;
+        assert_eq!(fixer.calls, 2);
+        assert_eq!(report.attempts(), 2);
+        assert!(report.blocks[0].fixed);
+        assert_eq!(report.blocks[0].summary(), "✅ bash code fixed after 2 attempts");
+        assert_eq!(report.text, "Try this:\n```bash\nif true; then echo hi; fi\n```\n");
+
+        // 直らなければ上限で止め、応答はそのまま
+        let mut fixer = ScriptedFixer { replies: Vec::new(), calls: 0 };
+        let report = pipeline.run(BROKEN, &mut fixer).await;
+        assert_eq!(fixer.calls, 3);
+        assert!(!report.blocks[0].fixed);
+        assert_eq!(report.blocks[0].summary(), "⚠️ Could not fix bash code after 3 attempts");
+        assert_eq!(report.text, BROKEN);
+
+        // 通るコードは修正を頼まない
+        let mut fixer = ScriptedFixer { replies: Vec::new(), calls: 0 };
+        let report = pipeline.run("```sh\necho ok\n```", &mut fixer).await;
+        assert_eq!((fixer.calls, report.attempts()), (0, 0));
+        assert_eq!(report.blocks[0].summary(), "✅ bash code verified");
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_respects_config() {
+        let mut fixer = ScriptedFixer { replies: Vec::new(), calls: 0 };
+
+        let config = VerificationConfig { enabled: false, ..Default::default() };
+        let mut pipeline = VerificationPipeline::from_config(&config);
+        let report = pipeline.run(BROKEN, &mut fixer).await;
+        assert!(report.blocks.is_empty());
+        assert_eq!(report.text, BROKEN);
+        assert!(pipeline.check(BROKEN, None).await.is_empty());
+        assert_eq!(pipeline.describe(), "Verification is off.");
+
+        // 実行中に有効にしても、対象外の言語は検証しない
+        pipeline.set_enabled(true);
+        let pipeline = pipeline.with_languages(["py", "RS"]);
+        let report = pipeline.run(BROKEN, &mut fixer).await;
+        assert!(report.blocks.is_empty());
+        assert_eq!(fixer.calls, 0);
+        assert!(pipeline.describe().starts_with("Verification is on: python, rust (up to 3 fix attempts, 30s timeout"));
+    }
+
+    /// コードに書いたミリ秒だけ待ってから返す（`bad` を含むコードは失敗）。同時に動いた数の最大を記録する
+    #[derive(Default)]
+    struct SlowVerifier {
+        running: std::sync::atomic::AtomicUsize,
+        peak: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait]
+    impl BlockVerifier for SlowVerifier {
+        async fn verify(&self, language: &str, code: &str) -> Result<VerificationResult> {
+            use std::sync::atomic::Ordering;
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(running, Ordering::SeqCst);
+            let millis = code.split_whitespace().find_map(|word| word.parse().ok()).unwrap_or(0);
+            tokio::time::sleep(Duration::from_millis(millis)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            let mut result = VerificationResult::skipped(language, code, "mock");
+            result.skipped = false;
+            if code.contains("bad") {
+                result.success = false;
+                result.error = format!("{} failed", code);
+            }
+            Ok(result)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocks_are_verified_concurrently_in_order() {
+        let verifier = Arc::new(SlowVerifier::default());
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let pipeline = VerificationPipeline::new(CodeVerifier::new())
+            .with_block_verifier(verifier.clone())
+            .with_parallelism(2)
+            .with_events(tx);
+        let response = "```python\nfirst 300\n```\n```python\nsecond 10\n```\n```python\nthird 200\n```\n```python\nfourth 10\n```\n";
+
+        let started = std::time::Instant::now();
+        let results = pipeline.check(response, None).await;
+        let codes: Vec<&str> = results.iter().map(|r| r.code.as_str()).collect();
+        assert_eq!(codes, ["first 300", "second 10", "third 200", "fourth 10"]);
+        assert_eq!(verifier.peak.load(std::sync::atomic::Ordering::SeqCst), 2);
+        assert!(started.elapsed() < Duration::from_millis(500), "{:?}", started.elapsed());
+
+        // 修正の依頼は1件ずつ、差し替えは応答内の順
+        let response = "```python\nbad 200\n```\n```python\nok 10\n```\n```python\nbad 10\n```\n";
+        let mut fixer = ScriptedFixer { replies: vec!["```python\nfixed 10\n```"], calls: 0 };
+        let report = pipeline.run(response, &mut fixer).await;
+        assert_eq!(fixer.calls, 2);
+        let attempts: Vec<usize> = report.blocks.iter().map(|b| b.attempts).collect();
+        assert_eq!(attempts, [1, 0, 1]);
+        assert_eq!(report.text, "```python\nfixed 10\n```\n```python\nok 10\n```\n```python\nfixed 10\n```\n");
+
+        let mut started = 0;
+        let mut fixes = 0;
+        while let Ok(event) = rx.try_recv() {
+            match event {
+                AgentEvent::VerificationStarted { total: 3, .. } => started += 1,
+                AgentEvent::FixRequested { attempt: 1, max_attempts: 3, .. } => fixes += 1,
+                _ => {}
+            }
+        }
+        assert_eq!((started, fixes), (3, 2));
+    }
 }
@@ -0,0 +1,226 @@
+//! ターン中の進行表示
+//!
+//! エージェントの進行イベントと LLM のリトライ状況を受け取り、スピナーの文言を切り替える。
+//! ターンの処理と同じタスクでイベントを受けるので、結果を表示する前に必ずスピナーを止められる
+
+use std::future::Future;
+use std::time::Instant;
+
+use anyhow::Result;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio_util::sync::CancellationToken;
+
+use super::broadcast::{self, OutputEvent};
+use super::output::{print_error, print_success, print_tool};
+use super::spinner::Spinner;
+use crate::agent::AgentEvent;
+use crate::llm::RetryEvent;
+
+/// スピナーを使ったターンの進行表示
+pub struct Activity {
+    spinner: Spinner,
+    events: UnboundedReceiver<AgentEvent>,
+    retries: UnboundedReceiver<RetryEvent>,
+    /// 文言の前に付ける見出し（"Fix attempt 2/3" など）
+    label: Option<String>,
+    /// 直近の段階（リトライの待機が終わったら戻す）
+    phase: String,
+    /// ターン中に実行したツールの数
+    tools_run: usize,
+    /// ストリーミング表示中はスピナーを出さず、ツールの実行を1行ずつ書く（出力が崩れるため）
+    streaming: bool,
+}
+
+impl Activity {
+    pub fn new(events: UnboundedReceiver<AgentEvent>, retries: UnboundedReceiver<RetryEvent>) -> Self {
+        Self {
+            spinner: Spinner::new(),
+            events,
+            retries,
+            label: None,
+            phase: String::new(),
+            tools_run: 0,
+            streaming: false,
+        }
+    }
+
+    /// 次のターンでストリーミング表示を使うか
+    pub fn set_streaming(&mut self, streaming: bool) {
+        self.streaming = streaming;
+    }
+
+    /// 次のターンの文言に付ける見出し
+    pub fn with_label(&mut self, label: impl Into<String>) -> &mut Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// ターンを実行しながら進行を表示し、終わったらスピナーを止めてから結果を返す
+    pub async fn track<T>(&mut self, turn: impl Future<Output = Result<T>>, cancel: &CancellationToken) -> Result<T> {
+        let started = Instant::now();
+        self.tools_run = 0;
+        self.set_phase("Sending…".to_string()).await;
+
+        tokio::pin!(turn);
+        let result = loop {
+            tokio::select! {
+                result = &mut turn => break result,
+                Some(event) = self.events.recv() => self.on_event(event).await,
+                Some(event) = self.retries.recv() => self.on_retry(event).await,
+            }
+        };
+        while let Ok(event) = self.events.try_recv() {
+            self.on_event(event).await;
+        }
+
+        let label = self.label.take();
+        if self.streaming || cancel.is_cancelled() {
+            self.spinner.stop().await;
+        } else {
+            match &result {
+                Ok(_) => self.spinner.stop_with_success(&self.summary(started.elapsed().as_secs_f64())).await,
+                Err(_) => {
+                    let what = label.unwrap_or_else(|| "Request".to_string());
+                    self.spinner.stop_with_error(&format!("{} failed", what)).await;
+                }
+            }
+        }
+        result
+    }
+
+    /// モデルの読み込み中に "Loading model…" を表示し、終わったらスピナーを止める（失敗の表示は呼び出し側）
+    pub async fn loading<T>(&mut self, model: &str, load: impl Future<Output = Result<T>>) -> Result<T> {
+        let started = Instant::now();
+        self.spinner.start(&format!("Loading model {}…", model));
+        let result = load.await;
+        match &result {
+            Ok(_) => {
+                let message = format!("{} loaded in {:.1}s", model, started.elapsed().as_secs_f64());
+                self.spinner.stop_with_success(&message).await;
+            }
+            Err(_) => self.spinner.stop().await,
+        }
+        result
+    }
+
+    fn summary(&self, elapsed_secs: f64) -> String {
+        match self.tools_run {
+            0 => format!("Done in {:.1}s", elapsed_secs),
+            1 => format!("Done in {:.1}s (1 tool)", elapsed_secs),
+            n => format!("Done in {:.1}s ({} tools)", elapsed_secs, n),
+        }
+    }
+
+    async fn on_event(&mut self, event: AgentEvent) {
+        if let AgentEvent::ToolFinished(..) = event {
+            self.tools_run += 1;
+        }
+        if self.streaming {
+            match &event {
+                AgentEvent::ToolStarted(name) => print_tool(name, "executing..."),
+                AgentEvent::ToolFinished(name, true) => print_success(&format!("[{}] completed", name)),
+                AgentEvent::ToolFinished(name, false) => print_error(&format!("[{}] failed", name)),
+                _ => {}
+            }
+        }
+        if let Some(text) = event.status_text() {
+            self.set_phase(text).await;
+        }
+    }
+
+    /// リトライの待機中はその状況を表示し、終わったら元の段階に戻す
+    ///
+    /// 待機の開始は配信（`local-code watch`）にも流す
+    async fn on_retry(&mut self, event: RetryEvent) {
+        match event {
+            RetryEvent::Waiting(status) => {
+                if status.is_first_tick() {
+                    broadcast::emit(|| OutputEvent::Retry {
+                        error: status.error.clone(),
+                        attempt: status.attempt,
+                        max_retries: status.max_retries,
+                        wait_ms: status.wait.as_millis() as u64,
+                        text: status.message(),
+                    });
+                }
+                self.show(status.message()).await;
+            }
+            RetryEvent::Finished => {
+                let phase = self.phase.clone();
+                self.show(phase).await;
+            }
+        }
+    }
+
+    async fn set_phase(&mut self, phase: String) {
+        self.phase = phase_text(self.label.as_deref(), &phase);
+        let text = self.phase.clone();
+        self.show(text).await;
+    }
+
+    async fn show(&mut self, text: String) {
+        if self.streaming {
+            return;
+        }
+        if self.spinner.is_running() {
+            self.spinner.update(&text).await;
+        } else {
+            self.spinner.start(&text);
+        }
+    }
+}
+
+/// 見出しがあれば "見出し · 段階" にする
+fn phase_text(label: Option<&str>, phase: &str) -> String {
+    match label {
+        Some(label) => format!("{} · {}", label, phase),
+        None => phase.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    #[test]
+    fn test_phase_text() {
+        assert_eq!(phase_text(None, "Generating…"), "Generating…");
+        assert_eq!(phase_text(Some("Fix attempt 2/3"), "Running grep…"), "Fix attempt 2/3 · Running grep…");
+    }
+
+    #[tokio::test]
+    async fn test_track_follows_events_and_counts_tools() {
+        let (events_tx, events_rx) = unbounded_channel();
+        let (_retry_tx, retry_rx) = unbounded_channel();
+        let mut activity = Activity::new(events_rx, retry_rx);
+        activity.with_label("Fix attempt 1/3");
+
+        let turn = async {
+            events_tx.send(AgentEvent::GenerationStarted).unwrap();
+            events_tx.send(AgentEvent::ToolStarted("grep".to_string())).unwrap();
+            events_tx.send(AgentEvent::ToolFinished("grep".to_string(), true)).unwrap();
+            events_tx.send(AgentEvent::Done).unwrap();
+            Ok(42)
+        };
+        let result = activity.track(turn, &CancellationToken::new()).await.unwrap();
+
+        assert_eq!(result, 42);
+        assert_eq!(activity.tools_run, 1);
+        assert_eq!(activity.phase, "Fix attempt 1/3 · Running grep…");
+        assert!(!activity.spinner.is_running());
+        // 見出しはそのターンだけ
+        assert!(activity.label.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_loading_stops_the_spinner_on_error() {
+        let (_events_tx, events_rx) = unbounded_channel();
+        let (_retry_tx, retry_rx) = unbounded_channel();
+        let mut activity = Activity::new(events_rx, retry_rx);
+
+        let result: Result<()> = activity.loading("qwen", async { Err(anyhow::anyhow!("connection refused")) }).await;
+        assert!(result.is_err());
+        assert!(!activity.spinner.is_running());
+    }
+}
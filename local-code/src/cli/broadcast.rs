@@ -0,0 +1,233 @@
+//! 表示内容の配信（`[ui] broadcast = true`）
+//!
+//! 表示した内容をイベントとしてプロジェクトの `.local-code/watch/stream.jsonl` に追記する。
+//! 1行1レコードの JSON で、`local-code watch` が末尾を追って同じ表示関数で描画する。
+//! 読み取り専用の配信で、見る側からの入力経路はない。
+//!
+//! ```json
+//! {"v":1,"ts":1767225600123,"event":{"type":"block","title":"USER","content":"hi"}}
+//! ```
+//!
+//! ファイルが上限を超えたら `stream.jsonl.1` に退避して新しいファイルに切り替える
+//! （セッション開始時も同様）。ディレクトリには `.gitignore` を置いてコミットされないようにする。
+//! 内容は平文なので、`[storage] encrypt = true` のときは配信しない。
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// ストリーム形式のバージョン（互換性のない変更で上げる）
+pub const STREAM_VERSION: u32 = 1;
+
+/// ファイルを切り替えるサイズ
+const DEFAULT_MAX_BYTES: u64 = 4 * 1024 * 1024;
+
+/// 表示イベント（表示関数と1対1）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputEvent {
+    /// 配信の開始
+    SessionStart { pid: u32 },
+    /// 配信の終了（本体の終了）
+    SessionEnd,
+    /// タイトル付きブロック（`print_formatted_block`）
+    Block { title: String, content: String },
+    Separator,
+    Processing { text: String },
+    Info { text: String },
+    Error { text: String },
+    Success { text: String },
+    Tool { name: String, text: String },
+    Mode { mode: String },
+    Debug { text: String },
+    /// LLM リクエストのリトライ待機の開始
+    Retry { error: String, attempt: u32, max_retries: u32, wait_ms: u64, text: String },
+    Newline,
+    /// ストリーミング出力の開始
+    StreamStart { prefix: Option<String> },
+    StreamText { text: String },
+    /// ストリーミング出力の終了（統計行があれば付く）
+    StreamEnd { stats: Option<StreamEndStats> },
+}
+
+/// ストリーミング終了時の統計行
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamEndStats {
+    pub tokens_per_second: f64,
+    pub total_tokens: u32,
+    pub prompt_tokens: u32,
+    pub note: Option<String>,
+}
+
+/// ストリームの1レコード
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamRecord {
+    /// 形式のバージョン
+    pub v: u32,
+    /// 書き込み時刻（UNIX エポックからのミリ秒）
+    pub ts: u64,
+    pub event: OutputEvent,
+}
+
+impl StreamRecord {
+    pub fn new(event: OutputEvent) -> Self {
+        Self { v: STREAM_VERSION, ts: now_millis(), event }
+    }
+}
+
+/// 現在時刻（UNIX エポックからのミリ秒）
+pub fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// プロジェクトのストリームファイル
+pub fn stream_path(project_root: &Path) -> PathBuf {
+    project_root.join(".local-code").join("watch").join("stream.jsonl")
+}
+
+/// 切り替え時の退避先
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".1");
+    path.with_file_name(name)
+}
+
+/// 追記専用のストリームファイル
+#[derive(Debug)]
+pub struct StreamWriter {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    max_bytes: u64,
+}
+
+impl StreamWriter {
+    /// 新しいセッションのストリームを作る（既存のファイルは退避する）
+    pub fn create(path: &Path) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+            let ignore = parent.join(".gitignore");
+            if !ignore.exists() {
+                fs::write(ignore, "# local-code watch のストリーム（会話の平文）\n*\n")?;
+            }
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Self::open_fresh(path)?,
+            written: 0,
+            max_bytes: DEFAULT_MAX_BYTES,
+        })
+    }
+
+    /// 切り替えるサイズを指定
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    fn open_fresh(path: &Path) -> io::Result<File> {
+        if path.exists() {
+            fs::rename(path, rotated_path(path))?;
+        }
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    /// イベントを1行追記
+    pub fn append(&mut self, event: OutputEvent) -> io::Result<()> {
+        if self.written >= self.max_bytes {
+            self.file = Self::open_fresh(&self.path)?;
+            self.written = 0;
+        }
+        let mut line = serde_json::to_vec(&StreamRecord::new(event))?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.written += line.len() as u64;
+        Ok(())
+    }
+}
+
+/// 配信中か（表示関数がイベントを組み立てる前に確認する）
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static SINK: Mutex<Option<StreamWriter>> = Mutex::new(None);
+
+/// 配信を開始
+pub fn start(path: &Path) -> io::Result<()> {
+    let mut writer = StreamWriter::create(path)?;
+    writer.append(OutputEvent::SessionStart { pid: std::process::id() })?;
+    if let Ok(mut sink) = SINK.lock() {
+        *sink = Some(writer);
+        ACTIVE.store(true, Ordering::Release);
+    }
+    Ok(())
+}
+
+/// 配信中ならイベントを書き込む（書き込みに失敗したら配信を止める）
+pub fn emit(event: impl FnOnce() -> OutputEvent) {
+    if !ACTIVE.load(Ordering::Acquire) {
+        return;
+    }
+    let Ok(mut sink) = SINK.lock() else {
+        return;
+    };
+    if let Some(writer) = sink.as_mut() {
+        if let Err(e) = writer.append(event()) {
+            tracing::warn!("Stopped broadcasting output: {}", e);
+            *sink = None;
+            ACTIVE.store(false, Ordering::Release);
+        }
+    }
+}
+
+/// 終了を書き込んで配信を止める
+pub fn finish() {
+    emit(|| OutputEvent::SessionEnd);
+    ACTIVE.store(false, Ordering::Release);
+    if let Ok(mut sink) = SINK.lock() {
+        *sink = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_format_is_versioned_and_tagged() {
+        let record = StreamRecord {
+            v: STREAM_VERSION,
+            ts: 42,
+            event: OutputEvent::Block { title: "USER".into(), content: "hi".into() },
+        };
+        assert_eq!(
+            serde_json::to_string(&record).unwrap(),
+            r#"{"v":1,"ts":42,"event":{"type":"block","title":"USER","content":"hi"}}"#
+        );
+    }
+
+    #[test]
+    fn test_writer_rotates_previous_session_and_large_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = stream_path(dir.path());
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "old session\n").unwrap();
+
+        let mut writer = StreamWriter::create(&path).unwrap().with_max_bytes(64);
+        assert_eq!(std::fs::read_to_string(rotated_path(&path)).unwrap(), "old session\n");
+        // 会話の平文をコミットしないよう、ディレクトリごと無視させる
+        let ignore = std::fs::read_to_string(path.with_file_name(".gitignore")).unwrap();
+        assert_eq!(ignore.lines().last(), Some("*"));
+
+        writer.append(OutputEvent::Info { text: "x".repeat(80) }).unwrap();
+        writer.append(OutputEvent::Info { text: "after".into() }).unwrap();
+        let current = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(current.lines().count(), 1);
+        assert!(current.contains("after"));
+        assert!(std::fs::read_to_string(rotated_path(&path)).unwrap().contains("xxxx"));
+    }
+}
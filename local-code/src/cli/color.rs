@@ -0,0 +1,169 @@
+//! 色付けの有効・無効
+//!
+//! `--no-color`・`NO_COLOR`・`CLICOLOR_FORCE`・標準出力が端末かどうかから一度だけ決め、
+//! 以後はどこからでも [`enabled`] で参照する。
+//! 出力側は crossterm の代わりにここの `SetForegroundColor` などを使えば、
+//! 無効なときはエスケープシーケンスを書かない。
+
+use std::fmt;
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+use crossterm::style::{self, Attribute, Color};
+use crossterm::Command;
+
+/// 色を使うかどうかの指定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    /// 標準出力が端末なら使う
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    /// コマンドライン引数と環境変数から決める（`--no-color` > `NO_COLOR` > `CLICOLOR_FORCE`）
+    pub fn detect(no_color_flag: bool) -> Self {
+        let set = |name: &str| std::env::var(name).is_ok_and(|v| !v.is_empty());
+        let forced = std::env::var("CLICOLOR_FORCE").is_ok_and(|v| !v.is_empty() && v != "0");
+        Self::from_inputs(no_color_flag, set("NO_COLOR"), forced)
+    }
+
+    fn from_inputs(no_color_flag: bool, no_color_env: bool, clicolor_force: bool) -> Self {
+        if no_color_flag || no_color_env {
+            ColorMode::Never
+        } else if clicolor_force {
+            ColorMode::Always
+        } else {
+            ColorMode::Auto
+        }
+    }
+
+    /// 実際に色を使うか
+    pub fn resolve(self, is_terminal: bool) -> bool {
+        match self {
+            ColorMode::Auto => is_terminal,
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+}
+
+static COLOR_ENABLED: OnceLock<bool> = OnceLock::new();
+
+/// 起動時に一度だけ決める（2回目以降は無視）
+pub fn init(mode: ColorMode) {
+    let enabled = *COLOR_ENABLED.get_or_init(|| mode.resolve(std::io::stdout().is_terminal()));
+    // crossterm の Stylize も同じ判断に合わせる
+    style::force_color_output(enabled);
+}
+
+/// 色付けが有効か（init 前は環境変数と端末から判断する）
+pub fn enabled() -> bool {
+    *COLOR_ENABLED.get_or_init(|| ColorMode::detect(false).resolve(std::io::stdout().is_terminal()))
+}
+
+/// 有効なときだけ内側のコマンドを書く
+fn write_if(enabled: bool, command: &impl Command, f: &mut impl fmt::Write) -> fmt::Result {
+    if enabled {
+        command.write_ansi(f)
+    } else {
+        Ok(())
+    }
+}
+
+macro_rules! styled_command {
+    ($(#[$doc:meta])* $name:ident $(($arg:ty))?) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub struct $name $((pub $arg))?;
+
+        impl Command for $name {
+            fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+                write_if(enabled(), &self.inner(), f)
+            }
+
+            #[cfg(windows)]
+            fn execute_winapi(&self) -> std::io::Result<()> {
+                if enabled() {
+                    self.inner().execute_winapi()
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    };
+}
+
+styled_command!(
+    /// 色付けが有効なときだけ文字色を変える
+    SetForegroundColor(Color)
+);
+styled_command!(
+    /// 色付けが有効なときだけ太字などの属性を付ける
+    SetAttribute(Attribute)
+);
+styled_command!(
+    /// 色付けが有効なときだけ色を戻す
+    ResetColor
+);
+
+impl SetForegroundColor {
+    fn inner(&self) -> style::SetForegroundColor {
+        style::SetForegroundColor(self.0)
+    }
+}
+
+impl SetAttribute {
+    fn inner(&self) -> style::SetAttribute {
+        style::SetAttribute(self.0)
+    }
+}
+
+impl ResetColor {
+    fn inner(&self) -> style::ResetColor {
+        style::ResetColor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ansi(enabled: bool, command: &impl Command) -> String {
+        let mut out = String::new();
+        write_if(enabled, command, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_mode_precedence() {
+        assert_eq!(ColorMode::from_inputs(true, false, true), ColorMode::Never);
+        assert_eq!(ColorMode::from_inputs(false, true, true), ColorMode::Never);
+        assert_eq!(ColorMode::from_inputs(false, false, true), ColorMode::Always);
+        assert_eq!(ColorMode::from_inputs(false, false, false), ColorMode::Auto);
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert!(ColorMode::Auto.resolve(true));
+        assert!(!ColorMode::Auto.resolve(false));
+        assert!(ColorMode::Always.resolve(false));
+        assert!(!ColorMode::Never.resolve(true));
+    }
+
+    #[test]
+    fn test_never_writes_no_escape_bytes() {
+        let enabled = ColorMode::Never.resolve(true);
+        let out = [
+            ansi(enabled, &SetForegroundColor(Color::Red).inner()),
+            ansi(enabled, &SetAttribute(Attribute::Bold).inner()),
+            ansi(enabled, &ResetColor.inner()),
+        ]
+        .concat();
+        assert!(!out.contains('\x1b'), "{:?}", out);
+
+        let out = ansi(ColorMode::Always.resolve(false), &SetAttribute(Attribute::Bold).inner());
+        assert!(out.starts_with('\x1b'));
+    }
+}
@@ -1,7 +1,21 @@
 use crate::agent::mode::ModeManager;
+use crate::agent::environment::EnvProber;
 use crate::agent::history::HistoryManager;
-use crate::skills::SkillRegistry;
+use crate::agent::export::ExportFormat;
+use crate::agent::memory::MemoryScope;
+use crate::agent::status::{StatusRegistry, SubsystemStatus};
+use crate::llm::RequestOptions;
+use crate::skills::{Skill, SkillOrigin, SkillRegistry};
+use crate::tools::Workspace;
+use super::layout::{terminal_width, truncate_with_ellipsis};
+use super::locale::CommandTable;
+use crossterm::style::Stylize;
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// /resume に表示する件数
+const MAX_RESUME_ENTRIES: usize = 10;
 
 /// Unix timestampを人間が読める形式に変換
 fn format_timestamp(timestamp: u64) -> String {
@@ -26,6 +40,110 @@ fn format_timestamp(timestamp: u64) -> String {
     "unknown".to_string()
 }
 
+/// `/dryrun` の操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DryRunAction {
+    On,
+    Off,
+    Report,
+    /// 引数なし（現在の状態を表示）
+    Status,
+}
+
+/// /config の操作
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigAction {
+    /// 引数なし（重ねた設定ファイルと、すべての値と出どころを表示）
+    Show,
+    /// 1項目の値（`/config get ollama.model`）
+    Get { key: String },
+    /// このセッションの値を変える（`/config set tools.bash_timeout 300`）
+    Set { key: String, value: String },
+    /// 現在の設定をユーザーの設定ファイルに書く
+    Save,
+}
+
+/// コマンド表の1行（`/help` の表示とローカライズした別名の基準）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandSpec {
+    /// 説明の識別子（ロケールファイルのキー）。正式名と同じなら別名を付けられる
+    pub key: &'static str,
+    /// 正式名（`/` なし）
+    pub name: &'static str,
+    /// 英語の別名
+    pub aliases: &'static [&'static str],
+    /// 引数の書式
+    pub usage: &'static str,
+    /// 英語の説明
+    pub summary: &'static str,
+}
+
+impl CommandSpec {
+    const fn new(key: &'static str, name: &'static str, aliases: &'static [&'static str], usage: &'static str, summary: &'static str) -> Self {
+        Self { key, name, aliases, usage, summary }
+    }
+
+    /// 別名を付けられる行か（`/status --env` のような引数違いの行は付けられない）
+    pub fn is_primary(&self) -> bool {
+        self.key == self.name
+    }
+}
+
+/// 組み込みコマンドの一覧（`/help` の表示順）
+pub const COMMAND_SPECS: &[CommandSpec] = &[
+    CommandSpec::new("help", "help", &["h", "?"], "", "Show this help message"),
+    CommandSpec::new("quit", "quit", &["q", "exit"], "", "Exit the REPL"),
+    CommandSpec::new("plan", "plan", &[], "", "Switch to Plan mode (read-only tools)"),
+    CommandSpec::new("execute", "execute", &["exec"], "", "Switch to Execute mode (all tools)"),
+    CommandSpec::new("mode", "mode", &[], "[name]", "List modes or switch (accept-edits skips write confirmations)"),
+    CommandSpec::new("clear", "clear", &["cls"], "", "Clear the screen"),
+    CommandSpec::new("undo", "undo", &[], "", "Remove your last message and everything the agent did in reply"),
+    CommandSpec::new("retry", "retry", &[], "[guidance]", "Remove the last exchange and send the same message again, optionally with added guidance"),
+    CommandSpec::new("context", "context", &["ctx"], "", "Show context usage and the cacheable prompt prefix"),
+    CommandSpec::new("tokens", "tokens", &["tok"], "", "Show estimated tokens by role and the remaining budget"),
+    CommandSpec::new("todos", "todos", &[], "", "Show the agent's current todo list"),
+    CommandSpec::new("stats", "stats", &[], "[reset]", "Show per-tool call counts, failures, durations and output size for this session"),
+    CommandSpec::new("config", "config", &[], "", "Show the layered config files and every setting with where its value came from"),
+    CommandSpec::new("config-get", "config", &[], "get <key>", "Show one setting (dotted key, e.g. ollama.model)"),
+    CommandSpec::new("config-set", "config", &[], "set <key> <value>", "Change a setting for this session, applying it to the model and agent where possible"),
+    CommandSpec::new("config-save", "config", &[], "save", "Write the current settings to the user config file (asks first; comments are not kept)"),
+    CommandSpec::new("status", "status", &[], "", "Show the health of each subsystem (--json for JSON)"),
+    CommandSpec::new("status-env", "status", &[], "--env", "Show versions of tools used in this session"),
+    CommandSpec::new("skills", "skills", &[], "[name | enable|disable <name>]", "List skills with descriptions, show one skill's details, or turn one on or off"),
+    CommandSpec::new("skill-new", "skill-new", &[], "<name>", "Create a SKILL.md template for a new skill (also /skills new)"),
+    CommandSpec::new("reload", "reload", &[], "", "Reload skills, superpowers commands and the bootstrap from disk"),
+    CommandSpec::new("model", "model", &[], "<name>", "Change the model"),
+    CommandSpec::new("set", "set", &[], "[key value]", "Show or change generation options (temperature, seed, ...)"),
+    CommandSpec::new("warm", "warm", &[], "", "Load the current model now so the next reply starts quickly"),
+    CommandSpec::new("lsp", "lsp", &[], "restart", "Restart the language server and reopen the files it had open"),
+    CommandSpec::new("save", "save", &[], "<name>", "Save current conversation"),
+    CommandSpec::new("load", "load", &[], "<name>", "Load a saved conversation"),
+    CommandSpec::new("history", "history", &["hist"], "[all]", "List saved conversations for this project (all: every project)"),
+    CommandSpec::new("export", "export", &[], "<md|html> <path> [--no-system]", "Write the conversation to a Markdown or HTML file under the project root (--no-system: leave out system messages and skill hints)"),
+    CommandSpec::new("delete", "delete", &[], "<name> [--force]", "Delete a saved conversation (asks first unless --force)"),
+    CommandSpec::new("rename", "rename", &[], "<old> <new>", "Rename a saved conversation"),
+    CommandSpec::new("resume", "resume", &[], "[name]", "List recent sessions for this project, or restore one"),
+    CommandSpec::new("roots", "roots", &[], "", "List the workspace roots (* marks the current one)"),
+    CommandSpec::new("root", "root", &[], "[label]", "Show or switch the root that unlabelled paths resolve against"),
+    CommandSpec::new("inputs", "inputs", &[], "[page]", "Show your past inputs by day (repeats collapsed)"),
+    CommandSpec::new("inputs-grep", "inputs", &[], "grep <text>", "Search past inputs"),
+    CommandSpec::new("inputs-clear", "inputs", &[], "clear", "Delete the saved input history"),
+    CommandSpec::new(
+        "dryrun",
+        "dryrun",
+        &["dry-run"],
+        "[on|off|report]",
+        "Simulate changes instead of making them, or list what would have changed",
+    ),
+    CommandSpec::new("verify", "verify", &[], "[on|off]", "Turn checking (and fixing) of code blocks in replies on or off for this session"),
+    CommandSpec::new("init", "init", &[], "[--force]", "Look at the project and write an agent.md for it (asks before writing)"),
+    CommandSpec::new("remember", "remember", &[], "[--global] <text>", "Remember a project convention in every later session (--global: all projects)"),
+    CommandSpec::new("memory", "memory", &[], "", "List remembered entries with their numbers"),
+    CommandSpec::new("forget", "forget", &[], "<n>", "Remove a remembered entry by its /memory number"),
+    CommandSpec::new("use", "use", &[], "<id> [text]", "Attach a past tool result (e.g. t3) in full to the next message"),
+    CommandSpec::new("use-clear", "use", &[], "clear", "Drop the staged tool results"),
+];
+
 /// CLIコマンド
 #[derive(Debug, Clone)]
 pub enum Command {
@@ -37,22 +155,84 @@ pub enum Command {
     Plan,
     /// Executeモードに切り替え
     Execute,
+    /// モード一覧表示・名前でモード切り替え
+    Mode { name: Option<String> },
     /// 画面クリア
     Clear,
+    /// 最後のやり取りを会話から取り除く
+    Undo,
+    /// 最後のやり取りを取り除き、同じメッセージを送り直す（`/retry but shorter` で指示を添える）
+    Retry { guidance: Option<String> },
+    /// コンテキスト（会話・システムプロンプト）の使用量を表示
+    Context,
+    /// 推定トークン数（システムプロンプト・会話・残り）とロール別の内訳を表示
+    Tokens,
+    /// エージェントの ToDo リストを表示
+    Todos,
+    /// ツールの使用統計とセッションの数値を表示（`/stats reset` で数え直す）
+    Stats { reset: bool },
+    /// 設定値とその出どころの表示、1項目の表示・変更、保存
+    Config(ConfigAction),
     /// スキル実行
     Skill { name: String, args: Option<String> },
     /// モデル変更
     Model { name: String },
-    /// 現在の状態を表示
-    Status,
+    /// 生成オプションの変更（`/set temperature 0.2`）。key も value もなければ現在値を表示
+    Set { key: Option<String>, value: Option<String> },
+    /// モデルを読み込んでおく（/model で切り替えたあとなど）
+    Warm,
+    /// 言語サーバーを起動し直す（`/lsp restart`）
+    LspRestart,
+    /// 各サブシステムの状態を表示（--env で使用ツールのバージョン、--json でJSON出力）
+    Status { env: bool, json: bool },
     /// スキル一覧表示
     Skills,
+    /// 新しいスキルの雛形を作る（`/skill-new <name>`・`/skills new <name>`）
+    SkillNew { name: String },
+    /// スキルを有効・無効にする（`/skills enable|disable <name>`）
+    SetSkillEnabled { name: String, enabled: bool },
+    /// スキルの詳細（`/skills <name>`）
+    SkillInfo { name: String },
+    /// スキル・superpowers コマンド・ブートストラップを読み込み直す
+    Reload,
     /// 会話を保存
     Save { name: String },
     /// 会話を読み込み
     Load { name: String },
-    /// 保存された会話一覧を表示
-    History,
+    /// 保存された会話一覧を表示（既定はこのプロジェクトのみ、`/history all` で全件）
+    History { all: bool },
+    /// 会話を Markdown・HTML に書き出す（`/export md notes/session.md`、`--no-system` でシステムメッセージを除く）
+    Export { format: ExportFormat, path: String, include_system: bool },
+    /// 保存した会話を削除（`--force` で確認なし）
+    DeleteHistory { name: String, force: bool },
+    /// 保存した会話の名前を変更
+    RenameHistory { from: String, to: String },
+    /// このプロジェクトの自動保存・保存済みの会話を一覧、または名前を指定して再開
+    Resume { name: Option<String> },
+    /// ワークスペースのルート一覧
+    Roots,
+    /// 現在のルートを表示・切り替え
+    Root { label: Option<String> },
+    /// REPL の入力履歴を日ごとに表示（`/inputs [page]`、`/inputs grep <語>` で検索）
+    Inputs { query: Option<String>, page: usize },
+    /// REPL の入力履歴を消す（確認あり）
+    InputsClear,
+    /// ドライランの切り替えと、模擬した変更の一覧（`/dryrun on|off|report`）
+    DryRun(DryRunAction),
+    /// 応答内のコードの検証を切り替える（`/verify on|off`、引数なしで現在の設定を表示）
+    Verify { enabled: Option<bool> },
+    /// プロジェクトを調べて agent.md を作る（`--force` で既存のファイルを上書き）
+    Init { force: bool },
+    /// 覚えておく事柄を追加（`/remember [--global] <text>`）
+    Remember { text: String, scope: MemoryScope },
+    /// 覚えている事柄の一覧
+    Memory,
+    /// /memory の番号で忘れる
+    Forget { index: usize },
+    /// 過去のツール結果を次のメッセージに固定（`/use <id> [instruction]`）
+    Use { id: String, instruction: Option<String> },
+    /// 固定予定のツール結果を破棄（`/use clear`）
+    UseClear,
     /// 不明なコマンド
     Unknown(String),
     /// 通常のメッセージ（コマンドではない）
@@ -60,6 +240,28 @@ pub enum Command {
 }
 
 impl Command {
+    /// `/skills [name]`・`/skills new <name>`・`/skills enable|disable <name>`
+    fn skills(args: &str) -> Self {
+        let words: Vec<&str> = args.split_whitespace().collect();
+        match words.as_slice() {
+            ["new", rest @ ..] => Command::skill_new(&rest.join(" ")),
+            [action @ ("enable" | "disable"), name] => Command::SetSkillEnabled {
+                name: name.to_string(),
+                enabled: *action == "enable",
+            },
+            ["enable" | "disable", ..] => Command::Unknown("usage: /skills enable|disable <name>".to_string()),
+            [name] => Command::SkillInfo { name: name.trim_start_matches('/').to_string() },
+            _ => Command::Skills,
+        }
+    }
+
+    fn skill_new(args: &str) -> Self {
+        match args.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [name] => Command::SkillNew { name: name.to_string() },
+            _ => Command::Unknown("usage: /skill-new <name>".to_string()),
+        }
+    }
+
     /// 入力テキストをコマンドにパース
     pub fn parse(input: &str) -> Self {
         let input = input.trim();
@@ -77,7 +279,34 @@ impl Command {
             "quit" | "q" | "exit" => Command::Quit,
             "plan" => Command::Plan,
             "execute" | "exec" => Command::Execute,
+            "mode" => Command::Mode { name: args },
             "clear" | "cls" => Command::Clear,
+            "undo" => match args.as_deref() {
+                None | Some("") => Command::Undo,
+                Some(_) => Command::Unknown("usage: /undo".to_string()),
+            },
+            "retry" => Command::Retry { guidance: args.filter(|a| !a.is_empty()) },
+            "context" | "ctx" => Command::Context,
+            "tokens" | "tok" => Command::Tokens,
+            "todos" => Command::Todos,
+            "stats" => match args.as_deref() {
+                None => Command::Stats { reset: false },
+                Some("reset") => Command::Stats { reset: true },
+                Some(_) => Command::Unknown("usage: /stats [reset]".to_string()),
+            },
+            "config" => {
+                let parts: Vec<&str> = args.as_deref().map(|a| a.splitn(3, char::is_whitespace).collect()).unwrap_or_default();
+                match parts.as_slice() {
+                    [] => Command::Config(ConfigAction::Show),
+                    ["get", key] => Command::Config(ConfigAction::Get { key: key.to_string() }),
+                    ["set", key, value] if !value.trim().is_empty() => Command::Config(ConfigAction::Set {
+                        key: key.to_string(),
+                        value: value.trim().to_string(),
+                    }),
+                    ["save"] => Command::Config(ConfigAction::Save),
+                    _ => Command::Unknown("usage: /config [get <key> | set <key> <value> | save]".to_string()),
+                }
+            }
             "model" => {
                 if let Some(name) = args {
                     Command::Model { name }
@@ -85,8 +314,34 @@ impl Command {
                     Command::Unknown("/model requires a model name".to_string())
                 }
             }
-            "status" => Command::Status,
-            "skills" => Command::Skills,
+            "set" => match args.as_deref().map(|a| a.splitn(2, char::is_whitespace).collect::<Vec<_>>()).as_deref() {
+                None => Command::Set { key: None, value: None },
+                Some([key, value]) if RequestOptions::KEYS.contains(key) => Command::Set {
+                    key: Some(key.to_string()),
+                    value: Some(value.trim().to_string()),
+                },
+                Some([key, ..]) if !RequestOptions::KEYS.contains(key) => Command::Unknown(format!(
+                    "unknown option '{}'; valid keys: {}",
+                    key,
+                    RequestOptions::KEYS.join(", ")
+                )),
+                Some(_) => Command::Unknown("usage: /set <key> <value> ('default' resets a key)".to_string()),
+            },
+            "warm" => Command::Warm,
+            "lsp" => match args.as_deref() {
+                Some("restart") => Command::LspRestart,
+                _ => Command::Unknown("usage: /lsp restart".to_string()),
+            },
+            "status" => {
+                let flags: Vec<&str> = args.as_deref().map(|a| a.split_whitespace().collect()).unwrap_or_default();
+                Command::Status {
+                    env: flags.contains(&"--env"),
+                    json: flags.contains(&"--json"),
+                }
+            }
+            "skills" => Command::skills(args.as_deref().unwrap_or("")),
+            "skill-new" => Command::skill_new(args.as_deref().unwrap_or("")),
+            "reload" => Command::Reload,
             "save" => {
                 if let Some(name) = args {
                     Command::Save { name }
@@ -101,7 +356,91 @@ impl Command {
                     Command::Unknown("/load requires a conversation name".to_string())
                 }
             }
-            "history" | "hist" => Command::History,
+            "history" | "hist" => match args.as_deref() {
+                None => Command::History { all: false },
+                Some("all" | "--all") => Command::History { all: true },
+                Some(_) => Command::Unknown("usage: /history [all]".to_string()),
+            },
+            "resume" => Command::Resume { name: args },
+            "delete" => {
+                let words: Vec<&str> = args.as_deref().map(|a| a.split_whitespace().collect()).unwrap_or_default();
+                let force = words.iter().any(|w| matches!(*w, "--force" | "-f"));
+                let names: Vec<&str> = words.into_iter().filter(|w| !matches!(*w, "--force" | "-f")).collect();
+                match names.as_slice() {
+                    [name] => Command::DeleteHistory { name: name.to_string(), force },
+                    _ => Command::Unknown("usage: /delete <name> [--force]".to_string()),
+                }
+            }
+            "rename" => match args.as_deref().map(|a| a.split_whitespace().collect::<Vec<_>>()).as_deref() {
+                Some([from, to]) => Command::RenameHistory { from: from.to_string(), to: to.to_string() },
+                _ => Command::Unknown("usage: /rename <old> <new>".to_string()),
+            },
+            "roots" => Command::Roots,
+            "root" => Command::Root { label: args },
+            "dryrun" | "dry-run" => match args.as_deref() {
+                None => Command::DryRun(DryRunAction::Status),
+                Some("on") => Command::DryRun(DryRunAction::On),
+                Some("off") => Command::DryRun(DryRunAction::Off),
+                Some("report") => Command::DryRun(DryRunAction::Report),
+                Some(_) => Command::Unknown("usage: /dryrun [on|off|report]".to_string()),
+            },
+            "verify" => match args.as_deref() {
+                None => Command::Verify { enabled: None },
+                Some("on") => Command::Verify { enabled: Some(true) },
+                Some("off") => Command::Verify { enabled: Some(false) },
+                Some(_) => Command::Unknown("usage: /verify [on|off]".to_string()),
+            },
+            "init" => match args.as_deref() {
+                None => Command::Init { force: false },
+                Some("--force" | "-f") => Command::Init { force: true },
+                Some(_) => Command::Unknown("usage: /init [--force]".to_string()),
+            },
+            "export" => {
+                let words: Vec<&str> = args.as_deref().unwrap_or("").split_whitespace().collect();
+                let include_system = !words.contains(&"--no-system");
+                let words: Vec<&str> = words.into_iter().filter(|w| *w != "--no-system").collect();
+                match (words.as_slice(), words.first().and_then(|f| ExportFormat::parse(f))) {
+                    ([_, path], Some(format)) => Command::Export { format, path: path.to_string(), include_system },
+                    _ => Command::Unknown("usage: /export <md|html> <path> [--no-system]".to_string()),
+                }
+            }
+            "remember" => {
+                let args = args.as_deref().unwrap_or("");
+                let (scope, text) = match args.strip_prefix("--global") {
+                    Some(rest) if rest.is_empty() || rest.starts_with(char::is_whitespace) => (MemoryScope::Global, rest.trim()),
+                    _ => (MemoryScope::Project, args),
+                };
+                if text.is_empty() {
+                    Command::Unknown("usage: /remember [--global] <text>".to_string())
+                } else {
+                    Command::Remember { text: text.to_string(), scope }
+                }
+            }
+            "memory" => Command::Memory,
+            "forget" => match args.as_deref().map(str::parse::<usize>) {
+                Some(Ok(index)) if index > 0 => Command::Forget { index },
+                _ => Command::Unknown("usage: /forget <n> (numbers from /memory)".to_string()),
+            },
+            "inputs" => match args.as_deref().map(|a| a.splitn(2, char::is_whitespace).collect::<Vec<_>>()) {
+                None => Command::Inputs { query: None, page: 1 },
+                Some(parts) if parts[0] == "clear" => Command::InputsClear,
+                Some(parts) if parts[0] == "grep" => match parts.get(1).map(|q| q.trim()).filter(|q| !q.is_empty()) {
+                    Some(query) => Command::Inputs { query: Some(query.to_string()), page: 1 },
+                    None => Command::Unknown("/inputs grep requires a search term".to_string()),
+                },
+                Some(parts) => match parts[0].parse::<usize>() {
+                    Ok(page) if page > 0 => Command::Inputs { query: None, page },
+                    _ => Command::Unknown("usage: /inputs [page] | /inputs grep <text> | /inputs clear".to_string()),
+                },
+            },
+            "use" => match args.as_deref().map(|a| a.splitn(2, char::is_whitespace).collect::<Vec<_>>()) {
+                Some(parts) if parts[0] == "clear" => Command::UseClear,
+                Some(parts) => Command::Use {
+                    id: parts[0].to_string(),
+                    instruction: parts.get(1).map(|s| s.trim().to_string()).filter(|s| !s.is_empty()),
+                },
+                None => Command::Unknown("/use requires a tool result id (e.g. /use t3) or 'clear'".to_string()),
+            },
             _ => {
                 // 未知のコマンドはスキルとして扱う
                 Command::Skill {
@@ -118,33 +457,83 @@ pub struct CommandHandler {
     mode_manager: ModeManager,
     history_manager: Option<HistoryManager>,
     skill_aliases: HashMap<String, String>,
+    env_prober: Option<Arc<EnvProber>>,
+    /// /status で集約するサブシステム（モードは最初から登録済み）
+    status_registry: StatusRegistry,
+    /// `/help` の表示言語と別名
+    command_table: Arc<CommandTable>,
+    /// /resume で一覧にするプロジェクト
+    project_root: Option<PathBuf>,
+    /// /roots・/root で扱うワークスペース
+    workspace: Option<Arc<Workspace>>,
 }
 
 impl CommandHandler {
     pub fn new(mode_manager: ModeManager) -> Self {
         let history_manager = HistoryManager::new().ok();
-        Self {
-            mode_manager,
-            history_manager,
-            skill_aliases: HashMap::new(),
-        }
+        Self::build(mode_manager, history_manager)
     }
 
     /// HistoryManagerを指定してCommandHandlerを作成
     pub fn with_history_manager(mode_manager: ModeManager, history_manager: HistoryManager) -> Self {
+        Self::build(mode_manager, Some(history_manager))
+    }
+
+    fn build(mode_manager: ModeManager, history_manager: Option<HistoryManager>) -> Self {
+        let status_registry = StatusRegistry::new();
+        status_registry.register(Arc::new(mode_manager.clone()));
         Self {
             mode_manager,
-            history_manager: Some(history_manager),
+            history_manager,
             skill_aliases: HashMap::new(),
+            env_prober: None,
+            status_registry,
+            command_table: Arc::new(CommandTable::default()),
+            project_root: None,
+            workspace: None,
         }
     }
 
+    /// /status に表示するサブシステムの登録先
+    pub fn status_registry(&self) -> &StatusRegistry {
+        &self.status_registry
+    }
+
     /// スキルエイリアスを設定
     pub fn with_skill_aliases(mut self, aliases: HashMap<String, String>) -> Self {
         self.skill_aliases = aliases;
         self
     }
 
+    /// Superpowers コマンドの別名を差し替える（/reload）
+    pub fn set_skill_aliases(&mut self, aliases: HashMap<String, String>) {
+        self.skill_aliases = aliases;
+    }
+
+    /// ローカライズしたコマンド表を設定（`/help` の表示に使う）
+    pub fn with_command_table(mut self, table: Arc<CommandTable>) -> Self {
+        self.command_table = table;
+        self
+    }
+
+    /// 環境フィンガープリントのプローブを設定
+    pub fn with_env_prober(mut self, prober: Arc<EnvProber>) -> Self {
+        self.env_prober = Some(prober);
+        self
+    }
+
+    /// /resume で一覧にするプロジェクトを設定
+    pub fn with_project_root(mut self, project_root: impl Into<PathBuf>) -> Self {
+        self.project_root = Some(project_root.into());
+        self
+    }
+
+    /// /roots・/root で扱うワークスペースを設定
+    pub fn with_workspace(mut self, workspace: Arc<Workspace>) -> Self {
+        self.workspace = Some(workspace);
+        self
+    }
+
     /// HistoryManagerへの参照を取得
     pub fn history_manager(&self) -> Option<&HistoryManager> {
         self.history_manager.as_ref()
@@ -167,27 +556,89 @@ impl CommandHandler {
                 self.mode_manager.to_execute().await;
                 CommandResult::Output("Switched to Execute mode (all tools available)".to_string())
             }
+            Command::Mode { name: None } => {
+                let current = self.mode_manager.current().await;
+                let lines: Vec<String> = self
+                    .mode_manager
+                    .mode_names()
+                    .into_iter()
+                    .map(|name| {
+                        let marker = if name == current.as_str() { "*" } else { " " };
+                        format!("{} {}", marker, name)
+                    })
+                    .collect();
+                CommandResult::Output(format!("Modes:\n{}", lines.join("\n")))
+            }
+            Command::Mode { name: Some(name) } => match self.mode_manager.resolve(name) {
+                Some(mode) => {
+                    self.mode_manager.set(mode.clone()).await;
+                    CommandResult::Output(format!(
+                        "Switched to {} mode (allowed tools: {})",
+                        mode,
+                        self.mode_manager.allowed_tools().await.join(", ")
+                    ))
+                }
+                None => CommandResult::Output(format!(
+                    "Unknown mode: {}. Available modes: {}",
+                    name,
+                    self.mode_manager.mode_names().join(", ")
+                )),
+            },
+            Command::Undo => CommandResult::Undo,
+            Command::Retry { guidance } => CommandResult::Retry { guidance: guidance.clone() },
             Command::Clear => {
                 CommandResult::Clear
             }
-            Command::Status => {
-                let mode = self.mode_manager.current().await;
-                let tools = self.mode_manager.allowed_tools().await;
-                CommandResult::Output(format!(
-                    "Mode: {}\nAllowed tools: {}",
-                    mode,
-                    tools.join(", ")
-                ))
+            Command::Context => CommandResult::ShowContext,
+            Command::Tokens => CommandResult::ShowTokens,
+            Command::Todos => CommandResult::ShowTodos,
+            Command::Stats { reset: false } => CommandResult::ShowStats,
+            Command::Stats { reset: true } => CommandResult::ResetStats,
+            Command::Config(action) => CommandResult::Config(action.clone()),
+            Command::Status { env: true, .. } => {
+                let text = match &self.env_prober {
+                    Some(prober) => format!("Environment:\n{}", prober.snapshot().to_display()),
+                    None => "Environment fingerprint is not available.".to_string(),
+                };
+                CommandResult::Output(text)
             }
+            Command::Status { env: false, json } => CommandResult::Status {
+                statuses: self.status_registry.collect().await,
+                json: *json,
+            },
             Command::Skills => {
-                let names = skill_registry.names();
-                if names.is_empty() {
-                    CommandResult::Output("No skills loaded".to_string())
+                let disabled = skill_registry.disabled_names();
+                let mut text = if skill_registry.is_empty() {
+                    "No skills loaded".to_string()
                 } else {
-                    CommandResult::Output(format!(
-                        "Available skills:\n{}",
-                        names.iter().map(|n| format!("  /{}", n)).collect::<Vec<_>>().join("\n")
-                    ))
+                    format!(
+                        "Available skills (details: /skills <name>):\n{}",
+                        render_skill_table(skill_registry, terminal_width())
+                    )
+                };
+                if !disabled.is_empty() {
+                    text.push_str(&format!(
+                        "\n\n{}",
+                        format!("Disabled (/skills enable <name>):\n{}", disabled.iter().map(|n| format!("  /{}", n)).collect::<Vec<_>>().join("\n")).dim()
+                    ));
+                }
+                CommandResult::Output(text)
+            }
+            Command::SkillInfo { name } => match skill_registry.get(name) {
+                Some(skill) => CommandResult::Output(render_skill_detail(name, skill, skill_registry.origin(skill))),
+                None if skill_registry.disabled_names().contains(name) => {
+                    CommandResult::Output(format!("Skill '{}' is disabled; turn it back on with /skills enable {}", name, name))
+                }
+                None => CommandResult::Output(format!("Unknown skill: {}. Use /skills to list available skills.", name)),
+            },
+            Command::SetSkillEnabled { name, enabled } => {
+                let is_disabled = skill_registry.disabled_names().contains(name);
+                if skill_registry.get(name).is_none() && !is_disabled {
+                    CommandResult::Output(format!("Unknown skill: {}. Use /skills to list available skills.", name))
+                } else if *enabled != is_disabled {
+                    CommandResult::Output(format!("Skill '{}' is already {}", name, if *enabled { "enabled" } else { "disabled" }))
+                } else {
+                    CommandResult::SetSkillEnabled { name: name.clone(), enabled: *enabled }
                 }
             }
             Command::Skill { name, args } => {
@@ -212,6 +663,23 @@ impl CommandHandler {
             Command::Model { name } => {
                 CommandResult::ChangeModel { name: name.clone() }
             }
+            Command::Set { key: Some(key), value: Some(value) } => CommandResult::SetOption {
+                key: key.clone(),
+                value: value.clone(),
+            },
+            Command::Set { .. } => CommandResult::ShowOptions,
+            Command::Warm => CommandResult::WarmUp,
+            Command::LspRestart => CommandResult::RestartLsp,
+            Command::Reload => CommandResult::Reload,
+            Command::SkillNew { name } => {
+                if COMMAND_SPECS.iter().any(|spec| spec.name == name || spec.aliases.contains(&name.as_str())) {
+                    CommandResult::Output(format!("/{} is a built-in command; choose another skill name", name))
+                } else if let Some(skill) = skill_registry.get(name) {
+                    CommandResult::Output(format!("Skill '{}' already exists at {}", name, skill.path.display()))
+                } else {
+                    CommandResult::SkillNew { name: name.clone() }
+                }
+            }
             Command::Unknown(msg) => {
                 CommandResult::Output(format!("Unknown command: {}", msg))
             }
@@ -224,60 +692,172 @@ impl CommandHandler {
             Command::Load { name } => {
                 CommandResult::LoadConversation { name: name.clone() }
             }
-            Command::History => {
-                self.list_history()
+            Command::History { all } => {
+                self.list_history(*all)
             }
+            Command::DeleteHistory { name, force } => CommandResult::DeleteConversation {
+                name: name.clone(),
+                force: *force,
+            },
+            Command::RenameHistory { from, to } => CommandResult::RenameConversation {
+                from: from.clone(),
+                to: to.clone(),
+            },
+            Command::Resume { name: Some(name) } => {
+                CommandResult::LoadConversation { name: name.clone() }
+            }
+            Command::Resume { name: None } => {
+                self.list_resumable()
+            }
+            Command::Roots => self.list_roots(),
+            Command::Root { label: None } => match &self.workspace {
+                Some(workspace) => {
+                    let current = workspace.current();
+                    CommandResult::Output(format!("Current root: {} ({})", current.label, current.path.display()))
+                }
+                None => CommandResult::Output("No workspace roots configured.".to_string()),
+            },
+            Command::Root { label: Some(label) } => match &self.workspace {
+                Some(workspace) if workspace.is_multi() => match workspace.set_current(label) {
+                    Ok(root) => CommandResult::RootChanged(format!(
+                        "Switched to root {} ({}); unlabelled paths now resolve there",
+                        root.label,
+                        root.path.display()
+                    )),
+                    Err(e) => CommandResult::Output(e.to_string()),
+                },
+                _ => CommandResult::Output(
+                    "Only one project root; pass --project more than once or --workspace to add roots.".to_string(),
+                ),
+            },
+            Command::Use { id, instruction } => CommandResult::PinToolResult {
+                id: id.clone(),
+                instruction: instruction.clone(),
+            },
+            Command::UseClear => CommandResult::ClearPinnedResults,
+            Command::Inputs { query, page } => CommandResult::ShowInputs {
+                query: query.clone(),
+                page: *page,
+            },
+            Command::InputsClear => CommandResult::ClearInputs,
+            Command::DryRun(action) => CommandResult::DryRun(*action),
+            Command::Verify { enabled } => CommandResult::Verify { enabled: *enabled },
+            Command::Init { force } => CommandResult::Init { force: *force },
+            Command::Remember { text, scope } => CommandResult::Remember {
+                text: text.clone(),
+                scope: *scope,
+            },
+            Command::Export { format, path, include_system } => CommandResult::Export {
+                format: *format,
+                path: path.clone(),
+                include_system: *include_system,
+            },
+            Command::Memory => CommandResult::ShowMemory,
+            Command::Forget { index } => CommandResult::Forget { index: *index },
         }
     }
 
-    /// 保存された会話履歴の一覧を表示
-    fn list_history(&self) -> CommandResult {
-        match &self.history_manager {
-            Some(manager) => {
-                match manager.list() {
-                    Ok(entries) => {
-                        if entries.is_empty() {
-                            CommandResult::Output("No saved conversations found.".to_string())
-                        } else {
-                            let mut output = String::from("Saved conversations:\n");
-                            for entry in entries {
-                                let datetime = format_timestamp(entry.saved_at);
-                                output.push_str(&format!(
-                                    "  {} ({} messages) - {}\n",
-                                    entry.name,
-                                    entry.message_count,
-                                    datetime
-                                ));
-                            }
-                            output.push_str("\nUse /load <name> to restore a conversation.");
-                            CommandResult::Output(output)
-                        }
-                    }
-                    Err(e) => CommandResult::Output(format!("Failed to list history: {}", e))
+    /// 保存された会話履歴の一覧を表示（all でなければこのプロジェクトのものだけ）
+    fn list_history(&self, all: bool) -> CommandResult {
+        let Some(manager) = &self.history_manager else {
+            return CommandResult::Output("History manager is not available.".to_string());
+        };
+        let entries = match manager.list() {
+            Ok(entries) => entries,
+            Err(e) => return CommandResult::Output(format!("Failed to list history: {}", e)),
+        };
+        let project = self.project_root.as_ref().map(|root| root.display().to_string());
+        let total = entries.len();
+        let entries: Vec<_> = match (&project, all) {
+            (Some(project), false) => entries
+                .into_iter()
+                .filter(|entry| entry.project_path.as_deref() == Some(project.as_str()))
+                .collect(),
+            _ => entries,
+        };
+        let hidden = total - entries.len();
+        let more = if hidden > 0 {
+            format!(
+                "\n({} conversation{} from other or unknown projects; /history all to show them)",
+                hidden,
+                if hidden == 1 { "" } else { "s" }
+            )
+        } else {
+            String::new()
+        };
+        if entries.is_empty() {
+            return CommandResult::Output(format!("No saved conversations found.{}", more));
+        }
+
+        let mut output = String::from(if all { "Saved conversations:\n" } else { "Saved conversations for this project:\n" });
+        for entry in entries {
+            let mut detail = match entry.message_count {
+                Some(count) if entry.encrypted => format!("{} messages, encrypted", count),
+                Some(count) => format!("{} messages", count),
+                None => "encrypted".to_string(),
+            };
+            if let Some(model) = &entry.model {
+                detail.push_str(&format!(", {}", model));
+            }
+            output.push_str(&format!("  {} ({}) - {}", entry.name, detail, format_timestamp(entry.saved_at)));
+            if all {
+                output.push_str(&format!("  [{}]", entry.project_path.as_deref().unwrap_or("unknown project")));
+            }
+            output.push('\n');
+        }
+        output.push_str("\nUse /load <name> to restore a conversation.");
+        output.push_str(&more);
+        CommandResult::Output(output)
+    }
+
+    /// このプロジェクトで保存された会話（自動保存を含む）を新しい順に表示
+    fn list_resumable(&self) -> CommandResult {
+        let (Some(manager), Some(root)) = (&self.history_manager, &self.project_root) else {
+            return CommandResult::Output("History manager is not available.".to_string());
+        };
+        match manager.list_for_project(root) {
+            Ok(entries) if entries.is_empty() => {
+                CommandResult::Output("No saved sessions for this project yet.".to_string())
+            }
+            Ok(entries) => {
+                let mut output = String::from("Recent sessions for this project:\n");
+                for entry in entries.iter().take(MAX_RESUME_ENTRIES) {
+                    let label = if entry.is_autosave() { " (autosave)" } else { "" };
+                    let count = entry.message_count.map(|c| format!("{} messages, ", c)).unwrap_or_default();
+                    output.push_str(&format!(
+                        "  {}{} - {}{}\n",
+                        entry.name,
+                        label,
+                        count,
+                        format_timestamp(entry.saved_at)
+                    ));
                 }
+                output.push_str("\nUse /resume <name> to restore a session.");
+                CommandResult::Output(output)
             }
-            None => CommandResult::Output("History manager is not available.".to_string())
+            Err(e) => CommandResult::Output(format!("Failed to list sessions: {}", e)),
         }
     }
 
+    /// ワークスペースのルート一覧
+    fn list_roots(&self) -> CommandResult {
+        let Some(workspace) = &self.workspace else {
+            return CommandResult::Output("No workspace roots configured.".to_string());
+        };
+        let current = workspace.current().label.as_str();
+        let lines: Vec<String> = workspace
+            .roots()
+            .iter()
+            .map(|root| {
+                let marker = if root.label == current { "*" } else { " " };
+                format!("{} {}  {}", marker, root.label, root.path.display())
+            })
+            .collect();
+        CommandResult::Output(format!("Roots:\n{}", lines.join("\n")))
+    }
+
     fn help_text(&self) -> String {
-        r#"
-Commands:
-  /help, /h, /?   - Show this help message
-  /quit, /q       - Exit the REPL
-  /plan           - Switch to Plan mode (read-only tools)
-  /execute, /exec - Switch to Execute mode (all tools)
-  /clear, /cls    - Clear the screen
-  /status         - Show current mode and available tools
-  /skills         - List available skills
-  /model <name>   - Change the model
-  /save <name>    - Save current conversation
-  /load <name>    - Load a saved conversation
-  /history, /hist - List saved conversations
-  /<skill-name>   - Run a skill
-
-Enter text to chat with the AI.
-"#.to_string()
+        self.command_table.help_text()
     }
 }
 
@@ -290,16 +870,155 @@ pub enum CommandResult {
     Exit,
     /// 画面クリア
     Clear,
+    /// 最後のやり取りを取り除く（会話はエージェントが持つ）
+    Undo,
+    /// 最後のやり取りを取り除いて送り直す
+    Retry { guidance: Option<String> },
     /// LLMにメッセージ送信
     SendToLLM(String),
     /// モデル変更
     ChangeModel { name: String },
+    /// 生成オプションを変更（値の検証と反映はエージェント）
+    SetOption { key: String, value: String },
+    /// 生成オプションの現在値（表示はエージェントの状態から）
+    ShowOptions,
+    /// モデルを読み込んでおく（スピナーの表示は CLI 層）
+    WarmUp,
+    /// 言語サーバーを起動し直す（言語サーバーは CLI 層が持つ）
+    RestartLsp,
+    /// スキルを読み込み直す（レジストリの差し替えは CLI 層）
+    Reload,
+    /// スキルの雛形を作る（置き場所は設定から CLI 層で決める）
+    SkillNew { name: String },
+    /// スキルを有効・無効にする（レジストリの差し替えは CLI 層）
+    SetSkillEnabled { name: String, enabled: bool },
     /// スキル実行
     Skill { name: String, args: Option<String> },
     /// 会話を保存
     SaveConversation { name: String },
     /// 会話を読み込み
     LoadConversation { name: String },
+    /// 保存した会話を削除（force でなければ確認は CLI 層）
+    DeleteConversation { name: String, force: bool },
+    /// 保存した会話の名前を変更
+    RenameConversation { from: String, to: String },
+    /// サブシステムの状態（表示はCLI層）
+    Status { statuses: Vec<SubsystemStatus>, json: bool },
+    /// ツール結果を次のメッセージに固定（解決はエージェント）
+    PinToolResult { id: String, instruction: Option<String> },
+    /// 固定予定のツール結果を破棄
+    ClearPinnedResults,
+    /// コンテキストの使用量（表示はエージェントの状態から）
+    ShowContext,
+    /// 推定トークン数の内訳（表示はエージェントの状態から）
+    ShowTokens,
+    /// ToDo リスト（リストは CLI 層が持つ）
+    ShowTodos,
+    /// ツールの使用統計（統計はエージェントが持つ）
+    ShowStats,
+    /// ツールの使用統計を捨てる
+    ResetStats,
+    /// 設定の表示・変更・保存（設定は CLI 層が持つ）
+    Config(ConfigAction),
+    /// 入力履歴（履歴は REPL が持つため表示は CLI 層）
+    ShowInputs { query: Option<String>, page: usize },
+    /// 入力履歴を消す（確認は CLI 層）
+    ClearInputs,
+    /// ドライランの操作（状態はエージェントと共有）
+    DryRun(DryRunAction),
+    /// 応答内のコードの検証の切り替え（設定は CLI 層が持つ）
+    Verify { enabled: Option<bool> },
+    /// agent.md を作る（LLM への依頼と確認は CLI 層）
+    Init { force: bool },
+    /// 会話を書き出す（会話はエージェント、上書きの確認は CLI 層）
+    Export { format: ExportFormat, path: String, include_system: bool },
+    /// 事柄を覚える（メモリはエージェントのコンテキストが持つ）
+    Remember { text: String, scope: MemoryScope },
+    /// 覚えている事柄の一覧（表示はエージェントの状態から）
+    ShowMemory,
+    /// 覚えている事柄を忘れる
+    Forget { index: usize },
+    /// 現在のルートを切り替えた（システムプロンプトの作り直しは CLI 層）
+    RootChanged(String),
+}
+
+/// 説明の最初の1文
+fn first_sentence(description: &str) -> &str {
+    let line = description.lines().next().unwrap_or("").trim();
+    match line.find(". ").map(|i| i + 1).or_else(|| line.find('。').map(|i| i + '。'.len_utf8())) {
+        Some(end) => &line[..end],
+        None => line,
+    }
+}
+
+/// /skills の一覧（名前・説明・読み込み元・トリガー）
+///
+/// superpowers 版は同名のスキルに上書きされているときだけ別の行にする
+fn render_skill_table(registry: &SkillRegistry, width: usize) -> String {
+    let rows: Vec<(String, &Skill)> = registry
+        .names()
+        .into_iter()
+        .filter_map(|name| registry.get(&name).map(|skill| (name, skill)))
+        .filter(|(name, skill)| {
+            name.strip_prefix("superpowers:")
+                .and_then(|base| registry.get(base))
+                .is_none_or(|plain| plain.path != skill.path)
+        })
+        .collect();
+    let name_width = rows.iter().map(|(name, _)| name.chars().count() + 1).max().unwrap_or(0);
+    // 名前・読み込み元・トリガーの列と区切りの空白を除いた残り
+    let description_width = width.saturating_sub(2 + name_width + 2 + 11 + 2 + 7 + 2).max(20);
+    rows.iter()
+        .map(|(name, skill)| {
+            let source = registry.origin(skill).map(SkillOrigin::label).unwrap_or("unknown");
+            let triggers = match (skill.triggers.is_empty(), skill.metadata.auto) {
+                (true, _) => "-",
+                (false, true) => "auto",
+                (false, false) => "trigger",
+            };
+            format!(
+                "  {:<name_width$}  {:<11}  {:<7}  {}",
+                format!("/{}", name),
+                source,
+                triggers,
+                truncate_with_ellipsis(first_sentence(&skill.metadata.description), description_width)
+            )
+            .trim_end()
+            .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `/skills <name>` の詳細
+fn render_skill_detail(name: &str, skill: &Skill, origin: Option<&SkillOrigin>) -> String {
+    let metadata = &skill.metadata;
+    let or_none = |items: Vec<String>| if items.is_empty() { "(none)".to_string() } else { items.join(", ") };
+    let mut lines = vec![
+        format!("/{}", name),
+        format!("  description:   {}", if metadata.description.is_empty() { "(none)" } else { metadata.description.trim() }),
+        format!("  source:        {}", origin.map(ToString::to_string).unwrap_or_else(|| "unknown".to_string())),
+        format!("  file:          {}", skill.path.display()),
+        format!("  triggers:      {}", or_none(metadata.triggers.clone())),
+        format!("  auto:          {}", metadata.auto),
+        format!("  priority:      {}", metadata.priority),
+        format!("  args_required: {}", metadata.args_required),
+    ];
+    if let Some(parent) = &metadata.parent {
+        lines.push(format!("  parent:        {}", parent));
+    }
+    if let Some(tools) = &metadata.allowed_tools {
+        lines.push(format!("  allowed-tools: {}", or_none(tools.clone())));
+    }
+    if !metadata.extra_paths.is_empty() {
+        let paths = metadata
+            .extra_paths
+            .iter()
+            .map(|extra| if extra.write { format!("{} (write)", extra.path) } else { extra.path.clone() })
+            .collect();
+        lines.push(format!("  extra_paths:   {}", or_none(paths)));
+    }
+    lines.join("\n")
 }
 
 #[cfg(test)]
@@ -312,6 +1031,61 @@ mod tests {
         assert!(matches!(Command::parse("/quit"), Command::Quit));
         assert!(matches!(Command::parse("/plan"), Command::Plan));
         assert!(matches!(Command::parse("/execute"), Command::Execute));
+        assert!(matches!(Command::parse("/use clear"), Command::UseClear));
+        assert!(matches!(Command::parse("/context"), Command::Context));
+        assert!(matches!(Command::parse("/tokens"), Command::Tokens));
+        assert!(matches!(Command::parse("/tok"), Command::Tokens));
+        assert!(matches!(Command::parse("/todos"), Command::Todos));
+        assert!(matches!(Command::parse("/stats"), Command::Stats { reset: false }));
+        assert!(matches!(Command::parse("/stats reset"), Command::Stats { reset: true }));
+        assert!(matches!(Command::parse("/stats now"), Command::Unknown(_)));
+        assert!(matches!(Command::parse("/config"), Command::Config(ConfigAction::Show)));
+        assert!(matches!(Command::parse("/config get ollama.model"), Command::Config(ConfigAction::Get { key }) if key == "ollama.model"));
+        assert!(matches!(
+            Command::parse("/config set ui.pager less -R"),
+            Command::Config(ConfigAction::Set { key, value }) if key == "ui.pager" && value == "less -R"
+        ));
+        assert!(matches!(Command::parse("/config save"), Command::Config(ConfigAction::Save)));
+        assert!(matches!(Command::parse("/config set ollama.model"), Command::Unknown(_)));
+        assert!(matches!(Command::parse("/config reset"), Command::Unknown(_)));
+        assert!(matches!(Command::parse("/use"), Command::Unknown(_)));
+        if let Command::Use { id, instruction } = Command::parse("/use t3 fix the failing call sites") {
+            assert_eq!(id, "t3");
+            assert_eq!(instruction.as_deref(), Some("fix the failing call sites"));
+        } else {
+            panic!("Expected Use command");
+        }
+
+        assert!(matches!(Command::parse("/set"), Command::Set { key: None, value: None }));
+        assert!(matches!(Command::parse("/warm"), Command::Warm));
+        assert!(matches!(Command::parse("/lsp restart"), Command::LspRestart));
+        assert!(matches!(Command::parse("/lsp"), Command::Unknown(_)));
+        assert!(matches!(Command::parse("/reload"), Command::Reload));
+        for input in ["/skill-new my-skill", "/skills new my-skill", "/skills  new   my-skill "] {
+            assert!(matches!(Command::parse(input), Command::SkillNew { name } if name == "my-skill"), "{}", input);
+        }
+        assert!(matches!(Command::parse("/skills"), Command::Skills));
+        assert!(matches!(Command::parse("/skills newest"), Command::SkillInfo { name } if name == "newest"));
+        assert!(matches!(Command::parse("/skills /review"), Command::SkillInfo { name } if name == "review"));
+        assert!(matches!(Command::parse("/skill-new"), Command::Unknown(_)));
+        assert!(matches!(Command::parse("/skills new a b"), Command::Unknown(_)));
+        assert!(matches!(
+            Command::parse("/skills disable superpowers:brainstorming"),
+            Command::SetSkillEnabled { name, enabled: false } if name == "superpowers:brainstorming"
+        ));
+        assert!(matches!(Command::parse("/skills enable lint"), Command::SetSkillEnabled { name, enabled: true } if name == "lint"));
+        assert!(matches!(Command::parse("/skills enable"), Command::Unknown(_)));
+        if let Command::Set { key, value } = Command::parse("/set temperature  0.2") {
+            assert_eq!(key.as_deref(), Some("temperature"));
+            assert_eq!(value.as_deref(), Some("0.2"));
+        } else {
+            panic!("Expected Set command");
+        }
+        match Command::parse("/set temp 0.2") {
+            Command::Unknown(msg) => assert!(msg.contains("valid keys: temperature, top_p"), "{}", msg),
+            other => panic!("Expected Unknown, got {:?}", other),
+        }
+        assert!(matches!(Command::parse("/set seed"), Command::Unknown(_)));
 
         if let Command::Model { name } = Command::parse("/model gpt-4") {
             assert_eq!(name, "gpt-4");
@@ -333,6 +1107,58 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_inputs() {
+        assert!(matches!(Command::parse("/inputs"), Command::Inputs { query: None, page: 1 }));
+        assert!(matches!(Command::parse("/inputs 3"), Command::Inputs { query: None, page: 3 }));
+        assert!(matches!(Command::parse("/inputs clear"), Command::InputsClear));
+        assert!(matches!(Command::parse("/inputs 0"), Command::Unknown(_)));
+        assert!(matches!(Command::parse("/inputs grep"), Command::Unknown(_)));
+        if let Command::Inputs { query, page } = Command::parse("/inputs grep cargo test") {
+            assert_eq!(query.as_deref(), Some("cargo test"));
+            assert_eq!(page, 1);
+        } else {
+            panic!("Expected Inputs command");
+        }
+    }
+
+    #[test]
+    fn test_parse_dryrun() {
+        assert!(matches!(Command::parse("/dryrun"), Command::DryRun(DryRunAction::Status)));
+        assert!(matches!(Command::parse("/dryrun on"), Command::DryRun(DryRunAction::On)));
+        assert!(matches!(Command::parse("/dryrun off"), Command::DryRun(DryRunAction::Off)));
+        assert!(matches!(Command::parse("/dryrun report"), Command::DryRun(DryRunAction::Report)));
+        assert!(matches!(Command::parse("/dryrun maybe"), Command::Unknown(_)));
+        assert!(matches!(Command::parse("/verify"), Command::Verify { enabled: None }));
+        assert!(matches!(Command::parse("/verify off"), Command::Verify { enabled: Some(false) }));
+        assert!(matches!(Command::parse("/verify on"), Command::Verify { enabled: Some(true) }));
+    }
+
+    #[test]
+    fn test_parse_memory_commands() {
+        assert!(matches!(
+            Command::parse("/remember we use thiserror"),
+            Command::Remember { ref text, scope: MemoryScope::Project } if text == "we use thiserror"
+        ));
+        assert!(matches!(
+            Command::parse("/remember --global  answer in Japanese"),
+            Command::Remember { ref text, scope: MemoryScope::Global } if text == "answer in Japanese"
+        ));
+        assert!(matches!(
+            Command::parse("/remember --globally"),
+            Command::Remember { scope: MemoryScope::Project, .. }
+        ));
+        assert!(matches!(Command::parse("/remember --global"), Command::Unknown(_)));
+        assert!(matches!(Command::parse("/memory"), Command::Memory));
+        assert!(matches!(Command::parse("/init"), Command::Init { force: false }));
+        assert!(matches!(Command::parse("/init --force"), Command::Init { force: true }));
+        assert!(matches!(Command::parse("/init now"), Command::Unknown(_)));
+        assert!(matches!(Command::parse("/forget 2"), Command::Forget { index: 2 }));
+        assert!(matches!(Command::parse("/forget 0"), Command::Unknown(_)));
+        assert!(matches!(Command::parse("/forget"), Command::Unknown(_)));
+        assert!(matches!(Command::parse("/verify later"), Command::Unknown(_)));
+    }
+
     #[test]
     fn test_parse_help_aliases() {
         assert!(matches!(Command::parse("/h"), Command::Help));
@@ -356,6 +1182,31 @@ mod tests {
         assert!(matches!(Command::parse("/clear"), Command::Clear));
     }
 
+    #[test]
+    fn test_parse_export() {
+        assert!(matches!(
+            Command::parse("/export md notes/session.md"),
+            Command::Export { format: ExportFormat::Markdown, ref path, include_system: true } if path == "notes/session.md"
+        ));
+        assert!(matches!(
+            Command::parse("/export html out.html --no-system"),
+            Command::Export { format: ExportFormat::Html, include_system: false, .. }
+        ));
+        assert!(matches!(Command::parse("/export pdf out.pdf"), Command::Unknown(_)));
+        assert!(matches!(Command::parse("/export md"), Command::Unknown(_)));
+    }
+
+    #[test]
+    fn test_parse_undo_and_retry() {
+        assert!(matches!(Command::parse("/undo"), Command::Undo));
+        assert!(matches!(Command::parse("/undo 2"), Command::Unknown(_)));
+        assert!(matches!(Command::parse("/retry"), Command::Retry { guidance: None }));
+        assert!(matches!(
+            Command::parse("/retry but shorter"),
+            Command::Retry { guidance: Some(ref g) } if g == "but shorter"
+        ));
+    }
+
     #[test]
     fn test_model_without_name() {
         if let Command::Unknown(msg) = Command::parse("/model") {
@@ -448,9 +1299,153 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_status_env() {
+        assert!(matches!(Command::parse("/status"), Command::Status { env: false, json: false }));
+        assert!(matches!(Command::parse("/status --env"), Command::Status { env: true, json: false }));
+        assert!(matches!(Command::parse("/status --json"), Command::Status { env: false, json: true }));
+    }
+
     #[test]
     fn test_parse_history_command() {
-        assert!(matches!(Command::parse("/history"), Command::History));
-        assert!(matches!(Command::parse("/hist"), Command::History));
+        assert!(matches!(Command::parse("/history"), Command::History { all: false }));
+        assert!(matches!(Command::parse("/hist"), Command::History { all: false }));
+        assert!(matches!(Command::parse("/history all"), Command::History { all: true }));
+        assert!(matches!(Command::parse("/history --all"), Command::History { all: true }));
+        assert!(matches!(Command::parse("/history yesterday"), Command::Unknown(_)));
+        assert!(matches!(Command::parse("/resume"), Command::Resume { name: None }));
+        assert!(matches!(Command::parse("/resume my-session"), Command::Resume { name: Some(n) } if n == "my-session"));
+    }
+
+    #[test]
+    fn test_parse_delete_and_rename() {
+        assert!(matches!(Command::parse("/delete old"), Command::DeleteHistory { name, force: false } if name == "old"));
+        assert!(matches!(Command::parse("/delete old --force"), Command::DeleteHistory { name, force: true } if name == "old"));
+        assert!(matches!(Command::parse("/delete -f old"), Command::DeleteHistory { name, force: true } if name == "old"));
+        assert!(matches!(Command::parse("/delete"), Command::Unknown(_)));
+        assert!(matches!(Command::parse("/delete a b"), Command::Unknown(_)));
+        assert!(matches!(Command::parse("/rename draft final"), Command::RenameHistory { from, to } if from == "draft" && to == "final"));
+        assert!(matches!(Command::parse("/rename draft"), Command::Unknown(_)));
+    }
+
+    #[tokio::test]
+    async fn test_mode_command_switches_to_configured_mode() {
+        use crate::agent::Mode;
+        use std::collections::BTreeMap;
+
+        let modes = BTreeMap::from([("review".to_string(), vec!["read".to_string()])]);
+        let effects = [("read".to_string(), crate::tools::ToolEffects::ReadOnly)].into_iter().collect();
+        let manager = ModeManager::new(Mode::Execute)
+            .with_allowlists(&modes)
+            .with_tool_effects(effects);
+        let handler = CommandHandler::new(manager.clone());
+        let skills = SkillRegistry::new();
+
+        let result = handler.handle(&Command::parse("/mode review"), &skills).await;
+        assert!(matches!(result, CommandResult::Output(msg) if msg == "Switched to review mode (allowed tools: read)"));
+        assert_eq!(manager.current().await, Mode::Custom("review".to_string()));
+
+        let result = handler.handle(&Command::parse("/mode nope"), &skills).await;
+        assert!(matches!(result, CommandResult::Output(msg) if msg.starts_with("Unknown mode: nope")));
+
+        let result = handler.handle(&Command::parse("/mode"), &skills).await;
+        assert!(matches!(result, CommandResult::Output(msg) if msg == "Modes:\n  plan\n  execute\n  accept-edits\n* review"));
+    }
+
+    #[tokio::test]
+    async fn test_skills_table_and_detail_view() {
+        use crate::agent::Mode;
+
+        let dir = tempfile::tempdir().unwrap();
+        let review = dir.path().join("review");
+        std::fs::create_dir(&review).unwrap();
+        std::fs::write(
+            review.join("SKILL.md"),
+            "---\nname: review\ndescription: Review a diff for bugs. Reports findings by severity.\ntriggers: [review, /code review/]\nauto: true\nallowed-tools: Read, Grep\n---\nReview",
+        )
+        .unwrap();
+        let mut skills = SkillRegistry::new();
+        skills.add_search_path(dir.path().to_path_buf());
+        skills.load_all().await.unwrap();
+
+        let table = render_skill_table(&skills, 80);
+        let row = table.lines().find(|line| line.trim_start().starts_with("/review ")).unwrap();
+        assert!(row.contains("user") && row.contains("auto") && row.ends_with("Review a diff for bugs."), "{}", row);
+        // 上書きされていない superpowers 版は1行にまとめる
+        assert!(table.contains("/brainstorming"));
+        assert!(!table.contains("/superpowers:brainstorming"));
+        assert!(table.lines().all(|line| line.chars().count() <= 80), "{}", table);
+
+        let handler = CommandHandler::new(ModeManager::new(Mode::Execute));
+        let result = handler.handle(&Command::parse("/skills review"), &skills).await;
+        let CommandResult::Output(detail) = result else { panic!("{:?}", result) };
+        assert!(detail.starts_with("/review\n"));
+        assert!(detail.contains("description:   Review a diff for bugs. Reports findings by severity."));
+        assert!(detail.contains(&format!("source:        user ({})", dir.path().display())));
+        assert!(detail.contains(&format!("file:          {}", review.join("SKILL.md").display())));
+        assert!(detail.contains("triggers:      review, /code review/"));
+        assert!(detail.contains("auto:          true"));
+        assert!(detail.contains("allowed-tools: read, grep"));
+
+        let result = handler.handle(&Command::parse("/skills superpowers:brainstorming"), &skills).await;
+        assert!(matches!(result, CommandResult::Output(msg) if msg.contains("source:        embedded") && msg.contains("file:          embedded://")));
+        let result = handler.handle(&Command::parse("/skills nope"), &skills).await;
+        assert!(matches!(result, CommandResult::Output(msg) if msg.starts_with("Unknown skill: nope")));
+    }
+
+    #[tokio::test]
+    async fn test_root_commands_switch_current_root() {
+        use crate::agent::Mode;
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("api")).unwrap();
+        std::fs::create_dir_all(dir.path().join("web")).unwrap();
+        let workspace = Arc::new(Workspace::from_paths(&[dir.path().join("api"), dir.path().join("web")]).unwrap());
+        let handler = CommandHandler::new(ModeManager::new(Mode::Execute)).with_workspace(Arc::clone(&workspace));
+        let skills = SkillRegistry::new();
+
+        let result = handler.handle(&Command::parse("/roots"), &skills).await;
+        assert!(matches!(&result, CommandResult::Output(msg) if msg.contains("* api") && msg.contains("  web")), "{:?}", result);
+
+        let result = handler.handle(&Command::parse("/root web"), &skills).await;
+        assert!(matches!(result, CommandResult::RootChanged(_)));
+        assert_eq!(workspace.current().label, "web");
+
+        let result = handler.handle(&Command::parse("/root docs"), &skills).await;
+        assert!(matches!(result, CommandResult::Output(msg) if msg.contains("unknown root 'docs'")));
+        assert_eq!(workspace.current().label, "web");
+    }
+
+    #[tokio::test]
+    async fn test_history_lists_this_project_by_default() {
+        use crate::agent::history::ConversationMetadata;
+        use crate::agent::{Conversation, Mode};
+
+        let dir = tempfile::tempdir().unwrap();
+        let manager = HistoryManager::with_directory(dir.path().to_path_buf()).unwrap();
+        let metadata = |project: &str| ConversationMetadata {
+            model: Some("qwen2.5-coder".to_string()),
+            project_path: Some(project.to_string()),
+            ..Default::default()
+        };
+        manager.save_with_metadata("mine", &Conversation::new(), metadata("/work/here")).unwrap();
+        manager.save_with_metadata("theirs", &Conversation::new(), metadata("/work/there")).unwrap();
+        manager.save("legacy", &Conversation::new()).unwrap();
+        let handler = CommandHandler::with_history_manager(ModeManager::new(Mode::Execute), manager)
+            .with_project_root("/work/here");
+        let skills = SkillRegistry::new();
+
+        let CommandResult::Output(text) = handler.handle(&Command::parse("/history"), &skills).await else {
+            panic!("expected output");
+        };
+        assert!(text.contains("  mine (0 messages, qwen2.5-coder)"), "{}", text);
+        assert!(!text.contains("theirs"));
+        assert!(text.contains("(2 conversations from other or unknown projects"), "{}", text);
+
+        let CommandResult::Output(text) = handler.handle(&Command::parse("/history all"), &skills).await else {
+            panic!("expected output");
+        };
+        assert!(text.contains("[/work/there]"), "{}", text);
+        assert!(text.contains("legacy (0 messages) - just now  [unknown project]"), "{}", text);
     }
 }
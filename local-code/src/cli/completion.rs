@@ -7,6 +7,8 @@
 use std::path::{Path, PathBuf};
 use std::fs;
 
+use crate::config::settable_keys;
+
 /// 利用可能なスラッシュコマンド一覧
 const COMMANDS: &[&str] = &[
     "/help",
@@ -20,21 +22,51 @@ const COMMANDS: &[&str] = &[
     "/exec",
     "/clear",
     "/cls",
+    "/undo",
+    "/retry",
     "/status",
+    "/stats",
+    "/config",
+    "/todos",
     "/skills",
+    "/skill-new",
+    "/reload",
     "/model",
+    "/set",
+    "/warm",
+    "/lsp",
     "/save",
     "/load",
     "/history",
     "/hist",
+    "/export",
+    "/delete",
+    "/rename",
+    "/resume",
+    "/roots",
+    "/root",
+    "/inputs",
+    "/dryrun",
+    "/verify",
+    "/init",
+    "/remember",
+    "/memory",
+    "/forget",
 ];
 
+/// 保存した会話の名前を引数に取るコマンド（最初の引数を補完する）
+const CONVERSATION_COMMANDS: &[&str] = &["/load", "/delete", "/rename", "/resume"];
+
 /// オートコンプリーター
 pub struct Completer {
     /// スキル名のリスト（動的に更新可能）
     skill_names: Vec<String>,
     /// 追加コマンド（動的に更新可能）
     extra_commands: Vec<String>,
+    /// ローカライズしたコマンドの別名（`/` の後ろに何か入力したときだけ候補にする）
+    localized_commands: Vec<String>,
+    /// 保存した会話の名前（`/load` などの引数の補完用）
+    conversation_names: Vec<String>,
     /// 現在の作業ディレクトリ
     working_dir: PathBuf,
 }
@@ -45,6 +77,8 @@ impl Completer {
         Self {
             skill_names: Vec::new(),
             extra_commands: Vec::new(),
+            localized_commands: Vec::new(),
+            conversation_names: Vec::new(),
             working_dir: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
         }
     }
@@ -59,6 +93,16 @@ impl Completer {
         self.extra_commands = commands;
     }
 
+    /// ローカライズしたコマンドの別名を設定
+    pub fn set_localized_commands(&mut self, commands: Vec<String>) {
+        self.localized_commands = commands;
+    }
+
+    /// 保存した会話の名前を設定
+    pub fn set_conversation_names(&mut self, names: Vec<String>) {
+        self.conversation_names = names;
+    }
+
     /// 作業ディレクトリを設定
     pub fn set_working_dir(&mut self, path: PathBuf) {
         self.working_dir = path;
@@ -99,8 +143,60 @@ impl Completer {
         Vec::new()
     }
 
+    /// カーソル直前の `@path` を補完する（入力の途中でも使える）
+    ///
+    /// パスはプロジェクトルート（作業ディレクトリ）からの相対。
+    /// 戻り値は置き換える範囲の開始位置（`@` の直後のバイト位置）と候補
+    pub fn complete_mention(&self, before_cursor: &str) -> Option<(usize, Vec<String>)> {
+        let start = before_cursor
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map(|(i, c)| i + c.len_utf8())
+            .unwrap_or(0);
+        let partial = before_cursor[start..].strip_prefix('@')?;
+        Some((start + 1, self.complete_relative_path(partial)))
+    }
+
+    /// 作業ディレクトリからの相対パスの補完（ディレクトリは末尾に /）
+    fn complete_relative_path(&self, partial: &str) -> Vec<String> {
+        let (dir, prefix) = match partial.rfind('/') {
+            Some(i) => (&partial[..=i], &partial[i + 1..]),
+            None => ("", partial),
+        };
+        let entries = match fs::read_dir(self.working_dir.join(dir)) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+
+        let prefix_lower = prefix.to_lowercase();
+        let mut candidates: Vec<String> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                // 隠しファイルは.で始まる入力の場合のみ表示
+                if name.starts_with('.') && !prefix.starts_with('.') {
+                    return None;
+                }
+                if !name.to_lowercase().starts_with(&prefix_lower) {
+                    return None;
+                }
+                let slash = if entry.path().is_dir() { "/" } else { "" };
+                Some(format!("{}{}{}", dir, name, slash))
+            })
+            .collect();
+        candidates.sort();
+        candidates
+    }
+
     /// コマンド補完
     fn complete_command(&self, input: &str) -> Vec<String> {
+        if let Some((command, partial)) = input.split_once(' ') {
+            if command == "/config" {
+                return complete_config_key(partial);
+            }
+            return self.complete_conversation_name(command, partial);
+        }
         let input_lower = input.to_lowercase();
         let mut candidates = Vec::new();
 
@@ -131,12 +227,37 @@ impl Completer {
             }
         }
 
+        // ローカライズした別名（"/" だけでは出さない）
+        if input.len() > 1 {
+            for alias in &self.localized_commands {
+                if alias.to_lowercase().starts_with(&input_lower) {
+                    candidates.push(alias.clone());
+                }
+            }
+        }
+
         // 重複を除去してソート
         candidates.sort();
         candidates.dedup();
         candidates
     }
 
+    /// `/load <名前>` などの会話名の補完（最初の引数のみ）
+    fn complete_conversation_name(&self, command: &str, partial: &str) -> Vec<String> {
+        if !CONVERSATION_COMMANDS.contains(&command) || partial.contains(char::is_whitespace) {
+            return Vec::new();
+        }
+        let mut candidates: Vec<String> = self
+            .conversation_names
+            .iter()
+            .filter(|name| name.starts_with(partial))
+            .map(|name| format!("{} {}", command, name))
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+        candidates
+    }
+
     /// ファイルパス補完
     fn complete_path(&self, input: &str) -> Vec<String> {
         let expanded = self.expand_tilde(input);
@@ -296,10 +417,46 @@ impl Completer {
     }
 }
 
+/// `/config` の操作と、`/config get|set` の後ろの設定キーの補完
+fn complete_config_key(partial: &str) -> Vec<String> {
+    let Some((action, prefix)) = partial.split_once(' ') else {
+        return ["get", "set", "save"]
+            .iter()
+            .filter(|action| action.starts_with(partial))
+            .map(|action| format!("/config {}", action))
+            .collect();
+    };
+    if !matches!(action, "get" | "set") || prefix.contains(char::is_whitespace) {
+        return Vec::new();
+    }
+    settable_keys()
+        .into_iter()
+        .filter(|key| key.starts_with(prefix))
+        .map(|key| format!("/config {} {}", action, key))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_mention_completion_mid_sentence() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src/agent")).unwrap();
+        std::fs::write(dir.path().join("src/agent/core.rs"), "").unwrap();
+        std::fs::write(dir.path().join("src/agent/context.rs"), "").unwrap();
+        let mut completer = Completer::new();
+        completer.set_working_dir(dir.path().to_path_buf());
+
+        let input = "look at @sr";
+        assert_eq!(completer.complete_mention(input), Some((9, vec!["src/".to_string()])));
+        let (start, candidates) = completer.complete_mention("look at @src/agent/co").unwrap();
+        assert_eq!(start, 9);
+        assert_eq!(candidates, vec!["src/agent/context.rs", "src/agent/core.rs"]);
+        assert_eq!(completer.complete_mention("look at src"), None);
+    }
+
     #[test]
     fn test_command_completion() {
         let completer = Completer::new();
@@ -327,6 +484,16 @@ mod tests {
         assert!(candidates.contains(&"/review".to_string()));
     }
 
+    #[test]
+    fn test_localized_aliases_only_when_typed() {
+        let mut completer = Completer::new();
+        completer.set_localized_commands(vec!["/保存".to_string(), "/履歴".to_string()]);
+
+        assert!(completer.complete("/").iter().all(|c| c.is_ascii()));
+        assert!(completer.complete("/s").iter().all(|c| c.is_ascii()));
+        assert_eq!(completer.complete("/保"), vec!["/保存"]);
+    }
+
     #[test]
     fn test_empty_input() {
         let completer = Completer::new();
@@ -411,4 +578,31 @@ mod tests {
 
         assert_eq!(candidates_lower, candidates_upper);
     }
+
+    #[test]
+    fn test_conversation_names_after_history_commands() {
+        let mut completer = Completer::new();
+        completer.set_conversation_names(vec!["draft".to_string(), "deploy-notes".to_string(), "work".to_string()]);
+
+        assert_eq!(completer.complete("/load d"), vec!["/load deploy-notes", "/load draft"]);
+        assert_eq!(completer.complete("/delete w"), vec!["/delete work"]);
+        assert_eq!(completer.complete("/rename dr"), vec!["/rename draft"]);
+        // 2つ目の引数（新しい名前）は補完しない
+        assert!(completer.complete("/rename draft d").is_empty());
+        assert!(completer.complete("/model d").is_empty());
+    }
+
+    #[test]
+    fn test_config_keys_after_config_set() {
+        let completer = Completer::new();
+        assert_eq!(completer.complete("/config s"), vec!["/config set", "/config save"]);
+        assert_eq!(
+            completer.complete("/config set ollama.read"),
+            vec!["/config set ollama.read_timeout"]
+        );
+        assert!(completer.complete("/config get tools.").contains(&"/config get tools.bash_timeout".to_string()));
+        // 値は補完しない
+        assert!(completer.complete("/config set tools.bash_timeout 3").is_empty());
+        assert!(completer.complete("/config save x").is_empty());
+    }
 }
@@ -1,16 +1,20 @@
 //! ツール実行確認ダイアログモジュール
 //!
-//! 危険なツール（bash, write, edit, git_commit）の実行前に
+//! 副作用のあるツール（`ToolEffects` が ReadOnly 以外）の実行前に
 //! ユーザー確認を求めるダイアログ機能を提供
 
 use std::io::{self, Write};
+
+use crate::agent::{ConfirmationPolicy, Mode};
+use crate::tools::ToolEffects;
 use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
     execute,
-    style::{Color, Print, ResetColor, SetForegroundColor},
+    style::{Color, Print},
+    terminal,
 };
 
-/// 確認が必要な危険なツールのリスト
-const DANGEROUS_TOOLS: &[&str] = &["bash", "write", "edit", "git_commit"];
+use super::color::{ResetColor, SetForegroundColor};
 
 /// 確認ダイアログの結果
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -19,6 +23,17 @@ pub enum ConfirmResult {
     Approved,
     /// ユーザーが拒否
     Denied,
+    /// 許可し、セッション中はこのツールを確認しない（`a`）
+    AlwaysAllowTool,
+    /// 許可し、セッション中は同じ内容の呼び出しを確認しない（`A`）
+    AlwaysAllowExact,
+}
+
+impl ConfirmResult {
+    /// 実行を許可したか
+    pub fn is_approved(self) -> bool {
+        self != ConfirmResult::Denied
+    }
 }
 
 /// 確認ダイアログ構造体
@@ -30,6 +45,10 @@ pub struct ConfirmDialog {
     details: String,
     /// 自動承認モード（テスト用）
     auto_approve: bool,
+    /// 「常に許可」の選択肢（a / A）を出す
+    always_choices: bool,
+    /// a と A の説明
+    always_labels: (String, String),
 }
 
 impl ConfirmDialog {
@@ -39,9 +58,23 @@ impl ConfirmDialog {
             action: action.into(),
             details: details.into(),
             auto_approve: false,
+            always_choices: false,
+            always_labels: ("always this tool".to_string(), "always this exact call".to_string()),
         }
     }
 
+    /// 「常に許可」の選択肢（a: このツール、A: この呼び出し）を出す
+    pub fn with_always_choices(mut self, always_choices: bool) -> Self {
+        self.always_choices = always_choices;
+        self
+    }
+
+    /// a と A の説明を変える（既定は「このツール」「この呼び出し」）
+    pub fn with_always_labels(mut self, tool: impl Into<String>, exact: impl Into<String>) -> Self {
+        self.always_labels = (tool.into(), exact.into());
+        self
+    }
+
     /// 自動承認モードを設定（テスト用）
     pub fn with_auto_approve(mut self, auto_approve: bool) -> Self {
         self.auto_approve = auto_approve;
@@ -94,7 +127,11 @@ impl ConfirmDialog {
         execute!(
             stdout,
             SetForegroundColor(Color::Yellow),
-            Print("Execute? [y/N]: "),
+            Print(if self.always_choices {
+                format!("Execute? [y/N, a = {}, A = {}]: ", self.always_labels.0, self.always_labels.1)
+            } else {
+                "Execute? [y/N]: ".to_string()
+            }),
             ResetColor
         )?;
         stdout.flush()?;
@@ -103,11 +140,9 @@ impl ConfirmDialog {
         let mut input = String::new();
         io::stdin().read_line(&mut input)?;
 
-        let trimmed = input.trim().to_lowercase();
-
-        // 明示的に "y" または "yes" の場合のみ承認
-        if trimmed == "y" || trimmed == "yes" {
-            Ok(ConfirmResult::Approved)
+        let result = parse_answer(&input, self.always_choices);
+        if result.is_approved() {
+            Ok(result)
         } else {
             // デフォルトは拒否（空入力含む）
             execute!(
@@ -121,9 +156,111 @@ impl ConfirmDialog {
     }
 }
 
-/// ツールが確認を必要とするか判定
-pub fn requires_confirmation(tool_name: &str) -> bool {
-    DANGEROUS_TOOLS.contains(&tool_name)
+/// 確認への入力を解釈する（明示的な y / yes のみ承認、空入力を含めそれ以外は拒否）
+///
+/// `a` と `A` は「常に許可」の選択肢を出しているときだけ受け付ける
+fn parse_answer(input: &str, always_choices: bool) -> ConfirmResult {
+    match input.trim() {
+        "a" if always_choices => ConfirmResult::AlwaysAllowTool,
+        "A" if always_choices => ConfirmResult::AlwaysAllowExact,
+        answer if answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes") => ConfirmResult::Approved,
+        _ => ConfirmResult::Denied,
+    }
+}
+
+/// パスフレーズを入力させる（入力内容は表示しない）
+///
+/// Esc または Ctrl+C で中断した場合は `Interrupted` エラーを返す
+pub fn prompt_passphrase(prompt: &str) -> io::Result<String> {
+    let mut stdout = io::stdout();
+    execute!(
+        stdout,
+        SetForegroundColor(Color::Yellow),
+        Print(format!("{}: ", prompt)),
+        ResetColor
+    )?;
+    stdout.flush()?;
+
+    terminal::enable_raw_mode()?;
+    let result = read_hidden_line();
+    terminal::disable_raw_mode()?;
+    println!();
+
+    result
+}
+
+fn read_hidden_line() -> io::Result<String> {
+    let mut input = String::new();
+    loop {
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Enter => return Ok(input),
+            KeyCode::Esc => return Err(io::Error::new(io::ErrorKind::Interrupted, "passphrase entry cancelled")),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "passphrase entry cancelled"));
+            }
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) => input.push(c),
+            _ => {}
+        }
+    }
+}
+
+/// 送信前チェックの指摘に対する選択
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendChoice {
+    /// そのまま送信
+    Send,
+    /// 入力に戻して編集
+    Edit,
+}
+
+/// 送信前チェックの指摘を1行表示し、Enter で送信、e / Esc / Ctrl+C で編集に戻る
+pub fn ask_send_or_edit(notice: &str) -> io::Result<SendChoice> {
+    let mut stdout = io::stdout();
+    execute!(
+        stdout,
+        SetForegroundColor(Color::Yellow),
+        Print(format!("⚠ {} ", notice)),
+        ResetColor
+    )?;
+    stdout.flush()?;
+
+    terminal::enable_raw_mode()?;
+    let result = read_send_choice();
+    terminal::disable_raw_mode()?;
+    println!();
+
+    result
+}
+
+fn read_send_choice() -> io::Result<SendChoice> {
+    loop {
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Enter => return Ok(SendChoice::Send),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(SendChoice::Edit),
+            KeyCode::Char('e') | KeyCode::Char('E') | KeyCode::Esc => return Ok(SendChoice::Edit),
+            _ => {}
+        }
+    }
+}
+
+/// ツールが確認を必要とするか判定（ツールの宣言した副作用と現在のモードから決まる）
+pub fn requires_confirmation(effects: ToolEffects, mode: &Mode) -> bool {
+    mode.requires_confirmation(effects)
 }
 
 /// 確認ダイアログを表示する便利関数
@@ -144,14 +281,16 @@ pub fn confirm(action: impl Into<String>, details: impl Into<String>) -> io::Res
 ///
 /// # Arguments
 /// * `tool_name` - ツール名
+/// * `effects` - ツールの副作用
+/// * `mode` - 現在のモード（AcceptEdits ではファイル書き込みの確認を省略）
 /// * `details` - 実行詳細
 ///
 /// # Returns
 /// * `Ok(true)` - 実行許可
 /// * `Ok(false)` - 実行拒否
 /// * `Err` - I/Oエラー
-pub fn confirm_tool_execution(tool_name: &str, details: &str) -> io::Result<bool> {
-    if !requires_confirmation(tool_name) {
+pub fn confirm_tool_execution(tool_name: &str, effects: ToolEffects, mode: &Mode, details: &str) -> io::Result<bool> {
+    if !requires_confirmation(effects, mode) {
         return Ok(true);
     }
 
@@ -163,22 +302,60 @@ pub fn confirm_tool_execution(tool_name: &str, details: &str) -> io::Result<bool
     Ok(result == ConfirmResult::Approved)
 }
 
+/// 端末のダイアログで尋ねる確認ポリシー（対話モード、「常に許可」を選べる）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DialogConfirmation;
+
+impl ConfirmationPolicy for DialogConfirmation {
+    fn decide(&self, tool: &str, details: &str) -> ConfirmResult {
+        ConfirmDialog::new(format!("Execute tool: {}", tool), details)
+            .with_always_choices(true)
+            .show()
+            .unwrap_or(ConfirmResult::Denied)
+    }
+
+    fn decide_grant(&self, skill: &str, details: &str, always_choices: bool) -> ConfirmResult {
+        ConfirmDialog::new(format!("Grant path access to skill: {}", skill), details)
+            .with_always_choices(always_choices)
+            .with_always_labels("always for this skill", "always for this path")
+            .show()
+            .unwrap_or(ConfirmResult::Denied)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_answer() {
+        assert_eq!(parse_answer("y\n", false), ConfirmResult::Approved);
+        assert_eq!(parse_answer(" YES ", false), ConfirmResult::Approved);
+        assert_eq!(parse_answer("\n", true), ConfirmResult::Denied);
+        assert_eq!(parse_answer("a\n", true), ConfirmResult::AlwaysAllowTool);
+        assert_eq!(parse_answer("A\n", true), ConfirmResult::AlwaysAllowExact);
+        // 選択肢を出していなければ「常に許可」は受け付けない
+        assert_eq!(parse_answer("a\n", false), ConfirmResult::Denied);
+        assert!(!ConfirmResult::Denied.is_approved());
+        assert!(ConfirmResult::AlwaysAllowExact.is_approved());
+    }
+
     #[test]
     fn test_requires_confirmation() {
-        // 危険なツールは確認が必要
-        assert!(requires_confirmation("bash"));
-        assert!(requires_confirmation("write"));
-        assert!(requires_confirmation("edit"));
-        assert!(requires_confirmation("git_commit"));
-
-        // 安全なツールは確認不要
-        assert!(!requires_confirmation("read"));
-        assert!(!requires_confirmation("glob"));
-        assert!(!requires_confirmation("grep"));
+        // 副作用のあるツールは確認が必要
+        assert!(requires_confirmation(ToolEffects::Executes, &Mode::Execute));
+        assert!(requires_confirmation(ToolEffects::Writes, &Mode::Execute));
+
+        // 読み取り専用は確認不要
+        assert!(!requires_confirmation(ToolEffects::ReadOnly, &Mode::Execute));
+        assert!(confirm_tool_execution("read", ToolEffects::ReadOnly, &Mode::Execute, "").unwrap());
+    }
+
+    #[test]
+    fn test_accept_edits_bypasses_write_confirmation() {
+        // ダイアログを出さずに承認される
+        assert!(confirm_tool_execution("write", ToolEffects::Writes, &Mode::AcceptEdits, "").unwrap());
+        assert!(requires_confirmation(ToolEffects::Executes, &Mode::AcceptEdits));
     }
 
     #[test]
@@ -0,0 +1,298 @@
+//! REPL の入力履歴の表示（`/inputs`）
+//!
+//! 入力履歴を日ごとにまとめ、同じ日の同じ入力は回数付きの1行にする（`cargo test ×7`）。
+//! 保存形式は1行1件で、時刻付きの行は `: <UNIX秒>;<入力>`（時刻のない古い行もそのまま読める）。
+//! 複数行の入力は `:: <UNIX秒>;<入力>` とし、`\` と改行を `\\`・`\n` にエスケープして1行に収める。
+
+use chrono::{Local, NaiveDate, TimeZone};
+
+/// 1ページに表示する行数
+pub const PAGE_SIZE: usize = 30;
+
+/// 入力履歴の1件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputEntry {
+    pub command: String,
+    /// 入力時刻（UNIX秒、古い形式の行では None）
+    pub timestamp: Option<u64>,
+}
+
+impl InputEntry {
+    pub fn new(command: impl Into<String>, timestamp: Option<u64>) -> Self {
+        Self { command: command.into(), timestamp }
+    }
+
+    /// 保存ファイルの1行を読む
+    pub fn parse_line(line: &str) -> Self {
+        if let Some((ts, escaped)) = line.strip_prefix(":: ").and_then(|rest| rest.split_once(';')) {
+            if ts.is_empty() || ts.parse::<u64>().is_ok() {
+                return Self::new(unescape(escaped), ts.parse().ok());
+            }
+        }
+        let timed = line
+            .strip_prefix(": ")
+            .and_then(|rest| rest.split_once(';'))
+            .and_then(|(ts, command)| Some(Self::new(command, Some(ts.parse().ok()?))));
+        timed.unwrap_or_else(|| Self::new(line, None))
+    }
+
+    /// 保存ファイルの1行
+    pub fn to_line(&self) -> String {
+        if self.command.contains('\n') {
+            let ts = self.timestamp.map(|ts| ts.to_string()).unwrap_or_default();
+            return format!(":: {};{}", ts, escape(&self.command));
+        }
+        match self.timestamp {
+            Some(ts) => format!(": {};{}", ts, self.command),
+            None => self.command.clone(),
+        }
+    }
+}
+
+/// 複数行の入力を1行にする
+fn escape(command: &str) -> String {
+    command.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(escaped: &str) -> String {
+    let mut command = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                command.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                command.push('\\');
+                chars.next();
+            }
+            _ => command.push(c),
+        }
+    }
+    command
+}
+
+/// 1日分の入力（新しい順、同じ入力は回数にまとめる）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputGroup {
+    /// 日付（時刻のない入力は None）
+    pub day: Option<NaiveDate>,
+    /// 入力と回数
+    pub items: Vec<(String, usize)>,
+}
+
+/// 日ごとにまとめる（新しい日が先、時刻のない入力は最後）
+pub fn group_by_day(entries: &[InputEntry], to_date: impl Fn(u64) -> NaiveDate) -> Vec<InputGroup> {
+    let mut groups: Vec<InputGroup> = Vec::new();
+    for entry in entries.iter().rev() {
+        let day = entry.timestamp.map(&to_date);
+        let index = match groups.iter().position(|g| g.day == day) {
+            Some(index) => index,
+            None => {
+                groups.push(InputGroup { day, items: Vec::new() });
+                groups.len() - 1
+            }
+        };
+        let items = &mut groups[index].items;
+        match items.iter_mut().find(|(command, _)| *command == entry.command) {
+            Some((_, count)) => *count += 1,
+            None => items.push((entry.command.clone(), 1)),
+        }
+    }
+    groups.sort_by(|a, b| match (a.day, b.day) {
+        (Some(a), Some(b)) => b.cmp(&a),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+    groups
+}
+
+/// 語を含む入力（大文字小文字を区別しない）
+pub fn search(entries: &[InputEntry], query: &str) -> Vec<InputEntry> {
+    let query = query.to_lowercase();
+    entries
+        .iter()
+        .filter(|e| e.command.to_lowercase().contains(&query))
+        .cloned()
+        .collect()
+}
+
+/// ページ数（空でも1）
+pub fn page_count(groups: &[InputGroup]) -> usize {
+    let rows: usize = groups.iter().map(|g| g.items.len()).sum();
+    rows.div_ceil(PAGE_SIZE).max(1)
+}
+
+/// 1ページ分（1始まり）を表示用に整形する。日付の見出しはページをまたいでも付ける
+pub fn render_page(groups: &[InputGroup], page: usize, today: NaiveDate) -> String {
+    let rows: Vec<(usize, &(String, usize))> = groups
+        .iter()
+        .enumerate()
+        .flat_map(|(i, g)| g.items.iter().map(move |item| (i, item)))
+        .collect();
+    if rows.is_empty() {
+        return "No input history.".to_string();
+    }
+    let pages = page_count(groups);
+    if page == 0 || page > pages {
+        return format!("No page {} (1-{}).", page, pages);
+    }
+
+    let mut lines = Vec::new();
+    let mut current = None;
+    for (group, (command, count)) in rows.iter().skip((page - 1) * PAGE_SIZE).take(PAGE_SIZE) {
+        if current != Some(*group) {
+            current = Some(*group);
+            lines.push(day_label(groups[*group].day, today));
+        }
+        let command = command.replace('\n', "⏎");
+        if *count > 1 {
+            lines.push(format!("  {} ×{}", command, count));
+        } else {
+            lines.push(format!("  {}", command));
+        }
+    }
+    if pages > 1 {
+        lines.push(String::new());
+        lines.push(format!("Page {}/{}", page, pages));
+    }
+    lines.join("\n")
+}
+
+/// `/inputs` の表示（日付はローカル時刻）。検索時は1ページ目だけを出す
+pub fn render(entries: &[InputEntry], query: Option<&str>, page: usize) -> String {
+    let to_date = |ts: u64| {
+        Local
+            .timestamp_opt(ts as i64, 0)
+            .single()
+            .map(|dt| dt.date_naive())
+            .unwrap_or_default()
+    };
+    let today = Local::now().date_naive();
+    match query {
+        Some(query) => {
+            let found = search(entries, query);
+            if found.is_empty() {
+                return format!("No inputs matching '{}'.", query);
+            }
+            let groups = group_by_day(&found, to_date);
+            let mut text = format!("{} inputs matching '{}':\n\n{}", found.len(), query, render_page(&groups, 1, today));
+            if page_count(&groups) > 1 {
+                text.push_str(" (refine the search to see more)");
+            }
+            text
+        }
+        None => {
+            let groups = group_by_day(entries, to_date);
+            let mut text = render_page(&groups, page, today);
+            if page < page_count(&groups) {
+                text.push_str(&format!(" (/inputs {} for more)", page + 1));
+            }
+            text
+        }
+    }
+}
+
+fn day_label(day: Option<NaiveDate>, today: NaiveDate) -> String {
+    match day {
+        Some(day) if day == today => format!("{} (today)", day),
+        Some(day) if Some(day) == today.pred_opt() => format!("{} (yesterday)", day),
+        Some(day) => day.to_string(),
+        None => "Earlier (undated)".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 86_400;
+    // 2026-10-16 00:00:00 UTC
+    const TODAY: u64 = 1_792_108_800;
+
+    fn utc_date(ts: u64) -> NaiveDate {
+        chrono::DateTime::from_timestamp(ts as i64, 0).unwrap().date_naive()
+    }
+
+    fn entry(command: &str, ts: Option<u64>) -> InputEntry {
+        InputEntry::new(command, ts)
+    }
+
+    #[test]
+    fn test_line_format_round_trip() {
+        let timed = entry("cargo test", Some(TODAY));
+        assert_eq!(timed.to_line(), ": 1792108800;cargo test");
+        assert_eq!(InputEntry::parse_line(&timed.to_line()), timed);
+        // 古い形式と、時刻に見えない行はそのまま入力として読む
+        assert_eq!(InputEntry::parse_line("/help"), entry("/help", None));
+        assert_eq!(InputEntry::parse_line(": not;a timestamp"), entry(": not;a timestamp", None));
+    }
+
+    #[test]
+    fn test_multiline_entry_round_trip() {
+        let multi = entry("printf 'a\\n' \\\n  | cat\nsecond", Some(TODAY));
+        let line = multi.to_line();
+        assert_eq!(line, ":: 1792108800;printf 'a\\\\n' \\\\\\n  | cat\\nsecond");
+        assert!(!line.contains('\n'));
+        assert_eq!(InputEntry::parse_line(&line), multi);
+
+        let undated = entry("one\ntwo", None);
+        assert_eq!(InputEntry::parse_line(&undated.to_line()), undated);
+    }
+
+    #[test]
+    fn test_group_by_day_collapses_duplicates() {
+        let entries = vec![
+            entry("/help", None),
+            entry("cargo test", Some(TODAY - DAY + 10)),
+            entry("fix it", Some(TODAY - DAY + 20)),
+            entry("cargo test", Some(TODAY - DAY + 30)),
+            entry("cargo test", Some(TODAY + 10)),
+            entry("/inputs", Some(TODAY + 20)),
+            entry("cargo test", Some(TODAY + 30)),
+        ];
+        let groups = group_by_day(&entries, utc_date);
+        let today = utc_date(TODAY);
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[0].day, Some(today));
+        assert_eq!(groups[0].items, vec![("cargo test".to_string(), 2), ("/inputs".to_string(), 1)]);
+        assert_eq!(groups[1].items, vec![("cargo test".to_string(), 2), ("fix it".to_string(), 1)]);
+        assert_eq!(groups[2].day, None);
+
+        let page = render_page(&groups, 1, today);
+        assert_eq!(
+            page,
+            "2026-10-16 (today)\n  cargo test ×2\n  /inputs\n\
+             2026-10-15 (yesterday)\n  cargo test ×2\n  fix it\n\
+             Earlier (undated)\n  /help"
+        );
+    }
+
+    #[test]
+    fn test_pagination_repeats_day_header() {
+        let entries: Vec<InputEntry> = (0..PAGE_SIZE + 5)
+            .map(|i| entry(&format!("input {}", i), Some(TODAY + i as u64)))
+            .collect();
+        let groups = group_by_day(&entries, utc_date);
+        assert_eq!(page_count(&groups), 2);
+
+        let first = render_page(&groups, 1, utc_date(TODAY));
+        assert!(first.starts_with("2026-10-16 (today)\n  input 34"));
+        assert!(first.ends_with("Page 1/2"));
+        let second = render_page(&groups, 2, utc_date(TODAY));
+        assert!(second.starts_with("2026-10-16 (today)\n  input 4\n"));
+        assert_eq!(second.lines().filter(|l| l.starts_with("  ")).count(), 5);
+        assert_eq!(render_page(&groups, 3, utc_date(TODAY)), "No page 3 (1-2).");
+        assert_eq!(render_page(&[], 1, utc_date(TODAY)), "No input history.");
+    }
+
+    #[test]
+    fn test_search_is_case_insensitive() {
+        let entries = vec![entry("Cargo Test", None), entry("git status", None), entry("cargo build", None)];
+        let found: Vec<String> = search(&entries, "cargo").into_iter().map(|e| e.command).collect();
+        assert_eq!(found, vec!["Cargo Test", "cargo build"]);
+        assert!(search(&entries, "missing").is_empty());
+    }
+}
@@ -0,0 +1,195 @@
+//! 端末幅に応じたレイアウト
+//!
+//! 表示内容と端末幅から整形結果を決める純粋関数群。
+//! 幅はキャッシュせず、描画のたびに `terminal_width` で取得する
+
+use crossterm::terminal;
+use unicode_width::UnicodeWidthChar;
+
+/// これより狭い場合は1行のバナーにする
+pub const COMPACT_BANNER_WIDTH: usize = 60;
+
+/// これより狭い場合はプロンプトの "(shift+tab)" を省略する
+pub const PROMPT_HINT_MIN_WIDTH: usize = 60;
+
+/// プロンプトが使ってよい幅の割合（残りは入力用）
+const PROMPT_WIDTH_RATIO: usize = 2;
+
+/// モデル名をこれより短くしか表示できない場合は省略する
+const MIN_MODEL_CHARS: usize = 4;
+
+/// コードブロックの最小幅
+const MIN_CODE_WIDTH: usize = 40;
+
+/// 現在の端末幅（取得できない場合は80）
+pub fn terminal_width() -> usize {
+    terminal::size().map(|(cols, _)| cols as usize).unwrap_or(80).max(1)
+}
+
+/// 最大 max 文字に切り詰める（切り詰めた場合は末尾を "…" にする）
+pub fn truncate_with_ellipsis(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    if max == 0 {
+        return String::new();
+    }
+    let mut truncated: String = text.chars().take(max - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// 狭い端末用の1行バナー（幅が十分なら None）
+pub fn compact_banner(version: &str, model: &str, width: usize) -> Option<String> {
+    if width >= COMPACT_BANNER_WIDTH {
+        return None;
+    }
+    let line = format!("local-code v{} · {}", version, model);
+    Some(truncate_with_ellipsis(&line, width.saturating_sub(1)))
+}
+
+/// プロンプトの表示要素
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptLayout {
+    /// 表示するモデル名（収まらない場合は None）
+    pub model: Option<String>,
+    /// "(shift+tab)" を表示するか
+    pub show_hint: bool,
+}
+
+/// プロンプトの表示要素を決める
+///
+/// `アイコン Mode model (shift+tab) ❯ ` が端末幅の半分に収まるようにする
+pub fn prompt_layout(mode: &str, model: &str, width: usize) -> PromptLayout {
+    const HINT: &str = " (shift+tab)";
+    // アイコン(2) + 空白 + モード + " ❯ "
+    let fixed = 3 + mode.chars().count() + 3;
+    let budget = width / PROMPT_WIDTH_RATIO;
+
+    let show_hint = width >= PROMPT_HINT_MIN_WIDTH && fixed + HINT.len() <= budget;
+    let used = fixed + if show_hint { HINT.len() } else { 0 };
+
+    // モデル名の前の空白1文字分を除く
+    let available = budget.saturating_sub(used + 1);
+    let model = if model.is_empty() || available < MIN_MODEL_CHARS {
+        None
+    } else {
+        Some(truncate_with_ellipsis(model, available))
+    };
+
+    PromptLayout { model, show_hint }
+}
+
+/// 区切り線
+pub fn separator(width: usize) -> String {
+    "-".repeat(width)
+}
+
+/// コードブロックの内側の幅（枠線 "│ " と " │" を除く）
+pub fn code_block_inner_width(longest_line: usize, width: usize) -> usize {
+    longest_line.max(MIN_CODE_WIDTH).min(width.saturating_sub(4).max(1))
+}
+
+/// 1行を幅 width ごとに折り返す
+pub fn wrap_to_width(line: &str, width: usize) -> Vec<String> {
+    let chars: Vec<char> = line.chars().collect();
+    if chars.is_empty() || width == 0 {
+        return vec![String::new()];
+    }
+    chars.chunks(width).map(|chunk| chunk.iter().collect()).collect()
+}
+
+/// 端末で占める行数（エスケープシーケンスは幅に数えず、文字の表示幅で折り返す）
+pub fn display_rows(line: &str, width: usize) -> usize {
+    let mut columns = 0;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // CSI シーケンスは終端の英字まで読み飛ばす
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            columns += c.width().unwrap_or(0);
+        }
+    }
+    columns.div_ceil(width.max(1)).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_with_ellipsis() {
+        assert_eq!(truncate_with_ellipsis("qwen2.5-coder", 20), "qwen2.5-coder");
+        assert_eq!(truncate_with_ellipsis("qwen2.5-coder", 6), "qwen2…");
+        assert_eq!(truncate_with_ellipsis("日本語モデル", 4), "日本語…");
+        assert_eq!(truncate_with_ellipsis("abc", 0), "");
+    }
+
+    #[test]
+    fn test_compact_banner_below_threshold() {
+        assert_eq!(compact_banner("0.1.0", "Rnj-1", 120), None);
+        assert_eq!(compact_banner("0.1.0", "Rnj-1", 60), None);
+        assert_eq!(
+            compact_banner("0.1.0", "Rnj-1", 59).as_deref(),
+            Some("local-code v0.1.0 · Rnj-1")
+        );
+        let narrow = compact_banner("0.1.0", "qwen2.5-coder:32b-instruct", 30).unwrap();
+        assert_eq!(narrow.chars().count(), 29);
+        assert!(narrow.ends_with('…'));
+    }
+
+    #[test]
+    fn test_prompt_layout_at_several_widths() {
+        let model = "qwen2.5-coder:32b-instruct";
+
+        let wide = prompt_layout("Execute", model, 200);
+        assert!(wide.show_hint);
+        assert_eq!(wide.model.as_deref(), Some(model));
+
+        // 80桁: ヒントは残し、モデル名を切り詰める
+        let medium = prompt_layout("Execute", model, 80);
+        assert!(medium.show_hint);
+        let shown = medium.model.unwrap();
+        assert!(shown.ends_with('…'));
+        assert!(3 + 7 + 12 + 1 + shown.chars().count() + 3 <= 40);
+
+        // 閾値未満: ヒントを省略
+        let narrow = prompt_layout("Execute", model, 50);
+        assert!(!narrow.show_hint);
+        assert_eq!(narrow.model.as_deref(), Some("qwen2.5-co…"));
+
+        // 非常に狭い: モデル名も省略
+        let tiny = prompt_layout("Execute", model, 30);
+        assert_eq!(tiny, PromptLayout { model: None, show_hint: false });
+    }
+
+    #[test]
+    fn test_code_block_width_clamps_to_terminal() {
+        assert_eq!(code_block_inner_width(10, 120), 40);
+        assert_eq!(code_block_inner_width(100, 120), 100);
+        assert_eq!(code_block_inner_width(100, 50), 46);
+        assert_eq!(code_block_inner_width(10, 30), 26);
+        assert_eq!(code_block_inner_width(10, 2), 1);
+    }
+
+    #[test]
+    fn test_wrap_and_separator() {
+        assert_eq!(wrap_to_width("abcdefg", 3), vec!["abc", "def", "g"]);
+        assert_eq!(wrap_to_width("", 3), vec![""]);
+        assert_eq!(separator(5), "-----");
+    }
+
+    #[test]
+    fn test_display_rows_ignores_escapes() {
+        assert_eq!(display_rows("", 10), 1);
+        assert_eq!(display_rows("abcdefghij", 10), 1);
+        assert_eq!(display_rows("abcdefghijk", 10), 2);
+        assert_eq!(display_rows("\x1b[1;36mabcdefghij\x1b[0m", 10), 1);
+        assert_eq!(display_rows("日本語テスト", 10), 2);
+    }
+}
@@ -0,0 +1,497 @@
+//! REPL の入力バッファ（複数行対応）
+//!
+//! 端末への描画から切り離した編集モデル。カーソルは文字単位で持ち、改行をまたいで動く。
+//! 2行目以降は `… ` のガターを付けて描く。幅は他の描画と同じく文字数で数える。
+//! Ctrl+U/K/W などで消した文字列は `KillRing` に入れ、Ctrl+Y で戻す。
+
+/// 継続行の先頭に付ける印
+pub const CONTINUATION_GUTTER: &str = "… ";
+
+/// キルリングに残す件数
+const KILL_RING_SIZE: usize = 16;
+
+/// 消した文字列の履歴（Ctrl+Y で最新のものを戻す）
+#[derive(Debug, Clone, Default)]
+pub struct KillRing {
+    entries: Vec<String>,
+}
+
+impl KillRing {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 消した文字列を追加（古いものから捨てる）
+    pub fn push(&mut self, text: String) {
+        if text.is_empty() {
+            return;
+        }
+        if self.entries.len() >= KILL_RING_SIZE {
+            self.entries.remove(0);
+        }
+        self.entries.push(text);
+    }
+
+    /// 最後に消した文字列
+    pub fn last(&self) -> Option<&str> {
+        self.entries.last().map(String::as_str)
+    }
+}
+
+/// 描画時の行の配置（入力の先頭行を 0 とした端末上の行）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferLayout {
+    /// 折り返しを含めた行数
+    pub rows: usize,
+    /// カーソルの行
+    pub cursor_row: usize,
+    /// カーソルの桁
+    pub cursor_col: usize,
+}
+
+/// 編集中の入力
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineBuffer {
+    text: String,
+    /// カーソル位置（文字単位）
+    cursor: usize,
+}
+
+impl LineBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 内容を指定して作る（カーソルは末尾）
+    pub fn from_text(text: impl Into<String>) -> Self {
+        let mut buffer = Self::new();
+        buffer.set(text);
+        buffer
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// カーソルより前の部分
+    pub fn before_cursor(&self) -> &str {
+        &self.text[..self.byte_index(self.cursor)]
+    }
+
+    /// 内容を置き換える（カーソルは末尾）
+    pub fn set(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+        self.cursor = self.text.chars().count();
+    }
+
+    pub fn clear(&mut self) {
+        self.set(String::new());
+    }
+
+    /// カーソル位置に1文字挿入
+    pub fn insert(&mut self, c: char) {
+        let index = self.byte_index(self.cursor);
+        self.text.insert(index, c);
+        self.cursor += 1;
+    }
+
+    /// カーソル位置に文字列を挿入
+    pub fn insert_str(&mut self, s: &str) {
+        let index = self.byte_index(self.cursor);
+        self.text.insert_str(index, s);
+        self.cursor += s.chars().count();
+    }
+
+    /// バイト範囲を置き換え、カーソルを置き換えた部分の直後に置く（補完用）
+    pub fn replace_range(&mut self, start: usize, end: usize, replacement: &str) {
+        self.text.replace_range(start..end, replacement);
+        self.cursor = self.text[..start + replacement.len()].chars().count();
+    }
+
+    /// カーソルの前の1文字を消す（改行も消して前の行とつなぐ）
+    pub fn backspace(&mut self) -> bool {
+        if self.cursor == 0 {
+            return false;
+        }
+        self.cursor -= 1;
+        let index = self.byte_index(self.cursor);
+        self.text.remove(index);
+        true
+    }
+
+    /// カーソル位置の1文字を消す
+    pub fn delete(&mut self) -> bool {
+        if self.cursor >= self.char_len() {
+            return false;
+        }
+        let index = self.byte_index(self.cursor);
+        self.text.remove(index);
+        true
+    }
+
+    /// 1文字左へ（行頭では前の行の末尾へ）
+    pub fn move_left(&mut self) -> bool {
+        if self.cursor == 0 {
+            return false;
+        }
+        self.cursor -= 1;
+        true
+    }
+
+    /// 1文字右へ（行末では次の行の先頭へ）
+    pub fn move_right(&mut self) -> bool {
+        if self.cursor >= self.char_len() {
+            return false;
+        }
+        self.cursor += 1;
+        true
+    }
+
+    /// 行頭へ。すでに行頭なら前の行の先頭へ
+    pub fn move_home(&mut self) -> bool {
+        if self.cursor == 0 {
+            return false;
+        }
+        let (row, col) = self.row_col();
+        let target_row = if col == 0 { row - 1 } else { row };
+        self.cursor = self.row_start(target_row);
+        true
+    }
+
+    /// 行末へ。すでに行末なら次の行の末尾へ
+    pub fn move_end(&mut self) -> bool {
+        let total = self.char_len();
+        if self.cursor >= total {
+            return false;
+        }
+        let (row, col) = self.row_col();
+        let line_len = self.line_len(row);
+        let target_row = if col == line_len { row + 1 } else { row };
+        self.cursor = self.row_start(target_row) + self.line_len(target_row);
+        true
+    }
+
+    /// 論理行の先頭へ（Ctrl+A）
+    pub fn move_line_start(&mut self) -> bool {
+        let start = self.row_start(self.row_col().0);
+        std::mem::replace(&mut self.cursor, start) != start
+    }
+
+    /// 論理行の末尾へ（Ctrl+E）
+    pub fn move_line_end(&mut self) -> bool {
+        let row = self.row_col().0;
+        let end = self.row_start(row) + self.line_len(row);
+        std::mem::replace(&mut self.cursor, end) != end
+    }
+
+    /// 前の単語の先頭へ（Alt+B、英数字と `_` を単語とみなす）
+    pub fn move_word_left(&mut self) -> bool {
+        let chars: Vec<char> = self.text.chars().collect();
+        let mut pos = self.cursor;
+        while pos > 0 && !is_word_char(chars[pos - 1]) {
+            pos -= 1;
+        }
+        while pos > 0 && is_word_char(chars[pos - 1]) {
+            pos -= 1;
+        }
+        std::mem::replace(&mut self.cursor, pos) != pos
+    }
+
+    /// 次の単語の末尾へ（Alt+F）
+    pub fn move_word_right(&mut self) -> bool {
+        let chars: Vec<char> = self.text.chars().collect();
+        let mut pos = self.cursor;
+        while pos < chars.len() && !is_word_char(chars[pos]) {
+            pos += 1;
+        }
+        while pos < chars.len() && is_word_char(chars[pos]) {
+            pos += 1;
+        }
+        std::mem::replace(&mut self.cursor, pos) != pos
+    }
+
+    /// 論理行の先頭からカーソルまでを消して返す（Ctrl+U）
+    pub fn kill_to_line_start(&mut self) -> Option<String> {
+        let start = self.row_start(self.row_col().0);
+        self.kill(start, self.cursor)
+    }
+
+    /// カーソルから論理行の末尾までを消して返す（Ctrl+K）
+    ///
+    /// 行末にいるときは改行を消して次の行とつなぐ
+    pub fn kill_to_line_end(&mut self) -> Option<String> {
+        let row = self.row_col().0;
+        let end = self.row_start(row) + self.line_len(row);
+        if end == self.cursor {
+            return self.kill(self.cursor, (end + 1).min(self.char_len()));
+        }
+        self.kill(self.cursor, end)
+    }
+
+    /// カーソルの前の単語を空白区切りで消して返す（Ctrl+W）
+    pub fn kill_word_back(&mut self) -> Option<String> {
+        let chars: Vec<char> = self.text.chars().collect();
+        let mut start = self.cursor;
+        while start > 0 && chars[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        while start > 0 && !chars[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        self.kill(start, self.cursor)
+    }
+
+    /// 文字範囲を消して返し、カーソルを範囲の先頭に置く
+    fn kill(&mut self, start: usize, end: usize) -> Option<String> {
+        if start >= end {
+            return None;
+        }
+        let (from, to) = (self.byte_index(start), self.byte_index(end));
+        let killed = self.text[from..to].to_string();
+        self.text.replace_range(from..to, "");
+        self.cursor = start;
+        Some(killed)
+    }
+
+    /// 末尾の `\` を継続の印として改行に置き換える（カーソルが末尾にあるときだけ）
+    pub fn continue_line(&mut self) -> bool {
+        if self.cursor != self.char_len() || !self.text.ends_with('\\') {
+            return false;
+        }
+        self.text.pop();
+        self.text.push('\n');
+        true
+    }
+
+    /// カーソルの論理行と桁（どちらも 0 始まり、桁は文字数）
+    pub fn row_col(&self) -> (usize, usize) {
+        let before = self.before_cursor();
+        let row = before.matches('\n').count();
+        let col = before.rsplit('\n').next().unwrap_or("").chars().count();
+        (row, col)
+    }
+
+    /// 端末上の配置を計算する
+    ///
+    /// `start_col` は1行目の開始桁（プロンプトの幅）、`width` は端末の幅。
+    /// ちょうど幅を埋めた行は、描画側で次の行に送るので1行多く数える
+    pub fn layout(&self, start_col: usize, width: usize) -> BufferLayout {
+        let width = width.max(1);
+        let gutter = CONTINUATION_GUTTER.chars().count();
+        let (cursor_line, cursor_col) = self.row_col();
+        let mut rows = 0;
+        let mut cursor = (0, 0);
+        for (i, line) in self.text.split('\n').enumerate() {
+            let prefix = if i == 0 { start_col } else { gutter };
+            if i == cursor_line {
+                let offset = prefix + cursor_col;
+                cursor = (rows + offset / width, offset % width);
+            }
+            rows += (prefix + line.chars().count()) / width + 1;
+        }
+        BufferLayout {
+            rows,
+            cursor_row: cursor.0,
+            cursor_col: cursor.1,
+        }
+    }
+
+    fn char_len(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    /// 論理行の先頭の位置（文字単位）
+    fn row_start(&self, row: usize) -> usize {
+        self.text
+            .split('\n')
+            .take(row)
+            .map(|line| line.chars().count() + 1)
+            .sum()
+    }
+
+    fn line_len(&self, row: usize) -> usize {
+        self.text.split('\n').nth(row).map_or(0, |line| line.chars().count())
+    }
+
+    fn byte_index(&self, char_index: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(char_index)
+            .map_or(self.text.len(), |(index, _)| index)
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_editing_across_lines() {
+        let mut buffer = LineBuffer::new();
+        buffer.insert_str("fn a()");
+        buffer.insert('\n');
+        buffer.insert_str("日本");
+        assert_eq!(buffer.text(), "fn a()\n日本");
+        assert_eq!(buffer.row_col(), (1, 2));
+
+        // 行頭から左へ動くと前の行の末尾に行く
+        buffer.move_left();
+        buffer.move_left();
+        assert_eq!(buffer.row_col(), (1, 0));
+        buffer.move_left();
+        assert_eq!(buffer.row_col(), (0, 6));
+        buffer.move_right();
+        assert_eq!(buffer.row_col(), (1, 0));
+
+        // 行頭での Backspace は前の行とつなぐ
+        assert!(buffer.backspace());
+        assert_eq!(buffer.text(), "fn a()日本");
+        buffer.insert('\n');
+        assert!(buffer.delete());
+        assert_eq!(buffer.text(), "fn a()\n本");
+        assert!(!LineBuffer::new().backspace());
+    }
+
+    #[test]
+    fn test_home_and_end_step_between_lines() {
+        let mut buffer = LineBuffer::from_text("one\ntwo\nthree");
+        assert_eq!(buffer.cursor(), 13);
+        assert!(buffer.move_home());
+        assert_eq!(buffer.row_col(), (2, 0));
+        assert!(buffer.move_home());
+        assert_eq!(buffer.row_col(), (1, 0));
+        assert!(buffer.move_end());
+        assert_eq!(buffer.row_col(), (1, 3));
+        assert!(buffer.move_end());
+        assert_eq!(buffer.row_col(), (2, 5));
+        assert!(!buffer.move_end());
+
+        buffer.move_home();
+        buffer.move_home();
+        buffer.move_home();
+        assert_eq!(buffer.cursor(), 0);
+        assert!(!buffer.move_home());
+    }
+
+    #[test]
+    fn test_trailing_backslash_continues() {
+        let mut buffer = LineBuffer::from_text("first line \\");
+        assert!(buffer.continue_line());
+        assert_eq!(buffer.text(), "first line \n");
+        assert_eq!(buffer.row_col(), (1, 0));
+        assert!(!buffer.continue_line());
+
+        // カーソルが末尾にないときは継続しない
+        let mut buffer = LineBuffer::from_text("a\\");
+        buffer.move_left();
+        assert!(!buffer.continue_line());
+    }
+
+    #[test]
+    fn test_layout_counts_gutter_and_wrapping() {
+        let buffer = LineBuffer::from_text("hello\nworld");
+        // プロンプト幅 4、端末幅 80
+        assert_eq!(buffer.layout(4, 80), BufferLayout { rows: 2, cursor_row: 1, cursor_col: 7 });
+
+        // 1行目が幅 10 で折り返す: "> " + 12文字 → 2行
+        let mut buffer = LineBuffer::from_text("abcdefghijkl\nxy");
+        assert_eq!(buffer.layout(2, 10), BufferLayout { rows: 3, cursor_row: 2, cursor_col: 4 });
+        buffer.move_home();
+        buffer.move_home();
+        assert_eq!(buffer.layout(2, 10), BufferLayout { rows: 3, cursor_row: 0, cursor_col: 2 });
+        buffer.set("abcdefgh");
+        // ちょうど幅を埋めるとカーソルは次の行の先頭
+        assert_eq!(buffer.layout(2, 10), BufferLayout { rows: 2, cursor_row: 1, cursor_col: 0 });
+    }
+
+    #[test]
+    fn test_replace_range_moves_cursor() {
+        let mut buffer = LineBuffer::from_text("see @sr and");
+        buffer.replace_range(4, 7, "@src/");
+        assert_eq!(buffer.text(), "see @src/ and");
+        assert_eq!(buffer.before_cursor(), "see @src/");
+    }
+
+    #[test]
+    fn test_line_start_and_end_stay_on_line() {
+        let mut buffer = LineBuffer::from_text("first\nsecond");
+        assert!(buffer.move_line_start());
+        assert_eq!(buffer.row_col(), (1, 0));
+        // Home と違い、行頭から前の行へは動かない
+        assert!(!buffer.move_line_start());
+        assert!(buffer.move_line_end());
+        assert_eq!(buffer.row_col(), (1, 6));
+        assert!(!buffer.move_line_end());
+    }
+
+    #[test]
+    fn test_word_movement_is_unicode_aware() {
+        let mut buffer = LineBuffer::from_text("fix 日本語 tests, now");
+        assert!(buffer.move_word_left());
+        assert_eq!(buffer.before_cursor(), "fix 日本語 tests, ");
+        buffer.move_word_left();
+        assert_eq!(buffer.before_cursor(), "fix 日本語 ");
+        buffer.move_word_left();
+        assert_eq!(buffer.before_cursor(), "fix ");
+        assert!(buffer.move_word_right());
+        assert_eq!(buffer.before_cursor(), "fix 日本語");
+        buffer.move_word_right();
+        assert_eq!(buffer.before_cursor(), "fix 日本語 tests");
+        buffer.move_line_start();
+        assert!(!buffer.move_word_left());
+    }
+
+    #[test]
+    fn test_kills_return_removed_text() {
+        let mut buffer = LineBuffer::from_text("git commit -m 日本語");
+        assert_eq!(buffer.kill_word_back().as_deref(), Some("日本語"));
+        assert_eq!(buffer.kill_word_back().as_deref(), Some("-m "));
+        assert_eq!(buffer.text(), "git commit ");
+
+        buffer.move_word_left();
+        assert_eq!(buffer.kill_to_line_end().as_deref(), Some("commit "));
+        assert_eq!(buffer.kill_to_line_start().as_deref(), Some("git "));
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.kill_to_line_start(), None);
+
+        // 複数行では今の行だけ、行末の Ctrl+K は改行を消す
+        let mut buffer = LineBuffer::from_text("one\ntwo");
+        assert_eq!(buffer.kill_to_line_start().as_deref(), Some("two"));
+        assert_eq!(buffer.text(), "one\n");
+        buffer.move_left();
+        assert_eq!(buffer.kill_to_line_end().as_deref(), Some("\n"));
+        assert_eq!(buffer.text(), "one");
+    }
+
+    #[test]
+    fn test_kill_ring_yanks_latest() {
+        let mut ring = KillRing::new();
+        assert_eq!(ring.last(), None);
+        ring.push(String::new());
+        assert_eq!(ring.last(), None);
+        for i in 0..20 {
+            ring.push(format!("kill {}", i));
+        }
+        assert_eq!(ring.last(), Some("kill 19"));
+        assert_eq!(ring.entries.len(), KILL_RING_SIZE);
+
+        let mut buffer = LineBuffer::from_text("hello world");
+        let killed = buffer.kill_word_back().unwrap();
+        ring.push(killed);
+        buffer.move_line_start();
+        buffer.insert_str(ring.last().unwrap());
+        assert_eq!(buffer.text(), "worldhello ");
+    }
+}
@@ -0,0 +1,394 @@
+//! コマンドの別名と `/help` の説明のローカライズ
+//!
+//! ロケールごとに埋め込みの TOML（`config/locales/<locale>.toml`）を読み、
+//! `~/.local-code/locales/<locale>.toml` があれば上書きする。
+//! 有効なロケールの別名（例: `/保存` → `/save`）は英語のコマンドと並んで使える。
+//! 別名が英語のコマンドやスキルと重なる場合は読み込みを失敗させる。
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+use super::commands::{Command, CommandSpec, COMMAND_SPECS};
+
+/// 埋め込みの日本語ロケール
+const JA_TOML: &str = include_str!("../../config/locales/ja.toml");
+
+/// 表示言語
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    En,
+    Ja,
+}
+
+impl Locale {
+    /// 設定値（`en`/`ja`/`auto`）から決める。`auto` は LC_ALL、LC_MESSAGES、LANG の順に見る
+    pub fn resolve(setting: &str) -> Self {
+        match setting.trim().to_lowercase().as_str() {
+            "en" => Locale::En,
+            "ja" => Locale::Ja,
+            "auto" | "" => {
+                let lang = ["LC_ALL", "LC_MESSAGES", "LANG"]
+                    .iter()
+                    .filter_map(|name| std::env::var(name).ok())
+                    .find(|value| !value.is_empty());
+                Self::from_lang(lang.as_deref())
+            }
+            other => {
+                tracing::warn!("Unknown ui.locale '{}', using en", other);
+                Locale::En
+            }
+        }
+    }
+
+    /// `ja_JP.UTF-8` のような環境変数の値から決める
+    pub fn from_lang(lang: Option<&str>) -> Self {
+        match lang {
+            Some(lang) if lang.to_lowercase().starts_with("ja") => Locale::Ja,
+            _ => Locale::En,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Ja => "ja",
+        }
+    }
+
+    /// 埋め込みのロケールファイル（英語は組み込みの表そのもの）
+    fn embedded(&self) -> Option<&'static str> {
+        match self {
+            Locale::En => None,
+            Locale::Ja => Some(JA_TOML),
+        }
+    }
+}
+
+/// ロケールファイル
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LocaleFile {
+    #[serde(default)]
+    help: HelpStrings,
+    /// コマンド表のキー → 別名と説明
+    #[serde(default)]
+    commands: BTreeMap<String, LocaleEntry>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HelpStrings {
+    header: Option<String>,
+    skill: Option<String>,
+    footer: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LocaleEntry {
+    aliases: Option<Vec<String>>,
+    summary: Option<String>,
+}
+
+/// ローカライズしたコマンド表
+#[derive(Debug, Clone)]
+pub struct CommandTable {
+    locale: Locale,
+    /// ローカライズした別名（小文字、`/` なし）→ 正式名
+    aliases: BTreeMap<String, &'static str>,
+    /// コマンド表のキー → 別名（表示順）
+    aliases_by_key: HashMap<&'static str, Vec<String>>,
+    /// コマンド表のキー → 説明
+    summaries: HashMap<&'static str, String>,
+    header: String,
+    skill: String,
+    footer: String,
+}
+
+impl CommandTable {
+    /// 英語だけの表
+    pub fn english() -> Self {
+        Self {
+            locale: Locale::En,
+            aliases: BTreeMap::new(),
+            aliases_by_key: HashMap::new(),
+            summaries: COMMAND_SPECS.iter().map(|spec| (spec.key, spec.summary.to_string())).collect(),
+            header: "Commands:".to_string(),
+            skill: "Run a skill".to_string(),
+            footer: "Enter text to chat with the AI.".to_string(),
+        }
+    }
+
+    /// ロケールの表を読む（`user_dir` の `<locale>.toml` で上書き）。`skills` は別名と重ねられない名前
+    pub fn load(locale: Locale, user_dir: Option<&Path>, skills: &[String]) -> Result<Self> {
+        let mut sources: Vec<(String, String)> = Vec::new();
+        if let Some(embedded) = locale.embedded() {
+            sources.push((format!("built-in {} locale", locale.as_str()), embedded.to_string()));
+        }
+        if let Some(path) = user_dir.map(|dir| dir.join(format!("{}.toml", locale.as_str()))) {
+            if path.exists() {
+                let content = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                sources.push((path.display().to_string(), content));
+            }
+        }
+        Self::from_sources(locale, &sources, skills)
+    }
+
+    /// 名前付きの TOML を順に重ねて表を作る（後のものが優先）
+    fn from_sources(locale: Locale, sources: &[(String, String)], skills: &[String]) -> Result<Self> {
+        let mut table = Self::english();
+        table.locale = locale;
+        for (origin, content) in sources {
+            let file: LocaleFile =
+                toml::from_str(content).with_context(|| format!("Invalid locale file ({})", origin))?;
+            table.apply(file, origin)?;
+        }
+        table.check_collisions(skills)?;
+        Ok(table)
+    }
+
+    fn apply(&mut self, file: LocaleFile, origin: &str) -> Result<()> {
+        for (key, entry) in file.commands {
+            let Some(spec) = COMMAND_SPECS.iter().find(|spec| spec.key == key) else {
+                bail!("Unknown command '{}' in locale file ({})", key, origin);
+            };
+            if let Some(summary) = entry.summary {
+                self.summaries.insert(spec.key, summary);
+            }
+            if let Some(aliases) = entry.aliases {
+                if !spec.is_primary() && !aliases.is_empty() {
+                    bail!("'{}' cannot have aliases; set them on '{}' ({})", key, spec.name, origin);
+                }
+                let aliases: Vec<String> = aliases
+                    .iter()
+                    .map(|alias| alias.trim().trim_start_matches('/').to_lowercase())
+                    .collect();
+                if aliases.iter().any(|alias| alias.is_empty() || alias.contains(char::is_whitespace)) {
+                    bail!("Aliases for /{} must be single words ({})", spec.name, origin);
+                }
+                self.aliases_by_key.insert(spec.key, aliases);
+            }
+        }
+        let help = file.help;
+        if let Some(header) = help.header {
+            self.header = header;
+        }
+        if let Some(skill) = help.skill {
+            self.skill = skill;
+        }
+        if let Some(footer) = help.footer {
+            self.footer = footer;
+        }
+        Ok(())
+    }
+
+    /// 別名が英語のコマンド・他の別名・スキルと重ならないか確かめて索引を作る
+    fn check_collisions(&mut self, skills: &[String]) -> Result<()> {
+        let builtin: HashSet<String> = COMMAND_SPECS
+            .iter()
+            .flat_map(|spec| std::iter::once(spec.name).chain(spec.aliases.iter().copied()))
+            .map(str::to_string)
+            .collect();
+        let skills: HashSet<String> = skills.iter().map(|s| s.to_lowercase()).collect();
+
+        self.aliases.clear();
+        for spec in COMMAND_SPECS.iter().filter(|spec| spec.is_primary()) {
+            for alias in self.aliases_by_key.get(spec.key).into_iter().flatten() {
+                if builtin.contains(alias) {
+                    bail!("Localized alias /{} for /{} collides with the built-in command /{}", alias, spec.name, alias);
+                }
+                if skills.contains(alias) {
+                    bail!("Localized alias /{} for /{} collides with the skill /{}", alias, spec.name, alias);
+                }
+                match self.aliases.insert(alias.clone(), spec.name) {
+                    Some(other) if other != spec.name => {
+                        bail!("Localized alias /{} is used for both /{} and /{}", alias, other, spec.name)
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn locale(&self) -> Locale {
+        self.locale
+    }
+
+    /// 別名を正式名にする（英語の名前や未知の名前はそのまま None）
+    pub fn resolve(&self, name: &str) -> Option<&'static str> {
+        self.aliases.get(&name.to_lowercase()).copied()
+    }
+
+    /// ローカライズした別名も受け付けてパースする
+    pub fn parse(&self, input: &str) -> Command {
+        let trimmed = input.trim();
+        if let Some(rest) = trimmed.strip_prefix('/') {
+            let (word, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            if let Some(name) = self.resolve(word) {
+                return Command::parse(&format!("/{} {}", name, args));
+            }
+        }
+        Command::parse(input)
+    }
+
+    /// 補完候補にするローカライズした別名（`/` 付き）
+    pub fn localized_commands(&self) -> Vec<String> {
+        self.aliases.keys().map(|alias| format!("/{}", alias)).collect()
+    }
+
+    /// `/help` の本文
+    pub fn help_text(&self) -> String {
+        let mut lines = vec![String::new(), self.header.clone()];
+        for spec in COMMAND_SPECS {
+            lines.push(format!("  {:<15} - {}", self.usage_line(spec), self.summaries[spec.key]));
+        }
+        lines.push(format!("  {:<15} - {}", "/<skill-name>", self.skill));
+        lines.push(String::new());
+        lines.push(self.footer.clone());
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    /// `/save, /保存 <name>` のような左列
+    fn usage_line(&self, spec: &CommandSpec) -> String {
+        let mut names = vec![format!("/{}", spec.name)];
+        if spec.is_primary() {
+            names.extend(spec.aliases.iter().map(|alias| format!("/{}", alias)));
+            names.extend(self.aliases_by_key.get(spec.key).into_iter().flatten().map(|alias| format!("/{}", alias)));
+        }
+        let names = names.join(", ");
+        if spec.usage.is_empty() {
+            names
+        } else {
+            format!("{} {}", names, spec.usage)
+        }
+    }
+}
+
+impl Default for CommandTable {
+    fn default() -> Self {
+        Self::english()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn japanese() -> CommandTable {
+        CommandTable::load(Locale::Ja, None, &[]).unwrap()
+    }
+
+    fn source(content: &str) -> Vec<(String, String)> {
+        vec![("test".to_string(), content.to_string())]
+    }
+
+    #[test]
+    fn test_locale_from_lang() {
+        assert_eq!(Locale::from_lang(Some("ja_JP.UTF-8")), Locale::Ja);
+        assert_eq!(Locale::from_lang(Some("en_US.UTF-8")), Locale::En);
+        assert_eq!(Locale::from_lang(Some("C")), Locale::En);
+        assert_eq!(Locale::from_lang(None), Locale::En);
+        assert_eq!(Locale::resolve("ja"), Locale::Ja);
+        assert_eq!(Locale::resolve("EN"), Locale::En);
+    }
+
+    #[test]
+    fn test_every_spec_is_a_builtin_command() {
+        for spec in COMMAND_SPECS {
+            let command = Command::parse(&format!("/{}", spec.name));
+            assert!(!matches!(command, Command::Skill { .. }), "/{} parses as a skill", spec.name);
+        }
+    }
+
+    #[test]
+    fn test_builtin_japanese_locale_covers_every_command() {
+        let file: LocaleFile = toml::from_str(JA_TOML).unwrap();
+        for spec in COMMAND_SPECS {
+            assert!(file.commands.get(spec.key).and_then(|e| e.summary.as_ref()).is_some(), "no summary for {}", spec.key);
+        }
+    }
+
+    #[test]
+    fn test_japanese_aliases_parse_alongside_english() {
+        let table = japanese();
+        assert!(matches!(table.parse("/保存 作業中"), Command::Save { name } if name == "作業中"));
+        assert!(matches!(table.parse("/履歴"), Command::History { all: false }));
+        assert!(matches!(table.parse("/save draft"), Command::Save { name } if name == "draft"));
+        assert!(matches!(table.parse("/h"), Command::Help));
+        assert!(matches!(table.parse("こんにちは"), Command::Message(_)));
+        // 英語のロケールでは日本語の別名はスキル扱い
+        assert!(matches!(CommandTable::english().parse("/保存 x"), Command::Skill { .. }));
+    }
+
+    #[test]
+    fn test_help_in_each_locale() {
+        let english = CommandTable::english().help_text();
+        assert!(english.contains("\nCommands:\n"));
+        assert!(english.contains("  /help, /h, /?   - Show this help message\n"));
+        assert!(english.contains("  /status --env   - Show versions of tools used in this session\n"));
+        assert!(english.contains("  /dryrun, /dry-run [on|off|report] - Simulate changes"));
+        assert!(english.contains("  /<skill-name>   - Run a skill\n"));
+        assert!(!english.contains("保存"));
+
+        let japanese = japanese().help_text();
+        assert!(japanese.contains("コマンド:"));
+        assert!(japanese.contains("  /save, /保存 <name> - 現在の会話を保存\n"), "{}", japanese);
+        assert!(japanese.contains("/history, /hist, /履歴"));
+        assert!(japanese.contains("  /status --env   - "));
+    }
+
+    #[test]
+    fn test_user_file_overrides_builtin() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("ja.toml"),
+            "[commands.save]\naliases = [\"セーブ\"]\nsummary = \"保存する\"\n",
+        )
+        .unwrap();
+        let table = CommandTable::load(Locale::Ja, Some(dir.path()), &[]).unwrap();
+        assert_eq!(table.resolve("セーブ"), Some("save"));
+        assert_eq!(table.resolve("保存"), None);
+        // 上書きしていない別名は残る
+        assert_eq!(table.resolve("履歴"), Some("history"));
+        assert!(table.help_text().contains("/save, /セーブ <name> - 保存する"));
+    }
+
+    #[test]
+    fn test_collisions_fail_loudly() {
+        let builtin = CommandTable::from_sources(Locale::Ja, &source("[commands.save]\naliases = [\"load\"]\n"), &[]);
+        assert_eq!(
+            builtin.unwrap_err().to_string(),
+            "Localized alias /load for /save collides with the built-in command /load"
+        );
+
+        let skill = CommandTable::load(Locale::Ja, None, &["保存".to_string()]);
+        assert_eq!(
+            skill.unwrap_err().to_string(),
+            "Localized alias /保存 for /save collides with the skill /保存"
+        );
+
+        let twice = CommandTable::from_sources(
+            Locale::Ja,
+            &source("[commands.save]\naliases = [\"記録\"]\n[commands.load]\naliases = [\"記録\"]\n"),
+            &[],
+        );
+        assert!(twice.unwrap_err().to_string().contains("is used for both /save and /load"));
+
+        let unknown = CommandTable::from_sources(Locale::Ja, &source("[commands.nope]\nsummary = \"x\"\n"), &[]);
+        assert!(unknown.unwrap_err().to_string().contains("Unknown command 'nope'"));
+    }
+
+    #[test]
+    fn test_localized_commands_for_completion() {
+        let commands = japanese().localized_commands();
+        assert!(commands.contains(&"/保存".to_string()));
+        assert!(commands.iter().all(|c| !c.is_ascii()));
+        assert!(CommandTable::english().localized_commands().is_empty());
+    }
+}
@@ -0,0 +1,506 @@
+//! アシスタントの応答の Markdown 表示
+//!
+//! 見出し・太字・リスト・インラインコードを端末の装飾に変え、フェンスで囲んだコードは
+//! syntect で言語ごとに色付けする。折り返しは表示幅で行い、装飾は区間ごとに閉じるため
+//! エスケープシーケンスの途中で改行することはない。
+//! `LOCAL_CODE_NO_MARKDOWN` が設定されているか、色付けが無効なら使わない。
+
+use std::sync::OnceLock;
+
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{FontStyle, Theme, ThemeSet};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+use unicode_width::UnicodeWidthChar;
+
+/// 設定すると Markdown を解釈せずにそのまま表示する
+pub const NO_MARKDOWN_ENV: &str = "LOCAL_CODE_NO_MARKDOWN";
+
+/// コードの色付けに使うテーマ（syntect の既定テーマ）
+const CODE_THEME: &str = "base16-ocean.dark";
+
+/// これより狭い幅は指定されても使わない
+const MIN_WIDTH: usize = 20;
+
+const RESET: &str = "\x1b[0m";
+
+/// Markdown として表示するか
+pub fn markdown_enabled() -> bool {
+    std::env::var_os(NO_MARKDOWN_ENV).is_none() && super::color::enabled()
+}
+
+/// 文字色
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fg {
+    /// 標準の16色（30〜37, 90〜97）
+    Ansi(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// 区間の装飾
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Style {
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    dim: bool,
+    fg: Option<Fg>,
+}
+
+impl Style {
+    const PLAIN: Style = Style { bold: false, italic: false, underline: false, dim: false, fg: None };
+    const DIM: Style = Style { dim: true, ..Style::PLAIN };
+    const CODE: Style = Style { fg: Some(Fg::Ansi(33)), ..Style::PLAIN };
+    const LINK: Style = Style { underline: true, fg: Some(Fg::Ansi(34)), ..Style::PLAIN };
+
+    fn heading(level: usize) -> Style {
+        match level {
+            1 => Style { bold: true, underline: true, fg: Some(Fg::Ansi(36)), ..Style::PLAIN },
+            2 => Style { bold: true, fg: Some(Fg::Ansi(36)), ..Style::PLAIN },
+            _ => Style { bold: true, ..Style::PLAIN },
+        }
+    }
+
+    /// SGR シーケンス（装飾なしなら空）
+    fn sgr(&self) -> String {
+        let mut codes: Vec<String> = Vec::new();
+        if self.bold {
+            codes.push("1".into());
+        }
+        if self.dim {
+            codes.push("2".into());
+        }
+        if self.italic {
+            codes.push("3".into());
+        }
+        if self.underline {
+            codes.push("4".into());
+        }
+        match self.fg {
+            Some(Fg::Ansi(code)) => codes.push(code.to_string()),
+            Some(Fg::Rgb(r, g, b)) => codes.push(format!("38;2;{};{};{}", r, g, b)),
+            None => {}
+        }
+        if codes.is_empty() {
+            String::new()
+        } else {
+            format!("\x1b[{}m", codes.join(";"))
+        }
+    }
+}
+
+/// 同じ装飾の文字列
+#[derive(Debug, Clone, PartialEq)]
+struct Span {
+    text: String,
+    style: Style,
+}
+
+impl Span {
+    fn new(text: impl Into<String>, style: Style) -> Self {
+        Self { text: text.into(), style }
+    }
+}
+
+/// Markdown を端末向けの文字列に変換する
+#[derive(Debug, Clone)]
+pub struct MarkdownRenderer {
+    width: usize,
+}
+
+impl MarkdownRenderer {
+    /// 折り返す幅（端末の桁数）を指定して作る
+    pub fn new(width: usize) -> Self {
+        Self { width: width.max(MIN_WIDTH) }
+    }
+
+    /// 変換した文字列（末尾の改行なし）
+    pub fn render(&self, text: &str) -> String {
+        let mut out: Vec<String> = Vec::new();
+        let mut lines = text.lines();
+        while let Some(line) = lines.next() {
+            let trimmed = line.trim_start();
+            if let Some(lang) = trimmed.strip_prefix("```") {
+                let code: Vec<&str> = lines.by_ref().take_while(|l| !l.trim_start().starts_with("```")).collect();
+                self.render_code(lang.trim(), &code, &mut out);
+            } else if let Some((level, title)) = heading(trimmed) {
+                out.extend(self.wrap(&inline(title, Style::heading(level)), &[], 0));
+            } else if is_rule(trimmed) {
+                out.push(render_line(&[Span::new("─".repeat(self.width), Style::DIM)]));
+            } else if let Some((indent, marker, rest)) = list_item(line) {
+                let prefix = vec![Span::new(format!("{}{} ", " ".repeat(indent), marker), Style::PLAIN)];
+                let hang = indent + marker.chars().count() + 1;
+                out.extend(self.wrap(&inline(rest, Style::PLAIN), &prefix, hang));
+            } else if let Some(quote) = trimmed.strip_prefix('>') {
+                let prefix = vec![Span::new("│ ", Style::DIM)];
+                let spans = inline(quote.trim_start(), Style { italic: true, ..Style::PLAIN });
+                out.extend(self.wrap(&spans, &prefix, 2).into_iter().enumerate().map(|(i, l)| {
+                    // 折り返した行にも引用の印を付ける
+                    if i == 0 { l } else { format!("{}{}", render_line(&prefix), &l[2..]) }
+                }));
+            } else if trimmed.is_empty() {
+                out.push(String::new());
+            } else {
+                out.extend(self.wrap(&inline(line, Style::PLAIN), &[], 0));
+            }
+        }
+        out.join("\n")
+    }
+
+    /// フェンスで囲んだコード（言語が分かれば色付け）
+    fn render_code(&self, lang: &str, code: &[&str], out: &mut Vec<String>) {
+        let header = if lang.is_empty() { "╭─".to_string() } else { format!("╭─ {}", lang) };
+        out.push(render_line(&[Span::new(header, Style::DIM)]));
+        let gutter = Span::new("│ ", Style::DIM);
+        let lines = highlight(lang, code).unwrap_or_else(|| {
+            code.iter().map(|l| vec![Span::new(*l, Style::PLAIN)]).collect()
+        });
+        let inner = self.width.saturating_sub(2).max(1);
+        for spans in lines {
+            for chunk in split_at_width(&spans, inner) {
+                let mut line = vec![gutter.clone()];
+                line.extend(chunk);
+                out.push(render_line(&line));
+            }
+        }
+        out.push(render_line(&[Span::new("╰─", Style::DIM)]));
+    }
+
+    /// 単語の区切りで折り返す（prefix は最初の行だけ、2行目以降は hang 桁の字下げ）
+    fn wrap(&self, spans: &[Span], prefix: &[Span], hang: usize) -> Vec<String> {
+        let mut lines: Vec<Vec<Span>> = Vec::new();
+        let mut current: Vec<Span> = prefix.to_vec();
+        let mut used: usize = prefix.iter().map(|s| text_width(&s.text)).sum();
+        let mut at_start = true;
+
+        for (word, style) in words(spans) {
+            let width = text_width(&word);
+            let is_space = word.trim().is_empty();
+            if used + width > self.width && !at_start {
+                trim_trailing_spaces(&mut current);
+                lines.push(std::mem::take(&mut current));
+                current.push(Span::new(" ".repeat(hang), Style::PLAIN));
+                used = hang;
+                at_start = true;
+                if is_space {
+                    continue;
+                }
+            }
+            if used + width > self.width {
+                // 1語が1行に収まらない場合は文字で切る
+                let available = self.width.saturating_sub(used).max(1);
+                let mut chunks = split_at_width(&[Span::new(word, style)], available).into_iter();
+                if let Some(first) = chunks.next() {
+                    current.extend(first);
+                }
+                for chunk in chunks {
+                    lines.push(std::mem::take(&mut current));
+                    current.push(Span::new(" ".repeat(hang), Style::PLAIN));
+                    current.extend(chunk);
+                }
+                used = current.iter().map(|s| text_width(&s.text)).sum();
+                at_start = false;
+                continue;
+            }
+            push_merged(&mut current, Span::new(word, style));
+            used += width;
+            at_start = false;
+        }
+        lines.push(current);
+        lines.iter().map(|l| render_line(l)).collect()
+    }
+}
+
+/// 装飾付きの区間を連結（区間ごとにリセットする）
+fn render_line(spans: &[Span]) -> String {
+    let mut out = String::new();
+    for span in spans.iter().filter(|s| !s.text.is_empty()) {
+        let sgr = span.style.sgr();
+        if sgr.is_empty() {
+            out.push_str(&span.text);
+        } else {
+            out.push_str(&sgr);
+            out.push_str(&span.text);
+            out.push_str(RESET);
+        }
+    }
+    out.trim_end_matches(' ').to_string()
+}
+
+/// 折り返す行の末尾の空白を除く（装飾付きの空白が残らないように）
+fn trim_trailing_spaces(line: &mut Vec<Span>) {
+    while let Some(last) = line.last_mut() {
+        let trimmed = last.text.trim_end_matches(' ').len();
+        last.text.truncate(trimmed);
+        if !last.text.is_empty() {
+            break;
+        }
+        line.pop();
+    }
+}
+
+/// 直前と同じ装飾ならつなげる
+fn push_merged(line: &mut Vec<Span>, span: Span) {
+    match line.last_mut() {
+        Some(last) if last.style == span.style => last.text.push_str(&span.text),
+        _ => line.push(span),
+    }
+}
+
+/// 語と空白に分ける（装飾は保つ）
+fn words(spans: &[Span]) -> Vec<(String, Style)> {
+    let mut out = Vec::new();
+    for span in spans {
+        let mut word = String::new();
+        let mut in_space = None;
+        for c in span.text.chars() {
+            let space = c == ' ';
+            if in_space.is_some_and(|s| s != space) {
+                out.push((std::mem::take(&mut word), span.style));
+            }
+            in_space = Some(space);
+            word.push(c);
+        }
+        if !word.is_empty() {
+            out.push((word, span.style));
+        }
+    }
+    out
+}
+
+/// 表示幅 width ごとに区間を切る（コードと長すぎる語用）
+fn split_at_width(spans: &[Span], width: usize) -> Vec<Vec<Span>> {
+    let mut lines: Vec<Vec<Span>> = vec![Vec::new()];
+    let mut used = 0;
+    for span in spans {
+        for c in span.text.chars() {
+            let w = c.width().unwrap_or(0);
+            if used + w > width && used > 0 {
+                lines.push(Vec::new());
+                used = 0;
+            }
+            push_merged(lines.last_mut().unwrap(), Span::new(c.to_string(), span.style));
+            used += w;
+        }
+    }
+    lines
+}
+
+fn text_width(text: &str) -> usize {
+    text.chars().map(|c| c.width().unwrap_or(0)).sum()
+}
+
+/// `# 見出し` のレベルと本文
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    line[level..].strip_prefix(' ').map(|rest| (level, rest.trim()))
+}
+
+/// `---`・`***`・`___` の区切り線
+fn is_rule(line: &str) -> bool {
+    let compact: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    compact.len() >= 3
+        && ['-', '*', '_'].iter().any(|&m| compact.chars().all(|c| c == m))
+}
+
+/// リスト項目の字下げ・表示する印・本文（入れ子は2桁ずつ字下げ）
+fn list_item(line: &str) -> Option<(usize, String, &str)> {
+    let spaces = line.len() - line.trim_start_matches(' ').len();
+    let rest = &line[spaces..];
+    let indent = (spaces / 2) * 2 + 2;
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(text) = rest.strip_prefix(bullet) {
+            return Some((indent, "•".to_string(), text));
+        }
+    }
+    let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 && digits <= 3 {
+        let after = &rest[digits..];
+        for sep in [". ", ") "] {
+            if let Some(text) = after.strip_prefix(sep) {
+                return Some((indent, format!("{}.", &rest[..digits]), text));
+            }
+        }
+    }
+    None
+}
+
+/// インラインの装飾（`code`、**太字**、*斜体*、[リンク](url)）
+fn inline(text: &str, base: Style) -> Vec<Span> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut spans: Vec<Span> = Vec::new();
+    let mut buf = String::new();
+    let (mut bold, mut italic) = (false, false);
+    let current = |bold: bool, italic: bool| Style { bold: base.bold || bold, italic: base.italic || italic, ..base };
+    let find = |from: usize, pattern: &[char]| {
+        (from..chars.len()).find(|&j| chars[j..].starts_with(pattern))
+    };
+
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let flush = |buf: &mut String, spans: &mut Vec<Span>, style: Style| {
+            if !buf.is_empty() {
+                push_merged(spans, Span::new(std::mem::take(buf), style));
+            }
+        };
+        if c == '`' {
+            if let Some(end) = find(i + 1, &['`']) {
+                flush(&mut buf, &mut spans, current(bold, italic));
+                let code: String = chars[i + 1..end].iter().collect();
+                spans.push(Span::new(code, Style { bold: base.bold, ..Style::CODE }));
+                i = end + 1;
+                continue;
+            }
+        } else if chars[i..].starts_with(&['*', '*']) && (bold || find(i + 2, &['*', '*']).is_some()) {
+            flush(&mut buf, &mut spans, current(bold, italic));
+            bold = !bold;
+            i += 2;
+            continue;
+        } else if c == '*'
+            && (italic || (chars.get(i + 1).is_some_and(|n| !n.is_whitespace()) && find(i + 1, &['*']).is_some()))
+        {
+            flush(&mut buf, &mut spans, current(bold, italic));
+            italic = !italic;
+            i += 1;
+            continue;
+        } else if c == '[' {
+            if let Some(mid) = find(i + 1, &[']', '(']) {
+                if let Some(end) = find(mid + 2, &[')']) {
+                    flush(&mut buf, &mut spans, current(bold, italic));
+                    let label: String = chars[i + 1..mid].iter().collect();
+                    let url: String = chars[mid + 2..end].iter().collect();
+                    spans.push(Span::new(label, Style::LINK));
+                    spans.push(Span::new(format!(" ({})", url), Style::DIM));
+                    i = end + 1;
+                    continue;
+                }
+            }
+        }
+        buf.push(c);
+        i += 1;
+    }
+    if !buf.is_empty() {
+        push_merged(&mut spans, Span::new(buf, current(bold, italic)));
+    }
+    spans
+}
+
+/// syntect の構文定義とテーマ（初回だけ読み込む）
+fn assets() -> &'static (SyntaxSet, Theme) {
+    static ASSETS: OnceLock<(SyntaxSet, Theme)> = OnceLock::new();
+    ASSETS.get_or_init(|| {
+        let syntaxes = SyntaxSet::load_defaults_newlines();
+        let mut themes = ThemeSet::load_defaults();
+        let theme = themes.themes.remove(CODE_THEME).unwrap_or_default();
+        (syntaxes, theme)
+    })
+}
+
+/// コードを行ごとに色付け（知らない言語なら None）
+fn highlight(lang: &str, code: &[&str]) -> Option<Vec<Vec<Span>>> {
+    if lang.is_empty() {
+        return None;
+    }
+    let (syntaxes, theme) = assets();
+    let syntax = syntaxes.find_syntax_by_token(lang)?;
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    let text = code.join("\n") + "\n";
+    let mut lines = Vec::new();
+    for line in LinesWithEndings::from(&text) {
+        let ranges = highlighter.highlight_line(line, syntaxes).ok()?;
+        let mut spans = Vec::new();
+        for (style, piece) in ranges {
+            let piece = piece.trim_end_matches('\n');
+            if piece.is_empty() {
+                continue;
+            }
+            let fg = style.foreground;
+            let span_style = Style {
+                bold: style.font_style.contains(FontStyle::BOLD),
+                italic: style.font_style.contains(FontStyle::ITALIC),
+                fg: Some(Fg::Rgb(fg.r, fg.g, fg.b)),
+                ..Style::PLAIN
+            };
+            push_merged(&mut spans, Span::new(piece, span_style));
+        }
+        lines.push(spans);
+    }
+    Some(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = include_str!("../../tests/fixtures/markdown/sample.md");
+    const SAMPLE_ANSI: &str = include_str!("../../tests/fixtures/markdown/sample.ansi");
+
+    /// エスケープシーケンスを除いた文字列
+    fn strip_ansi(text: &str) -> String {
+        let mut out = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                for c in chars.by_ref() {
+                    if c == 'm' {
+                        break;
+                    }
+                }
+            } else {
+                out.push(c);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_sample_document_snapshot() {
+        let rendered = MarkdownRenderer::new(60).render(SAMPLE);
+        assert_eq!(rendered, SAMPLE_ANSI.trim_end_matches('\n'), "rendered:\n{}", rendered);
+    }
+
+    #[test]
+    fn test_inline_styles() {
+        let rendered = MarkdownRenderer::new(80).render("Use **cargo** and `fmt`, see [docs](https://x.dev) *now*");
+        assert_eq!(
+            rendered,
+            "Use \x1b[1mcargo\x1b[0m and \x1b[33mfmt\x1b[0m, see \x1b[4;34mdocs\x1b[0m\x1b[2m (https://x.dev)\x1b[0m \x1b[3mnow\x1b[0m"
+        );
+        // 閉じていない記号はそのまま
+        assert_eq!(MarkdownRenderer::new(80).render("2 * 3 = 6 and `x"), "2 * 3 = 6 and `x");
+    }
+
+    #[test]
+    fn test_wrapped_lines_fit_and_close_their_styles() {
+        let text = "- **a bold phrase that keeps going well past the edge of a narrow terminal**";
+        let rendered = MarkdownRenderer::new(24).render(text);
+        for line in rendered.lines() {
+            assert!(text_width(&strip_ansi(line)) <= 24, "{:?}", line);
+            // 行ごとに装飾を閉じる
+            assert_eq!(line.matches("\x1b[1m").count(), line.matches(RESET).count(), "{:?}", line);
+        }
+        let plain: Vec<String> = rendered.lines().map(strip_ansi).collect();
+        assert!(plain[0].starts_with("  • a bold"));
+        assert!(plain[1].starts_with("    "), "{:?}", plain);
+    }
+
+    #[test]
+    fn test_code_is_highlighted_and_wrapped() {
+        let long = format!("let s = \"{}\";", "x".repeat(60));
+        let rendered = MarkdownRenderer::new(40).render(&format!("```rust\nfn main() {{}}\n{}\n```", long));
+        assert!(rendered.contains("\x1b[38;2;"), "{}", rendered);
+        let plain: Vec<String> = rendered.lines().map(strip_ansi).collect();
+        assert_eq!(plain[0], "╭─ rust");
+        assert_eq!(plain[1], "│ fn main() {}");
+        assert!(plain.iter().all(|l| text_width(l) <= 40));
+        assert_eq!(plain.last().unwrap(), "╰─");
+
+        // 知らない言語は色付けしない
+        let rendered = MarkdownRenderer::new(40).render("```nosuchlang\nplain text\n```");
+        assert!(rendered.contains("\x1b[2m│ \x1b[0mplain text"), "{:?}", rendered);
+    }
+}
@@ -5,21 +5,39 @@ pub mod spinner;
 pub mod completion;
 pub mod confirm;
 pub mod ui;
+pub mod layout;
+pub mod status;
+pub mod broadcast;
+pub mod watch;
+pub mod prompt_lint;
+pub mod print_mode;
+pub mod inputs;
+pub mod preprocess;
+pub mod line_buffer;
+pub mod locale;
+pub mod markdown;
+pub mod color;
+pub mod activity;
 
 pub use repl::Repl;
-pub use commands::{Command, CommandHandler, CommandResult};
+pub use commands::{Command, CommandHandler, CommandResult, ConfigAction, DryRunAction};
 pub use output::{
     print_error, print_success, print_tool, print_mode, print_info, print_banner,
     print_startup_banner,
     StreamingWriter, print_streaming_start, print_streaming_text,
     print_streaming_end, print_streaming_end_with_stats,
-    OutputPostProcessor,
+    OutputPostProcessor, ThoughtFilter,
 };
 pub use spinner::Spinner;
+pub use activity::Activity;
+pub use prompt_lint::PromptLinter;
+pub use preprocess::InputPreprocessor;
+pub use locale::{CommandTable, Locale};
+pub use status::print_status;
 pub use completion::{Completer, CompletionResult};
-pub use confirm::{ConfirmDialog, ConfirmResult, confirm, confirm_tool_execution, requires_confirmation};
+pub use confirm::{ConfirmDialog, ConfirmResult, DialogConfirmation, confirm, confirm_tool_execution, prompt_passphrase, requires_confirmation, ask_send_or_edit, SendChoice};
 pub use ui::{
-    Ui, StatusLine,
-    print_separator, print_formatted_block, print_processing,
+    Ui, StatusLine, PagingMode, configure_pager,
+    print_separator, print_formatted_block, print_dim_block, print_processing,
     print_error as ui_print_error, print_info as ui_print_info,
 };
@@ -6,7 +6,13 @@
 use std::io::{self, Write};
 use crossterm::{
     execute,
-    style::{Color, Print, ResetColor, SetForegroundColor, Attribute, SetAttribute},
+    style::{Attribute, Color, Print, Stylize},
+};
+
+use super::broadcast::{self, OutputEvent, StreamEndStats};
+use super::color::{ResetColor, SetAttribute, SetForegroundColor};
+use super::layout::{
+    code_block_inner_width, compact_banner, terminal_width, truncate_with_ellipsis, wrap_to_width,
 };
 
 /// Unicodeアイコンとフォールバック文字
@@ -55,7 +61,7 @@ impl Icons {
             return false;
         }
         // TERM環境変数をチェック
-        std::env::var("TERM").map_or(false, |term| {
+        std::env::var("TERM").is_ok_and(|term| {
             !term.contains("dumb") && !term.contains("linux")
         })
     }
@@ -123,6 +129,7 @@ pub fn print_error_message(msg: &str) {
 
 /// エラーメッセージを赤色で出力 (レガシー互換)
 pub fn print_error(msg: &str) {
+    broadcast::emit(|| OutputEvent::Error { text: msg.to_string() });
     let mut stdout = io::stdout();
     let _ = execute!(
         stdout,
@@ -135,6 +142,7 @@ pub fn print_error(msg: &str) {
 
 /// 成功メッセージを緑色で出力
 pub fn print_success(msg: &str) {
+    broadcast::emit(|| OutputEvent::Success { text: msg.to_string() });
     let mut stdout = io::stdout();
     let _ = execute!(
         stdout,
@@ -147,6 +155,7 @@ pub fn print_success(msg: &str) {
 
 /// ツール実行メッセージを出力（ツール名=シアン、メッセージ=デフォルト）
 pub fn print_tool(name: &str, msg: &str) {
+    broadcast::emit(|| OutputEvent::Tool { name: name.to_string(), text: msg.to_string() });
     let mut stdout = io::stdout();
     let _ = execute!(
         stdout,
@@ -160,6 +169,7 @@ pub fn print_tool(name: &str, msg: &str) {
 
 /// モード表示を黄色で出力
 pub fn print_mode(mode: &str) {
+    broadcast::emit(|| OutputEvent::Mode { mode: mode.to_string() });
     let mut stdout = io::stdout();
     let _ = execute!(
         stdout,
@@ -172,6 +182,7 @@ pub fn print_mode(mode: &str) {
 
 /// 情報メッセージを青色で出力
 pub fn print_info(msg: &str) {
+    broadcast::emit(|| OutputEvent::Info { text: msg.to_string() });
     let mut stdout = io::stdout();
     let _ = execute!(
         stdout,
@@ -182,6 +193,27 @@ pub fn print_info(msg: &str) {
     let _ = stdout.flush();
 }
 
+/// デバッグ情報を暗い色で出力
+pub fn print_debug(msg: &str) {
+    broadcast::emit(|| OutputEvent::Debug { text: msg.to_string() });
+    let mut stdout = io::stdout();
+    let _ = execute!(
+        stdout,
+        SetForegroundColor(Color::DarkGrey),
+        Print(format!("[debug] {}\n", msg)),
+        ResetColor
+    );
+    let _ = stdout.flush();
+}
+
+/// 空行を出力
+pub fn print_newline() {
+    broadcast::emit(|| OutputEvent::Newline);
+    let mut stdout = io::stdout();
+    let _ = execute!(stdout, Print("\n"));
+    let _ = stdout.flush();
+}
+
 /// 起動時のバナーを表示
 pub fn print_banner(version: &str, mode: &str, model: &str, project: &str, skills: usize) {
     let mut stdout = io::stdout();
@@ -211,6 +243,21 @@ pub fn print_banner(version: &str, mode: &str, model: &str, project: &str, skill
 /// Claude Code風の起動バナーを表示
 pub fn print_startup_banner(version: &str, model: &str, project: &str, commands: &[String]) {
     let mut stdout = io::stdout();
+    let width = terminal_width();
+
+    // 狭い端末ではロゴが折り返して崩れるため1行で表示
+    if let Some(line) = compact_banner(version, model, width) {
+        let _ = execute!(
+            stdout,
+            SetForegroundColor(Color::Cyan),
+            SetAttribute(Attribute::Bold),
+            Print(format!("{}\n\n", line)),
+            SetAttribute(Attribute::Reset),
+            ResetColor
+        );
+        let _ = stdout.flush();
+        return;
+    }
 
     // ASCIIアートロゴ（LOCAL）
     const LOGO: &[&str] = &[
@@ -254,11 +301,16 @@ pub fn print_startup_banner(version: &str, model: &str, project: &str, commands:
 
     // モデル・プロジェクト情報（右寄せ風に空白でインデント）
     let info_indent = " ".repeat(logo_width.saturating_sub(20));
+    let info_width = width.saturating_sub(info_indent.len() + 3);
     let _ = execute!(
         stdout,
         SetForegroundColor(Color::DarkGrey),
-        Print(format!("{}  {} · local\n", info_indent, model)),
-        Print(format!("{}  {}\n", info_indent, display_project)),
+        Print(format!(
+            "{}  {}\n",
+            info_indent,
+            truncate_with_ellipsis(&format!("{} · local", model), info_width)
+        )),
+        Print(format!("{}  {}\n", info_indent, truncate_with_ellipsis(&display_project, info_width))),
         ResetColor
     );
 
@@ -321,49 +373,19 @@ fn shorten_home_path(path: &str) -> String {
 pub struct OutputPostProcessor;
 
 impl OutputPostProcessor {
-    /// THOUGHTブロックを除去
+    /// THOUGHTブロック（`THOUGHT:` の段落と `<think>`/`<thought>` タグの中身）を除去
     pub fn remove_thought_blocks(content: &str) -> String {
-        let mut result = String::new();
-        let mut in_thought = false;
-        let mut skip_until_newline = false;
-
-        for line in content.lines() {
-            let trimmed = line.trim();
-
-            // THOUGHT: で始まる行をスキップ
-            if trimmed.starts_with("THOUGHT:") || trimmed.starts_with("**THOUGHT:**") {
-                skip_until_newline = true;
-                continue;
-            }
-
-            // 空行でTHOUGHTスキップをリセット
-            if skip_until_newline {
-                if trimmed.is_empty() {
-                    skip_until_newline = false;
-                }
-                continue;
-            }
-
-            // <thought>タグ内をスキップ
-            if trimmed.to_lowercase().starts_with("<thought>") {
-                in_thought = true;
-                continue;
-            }
-            if trimmed.to_lowercase().starts_with("</thought>") {
-                in_thought = false;
-                continue;
-            }
-            if in_thought {
-                continue;
-            }
-
-            result.push_str(line);
-            result.push('\n');
-        }
-
+        let mut filter = Self::stream_filter();
+        let mut result = filter.feed(content);
+        result.push_str(&filter.finish());
         result.trim().to_string()
     }
 
+    /// ストリーミング表示用の THOUGHT フィルタ（チャンクの境目で分かれたタグも扱う）
+    pub fn stream_filter() -> ThoughtFilter {
+        ThoughtFilter::new()
+    }
+
     /// コードブロックのみを抽出（説明文を除去）
     pub fn extract_code_only(content: &str) -> String {
         let blocks = detect_code_blocks(content);
@@ -384,6 +406,12 @@ impl OutputPostProcessor {
             .join("\n\n")
     }
 
+    /// 表示済みの応答と整形後の応答が空白の違い以上に異なるか（ストリーミング後に整形版を出し直すかの判断）
+    pub fn differs_materially(shown: &str, processed: &str) -> bool {
+        let normalize = |text: &str| text.split_whitespace().collect::<Vec<_>>().join(" ");
+        normalize(shown) != normalize(processed)
+    }
+
     /// 完全なポストプロセス（THOUGHT除去 + コードのみ抽出オプション）
     pub fn process(content: &str, code_only: bool) -> String {
         let cleaned = Self::remove_thought_blocks(content);
@@ -395,6 +423,217 @@ impl OutputPostProcessor {
     }
 }
 
+/// 行頭に来ると段落の終わり（空行）まで思考として扱う目印
+const THOUGHT_MARKERS: [&str; 2] = ["THOUGHT:", "**THOUGHT:**"];
+
+/// 思考を囲むタグ（開きタグと閉じタグ、大文字小文字は区別しない）
+const THOUGHT_TAGS: [(&str, &str); 2] = [("<think>", "</think>"), ("<thought>", "</thought>")];
+
+/// 思考の代わりに表示する目印
+pub const THINKING_INDICATOR: &str = "(thinking…)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ThoughtState {
+    /// 通常のテキスト（line_start: 保留中のテキストが行頭から始まるか）
+    Text { line_start: bool },
+    /// `THOUGHT:` の段落の中
+    Paragraph { line_start: bool },
+    /// タグの中（閉じタグを待つ）
+    Tag { close: &'static str },
+}
+
+/// チャンクごとに THOUGHT を取り除くフィルタ
+///
+/// タグや `THOUGHT:` の途中でチャンクが切れた場合は、判定できるまで保留する
+#[derive(Debug)]
+pub struct ThoughtFilter {
+    state: ThoughtState,
+    /// まだ判定していないテキスト
+    pending: String,
+    /// 思考の直後の空白を読み飛ばす
+    skip_whitespace: bool,
+    /// 思考を取り除いた位置に出す目印
+    indicator: Option<String>,
+}
+
+impl ThoughtFilter {
+    fn new() -> Self {
+        Self {
+            state: ThoughtState::Text { line_start: true },
+            pending: String::new(),
+            skip_whitespace: false,
+            indicator: None,
+        }
+    }
+
+    /// 思考の代わりに "(thinking…)" を出す（colored なら暗く表示）
+    pub fn with_indicator(mut self, colored: bool) -> Self {
+        self.indicator = Some(if colored {
+            format!("{}\n", THINKING_INDICATOR.dim())
+        } else {
+            format!("{}\n", THINKING_INDICATOR)
+        });
+        self
+    }
+
+    /// チャンクを受け取り、表示してよい部分を返す
+    pub fn feed(&mut self, chunk: &str) -> String {
+        self.pending.push_str(chunk);
+        let mut out = String::new();
+        while self.step(&mut out) {}
+        out
+    }
+
+    /// ストリームの終わりに保留中のテキストを返す（思考の途中なら捨てる）
+    pub fn finish(&mut self) -> String {
+        let rest = std::mem::take(&mut self.pending);
+        match self.state {
+            ThoughtState::Text { .. } if !self.skip_whitespace => rest,
+            _ => String::new(),
+        }
+    }
+
+    /// 保留中のテキストを1段階処理する（続きを待つ必要があれば false）
+    fn step(&mut self, out: &mut String) -> bool {
+        if self.pending.is_empty() {
+            return false;
+        }
+        match self.state {
+            ThoughtState::Text { line_start } => self.step_text(line_start, out),
+            ThoughtState::Paragraph { line_start } => self.step_paragraph(line_start),
+            ThoughtState::Tag { close } => self.step_tag(close),
+        }
+    }
+
+    fn step_text(&mut self, line_start: bool, out: &mut String) -> bool {
+        if self.skip_whitespace {
+            let trimmed = self.pending.trim_start();
+            if trimmed.is_empty() {
+                self.pending.clear();
+                return false;
+            }
+            self.pending = trimmed.to_string();
+            self.skip_whitespace = false;
+        }
+
+        if line_start {
+            let indented = self.pending.trim_start_matches([' ', '\t']);
+            if indented.is_empty() {
+                return false;
+            }
+            if let Some(marker) = THOUGHT_MARKERS.iter().find(|m| indented.starts_with(*m)) {
+                let consumed = self.pending.len() - indented.len() + marker.len();
+                self.pending.drain(..consumed);
+                self.enter(ThoughtState::Paragraph { line_start: false }, out);
+                return true;
+            }
+            if THOUGHT_MARKERS.iter().any(|m| m.starts_with(indented)) {
+                return false;
+            }
+            self.state = ThoughtState::Text { line_start: false };
+        }
+
+        for (i, c) in self.pending.char_indices() {
+            match c {
+                '\n' => {
+                    out.push_str(&self.pending[..=i]);
+                    self.pending.drain(..=i);
+                    self.state = ThoughtState::Text { line_start: true };
+                    return true;
+                }
+                '<' => {
+                    let rest = &self.pending[i..];
+                    if let Some((open, close)) = THOUGHT_TAGS.iter().find(|(open, _)| starts_with_ignore_case(rest, open)) {
+                        out.push_str(&self.pending[..i]);
+                        self.pending.drain(..i + open.len());
+                        self.enter(ThoughtState::Tag { close }, out);
+                        return true;
+                    }
+                    if THOUGHT_TAGS.iter().any(|(open, _)| is_partial_match(rest, open)) {
+                        out.push_str(&self.pending[..i]);
+                        self.pending.drain(..i);
+                        return false;
+                    }
+                }
+                _ => {}
+            }
+        }
+        out.push_str(&self.pending);
+        self.pending.clear();
+        false
+    }
+
+    fn step_paragraph(&mut self, line_start: bool) -> bool {
+        match self.pending.find('\n') {
+            Some(i) => {
+                let blank = line_start && self.pending[..i].trim().is_empty();
+                self.pending.drain(..=i);
+                self.state = if blank {
+                    self.skip_whitespace = true;
+                    ThoughtState::Text { line_start: true }
+                } else {
+                    ThoughtState::Paragraph { line_start: true }
+                };
+                true
+            }
+            None => {
+                // 空行かどうかまだ分からない
+                if !(line_start && self.pending.trim().is_empty()) {
+                    self.pending.clear();
+                    self.state = ThoughtState::Paragraph { line_start: false };
+                }
+                false
+            }
+        }
+    }
+
+    fn step_tag(&mut self, close: &'static str) -> bool {
+        let found = self
+            .pending
+            .char_indices()
+            .find(|(i, _)| starts_with_ignore_case(&self.pending[*i..], close));
+        match found {
+            Some((i, _)) => {
+                self.pending.drain(..i + close.len());
+                self.state = ThoughtState::Text { line_start: true };
+                self.skip_whitespace = true;
+                true
+            }
+            None => {
+                // 閉じタグの途中かもしれない末尾だけ残す
+                let keep = self
+                    .pending
+                    .char_indices()
+                    .map(|(i, _)| i)
+                    .find(|&i| is_partial_match(&self.pending[i..], close))
+                    .unwrap_or(self.pending.len());
+                self.pending.drain(..keep);
+                false
+            }
+        }
+    }
+
+    fn enter(&mut self, state: ThoughtState, out: &mut String) {
+        self.state = state;
+        if let Some(indicator) = &self.indicator {
+            if !out.is_empty() && !out.ends_with('\n') {
+                out.push('\n');
+            }
+            out.push_str(indicator);
+        }
+    }
+}
+
+/// text が tag で始まるか（ASCII の大文字小文字は区別しない）
+fn starts_with_ignore_case(text: &str, tag: &str) -> bool {
+    text.len() >= tag.len() && text.as_bytes()[..tag.len()].eq_ignore_ascii_case(tag.as_bytes())
+}
+
+/// text 全体が tag の途中までと一致するか（続きのチャンクで tag になりうる）
+fn is_partial_match(text: &str, tag: &str) -> bool {
+    text.len() < tag.len() && tag.as_bytes()[..text.len()].eq_ignore_ascii_case(text.as_bytes())
+}
+
 /// コードブロック情報
 #[derive(Debug, Clone)]
 pub struct CodeBlock {
@@ -413,9 +652,9 @@ pub fn detect_code_blocks(content: &str) -> Vec<CodeBlock> {
 
     while i < lines.len() {
         let line = lines[i].trim();
-        if line.starts_with("```") {
-            let language = if line.len() > 3 {
-                Some(line[3..].trim().to_string())
+        if let Some(rest) = line.strip_prefix("```") {
+            let language = if !rest.is_empty() {
+                Some(rest.trim().to_string())
             } else {
                 None
             };
@@ -449,12 +688,12 @@ pub fn print_code_block(block: &CodeBlock) {
     let mut stdout = io::stdout();
     let lines: Vec<&str> = block.code.lines().collect();
 
-    // 最大幅を計算
-    let max_width = lines.iter()
+    // 最大幅を計算（端末幅を超えない）
+    let longest = lines.iter()
         .map(|l| l.chars().count())
         .max()
-        .unwrap_or(0)
-        .max(40);
+        .unwrap_or(0);
+    let max_width = code_block_inner_width(longest, terminal_width());
 
     let border = "─".repeat(max_width + 2);
 
@@ -466,10 +705,11 @@ pub fn print_code_block(block: &CodeBlock) {
 
     if let Some(lang) = &block.language {
         let lang_display = format!("─[ {} ]", lang);
-        let remaining = max_width + 2 - lang_display.chars().count();
+        let lang_display = truncate_with_ellipsis(&lang_display, max_width + 2);
+        let remaining = (max_width + 2).saturating_sub(lang_display.chars().count());
         let _ = execute!(
             stdout,
-            Print(format!("╭{}{}\n", lang_display, "─".repeat(remaining.max(0))))
+            Print(format!("╭{}{}\n", lang_display, "─".repeat(remaining)))
         );
     } else {
         let _ = execute!(
@@ -480,7 +720,7 @@ pub fn print_code_block(block: &CodeBlock) {
 
     // コード内容
     let _ = execute!(stdout, SetForegroundColor(Color::White));
-    for line in &lines {
+    for line in lines.iter().flat_map(|l| wrap_to_width(l, max_width)) {
         let _ = execute!(
             stdout,
             SetForegroundColor(Color::DarkGrey),
@@ -514,6 +754,7 @@ pub fn print_formatted_block(title: &str, content: &str) {
         "ASSISTANT" => (Color::Green, Icons::assistant()),
         "TOOL" => (Color::Cyan, Icons::tool()),
         "ERROR" => (Color::Red, Icons::error()),
+        "WARNING" => (Color::Yellow, Icons::error()),
         "INFO" => (Color::Blue, Icons::info()),
         "SKILL" => (Color::Magenta, Icons::tool()),
         _ => (Color::White, ""),
@@ -566,6 +807,8 @@ pub struct StreamingWriter {
     stdout: io::Stdout,
     color: Option<Color>,
     buffer: String,
+    /// 統計行に添える注記
+    note: Option<String>,
 }
 
 impl StreamingWriter {
@@ -575,6 +818,7 @@ impl StreamingWriter {
             stdout: io::stdout(),
             color: None,
             buffer: String::new(),
+            note: None,
         }
     }
 
@@ -584,11 +828,13 @@ impl StreamingWriter {
             stdout: io::stdout(),
             color: Some(color),
             buffer: String::new(),
+            note: None,
         }
     }
 
     /// ストリーミング開始（プレフィックスを表示）
     pub fn start(&mut self, prefix: Option<&str>) {
+        broadcast::emit(|| OutputEvent::StreamStart { prefix: prefix.map(str::to_string) });
         if let Some(p) = prefix {
             let _ = execute!(
                 self.stdout,
@@ -607,6 +853,7 @@ impl StreamingWriter {
 
     /// 文字単位で出力（フラッシュあり）
     pub fn write_char(&mut self, c: char) {
+        broadcast::emit(|| OutputEvent::StreamText { text: c.to_string() });
         self.buffer.push(c);
         print!("{}", c);
         let _ = self.stdout.flush();
@@ -614,6 +861,7 @@ impl StreamingWriter {
 
     /// テキストを出力（フラッシュあり）
     pub fn write(&mut self, text: &str) {
+        broadcast::emit(|| OutputEvent::StreamText { text: text.to_string() });
         self.buffer.push_str(text);
         print!("{}", text);
         let _ = self.stdout.flush();
@@ -621,12 +869,14 @@ impl StreamingWriter {
 
     /// テキストを即座に出力（バッファリングなし）
     pub fn write_immediate(&mut self, text: &str) {
+        broadcast::emit(|| OutputEvent::StreamText { text: text.to_string() });
         print!("{}", text);
         let _ = self.stdout.flush();
     }
 
     /// ストリーミング終了
     pub fn finish(&mut self) {
+        broadcast::emit(|| OutputEvent::StreamEnd { stats: None });
         if self.color.is_some() {
             let _ = execute!(self.stdout, ResetColor);
         }
@@ -634,19 +884,42 @@ impl StreamingWriter {
         let _ = self.stdout.flush();
     }
 
+    /// 統計行に添える注記を設定
+    pub fn set_note(&mut self, note: Option<String>) {
+        self.note = note;
+    }
+
     /// 統計情報を表示して終了
-    pub fn finish_with_stats(&mut self, tokens_per_second: f64, total_tokens: u32) {
+    ///
+    /// `prompt_tokens` はサーバーが評価し直したプロンプトのトークン数（キャッシュが効くと小さくなる）
+    pub fn finish_with_stats(&mut self, tokens_per_second: f64, total_tokens: u32, prompt_tokens: u32) {
+        broadcast::emit(|| OutputEvent::StreamEnd {
+            stats: Some(StreamEndStats {
+                tokens_per_second,
+                total_tokens,
+                prompt_tokens,
+                note: self.note.clone(),
+            }),
+        });
         if self.color.is_some() {
             let _ = execute!(self.stdout, ResetColor);
         }
         println!(); // 改行
 
         // 統計情報を暗い色で表示
+        let note = self
+            .note
+            .as_deref()
+            .map(|n| format!(" · {}", n))
+            .unwrap_or_default();
         let _ = execute!(
             self.stdout,
             SetForegroundColor(Color::DarkGrey),
             SetAttribute(Attribute::Dim),
-            Print(format!("[{} tokens, {:.1} tok/s]\n", total_tokens, tokens_per_second)),
+            Print(format!(
+                "[{} tokens, {:.1} tok/s, {} prompt tokens evaluated{}]\n",
+                total_tokens, tokens_per_second, prompt_tokens, note
+            )),
             SetAttribute(Attribute::Reset),
             ResetColor
         );
@@ -672,6 +945,7 @@ impl Default for StreamingWriter {
 
 /// ストリーミング出力を開始（シンプルなAPI）
 pub fn print_streaming_start(prefix: Option<&str>) {
+    broadcast::emit(|| OutputEvent::StreamStart { prefix: prefix.map(str::to_string) });
     let mut stdout = io::stdout();
     if let Some(p) = prefix {
         let _ = execute!(
@@ -686,6 +960,7 @@ pub fn print_streaming_start(prefix: Option<&str>) {
 
 /// ストリーミングテキストを出力（即座にフラッシュ）
 pub fn print_streaming_text(text: &str) {
+    broadcast::emit(|| OutputEvent::StreamText { text: text.to_string() });
     let mut stdout = io::stdout();
     print!("{}", text);
     let _ = stdout.flush();
@@ -693,6 +968,7 @@ pub fn print_streaming_text(text: &str) {
 
 /// ストリーミング出力を終了
 pub fn print_streaming_end() {
+    broadcast::emit(|| OutputEvent::StreamEnd { stats: None });
     println!();
     let _ = io::stdout().flush();
 }
@@ -724,6 +1000,62 @@ mod tests {
         print_info("test info");
     }
 
+    #[test]
+    fn test_differs_materially_ignores_whitespace() {
+        assert!(!OutputPostProcessor::differs_materially("Hello  world\n\n", "Hello world"));
+        assert!(OutputPostProcessor::differs_materially(
+            "THOUGHT: plan it\n\nHello",
+            &OutputPostProcessor::process("THOUGHT: plan it\n\nHello", false)
+        ));
+    }
+
+    /// chunks を順に通した結果
+    fn filter_chunks(chunks: &[&str]) -> String {
+        let mut filter = OutputPostProcessor::stream_filter();
+        let mut out: String = chunks.iter().map(|c| filter.feed(c)).collect();
+        out.push_str(&filter.finish());
+        out
+    }
+
+    #[test]
+    fn test_stream_filter_handles_every_split_position() {
+        let cases = [
+            ("<think>plan it</think>\n\nHello <b>world</b>", "Hello <b>world</b>"),
+            ("Before <THOUGHT>hidden</Thought> after", "Before after"),
+            ("THOUGHT: plan it\nstill planning\n\nAnswer\n", "Answer\n"),
+            ("Intro\n  **THOUGHT:** hmm\n\nDone", "Intro\nDone"),
+            ("a < b and THOUGHT: stays", "a < b and THOUGHT: stays"),
+            ("<thin", "<thin"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(filter_chunks(&[input]), expected, "whole: {:?}", input);
+            for (i, _) in input.char_indices().skip(1) {
+                let (a, b) = input.split_at(i);
+                assert_eq!(filter_chunks(&[a, b]), expected, "split at {}: {:?}", i, input);
+            }
+            let chars: Vec<String> = input.chars().map(String::from).collect();
+            let chars: Vec<&str> = chars.iter().map(String::as_str).collect();
+            assert_eq!(filter_chunks(&chars), expected, "per char: {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_stream_filter_indicator() {
+        let mut filter = OutputPostProcessor::stream_filter().with_indicator(false);
+        let mut out = filter.feed("Let me see <think>a lot of");
+        out.push_str(&filter.feed(" reasoning</think> Sure."));
+        out.push_str(&filter.finish());
+        assert_eq!(out, "Let me see \n(thinking…)\nSure.");
+    }
+
+    #[test]
+    fn test_remove_thought_blocks() {
+        assert_eq!(
+            OutputPostProcessor::remove_thought_blocks("<think>\nplan\n</think>\n\nTHOUGHT: more\n\nAnswer"),
+            "Answer"
+        );
+    }
+
     #[test]
     fn test_streaming_writer() {
         let mut writer = StreamingWriter::new();
@@ -0,0 +1,202 @@
+//! 送信前の入力の前処理（`@path` のファイル添付）
+//!
+//! 「look at @src/agent/core.rs and explain process()」のように `@` で始まるパスを書くと、
+//! プロジェクトルートからの相対パスとしてファイルを読み、パスを付けたコードブロックとしてメッセージの後ろに添付する。
+//! 本文の `@path` はそのまま残す。
+
+use std::path::{Path, PathBuf};
+
+/// 1ファイルあたりの添付の上限（バイト）
+pub const DEFAULT_MAX_BYTES: usize = 32 * 1024;
+
+/// 添付した1ファイル
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    /// 入力に書かれたパス
+    pub path: String,
+    /// ファイルの大きさ（バイト）
+    pub size: usize,
+    /// 上限で切り詰めたか
+    pub truncated: bool,
+}
+
+/// 前処理の結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Preprocessed {
+    /// 送信するメッセージ（添付がなければ入力のまま）
+    pub message: String,
+    pub attachments: Vec<Attachment>,
+    /// 添付できなかった `@path` とその理由
+    pub skipped: Vec<(String, String)>,
+}
+
+/// `@path` を展開する前処理
+pub struct InputPreprocessor {
+    root: PathBuf,
+    max_bytes: usize,
+}
+
+impl InputPreprocessor {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            max_bytes: DEFAULT_MAX_BYTES,
+        }
+    }
+
+    /// 1ファイルあたりの上限を設定
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// 入力中の `@path` を読み、添付したメッセージを作る
+    pub fn process(&self, input: &str) -> Preprocessed {
+        let mut attachments = Vec::new();
+        let mut skipped = Vec::new();
+        let mut blocks = Vec::new();
+
+        for path in mentions(input) {
+            if attachments.iter().any(|a: &Attachment| a.path == path) || skipped.iter().any(|(p, _)| *p == path) {
+                continue;
+            }
+            match self.read(&path) {
+                Ok((content, size, truncated)) => {
+                    blocks.push(fenced(&path, &content, truncated.then_some(size)));
+                    attachments.push(Attachment { path, size, truncated });
+                }
+                Err(reason) => skipped.push((path, reason)),
+            }
+        }
+
+        let message = if blocks.is_empty() {
+            input.to_string()
+        } else {
+            format!("{}\n\n{}", input, blocks.join("\n\n"))
+        };
+        Preprocessed { message, attachments, skipped }
+    }
+
+    /// ファイルを読む（上限を超えたら文字境界で切る）。戻り値は内容・元の大きさ・切り詰めたか
+    fn read(&self, path: &str) -> Result<(String, usize, bool), String> {
+        let full = resolve(&self.root, path).ok_or_else(|| "outside the project".to_string())?;
+        if full.is_dir() {
+            return Err("is a directory".to_string());
+        }
+        let bytes = std::fs::read(&full).map_err(|_| "not found".to_string())?;
+        let content = String::from_utf8(bytes).map_err(|_| "not a text file".to_string())?;
+        let size = content.len();
+        if size <= self.max_bytes {
+            return Ok((content, size, false));
+        }
+        let mut end = self.max_bytes;
+        while !content.is_char_boundary(end) {
+            end -= 1;
+        }
+        Ok((content[..end].to_string(), size, true))
+    }
+}
+
+/// 入力中の `@path`（行頭か空白の直後の `@`）を出現順に返す。末尾の句読点は含めない
+pub fn mentions(input: &str) -> Vec<String> {
+    input
+        .split_whitespace()
+        .filter_map(|word| word.strip_prefix('@'))
+        .map(|path| path.trim_end_matches(|c: char| ",.;:!?)]}'\"`".contains(c)))
+        .filter(|path| !path.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// プロジェクトルートからの相対パスを解決する（ルートの外は None）
+fn resolve(root: &Path, path: &str) -> Option<PathBuf> {
+    let full = root.join(path);
+    let canonical = full.canonicalize().ok().unwrap_or(full);
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    canonical.starts_with(&root).then_some(canonical)
+}
+
+/// パスをラベルにしたコードブロック（内容にバッククォートの連続があればフェンスを長くする）
+fn fenced(path: &str, content: &str, truncated_from: Option<usize>) -> String {
+    let longest_run = content
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    let mut block = format!("{}{}\n{}", fence, path, content);
+    if !content.ends_with('\n') {
+        block.push('\n');
+    }
+    block.push_str(&fence);
+    if let Some(size) = truncated_from {
+        block.push_str(&format!(
+            "\n[{} truncated: showing the first {} of {} bytes]",
+            path,
+            content.len(),
+            size
+        ));
+    }
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src/agent")).unwrap();
+        std::fs::write(dir.path().join("src/agent/core.rs"), "fn process() {}\n").unwrap();
+        std::fs::write(dir.path().join("README.md"), "# Title\n```sh\nrun\n```\n").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_multiple_mentions_are_attached_in_order() {
+        let dir = project();
+        let pre = InputPreprocessor::new(dir.path());
+        let result = pre.process("compare @src/agent/core.rs with @README.md, and @src/agent/core.rs again");
+
+        let paths: Vec<&str> = result.attachments.iter().map(|a| a.path.as_str()).collect();
+        assert_eq!(paths, vec!["src/agent/core.rs", "README.md"]);
+        assert!(result.skipped.is_empty());
+        assert_eq!(
+            result.message,
+            "compare @src/agent/core.rs with @README.md, and @src/agent/core.rs again\n\n\
+             ```src/agent/core.rs\nfn process() {}\n```\n\n\
+             ````README.md\n# Title\n```sh\nrun\n```\n````"
+        );
+    }
+
+    #[test]
+    fn test_missing_file_is_left_untouched() {
+        let dir = project();
+        let pre = InputPreprocessor::new(dir.path());
+        let input = "explain @src/agent/missing.rs please, mail me at me@example.com";
+        let result = pre.process(input);
+
+        assert_eq!(result.message, input);
+        assert!(result.attachments.is_empty());
+        assert_eq!(result.skipped, vec![("src/agent/missing.rs".to_string(), "not found".to_string())]);
+
+        let outside = pre.process("read @../secret.txt and @src");
+        assert_eq!(outside.message, "read @../secret.txt and @src");
+        assert_eq!(outside.skipped[1], ("src".to_string(), "is a directory".to_string()));
+    }
+
+    #[test]
+    fn test_large_file_is_truncated() {
+        let dir = project();
+        std::fs::write(dir.path().join("big.txt"), "あ".repeat(100)).unwrap();
+        let pre = InputPreprocessor::new(dir.path()).with_max_bytes(10);
+        let result = pre.process("@big.txt");
+
+        assert_eq!(result.attachments, vec![Attachment { path: "big.txt".to_string(), size: 300, truncated: true }]);
+        // 文字の途中では切らない（3バイト文字3つ分）
+        assert_eq!(
+            result.message,
+            "@big.txt\n\n```big.txt\nあああ\n```\n[big.txt truncated: showing the first 9 of 300 bytes]"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! 非対話モード（`local-code -p "prompt"`）
+//!
+//! バナーや REPL を出さずに1回だけ処理し、最終的な応答だけを標準出力に書く。
+//! スクリプトや CI から使うため、出力は色なしのテキストで、失敗は終了コードで返す。
+
+use std::io::{self, IsTerminal, Read};
+
+use crate::agent::{Agent, SessionStats, TurnRecord, VerificationPipeline};
+use crate::llm::RetryStatus;
+use crate::tools::{TodoItem, TodoList};
+
+/// 非対話モードの出力形式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// 応答のテキストだけ
+    #[default]
+    Text,
+    /// ターンごとに1行の JSON（`TurnRecord`）
+    Json,
+}
+
+/// 標準入力がパイプなら内容を読む（端末なら None）
+pub fn read_piped_stdin() -> io::Result<Option<String>> {
+    let mut stdin = io::stdin();
+    if stdin.is_terminal() {
+        return Ok(None);
+    }
+    let mut content = String::new();
+    stdin.read_to_string(&mut content)?;
+    Ok(Some(content))
+}
+
+/// `-p` の指示とパイプの内容から送信するメッセージを作る（どちらもなければ None）
+///
+/// パイプの内容は `<stdin>` で囲んで指示の後ろに付ける。指示がなければパイプの内容だけを送る
+pub fn compose_prompt(prompt: Option<&str>, piped: Option<&str>) -> Option<String> {
+    let prompt = prompt.map(str::trim).filter(|p| !p.is_empty());
+    let piped = piped.map(|s| s.trim_end()).filter(|s| !s.trim().is_empty());
+    match (prompt, piped) {
+        (Some(prompt), Some(piped)) => Some(format!("{}\n\n<stdin>\n{}\n</stdin>", prompt, piped)),
+        (Some(prompt), None) => Some(prompt.to_string()),
+        (None, Some(piped)) => Some(piped.to_string()),
+        (None, None) => None,
+    }
+}
+
+/// 1回処理して応答を標準出力に書き、終了コードを返す
+///
+/// テキストではエラーを標準エラー出力に書く。JSON ではエラーも `{"prompt", "error"}` として標準出力に書く。
+/// JSON の `verifications` は `verification` で検証した結果（修正は頼まない）
+pub async fn run(
+    agent: &mut Agent,
+    prompt: &str,
+    format: OutputFormat,
+    todos: &TodoList,
+    verification: &VerificationPipeline,
+) -> i32 {
+    match format {
+        OutputFormat::Text => match agent.process(prompt).await {
+            Ok(response) => {
+                println!("{}", response.trim_end());
+                0
+            }
+            Err(e) => {
+                eprintln!("Error: {:#}", e);
+                1
+            }
+        },
+        OutputFormat::Json => match agent.process_detailed(prompt).await {
+            Ok(mut record) => {
+                record.verify_code_blocks(verification, agent.project_root()).await;
+                let todos = todos.lock().unwrap().clone();
+                match turn_json(&record, &agent.session_stats(), &todos) {
+                    Ok(json) => {
+                        println!("{}", json);
+                        0
+                    }
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        1
+                    }
+                }
+            }
+            Err(e) => {
+                println!("{}", serde_json::json!({ "prompt": prompt, "error": format!("{:#}", e) }));
+                1
+            }
+        },
+    }
+}
+
+/// `--output json` の1行（ターンの記録にセッションの使用統計 `session_stats` と ToDo リスト `todos` を加える）
+fn turn_json(record: &TurnRecord, stats: &SessionStats, todos: &[TodoItem]) -> serde_json::Result<String> {
+    let mut json = serde_json::to_value(record)?;
+    json["session_stats"] = serde_json::to_value(stats)?;
+    json["todos"] = serde_json::to_value(todos)?;
+    serde_json::to_string(&json)
+}
+
+/// リトライ待機の開始を標準エラー出力に1行書く
+///
+/// JSON 形式では標準出力のレコードと混ざらないよう、同じく標準エラー出力に JSON で書く
+pub fn log_retry(status: &RetryStatus, format: OutputFormat) {
+    match format {
+        OutputFormat::Text => eprintln!("{}", status.message()),
+        OutputFormat::Json => eprintln!("{}", retry_json(status)),
+    }
+}
+
+/// リトライ待機のイベント（`{"event": "retry", ...}`）
+pub fn retry_json(status: &RetryStatus) -> serde_json::Value {
+    serde_json::json!({
+        "event": "retry",
+        "kind": status.kind.as_str(),
+        "error": status.error,
+        "attempt": status.attempt,
+        "max_retries": status.max_retries,
+        "wait_ms": status.wait.as_millis() as u64,
+        "elapsed_ms": status.elapsed.as_millis() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compose_prompt() {
+        assert_eq!(compose_prompt(Some("explain"), None).as_deref(), Some("explain"));
+        assert_eq!(
+            compose_prompt(Some("what caused this?"), Some("panic at main.rs:3\n")).as_deref(),
+            Some("what caused this?\n\n<stdin>\npanic at main.rs:3\n</stdin>")
+        );
+        assert_eq!(compose_prompt(None, Some("summarize this\n")).as_deref(), Some("summarize this"));
+        // 空のパイプ（/dev/null など）は付けない
+        assert_eq!(compose_prompt(Some("explain"), Some("  \n")).as_deref(), Some("explain"));
+        assert_eq!(compose_prompt(Some("  "), None), None);
+        assert_eq!(compose_prompt(None, None), None);
+    }
+
+    #[test]
+    fn test_turn_json_includes_session_stats_and_todos() {
+        use crate::agent::ToolStats;
+        use std::time::Duration;
+
+        let stats = ToolStats::new();
+        stats.record_tool("read", Duration::from_millis(3), true, 42);
+        stats.record_turn(None);
+        let todos = [TodoItem {
+            content: "Explain".to_string(),
+            status: crate::tools::TodoStatus::InProgress,
+        }];
+        let line = turn_json(&TurnRecord::new("hi"), &stats.snapshot(), &todos).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+
+        assert_eq!(json["prompt"], "hi");
+        assert_eq!(json["session_stats"]["turns"], 1);
+        assert_eq!(json["session_stats"]["tools"]["read"]["invocations"], 1);
+        assert_eq!(json["session_stats"]["tools"]["read"]["output_bytes"], 42);
+        assert_eq!(json["todos"], serde_json::json!([{"content": "Explain", "status": "in_progress"}]));
+    }
+
+    #[test]
+    fn test_retry_json() {
+        use crate::llm::RetryableError;
+        use std::time::Duration;
+
+        let status = RetryStatus {
+            kind: RetryableError::Connection,
+            error: "connection refused".to_string(),
+            attempt: 2,
+            max_retries: 4,
+            wait: Duration::from_secs(2),
+            remaining: Duration::from_secs(2),
+            elapsed: Duration::from_millis(3500),
+        };
+        assert_eq!(
+            retry_json(&status),
+            serde_json::json!({
+                "event": "retry",
+                "kind": "connection",
+                "error": "connection refused",
+                "attempt": 2,
+                "max_retries": 4,
+                "wait_ms": 2000,
+                "elapsed_ms": 3500,
+            })
+        );
+    }
+}
@@ -0,0 +1,338 @@
+//! 送信前のメッセージチェック
+//!
+//! メッセージ中のファイルパスやツール名が実在するかを送信前に確かめ、
+//! 見つからなければ近い候補を示す（`src/agnet/core.rs` → `src/agent/core.rs`）。
+//! 誤検出を避けるため、パスらしさ・ツール名らしさの判定は保守的にしている。
+//!
+//! - パス: `/` と拡張子を含むトークン（URL、絶対パス、`..`、数字だけの部分は対象外）。
+//!   先頭のディレクトリも近い候補もなければパスではないとみなす
+//! - ツール名: 直後に `tool` が続く識別子（`the grpe tool`）。近い候補があるときだけ指摘する
+
+use std::path::{Path, PathBuf};
+
+/// 拡張子の最大長
+const MAX_EXTENSION_LEN: usize = 8;
+
+/// 指摘の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintKind {
+    Path,
+    Tool,
+}
+
+/// 解決できなかった参照
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintIssue {
+    pub kind: LintKind,
+    /// メッセージ中の表記
+    pub token: String,
+    /// 最も近い実在の候補
+    pub suggestion: Option<String>,
+}
+
+impl LintIssue {
+    /// 1件分の説明（`src/agnet/core.rs not found — did you mean src/agent/core.rs?`）
+    pub fn message(&self) -> String {
+        let problem = match self.kind {
+            LintKind::Path => format!("{} not found", self.token),
+            LintKind::Tool => format!("{} is not a known tool", self.token),
+        };
+        match &self.suggestion {
+            Some(suggestion) => format!("{} — did you mean {}?", problem, suggestion),
+            None => problem,
+        }
+    }
+}
+
+/// 対話モードで送信前に出す1行
+pub fn notice(issues: &[LintIssue]) -> String {
+    let messages: Vec<String> = issues.iter().map(|i| i.message()).collect();
+    format!("{} [Enter to send anyway, e to edit]", messages.join("; "))
+}
+
+/// 非対話モード向けに、訂正のヒントをメッセージに書き添える
+pub fn annotate(message: &str, issues: &[LintIssue]) -> String {
+    if issues.is_empty() {
+        return message.to_string();
+    }
+    let notes: Vec<String> = issues.iter().map(|i| format!("Note: {}", i.message())).collect();
+    format!("{}\n\n{}", message, notes.join("\n"))
+}
+
+/// メッセージ中のパスとツール名を確認する
+#[derive(Debug, Clone)]
+pub struct PromptLinter {
+    root: PathBuf,
+    tool_names: Vec<String>,
+}
+
+impl PromptLinter {
+    pub fn new(root: impl Into<PathBuf>, tool_names: Vec<String>) -> Self {
+        Self {
+            root: root.into(),
+            tool_names,
+        }
+    }
+
+    /// 解決できなかった参照を返す（同じ表記は1回だけ）
+    pub fn lint(&self, message: &str) -> Vec<LintIssue> {
+        let words: Vec<&str> = message.split_whitespace().collect();
+        let mut issues: Vec<LintIssue> = Vec::new();
+        for (i, word) in words.iter().enumerate() {
+            let token = strip_wrapping(word);
+            let issue = if let Some(path) = path_candidate(token) {
+                self.check_path(path)
+            } else if is_tool_mention(token, words.get(i + 1).copied()) {
+                self.check_tool(token)
+            } else {
+                None
+            };
+            if let Some(issue) = issue {
+                if !issues.iter().any(|existing| existing.token == issue.token) {
+                    issues.push(issue);
+                }
+            }
+        }
+        issues
+    }
+
+    fn check_path(&self, path: &str) -> Option<LintIssue> {
+        if self.root.join(path).exists() {
+            return None;
+        }
+        let components: Vec<&str> = path.split('/').collect();
+        let (dirs, _file) = components.split_at(components.len() - 1);
+        let dirs_exist = self.root.join(dirs.join("/")).is_dir();
+        let suggestion = closest_path(&self.root, &components);
+        if suggestion.is_none() && !dirs_exist {
+            // 先頭から見当たらないものはパスではないとみなす
+            return None;
+        }
+        Some(LintIssue {
+            kind: LintKind::Path,
+            token: path.to_string(),
+            suggestion,
+        })
+    }
+
+    fn check_tool(&self, name: &str) -> Option<LintIssue> {
+        if self.tool_names.iter().any(|t| t == name) {
+            return None;
+        }
+        let suggestion = closest(name, self.tool_names.iter().map(|s| s.as_str()))?;
+        Some(LintIssue {
+            kind: LintKind::Tool,
+            token: name.to_string(),
+            suggestion: Some(suggestion.to_string()),
+        })
+    }
+}
+
+/// 引用符・括弧・文末の句読点を外す
+fn strip_wrapping(word: &str) -> &str {
+    let word = word.trim_matches(|c: char| "`'\"()[]{}<>".contains(c));
+    let word = word.trim_end_matches(|c: char| ",;:!?.".contains(c));
+    let word = word.trim_matches(|c: char| "`'\"()[]{}<>".contains(c));
+    word.strip_prefix('@').unwrap_or(word)
+}
+
+/// プロジェクト内の相対パスらしいトークンなら正規化して返す
+fn path_candidate(token: &str) -> Option<&str> {
+    if token.contains("://") || token.starts_with('/') || token.starts_with('~') {
+        return None;
+    }
+    if !token.chars().all(|c| c.is_ascii_alphanumeric() || "_-./".contains(c)) {
+        return None;
+    }
+    let token = token.strip_prefix("./").unwrap_or(token);
+    let segments: Vec<&str> = token.split('/').collect();
+    if segments.len() < 2 || segments.iter().any(|s| s.is_empty() || *s == "." || *s == "..") {
+        return None;
+    }
+    // バージョン表記（1.2/3、v1.2/3.4）などを除く
+    if segments.iter().any(|s| s.chars().all(|c| c.is_ascii_digit() || c == '.')) {
+        return None;
+    }
+    let file = segments.last()?;
+    let (stem, extension) = file.rsplit_once('.')?;
+    let valid_extension = !extension.is_empty()
+        && extension.len() <= MAX_EXTENSION_LEN
+        && extension.chars().all(|c| c.is_ascii_alphanumeric())
+        && extension.chars().any(|c| c.is_ascii_alphabetic());
+    if stem.is_empty() || !valid_extension || stem.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(token)
+}
+
+/// `<name> tool` の形で書かれたツール名か
+fn is_tool_mention(token: &str, next: Option<&str>) -> bool {
+    let is_identifier = token.chars().next().is_some_and(|c| c.is_ascii_lowercase())
+        && token.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    let followed_by_tool = next
+        .map(|w| strip_wrapping(w).to_lowercase())
+        .is_some_and(|w| w == "tool" || w == "tools");
+    is_identifier && followed_by_tool
+}
+
+/// 各要素を実在するエントリーに寄せたパス（寄せられない要素があれば None）
+fn closest_path(root: &Path, components: &[&str]) -> Option<String> {
+    let mut dir = root.to_path_buf();
+    let mut resolved = Vec::with_capacity(components.len());
+    let mut corrected = false;
+    for component in components {
+        let name = if dir.join(component).exists() {
+            component.to_string()
+        } else {
+            let entries: Vec<String> = std::fs::read_dir(&dir)
+                .ok()?
+                .filter_map(|e| e.ok())
+                .filter_map(|e| e.file_name().into_string().ok())
+                .collect();
+            corrected = true;
+            closest(component, entries.iter().map(|s| s.as_str()))?.to_string()
+        };
+        dir.push(&name);
+        resolved.push(name);
+    }
+    corrected.then(|| resolved.join("/"))
+}
+
+/// 十分に近い候補のうち最も近いもの（同じ距離なら辞書順で先のもの）
+pub(crate) fn closest<'a>(target: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    candidates
+        .filter_map(|candidate| {
+            let distance = edit_distance(target, candidate);
+            is_close(target, distance).then_some((distance, candidate))
+        })
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// 入力ミスとみなせる距離か（短い名前ほど厳しくする）
+fn is_close(target: &str, distance: usize) -> bool {
+    let len = target.chars().count();
+    distance > 0 && distance <= 2 && distance * 3 <= len
+}
+
+/// 隣接文字の入れ替えを1回と数える編集距離
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linter() -> (tempfile::TempDir, PromptLinter) {
+        let dir = tempfile::tempdir().unwrap();
+        for file in ["src/agent/core.rs", "src/agent/mode.rs", "src/main.rs", "README.md", "docs/guide.md"] {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let tools = ["read", "grep", "glob", "git_status", "lsp_definition"].map(String::from).to_vec();
+        let linter = PromptLinter::new(dir.path(), tools);
+        (dir, linter)
+    }
+
+    #[test]
+    fn test_messages_that_must_trip() {
+        let (_dir, linter) = linter();
+        let cases = [
+            ("fix the bug in src/agnet/core.rs", "src/agnet/core.rs", Some("src/agent/core.rs")),
+            ("look at `src/agent/cor.rs`.", "src/agent/cor.rs", Some("src/agent/core.rs")),
+            ("see ./src/mian.rs please", "src/mian.rs", Some("src/main.rs")),
+            ("check @docs/gide.md", "docs/gide.md", Some("docs/guide.md")),
+            ("open (src/agent/missing.rs) and add a test", "src/agent/missing.rs", None),
+            ("srcc/main.rs crashes", "srcc/main.rs", Some("src/main.rs")),
+            ("use the grpe tool to find it", "grpe", Some("grep")),
+            ("run the git_stauts tool", "git_stauts", Some("git_status")),
+            ("try the lsp_defintion tool.", "lsp_defintion", Some("lsp_definition")),
+        ];
+        for (message, token, suggestion) in cases {
+            let issues = linter.lint(message);
+            assert_eq!(issues.len(), 1, "{}: {:?}", message, issues);
+            assert_eq!(issues[0].token, token, "{}", message);
+            assert_eq!(issues[0].suggestion.as_deref(), suggestion, "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_messages_that_must_not_trip() {
+        let (_dir, linter) = linter();
+        let messages = [
+            "fix the bug in src/agent/core.rs",
+            "see https://example.com/docs/index.html for details",
+            "upgrade from 1.2/3 to 1.4/5",
+            "bump v1.2/3.4 and 2.0/1.0",
+            "compute x/y.len and a/b.c in the loop",
+            "the ratio is width/height.abs()",
+            "this is an and/or situation, TCP/IP and km/h.",
+            "read /etc/hosts and ~/notes/todo.md",
+            "compare ../other/repo.rs with it",
+            "github.com/user/repo.git is the upstream",
+            "use the build tool and the search tools",
+            "call foo_bar and my_var in the code",
+            "use the grep tool",
+            "src/agent/core.rs: line 10",
+            "docs/guide.md and README.md",
+            "std::path::Path/PathBuf.rs",
+            "the 3/4.5 split",
+        ];
+        for message in messages {
+            assert_eq!(linter.lint(message), Vec::<LintIssue>::new(), "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_repeated_token_reported_once() {
+        let (_dir, linter) = linter();
+        assert_eq!(linter.lint("src/agnet/core.rs and again src/agnet/core.rs").len(), 1);
+    }
+
+    #[test]
+    fn test_notice_and_annotation() {
+        let issues = vec![LintIssue {
+            kind: LintKind::Path,
+            token: "src/agnet/core.rs".into(),
+            suggestion: Some("src/agent/core.rs".into()),
+        }];
+        assert_eq!(
+            notice(&issues),
+            "src/agnet/core.rs not found — did you mean src/agent/core.rs? [Enter to send anyway, e to edit]"
+        );
+        assert_eq!(
+            annotate("fix it", &issues),
+            "fix it\n\nNote: src/agnet/core.rs not found — did you mean src/agent/core.rs?"
+        );
+        assert_eq!(annotate("fix it", &[]), "fix it");
+    }
+
+    #[test]
+    fn test_edit_distance_counts_transposition_once() {
+        assert_eq!(edit_distance("agnet", "agent"), 1);
+        assert_eq!(edit_distance("grpe", "grep"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert!(!is_close("ab", 1));
+        assert!(is_close("agnet", 1));
+    }
+}
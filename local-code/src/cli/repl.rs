@@ -3,7 +3,7 @@ use crossterm::{
     cursor,
     event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
     execute,
-    style::{Color, Print, ResetColor, SetForegroundColor, Attribute, SetAttribute},
+    style::{Attribute, Color, Print},
     terminal::{self, ClearType},
 };
 use std::fs::{self, File};
@@ -11,12 +11,18 @@ use std::io::{self, BufRead, BufReader, Write};
 use std::path::PathBuf;
 use std::future::Future;
 
+use super::color::{ResetColor, SetAttribute, SetForegroundColor};
+use super::commands::Command;
 use super::completion::{Completer, CompletionResult};
+use super::inputs::InputEntry;
+use super::line_buffer::{KillRing, LineBuffer, CONTINUATION_GUTTER};
+use super::layout::{prompt_layout, terminal_width};
 use super::output::Icons;
+use crate::agent::{Mode, ModeManager};
 
 /// コマンド履歴を管理する構造体
 pub struct CommandHistory {
-    history: Vec<String>,
+    history: Vec<InputEntry>,
     position: usize,
     history_file: PathBuf,
     max_history: usize,
@@ -48,7 +54,7 @@ impl CommandHistory {
         config_dir.join("command_history")
     }
 
-    fn load_from_file(path: &PathBuf) -> Vec<String> {
+    fn load_from_file(path: &PathBuf) -> Vec<InputEntry> {
         if !path.exists() {
             return Vec::new();
         }
@@ -57,8 +63,9 @@ impl CommandHistory {
             Ok(file) => {
                 BufReader::new(file)
                     .lines()
-                    .filter_map(|line| line.ok())
+                    .map_while(|line| line.ok())
                     .filter(|line| !line.is_empty())
+                    .map(|line| InputEntry::parse_line(&line))
                     .collect()
             }
             Err(_) => Vec::new(),
@@ -75,8 +82,9 @@ impl CommandHistory {
             0
         };
 
-        for cmd in &self.history[start..] {
-            writeln!(file, "{}", cmd)?;
+        // 複数行の入力もエスケープして1行1件で保存する
+        for entry in &self.history[start..] {
+            writeln!(file, "{}", entry.to_line())?;
         }
 
         Ok(())
@@ -89,13 +97,11 @@ impl CommandHistory {
             return;
         }
 
-        // 直前と同じコマンドは追加しない
-        if self.history.last().map_or(false, |last| last == &cmd) {
-            self.position = self.history.len();
-            return;
-        }
-
-        self.history.push(cmd);
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .ok();
+        self.history.push(InputEntry::new(cmd, timestamp));
         self.position = self.history.len();
 
         // ファイルに保存
@@ -112,23 +118,35 @@ impl CommandHistory {
             self.position -= 1;
         }
 
-        self.history.get(self.position)
+        // 連続した同じ入力は1つとして辿る
+        while self.position > 0
+            && self.history[self.position - 1].command == self.history[self.position].command
+        {
+            self.position -= 1;
+        }
+
+        self.history.get(self.position).map(|e| &e.command)
     }
 
     /// 次の履歴を取得
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Option<&String> {
         if self.history.is_empty() {
             return None;
         }
 
         if self.position < self.history.len() {
+            let current = self.history[self.position].command.clone();
             self.position += 1;
+            while self.position < self.history.len() && self.history[self.position].command == current {
+                self.position += 1;
+            }
         }
 
         if self.position >= self.history.len() {
             None // 最新位置では空を返す（新規入力用）
         } else {
-            self.history.get(self.position)
+            self.history.get(self.position).map(|e| &e.command)
         }
     }
 
@@ -136,6 +154,24 @@ impl CommandHistory {
     pub fn reset_position(&mut self) {
         self.position = self.history.len();
     }
+
+    /// 履歴の全件（古い順）
+    pub fn entries(&self) -> &[InputEntry] {
+        &self.history
+    }
+
+    /// 保存先のファイル
+    pub fn path(&self) -> &PathBuf {
+        &self.history_file
+    }
+
+    /// 履歴を消し、保存ファイルも空にする
+    pub fn clear(&mut self) -> Result<()> {
+        self.history.clear();
+        self.position = 0;
+        File::create(&self.history_file)?;
+        Ok(())
+    }
 }
 
 impl Default for CommandHistory {
@@ -154,6 +190,12 @@ pub struct Repl {
     superpowers_commands: Vec<String>,
     superpowers_cycle: Option<SuperpowersCycleState>,
     workflow_next_index: usize,  // 次回の初期インデックス
+    mode_manager: Option<ModeManager>,  // 空入力での Shift+Tab で切り替える
+    status_segment: Option<String>,  // モードの後ろに薄く出す状態（コンテキスト警告など）
+    pending_input: Option<String>,  // 次の入力の初期値（送信前チェックで編集に戻ったとき）
+    input_start_col: usize,  // 入力の1行目が始まる桁（プロンプトの幅）
+    drawn_cursor_row: usize,  // 描画したカーソルの行（入力の1行目から数える）
+    kill_ring: KillRing,  // Ctrl+U/K/W で消した文字列（入力をまたいで残す）
 }
 
 struct CompletionState {
@@ -166,7 +208,6 @@ struct CompletionState {
 /// Superpowersコマンドサイクル状態
 struct SuperpowersCycleState {
     index: usize,
-    workflow_index: usize,  // ワークフロー進行位置を保持
 }
 
 impl Repl {
@@ -181,6 +222,12 @@ impl Repl {
             superpowers_commands: Vec::new(),
             superpowers_cycle: None,
             workflow_next_index: 0,
+            mode_manager: None,
+            status_segment: None,
+            pending_input: None,
+            input_start_col: 0,
+            drawn_cursor_row: 0,
+            kill_ring: KillRing::new(),
         }
     }
 
@@ -190,6 +237,21 @@ impl Repl {
         self.update_prompt();
     }
 
+    /// モードマネージャーを設定（空入力での Shift+Tab でモードを切り替える）
+    pub fn set_mode_manager(&mut self, mode_manager: ModeManager) {
+        self.mode_manager = Some(mode_manager);
+    }
+
+    /// モードの後ろに表示する状態を設定（None で消す）
+    pub fn set_status_segment(&mut self, segment: Option<String>) {
+        self.status_segment = segment;
+    }
+
+    /// 次の入力をこの内容から始める（カーソルは末尾）
+    pub fn set_pending_input(&mut self, input: String) {
+        self.pending_input = Some(input);
+    }
+
     /// モデルを設定
     pub fn set_model(&mut self, model: String) {
         self.model = model;
@@ -214,6 +276,16 @@ impl Repl {
         self.completer.set_skills(skills);
     }
 
+    /// ローカライズしたコマンドの別名を設定（補完用）
+    pub fn set_localized_commands(&mut self, commands: Vec<String>) {
+        self.completer.set_localized_commands(commands);
+    }
+
+    /// 保存した会話の名前を設定（`/load` などの補完用）
+    pub fn set_conversation_names(&mut self, names: Vec<String>) {
+        self.completer.set_conversation_names(names);
+    }
+
     /// 追加コマンドを設定（補完用）
     pub fn set_commands(&mut self, commands: Vec<String>) {
         self.completer.set_extra_commands(commands);
@@ -232,19 +304,29 @@ impl Repl {
     /// モードアイコン付きプロンプトを表示
     pub fn print_prompt_with_icon(&self, mode_icon: Option<&str>) -> Result<()> {
         let mut stdout = io::stdout();
-        let icon = mode_icon.unwrap_or(if self.mode.to_lowercase() == "plan" { "📋" } else { "⏵⏵" });
-
-        // アイコン Mode (shift+tab) ❯ 形式で表示
+        let (default_icon, icon_color, mode_color) = mode_style(&self.mode);
+        let icon = mode_icon.unwrap_or(default_icon);
+
+        // アイコン Mode model (shift+tab) ❯ 形式で表示（端末幅に応じて省略）
+        let segment = self.status_segment.as_ref().map(|s| format!(" {}", s)).unwrap_or_default();
+        let layout = prompt_layout(&format!("{}{}", self.mode, segment), &self.model, terminal_width());
+        let model = layout.model.map(|m| format!(" {}", m)).unwrap_or_default();
+        let hint = if layout.show_hint { " (shift+tab)" } else { "" };
         let _ = execute!(
             stdout,
-            SetForegroundColor(Color::Magenta),
+            SetForegroundColor(icon_color),
             Print(format!("{} ", icon)),
             ResetColor,
-            SetForegroundColor(Color::Yellow),
-            Print(format!("{}", self.mode)),
+            SetForegroundColor(mode_color),
+            Print(self.mode.to_string()),
             ResetColor,
             SetForegroundColor(Color::DarkGrey),
-            Print(" (shift+tab)"),
+            SetAttribute(Attribute::Dim),
+            Print(segment),
+            SetAttribute(Attribute::Reset),
+            SetForegroundColor(Color::DarkGrey),
+            Print(model),
+            Print(hint),
             ResetColor,
             SetForegroundColor(Color::Cyan),
             SetAttribute(Attribute::Bold),
@@ -295,6 +377,10 @@ impl Repl {
         self.completer.set_working_dir(path);
     }
 
+    /// 組み込み用の簡易ループ
+    ///
+    /// 終了コマンド（/quit など）でループを抜け、それ以外の入力はスラッシュコマンドも含めて
+    /// `on_message` に渡す。コマンドの解釈は `Command::parse` に一本化している
     pub async fn run<F, Fut>(&mut self, mut on_message: F) -> Result<()>
     where
         F: FnMut(&str) -> Fut,
@@ -324,29 +410,17 @@ impl Repl {
                 continue;
             }
 
-            self.command_history.add(input.to_string());
-
-            // コマンド処理
-            if input.starts_with('/') {
-                match self.handle_command(input).await {
-                    Ok(should_quit) => {
-                        if should_quit {
-                            println!("Goodbye!");
-                            break;
-                        }
-                    }
-                    Err(e) => eprintln!("Error: {}", e),
-                }
-            } else {
-                // 通常の入力（LLMに送信）
-                on_message(input).await?;
+            if matches!(Command::parse(input), Command::Quit) {
+                println!("Goodbye!");
+                break;
             }
+            on_message(input).await?;
         }
 
         Ok(())
     }
 
-    /// crosstermを使用して履歴対応の行読み取り
+    /// crosstermを使用して履歴対応の行読み取り（確定した入力は履歴に記録する）
     pub fn read_line_with_history(&mut self) -> Result<String> {
         terminal::enable_raw_mode()?;
 
@@ -355,19 +429,42 @@ impl Repl {
         terminal::disable_raw_mode()?;
         println!(); // 改行を追加
 
+        if let Ok(line) = &result {
+            self.command_history.add(line.trim().to_string());
+        }
         result
     }
 
+    /// 入力履歴（`/inputs` 用）
+    pub fn input_history(&self) -> &CommandHistory {
+        &self.command_history
+    }
+
+    /// 入力履歴を消す（保存ファイルも空にする）
+    pub fn clear_input_history(&mut self) -> Result<()> {
+        self.command_history.clear()
+    }
+
     fn read_line_internal(&mut self) -> Result<String> {
-        let mut input = String::new();
-        let mut cursor_pos: usize = 0; // char index
-        let mut stdout = io::stdout();
+        let mut buffer = LineBuffer::from_text(self.pending_input.take().unwrap_or_default());
+        // プロンプトの直後から描く（継続行と折り返しの位置の基準）
+        self.input_start_col = cursor::position().map(|(col, _)| col as usize).unwrap_or(0);
+        self.drawn_cursor_row = 0;
+        if !buffer.is_empty() {
+            self.render(&buffer)?;
+        }
 
         self.command_history.reset_position();
 
         loop {
             if event::poll(std::time::Duration::from_millis(100))? {
-                if let Event::Key(key_event) = event::read()? {
+                let ev = event::read()?;
+                if let Event::Resize(..) = ev {
+                    // 端末幅が変わったらプロンプトを再計算して描き直す
+                    self.redraw_line(&buffer)?;
+                    continue;
+                }
+                if let Event::Key(key_event) = ev {
                     // Only process key press events, not release events
                     if key_event.kind != KeyEventKind::Press {
                         continue;
@@ -376,36 +473,42 @@ impl Repl {
                         self.completion_state = None;
                     }
                     match key_event {
+                        // Alt+Enter / Ctrl+J: 送信せずに改行を入れる
+                        KeyEvent {
+                            code: KeyCode::Enter,
+                            modifiers: KeyModifiers::ALT,
+                            ..
+                        }
+                        | KeyEvent {
+                            code: KeyCode::Char('j'),
+                            modifiers: KeyModifiers::CONTROL,
+                            ..
+                        } => {
+                            buffer.insert('\n');
+                            self.render(&buffer)?;
+                        }
                         KeyEvent {
                             code: KeyCode::Enter,
                             ..
                         } => {
                             // ペースト検出: 短時間内に次の入力があれば改行として扱う
                             if event::poll(std::time::Duration::from_millis(30))? {
-                                // ペースト中 - 改行を挿入して継続
-                                let byte_idx = byte_index(&input, cursor_pos);
-                                input.insert(byte_idx, '\n');
-                                cursor_pos += 1;
-                                // 改行を表示
-                                write!(stdout, "\r\n")?;
-                                // 残りの文字を再描画
-                                let remaining = &input[byte_index(&input, cursor_pos)..];
-                                if !remaining.is_empty() {
-                                    write!(stdout, "{}", remaining)?;
-                                    let remaining_chars = char_len(remaining);
-                                    if remaining_chars > 0 {
-                                        execute!(stdout, cursor::MoveLeft(remaining_chars as u16))?;
-                                    }
-                                }
-                                stdout.flush()?;
+                                buffer.insert('\n');
+                                self.render(&buffer)?;
+                                continue;
+                            }
+                            // 行末の `\` は次の行に続ける
+                            if buffer.continue_line() {
+                                self.render(&buffer)?;
                                 continue;
                             }
                             // 通常のEnter - 入力確定
                             // 実行したコマンドがsuperpowersの場合、次のインデックスを記録
-                            if let Some(idx) = self.superpowers_commands.iter().position(|c| c == &input) {
+                            if let Some(idx) = self.superpowers_commands.iter().position(|c| c == buffer.text()) {
                                 self.workflow_next_index = (idx + 1) % self.superpowers_commands.len();
                             }
                             self.superpowers_cycle = None;  // サイクルをリセット
+                            self.move_below_input(&buffer)?;
                             break;
                         }
                         KeyEvent {
@@ -413,28 +516,24 @@ impl Repl {
                             modifiers: KeyModifiers::CONTROL,
                             ..
                         } => {
+                            self.move_below_input(&buffer)?;
                             return Err(anyhow::anyhow!("interrupted"));
                         }
                         KeyEvent {
                             code: KeyCode::Char('d'),
                             modifiers: KeyModifiers::CONTROL,
                             ..
-                        } => {
-                            if input.is_empty() {
-                                return Ok("/quit".to_string());
-                            }
+                        } if buffer.is_empty() => {
+                            return Ok("/quit".to_string());
                         }
                         KeyEvent {
                             code: KeyCode::Esc,
                             ..
-                        } => {
-                            if self.superpowers_cycle.is_some() {
-                                self.superpowers_cycle = None;
-                                Self::clear_line_static(&mut stdout, cursor_pos)?;
-                                input.clear();
-                                cursor_pos = 0;
-                                continue;
-                            }
+                        } if self.superpowers_cycle.is_some() => {
+                            self.superpowers_cycle = None;
+                            buffer.clear();
+                            self.render(&buffer)?;
+                            continue;
                         }
                         KeyEvent {
                             code: KeyCode::Up,
@@ -442,12 +541,9 @@ impl Repl {
                         } => {
                             let prev_cmd = self.command_history.prev().cloned();
                             if let Some(cmd) = prev_cmd {
-                                // 現在の入力をクリアして履歴を表示
-                                Self::clear_line_static(&mut stdout, cursor_pos)?;
-                                input = cmd;
-                                cursor_pos = input.len();
-                                write!(stdout, "{}", input)?;
-                                stdout.flush()?;
+                                // 現在の入力を履歴の内容（複数行ならすべての行）で描き直す
+                                buffer.set(cmd);
+                                self.render(&buffer)?;
                             }
                         }
                         KeyEvent {
@@ -455,94 +551,85 @@ impl Repl {
                             ..
                         } => {
                             let next_cmd = self.command_history.next().cloned();
-                            Self::clear_line_static(&mut stdout, cursor_pos)?;
-                            if let Some(cmd) = next_cmd {
-                                input = cmd;
-                            } else {
-                                input.clear();
-                            }
-                            cursor_pos = input.len();
-                            write!(stdout, "{}", input)?;
-                            stdout.flush()?;
-                        }
-                        KeyEvent {
-                            code: KeyCode::Left,
-                            ..
-                        } => {
-                            if cursor_pos > 0 {
-                                cursor_pos -= 1;
-                                execute!(stdout, cursor::MoveLeft(1))?;
-                            }
-                        }
-                        KeyEvent {
-                            code: KeyCode::Right,
-                            ..
-                        } => {
-                            if cursor_pos < char_len(&input) {
-                                cursor_pos += 1;
-                                execute!(stdout, cursor::MoveRight(1))?;
-                            }
-                        }
-                        KeyEvent {
-                            code: KeyCode::Home,
-                            ..
-                        } => {
-                            if cursor_pos > 0 {
-                                execute!(stdout, cursor::MoveLeft(cursor_pos as u16))?;
-                                cursor_pos = 0;
-                            }
+                            buffer.set(next_cmd.unwrap_or_default());
+                            self.render(&buffer)?;
                         }
                         KeyEvent {
-                            code: KeyCode::End,
+                            code:
+                                code @ (KeyCode::Left
+                                | KeyCode::Right
+                                | KeyCode::Home
+                                | KeyCode::End
+                                | KeyCode::Backspace
+                                | KeyCode::Delete),
                             ..
                         } => {
-                            let total = char_len(&input);
-                            if cursor_pos < total {
-                                let move_right = total - cursor_pos;
-                                execute!(stdout, cursor::MoveRight(move_right as u16))?;
-                                cursor_pos = total;
+                            // 改行をまたいで動く・消す
+                            let changed = match code {
+                                KeyCode::Left => buffer.move_left(),
+                                KeyCode::Right => buffer.move_right(),
+                                KeyCode::Home => buffer.move_home(),
+                                KeyCode::End => buffer.move_end(),
+                                KeyCode::Backspace => buffer.backspace(),
+                                _ => buffer.delete(),
+                            };
+                            if changed {
+                                self.render(&buffer)?;
                             }
                         }
                         KeyEvent {
-                            code: KeyCode::Backspace,
+                            code: KeyCode::Char(c @ ('a' | 'e' | 'u' | 'k' | 'w' | 'y')),
+                            modifiers: KeyModifiers::CONTROL,
                             ..
                         } => {
-                            if cursor_pos > 0 {
-                                cursor_pos -= 1;
-                                let byte_idx = byte_index(&input, cursor_pos);
-                                input.remove(byte_idx);
-
-                                // カーソルを左に移動して、残りの文字を再描画
-                                execute!(stdout, cursor::MoveLeft(1))?;
-                                let remaining = &input[byte_index(&input, cursor_pos)..];
-                                write!(stdout, "{} ", remaining)?;
-                                // カーソルを正しい位置に戻す
-                                let move_back = char_len(remaining) + 1;
-                                execute!(stdout, cursor::MoveLeft(move_back as u16))?;
-                                stdout.flush()?;
+                            // readline 互換の編集キー
+                            let changed = match c {
+                                'a' => buffer.move_line_start(),
+                                'e' => buffer.move_line_end(),
+                                'y' => match self.kill_ring.last() {
+                                    Some(text) => {
+                                        buffer.insert_str(text);
+                                        true
+                                    }
+                                    None => false,
+                                },
+                                _ => {
+                                    let killed = match c {
+                                        'u' => buffer.kill_to_line_start(),
+                                        'k' => buffer.kill_to_line_end(),
+                                        _ => buffer.kill_word_back(),
+                                    };
+                                    killed.map(|text| self.kill_ring.push(text)).is_some()
+                                }
+                            };
+                            if changed {
+                                self.render(&buffer)?;
                             }
                         }
                         KeyEvent {
-                            code: KeyCode::Delete,
+                            code: KeyCode::Char(c @ ('b' | 'f')),
+                            modifiers: KeyModifiers::ALT,
                             ..
                         } => {
-                            if cursor_pos < char_len(&input) {
-                                let byte_idx = byte_index(&input, cursor_pos);
-                                input.remove(byte_idx);
-
-                                // 残りの文字を再描画
-                                let remaining = &input[byte_index(&input, cursor_pos)..];
-                                write!(stdout, "{} ", remaining)?;
-                                let move_back = char_len(remaining) + 1;
-                                execute!(stdout, cursor::MoveLeft(move_back as u16))?;
-                                stdout.flush()?;
+                            let changed = if c == 'b' { buffer.move_word_left() } else { buffer.move_word_right() };
+                            if changed {
+                                self.render(&buffer)?;
                             }
                         }
                         KeyEvent {
                             code: KeyCode::BackTab,
                             ..
                         } => {
-                            // Shift+Tab: Superpowersコマンドをサイクル
+                            // 空入力での Shift+Tab: モードを切り替え（Plan → Execute → AcceptEdits）
+                            if buffer.is_empty() {
+                                if let Some(mode) = self.mode_manager.as_ref().and_then(|m| m.cycle()) {
+                                    self.set_mode(mode.to_string());
+                                    self.redraw_line(&buffer)?;
+                                    continue;
+                                }
+                            }
+
+                            // 入力がある場合: Superpowersコマンドをサイクル
                             if self.superpowers_commands.is_empty() {
                                 continue;
                             }
@@ -556,26 +643,20 @@ impl Repl {
                                 None => {
                                     // ワークフロー位置から開始
                                     let start_idx = self.workflow_next_index;
-                                    self.superpowers_cycle = Some(SuperpowersCycleState {
-                                        index: start_idx,
-                                        workflow_index: start_idx,
-                                    });
+                                    self.superpowers_cycle = Some(SuperpowersCycleState { index: start_idx });
                                     start_idx
                                 }
                             };
 
                             // 入力を選択中コマンドに置換
-                            let cmd = self.superpowers_commands[next_index].clone();
-                            Self::clear_line_static(&mut stdout, cursor_pos)?;
-                            input = cmd;
-                            cursor_pos = input.len();
-                            write!(stdout, "{}", input)?;
-                            stdout.flush()?;
+                            buffer.set(self.superpowers_commands[next_index].clone());
+                            self.render(&buffer)?;
                         }
                         KeyEvent {
                             code: KeyCode::Tab,
                             ..
                         } => {
+                            let input = buffer.text().to_string();
                             // 前回の補完状態を継続するか判定（現在のインデックスの候補と一致するか）
                             let continue_empty_cycle = self.completion_state.as_ref()
                                 .map(|s| s.from_empty && s.candidates.get(s.index).map(|c| c == &input).unwrap_or(false))
@@ -600,12 +681,25 @@ impl Repl {
                                 }
 
                                 if let Some(state) = &self.completion_state {
-                                    Self::clear_line_static(&mut stdout, cursor_pos)?;
-                                    input = state.candidates[state.index].clone();
-                                    cursor_pos = input.len();
-                                    write!(stdout, "{}", input)?;
-                                    stdout.flush()?;
+                                    buffer.set(state.candidates[state.index].clone());
+                                    self.render(&buffer)?;
                                 }
+                            } else if let Some((start, candidates)) = self
+                                .completer
+                                .complete_mention(buffer.before_cursor())
+                            {
+                                // 入力途中の @path → その部分だけを補完
+                                let end = buffer.before_cursor().len();
+                                let replacement = match Completer::common_prefix(&candidates) {
+                                    Some(prefix) if prefix.len() > end - start => prefix,
+                                    _ if candidates.len() > 1 => {
+                                        self.list_candidates(&buffer, &candidates)?;
+                                        continue;
+                                    }
+                                    _ => continue,
+                                };
+                                buffer.replace_range(start, end, &replacement);
+                                self.render(&buffer)?;
                             } else if input.starts_with('/') {
                                 // "/" で始まる → 従来のコマンド補完（全コマンド対象）
                                 let seed = self
@@ -636,39 +730,22 @@ impl Repl {
                                 }
 
                                 if let Some(state) = &self.completion_state {
-                                    Self::clear_line_static(&mut stdout, cursor_pos)?;
-                                    input = state.candidates[state.index].clone();
-                                    cursor_pos = input.len();
-                                    write!(stdout, "{}", input)?;
-                                    stdout.flush()?;
+                                    buffer.set(state.candidates[state.index].clone());
+                                    self.render(&buffer)?;
                                 }
                             } else {
                                 // パス補完は従来通り
                                 match self.completer.complete_with_result(&input) {
                                     CompletionResult::Single(completion) => {
-                                        Self::clear_line_static(&mut stdout, cursor_pos)?;
-                                        input = completion;
-                                        cursor_pos = input.len();
-                                        write!(stdout, "{}", input)?;
-                                        stdout.flush()?;
+                                        buffer.set(completion);
+                                        self.render(&buffer)?;
                                     }
                                     CompletionResult::Multiple { common_prefix, candidates } => {
                                         if common_prefix.len() > input.len() {
-                                            Self::clear_line_static(&mut stdout, cursor_pos)?;
-                                            input = common_prefix;
-                                            cursor_pos = input.len();
-                                            write!(stdout, "{}", input)?;
-                                            stdout.flush()?;
+                                            buffer.set(common_prefix);
+                                            self.render(&buffer)?;
                                         } else {
-                                            write!(stdout, "\r\n")?;
-                                            for (i, candidate) in candidates.iter().enumerate() {
-                                                if i > 0 && i % 4 == 0 {
-                                                    write!(stdout, "\r\n")?;
-                                                }
-                                                write!(stdout, "{:<20}", candidate)?;
-                                            }
-                                            write!(stdout, "\r\n{}{}", self.prompt, input)?;
-                                            stdout.flush()?;
+                                            self.list_candidates(&buffer, &candidates)?;
                                         }
                                     }
                                     CompletionResult::None => {}
@@ -681,20 +758,8 @@ impl Repl {
                             ..
                         } => {
                             self.superpowers_cycle = None;  // 通常入力でサイクルをリセット
-                            let byte_idx = byte_index(&input, cursor_pos);
-                            input.insert(byte_idx, c);
-                            cursor_pos += 1;
-
-                            // カーソル位置に文字を挿入
-                            let remaining = &input[byte_index(&input, cursor_pos - 1)..];
-                            write!(stdout, "{}", remaining)?;
-
-                            // カーソルを正しい位置に戻す
-                            let remaining_chars = char_len(remaining);
-                            if remaining_chars > 1 {
-                                execute!(stdout, cursor::MoveLeft((remaining_chars - 1) as u16))?;
-                            }
-                            stdout.flush()?;
+                            buffer.insert(c);
+                            self.render(&buffer)?;
                         }
                         _ => {}
                     }
@@ -702,103 +767,102 @@ impl Repl {
             }
         }
 
-        Ok(input)
+        Ok(buffer.text().to_string())
     }
 
-    /// 現在の行をクリア（静的メソッド）
-    fn clear_line_static(stdout: &mut io::Stdout, cursor_pos: usize) -> Result<()> {
-        // カーソルを行頭に移動
-        if cursor_pos > 0 {
-            execute!(stdout, cursor::MoveLeft(cursor_pos as u16))?;
+    /// 入力を描き直す（前回描いた行を消し、継続行にはガターを付ける）
+    fn render(&mut self, buffer: &LineBuffer) -> Result<()> {
+        let mut stdout = io::stdout();
+        let width = terminal_width();
+        if self.drawn_cursor_row > 0 {
+            execute!(stdout, cursor::MoveUp(self.drawn_cursor_row as u16))?;
+        }
+        execute!(
+            stdout,
+            cursor::MoveToColumn(self.input_start_col as u16),
+            terminal::Clear(ClearType::FromCursorDown)
+        )?;
+        for (i, line) in buffer.text().split('\n').enumerate() {
+            let prefix = if i == 0 {
+                self.input_start_col
+            } else {
+                write!(stdout, "\r\n")?;
+                execute!(
+                    stdout,
+                    SetForegroundColor(Color::DarkGrey),
+                    Print(CONTINUATION_GUTTER),
+                    ResetColor
+                )?;
+                CONTINUATION_GUTTER.chars().count()
+            };
+            write!(stdout, "{}", line)?;
+            // ちょうど幅を埋めた行は次の行に送る（配置の計算と合わせる）
+            let used = prefix + line.chars().count();
+            if used > 0 && used % width == 0 {
+                write!(stdout, " \r")?;
+            }
+        }
+
+        // 最終行の末尾から目的の位置へ戻る
+        let layout = buffer.layout(self.input_start_col, width);
+        let up = layout.rows - 1 - layout.cursor_row;
+        if up > 0 {
+            execute!(stdout, cursor::MoveUp(up as u16))?;
         }
-        // 行末までクリア
-        execute!(stdout, terminal::Clear(ClearType::UntilNewLine))?;
+        execute!(stdout, cursor::MoveToColumn(layout.cursor_col as u16))?;
+        stdout.flush()?;
+        self.drawn_cursor_row = layout.cursor_row;
         Ok(())
     }
 
-    pub fn read_line(&self) -> Result<String> {
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        Ok(input)
+    /// カーソルを入力の最終行の末尾へ動かす（確定・候補一覧の前）
+    fn move_below_input(&mut self, buffer: &LineBuffer) -> Result<()> {
+        let mut stdout = io::stdout();
+        let layout = LineBuffer::from_text(buffer.text()).layout(self.input_start_col, terminal_width());
+        let down = layout.rows - 1 - self.drawn_cursor_row.min(layout.rows - 1);
+        if down > 0 {
+            execute!(stdout, cursor::MoveDown(down as u16))?;
+        }
+        execute!(stdout, cursor::MoveToColumn(layout.cursor_col as u16))?;
+        stdout.flush()?;
+        self.drawn_cursor_row = layout.cursor_row;
+        Ok(())
     }
 
-    async fn handle_command(&self, input: &str) -> Result<bool> {
-        let parts: Vec<&str> = input.split_whitespace().collect();
-        let command = parts.first().unwrap_or(&"");
-
-        match *command {
-            "/quit" | "/q" | "/exit" => Ok(true),
-            "/help" | "/h" => {
-                self.print_help();
-                Ok(false)
-            }
-            "/plan" => {
-                println!("Switched to Plan mode (read-only tools)");
-                Ok(false)
-            }
-            "/execute" | "/exec" => {
-                println!("Switched to Execute mode (all tools available)");
-                Ok(false)
-            }
-            "/clear" => {
-                print!("\x1B[2J\x1B[1;1H");
-                Ok(false)
-            }
-            "/history" => {
-                self.print_history();
-                Ok(false)
-            }
-            _ => {
-                println!("Unknown command: {}. Type /help for available commands.", command);
-                Ok(false)
+    /// 補完候補を入力の下に並べ、プロンプトと入力を描き直す
+    fn list_candidates(&mut self, buffer: &LineBuffer, candidates: &[String]) -> Result<()> {
+        self.move_below_input(buffer)?;
+        let mut stdout = io::stdout();
+        write!(stdout, "\r\n")?;
+        for (i, candidate) in candidates.iter().enumerate() {
+            if i > 0 && i % 4 == 0 {
+                write!(stdout, "\r\n")?;
             }
+            write!(stdout, "{:<20}", candidate)?;
         }
+        write!(stdout, "\r\n")?;
+        self.drawn_cursor_row = 0;
+        self.redraw_line(buffer)
     }
 
-    fn print_help(&self) {
-        println!("
-Commands:
-  /help, /h       - Show this help message
-  /quit, /q       - Exit the REPL
-  /plan           - Switch to Plan mode (read-only tools)
-  /execute, /exec - Switch to Execute mode (all tools)
-  /clear          - Clear the screen
-  /history        - Show command history
-  /<skill-name>   - Run a skill
-
-Navigation:
-  Up/Down arrows  - Navigate command history
-  Left/Right      - Move cursor
-  Home/End        - Jump to start/end of line
-  Ctrl+C          - Cancel current input
-  Ctrl+D          - Exit (when input is empty)
-
-Enter text to chat with the AI.
-");
-    }
-
-    fn print_history(&self) {
-        println!("\nCommand History:");
-        println!("----------------");
-        for (i, cmd) in self.command_history.history.iter().enumerate() {
-            println!("{:4}: {}", i + 1, cmd);
+    /// プロンプトと入力を描き直す（リサイズ・モード切り替え後）
+    fn redraw_line(&mut self, buffer: &LineBuffer) -> Result<()> {
+        let mut stdout = io::stdout();
+        if self.drawn_cursor_row > 0 {
+            execute!(stdout, cursor::MoveUp(self.drawn_cursor_row as u16))?;
         }
-        println!();
+        execute!(stdout, cursor::MoveToColumn(0), terminal::Clear(ClearType::FromCursorDown))?;
+        self.print_prompt_with_icon(None)?;
+        self.input_start_col = cursor::position().map(|(col, _)| col as usize).unwrap_or(0);
+        self.drawn_cursor_row = 0;
+        self.render(buffer)
     }
-}
-
-fn char_len(text: &str) -> usize {
-    text.chars().count()
-}
 
-fn byte_index(text: &str, char_index: usize) -> usize {
-    if char_index == 0 {
-        return 0;
+    pub fn read_line(&self) -> Result<String> {
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        Ok(input)
     }
-    text.char_indices()
-        .nth(char_index)
-        .map(|(idx, _)| idx)
-        .unwrap_or_else(|| text.len())
 }
 
 impl Default for Repl {
@@ -806,3 +870,26 @@ impl Default for Repl {
         Self::new()
     }
 }
+
+/// モード名に対応するアイコンと色（アイコン色, モード名の色）
+///
+/// accept-edits は確認なしで書き込むため目立つ色にする
+fn mode_style(mode: &str) -> (&'static str, Color, Color) {
+    let mode = Mode::parse_mode(mode).unwrap_or_default();
+    match mode {
+        Mode::AcceptEdits => (mode.icon(), Color::Green, Color::Green),
+        _ => (mode.icon(), Color::Magenta, Color::Yellow),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mode_style() {
+        assert_eq!(mode_style("plan").0, "📋");
+        assert_eq!(mode_style("execute"), ("⏵⏵", Color::Magenta, Color::Yellow));
+        assert_eq!(mode_style("accept-edits"), ("✎", Color::Green, Color::Green));
+    }
+}
@@ -10,10 +10,12 @@ use tokio::sync::Mutex;
 use crossterm::{
     cursor::{Hide, MoveToColumn, Show},
     execute,
-    style::{Color, Print, ResetColor, SetForegroundColor},
+    style::{Color, Print},
     terminal::{Clear, ClearType},
 };
 
+use super::color::{self, ResetColor, SetForegroundColor};
+
 /// スピナーのフレーム（Brailleパターン）
 const SPINNER_FRAMES: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
 
@@ -63,6 +65,22 @@ impl Spinner {
             *msg_guard = msg.to_string();
         }
 
+        // 色が使えない出力先ではアニメーションせず、メッセージが変わるたびに1行書く
+        if !color::enabled() {
+            self.handle = Some(tokio::spawn(async move {
+                let mut last = String::new();
+                while running.load(Ordering::SeqCst) {
+                    let current_msg = message.lock().await.clone();
+                    if current_msg != last {
+                        println!("{}...", current_msg.trim_end_matches('.'));
+                        last = current_msg;
+                    }
+                    tokio::time::sleep(Duration::from_millis(FRAME_INTERVAL_MS)).await;
+                }
+            }));
+            return;
+        }
+
         // スピナータスクを起動
         self.handle = Some(tokio::spawn(async move {
             let mut frame_idx = 0;
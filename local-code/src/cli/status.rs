@@ -0,0 +1,107 @@
+//! /status の表示
+
+use crossterm::style::{Color, Stylize};
+use std::io::Write;
+
+use crate::agent::status::{HealthState, StatusRegistry, SubsystemStatus};
+
+fn state_color(state: HealthState) -> Color {
+    match state {
+        HealthState::Ok => Color::Green,
+        HealthState::Degraded => Color::Yellow,
+        HealthState::Failed => Color::Red,
+        HealthState::Disabled => Color::DarkGrey,
+    }
+}
+
+/// 状態の表（colored なら状態欄を色分け）
+pub fn format_status_table(statuses: &[SubsystemStatus], colored: bool) -> String {
+    let name_width = statuses.iter().map(|s| s.name.chars().count()).max().unwrap_or(0);
+    let state_width = "disabled".len();
+
+    let mut out = String::new();
+    for status in statuses {
+        let state = format!("{:<width$}", status.state.as_str(), width = state_width);
+        let state = if colored {
+            state.with(state_color(status.state)).to_string()
+        } else {
+            state
+        };
+        out.push_str(&format!(
+            "{:<name_width$}  {}  {}\n",
+            status.name,
+            state,
+            status.detail,
+            name_width = name_width
+        ));
+        if let (Some(hint), HealthState::Degraded | HealthState::Failed) = (&status.hint, status.state) {
+            out.push_str(&format!("{:<name_width$}  → {}\n", "", hint, name_width = name_width));
+        }
+    }
+    out.trim_end().to_string()
+}
+
+/// JSON出力（`overall` と各サブシステム）
+pub fn format_status_json(statuses: &[SubsystemStatus]) -> String {
+    let value = serde_json::json!({
+        "overall": StatusRegistry::overall(statuses),
+        "subsystems": statuses,
+    });
+    serde_json::to_string_pretty(&value).unwrap_or_default()
+}
+
+/// /status の結果を表示
+pub fn print_status(statuses: &[SubsystemStatus], json: bool) {
+    let mut stdout = std::io::stdout();
+    if json {
+        let _ = writeln!(stdout, "{}", format_status_json(statuses));
+    } else {
+        let _ = writeln!(stdout, "{}", format_status_table(statuses, super::color::enabled()));
+    }
+    let _ = stdout.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statuses() -> Vec<SubsystemStatus> {
+        vec![
+            SubsystemStatus::ok("mode", "execute: bash, read"),
+            SubsystemStatus::degraded("llm", "last request failed").with_hint(Some("Check the server")),
+            SubsystemStatus::failed("lsp", "rust-analyzer failed to start").with_hint(Some("Install it")),
+            SubsystemStatus::disabled("skills", "none").with_hint(Some("not shown")),
+        ]
+    }
+
+    #[test]
+    fn test_table_lists_hints_for_problems_only() {
+        let table = format_status_table(&statuses(), false);
+        assert_eq!(
+            table,
+            "mode    ok        execute: bash, read\n\
+             llm     degraded  last request failed\n\
+             \x20       → Check the server\n\
+             lsp     failed    rust-analyzer failed to start\n\
+             \x20       → Install it\n\
+             skills  disabled  none"
+        );
+    }
+
+    #[test]
+    fn test_colored_table_wraps_state() {
+        let table = format_status_table(&statuses()[..1], true);
+        assert!(table.contains("\x1b["));
+        assert!(table.contains("ok"));
+    }
+
+    #[test]
+    fn test_json_output() {
+        let value: serde_json::Value = serde_json::from_str(&format_status_json(&statuses())).unwrap();
+        assert_eq!(value["overall"], "failed");
+        assert_eq!(value["subsystems"].as_array().unwrap().len(), 4);
+        assert_eq!(value["subsystems"][1]["state"], "degraded");
+        assert_eq!(value["subsystems"][1]["hint"], "Check the server");
+        assert!(value["subsystems"][0].get("hint").is_none());
+    }
+}
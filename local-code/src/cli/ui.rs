@@ -1,11 +1,20 @@
 use anyhow::Result;
 use crossterm::{
     cursor,
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
     execute,
-    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor},
-    terminal::{self, Clear, ClearType},
+    style::{Attribute, Color, Print},
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
+use std::ops::Range;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use super::broadcast::{self, OutputEvent};
+use super::color::{ResetColor, SetAttribute, SetForegroundColor};
+use super::layout::{display_rows, separator, terminal_width, wrap_to_width};
+use super::markdown::{markdown_enabled, MarkdownRenderer};
 
 const SEPARATOR_MARK: &str = "__LOCAL_CODE_SEPARATOR__";
 
@@ -71,7 +80,7 @@ impl Ui {
         let cols = cols as usize;
         let rows = rows as usize;
 
-        let header_lines = vec![
+        let header_lines = [
             self.title.clone(),
             format!(
                 "Model: {} | Project: {} | Skills: {}",
@@ -83,7 +92,7 @@ impl Ui {
         ];
 
         let commands_count = self.status.commands.len();
-        let footer_lines = vec![
+        let footer_lines = [
             "-".repeat(cols.max(1)),
             format!(
                 "Mode: {} | Model: {} | Commands: {} | Skills: {}",
@@ -181,8 +190,12 @@ fn shorten_home_path(path: &str) -> String {
 
 /// セパレータを出力
 pub fn print_separator() {
-    let (cols, _) = terminal::size().unwrap_or((80, 24));
-    println!("{}", "-".repeat(cols as usize));
+    broadcast::emit(|| OutputEvent::Separator);
+    write_separator();
+}
+
+fn write_separator() {
+    println!("{}", separator(terminal_width()));
 }
 
 /// 情報メッセージを出力
@@ -214,6 +227,7 @@ pub fn print_error(message: &str) {
 
 /// フォーマット済みブロックを出力（タイトル付き）
 pub fn print_formatted_block(title: &str, content: &str) {
+    broadcast::emit(|| OutputEvent::Block { title: title.to_string(), content: content.to_string() });
     let mut stdout = io::stdout();
 
     // タイトルに応じて色を設定
@@ -226,7 +240,7 @@ pub fn print_formatted_block(title: &str, content: &str) {
         _ => Color::Yellow,
     };
 
-    print_separator();
+    write_separator();
     let _ = execute!(
         stdout,
         SetForegroundColor(color),
@@ -236,13 +250,36 @@ pub fn print_formatted_block(title: &str, content: &str) {
         ResetColor
     );
 
-    if !content.is_empty() {
-        println!("{}", content);
+    if content.is_empty() {
+        return;
+    }
+    let rendered = if title.eq_ignore_ascii_case("ASSISTANT") && markdown_enabled() {
+        MarkdownRenderer::new(terminal_width()).render(content)
+    } else {
+        content.to_string()
+    };
+    let pageable = ["ASSISTANT", "TOOL"].iter().any(|t| title.eq_ignore_ascii_case(t));
+    if pageable && page_if_long(&rendered) {
+        return;
     }
+    println!("{}", rendered);
+}
+
+/// 本文の邪魔にならないよう暗い色でブロックを出力（ToDo リストの更新など）
+pub fn print_dim_block(title: &str, content: &str) {
+    broadcast::emit(|| OutputEvent::Block { title: title.to_string(), content: content.to_string() });
+    let mut stdout = io::stdout();
+    let _ = execute!(
+        stdout,
+        SetForegroundColor(Color::DarkGrey),
+        Print(format!("{}:\n{}\n", title, content)),
+        ResetColor
+    );
 }
 
 /// 処理中メッセージを出力
 pub fn print_processing(message: &str) {
+    broadcast::emit(|| OutputEvent::Processing { text: message.to_string() });
     let mut stdout = io::stdout();
     let _ = execute!(
         stdout,
@@ -251,3 +288,274 @@ pub fn print_processing(message: &str) {
         ResetColor
     );
 }
+
+// ============================================================
+// ページャー
+// ============================================================
+
+/// 長い出力をページャーで表示するか（`ui.paging`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PagingMode {
+    /// 使わない
+    #[default]
+    Never,
+    /// 端末の高さを超えるときだけ
+    Auto,
+    /// 常に使う
+    Always,
+}
+
+impl PagingMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "never" => Some(PagingMode::Never),
+            "auto" => Some(PagingMode::Auto),
+            "always" => Some(PagingMode::Always),
+            _ => None,
+        }
+    }
+}
+
+/// ページャーの設定（対話セッションの開始時に一度だけ設定する）
+#[derive(Debug, Clone, Default)]
+struct PagerSettings {
+    mode: PagingMode,
+    /// 外部ページャーのコマンド（None なら内蔵ページャー）
+    command: Option<String>,
+}
+
+static PAGER: OnceLock<PagerSettings> = OnceLock::new();
+
+/// ページャーを有効にする（設定しなければ使わない。-p や watch では呼ばない）
+pub fn configure_pager(mode: PagingMode, command: Option<String>) {
+    let command = command.filter(|c| !c.trim().is_empty());
+    let _ = PAGER.set(PagerSettings { mode, command });
+}
+
+/// ページャーで表示するか（ブロックの見出しと区切り線の2行も高さに含める）
+pub fn should_page(mode: PagingMode, content_rows: usize, terminal_rows: usize, interactive: bool) -> bool {
+    match mode {
+        _ if !interactive => false,
+        PagingMode::Never => false,
+        PagingMode::Always => true,
+        PagingMode::Auto => content_rows + 2 > terminal_rows,
+    }
+}
+
+/// 長ければページャーで表示する（表示した場合は true）
+fn page_if_long(text: &str) -> bool {
+    let Some(settings) = PAGER.get() else {
+        return false;
+    };
+    let interactive = io::stdout().is_terminal() && io::stdin().is_terminal();
+    let (cols, rows) = terminal::size().map(|(c, r)| (c as usize, r as usize)).unwrap_or((80, 24));
+    let content_rows: usize = text.lines().map(|line| display_rows(line, cols)).sum();
+    if !should_page(settings.mode, content_rows, rows, interactive) {
+        return false;
+    }
+
+    if let Some(command) = &settings.command {
+        match run_external_pager(command, text) {
+            Ok(()) => return true,
+            Err(e) => tracing::warn!("Pager '{}' failed, using the built-in pager: {}", command, e),
+        }
+    }
+    let lines: Vec<String> = text
+        .lines()
+        .flat_map(|line| {
+            // 装飾付きの行は描画時にすでに幅に収めてある
+            if line.contains('\x1b') { vec![line.to_string()] } else { wrap_to_width(line, cols) }
+        })
+        .collect();
+    match run_builtin_pager(&lines) {
+        Ok(()) => {
+            println!("({} lines shown in the pager)", lines.len());
+            true
+        }
+        Err(e) => {
+            tracing::warn!("Built-in pager failed: {}", e);
+            false
+        }
+    }
+}
+
+/// `sh -c` で外部ページャーを起動し、標準入力に渡す
+fn run_external_pager(command: &str, text: &str) -> io::Result<()> {
+    let mut child = Command::new("sh").arg("-c").arg(command).stdin(Stdio::piped()).spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // ページャーを途中で閉じた場合の書き込みエラーは無視する
+        let _ = stdin.write_all(text.as_bytes());
+        let _ = stdin.write_all(b"\n");
+    }
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("exited with {}", status)))
+    }
+}
+
+/// 内蔵ページャーの表示範囲
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PagerWindow {
+    top: usize,
+    height: usize,
+    total: usize,
+}
+
+impl PagerWindow {
+    pub fn new(total: usize, height: usize) -> Self {
+        Self { top: 0, height: height.max(1), total }
+    }
+
+    fn max_top(&self) -> usize {
+        self.total.saturating_sub(self.height)
+    }
+
+    /// delta 行スクロール（範囲外には出ない）
+    pub fn scroll(&mut self, delta: isize) {
+        let top = self.top as isize + delta;
+        self.top = top.clamp(0, self.max_top() as isize) as usize;
+    }
+
+    pub fn page_down(&mut self) {
+        self.scroll(self.height as isize);
+    }
+
+    pub fn page_up(&mut self) {
+        self.scroll(-(self.height as isize));
+    }
+
+    pub fn home(&mut self) {
+        self.top = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.top = self.max_top();
+    }
+
+    /// 端末の高さが変わったとき
+    pub fn resize(&mut self, height: usize) {
+        self.height = height.max(1);
+        self.top = self.top.min(self.max_top());
+    }
+
+    /// 表示する行の範囲
+    pub fn visible(&self) -> Range<usize> {
+        self.top..(self.top + self.height).min(self.total)
+    }
+}
+
+/// j/k・space/b・g/G・q で操作する内蔵ページャー（最下行は位置の表示）
+fn run_builtin_pager(lines: &[String]) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    let rows = terminal::size().map(|(_, r)| r as usize).unwrap_or(24);
+    let mut window = PagerWindow::new(lines.len(), rows.saturating_sub(1));
+
+    execute!(stdout, EnterAlternateScreen, cursor::Hide)?;
+    terminal::enable_raw_mode()?;
+    let result = (|| -> io::Result<()> {
+        loop {
+            draw_pager(&mut stdout, lines, &window)?;
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+                    KeyCode::Char('j') | KeyCode::Down | KeyCode::Enter => window.scroll(1),
+                    KeyCode::Char('k') | KeyCode::Up => window.scroll(-1),
+                    KeyCode::Char(' ') | KeyCode::Char('f') | KeyCode::PageDown => window.page_down(),
+                    KeyCode::Char('b') | KeyCode::PageUp => window.page_up(),
+                    KeyCode::Char('g') | KeyCode::Home => window.home(),
+                    KeyCode::Char('G') | KeyCode::End => window.end(),
+                    _ => {}
+                },
+                Event::Resize(_, rows) => window.resize((rows as usize).saturating_sub(1)),
+                _ => {}
+            }
+        }
+    })();
+    terminal::disable_raw_mode()?;
+    execute!(stdout, cursor::Show, LeaveAlternateScreen)?;
+    result
+}
+
+fn draw_pager(stdout: &mut io::Stdout, lines: &[String], window: &PagerWindow) -> io::Result<()> {
+    execute!(stdout, Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+    let visible = window.visible();
+    for line in &lines[visible.clone()] {
+        write!(stdout, "{}\r\n", line)?;
+    }
+    let status = format!(
+        " lines {}-{} of {}  (j/k, space/b, g/G, q to quit) ",
+        visible.start + 1,
+        visible.end,
+        lines.len()
+    );
+    execute!(
+        stdout,
+        cursor::MoveTo(0, window.height as u16),
+        SetAttribute(Attribute::Reverse),
+        Print(status),
+        SetAttribute(Attribute::Reset)
+    )?;
+    stdout.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paging_mode_parse() {
+        assert_eq!(PagingMode::parse("auto"), Some(PagingMode::Auto));
+        assert_eq!(PagingMode::parse(" Always "), Some(PagingMode::Always));
+        assert_eq!(PagingMode::parse("never"), Some(PagingMode::Never));
+        assert_eq!(PagingMode::parse("sometimes"), None);
+    }
+
+    #[test]
+    fn test_should_page_threshold() {
+        // 見出しと区切り線の2行を含めて端末に収まるなら表示するだけ
+        assert!(!should_page(PagingMode::Auto, 22, 24, true));
+        assert!(should_page(PagingMode::Auto, 23, 24, true));
+        assert!(should_page(PagingMode::Always, 1, 24, true));
+        assert!(!should_page(PagingMode::Never, 2000, 24, true));
+        // パイプや -p では使わない
+        assert!(!should_page(PagingMode::Always, 2000, 24, false));
+        assert!(!should_page(PagingMode::Auto, 2000, 24, false));
+    }
+
+    #[test]
+    fn test_pager_window_scrolls_within_bounds() {
+        let mut window = PagerWindow::new(100, 20);
+        assert_eq!(window.visible(), 0..20);
+        window.scroll(-5);
+        assert_eq!(window.visible(), 0..20);
+        window.scroll(3);
+        assert_eq!(window.visible(), 3..23);
+        window.page_down();
+        assert_eq!(window.visible(), 23..43);
+        window.end();
+        assert_eq!(window.visible(), 80..100);
+        window.page_down();
+        assert_eq!(window.visible(), 80..100);
+        window.page_up();
+        assert_eq!(window.visible(), 60..80);
+        window.home();
+        assert_eq!(window.visible(), 0..20);
+    }
+
+    #[test]
+    fn test_pager_window_shorter_than_screen_and_resize() {
+        let mut window = PagerWindow::new(5, 20);
+        window.page_down();
+        assert_eq!(window.visible(), 0..5);
+
+        let mut window = PagerWindow::new(100, 20);
+        window.end();
+        window.resize(50);
+        assert_eq!(window.visible(), 50..100);
+        window.resize(0);
+        assert_eq!(window.visible(), 50..51);
+    }
+}
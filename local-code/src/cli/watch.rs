@@ -0,0 +1,506 @@
+//! `local-code watch` - 他のセッションの表示を読み取り専用で追う
+//!
+//! 本体が `[ui] broadcast = true` で書き出すストリームファイルの末尾を追い、
+//! 本体と同じ表示関数で描画する（色や幅は見る側の端末に合わせる）。
+//! 見る側から本体へ入力する経路はない。
+
+use anyhow::Result;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::broadcast::{now_millis, stream_path, OutputEvent, StreamRecord, STREAM_VERSION};
+use super::output::{
+    print_debug, print_error, print_info, print_mode, print_newline, print_streaming_end, print_success,
+    print_tool, StreamingWriter,
+};
+use super::ui::{print_formatted_block, print_processing, print_separator};
+
+/// ファイルを確認する間隔
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// これより遅れていなければ「live」
+const LIVE_THRESHOLD_MS: u64 = 2000;
+
+/// 本体プロセスの生存を確認する間隔（ポーリング回数）
+const LIVENESS_POLLS: u32 = 10;
+
+/// 末尾追跡で得たもの
+#[derive(Debug, Clone, PartialEq)]
+pub enum TailItem {
+    Record(StreamRecord),
+    /// ファイルが切り替わった（新しいファイルを先頭から読む）
+    Reopened,
+    /// 対応していないバージョンのレコード
+    Unsupported(u32),
+}
+
+/// ファイルの同一性（切り替えの検出用）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileId {
+    #[cfg(unix)]
+    dev: u64,
+    #[cfg(unix)]
+    ino: u64,
+}
+
+impl FileId {
+    fn of(metadata: &std::fs::Metadata) -> Self {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            Self { dev: metadata.dev(), ino: metadata.ino() }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = metadata;
+            Self {}
+        }
+    }
+}
+
+/// ストリームファイルの末尾を追う
+///
+/// 開いたファイルは切り替え（rename）後も読み切ってから新しいファイルへ移るため、
+/// 切り替えの直前に書かれたレコードも落とさない
+#[derive(Debug)]
+pub struct StreamTailer {
+    path: PathBuf,
+    file: Option<File>,
+    id: Option<FileId>,
+    position: u64,
+    partial: Vec<u8>,
+}
+
+impl StreamTailer {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            file: None,
+            id: None,
+            position: 0,
+            partial: Vec::new(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// ファイルを開いているか（まだセッションがなければ false）
+    pub fn is_open(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// 新しく書かれたレコードを読む
+    pub fn poll(&mut self) -> Vec<TailItem> {
+        let mut items = Vec::new();
+        if self.file.is_none() && !self.open() {
+            return items;
+        }
+        self.drain(&mut items);
+
+        if self.was_replaced() {
+            // 古いファイルの残りは読み切った。途中の行は捨てて新しいファイルへ
+            self.file = None;
+            self.partial.clear();
+            if self.open() {
+                items.push(TailItem::Reopened);
+                self.drain(&mut items);
+            }
+        }
+        items
+    }
+
+    fn open(&mut self) -> bool {
+        let Ok(file) = File::open(&self.path) else {
+            return false;
+        };
+        self.id = file.metadata().ok().map(|m| FileId::of(&m));
+        self.file = Some(file);
+        self.position = 0;
+        true
+    }
+
+    /// パスが別のファイルを指しているか、ファイルが短くなったか
+    fn was_replaced(&self) -> bool {
+        match std::fs::metadata(&self.path) {
+            Ok(metadata) => Some(FileId::of(&metadata)) != self.id || metadata.len() < self.position,
+            // 切り替えの途中。次のポーリングで開き直す
+            Err(_) => false,
+        }
+    }
+
+    fn drain(&mut self, items: &mut Vec<TailItem>) {
+        let Some(file) = self.file.as_mut() else {
+            return;
+        };
+        let mut buf = Vec::new();
+        if let Err(e) = file.read_to_end(&mut buf) {
+            if e.kind() != io::ErrorKind::Interrupted {
+                tracing::warn!("Failed to read {}: {}", self.path.display(), e);
+            }
+            return;
+        }
+        self.position += buf.len() as u64;
+        self.partial.extend_from_slice(&buf);
+
+        // 最後の改行までが完成した行
+        let Some(end) = self.partial.iter().rposition(|&b| b == b'\n') else {
+            return;
+        };
+        let complete: Vec<u8> = self.partial.drain(..=end).collect();
+        for line in complete.split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+            if let Some(item) = parse_line(line) {
+                items.push(item);
+            }
+        }
+    }
+}
+
+/// 1行を解釈（壊れた行は無視）
+fn parse_line(line: &[u8]) -> Option<TailItem> {
+    let value: serde_json::Value = serde_json::from_slice(line).ok()?;
+    let version = value.get("v").and_then(|v| v.as_u64())? as u32;
+    if version != STREAM_VERSION {
+        return Some(TailItem::Unsupported(version));
+    }
+    match serde_json::from_value(value) {
+        Ok(record) => Some(TailItem::Record(record)),
+        Err(e) => {
+            tracing::debug!("Skipping malformed stream record: {}", e);
+            None
+        }
+    }
+}
+
+/// 本体との遅れ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    Live,
+    Behind { seconds: u64 },
+}
+
+impl Freshness {
+    /// 描画したレコードの時刻から判定
+    pub fn of(record_ts: u64, now: u64) -> Self {
+        let lag = now.saturating_sub(record_ts);
+        if lag < LIVE_THRESHOLD_MS {
+            Freshness::Live
+        } else {
+            Freshness::Behind { seconds: lag / 1000 }
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Freshness::Live => "live".to_string(),
+            Freshness::Behind { seconds } => format!("{} seconds behind", seconds),
+        }
+    }
+}
+
+/// 遅れの表示（状態が変わったときだけ1行出す）
+#[derive(Debug, Default)]
+pub struct LagIndicator {
+    behind: Option<bool>,
+}
+
+impl LagIndicator {
+    /// 1回分のレコードを描画する前に呼ぶ（先頭のレコードの時刻で判定）
+    pub fn before_batch(&mut self, first_ts: u64, now: u64) -> Option<String> {
+        let freshness = Freshness::of(first_ts, now);
+        self.transition(freshness)
+    }
+
+    /// ファイルを読み切ったら live
+    pub fn caught_up(&mut self) -> Option<String> {
+        self.transition(Freshness::Live)
+    }
+
+    fn transition(&mut self, freshness: Freshness) -> Option<String> {
+        let behind = freshness != Freshness::Live;
+        if self.behind == Some(behind) {
+            return None;
+        }
+        self.behind = Some(behind);
+        Some(format!("[watch] {}", freshness.label()))
+    }
+}
+
+/// 本体の状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Primary {
+    /// まだ開始を見ていない
+    Unknown,
+    Running { pid: u32 },
+    Ended,
+}
+
+/// 受け取ったイベントを本体と同じ表示関数で描画する
+#[derive(Default)]
+pub struct Renderer {
+    stream: Option<StreamingWriter>,
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn render(&mut self, event: &OutputEvent) {
+        match event {
+            OutputEvent::SessionStart { pid } => {
+                self.end_stream();
+                print_info(&format!("[watch] Primary session started (pid {})", pid));
+            }
+            OutputEvent::SessionEnd => {
+                self.end_stream();
+                print_info("[watch] Primary session ended; waiting for a new session (Ctrl+C to quit)");
+            }
+            OutputEvent::Block { title, content } => print_formatted_block(title, content),
+            OutputEvent::Separator => print_separator(),
+            OutputEvent::Processing { text } => print_processing(text),
+            OutputEvent::Info { text } => print_info(text),
+            OutputEvent::Error { text } => print_error(text),
+            OutputEvent::Success { text } => print_success(text),
+            OutputEvent::Tool { name, text } => print_tool(name, text),
+            OutputEvent::Mode { mode } => print_mode(mode),
+            OutputEvent::Debug { text } => print_debug(text),
+            OutputEvent::Retry { text, .. } => print_processing(text),
+            OutputEvent::Newline => print_newline(),
+            OutputEvent::StreamStart { prefix } => {
+                let mut writer = StreamingWriter::new();
+                writer.start(prefix.as_deref());
+                self.stream = Some(writer);
+            }
+            OutputEvent::StreamText { text } => match self.stream.as_mut() {
+                Some(writer) => writer.write_immediate(text),
+                None => super::output::print_streaming_text(text),
+            },
+            OutputEvent::StreamEnd { stats } => match (self.stream.take(), stats) {
+                (Some(mut writer), Some(stats)) => {
+                    writer.set_note(stats.note.clone());
+                    writer.finish_with_stats(stats.tokens_per_second, stats.total_tokens, stats.prompt_tokens);
+                }
+                (Some(mut writer), None) => writer.finish(),
+                (None, _) => print_streaming_end(),
+            },
+        }
+    }
+
+    /// 途中のストリーミング出力を閉じる（本体の終了や切り替え時）
+    pub fn end_stream(&mut self) {
+        if let Some(mut writer) = self.stream.take() {
+            writer.finish();
+        }
+    }
+}
+
+/// 本体プロセスが動いているか（確認できない環境では動いているとみなす）
+fn is_running(pid: u32) -> bool {
+    if cfg!(target_os = "linux") {
+        Path::new("/proc").join(pid.to_string()).exists()
+    } else {
+        true
+    }
+}
+
+/// `local-code watch` を実行（Ctrl+C で終了）
+pub async fn run_watch(project_root: &Path) -> Result<()> {
+    let mut tailer = StreamTailer::new(stream_path(project_root));
+    let mut renderer = Renderer::new();
+    let mut indicator = LagIndicator::default();
+    let mut primary = Primary::Unknown;
+    let mut warned_version = false;
+    let mut idle_polls = 0u32;
+
+    print_info(&format!("[watch] Watching {} (read-only, Ctrl+C to quit)", tailer.path().display()));
+    if !tailer.path().exists() {
+        print_info("[watch] No session yet; start one with [ui] broadcast = true in this project");
+    }
+
+    loop {
+        let items = tailer.poll();
+        if items.is_empty() {
+            idle_polls += 1;
+            if idle_polls.is_multiple_of(LIVENESS_POLLS) {
+                if let Primary::Running { pid } = primary {
+                    if !is_running(pid) {
+                        renderer.end_stream();
+                        print_info(&format!(
+                            "[watch] Primary session (pid {}) is no longer running; waiting for a new session",
+                            pid
+                        ));
+                        primary = Primary::Ended;
+                    }
+                }
+            }
+        } else {
+            idle_polls = 0;
+            let first_ts = items.iter().find_map(|item| match item {
+                TailItem::Record(record) => Some(record.ts),
+                _ => None,
+            });
+            if let Some(line) = first_ts.and_then(|ts| indicator.before_batch(ts, now_millis())) {
+                print_processing(&line);
+            }
+            for item in &items {
+                match item {
+                    TailItem::Record(record) => {
+                        match record.event {
+                            OutputEvent::SessionStart { pid } => primary = Primary::Running { pid },
+                            OutputEvent::SessionEnd => primary = Primary::Ended,
+                            _ => {}
+                        }
+                        renderer.render(&record.event);
+                    }
+                    // サイズによる切り替えはストリーミング出力の途中でも起きるので何もしない
+                    TailItem::Reopened => {}
+                    TailItem::Unsupported(version) if !warned_version => {
+                        warned_version = true;
+                        print_error(&format!(
+                            "[watch] The session writes stream version {} but this build reads version {}; update local-code",
+                            version, STREAM_VERSION
+                        ));
+                    }
+                    TailItem::Unsupported(_) => {}
+                }
+            }
+            if let Some(line) = indicator.caught_up() {
+                print_processing(&line);
+            }
+        }
+
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            _ = tokio::time::sleep(POLL_INTERVAL) => {}
+        }
+    }
+
+    renderer.end_stream();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::broadcast::StreamWriter;
+    use std::io::Write;
+
+    fn events(items: &[TailItem]) -> Vec<OutputEvent> {
+        items
+            .iter()
+            .filter_map(|item| match item {
+                TailItem::Record(record) => Some(record.event.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn info(text: &str) -> OutputEvent {
+        OutputEvent::Info { text: text.to_string() }
+    }
+
+    #[test]
+    fn test_tails_appended_records_and_partial_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = stream_path(dir.path());
+        let mut tailer = StreamTailer::new(&path);
+        assert!(tailer.poll().is_empty());
+        assert!(!tailer.is_open());
+
+        let mut writer = StreamWriter::create(&path).unwrap();
+        writer.append(OutputEvent::SessionStart { pid: 7 }).unwrap();
+        writer.append(info("one")).unwrap();
+        assert_eq!(events(&tailer.poll()), vec![OutputEvent::SessionStart { pid: 7 }, info("one")]);
+
+        // 書きかけの行は完成するまで待つ
+        let line = serde_json::to_string(&StreamRecord::new(info("two"))).unwrap();
+        let (head, tail) = line.split_at(10);
+        let mut raw = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        raw.write_all(head.as_bytes()).unwrap();
+        assert!(tailer.poll().is_empty());
+        raw.write_all(format!("{}\n", tail).as_bytes()).unwrap();
+        assert_eq!(events(&tailer.poll()), vec![info("two")]);
+    }
+
+    #[test]
+    fn test_rotation_keeps_records_written_before_the_switch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = stream_path(dir.path());
+        let mut writer = StreamWriter::create(&path).unwrap().with_max_bytes(200);
+        let mut tailer = StreamTailer::new(&path);
+
+        writer.append(info("first")).unwrap();
+        assert_eq!(events(&tailer.poll()), vec![info("first")]);
+
+        // 上限を超えて切り替わる前後の両方を1回のポーリングで受け取る
+        writer.append(info(&"x".repeat(200))).unwrap();
+        writer.append(info("after rotation")).unwrap();
+        let items = tailer.poll();
+        assert_eq!(events(&items), vec![info(&"x".repeat(200)), info("after rotation")]);
+        assert!(items.contains(&TailItem::Reopened));
+
+        writer.append(info("more")).unwrap();
+        assert_eq!(events(&tailer.poll()), vec![info("more")]);
+    }
+
+    #[test]
+    fn test_new_session_replaces_ended_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = stream_path(dir.path());
+        let mut first = StreamWriter::create(&path).unwrap();
+        first.append(OutputEvent::SessionStart { pid: 1 }).unwrap();
+        first.append(OutputEvent::SessionEnd).unwrap();
+        let mut tailer = StreamTailer::new(&path);
+        assert_eq!(events(&tailer.poll()).last(), Some(&OutputEvent::SessionEnd));
+
+        let mut second = StreamWriter::create(&path).unwrap();
+        second.append(OutputEvent::SessionStart { pid: 2 }).unwrap();
+        assert_eq!(events(&tailer.poll()), vec![OutputEvent::SessionStart { pid: 2 }]);
+    }
+
+    #[test]
+    fn test_unknown_version_and_garbage_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stream.jsonl");
+        std::fs::write(
+            &path,
+            "not json\n{\"v\":99,\"ts\":0,\"event\":{\"type\":\"hologram\"}}\n{\"v\":1,\"ts\":0,\"event\":{\"type\":\"newline\"}}\n",
+        )
+        .unwrap();
+        let items = StreamTailer::new(&path).poll();
+        assert_eq!(
+            items,
+            vec![
+                TailItem::Unsupported(99),
+                TailItem::Record(StreamRecord { v: 1, ts: 0, event: OutputEvent::Newline })
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lag_indicator_reports_transitions_only() {
+        let mut indicator = LagIndicator::default();
+        // 追いつくまでの再生中は遅れを表示
+        assert_eq!(indicator.before_batch(1_000, 43_500), Some("[watch] 42 seconds behind".to_string()));
+        assert_eq!(indicator.before_batch(2_000, 43_500), None);
+        assert_eq!(indicator.caught_up(), Some("[watch] live".to_string()));
+        assert_eq!(indicator.before_batch(43_000, 43_500), None);
+        assert_eq!(indicator.caught_up(), None);
+        assert_eq!(Freshness::of(10_000, 10_500), Freshness::Live);
+    }
+
+    #[test]
+    fn test_renderer_handles_stream_events_without_start() {
+        let mut renderer = Renderer::new();
+        renderer.render(&OutputEvent::StreamText { text: "orphan".into() });
+        renderer.render(&OutputEvent::StreamEnd { stats: None });
+        renderer.render(&OutputEvent::StreamStart { prefix: Some("AI:".into()) });
+        renderer.render(&OutputEvent::StreamText { text: "hi".into() });
+        renderer.render(&OutputEvent::SessionEnd);
+        assert!(renderer.stream.is_none());
+    }
+}
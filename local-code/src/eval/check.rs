@@ -0,0 +1,214 @@
+//! フィクスチャのチェック評価
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+use tokio::process::Command;
+
+/// チェック結果の詳細に含める出力の最大文字数
+const MAX_DETAIL_CHARS: usize = 500;
+
+/// チェックスクリプトに渡すレスポンスファイルの環境変数
+pub const RESPONSE_ENV: &str = "LOCAL_CODE_EVAL_RESPONSE";
+
+/// 1件のチェック
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Check {
+    /// ファイルが存在する
+    FileExists { path: String },
+    /// ファイルが文字列を含む
+    FileContains { path: String, contains: String },
+    /// 作業ディレクトリで `sh -c` したコマンドが終了コード0
+    Command { command: String },
+    /// 応答が正規表現にマッチする
+    ResponseMatches { pattern: String },
+    /// フィクスチャ内のスクリプトが終了コード0
+    Script { path: String },
+}
+
+/// チェックの実行環境
+pub struct CheckContext<'a> {
+    /// エージェントが作業した一時ディレクトリ
+    pub workdir: &'a Path,
+    /// フィクスチャのディレクトリ（スクリプトの場所）
+    pub fixture_dir: &'a Path,
+    /// エージェントの最終応答
+    pub response: &'a str,
+    /// 応答を書き出したファイル（コマンドに環境変数で渡す）
+    pub response_file: &'a Path,
+    /// コマンド・スクリプトのタイムアウト
+    pub timeout: Duration,
+}
+
+/// チェックの結果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckOutcome {
+    /// チェックの説明
+    pub check: String,
+    pub passed: bool,
+    /// 不合格の理由
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl Check {
+    /// レポート用の説明
+    pub fn describe(&self) -> String {
+        match self {
+            Check::FileExists { path } => format!("file exists: {}", path),
+            Check::FileContains { path, contains } => format!("{} contains {:?}", path, contains),
+            Check::Command { command } => format!("command succeeds: {}", command),
+            Check::ResponseMatches { pattern } => format!("response matches /{}/", pattern),
+            Check::Script { path } => format!("script succeeds: {}", path),
+        }
+    }
+
+    /// チェックを評価
+    pub async fn evaluate(&self, ctx: &CheckContext<'_>) -> CheckOutcome {
+        let result = match self {
+            Check::FileExists { path } => {
+                if ctx.workdir.join(path).exists() {
+                    Ok(())
+                } else {
+                    Err(format!("{} does not exist", path))
+                }
+            }
+            Check::FileContains { path, contains } => match std::fs::read_to_string(ctx.workdir.join(path)) {
+                Ok(content) if content.contains(contains.as_str()) => Ok(()),
+                Ok(_) => Err(format!("{} does not contain {:?}", path, contains)),
+                Err(e) => Err(format!("cannot read {}: {}", path, e)),
+            },
+            Check::Command { command } => {
+                let mut cmd = Command::new("sh");
+                cmd.arg("-c").arg(command);
+                run(cmd, ctx).await
+            }
+            Check::ResponseMatches { pattern } => match regex::Regex::new(pattern) {
+                Ok(re) if re.is_match(ctx.response) => Ok(()),
+                Ok(_) => Err(format!("response does not match: {}", truncate(ctx.response))),
+                Err(e) => Err(format!("invalid pattern: {}", e)),
+            },
+            Check::Script { path } => {
+                let mut cmd = Command::new("sh");
+                cmd.arg(ctx.fixture_dir.join(path));
+                run(cmd, ctx).await
+            }
+        };
+
+        CheckOutcome {
+            check: self.describe(),
+            passed: result.is_ok(),
+            detail: result.err(),
+        }
+    }
+}
+
+/// 作業ディレクトリでコマンドを実行し、終了コード0なら Ok
+async fn run(mut cmd: Command, ctx: &CheckContext<'_>) -> Result<(), String> {
+    cmd.current_dir(ctx.workdir)
+        .env(RESPONSE_ENV, ctx.response_file)
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true);
+
+    let output = match tokio::time::timeout(ctx.timeout, cmd.output()).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => return Err(format!("failed to run: {}", e)),
+        Err(_) => return Err(format!("timed out after {} seconds", ctx.timeout.as_secs())),
+    };
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let mut combined = String::from_utf8_lossy(&output.stdout).into_owned();
+    combined.push_str(&String::from_utf8_lossy(&output.stderr));
+    Err(format!(
+        "exited with {}: {}",
+        output.status.code().map(|c| c.to_string()).unwrap_or_else(|| "signal".to_string()),
+        truncate(combined.trim())
+    ))
+}
+
+/// 末尾を残して切り詰める（失敗理由は出力の最後にあることが多い）
+fn truncate(text: &str) -> String {
+    let count = text.chars().count();
+    if count <= MAX_DETAIL_CHARS {
+        return text.to_string();
+    }
+    let tail: String = text.chars().skip(count - MAX_DETAIL_CHARS).collect();
+    format!("…{}", tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    async fn evaluate(check: Check, workdir: &Path, response: &str) -> CheckOutcome {
+        let response_file = workdir.join("response.txt");
+        std::fs::write(&response_file, response).unwrap();
+        let ctx = CheckContext {
+            workdir,
+            fixture_dir: workdir,
+            response,
+            response_file: &response_file,
+            timeout: Duration::from_secs(5),
+        };
+        check.evaluate(&ctx).await
+    }
+
+    #[tokio::test]
+    async fn test_file_checks() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("out.txt"), "hello eval").unwrap();
+
+        let exists = Check::FileExists { path: "out.txt".to_string() };
+        assert!(evaluate(exists, dir.path(), "").await.passed);
+
+        let missing = Check::FileExists { path: "nope.txt".to_string() };
+        let outcome = evaluate(missing, dir.path(), "").await;
+        assert!(!outcome.passed);
+        assert_eq!(outcome.detail.as_deref(), Some("nope.txt does not exist"));
+
+        let contains = Check::FileContains {
+            path: "out.txt".to_string(),
+            contains: "eval".to_string(),
+        };
+        assert!(evaluate(contains, dir.path(), "").await.passed);
+        let wrong = Check::FileContains {
+            path: "out.txt".to_string(),
+            contains: "bye".to_string(),
+        };
+        assert!(!evaluate(wrong, dir.path(), "").await.passed);
+    }
+
+    #[tokio::test]
+    async fn test_command_and_script_checks() {
+        let dir = tempdir().unwrap();
+        let ok = Check::Command { command: "test -d .".to_string() };
+        assert!(evaluate(ok, dir.path(), "").await.passed);
+
+        let failing = Check::Command { command: "echo broken >&2; exit 3".to_string() };
+        let outcome = evaluate(failing, dir.path(), "").await;
+        assert!(!outcome.passed);
+        assert_eq!(outcome.detail.as_deref(), Some("exited with 3: broken"));
+
+        // スクリプトには応答ファイルのパスが渡される
+        std::fs::write(dir.path().join("check.sh"), "grep -q finished \"$LOCAL_CODE_EVAL_RESPONSE\"").unwrap();
+        let script = Check::Script { path: "check.sh".to_string() };
+        assert!(evaluate(script.clone(), dir.path(), "all finished").await.passed);
+        assert!(!evaluate(script, dir.path(), "gave up").await.passed);
+    }
+
+    #[tokio::test]
+    async fn test_response_matches() {
+        let dir = tempdir().unwrap();
+        let check = Check::ResponseMatches { pattern: "(?i)^done".to_string() };
+        assert!(evaluate(check.clone(), dir.path(), "Done.").await.passed);
+        assert!(!evaluate(check, dir.path(), "not done").await.passed);
+
+        let invalid = Check::ResponseMatches { pattern: "(".to_string() };
+        let outcome = evaluate(invalid, dir.path(), "").await;
+        assert!(outcome.detail.unwrap().starts_with("invalid pattern"));
+    }
+}
@@ -0,0 +1,204 @@
+//! 評価フィクスチャの読み込み
+//!
+//! フィクスチャは次のファイルを持つディレクトリ:
+//! - `task.md`: エージェントに渡すプロンプト（必須）
+//! - `project/`: 開始時のプロジェクト（任意、一時ディレクトリにコピーされる）
+//! - `expect.toml`: チェックとタイムアウトの指定（任意）
+//! - `check.sh`: 作業ディレクトリで実行し、終了コード0で合格とするスクリプト（任意）
+//!
+//! `expect.toml` と `check.sh` の少なくとも一方が必要
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::check::Check;
+
+/// プロンプトのファイル名
+pub const TASK_FILE: &str = "task.md";
+/// 開始時プロジェクトのディレクトリ名
+pub const PROJECT_DIR: &str = "project";
+/// チェック指定のファイル名
+pub const EXPECT_FILE: &str = "expect.toml";
+/// チェックスクリプトのファイル名
+pub const CHECK_SCRIPT: &str = "check.sh";
+
+/// expect.toml の内容
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExpectFile {
+    /// このフィクスチャのタイムアウト（秒）
+    timeout_secs: Option<u64>,
+    #[serde(default, rename = "check")]
+    checks: Vec<Check>,
+}
+
+/// 評価フィクスチャ
+#[derive(Debug, Clone)]
+pub struct Fixture {
+    /// フィクスチャ名（ディレクトリ名）
+    pub name: String,
+    /// フィクスチャのディレクトリ
+    pub dir: PathBuf,
+    /// プロンプト
+    pub task: String,
+    /// 開始時プロジェクト
+    pub project: Option<PathBuf>,
+    /// チェック（expect.toml の順、check.sh は最後）
+    pub checks: Vec<Check>,
+    /// タイムアウト（未指定なら実行時の既定値）
+    pub timeout: Option<Duration>,
+}
+
+impl Fixture {
+    /// ディレクトリからフィクスチャを読み込む
+    pub fn load(dir: &Path) -> Result<Self> {
+        let name = dir
+            .file_name()
+            .and_then(|s| s.to_str())
+            .context("Fixture directory has no name")?
+            .to_string();
+
+        let task_path = dir.join(TASK_FILE);
+        let task = std::fs::read_to_string(&task_path)
+            .with_context(|| format!("Failed to read {}", task_path.display()))?;
+        if task.trim().is_empty() {
+            anyhow::bail!("{} is empty", task_path.display());
+        }
+
+        let expect_path = dir.join(EXPECT_FILE);
+        let expect: ExpectFile = if expect_path.exists() {
+            let content = std::fs::read_to_string(&expect_path)
+                .with_context(|| format!("Failed to read {}", expect_path.display()))?;
+            toml::from_str(&content)
+                .with_context(|| format!("Failed to parse {}", expect_path.display()))?
+        } else {
+            ExpectFile::default()
+        };
+
+        let mut checks = expect.checks;
+        if dir.join(CHECK_SCRIPT).is_file() {
+            checks.push(Check::Script {
+                path: CHECK_SCRIPT.to_string(),
+            });
+        }
+        if checks.is_empty() {
+            anyhow::bail!(
+                "Fixture '{}' has no checks (add {} or {})",
+                name,
+                EXPECT_FILE,
+                CHECK_SCRIPT
+            );
+        }
+
+        let project = Some(dir.join(PROJECT_DIR)).filter(|p| p.is_dir());
+
+        Ok(Self {
+            name,
+            dir: dir.to_path_buf(),
+            task,
+            project,
+            checks,
+            timeout: expect.timeout_secs.map(Duration::from_secs),
+        })
+    }
+}
+
+/// スイートディレクトリ直下の（task.md を持つ）フィクスチャを名前順に読み込む
+pub fn load_suite(suite_dir: &Path) -> Result<Vec<Fixture>> {
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(suite_dir)
+        .with_context(|| format!("Failed to read suite directory: {}", suite_dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.join(TASK_FILE).is_file())
+        .collect();
+    dirs.sort();
+
+    if dirs.is_empty() {
+        anyhow::bail!("No fixtures (directories with {}) in {}", TASK_FILE, suite_dir.display());
+    }
+
+    dirs.iter().map(|dir| Fixture::load(dir)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_load_fixture_with_expect_and_script() {
+        let dir = tempdir().unwrap();
+        let fixture_dir = dir.path().join("add_fn");
+        std::fs::create_dir_all(fixture_dir.join(PROJECT_DIR)).unwrap();
+        std::fs::write(fixture_dir.join(TASK_FILE), "Add a function").unwrap();
+        std::fs::write(
+            fixture_dir.join(EXPECT_FILE),
+            r#"
+timeout_secs = 60
+
+[[check]]
+type = "file_contains"
+path = "src/lib.rs"
+contains = "fn add"
+
+[[check]]
+type = "response_matches"
+pattern = "(?i)done"
+"#,
+        )
+        .unwrap();
+        std::fs::write(fixture_dir.join(CHECK_SCRIPT), "exit 0").unwrap();
+
+        let fixture = Fixture::load(&fixture_dir).unwrap();
+        assert_eq!(fixture.name, "add_fn");
+        assert_eq!(fixture.timeout, Some(Duration::from_secs(60)));
+        assert_eq!(fixture.project, Some(fixture_dir.join(PROJECT_DIR)));
+        assert_eq!(
+            fixture.checks,
+            vec![
+                Check::FileContains {
+                    path: "src/lib.rs".to_string(),
+                    contains: "fn add".to_string()
+                },
+                Check::ResponseMatches {
+                    pattern: "(?i)done".to_string()
+                },
+                Check::Script {
+                    path: CHECK_SCRIPT.to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fixture_without_checks_is_rejected() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join(TASK_FILE), "Do something").unwrap();
+        let err = Fixture::load(dir.path()).unwrap_err();
+        assert!(err.to_string().contains("has no checks"));
+    }
+
+    #[test]
+    fn test_unknown_check_type_is_an_error() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join(TASK_FILE), "Do something").unwrap();
+        std::fs::write(dir.path().join(EXPECT_FILE), "[[check]]\ntype = \"psychic\"\n").unwrap();
+        assert!(Fixture::load(dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_load_suite_skips_non_fixtures() {
+        let dir = tempdir().unwrap();
+        for name in ["b", "a"] {
+            let fixture = dir.path().join(name);
+            std::fs::create_dir(&fixture).unwrap();
+            std::fs::write(fixture.join(TASK_FILE), "task").unwrap();
+            std::fs::write(fixture.join(CHECK_SCRIPT), "exit 0").unwrap();
+        }
+        std::fs::create_dir(dir.path().join("results")).unwrap();
+
+        let names: Vec<String> = load_suite(dir.path()).unwrap().into_iter().map(|f| f.name).collect();
+        assert_eq!(names, vec!["a", "b"]);
+    }
+}
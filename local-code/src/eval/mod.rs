@@ -0,0 +1,13 @@
+//! 評価ハーネス（`local-code eval`）
+//!
+//! フィクスチャのスイートをモデルごとに実行し、結果を JSON で比較する
+
+pub mod check;
+pub mod fixture;
+pub mod report;
+pub mod runner;
+
+pub use check::{Check, CheckOutcome};
+pub use fixture::{load_suite, Fixture};
+pub use report::{diff_reports, EvalReport, FixtureResult, ReportDiff};
+pub use runner::{run_fixture, run_suite, EvalOptions};
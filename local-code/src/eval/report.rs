@@ -0,0 +1,273 @@
+//! 評価結果のレポートと比較
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use super::check::CheckOutcome;
+
+/// 1フィクスチャの結果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FixtureResult {
+    pub name: String,
+    pub passed: bool,
+    /// エージェント実行とチェックにかかった時間
+    pub duration_ms: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// 実行したツール呼び出しの数
+    pub tool_calls: usize,
+    pub checks: Vec<CheckOutcome>,
+    /// エージェントが失敗・タイムアウトした場合の理由
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// スイート全体の結果（実行間で比較できる JSON）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalReport {
+    /// スイートのディレクトリ
+    pub suite: String,
+    pub model: String,
+    /// 開始時刻（RFC 3339）
+    pub started_at: String,
+    pub results: Vec<FixtureResult>,
+}
+
+impl EvalReport {
+    /// 合格したフィクスチャ数
+    pub fn passed(&self) -> usize {
+        self.results.iter().filter(|r| r.passed).count()
+    }
+
+    /// すべて合格したか
+    pub fn all_passed(&self) -> bool {
+        self.passed() == self.results.len()
+    }
+
+    /// 結果の表
+    pub fn to_table(&self) -> String {
+        let name_width = self
+            .results
+            .iter()
+            .map(|r| r.name.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max("fixture".len());
+
+        let mut out = format!(
+            "{:<width$}  {:<6}  {:>9}  {:>8}  {:>6}\n",
+            "fixture",
+            "result",
+            "time",
+            "tokens",
+            "tools",
+            width = name_width
+        );
+        for result in &self.results {
+            out.push_str(&format!(
+                "{:<width$}  {:<6}  {:>8.1}s  {:>8}  {:>6}\n",
+                result.name,
+                if result.passed { "PASS" } else { "FAIL" },
+                result.duration_ms as f64 / 1000.0,
+                result.prompt_tokens + result.completion_tokens,
+                result.tool_calls,
+                width = name_width
+            ));
+            if let Some(error) = &result.error {
+                out.push_str(&format!("    error: {}\n", error));
+            }
+            for check in result.checks.iter().filter(|c| !c.passed) {
+                out.push_str(&format!(
+                    "    ✗ {}: {}\n",
+                    check.check,
+                    check.detail.as_deref().unwrap_or("failed")
+                ));
+            }
+        }
+        out.push_str(&format!(
+            "\n{}/{} passed (model: {})",
+            self.passed(),
+            self.results.len(),
+            self.model
+        ));
+        out
+    }
+
+    /// JSON で保存
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// JSON から読み込む
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&content).with_context(|| format!("Invalid eval report: {}", path.display()))
+    }
+}
+
+/// 2つのレポートの差分
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ReportDiff {
+    /// 合格→不合格
+    pub regressions: Vec<String>,
+    /// 不合格→合格
+    pub fixes: Vec<String>,
+    /// 後のレポートにだけあるフィクスチャ
+    pub added: Vec<String>,
+    /// 前のレポートにだけあるフィクスチャ
+    pub removed: Vec<String>,
+    /// 両方にあるフィクスチャの (名前, 前の時間, 後の時間, 前のトークン, 後のトークン)
+    pub changes: Vec<(String, u64, u64, u64, u64)>,
+}
+
+/// 前後のレポートを比較
+pub fn diff_reports(before: &EvalReport, after: &EvalReport) -> ReportDiff {
+    let before_map: BTreeMap<&str, &FixtureResult> =
+        before.results.iter().map(|r| (r.name.as_str(), r)).collect();
+    let after_map: BTreeMap<&str, &FixtureResult> =
+        after.results.iter().map(|r| (r.name.as_str(), r)).collect();
+
+    let mut diff = ReportDiff::default();
+    for (name, a) in &after_map {
+        let Some(b) = before_map.get(name) else {
+            diff.added.push(name.to_string());
+            continue;
+        };
+        match (b.passed, a.passed) {
+            (true, false) => diff.regressions.push(name.to_string()),
+            (false, true) => diff.fixes.push(name.to_string()),
+            _ => {}
+        }
+        diff.changes.push((
+            name.to_string(),
+            b.duration_ms,
+            a.duration_ms,
+            b.prompt_tokens + b.completion_tokens,
+            a.prompt_tokens + a.completion_tokens,
+        ));
+    }
+    diff.removed = before_map
+        .keys()
+        .filter(|name| !after_map.contains_key(*name))
+        .map(|name| name.to_string())
+        .collect();
+    diff
+}
+
+impl ReportDiff {
+    /// 表示用テキスト
+    pub fn to_display(&self) -> String {
+        let mut out = String::new();
+        for (label, names) in [
+            ("Regressions", &self.regressions),
+            ("Fixed", &self.fixes),
+            ("New fixtures", &self.added),
+            ("Missing fixtures", &self.removed),
+        ] {
+            if !names.is_empty() {
+                out.push_str(&format!("{}: {}\n", label, names.join(", ")));
+            }
+        }
+        if !self.changes.is_empty() {
+            out.push('\n');
+            for (name, before_ms, after_ms, before_tokens, after_tokens) in &self.changes {
+                out.push_str(&format!(
+                    "{}: {:.1}s → {:.1}s, {} → {} tokens\n",
+                    name,
+                    *before_ms as f64 / 1000.0,
+                    *after_ms as f64 / 1000.0,
+                    before_tokens,
+                    after_tokens
+                ));
+            }
+        }
+        if self.regressions.is_empty() && self.fixes.is_empty() {
+            out.push_str("\nNo pass/fail changes");
+        }
+        out.trim().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(name: &str, passed: bool, duration_ms: u64) -> FixtureResult {
+        FixtureResult {
+            name: name.to_string(),
+            passed,
+            duration_ms,
+            prompt_tokens: 10,
+            completion_tokens: 5,
+            tool_calls: 1,
+            checks: Vec::new(),
+            error: None,
+        }
+    }
+
+    fn report(results: Vec<FixtureResult>) -> EvalReport {
+        EvalReport {
+            suite: "suite".to_string(),
+            model: "m".to_string(),
+            started_at: "2026-01-01T00:00:00Z".to_string(),
+            results,
+        }
+    }
+
+    #[test]
+    fn test_diff_reports() {
+        let before = report(vec![
+            result("a", true, 1000),
+            result("b", false, 2000),
+            result("c", true, 100),
+            result("gone", true, 100),
+        ]);
+        let after = report(vec![
+            result("a", false, 1500),
+            result("b", true, 2000),
+            result("c", true, 100),
+            result("new", true, 100),
+        ]);
+
+        let diff = diff_reports(&before, &after);
+        assert_eq!(diff.regressions, vec!["a"]);
+        assert_eq!(diff.fixes, vec!["b"]);
+        assert_eq!(diff.added, vec!["new"]);
+        assert_eq!(diff.removed, vec!["gone"]);
+        assert_eq!(diff.changes[0], ("a".to_string(), 1000, 1500, 15, 15));
+        assert!(diff.to_display().starts_with("Regressions: a\nFixed: b"));
+    }
+
+    #[test]
+    fn test_report_round_trip_and_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested/report.json");
+        let mut failing = result("broken", false, 1234);
+        failing.checks.push(CheckOutcome {
+            check: "file exists: out.txt".to_string(),
+            passed: false,
+            detail: Some("out.txt does not exist".to_string()),
+        });
+        let original = report(vec![result("ok", true, 500), failing]);
+
+        original.save(&path).unwrap();
+        let loaded = EvalReport::load(&path).unwrap();
+        assert_eq!(loaded.results, original.results);
+
+        let table = loaded.to_table();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0].split_whitespace().collect::<Vec<_>>(), ["fixture", "result", "time", "tokens", "tools"]);
+        assert_eq!(lines[1].split_whitespace().collect::<Vec<_>>(), ["ok", "PASS", "0.5s", "15", "1"]);
+        assert_eq!(lines[2].split_whitespace().collect::<Vec<_>>(), ["broken", "FAIL", "1.2s", "15", "1"]);
+        assert!(table.contains("✗ file exists: out.txt: out.txt does not exist"));
+        assert!(table.ends_with("1/2 passed (model: m)"));
+    }
+}
@@ -0,0 +1,282 @@
+//! フィクスチャの実行
+
+use anyhow::{Context, Result};
+use futures::stream::{self, StreamExt};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::check::CheckContext;
+use super::fixture::Fixture;
+use super::report::{EvalReport, FixtureResult};
+use crate::agent::{Agent, AgentConfig, AutoApprove, Mode, ModeManager, Role, ToolConfirmation};
+use crate::config::OllamaConfig;
+use crate::skills::SkillRegistry;
+use crate::tools::bash::BashTool;
+use crate::tools::file::{ApplyPatchTool, EditTool, ReadTool, WriteTool};
+use crate::tools::git::{GitAddTool, GitCommitTool, GitDiffTool, GitLogTool, GitStatusTool};
+use crate::tools::search::{GlobTool, GrepTool};
+use crate::tools::{PathPolicy, ToolRegistry, Workspace};
+
+/// 評価の実行設定
+#[derive(Debug, Clone)]
+pub struct EvalOptions {
+    /// 接続先とモデル（実行ごとに差し替える）
+    pub ollama: OllamaConfig,
+    /// フィクスチャごとのタイムアウト（expect.toml の指定が優先）
+    pub timeout: Duration,
+    /// 同時に実行するフィクスチャ数
+    pub parallel: usize,
+    /// 会話履歴の最大メッセージ数
+    pub max_messages: usize,
+}
+
+impl EvalOptions {
+    pub fn new(ollama: OllamaConfig) -> Self {
+        Self {
+            ollama,
+            timeout: Duration::from_secs(300),
+            parallel: 1,
+            max_messages: 100,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_parallel(mut self, parallel: usize) -> Self {
+        self.parallel = parallel.max(1);
+        self
+    }
+}
+
+/// スイートを実行してレポートを作る（結果はフィクスチャの順）
+pub async fn run_suite(suite: &Path, fixtures: &[Fixture], options: &EvalOptions) -> EvalReport {
+    let started_at = chrono::Utc::now().to_rfc3339();
+    let results = stream::iter(fixtures.iter().map(|fixture| run_fixture(fixture, options)))
+        .buffered(options.parallel)
+        .collect()
+        .await;
+
+    EvalReport {
+        suite: suite.display().to_string(),
+        model: options.ollama.model.clone(),
+        started_at,
+        results,
+    }
+}
+
+/// 1フィクスチャを一時ディレクトリで実行し、チェックを適用する
+pub async fn run_fixture(fixture: &Fixture, options: &EvalOptions) -> FixtureResult {
+    let start = Instant::now();
+    let mut result = FixtureResult {
+        name: fixture.name.clone(),
+        passed: false,
+        duration_ms: 0,
+        prompt_tokens: 0,
+        completion_tokens: 0,
+        tool_calls: 0,
+        checks: Vec::new(),
+        error: None,
+    };
+
+    if let Err(e) = run_in_sandbox(fixture, options, &mut result).await {
+        result.error = Some(format!("{:#}", e));
+    }
+
+    result.passed = result.error.is_none() && result.checks.iter().all(|c| c.passed);
+    result.duration_ms = start.elapsed().as_millis() as u64;
+    result
+}
+
+async fn run_in_sandbox(fixture: &Fixture, options: &EvalOptions, result: &mut FixtureResult) -> Result<()> {
+    let sandbox = tempfile::tempdir().context("Failed to create a temporary directory")?;
+    let workdir = sandbox.path().join("work");
+    match &fixture.project {
+        Some(project) => copy_dir(project, &workdir)
+            .with_context(|| format!("Failed to copy {}", project.display()))?,
+        None => std::fs::create_dir(&workdir)?,
+    }
+
+    let tools = fixture_tools(&workdir);
+    let mode = ModeManager::new(Mode::Execute).with_tool_effects(tools.effects());
+    let config = AgentConfig::from_ollama_config(&options.ollama, Mode::Execute, options.max_messages);
+    let mut agent = Agent::new(config, tools, Arc::new(SkillRegistry::new()), mode);
+    // 非対話モードの --yes と同じく、確認が必要なツールも尋ねずに許可する（一時ディレクトリの中だけなので）
+    agent.set_confirmation(Arc::new(ToolConfirmation::new(Arc::new(AutoApprove))));
+    agent.load_context(&workdir).await?;
+
+    let timeout = fixture.timeout.unwrap_or(options.timeout);
+    let outcome = tokio::time::timeout(timeout, agent.process(&fixture.task)).await;
+
+    let usage = agent.llm().token_usage();
+    result.prompt_tokens = usage.prompt_tokens;
+    result.completion_tokens = usage.completion_tokens;
+    result.tool_calls = agent
+        .conversation()
+        .messages()
+        .iter()
+        .filter(|m| m.role == Role::Tool)
+        .count();
+
+    let response = match outcome {
+        Ok(response) => response?,
+        Err(_) => anyhow::bail!("timed out after {} seconds", timeout.as_secs()),
+    };
+
+    let response_file = sandbox.path().join("response.txt");
+    std::fs::write(&response_file, &response)?;
+    let ctx = CheckContext {
+        workdir: &workdir,
+        fixture_dir: &fixture.dir,
+        response: &response,
+        response_file: &response_file,
+        timeout,
+    };
+    for check in &fixture.checks {
+        result.checks.push(check.evaluate(&ctx).await);
+    }
+    Ok(())
+}
+
+/// 作業ディレクトリに閉じたツール一式（上書き確認なし）
+///
+/// 相対パスは作業ディレクトリから解決し、その外のパスは読み書きしない
+fn fixture_tools(workdir: &Path) -> ToolRegistry {
+    let workspace = Arc::new(Workspace::single(workdir));
+    let policy = Arc::new(PathPolicy::new(workdir));
+    let mut tools = ToolRegistry::new();
+    tools.register(Arc::new(ReadTool::new().with_workspace(Arc::clone(&workspace)).with_path_policy(Arc::clone(&policy))));
+    tools.register(Arc::new(WriteTool::new().with_workspace(Arc::clone(&workspace)).with_path_policy(Arc::clone(&policy))));
+    tools.register(Arc::new(EditTool::new().with_workspace(Arc::clone(&workspace)).with_path_policy(Arc::clone(&policy))));
+    tools.register(Arc::new(
+        ApplyPatchTool::new(workdir).with_workspace(Arc::clone(&workspace)).with_path_policy(Arc::clone(&policy)),
+    ));
+    tools.register(Arc::new(GlobTool::new().with_workspace(Arc::clone(&workspace)).with_path_policy(Arc::clone(&policy))));
+    tools.register(Arc::new(GrepTool::new().with_workspace(workspace).with_path_policy(policy)));
+    tools.register(Arc::new(BashTool::with_timeout(120, workdir)));
+    tools.register(Arc::new(GitStatusTool::new().with_root(workdir)));
+    tools.register(Arc::new(GitDiffTool::new().with_root(workdir)));
+    tools.register(Arc::new(GitAddTool::new().with_root(workdir)));
+    tools.register(Arc::new(GitCommitTool::new().with_root(workdir)));
+    tools.register(Arc::new(GitLogTool::new().with_root(workdir)));
+    tools
+}
+
+/// ディレクトリを再帰的にコピー
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::load_suite;
+    use crate::llm::mock_server::{http_response, spawn_mock_handler};
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn sample_suite() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/eval")
+    }
+
+    /// サンプルフィクスチャのプロンプトに応じて決まった応答を返すモック
+    async fn mock_backend() -> String {
+        spawn_mock_handler(|request| {
+            let response = if request.contains("eval-write-probe.txt") {
+                "Writing it.\n```json\n{\"tool\": \"write\", \"params\": {\"file_path\": \"eval-write-probe.txt\", \"content\": \"written by the write tool\\n\"}}\n```"
+            } else if request.contains("hello.txt") {
+                "Creating the file.\n```json\n{\"tool\": \"bash\", \"params\": {\"command\": \"echo hello eval > hello.txt\"}}\n```"
+            } else if request.contains("capital of France") {
+                "The capital of France is Paris."
+            } else {
+                "I don't know."
+            };
+            let body = json!({
+                "model": "mock",
+                "response": response,
+                "done": true,
+                "prompt_eval_count": 20,
+                "eval_count": 7
+            });
+            http_response("200 OK", &[], &body.to_string())
+        })
+        .await
+    }
+
+    fn options(url: String) -> EvalOptions {
+        let ollama = OllamaConfig {
+            url,
+            model: "mock".to_string(),
+            ..OllamaConfig::default()
+        };
+        EvalOptions::new(ollama)
+            .with_timeout(Duration::from_secs(30))
+            .with_parallel(2)
+    }
+
+    #[tokio::test]
+    async fn test_sample_suite_passes_against_mock_backend() {
+        let url = mock_backend().await;
+        let fixtures = load_suite(&sample_suite()).unwrap();
+        let report = run_suite(&sample_suite(), &fixtures, &options(url)).await;
+
+        assert!(report.all_passed(), "{}", report.to_table());
+        let names: Vec<&str> = report.results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["answer_question", "create_file", "write_file"]);
+
+        let create = &report.results[1];
+        assert_eq!(create.tool_calls, 1);
+        assert_eq!(create.prompt_tokens, 20);
+        assert_eq!(create.completion_tokens, 7);
+        // フィクスチャのディレクトリ自体は変更されない
+        assert!(!sample_suite().join("create_file/hello.txt").exists());
+
+        // write ツールの相対パスは一時ディレクトリに書かれ、カレントディレクトリには書かれない
+        let write = &report.results[2];
+        assert_eq!(write.tool_calls, 1);
+        assert!(write.checks.iter().all(|c| c.passed), "{:?}", write.checks);
+        assert!(!Path::new("eval-write-probe.txt").exists());
+        assert!(!sample_suite().join("write_file/eval-write-probe.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_failed_checks_and_timeouts_are_reported() {
+        let url = mock_backend().await;
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("task.md"), "Say something").unwrap();
+        std::fs::write(
+            dir.path().join("expect.toml"),
+            "[[check]]\ntype = \"file_exists\"\npath = \"missing.txt\"\n",
+        )
+        .unwrap();
+        let fixture = Fixture::load(dir.path()).unwrap();
+
+        let result = run_fixture(&fixture, &options(url)).await;
+        assert!(!result.passed);
+        assert!(result.error.is_none());
+        assert_eq!(result.checks[0].detail.as_deref(), Some("missing.txt does not exist"));
+
+        // 応答しないサーバーはタイムアウトになる
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let silent = format!("http://{}", listener.local_addr().unwrap());
+        let mut slow = fixture.clone();
+        slow.timeout = Some(Duration::from_millis(200));
+        let result = run_fixture(&slow, &options(silent)).await;
+        assert!(!result.passed);
+        assert!(result.checks.is_empty());
+        assert!(result.error.unwrap().starts_with("timed out"));
+    }
+}
@@ -6,15 +6,16 @@
 pub mod agent;
 pub mod cli;
 pub mod config;
+pub mod eval;
 pub mod llm;
 pub mod skills;
 pub mod tools;
 
 // 主要な型の再エクスポート
-pub use agent::{Agent, AgentConfig, AgentContext, Conversation, Message, Mode, ModeManager, Role, CodeVerifier, VerificationResult};
+pub use agent::{Agent, AgentConfig, AgentContext, Conversation, Message, Mode, ModeManager, Role, CodeVerifier, VerificationPipeline, VerificationResult};
 pub use cli::{Command, CommandHandler, CommandResult, Repl};
 pub use config::{Config, OllamaConfig, AgentConfig as ConfigAgentConfig, ToolsConfig, SkillsConfig, LspConfig};
-pub use llm::{OllamaClient, StreamingResponse, ToolCall, ToolCallParser};
+pub use llm::{LlmBackend, OllamaClient, OpenAiCompatClient, StreamingResponse, ToolCall, ToolCallParser};
 pub use skills::{Skill, SkillExecutor, SkillMetadata, SkillRegistry, TriggerDetector};
 pub use tools::{Tool, ToolDefinition, ToolRegistry, ToolResult};
 
@@ -26,3 +27,50 @@ pub const DEFAULT_OLLAMA_URL: &str = "http://localhost:11434";
 
 /// デフォルトのモデル名
 pub const DEFAULT_MODEL: &str = "Rnj-1";
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    /// CLI層（src/cli/, src/main.rs）以外でstdout/stderrに直接書き込んでいないか検査
+    #[test]
+    fn test_library_modules_do_not_print_directly() {
+        let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let mut offenders = Vec::new();
+        collect_print_calls(&src, &src, &mut offenders);
+        assert!(offenders.is_empty(), "direct stdout/stderr writes outside CLI layer: {:?}", offenders);
+    }
+
+    fn collect_print_calls(root: &Path, dir: &Path, offenders: &mut Vec<String>) {
+        for entry in std::fs::read_dir(dir).unwrap().flatten() {
+            let path = entry.path();
+            let relative = path.strip_prefix(root).unwrap().to_string_lossy().replace('\\', "/");
+            if relative == "cli" || relative == "main.rs" {
+                continue;
+            }
+            if path.is_dir() {
+                collect_print_calls(root, &path, offenders);
+                continue;
+            }
+            if path.extension().and_then(|s| s.to_str()) != Some("rs") {
+                continue;
+            }
+
+            let content = std::fs::read_to_string(&path).unwrap();
+            // テストモジュールは対象外
+            let code = content.split("#[cfg(test)]").next().unwrap_or("");
+            for (i, line) in code.lines().enumerate() {
+                let trimmed = line.trim_start();
+                if trimmed.starts_with("//") {
+                    continue;
+                }
+                if ["println!", "eprintln!", "print!(", "eprint!(", "io::stdout()", "io::stderr()"]
+                    .iter()
+                    .any(|p| trimmed.contains(p))
+                {
+                    offenders.push(format!("{}:{}", relative, i + 1));
+                }
+            }
+        }
+    }
+}
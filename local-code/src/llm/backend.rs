@@ -0,0 +1,93 @@
+//! LLM バックエンドの共通インターフェース
+//!
+//! `[llm] provider` で Ollama と OpenAI 互換サーバー（llama.cpp、LM Studio など）を切り替える。
+//! エージェントはこのトレイトだけを通して生成を依頼する
+
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+
+use super::client::{RequestOptions, RetryEvent, TokenUsage};
+use super::context_window::CtxDecision;
+use super::health::HealthStatus;
+use super::streaming::{StreamStats, StreamingResponse};
+use crate::agent::session::SessionResettable;
+use crate::agent::status::StatusProvider;
+
+/// 生成を担うバックエンド
+#[async_trait]
+pub trait LlmBackend: Send + Sync {
+    /// プロバイダー名（"ollama" / "openai"）
+    fn provider(&self) -> &'static str;
+
+    /// 使用中のモデル名
+    fn model(&self) -> &str;
+
+    /// モデルを変更（/model）
+    fn set_model(&mut self, model: String);
+
+    /// サーバーの URL
+    fn base_url(&self) -> &str;
+
+    /// 生成して、応答と統計情報（取れた場合）を返す（リトライ付き）
+    async fn generate_with_stats(&self, prompt: &str, system: Option<&str>) -> Result<(String, Option<StreamStats>)>;
+
+    /// 生成して応答だけを返す
+    async fn generate(&self, prompt: &str, system: Option<&str>) -> Result<String> {
+        Ok(self.generate_with_stats(prompt, system).await?.0)
+    }
+
+    /// オプションを指定して生成（未指定の項目は生成オプションに従う）
+    async fn generate_with_options(&self, prompt: &str, system: Option<&str>, options: RequestOptions) -> Result<String>;
+
+    /// トークン単位で受信する生成
+    async fn generate_streaming(&self, prompt: &str, system: Option<&str>) -> Result<StreamingResponse>;
+
+    /// モデルを読み込んでおく（読み込みを制御できないバックエンドは何もせず false）
+    async fn warm_up(&self) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// サーバーとモデルの有無の診断（起動時、対応するバックエンドのみ）
+    async fn health_check(&self) -> Option<HealthStatus> {
+        None
+    }
+
+    /// サーバーにあるモデル名の一覧
+    async fn list_models(&self) -> Result<Vec<String>>;
+
+    /// 毎回のリクエストに付ける生成オプション
+    fn options(&self) -> &RequestOptions;
+
+    /// 生成オプションを変更（/set）
+    fn options_mut(&mut self) -> &mut RequestOptions;
+
+    /// リトライ状況の通知先を設定
+    fn set_status_sender(&mut self, tx: Option<UnboundedSender<RetryEvent>>);
+
+    /// リトライ待機を打ち切るトークンを設定（None で解除）
+    fn set_cancel_token(&mut self, cancel: Option<CancellationToken>);
+
+    /// サーバーが報告したトークン数の累計
+    fn token_usage(&self) -> TokenUsage;
+
+    /// サーバーとモデルの状態を報告するプロバイダー（/status）
+    fn status_provider(&self) -> Arc<dyn StatusProvider>;
+
+    /// コンテキストの使用状況を報告するプロバイダー（num_ctx を扱うバックエンドのみ）
+    fn context_status_provider(&self) -> Option<Arc<dyn StatusProvider>> {
+        None
+    }
+
+    /// 直近のリクエストでの num_ctx の決定
+    fn last_ctx_decision(&self) -> Option<CtxDecision> {
+        None
+    }
+
+    /// セッション遷移時にリセットする状態
+    fn session_state(&self) -> Option<Arc<dyn SessionResettable>> {
+        None
+    }
+}
@@ -1,16 +1,30 @@
 //! OLLAMAクライアント - エラーリトライ機能付き
 //!
 //! 接続エラー時の自動リトライ（エクスポネンシャルバックオフ）をサポート
+//! モデルロード中・混雑中のレスポンスは個別に判定し、待機状況を通知する
 //! ストリーミング出力にも対応
 
-use anyhow::{Context, Result};
-use reqwest::Client;
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
 use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
 
+use crate::agent::session::{ResetAction, ResetTarget, SessionResettable};
+use crate::agent::status::StatusProvider;
 use crate::config::{OllamaConfig, RetryConfig};
-use super::streaming::{generate_streaming as streaming_impl, StreamingResponse};
+use super::context_window::{
+    estimate_tokens, parse_context_length, ContextNegotiator, CtxDecision, DEFAULT_MAX_NUM_CTX,
+};
+use super::backend::LlmBackend;
+use super::health::{check_health, fetch_models, ContextStatus, HealthStatus, LlmHealth, LlmStatus, ModelsApi};
+use super::streaming::{generate_streaming as streaming_impl, StreamStats, StreamingResponse};
 
 /// リトライ可能なエラーの種類
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -21,6 +35,12 @@ pub enum RetryableError {
     Timeout,
     /// サーバーエラー（5xx）
     ServerError,
+    /// モデルをロード中
+    Loading,
+    /// サーバーが混雑中（キュー満杯など）
+    Busy,
+    /// モデルがサーバーにない（リトライしても解決しない）
+    ModelMissing,
     /// リクエストエラー（リトライ不可）
     NonRetryable,
 }
@@ -48,12 +68,44 @@ impl RetryableError {
         }
     }
 
+    /// HTTPステータスとレスポンスボディから判定
+    ///
+    /// Ollamaはモデルロード中や同時リクエスト上限超過時に
+    /// `{"error": "..."}` 形式のボディを返すため、本文も確認する
+    pub fn from_status(status: StatusCode, body: &str) -> Self {
+        let body = body.to_lowercase();
+        if body.contains("loading model") || body.contains("model is loading") || body.contains("model loading") {
+            RetryableError::Loading
+        } else if body.contains("model") && body.contains("not found") {
+            RetryableError::ModelMissing
+        } else if status == StatusCode::TOO_MANY_REQUESTS
+            || body.contains("server busy")
+            || body.contains("maximum pending requests")
+        {
+            RetryableError::Busy
+        } else if status.is_server_error() {
+            RetryableError::ServerError
+        } else {
+            RetryableError::NonRetryable
+        }
+    }
+
     /// リトライ可能かどうか
     pub fn is_retryable(&self) -> bool {
-        matches!(
-            self,
-            RetryableError::Connection | RetryableError::Timeout | RetryableError::ServerError
-        )
+        !matches!(self, RetryableError::NonRetryable | RetryableError::ModelMissing)
+    }
+
+    /// 対処方法（/status に表示）
+    pub fn hint(&self) -> &'static str {
+        match self {
+            RetryableError::Connection => "Start the server with `ollama serve` or check [ollama] url",
+            RetryableError::Timeout => "Raise [ollama] read_timeout or try a smaller model",
+            RetryableError::ServerError => "Check the Ollama server log",
+            RetryableError::Loading => "Wait for the model to finish loading",
+            RetryableError::Busy => "Wait, or raise OLLAMA_NUM_PARALLEL / OLLAMA_MAX_QUEUE on the server",
+            RetryableError::ModelMissing => "Pull it with `ollama pull <model>` or change it with /model",
+            RetryableError::NonRetryable => "Check the model name with `ollama list`",
+        }
     }
 
     /// エラーの説明
@@ -62,9 +114,424 @@ impl RetryableError {
             RetryableError::Connection => "接続エラー",
             RetryableError::Timeout => "タイムアウト",
             RetryableError::ServerError => "サーバーエラー",
+            RetryableError::Loading => "モデルロード中",
+            RetryableError::Busy => "サーバー混雑中",
+            RetryableError::ModelMissing => "モデルが見つかりません",
             RetryableError::NonRetryable => "リクエストエラー",
         }
     }
+
+    /// 機械可読な名前（JSON 出力用）
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RetryableError::Connection => "connection",
+            RetryableError::Timeout => "timeout",
+            RetryableError::ServerError => "server_error",
+            RetryableError::Loading => "loading",
+            RetryableError::Busy => "busy",
+            RetryableError::ModelMissing => "model_missing",
+            RetryableError::NonRetryable => "non_retryable",
+        }
+    }
+}
+
+/// リトライ待機中の状態
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryStatus {
+    /// 待機の原因
+    pub kind: RetryableError,
+    /// 失敗の短い説明（例: `connection refused`、`HTTP 500 Internal Server Error`）
+    pub error: String,
+    /// 何回目のリトライか（1始まり）
+    pub attempt: u32,
+    /// この種類のエラーに対する最大リトライ回数
+    pub max_retries: u32,
+    /// 今回の待機時間
+    pub wait: Duration,
+    /// 待機の残り時間
+    pub remaining: Duration,
+    /// 最初のリクエストからの経過時間
+    pub elapsed: Duration,
+}
+
+impl RetryStatus {
+    /// スピナーに表示するメッセージ
+    pub fn message(&self) -> String {
+        let secs = self.elapsed.as_secs();
+        match self.kind {
+            RetryableError::Loading => format!("model loading on server… {}s", secs),
+            RetryableError::Busy => format!("server busy, waiting in queue… {}s", secs),
+            _ => format!(
+                "{} — retrying in {}s (attempt {}/{})",
+                self.error,
+                ceil_secs(self.remaining),
+                self.attempt,
+                self.max_retries
+            ),
+        }
+    }
+
+    /// 待機の最初の通知か（ログには1回の待機につき1行だけ書く）
+    pub fn is_first_tick(&self) -> bool {
+        self.remaining == self.wait
+    }
+}
+
+/// 秒単位に切り上げる（残り 1.2 秒は「2s」と表示）
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_millis().div_ceil(1000) as u64
+}
+
+/// リトライ状況の通知イベント
+#[derive(Debug, Clone, PartialEq)]
+pub enum RetryEvent {
+    /// 待機中（約1秒ごとに経過時間を更新して送信）
+    Waiting(RetryStatus),
+    /// リトライが終了した（成功・失敗を問わない）
+    Finished,
+}
+
+/// 待機状況の通知間隔
+const STATUS_TICK: Duration = Duration::from_secs(1);
+
+/// 待機したリトライの終了を通知する（途中で破棄されても送る）
+struct FinishNotice<'a> {
+    retrier: &'a Retrier,
+    waited: bool,
+}
+
+impl Drop for FinishNotice<'_> {
+    fn drop(&mut self) {
+        if self.waited {
+            self.retrier.notify(RetryEvent::Finished);
+        }
+    }
+}
+
+/// Retry-After で指定された待機時間の上限
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+
+/// リクエスト失敗の詳細（JSONデコード前に取得）
+#[derive(Debug)]
+pub(crate) enum RequestFailure {
+    /// 送信・受信時のエラー
+    Transport(reqwest::Error),
+    /// エラーステータスのレスポンス
+    Status {
+        status: StatusCode,
+        body: String,
+        retry_after: Option<Duration>,
+    },
+    /// 成功ステータスだが本文が `{"error": ...}`、または応答として読めない
+    Api(String),
+}
+
+impl RequestFailure {
+    fn classify(&self) -> RetryableError {
+        match self {
+            RequestFailure::Transport(e) => RetryableError::from_reqwest_error(e),
+            RequestFailure::Status { status, body, .. } => RetryableError::from_status(*status, body),
+            RequestFailure::Api(message) => RetryableError::from_status(StatusCode::OK, message),
+        }
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            RequestFailure::Status { retry_after, .. } => *retry_after,
+            RequestFailure::Transport(_) | RequestFailure::Api(_) => None,
+        }
+    }
+
+    /// 失敗の短い説明（リトライの表示と最終的なエラーに使う）
+    fn summary(&self) -> String {
+        match self {
+            RequestFailure::Transport(e) => {
+                let chain = std::iter::successors(Some(e as &dyn std::error::Error), |e| e.source())
+                    .map(|e| e.to_string().to_lowercase())
+                    .collect::<Vec<_>>()
+                    .join(": ");
+                if chain.contains("connection refused") {
+                    "connection refused".to_string()
+                } else if chain.contains("connection reset") {
+                    "connection reset".to_string()
+                } else if e.is_timeout() {
+                    "request timed out".to_string()
+                } else if e.is_connect() {
+                    "connection failed".to_string()
+                } else {
+                    "request failed".to_string()
+                }
+            }
+            RequestFailure::Status { status, .. } => format!("HTTP {}", status),
+            RequestFailure::Api(message) => message.clone(),
+        }
+    }
+
+    pub(crate) fn into_error(self) -> anyhow::Error {
+        match self {
+            RequestFailure::Transport(e) => anyhow::Error::new(e),
+            RequestFailure::Status { status, body, .. } => {
+                let message = api_error_message(&body).unwrap_or_else(|| body.trim().to_string());
+                anyhow::anyhow!("HTTP {}: {}", status, message)
+            }
+            RequestFailure::Api(message) => anyhow::anyhow!(message),
+        }
+    }
+}
+
+impl From<reqwest::Error> for RequestFailure {
+    fn from(error: reqwest::Error) -> Self {
+        RequestFailure::Transport(error)
+    }
+}
+
+/// エラーステータスならボディを読み取って RequestFailure に変換
+pub(crate) async fn check_status(response: reqwest::Response) -> Result<reqwest::Response, RequestFailure> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_retry_after);
+    let body = response.text().await.unwrap_or_default();
+
+    Err(RequestFailure::Status { status, body, retry_after })
+}
+
+/// 成功ステータスのボディを読む（`{"error": ...}` ならデコードエラーではなくそのメッセージで失敗する）
+pub(crate) async fn decode_json<T: DeserializeOwned>(response: reqwest::Response) -> Result<T, RequestFailure> {
+    let body = response.text().await?;
+    serde_json::from_str(&body).map_err(|e| {
+        RequestFailure::Api(api_error_message(&body).unwrap_or_else(|| format!("unexpected response from server: {}", e)))
+    })
+}
+
+/// Ollama などが返す `{"error": "..."}` のメッセージ
+pub(crate) fn api_error_message(body: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(body.trim()).ok()?;
+    match &value["error"] {
+        serde_json::Value::String(message) => Some(message.clone()),
+        // OpenAI 互換サーバーは {"error": {"message": ...}}
+        serde_json::Value::Object(error) => error.get("message")?.as_str().map(str::to_string),
+        _ => None,
+    }
+}
+
+/// Retry-After ヘッダー（秒数指定）を解釈
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
+
+/// num_ctx ネゴシエーション状態の共有ハンドル
+///
+/// OllamaClient と状態を共有し、セッション遷移時にリセットする
+pub struct ContextState {
+    context: Arc<Mutex<ContextNegotiator>>,
+    model_max_ctx: Arc<Mutex<HashMap<String, Option<u32>>>>,
+    last_ctx: Arc<Mutex<Option<CtxDecision>>>,
+}
+
+impl SessionResettable for ContextState {
+    fn reset_target(&self) -> ResetTarget {
+        ResetTarget::ContextWindow
+    }
+
+    fn reset(&self, action: ResetAction) {
+        if let Ok(mut last) = self.last_ctx.lock() {
+            *last = None;
+        }
+        if action == ResetAction::Full {
+            if let Ok(mut context) = self.context.lock() {
+                context.reset();
+            }
+            if let Ok(mut cache) = self.model_max_ctx.lock() {
+                cache.clear();
+            }
+        }
+    }
+}
+
+/// リトライの方針と待機状況の通知（バックエンド間で共通）
+#[derive(Clone)]
+pub(crate) struct Retrier {
+    pub(crate) config: RetryConfig,
+    /// リトライ状況の通知先
+    status_tx: Option<UnboundedSender<RetryEvent>>,
+    /// リトライ待機を打ち切るトークン（ターンの処理中だけ設定される）
+    cancel: Option<CancellationToken>,
+    /// 直近のエラーの記録先（/status 用）
+    health: Arc<Mutex<LlmHealth>>,
+}
+
+impl Retrier {
+    pub(crate) fn new(config: RetryConfig, health: Arc<Mutex<LlmHealth>>) -> Self {
+        Self {
+            config,
+            status_tx: None,
+            cancel: None,
+            health,
+        }
+    }
+
+    pub(crate) fn set_status_sender(&mut self, tx: Option<UnboundedSender<RetryEvent>>) {
+        self.status_tx = tx;
+    }
+
+    pub(crate) fn set_cancel_token(&mut self, cancel: Option<CancellationToken>) {
+        self.cancel = cancel;
+    }
+
+    /// バックオフ時間を計算（エクスポネンシャルバックオフ）
+    pub(crate) fn calculate_backoff(&self, attempt: u32) -> Duration {
+        self.backoff_with_ceiling(attempt, self.config.max_backoff_ms)
+    }
+
+    /// エラー種別ごとのバックオフ時間を計算
+    ///
+    /// モデルロード中は接続エラーより長い上限まで待つ
+    pub(crate) fn calculate_backoff_for(&self, kind: RetryableError, attempt: u32) -> Duration {
+        match kind {
+            RetryableError::Loading => {
+                self.backoff_with_ceiling(attempt, self.config.loading_max_backoff_ms)
+            }
+            _ => self.calculate_backoff(attempt),
+        }
+    }
+
+    fn backoff_with_ceiling(&self, attempt: u32, ceiling_ms: u64) -> Duration {
+        let backoff_ms = (self.config.initial_backoff_ms as f64)
+            * self.config.backoff_multiplier.powi(attempt as i32);
+        let backoff_ms = backoff_ms.min(ceiling_ms as f64) as u64;
+        Duration::from_millis(backoff_ms)
+    }
+
+    /// エラー種別ごとの最大リトライ回数
+    pub(crate) fn max_retries_for(&self, kind: RetryableError) -> u32 {
+        match kind {
+            RetryableError::Loading => self.config.loading_max_retries,
+            _ => self.config.max_retries,
+        }
+    }
+
+    /// リトライ状況を通知
+    fn notify(&self, event: RetryEvent) {
+        if let Some(tx) = &self.status_tx {
+            let _ = tx.send(event);
+        }
+    }
+
+    /// 経過時間を通知しながら待機
+    ///
+    /// キャンセルされたら待機を打ち切って false を返す
+    async fn wait_with_status(&self, status: RetryStatus, started: Instant) -> bool {
+        let mut now = Instant::now();
+        let deadline = now + status.wait;
+        loop {
+            self.notify(RetryEvent::Waiting(RetryStatus {
+                remaining: deadline.saturating_duration_since(now),
+                elapsed: started.elapsed(),
+                ..status.clone()
+            }));
+            if now >= deadline {
+                return true;
+            }
+            let tick = sleep((deadline - now).min(STATUS_TICK));
+            match &self.cancel {
+                Some(cancel) => {
+                    tokio::select! {
+                        _ = cancel.cancelled() => return false,
+                        _ = tick => {}
+                    }
+                }
+                None => tick.await,
+            }
+            now = Instant::now();
+        }
+    }
+
+    /// リトライ付きでリクエストを送信
+    ///
+    /// 待機したあとは、成功・失敗・キャンセル（Future の破棄を含む）のいずれでも
+    /// `RetryEvent::Finished` を送る
+    pub(crate) async fn send_with_retry<T, F, Fut>(&self, operation: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: std::future::Future<Output = Result<T, RequestFailure>>,
+    {
+        let started = Instant::now();
+        let mut finished = FinishNotice { retrier: self, waited: false };
+        let mut attempt: u32 = 0;
+
+        loop {
+            let failure = match operation().await {
+                Ok(result) => {
+                    if let Ok(mut health) = self.health.lock() {
+                        health.last_error = None;
+                    }
+                    return Ok(result);
+                }
+                Err(failure) => failure,
+            };
+
+            let error_type = failure.classify();
+            let max_retries = self.max_retries_for(error_type);
+
+            if !error_type.is_retryable() || attempt >= max_retries {
+                // リトライ不可またはリトライ回数超過
+                let summary = failure.summary();
+                let error = failure.into_error();
+                if let Ok(mut health) = self.health.lock() {
+                    health.record_error(error_type, &error);
+                }
+                let context = if attempt == 0 {
+                    format!("リクエスト失敗 ({})", error_type.description())
+                } else {
+                    format!(
+                        "failed after {} attempts over {}s: {}",
+                        attempt + 1,
+                        started.elapsed().as_secs(),
+                        summary
+                    )
+                };
+                return Err(error.context(context));
+            }
+
+            // Retry-After があれば優先し、なければバックオフを計算
+            let backoff = failure
+                .retry_after()
+                .map(|d| d.min(MAX_RETRY_AFTER))
+                .unwrap_or_else(|| self.calculate_backoff_for(error_type, attempt));
+            tracing::warn!(
+                attempt = attempt + 1,
+                max_retries,
+                error_type = error_type.description(),
+                backoff_ms = backoff.as_millis() as u64,
+                "リトライ待機中..."
+            );
+
+            let status = RetryStatus {
+                kind: error_type,
+                error: failure.summary(),
+                attempt: attempt + 1,
+                max_retries,
+                wait: backoff,
+                remaining: backoff,
+                elapsed: started.elapsed(),
+            };
+            finished.waited = true;
+            if !self.wait_with_status(status, started).await {
+                return Err(failure
+                    .into_error()
+                    .context(format!("cancelled while waiting to retry (attempt {})", attempt + 1)));
+            }
+            attempt += 1;
+        }
+    }
+
 }
 
 #[derive(Clone)]
@@ -72,7 +539,145 @@ pub struct OllamaClient {
     client: Client,
     base_url: String,
     model: String,
-    retry_config: RetryConfig,
+    /// リトライの方針と通知
+    retry: Retrier,
+    /// num_ctx のネゴシエーション（クローン間で共有）
+    context: Arc<Mutex<ContextNegotiator>>,
+    /// モデルごとの最大コンテキスト長（/api/show の結果）
+    model_max_ctx: Arc<Mutex<HashMap<String, Option<u32>>>>,
+    /// 直近のリクエストでの num_ctx の決定
+    last_ctx: Arc<Mutex<Option<CtxDecision>>>,
+    /// 累計トークン数（サーバーが返した値）
+    usage: Arc<Mutex<TokenUsage>>,
+    /// 使用中のモデルと直近のエラー（/status 用、クローン間で共有）
+    health: Arc<Mutex<LlmHealth>>,
+    /// 毎回のリクエストに付ける生成オプション
+    options: RequestOptions,
+    /// モデルをメモリに残す時間（keep_alive、None なら送らない）
+    keep_alive: Option<String>,
+}
+
+/// サーバーが報告したトークン数の累計
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    /// プロンプトのトークン数（prompt_eval_count）
+    pub prompt_tokens: u64,
+    /// 生成したトークン数（eval_count）
+    pub completion_tokens: u64,
+}
+
+impl TokenUsage {
+    /// 合計トークン数
+    pub fn total(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+/// リクエストごとのモデルオプション（`[ollama.options]` と /set、未設定の項目は送らない）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RequestOptions {
+    /// 固定する num_ctx（未設定ならプロンプトの長さに合わせて決める）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    /// 生成する最大トークン数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    /// 生成を止める文字列
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+}
+
+impl RequestOptions {
+    /// /set で変更できる項目
+    pub const KEYS: [&'static str; 7] = ["temperature", "top_p", "top_k", "num_ctx", "num_predict", "seed", "stop"];
+
+    /// 設定されている項目がないか
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// 未設定の項目を base の値で埋める
+    pub fn or(mut self, base: &RequestOptions) -> Self {
+        self.num_ctx = self.num_ctx.or(base.num_ctx);
+        self.temperature = self.temperature.or(base.temperature);
+        self.top_p = self.top_p.or(base.top_p);
+        self.top_k = self.top_k.or(base.top_k);
+        self.num_predict = self.num_predict.or(base.num_predict);
+        self.seed = self.seed.or(base.seed);
+        if self.stop.is_empty() {
+            self.stop = base.stop.clone();
+        }
+        self
+    }
+
+    /// 項目を文字列の値で設定する（"default" で未設定に戻す、stop はカンマ区切り）
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        let unset = value.eq_ignore_ascii_case("default");
+        match key {
+            "temperature" => self.temperature = parse_option(key, value, unset, 0.0..=2.0)?,
+            "top_p" => self.top_p = parse_option(key, value, unset, 0.0..=1.0)?,
+            "top_k" => self.top_k = parse_option(key, value, unset, 1..=u32::MAX)?,
+            "num_ctx" => self.num_ctx = parse_option(key, value, unset, 1..=u32::MAX)?,
+            "num_predict" => self.num_predict = parse_option(key, value, unset, 1..=u32::MAX)?,
+            "seed" => self.seed = parse_option(key, value, unset, i64::MIN..=i64::MAX)?,
+            "stop" => {
+                self.stop = if unset {
+                    Vec::new()
+                } else {
+                    value.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect()
+                };
+            }
+            _ => {
+                return Err(format!("unknown option '{}'; valid keys: {}", key, Self::KEYS.join(", ")));
+            }
+        }
+        Ok(())
+    }
+
+    /// 各項目の現在値（未設定は "default"）
+    pub fn describe(&self) -> Vec<(&'static str, String)> {
+        fn show<T: ToString>(value: &Option<T>) -> String {
+            value.as_ref().map(T::to_string).unwrap_or_else(|| "default".to_string())
+        }
+        vec![
+            ("temperature", show(&self.temperature)),
+            ("top_p", show(&self.top_p)),
+            ("top_k", show(&self.top_k)),
+            ("num_ctx", self.num_ctx.map(|n| n.to_string()).unwrap_or_else(|| "auto".to_string())),
+            ("num_predict", show(&self.num_predict)),
+            ("seed", show(&self.seed)),
+            ("stop", if self.stop.is_empty() { "default".to_string() } else { format!("{:?}", self.stop) }),
+        ]
+    }
+}
+
+/// /set の値を解釈する（範囲外はエラー）
+fn parse_option<T>(key: &str, value: &str, unset: bool, range: std::ops::RangeInclusive<T>) -> Result<Option<T>, String>
+where
+    T: std::str::FromStr + PartialOrd + std::fmt::Display,
+{
+    if unset {
+        return Ok(None);
+    }
+    match value.parse::<T>() {
+        Ok(parsed) if range.contains(&parsed) => Ok(Some(parsed)),
+        _ => Err(format!(
+            "invalid value for {}: '{}' (expected a number from {} to {}, or 'default')",
+            key,
+            value,
+            range.start(),
+            range.end()
+        )),
+    }
 }
 
 #[derive(Serialize)]
@@ -82,6 +687,10 @@ struct GenerateRequest {
     stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     system: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<RequestOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -89,18 +698,48 @@ pub struct GenerateResponse {
     pub model: String,
     pub response: String,
     pub done: bool,
+    #[serde(default)]
+    pub prompt_eval_count: Option<u64>,
+    #[serde(default)]
+    pub eval_count: Option<u64>,
+    /// 総処理時間（ナノ秒）
+    #[serde(default)]
+    pub total_duration: Option<u64>,
+    /// 生成にかかった時間（ナノ秒）
+    #[serde(default)]
+    pub eval_duration: Option<u64>,
 }
 
-impl OllamaClient {
-    fn build_client(connect_timeout_secs: u64, read_timeout_secs: u64) -> Client {
-        Client::builder()
-            .connect_timeout(Duration::from_secs(connect_timeout_secs))
-            .read_timeout(Duration::from_secs(read_timeout_secs))
-            .no_proxy()
-            .build()
-            .unwrap_or_else(|_| Client::new())
+impl GenerateResponse {
+    /// 統計情報（サーバーがカウンタを返した場合のみ）
+    pub fn stats(&self) -> Option<StreamStats> {
+        self.eval_count.map(|eval_count| {
+            StreamStats::from_counts(
+                self.total_duration,
+                self.prompt_eval_count.map(|c| c as u32),
+                Some(eval_count as u32),
+                self.eval_duration,
+            )
+        })
     }
+}
+
+/// タイムアウトとプロキシの扱いを設定した HTTP クライアント
+pub(crate) fn build_http_client(base_url: &str, connect_timeout_secs: u64, read_timeout_secs: u64) -> Client {
+    let builder = Client::builder()
+        .connect_timeout(Duration::from_secs(connect_timeout_secs))
+        .read_timeout(Duration::from_secs(read_timeout_secs));
+    let no_proxy = std::env::var("NO_PROXY").or_else(|_| std::env::var("no_proxy")).ok();
+    // ローカルのサーバーや NO_PROXY に含まれるホストには HTTPS_PROXY などを使わない
+    let builder = if bypasses_proxy(base_url, no_proxy.as_deref()) {
+        builder.no_proxy()
+    } else {
+        builder
+    };
+    builder.build().unwrap_or_else(|_| Client::new())
+}
 
+impl OllamaClient {
     /// 基本的なクライアントを作成（デフォルトタイムアウト使用）
     pub fn new(base_url: &str, model: &str) -> Self {
         Self::with_timeout(base_url, model, 30, 300)
@@ -113,99 +752,249 @@ impl OllamaClient {
         connect_timeout_secs: u64,
         read_timeout_secs: u64,
     ) -> Self {
-        let client = Self::build_client(connect_timeout_secs, read_timeout_secs);
+        let client = build_http_client(base_url, connect_timeout_secs, read_timeout_secs);
 
+        let health = Arc::new(Mutex::new(LlmHealth::new(model)));
         Self {
             client,
             base_url: base_url.to_string(),
             model: model.to_string(),
-            retry_config: RetryConfig::default(),
+            retry: Retrier::new(RetryConfig::default(), Arc::clone(&health)),
+            context: Arc::new(Mutex::new(ContextNegotiator::new(None, DEFAULT_MAX_NUM_CTX))),
+            model_max_ctx: Arc::new(Mutex::new(HashMap::new())),
+            last_ctx: Arc::new(Mutex::new(None)),
+            usage: Arc::new(Mutex::new(TokenUsage::default())),
+            health,
+            options: RequestOptions::default(),
+            keep_alive: None,
         }
     }
 
     /// OllamaConfigからクライアントを作成
     pub fn from_config(config: &OllamaConfig) -> Self {
-        let client = Self::build_client(config.connect_timeout, config.read_timeout);
+        let client = build_http_client(&config.url, config.connect_timeout, config.read_timeout);
 
+        let health = Arc::new(Mutex::new(LlmHealth::new(&config.model)));
         Self {
             client,
             base_url: config.url.clone(),
             model: config.model.clone(),
-            retry_config: config.retry.clone(),
+            retry: Retrier::new(config.retry.clone(), Arc::clone(&health)),
+            context: Arc::new(Mutex::new(ContextNegotiator::new(config.num_ctx, config.max_num_ctx))),
+            model_max_ctx: Arc::new(Mutex::new(HashMap::new())),
+            last_ctx: Arc::new(Mutex::new(None)),
+            usage: Arc::new(Mutex::new(TokenUsage::default())),
+            health,
+            options: config.options.clone(),
+            keep_alive: Some(config.keep_alive.clone()).filter(|k| !k.trim().is_empty()),
         }
     }
 
     /// リトライ設定を更新
     pub fn with_retry_config(mut self, retry_config: RetryConfig) -> Self {
-        self.retry_config = retry_config;
+        self.retry.config = retry_config;
         self
     }
 
-    /// モデル名を更新
-    pub fn set_model(&mut self, model: impl Into<String>) {
-        self.model = model.into();
+    /// 生成オプションを設定
+    pub fn with_options(mut self, options: RequestOptions) -> Self {
+        self.options = options;
+        self
     }
 
-    /// バックオフ時間を計算（エクスポネンシャルバックオフ）
-    fn calculate_backoff(&self, attempt: u32) -> Duration {
-        let backoff_ms = (self.retry_config.initial_backoff_ms as f64)
-            * self.retry_config.backoff_multiplier.powi(attempt as i32);
-        let backoff_ms = backoff_ms.min(self.retry_config.max_backoff_ms as f64) as u64;
-        Duration::from_millis(backoff_ms)
+    /// モデルをメモリに残す時間を設定（None なら送らずサーバーの既定値）
+    pub fn with_keep_alive(mut self, keep_alive: Option<String>) -> Self {
+        self.keep_alive = keep_alive;
+        self
     }
 
-    /// リトライ付きでリクエストを送信
-    async fn send_with_retry<T, F, Fut>(&self, operation: F) -> Result<T>
-    where
-        F: Fn() -> Fut,
-        Fut: std::future::Future<Output = Result<T, reqwest::Error>>,
-    {
-        let mut last_error: Option<reqwest::Error> = None;
-
-        for attempt in 0..=self.retry_config.max_retries {
-            match operation().await {
-                Ok(result) => return Ok(result),
-                Err(error) => {
-                    let error_type = RetryableError::from_reqwest_error(&error);
-
-                    if !error_type.is_retryable() || attempt >= self.retry_config.max_retries {
-                        // リトライ不可またはリトライ回数超過
-                        return Err(error).context(format!(
-                            "リクエスト失敗 ({}): {}回のリトライ後",
-                            error_type.description(),
-                            attempt
-                        ));
-                    }
+    /// 現在の生成オプション
+    pub fn options(&self) -> &RequestOptions {
+        &self.options
+    }
 
-                    // バックオフを計算して待機
-                    let backoff = self.calculate_backoff(attempt);
-                    tracing::warn!(
-                        attempt = attempt + 1,
-                        max_retries = self.retry_config.max_retries,
-                        error_type = error_type.description(),
-                        backoff_ms = backoff.as_millis() as u64,
-                        "リトライ待機中..."
-                    );
-
-                    sleep(backoff).await;
-                    last_error = Some(error);
-                }
+    /// 生成オプションを変更（/set）
+    pub fn options_mut(&mut self) -> &mut RequestOptions {
+        &mut self.options
+    }
+
+    /// num_ctx の設定値と引き上げ上限を設定
+    pub fn with_context_limits(self, num_ctx: Option<u32>, max_num_ctx: u32) -> Self {
+        if let Ok(mut context) = self.context.lock() {
+            *context = ContextNegotiator::new(num_ctx, max_num_ctx);
+        }
+        self
+    }
+
+    /// 直近のリクエストでの num_ctx の決定
+    pub fn last_ctx_decision(&self) -> Option<CtxDecision> {
+        self.last_ctx.lock().ok().and_then(|d| *d)
+    }
+
+    /// これまでのリクエストの累計トークン数
+    pub fn token_usage(&self) -> TokenUsage {
+        self.usage.lock().map(|u| *u).unwrap_or_default()
+    }
+
+    fn record_usage(&self, response: &GenerateResponse) {
+        if let Ok(mut usage) = self.usage.lock() {
+            usage.prompt_tokens += response.prompt_eval_count.unwrap_or(0);
+            usage.completion_tokens += response.eval_count.unwrap_or(0);
+        }
+    }
+
+    pub(crate) fn record_ctx_decision(&self, decision: CtxDecision) {
+        if let Ok(mut last) = self.last_ctx.lock() {
+            *last = Some(decision);
+        }
+    }
+
+    /// サーバーとモデルの状態を報告するプロバイダー
+    pub fn status_provider(&self) -> LlmStatus {
+        LlmStatus::new(self.client.clone(), self.base_url.clone(), Arc::clone(&self.health))
+    }
+
+    /// コンテキスト使用状況を報告するプロバイダー
+    pub fn context_status_provider(&self) -> ContextStatus {
+        ContextStatus::new(Arc::clone(&self.last_ctx), Arc::clone(&self.usage))
+    }
+
+    /// セッション遷移時のリセット用ハンドル
+    pub fn session_state(&self) -> ContextState {
+        ContextState {
+            context: Arc::clone(&self.context),
+            model_max_ctx: Arc::clone(&self.model_max_ctx),
+            last_ctx: Arc::clone(&self.last_ctx),
+        }
+    }
+
+    /// モデルの最大コンテキスト長を取得（/api/show、モデルごとにキャッシュ）
+    pub async fn model_context_length(&self) -> Option<u32> {
+        if let Some(cached) = self.model_max_ctx.lock().ok().and_then(|c| c.get(&self.model).copied()) {
+            return cached;
+        }
+
+        let result = async {
+            let response = self
+                .client
+                .post(format!("{}/api/show", self.base_url))
+                .json(&serde_json::json!({ "model": self.model }))
+                .send()
+                .await
+                .ok()?;
+            if !response.status().is_success() {
+                return None;
+            }
+            let body: serde_json::Value = response.json().await.ok()?;
+            parse_context_length(&body)
+        }
+        .await;
+
+        if let Ok(mut cache) = self.model_max_ctx.lock() {
+            cache.insert(self.model.clone(), result);
+        }
+        result
+    }
+
+    /// 生成オプションにプロンプトに合わせた num_ctx を加えてリクエストオプションを作る
+    ///
+    /// num_ctx を固定している場合は引き上げない
+    async fn request_options(&self, prompt: &str, system: Option<&str>) -> Option<RequestOptions> {
+        let mut options = self.options.clone();
+        if options.num_ctx.is_none() {
+            options.num_ctx = self.negotiate_options(prompt, system).await.and_then(|o| o.num_ctx);
+        }
+        (!options.is_empty()).then_some(options)
+    }
+
+    /// プロンプトの長さから num_ctx を決定してリクエストオプションを作る
+    async fn negotiate_options(&self, prompt: &str, system: Option<&str>) -> Option<RequestOptions> {
+        let tokens = estimate_tokens(prompt) + system.map(estimate_tokens).unwrap_or(0);
+
+        let fits = self
+            .context
+            .lock()
+            .map(|c| c.fits(&self.model, tokens))
+            .unwrap_or(true);
+        // 収まる場合は /api/show を呼ばない
+        let model_max = if fits { None } else { self.model_context_length().await };
+
+        let decision = self
+            .context
+            .lock()
+            .map(|mut c| c.negotiate(&self.model, tokens, model_max))
+            .unwrap_or(CtxDecision::Default);
+
+        match decision {
+            CtxDecision::Raised { from, to } => {
+                tracing::info!(model = %self.model, from, to, estimated_tokens = tokens, "num_ctx を引き上げ");
+            }
+            CtxDecision::Insufficient { needed, max } => {
+                tracing::warn!(model = %self.model, needed, max, "プロンプトが最大コンテキスト長を超えています");
             }
+            _ => {}
+        }
+        self.record_ctx_decision(decision);
+
+        decision.num_ctx().map(|num_ctx| RequestOptions {
+            num_ctx: Some(num_ctx),
+            ..RequestOptions::default()
+        })
+    }
+
+    /// モデル名を更新
+    pub fn set_model(&mut self, model: impl Into<String>) {
+        self.model = model.into();
+        if let Ok(mut health) = self.health.lock() {
+            health.model = self.model.clone();
         }
+    }
+
+    /// リトライ状況の通知先を設定
+    pub fn set_status_sender(&mut self, tx: Option<UnboundedSender<RetryEvent>>) {
+        self.retry.set_status_sender(tx);
+    }
 
-        // ここには到達しないはずだが、念のため
-        Err(last_error
-            .map(|e| anyhow::anyhow!(e))
-            .unwrap_or_else(|| anyhow::anyhow!("不明なエラー")))
+    /// リトライ待機を打ち切るトークンを設定（None で解除）
+    pub fn set_cancel_token(&mut self, cancel: Option<CancellationToken>) {
+        self.retry.set_cancel_token(cancel);
     }
 
     /// 生成リクエストを送信（リトライ付き）
     pub async fn generate(&self, prompt: &str, system: Option<&str>) -> Result<String> {
+        Ok(self.generate_with_stats(prompt, system).await?.0)
+    }
+
+    /// 生成して、応答と統計情報（取れた場合）を返す
+    pub async fn generate_with_stats(&self, prompt: &str, system: Option<&str>) -> Result<(String, Option<StreamStats>)> {
+        let options = self.request_options(prompt, system).await;
+        self.generate_request(prompt, system, options).await
+    }
+
+    /// オプション（温度、最大トークン数など）を指定して生成（未指定の項目は生成オプションに従う）
+    pub async fn generate_with_options(
+        &self,
+        prompt: &str,
+        system: Option<&str>,
+        options: RequestOptions,
+    ) -> Result<String> {
+        let base = self.request_options(prompt, system).await.unwrap_or_default();
+        Ok(self.generate_request(prompt, system, Some(options.or(&base))).await?.0)
+    }
+
+    async fn generate_request(
+        &self,
+        prompt: &str,
+        system: Option<&str>,
+        options: Option<RequestOptions>,
+    ) -> Result<(String, Option<StreamStats>)> {
         let request = GenerateRequest {
             model: self.model.clone(),
             prompt: prompt.to_string(),
             stream: false,
             system: system.map(|s| s.to_string()),
+            options,
+            keep_alive: self.keep_alive.clone(),
         };
 
         let url = format!("{}/api/generate", self.base_url);
@@ -213,23 +1002,51 @@ impl OllamaClient {
         let request_json = serde_json::to_value(&request)?;
 
         let response: GenerateResponse = self
+            .retry
             .send_with_retry(|| {
                 let client = client.clone();
                 let url = url.clone();
                 let request_json = request_json.clone();
                 async move {
-                    client
-                        .post(&url)
-                        .json(&request_json)
-                        .send()
-                        .await?
-                        .json::<GenerateResponse>()
-                        .await
+                    let response = client.post(&url).json(&request_json).send().await?;
+                    let response = check_status(response).await?;
+                    decode_json::<GenerateResponse>(response).await
                 }
             })
             .await?;
 
-        Ok(response.response)
+        self.record_usage(&response);
+        let stats = response.stats();
+        Ok((response.response, stats))
+    }
+
+    /// モデルを読み込んでおく（空のプロンプトを送り、生成はしない）
+    ///
+    /// アイドル後の最初のリクエストがモデルの再読み込みで待たされないように、起動時と /warm で呼ぶ
+    pub async fn warm_up(&self) -> Result<()> {
+        let request = GenerateRequest {
+            model: self.model.clone(),
+            prompt: String::new(),
+            stream: false,
+            system: None,
+            options: None,
+            keep_alive: self.keep_alive.clone(),
+        };
+        let response = self
+            .client
+            .post(format!("{}/api/generate", self.base_url))
+            .json(&request)
+            .send()
+            .await?;
+        check_status(response).await.map_err(RequestFailure::into_error)?;
+        Ok(())
+    }
+
+    /// サーバーに接続でき、設定したモデルがあるかを確かめる（/api/tags）
+    ///
+    /// 起動時に呼び、リトライで待たせる前に対処方法を示す
+    pub async fn health_check(&self) -> HealthStatus {
+        check_health(&self.client, &self.base_url, &self.model).await
     }
 
     /// 生成リクエストを送信（リトライなし - 後方互換性のため）
@@ -239,6 +1056,8 @@ impl OllamaClient {
             prompt: prompt.to_string(),
             stream: false,
             system: system.map(|s| s.to_string()),
+            options: None,
+            keep_alive: self.keep_alive.clone(),
         };
 
         let response = self
@@ -246,9 +1065,9 @@ impl OllamaClient {
             .post(format!("{}/api/generate", self.base_url))
             .json(&request)
             .send()
-            .await?
-            .json::<GenerateResponse>()
             .await?;
+        let response = check_status(response).await.map_err(RequestFailure::into_error)?;
+        let response: GenerateResponse = decode_json(response).await.map_err(RequestFailure::into_error)?;
 
         Ok(response.response)
     }
@@ -263,7 +1082,7 @@ impl OllamaClient {
 
     /// 現在のリトライ設定を取得
     pub fn retry_config(&self) -> &RetryConfig {
-        &self.retry_config
+        &self.retry.config
     }
 
     /// 内部のreqwestクライアントを取得
@@ -279,21 +1098,140 @@ impl OllamaClient {
         prompt: &str,
         system: Option<&str>,
     ) -> Result<StreamingResponse> {
+        let options = self.request_options(prompt, system).await;
         streaming_impl(
             &self.client,
             &self.base_url,
             &self.model,
             prompt,
             system,
+            options,
+            self.keep_alive.as_deref(),
         )
         .await
     }
 }
 
+#[async_trait]
+impl LlmBackend for OllamaClient {
+    fn provider(&self) -> &'static str {
+        "ollama"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn set_model(&mut self, model: String) {
+        OllamaClient::set_model(self, model);
+    }
+
+    fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    async fn generate_with_stats(&self, prompt: &str, system: Option<&str>) -> Result<(String, Option<StreamStats>)> {
+        OllamaClient::generate_with_stats(self, prompt, system).await
+    }
+
+    async fn generate_with_options(&self, prompt: &str, system: Option<&str>, options: RequestOptions) -> Result<String> {
+        OllamaClient::generate_with_options(self, prompt, system, options).await
+    }
+
+    async fn generate_streaming(&self, prompt: &str, system: Option<&str>) -> Result<StreamingResponse> {
+        OllamaClient::generate_streaming(self, prompt, system).await
+    }
+
+    async fn warm_up(&self) -> Result<bool> {
+        OllamaClient::warm_up(self).await?;
+        Ok(true)
+    }
+
+    async fn health_check(&self) -> Option<HealthStatus> {
+        Some(OllamaClient::health_check(self).await)
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        fetch_models(&self.client, &self.base_url, &ModelsApi::Ollama)
+            .await
+            .map_err(|kind| anyhow::anyhow!("failed to list models on {} ({})", self.base_url, kind.description()))
+    }
+
+    fn options(&self) -> &RequestOptions {
+        &self.options
+    }
+
+    fn options_mut(&mut self) -> &mut RequestOptions {
+        &mut self.options
+    }
+
+    fn set_status_sender(&mut self, tx: Option<UnboundedSender<RetryEvent>>) {
+        self.retry.set_status_sender(tx);
+    }
+
+    fn set_cancel_token(&mut self, cancel: Option<CancellationToken>) {
+        self.retry.set_cancel_token(cancel);
+    }
+
+    fn token_usage(&self) -> TokenUsage {
+        OllamaClient::token_usage(self)
+    }
+
+    fn status_provider(&self) -> Arc<dyn StatusProvider> {
+        Arc::new(OllamaClient::status_provider(self))
+    }
+
+    fn context_status_provider(&self) -> Option<Arc<dyn StatusProvider>> {
+        Some(Arc::new(OllamaClient::context_status_provider(self)))
+    }
+
+    fn last_ctx_decision(&self) -> Option<CtxDecision> {
+        OllamaClient::last_ctx_decision(self)
+    }
+
+    fn session_state(&self) -> Option<Arc<dyn SessionResettable>> {
+        Some(Arc::new(OllamaClient::session_state(self)))
+    }
+}
+
+/// プロキシを通さずに接続するか（ループバック、または NO_PROXY に一致するホスト）
+fn bypasses_proxy(base_url: &str, no_proxy: Option<&str>) -> bool {
+    let Some(host) = reqwest::Url::parse(base_url).ok().and_then(|u| u.host_str().map(str::to_string)) else {
+        return true;
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']').to_ascii_lowercase();
+    let is_loopback = host == "localhost"
+        || host.ends_with(".localhost")
+        || host.parse::<std::net::IpAddr>().is_ok_and(|ip| ip.is_loopback() || ip.is_unspecified());
+    if is_loopback {
+        return true;
+    }
+    no_proxy.unwrap_or_default().split(',').map(str::trim).filter(|e| !e.is_empty()).any(|entry| {
+        let entry = entry.to_ascii_lowercase();
+        let domain = entry.trim_start_matches("*.").trim_start_matches('.');
+        entry == "*" || host == domain || host.ends_with(&format!(".{}", domain))
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_proxy_bypass_for_local_and_no_proxy_hosts() {
+        assert!(bypasses_proxy("http://localhost:11434", None));
+        assert!(bypasses_proxy("http://127.0.0.1:11434", None));
+        assert!(bypasses_proxy("http://[::1]:11434", None));
+        assert!(bypasses_proxy("http://0.0.0.0:11434", None));
+        assert!(!bypasses_proxy("http://gpu-box:11434", None));
+        assert!(!bypasses_proxy("https://ollama.example.com", Some("other.com")));
+        assert!(bypasses_proxy("https://ollama.example.com", Some("localhost, .example.com")));
+        assert!(bypasses_proxy("https://ollama.example.com", Some("example.com")));
+        assert!(!bypasses_proxy("https://notexample.com", Some("example.com")));
+        assert!(bypasses_proxy("http://10.0.0.5:11434", Some("10.0.0.5")));
+        assert!(bypasses_proxy("http://gpu-box:11434", Some("*")));
+    }
+
     #[test]
     fn test_retryable_error_classification() {
         // 接続エラーはリトライ可能
@@ -304,6 +1242,15 @@ mod tests {
         assert!(RetryableError::ServerError.is_retryable());
         // 非リトライエラーはリトライ不可
         assert!(!RetryableError::NonRetryable.is_retryable());
+        // モデルがなければリトライしても解決しない
+        assert!(!RetryableError::ModelMissing.is_retryable());
+        assert_eq!(
+            RetryableError::from_status(StatusCode::NOT_FOUND, r#"{"error":"model 'qwen' not found"}"#),
+            RetryableError::ModelMissing
+        );
+        assert_eq!(api_error_message(r#"{"error":"model 'qwen' not found"}"#).as_deref(), Some("model 'qwen' not found"));
+        assert_eq!(api_error_message(r#"{"error":{"message":"no such model"}}"#).as_deref(), Some("no such model"));
+        assert_eq!(api_error_message("<html>"), None);
     }
 
     #[test]
@@ -311,23 +1258,23 @@ mod tests {
         let client = OllamaClient::new("http://localhost:11434", "test");
 
         // デフォルト設定: 1000ms, 倍率2.0
-        let backoff_0 = client.calculate_backoff(0);
+        let backoff_0 = client.retry.calculate_backoff(0);
         assert_eq!(backoff_0, Duration::from_millis(1000)); // 1秒
 
-        let backoff_1 = client.calculate_backoff(1);
+        let backoff_1 = client.retry.calculate_backoff(1);
         assert_eq!(backoff_1, Duration::from_millis(2000)); // 2秒
 
-        let backoff_2 = client.calculate_backoff(2);
+        let backoff_2 = client.retry.calculate_backoff(2);
         assert_eq!(backoff_2, Duration::from_millis(4000)); // 4秒
     }
 
     #[test]
     fn test_backoff_max_limit() {
         let mut client = OllamaClient::new("http://localhost:11434", "test");
-        client.retry_config.max_backoff_ms = 5000;
+        client.retry.config.max_backoff_ms = 5000;
 
         // 4回目のリトライ: 1000 * 2^4 = 16000ms だが、max 5000ms に制限
-        let backoff = client.calculate_backoff(4);
+        let backoff = client.retry.calculate_backoff(4);
         assert_eq!(backoff, Duration::from_millis(5000));
     }
 
@@ -339,18 +1286,464 @@ mod tests {
             timeout: 300,
             connect_timeout: 60,
             read_timeout: 600,
+            num_ctx: Some(8192),
+            max_num_ctx: 65536,
             retry: RetryConfig {
                 max_retries: 5,
                 initial_backoff_ms: 2000,
                 backoff_multiplier: 1.5,
                 max_backoff_ms: 30000,
+                ..RetryConfig::default()
             },
+            options: RequestOptions::default(),
+            keep_alive: "30m".to_string(),
         };
 
         let client = OllamaClient::from_config(&config);
         assert_eq!(client.base_url(), "http://custom:11434");
+        assert_eq!(client.keep_alive.as_deref(), Some("30m"));
         assert_eq!(client.model(), "custom-model");
         assert_eq!(client.retry_config().max_retries, 5);
         assert_eq!(client.retry_config().initial_backoff_ms, 2000);
     }
+
+    #[test]
+    fn test_classify_loading_and_busy() {
+        let loading = RetryableError::from_status(
+            StatusCode::SERVICE_UNAVAILABLE,
+            r#"{"error":"loading model"}"#,
+        );
+        assert_eq!(loading, RetryableError::Loading);
+
+        let busy = RetryableError::from_status(
+            StatusCode::SERVICE_UNAVAILABLE,
+            r#"{"error":"server busy, please try again.  maximum pending requests exceeded"}"#,
+        );
+        assert_eq!(busy, RetryableError::Busy);
+
+        assert_eq!(
+            RetryableError::from_status(StatusCode::TOO_MANY_REQUESTS, ""),
+            RetryableError::Busy
+        );
+        assert_eq!(
+            RetryableError::from_status(StatusCode::INTERNAL_SERVER_ERROR, "oops"),
+            RetryableError::ServerError
+        );
+        assert_eq!(
+            RetryableError::from_status(StatusCode::NOT_FOUND, r#"{"error":"model not found"}"#),
+            RetryableError::ModelMissing
+        );
+        assert_eq!(
+            RetryableError::from_status(StatusCode::BAD_REQUEST, r#"{"error":"invalid options"}"#),
+            RetryableError::NonRetryable
+        );
+    }
+
+    #[test]
+    fn test_loading_backoff_ceiling_is_longer() {
+        let client = OllamaClient::new("http://localhost:11434", "test");
+
+        // 1000 * 2^5 = 32000ms: 接続エラーは10秒、ロード中は30秒で頭打ち
+        assert_eq!(
+            client.retry.calculate_backoff_for(RetryableError::Connection, 5),
+            Duration::from_millis(10000)
+        );
+        assert_eq!(
+            client.retry.calculate_backoff_for(RetryableError::Loading, 5),
+            Duration::from_millis(30000)
+        );
+        assert!(client.retry.max_retries_for(RetryableError::Loading) > client.retry.max_retries_for(RetryableError::Busy));
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("5"), Some(Duration::from_secs(5)));
+        assert_eq!(parse_retry_after(" 12 "), Some(Duration::from_secs(12)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+    }
+
+    #[test]
+    fn test_retry_status_message() {
+        let status = RetryStatus {
+            kind: RetryableError::Loading,
+            error: "HTTP 503 Service Unavailable".to_string(),
+            attempt: 1,
+            max_retries: 10,
+            wait: Duration::from_secs(1),
+            remaining: Duration::from_secs(1),
+            elapsed: Duration::from_secs(34),
+        };
+        assert_eq!(status.message(), "model loading on server… 34s");
+
+        let status = RetryStatus {
+            kind: RetryableError::Connection,
+            error: "connection refused".to_string(),
+            attempt: 2,
+            max_retries: 4,
+            wait: Duration::from_secs(2),
+            remaining: Duration::from_millis(1200),
+            elapsed: Duration::from_secs(3),
+        };
+        assert_eq!(status.message(), "connection refused — retrying in 2s (attempt 2/4)");
+        assert!(!status.is_first_tick());
+    }
+
+    #[test]
+    fn test_request_options_skip_unset_fields() {
+        assert_eq!(serde_json::to_value(RequestOptions::default()).unwrap(), serde_json::json!({}));
+
+        let options = RequestOptions {
+            temperature: Some(0.5),
+            seed: Some(42),
+            stop: vec!["</answer>".to_string()],
+            ..RequestOptions::default()
+        };
+        assert_eq!(
+            serde_json::to_value(&options).unwrap(),
+            serde_json::json!({"temperature": 0.5, "seed": 42, "stop": ["</answer>"]})
+        );
+    }
+
+    #[test]
+    fn test_request_options_set() {
+        let mut options = RequestOptions::default();
+        options.set("temperature", "0.2").unwrap();
+        options.set("stop", "###, END").unwrap();
+        options.set("num_ctx", "16384").unwrap();
+        assert_eq!(options.temperature, Some(0.2));
+        assert_eq!(options.stop, vec!["###", "END"]);
+
+        options.set("num_ctx", "default").unwrap();
+        assert_eq!(options.num_ctx, None);
+
+        let error = options.set("top_p", "1.5").unwrap_err();
+        assert!(error.contains("from 0 to 1"), "{}", error);
+        let error = options.set("top_k", "many").unwrap_err();
+        assert!(error.contains("invalid value for top_k"), "{}", error);
+        let error = options.set("temp", "1").unwrap_err();
+        assert!(error.contains("valid keys: temperature, top_p, top_k, num_ctx, num_predict, seed, stop"), "{}", error);
+    }
+
+    #[test]
+    fn test_request_options_or_prefers_own_values() {
+        let base = RequestOptions {
+            temperature: Some(0.7),
+            seed: Some(1),
+            ..RequestOptions::default()
+        };
+        let merged = RequestOptions {
+            temperature: Some(0.0),
+            num_predict: Some(200),
+            ..RequestOptions::default()
+        }
+        .or(&base);
+        assert_eq!(merged.temperature, Some(0.0));
+        assert_eq!(merged.seed, Some(1));
+        assert_eq!(merged.num_predict, Some(200));
+    }
+
+    mod mock_server {
+        use super::*;
+        use crate::llm::mock_server::{http_response, spawn_mock_server};
+        use tokio::sync::mpsc;
+
+        fn success_response() -> String {
+            http_response("200 OK", &[], r#"{"model":"test","response":"hello","done":true}"#)
+        }
+
+        fn fast_retry_config() -> RetryConfig {
+            RetryConfig {
+                max_retries: 1,
+                initial_backoff_ms: 10,
+                backoff_multiplier: 2.0,
+                max_backoff_ms: 10,
+                loading_max_backoff_ms: 40,
+                loading_max_retries: 3,
+            }
+        }
+
+        fn drain(rx: &mut mpsc::UnboundedReceiver<RetryEvent>) -> Vec<RetryEvent> {
+            let mut events = Vec::new();
+            while let Ok(event) = rx.try_recv() {
+                events.push(event);
+            }
+            events
+        }
+
+        #[tokio::test]
+        async fn test_loading_retries_beyond_normal_limit() {
+            let loading = http_response("503 Service Unavailable", &[], r#"{"error":"loading model"}"#);
+            let url = spawn_mock_server(vec![loading.clone(), loading, success_response()]).await;
+
+            let (tx, mut rx) = mpsc::unbounded_channel();
+            let mut client = OllamaClient::new(&url, "test").with_retry_config(fast_retry_config());
+            client.set_status_sender(Some(tx));
+
+            // 通常の max_retries(1) を超えてもロード中ならリトライを続ける
+            let response = client.generate("hi", None).await.unwrap();
+            assert_eq!(response, "hello");
+
+            let events = drain(&mut rx);
+            let waits: Vec<&RetryStatus> = events
+                .iter()
+                .filter_map(|e| match e {
+                    RetryEvent::Waiting(status) => Some(status),
+                    RetryEvent::Finished => None,
+                })
+                .collect();
+            assert!(waits.iter().all(|s| s.kind == RetryableError::Loading));
+            assert_eq!(waits.first().unwrap().wait, Duration::from_millis(10));
+            assert_eq!(waits.last().unwrap().wait, Duration::from_millis(20));
+            assert!(waits[0].message().starts_with("model loading on server…"));
+            assert_eq!(events.last(), Some(&RetryEvent::Finished));
+        }
+
+        #[tokio::test]
+        async fn test_busy_respects_retry_after() {
+            let busy = http_response(
+                "503 Service Unavailable",
+                &[("Retry-After", "1")],
+                r#"{"error":"server busy, please try again.  maximum pending requests exceeded"}"#,
+            );
+            let url = spawn_mock_server(vec![busy, success_response()]).await;
+
+            let (tx, mut rx) = mpsc::unbounded_channel();
+            let mut client = OllamaClient::new(&url, "test").with_retry_config(fast_retry_config());
+            client.set_status_sender(Some(tx));
+
+            let started = Instant::now();
+            let response = client.generate("hi", None).await.unwrap();
+            assert_eq!(response, "hello");
+            // バックオフ上限(10ms)ではなく Retry-After の1秒待つ
+            assert!(started.elapsed() >= Duration::from_secs(1));
+
+            let events = drain(&mut rx);
+            match &events[0] {
+                RetryEvent::Waiting(status) => {
+                    assert_eq!(status.kind, RetryableError::Busy);
+                    assert_eq!(status.wait, Duration::from_secs(1));
+                    assert!(status.message().starts_with("server busy"));
+                }
+                other => panic!("unexpected event: {:?}", other),
+            }
+            assert_eq!(events.last(), Some(&RetryEvent::Finished));
+        }
+
+        #[tokio::test]
+        async fn test_reports_each_retry_then_succeeds() {
+            let failing = http_response("500 Internal Server Error", &[], r#"{"error":"boom"}"#);
+            let url = spawn_mock_server(vec![failing.clone(), failing, success_response()]).await;
+
+            let (tx, mut rx) = mpsc::unbounded_channel();
+            let mut client = OllamaClient::new(&url, "test").with_retry_config(RetryConfig {
+                max_retries: 3,
+                ..fast_retry_config()
+            });
+            client.set_status_sender(Some(tx));
+
+            assert_eq!(client.generate("hi", None).await.unwrap(), "hello");
+
+            // 待機ごとに最初の通知が1つずつ、最後に Finished
+            let events = drain(&mut rx);
+            let starts: Vec<(u32, u32, String)> = events
+                .iter()
+                .filter_map(|e| match e {
+                    RetryEvent::Waiting(status) if status.is_first_tick() => {
+                        Some((status.attempt, status.max_retries, status.error.clone()))
+                    }
+                    _ => None,
+                })
+                .collect();
+            let error = "HTTP 500 Internal Server Error".to_string();
+            assert_eq!(starts, vec![(1, 3, error.clone()), (2, 3, error)]);
+            assert_eq!(events.last(), Some(&RetryEvent::Finished));
+            assert_eq!(events.iter().filter(|e| **e == RetryEvent::Finished).count(), 1);
+        }
+
+        #[tokio::test]
+        async fn test_final_error_summarizes_attempts() {
+            // 閉じたポートへの接続は拒否される
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            drop(listener);
+
+            let client = OllamaClient::new(&url, "test").with_retry_config(fast_retry_config());
+            let error = client.generate("hi", None).await.unwrap_err();
+            assert!(
+                error.to_string().starts_with("failed after 2 attempts over 0s: connection refused"),
+                "{:#}",
+                error
+            );
+        }
+
+        #[tokio::test]
+        async fn test_cancel_interrupts_backoff() {
+            let failing = http_response("500 Internal Server Error", &[], "");
+            let url = spawn_mock_server(vec![failing]).await;
+
+            let (tx, mut rx) = mpsc::unbounded_channel();
+            let cancel = CancellationToken::new();
+            let mut client = OllamaClient::new(&url, "test").with_retry_config(RetryConfig {
+                initial_backoff_ms: 30_000,
+                max_backoff_ms: 30_000,
+                ..fast_retry_config()
+            });
+            client.set_status_sender(Some(tx));
+            client.set_cancel_token(Some(cancel.clone()));
+
+            let canceller = cancel.clone();
+            tokio::spawn(async move {
+                sleep(Duration::from_millis(50)).await;
+                canceller.cancel();
+            });
+            let started = Instant::now();
+            let error = client.generate("hi", None).await.unwrap_err();
+            assert!(started.elapsed() < Duration::from_secs(5));
+            assert!(error.to_string().contains("cancelled while waiting to retry"));
+            assert_eq!(drain(&mut rx).last(), Some(&RetryEvent::Finished));
+        }
+
+        #[tokio::test]
+        async fn test_non_retryable_status_keeps_body() {
+            let not_found = http_response("404 Not Found", &[], r#"{"error":"model 'test' not found"}"#);
+            let url = spawn_mock_server(vec![not_found]).await;
+
+            let (tx, mut rx) = mpsc::unbounded_channel();
+            let mut client = OllamaClient::new(&url, "test").with_retry_config(fast_retry_config());
+            client.set_status_sender(Some(tx));
+
+            let error = client.generate("hi", None).await.unwrap_err();
+            assert!(format!("{:#}", error).contains("model 'test' not found"));
+            assert!(drain(&mut rx).is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_options_are_sent_with_generate_and_stream() {
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let seen = Arc::clone(&requests);
+            let url = crate::llm::mock_server::spawn_mock_handler(move |request| {
+                let body = request.split("\r\n\r\n").nth(1).unwrap_or_default().to_string();
+                seen.lock().unwrap().push(body);
+                http_response("200 OK", &[], r#"{"model":"test","response":"hello","done":true}"#)
+            })
+            .await;
+            let options = RequestOptions {
+                temperature: Some(0.25),
+                num_ctx: Some(4096),
+                ..RequestOptions::default()
+            };
+            let client = OllamaClient::new(&url, "test").with_options(options);
+
+            client.generate("hi", None).await.unwrap();
+            let mut stream = client.generate_streaming("hi", None).await.unwrap();
+            while stream.next().await.is_some() {}
+
+            let bodies: Vec<serde_json::Value> =
+                requests.lock().unwrap().iter().map(|b| serde_json::from_str(b).unwrap()).collect();
+            assert_eq!(bodies.len(), 2);
+            for body in &bodies {
+                // num_ctx は固定値のまま送る
+                assert_eq!(body["options"], serde_json::json!({"num_ctx": 4096, "temperature": 0.25}));
+            }
+            assert_eq!(bodies[1]["stream"], true);
+        }
+
+        #[tokio::test]
+        async fn test_missing_model_is_not_retried() {
+            let requests = Arc::new(Mutex::new(0));
+            let seen = Arc::clone(&requests);
+            let url = crate::llm::mock_server::spawn_mock_handler(move |_| {
+                *seen.lock().unwrap() += 1;
+                http_response("500 Internal Server Error", &[], r#"{"error":"model \"Rnj-1\" not found, try pulling it first"}"#)
+            })
+            .await;
+            let client = OllamaClient::new(&url, "Rnj-1").with_retry_config(fast_retry_config());
+
+            let error = client.generate("hi", None).await.unwrap_err();
+            assert_eq!(*requests.lock().unwrap(), 1);
+            let message = format!("{:#}", error);
+            assert!(message.contains("モデルが見つかりません"), "{}", message);
+            // JSON ではなくメッセージを返す
+            assert!(message.contains(r#"model "Rnj-1" not found, try pulling it first"#), "{}", message);
+            assert!(!message.contains(r#"{"error""#), "{}", message);
+        }
+
+        #[tokio::test]
+        async fn test_error_body_with_success_status_is_reported() {
+            let url = spawn_mock_server(vec![http_response("200 OK", &[], r#"{"error":"model not found"}"#)]).await;
+            let client = OllamaClient::new(&url, "test").with_retry_config(fast_retry_config());
+
+            let error = client.generate("hi", None).await.unwrap_err();
+            assert!(format!("{:#}", error).contains("model not found"));
+            assert!(!format!("{:#}", error).contains("decoding"));
+        }
+
+        #[tokio::test]
+        async fn test_health_check() {
+            let url = spawn_mock_server(vec![http_response("200 OK", &[], r#"{"models":[{"name":"qwen:latest"}]}"#)]).await;
+            assert_eq!(OllamaClient::new(&url, "qwen").health_check().await, HealthStatus::Ok);
+        }
+
+        #[tokio::test]
+        async fn test_keep_alive_is_sent_with_every_request() {
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let seen = Arc::clone(&requests);
+            let url = crate::llm::mock_server::spawn_mock_handler(move |request| {
+                let body = request.split("\r\n\r\n").nth(1).unwrap_or_default().to_string();
+                seen.lock().unwrap().push(body);
+                http_response("200 OK", &[], r#"{"model":"test","response":"","done":true}"#)
+            })
+            .await;
+            let client = OllamaClient::new(&url, "test").with_keep_alive(Some("10m".to_string()));
+
+            client.warm_up().await.unwrap();
+            client.generate("hi", None).await.unwrap();
+            let mut stream = client.generate_streaming("hi", None).await.unwrap();
+            while stream.next().await.is_some() {}
+
+            let bodies: Vec<serde_json::Value> =
+                requests.lock().unwrap().iter().map(|b| serde_json::from_str(b).unwrap()).collect();
+            assert_eq!(bodies.len(), 3);
+            assert!(bodies.iter().all(|body| body["keep_alive"] == "10m"));
+            // ウォームアップは空のプロンプトだけ（生成しない）
+            assert_eq!(bodies[0]["prompt"], "");
+            assert!(bodies[0].get("options").is_none());
+
+            // 設定しなければ送らない
+            let client = OllamaClient::new(&url, "test");
+            client.generate("hi", None).await.unwrap();
+            let body: serde_json::Value = serde_json::from_str(requests.lock().unwrap().last().unwrap()).unwrap();
+            assert!(body.get("keep_alive").is_none());
+        }
+
+        #[tokio::test]
+        async fn test_warm_up_failure_is_an_error_not_a_panic() {
+            let url = spawn_mock_server(vec![http_response("404 Not Found", &[], r#"{"error":"model 'test' not found"}"#)]).await;
+            let client = OllamaClient::new(&url, "test");
+            let error = client.warm_up().await.unwrap_err();
+            assert!(format!("{:#}", error).contains("model 'test' not found"));
+
+            // サーバーに接続できなくても待たずに失敗する
+            let client = OllamaClient::with_timeout("http://127.0.0.1:1", "test", 1, 1);
+            assert!(client.warm_up().await.is_err());
+        }
+
+        #[tokio::test]
+        async fn test_raises_num_ctx_for_long_prompt() {
+            let show = http_response("200 OK", &[], r#"{"model_info":{"llama.context_length":131072}}"#);
+            let url = spawn_mock_server(vec![show, success_response(), success_response()]).await;
+            let client = OllamaClient::new(&url, "test").with_context_limits(None, 32768);
+
+            let long_prompt = "a".repeat(40_000);
+            client.generate(&long_prompt, None).await.unwrap();
+            assert_eq!(
+                client.last_ctx_decision(),
+                Some(CtxDecision::Raised { from: 2048, to: 16384 })
+            );
+
+            // 短いプロンプトでも引き上げた値を維持（/api/show は再呼び出ししない）
+            client.generate("hi", None).await.unwrap();
+            assert_eq!(client.last_ctx_decision(), Some(CtxDecision::Use(16384)));
+        }
+    }
 }
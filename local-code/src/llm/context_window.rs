@@ -0,0 +1,215 @@
+//! コンテキスト長（num_ctx）のネゴシエーション
+//!
+//! Ollamaはモデルが対応していても小さな num_ctx で動作するため、
+//! プロンプトが収まらない場合はリクエストごとに num_ctx を引き上げる。
+//! num_ctx の変更はサーバー側でモデルの再ロードを伴うため、
+//! 一度引き上げた値はセッション中モデルごとに維持する
+
+use std::collections::HashMap;
+
+/// Ollamaのデフォルト num_ctx
+pub const OLLAMA_DEFAULT_NUM_CTX: u32 = 2048;
+
+/// num_ctx を引き上げる上限のデフォルト
+pub const DEFAULT_MAX_NUM_CTX: u32 = 32768;
+
+/// 応答生成用に確保するトークン数
+const RESPONSE_RESERVE_TOKENS: u32 = 1024;
+
+/// テキストのトークン数を推定
+///
+/// ASCIIは4文字=1トークン、非ASCIIは2文字=1トークンとみなす
+pub fn estimate_tokens(text: &str) -> usize {
+    let ascii_count = text.chars().filter(|c| c.is_ascii()).count();
+    let non_ascii_count = text.chars().filter(|c| !c.is_ascii()).count();
+    (ascii_count / 4) + (non_ascii_count / 2) + 1
+}
+
+/// /api/show のレスポンスからモデルの最大コンテキスト長を取得
+///
+/// `model_info` の `<arch>.context_length` を参照する
+pub fn parse_context_length(show_response: &serde_json::Value) -> Option<u32> {
+    show_response
+        .get("model_info")?
+        .as_object()?
+        .iter()
+        .find(|(key, _)| key.ends_with(".context_length"))
+        .and_then(|(_, value)| value.as_u64())
+        .map(|v| v.min(u32::MAX as u64) as u32)
+}
+
+/// num_ctx の決定結果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CtxDecision {
+    /// サーバーのデフォルトのまま（num_ctx を送らない）
+    Default,
+    /// 指定値を使用（設定値またはセッション中に引き上げた値）
+    Use(u32),
+    /// このリクエストで引き上げた
+    Raised { from: u32, to: u32 },
+    /// モデルの最大値でも足りない
+    Insufficient { needed: u32, max: u32 },
+}
+
+impl CtxDecision {
+    /// リクエストに指定する num_ctx
+    pub fn num_ctx(&self) -> Option<u32> {
+        match self {
+            CtxDecision::Default => None,
+            CtxDecision::Use(n) => Some(*n),
+            CtxDecision::Raised { to, .. } => Some(*to),
+            CtxDecision::Insufficient { max, .. } => Some(*max),
+        }
+    }
+
+    /// 統計行に表示する注記
+    pub fn note(&self) -> Option<String> {
+        match self {
+            CtxDecision::Raised { to, .. } => Some(format!("ctx raised to {} for this turn", to)),
+            CtxDecision::Insufficient { needed, max } => Some(format!(
+                "prompt needs ~{} tokens but ctx is capped at {}",
+                needed, max
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// num_ctx のネゴシエーター
+#[derive(Debug, Clone)]
+pub struct ContextNegotiator {
+    /// 設定された num_ctx（未設定ならサーバーのデフォルト）
+    configured: Option<u32>,
+    /// 引き上げの上限（VRAMは有限のため）
+    ceiling: u32,
+    /// モデルごとにセッション中に使った最大の num_ctx
+    session: HashMap<String, u32>,
+}
+
+impl ContextNegotiator {
+    pub fn new(configured: Option<u32>, ceiling: u32) -> Self {
+        Self {
+            configured,
+            ceiling,
+            session: HashMap::new(),
+        }
+    }
+
+    /// セッション中に引き上げた値を破棄
+    pub fn reset(&mut self) {
+        self.session.clear();
+    }
+
+    /// 現在有効な num_ctx
+    pub fn current(&self, model: &str) -> u32 {
+        self.session
+            .get(model)
+            .copied()
+            .or(self.configured)
+            .unwrap_or(OLLAMA_DEFAULT_NUM_CTX)
+    }
+
+    /// 引き上げなしでプロンプトが収まるか
+    pub fn fits(&self, model: &str, prompt_tokens: usize) -> bool {
+        Self::needed(prompt_tokens) <= self.current(model)
+    }
+
+    fn needed(prompt_tokens: usize) -> u32 {
+        (prompt_tokens as u64 + RESPONSE_RESERVE_TOKENS as u64).min(u32::MAX as u64) as u32
+    }
+
+    /// プロンプトのトークン数とモデルの最大値から num_ctx を決定
+    pub fn negotiate(&mut self, model: &str, prompt_tokens: usize, model_max: Option<u32>) -> CtxDecision {
+        let current = self.current(model);
+        let needed = Self::needed(prompt_tokens);
+
+        if needed <= current {
+            return if self.session.contains_key(model) || self.configured.is_some() {
+                CtxDecision::Use(current)
+            } else {
+                CtxDecision::Default
+            };
+        }
+
+        let limit = model_max.map_or(self.ceiling, |max| max.min(self.ceiling));
+        if needed > limit {
+            if limit > current {
+                self.session.insert(model.to_string(), limit);
+            }
+            return CtxDecision::Insufficient {
+                needed,
+                max: limit.max(current),
+            };
+        }
+
+        let bucket = needed.checked_next_power_of_two().unwrap_or(limit).min(limit);
+        self.session.insert(model.to_string(), bucket);
+        CtxDecision::Raised { from: current, to: bucket }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_raises_to_next_power_of_two() {
+        let mut negotiator = ContextNegotiator::new(None, 32768);
+        assert_eq!(negotiator.negotiate("m", 500, Some(32768)), CtxDecision::Default);
+        assert_eq!(
+            negotiator.negotiate("m", 5000, Some(32768)),
+            CtxDecision::Raised { from: 2048, to: 8192 }
+        );
+    }
+
+    #[test]
+    fn test_sticks_at_raised_value_per_model() {
+        let mut negotiator = ContextNegotiator::new(None, 32768);
+        negotiator.negotiate("m", 5000, Some(32768));
+
+        // 小さいプロンプトでも引き上げた値を維持（再ロード回避）
+        assert_eq!(negotiator.negotiate("m", 100, Some(32768)), CtxDecision::Use(8192));
+        // 別モデルには影響しない
+        assert_eq!(negotiator.negotiate("other", 100, Some(32768)), CtxDecision::Default);
+        // さらに大きいプロンプトなら次のバケットへ
+        assert_eq!(
+            negotiator.negotiate("m", 9000, Some(32768)),
+            CtxDecision::Raised { from: 8192, to: 16384 }
+        );
+    }
+
+    #[test]
+    fn test_bounded_by_model_max_and_ceiling() {
+        let mut negotiator = ContextNegotiator::new(Some(4096), 16384);
+        // モデルの最大値が次のバケットより小さい場合は最大値を使う
+        assert_eq!(
+            negotiator.negotiate("small", 9000, Some(12000)),
+            CtxDecision::Raised { from: 4096, to: 12000 }
+        );
+        // 上限を超える場合は Insufficient
+        assert_eq!(
+            negotiator.negotiate("big", 20000, Some(131072)),
+            CtxDecision::Insufficient { needed: 21024, max: 16384 }
+        );
+        assert_eq!(negotiator.current("big"), 16384);
+    }
+
+    #[test]
+    fn test_parse_context_length() {
+        let show = serde_json::json!({
+            "model_info": {
+                "general.architecture": "llama",
+                "llama.context_length": 131072,
+                "llama.embedding_length": 4096
+            }
+        });
+        assert_eq!(parse_context_length(&show), Some(131072));
+        assert_eq!(parse_context_length(&serde_json::json!({})), None);
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(&"a".repeat(400)), 101);
+        assert_eq!(estimate_tokens(&"あ".repeat(400)), 201);
+    }
+}
@@ -0,0 +1,368 @@
+//! LLMクライアントの状態報告（/status）
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::client::{RetryableError, TokenUsage};
+use super::context_window::CtxDecision;
+use crate::agent::status::{StatusProvider, SubsystemStatus};
+
+/// 到達確認のタイムアウト
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// 詳細に表示するエラーメッセージの最大文字数
+const MAX_ERROR_CHARS: usize = 120;
+
+/// 直近の失敗したリクエスト
+#[derive(Debug, Clone)]
+pub(crate) struct LastError {
+    pub kind: RetryableError,
+    pub message: String,
+}
+
+/// クライアント間で共有する状態
+#[derive(Debug, Clone)]
+pub(crate) struct LlmHealth {
+    /// 使用中のモデル（/model で変わる）
+    pub model: String,
+    /// 直近のリクエストが失敗していればその内容（成功でクリア）
+    pub last_error: Option<LastError>,
+}
+
+impl LlmHealth {
+    pub fn new(model: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            last_error: None,
+        }
+    }
+
+    pub fn record_error(&mut self, kind: RetryableError, error: &anyhow::Error) {
+        let message = error.to_string();
+        let message = match message.char_indices().nth(MAX_ERROR_CHARS) {
+            Some((idx, _)) => format!("{}…", &message[..idx]),
+            None => message,
+        };
+        self.last_error = Some(LastError { kind, message });
+    }
+}
+
+#[derive(Deserialize)]
+struct TagsResponse {
+    #[serde(default)]
+    models: Vec<TagEntry>,
+}
+
+#[derive(Deserialize)]
+struct TagEntry {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct ModelsResponse {
+    #[serde(default)]
+    data: Vec<ModelEntry>,
+}
+
+#[derive(Deserialize)]
+struct ModelEntry {
+    id: String,
+}
+
+/// モデル一覧の取得方法（サーバーの API の種類）
+#[derive(Debug, Clone)]
+pub(crate) enum ModelsApi {
+    /// Ollama の /api/tags
+    Ollama,
+    /// OpenAI 互換の /models（API キーがあれば Bearer で送る）
+    OpenAi { api_key: Option<String> },
+}
+
+/// サーバーからモデル名一覧を取得
+pub(crate) async fn fetch_models(client: &Client, base_url: &str, api: &ModelsApi) -> Result<Vec<String>, RetryableError> {
+    let request = match api {
+        ModelsApi::Ollama => client.get(format!("{}/api/tags", base_url)),
+        ModelsApi::OpenAi { api_key } => {
+            let request = client.get(format!("{}/models", base_url));
+            match api_key {
+                Some(key) => request.bearer_auth(key),
+                None => request,
+            }
+        }
+    };
+    let response = request
+        .timeout(PROBE_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| RetryableError::from_reqwest_error(&e))?;
+    if !response.status().is_success() {
+        return Err(RetryableError::ServerError);
+    }
+    match api {
+        ModelsApi::Ollama => {
+            let tags: TagsResponse = response.json().await.map_err(|_| RetryableError::ServerError)?;
+            Ok(tags.models.into_iter().map(|m| m.name).collect())
+        }
+        ModelsApi::OpenAi { .. } => {
+            let models: ModelsResponse = response.json().await.map_err(|_| RetryableError::ServerError)?;
+            Ok(models.data.into_iter().map(|m| m.id).collect())
+        }
+    }
+}
+
+/// サーバーの到達性・モデルの有無・直近のエラーを報告
+pub struct LlmStatus {
+    client: Client,
+    base_url: String,
+    api: ModelsApi,
+    health: Arc<Mutex<LlmHealth>>,
+}
+
+impl LlmStatus {
+    pub(crate) fn new(client: Client, base_url: String, health: Arc<Mutex<LlmHealth>>) -> Self {
+        Self {
+            client,
+            base_url,
+            api: ModelsApi::Ollama,
+            health,
+        }
+    }
+
+    /// モデル一覧の取得方法を変える（OpenAI 互換サーバー）
+    pub(crate) fn with_api(mut self, api: ModelsApi) -> Self {
+        self.api = api;
+        self
+    }
+
+    /// モデルがないときの対処
+    fn missing_model_hint(&self, model: &str) -> String {
+        match self.api {
+            ModelsApi::Ollama => format!("Run `ollama pull {}` or change it with /model", model),
+            ModelsApi::OpenAi { .. } => format!("Load {} on the server or change it with /model", model),
+        }
+    }
+}
+
+/// タグなしのモデル名は `:latest` と同じ（大文字小文字は区別しない）
+fn has_model(models: &[String], model: &str) -> bool {
+    let normalize = |name: &str| name.strip_suffix(":latest").unwrap_or(name).to_ascii_lowercase();
+    let model = normalize(model);
+    models.iter().any(|name| normalize(name) == model)
+}
+
+/// 案内に並べるモデル名の最大数
+const MAX_LISTED_MODELS: usize = 8;
+
+/// 起動時の診断結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HealthStatus {
+    /// サーバーに接続でき、モデルもある
+    Ok,
+    /// サーバーに接続できない
+    ServerDown { url: String },
+    /// モデルがサーバーにない
+    ModelMissing { model: String, available: Vec<String> },
+}
+
+impl HealthStatus {
+    /// 利用者向けの対処方法（Ok なら None）
+    pub fn guidance(&self) -> Option<String> {
+        match self {
+            HealthStatus::Ok => None,
+            HealthStatus::ServerDown { url } => Some(format!(
+                "Could not reach Ollama. Is it running on {}?\nStart it with `ollama serve`, or point [ollama] url / OLLAMA_HOST at the right host.",
+                url
+            )),
+            HealthStatus::ModelMissing { model, available } => {
+                let mut text = format!("Model '{}' is not installed. Run `ollama pull {}`", model, model);
+                if available.is_empty() {
+                    text.push('.');
+                } else {
+                    let mut names: Vec<&str> = available.iter().take(MAX_LISTED_MODELS).map(String::as_str).collect();
+                    if available.len() > MAX_LISTED_MODELS {
+                        names.push("…");
+                    }
+                    text.push_str(&format!(", or switch with /model <name>.\nInstalled: {}", names.join(", ")));
+                }
+                Some(text)
+            }
+        }
+    }
+}
+
+/// サーバーに接続でき、モデルがあるかを確かめる（Ollama の /api/tags）
+pub(crate) async fn check_health(client: &Client, base_url: &str, model: &str) -> HealthStatus {
+    match fetch_models(client, base_url, &ModelsApi::Ollama).await {
+        Err(_) => HealthStatus::ServerDown { url: base_url.to_string() },
+        Ok(models) if has_model(&models, model) => HealthStatus::Ok,
+        Ok(available) => HealthStatus::ModelMissing { model: model.to_string(), available },
+    }
+}
+
+#[async_trait]
+impl StatusProvider for LlmStatus {
+    async fn status(&self) -> SubsystemStatus {
+        let health = self
+            .health
+            .lock()
+            .map(|h| h.clone())
+            .unwrap_or_else(|_| LlmHealth::new("unknown"));
+
+        let models = match fetch_models(&self.client, &self.base_url, &self.api).await {
+            Ok(models) => models,
+            Err(kind) => {
+                return SubsystemStatus::failed(
+                    "llm",
+                    format!("{} unreachable ({})", self.base_url, kind.description()),
+                )
+                .with_hint(Some(kind.hint()));
+            }
+        };
+        if !has_model(&models, &health.model) {
+            return SubsystemStatus::degraded(
+                "llm",
+                format!("model '{}' is not available on {}", health.model, self.base_url),
+            )
+            .with_hint(Some(self.missing_model_hint(&health.model)));
+        }
+        match health.last_error {
+            Some(error) => SubsystemStatus::degraded(
+                "llm",
+                format!("{}, last request failed: {}", health.model, error.message),
+            )
+            .with_hint(Some(error.kind.hint())),
+            None => SubsystemStatus::ok("llm", format!("{} at {}", health.model, self.base_url)),
+        }
+    }
+}
+
+/// num_ctx の状況と累計トークン数を報告
+pub struct ContextStatus {
+    last_ctx: Arc<Mutex<Option<CtxDecision>>>,
+    usage: Arc<Mutex<TokenUsage>>,
+}
+
+impl ContextStatus {
+    pub(crate) fn new(last_ctx: Arc<Mutex<Option<CtxDecision>>>, usage: Arc<Mutex<TokenUsage>>) -> Self {
+        Self { last_ctx, usage }
+    }
+}
+
+#[async_trait]
+impl StatusProvider for ContextStatus {
+    async fn status(&self) -> SubsystemStatus {
+        let decision = self.last_ctx.lock().ok().and_then(|d| *d);
+        let tokens = self.usage.lock().map(|u| u.total()).unwrap_or(0);
+        let ctx = match decision.and_then(|d| d.num_ctx()) {
+            Some(n) => format!("num_ctx {}", n),
+            None => "server default num_ctx".to_string(),
+        };
+        let detail = format!("{}, {} tokens used this session", ctx, tokens);
+
+        match decision {
+            None => SubsystemStatus::ok("context", "no requests yet"),
+            Some(CtxDecision::Insufficient { needed, max }) => SubsystemStatus::degraded(
+                "context",
+                format!("prompt needs ~{} tokens but num_ctx is capped at {}", needed, max),
+            )
+            .with_hint(Some("Raise [ollama] max_num_ctx or start over with /clear")),
+            Some(_) => SubsystemStatus::ok("context", detail),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::status::HealthState;
+    use crate::llm::mock_server::{http_response, spawn_mock_handler};
+
+    fn provider(url: String, model: &str) -> (LlmStatus, Arc<Mutex<LlmHealth>>) {
+        let health = Arc::new(Mutex::new(LlmHealth::new(model)));
+        (LlmStatus::new(Client::new(), url, Arc::clone(&health)), health)
+    }
+
+    async fn tags_server() -> String {
+        spawn_mock_handler(|_| {
+            http_response("200 OK", &[], r#"{"models": [{"name": "qwen:latest"}, {"name": "llama3:8b"}]}"#)
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_llm_status_states() {
+        let url = tags_server().await;
+
+        let (ok, health) = provider(url.clone(), "qwen");
+        assert_eq!(ok.status().await.state, HealthState::Ok);
+
+        // 直近のリクエストが失敗していれば degraded
+        health
+            .lock()
+            .unwrap()
+            .record_error(RetryableError::Timeout, &anyhow::anyhow!("timed out"));
+        let status = ok.status().await;
+        assert_eq!(status.state, HealthState::Degraded);
+        assert_eq!(status.hint.as_deref(), Some(RetryableError::Timeout.hint()));
+
+        let (missing, _) = provider(url, "mistral");
+        let status = missing.status().await;
+        assert_eq!(status.state, HealthState::Degraded);
+        assert!(status.hint.unwrap().contains("ollama pull mistral"));
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let (unreachable, _) = provider(closed, "qwen");
+        let status = unreachable.status().await;
+        assert_eq!(status.state, HealthState::Failed);
+        assert_eq!(status.hint.as_deref(), Some(RetryableError::Connection.hint()));
+    }
+
+    #[tokio::test]
+    async fn test_check_health_reports_each_failure() {
+        let url = tags_server().await;
+        let client = Client::new();
+
+        assert_eq!(check_health(&client, &url, "qwen").await, HealthStatus::Ok);
+        assert_eq!(check_health(&client, &url, "Qwen:latest").await, HealthStatus::Ok);
+        assert_eq!(check_health(&client, &url, "llama3:8b").await, HealthStatus::Ok);
+
+        let missing = check_health(&client, &url, "Rnj-1").await;
+        assert_eq!(
+            missing,
+            HealthStatus::ModelMissing {
+                model: "Rnj-1".to_string(),
+                available: vec!["qwen:latest".to_string(), "llama3:8b".to_string()],
+            }
+        );
+        let guidance = missing.guidance().unwrap();
+        assert!(guidance.contains("ollama pull Rnj-1"));
+        assert!(guidance.contains("Installed: qwen:latest, llama3:8b"));
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let down = check_health(&client, &closed, "qwen").await;
+        assert_eq!(down, HealthStatus::ServerDown { url: closed.clone() });
+        assert!(down.guidance().unwrap().contains(&format!("Is it running on {}?", closed)));
+        assert_eq!(HealthStatus::Ok.guidance(), None);
+    }
+
+    #[tokio::test]
+    async fn test_context_status() {
+        let last_ctx = Arc::new(Mutex::new(None));
+        let usage = Arc::new(Mutex::new(TokenUsage { prompt_tokens: 100, completion_tokens: 20 }));
+        let provider = ContextStatus::new(Arc::clone(&last_ctx), usage);
+        assert_eq!(provider.status().await.detail, "no requests yet");
+
+        *last_ctx.lock().unwrap() = Some(CtxDecision::Use(8192));
+        assert_eq!(provider.status().await.detail, "num_ctx 8192, 120 tokens used this session");
+
+        *last_ctx.lock().unwrap() = Some(CtxDecision::Insufficient { needed: 40000, max: 32768 });
+        assert_eq!(provider.status().await.state, HealthState::Degraded);
+    }
+}
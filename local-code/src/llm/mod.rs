@@ -1,7 +1,19 @@
+pub mod backend;
 pub mod client;
+pub mod context_window;
+pub mod health;
+pub mod openai;
+pub mod openai_streaming;
 pub mod streaming;
 pub mod tool_call;
 
-pub use client::OllamaClient;
+#[cfg(test)]
+pub(crate) mod mock_server;
+
+pub use backend::LlmBackend;
+pub use client::{ContextState, OllamaClient, RequestOptions, RetryEvent, RetryStatus, RetryableError, TokenUsage};
+pub use context_window::{ContextNegotiator, CtxDecision};
+pub use health::{ContextStatus, HealthStatus, LlmStatus};
+pub use openai::OpenAiCompatClient;
 pub use streaming::{StreamingResponse, StreamChunkData, StreamStats};
-pub use tool_call::{ToolCall, ToolCallParser};
+pub use tool_call::{ToolCall, ToolCallFormat, ToolCallParser};
@@ -0,0 +1,385 @@
+//! OpenAI 互換サーバー（llama.cpp server、LM Studio、vLLM など）のクライアント
+//!
+//! `/chat/completions` に system と user のメッセージを送る。リトライは Ollama と同じ方針で行い、
+//! num_ctx のネゴシエーションはしない（コンテキスト長はサーバー側で決まる）
+
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+
+use crate::agent::status::StatusProvider;
+use crate::config::RetryConfig;
+use super::backend::LlmBackend;
+use super::client::{build_http_client, check_status, decode_json, RequestOptions, Retrier, RetryEvent, TokenUsage};
+use super::health::{fetch_models, LlmHealth, LlmStatus, ModelsApi};
+use super::openai_streaming::generate_streaming as streaming_impl;
+use super::streaming::{StreamStats, StreamingResponse};
+
+/// サーバーが報告したトークン数
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub(crate) struct Usage {
+    #[serde(default)]
+    pub prompt_tokens: u32,
+    #[serde(default)]
+    pub completion_tokens: u32,
+}
+
+impl Usage {
+    /// 統計情報（時間はサーバーが返さないので受信までの経過時間を使う）
+    pub(crate) fn stats(&self, elapsed: Duration) -> StreamStats {
+        let nanos = elapsed.as_nanos() as u64;
+        StreamStats::from_counts(
+            Some(nanos),
+            Some(self.prompt_tokens),
+            Some(self.completion_tokens),
+            Some(nanos),
+        )
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ChatResponse {
+    #[serde(default)]
+    choices: Vec<ChatChoice>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChatChoice {
+    message: ChatMessage,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChatMessage {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct OpenAiCompatClient {
+    client: Client,
+    base_url: String,
+    model: String,
+    /// Authorization: Bearer に使うキー（ローカルサーバーでは不要なことが多い）
+    api_key: Option<String>,
+    /// リトライの方針と通知
+    retry: Retrier,
+    /// 使用中のモデルと直近のエラー（/status 用、クローン間で共有）
+    health: Arc<Mutex<LlmHealth>>,
+    /// 累計トークン数（サーバーが返した値）
+    usage: Arc<Mutex<TokenUsage>>,
+    /// 毎回のリクエストに付ける生成オプション
+    options: RequestOptions,
+}
+
+impl OpenAiCompatClient {
+    /// 基本的なクライアントを作成（base_url は `/v1` まで含める）
+    pub fn new(base_url: &str, model: &str) -> Self {
+        Self::with_timeout(base_url, model, 30, 300)
+    }
+
+    /// タイムアウト設定付きでクライアントを作成
+    pub fn with_timeout(base_url: &str, model: &str, connect_timeout_secs: u64, read_timeout_secs: u64) -> Self {
+        let base_url = base_url.trim_end_matches('/');
+        let health = Arc::new(Mutex::new(LlmHealth::new(model)));
+        Self {
+            client: build_http_client(base_url, connect_timeout_secs, read_timeout_secs),
+            base_url: base_url.to_string(),
+            model: model.to_string(),
+            api_key: None,
+            retry: Retrier::new(RetryConfig::default(), Arc::clone(&health)),
+            health,
+            usage: Arc::new(Mutex::new(TokenUsage::default())),
+            options: RequestOptions::default(),
+        }
+    }
+
+    /// API キーを設定
+    pub fn with_api_key(mut self, api_key: Option<String>) -> Self {
+        self.api_key = api_key;
+        self
+    }
+
+    /// リトライ設定を更新
+    pub fn with_retry_config(mut self, retry_config: RetryConfig) -> Self {
+        self.retry.config = retry_config;
+        self
+    }
+
+    /// 生成オプションを設定
+    pub fn with_options(mut self, options: RequestOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// `/chat/completions` のリクエストボディ
+    ///
+    /// num_predict は max_tokens に読み替え、num_ctx は送らない
+    fn request_body(&self, prompt: &str, system: Option<&str>, options: &RequestOptions, stream: bool) -> serde_json::Value {
+        let mut messages = Vec::new();
+        if let Some(system) = system {
+            messages.push(serde_json::json!({ "role": "system", "content": system }));
+        }
+        messages.push(serde_json::json!({ "role": "user", "content": prompt }));
+
+        let mut body = serde_json::json!({
+            "model": self.model,
+            "messages": messages,
+            "stream": stream,
+        });
+        let fields = [
+            ("temperature", options.temperature.map(serde_json::Value::from)),
+            ("top_p", options.top_p.map(serde_json::Value::from)),
+            ("top_k", options.top_k.map(serde_json::Value::from)),
+            ("max_tokens", options.num_predict.map(serde_json::Value::from)),
+            ("seed", options.seed.map(serde_json::Value::from)),
+            ("stop", (!options.stop.is_empty()).then(|| serde_json::Value::from(options.stop.clone()))),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                body[key] = value;
+            }
+        }
+        if stream {
+            // 最後のチャンクで使用量を返してもらう（対応していないサーバーは無視する）
+            body["stream_options"] = serde_json::json!({ "include_usage": true });
+        }
+        body
+    }
+
+    async fn chat(&self, prompt: &str, system: Option<&str>, options: &RequestOptions) -> Result<(String, Option<StreamStats>)> {
+        let body = self.request_body(prompt, system, options, false);
+        let url = format!("{}/chat/completions", self.base_url);
+        let client = self.client.clone();
+        let api_key = self.api_key.clone();
+        let started = Instant::now();
+
+        let response: ChatResponse = self
+            .retry
+            .send_with_retry(|| {
+                let request = client.post(&url).json(&body);
+                let request = match &api_key {
+                    Some(key) => request.bearer_auth(key),
+                    None => request,
+                };
+                async move {
+                    let response = check_status(request.send().await?).await?;
+                    decode_json::<ChatResponse>(response).await
+                }
+            })
+            .await?;
+
+        let stats = response.usage.map(|usage| {
+            if let Ok(mut total) = self.usage.lock() {
+                total.prompt_tokens += usage.prompt_tokens as u64;
+                total.completion_tokens += usage.completion_tokens as u64;
+            }
+            usage.stats(started.elapsed())
+        });
+        let text = response
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|choice| choice.message.content)
+            .unwrap_or_default();
+        Ok((text, stats))
+    }
+}
+
+#[async_trait]
+impl LlmBackend for OpenAiCompatClient {
+    fn provider(&self) -> &'static str {
+        "openai"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn set_model(&mut self, model: String) {
+        self.model = model;
+        if let Ok(mut health) = self.health.lock() {
+            health.model = self.model.clone();
+        }
+    }
+
+    fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    async fn generate_with_stats(&self, prompt: &str, system: Option<&str>) -> Result<(String, Option<StreamStats>)> {
+        self.chat(prompt, system, &self.options).await
+    }
+
+    async fn generate_with_options(&self, prompt: &str, system: Option<&str>, options: RequestOptions) -> Result<String> {
+        Ok(self.chat(prompt, system, &options.or(&self.options)).await?.0)
+    }
+
+    async fn generate_streaming(&self, prompt: &str, system: Option<&str>) -> Result<StreamingResponse> {
+        let body = self.request_body(prompt, system, &self.options, true);
+        streaming_impl(
+            &self.client,
+            &format!("{}/chat/completions", self.base_url),
+            self.api_key.as_deref(),
+            body,
+        )
+        .await
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        let api = ModelsApi::OpenAi { api_key: self.api_key.clone() };
+        fetch_models(&self.client, &self.base_url, &api)
+            .await
+            .map_err(|kind| anyhow::anyhow!("failed to list models on {} ({})", self.base_url, kind.description()))
+    }
+
+    fn options(&self) -> &RequestOptions {
+        &self.options
+    }
+
+    fn options_mut(&mut self) -> &mut RequestOptions {
+        &mut self.options
+    }
+
+    fn set_status_sender(&mut self, tx: Option<UnboundedSender<RetryEvent>>) {
+        self.retry.set_status_sender(tx);
+    }
+
+    fn set_cancel_token(&mut self, cancel: Option<CancellationToken>) {
+        self.retry.set_cancel_token(cancel);
+    }
+
+    fn token_usage(&self) -> TokenUsage {
+        self.usage.lock().map(|u| *u).unwrap_or_default()
+    }
+
+    fn status_provider(&self) -> Arc<dyn StatusProvider> {
+        let api = ModelsApi::OpenAi { api_key: self.api_key.clone() };
+        Arc::new(LlmStatus::new(self.client.clone(), self.base_url.clone(), Arc::clone(&self.health)).with_api(api))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::mock_server::{http_response, spawn_mock_handler, spawn_mock_server};
+
+    const CHAT_STREAM: &str = include_str!("../../tests/fixtures/openai/chat_stream.sse");
+
+    fn fast_retry_config() -> RetryConfig {
+        RetryConfig {
+            max_retries: 1,
+            initial_backoff_ms: 10,
+            backoff_multiplier: 2.0,
+            max_backoff_ms: 10,
+            loading_max_backoff_ms: 40,
+            loading_max_retries: 3,
+        }
+    }
+
+    #[test]
+    fn test_request_body_maps_options() {
+        let options = RequestOptions {
+            num_ctx: Some(8192),
+            temperature: Some(0.5),
+            num_predict: Some(256),
+            stop: vec!["</done>".to_string()],
+            ..RequestOptions::default()
+        };
+        let client = OpenAiCompatClient::new("http://localhost:8080/v1/", "qwen").with_options(options);
+        let body = client.request_body("hi", Some("be brief"), client.options(), false);
+
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "model": "qwen",
+                "messages": [
+                    {"role": "system", "content": "be brief"},
+                    {"role": "user", "content": "hi"},
+                ],
+                "stream": false,
+                "temperature": 0.5,
+                "max_tokens": 256,
+                "stop": ["</done>"],
+            })
+        );
+        assert_eq!(client.base_url(), "http://localhost:8080/v1");
+    }
+
+    #[tokio::test]
+    async fn test_chat_sends_bearer_and_records_usage() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&requests);
+        let url = spawn_mock_handler(move |request| {
+            seen.lock().unwrap().push(request.to_string());
+            http_response(
+                "200 OK",
+                &[],
+                r#"{"choices":[{"message":{"role":"assistant","content":"hello"}}],"usage":{"prompt_tokens":12,"completion_tokens":3}}"#,
+            )
+        })
+        .await;
+        let client = OpenAiCompatClient::new(&url, "qwen").with_api_key(Some("secret".to_string()));
+
+        let (text, stats) = client.generate_with_stats("hi", None).await.unwrap();
+        assert_eq!(text, "hello");
+        assert_eq!(stats.unwrap().eval_count, 3);
+        assert_eq!(client.token_usage(), TokenUsage { prompt_tokens: 12, completion_tokens: 3 });
+
+        let request = requests.lock().unwrap()[0].clone();
+        assert!(request.starts_with("POST /chat/completions"));
+        assert!(request.to_ascii_lowercase().contains("authorization: bearer secret"));
+    }
+
+    #[tokio::test]
+    async fn test_chat_retries_server_errors() {
+        let url = spawn_mock_server(vec![
+            http_response("503 Service Unavailable", &[], r#"{"error":"busy"}"#),
+            http_response("200 OK", &[], r#"{"choices":[{"message":{"content":"ok"}}]}"#),
+        ])
+        .await;
+        let client = OpenAiCompatClient::new(&url, "qwen").with_retry_config(fast_retry_config());
+
+        assert_eq!(client.generate("hi", None).await.unwrap(), "ok");
+    }
+
+    #[tokio::test]
+    async fn test_streaming_reads_sse_fixture() {
+        let url = spawn_mock_server(vec![format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n{}",
+            CHAT_STREAM
+        )])
+        .await;
+        let client = OpenAiCompatClient::new(&url, "qwen");
+
+        let mut stream = client.generate_streaming("hi", None).await.unwrap();
+        let mut last = None;
+        while let Some(chunk) = stream.next().await {
+            last = Some(chunk);
+        }
+        assert_eq!(stream.accumulated(), "Hello! Here is `main`:\n\n```rust\nfn main() {}\n```");
+        let last = last.unwrap();
+        assert!(last.done);
+        assert_eq!(last.stats.unwrap().prompt_eval_count, 21);
+    }
+
+    #[tokio::test]
+    async fn test_list_models() {
+        let url = spawn_mock_server(vec![http_response(
+            "200 OK",
+            &[],
+            r#"{"object":"list","data":[{"id":"qwen","object":"model"},{"id":"llama","object":"model"}]}"#,
+        )])
+        .await;
+        let client = OpenAiCompatClient::new(&url, "qwen");
+        assert_eq!(client.list_models().await.unwrap(), vec!["qwen", "llama"]);
+        // 読み込みはサーバー任せなのでウォームアップは何もしない
+        assert!(!client.warm_up().await.unwrap());
+    }
+}
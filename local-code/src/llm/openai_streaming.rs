@@ -0,0 +1,193 @@
+//! OpenAI 互換 API のストリーミング処理
+//!
+//! Server-Sent Events の `data: {...}` 行から `choices[0].delta.content` を取り出してトークンとして送り、
+//! `data: [DONE]` で終える。Ollama の NDJSON は `streaming.rs` が扱う
+
+use anyhow::Result;
+use futures::StreamExt;
+use reqwest::Client;
+use serde::Deserialize;
+use std::time::Instant;
+use tokio::sync::mpsc;
+
+use super::client::api_error_message;
+use super::openai::Usage;
+use super::streaming::{StreamChunkData, StreamingResponse};
+
+/// SSE の1イベント
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum SseEvent {
+    /// `data:` 行の内容
+    Data(String),
+    /// `data: [DONE]`
+    Done,
+}
+
+/// 受信したバイト列を行に分けて SSE のイベントを取り出す（行の途中で切れたチャンクは次に持ち越す）
+#[derive(Debug, Default)]
+pub(crate) struct SseParser {
+    buffer: Vec<u8>,
+}
+
+impl SseParser {
+    pub(crate) fn feed(&mut self, bytes: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(bytes);
+        let mut events = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let Ok(line) = std::str::from_utf8(&line) else {
+                continue;
+            };
+            // コメント（`:` 始まり）や `event:` 行は使わない
+            let Some(data) = line.trim_end().strip_prefix("data:") else {
+                continue;
+            };
+            match data.trim_start() {
+                "" => {}
+                "[DONE]" => events.push(SseEvent::Done),
+                data => events.push(SseEvent::Data(data.to_string())),
+            }
+        }
+        events
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ChatChunk {
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChunkChoice {
+    #[serde(default)]
+    delta: Delta,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct Delta {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+/// `data:` の JSON からテキストと使用量（最後のチャンクにだけ付くことがある）を取り出す
+pub(crate) fn parse_chunk(data: &str) -> Option<(String, Option<Usage>)> {
+    let chunk: ChatChunk = serde_json::from_str(data).ok()?;
+    let text = chunk
+        .choices
+        .into_iter()
+        .filter_map(|choice| choice.delta.content)
+        .collect::<String>();
+    Some((text, chunk.usage))
+}
+
+/// `/chat/completions` にストリーミングで送信
+pub async fn generate_streaming(
+    client: &Client,
+    url: &str,
+    api_key: Option<&str>,
+    body: serde_json::Value,
+) -> Result<StreamingResponse> {
+    let (tx, rx) = mpsc::channel(100);
+
+    let request = client.post(url).json(&body);
+    let request = match api_key {
+        Some(key) => request.bearer_auth(key),
+        None => request,
+    };
+    let response = request.send().await?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        let message = api_error_message(&body).unwrap_or(body);
+        return Err(anyhow::anyhow!("OpenAI互換サーバーエラー: {} - {}", status, message));
+    }
+
+    let started = Instant::now();
+    let mut stream = response.bytes_stream();
+
+    let task = tokio::spawn(async move {
+        let mut parser = SseParser::default();
+        let mut usage: Option<Usage> = None;
+
+        while let Some(chunk) = stream.next().await {
+            let Ok(bytes) = chunk else {
+                tracing::warn!("ストリーミング中にエラーが発生しました");
+                break;
+            };
+            for event in parser.feed(&bytes) {
+                match event {
+                    SseEvent::Data(data) => {
+                        let Some((text, chunk_usage)) = parse_chunk(&data) else {
+                            continue;
+                        };
+                        usage = chunk_usage.or(usage);
+                        if text.is_empty() {
+                            continue;
+                        }
+                        let chunk = StreamChunkData { text, done: false, stats: None };
+                        if tx.send(chunk).await.is_err() {
+                            return; // レシーバーがドロップされた
+                        }
+                    }
+                    SseEvent::Done => {
+                        let stats = usage.map(|u| u.stats(started.elapsed()));
+                        let _ = tx.send(StreamChunkData { text: String::new(), done: true, stats }).await;
+                        return;
+                    }
+                }
+            }
+        }
+    });
+
+    Ok(StreamingResponse::with_task(rx, task))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHAT_STREAM: &str = include_str!("../../tests/fixtures/openai/chat_stream.sse");
+
+    #[test]
+    fn test_parses_fixture_stream() {
+        let mut parser = SseParser::default();
+        let events = parser.feed(CHAT_STREAM.as_bytes());
+        assert_eq!(events.last(), Some(&SseEvent::Done));
+
+        let mut text = String::new();
+        let mut usage = None;
+        for event in &events {
+            if let SseEvent::Data(data) = event {
+                let (chunk, chunk_usage) = parse_chunk(data).unwrap();
+                text.push_str(&chunk);
+                usage = chunk_usage.or(usage);
+            }
+        }
+        assert_eq!(text, "Hello! Here is `main`:\n\n```rust\nfn main() {}\n```");
+        let usage = usage.unwrap();
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens), (21, 14));
+    }
+
+    #[test]
+    fn test_parser_joins_lines_split_across_chunks() {
+        let whole = SseParser::default().feed(CHAT_STREAM.as_bytes());
+        for size in [1, 3, 17] {
+            let mut parser = SseParser::default();
+            let events: Vec<SseEvent> = CHAT_STREAM.as_bytes().chunks(size).flat_map(|c| parser.feed(c)).collect();
+            assert_eq!(events, whole, "chunk size {}", size);
+        }
+    }
+
+    #[test]
+    fn test_ignores_comments_and_role_only_chunks() {
+        let mut parser = SseParser::default();
+        let events = parser.feed(b": keep-alive\r\nevent: message\r\ndata: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\r\n\r\n");
+        assert_eq!(events.len(), 1);
+        let SseEvent::Data(data) = &events[0] else { panic!("expected data") };
+        assert_eq!(parse_chunk(data).unwrap().0, "");
+    }
+}
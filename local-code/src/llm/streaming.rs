@@ -7,6 +7,10 @@ use futures::StreamExt;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use super::client::{api_error_message, RequestOptions};
 
 #[derive(Serialize)]
 struct GenerateRequest {
@@ -15,6 +19,10 @@ struct GenerateRequest {
     stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     system: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<RequestOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<String>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -52,7 +60,7 @@ pub struct StreamChunkData {
 }
 
 /// ストリーミング完了時の統計情報
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct StreamStats {
     /// 総処理時間（ナノ秒）
     pub total_duration: u64,
@@ -64,6 +72,30 @@ pub struct StreamStats {
     pub tokens_per_second: f64,
 }
 
+impl StreamStats {
+    /// 応答の最終チャンクに含まれるカウンタから作る（時間はナノ秒）
+    pub fn from_counts(
+        total_duration: Option<u64>,
+        prompt_eval_count: Option<u32>,
+        eval_count: Option<u32>,
+        eval_duration: Option<u64>,
+    ) -> Self {
+        let eval_count = eval_count.unwrap_or(0);
+        let eval_duration = eval_duration.unwrap_or(1); // 0除算防止
+        let tokens_per_second = if eval_duration > 0 {
+            (eval_count as f64) / (eval_duration as f64 / 1_000_000_000.0)
+        } else {
+            0.0
+        };
+        Self {
+            total_duration: total_duration.unwrap_or(0),
+            prompt_eval_count: prompt_eval_count.unwrap_or(0),
+            eval_count,
+            tokens_per_second,
+        }
+    }
+}
+
 /// ストリーミングレスポンス
 ///
 /// トークン単位でレスポンスを受信するためのイテレータ風インターフェース
@@ -71,9 +103,51 @@ pub struct StreamingResponse {
     receiver: mpsc::Receiver<StreamChunkData>,
     /// 累積されたテキスト
     accumulated_text: String,
+    /// HTTPレスポンスを読み取るタスク（ドロップ時に中断）
+    task: Option<JoinHandle<()>>,
 }
 
 impl StreamingResponse {
+    /// チャンク受信チャネルからレスポンスを作成
+    pub fn from_receiver(receiver: mpsc::Receiver<StreamChunkData>) -> Self {
+        Self {
+            receiver,
+            accumulated_text: String::new(),
+            task: None,
+        }
+    }
+
+    /// HTTPレスポンスを読み取るタスクと組にして作成（ドロップ時にタスクを中断）
+    pub(crate) fn with_task(receiver: mpsc::Receiver<StreamChunkData>, task: JoinHandle<()>) -> Self {
+        Self {
+            receiver,
+            accumulated_text: String::new(),
+            task: Some(task),
+        }
+    }
+
+    /// 次のチャンクを取得（キャンセル対応）
+    ///
+    /// トークンがキャンセルされた場合は読み取りタスクを中断してNoneを返す。
+    /// それまでに受信したテキストは accumulated() で取得できる
+    pub async fn next_or_cancel(&mut self, cancel: &CancellationToken) -> Option<StreamChunkData> {
+        tokio::select! {
+            _ = cancel.cancelled() => {
+                self.abort();
+                None
+            }
+            chunk = self.next() => chunk,
+        }
+    }
+
+    /// 読み取りタスクを中断し、HTTPレスポンスを破棄
+    pub fn abort(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+        self.receiver.close();
+    }
+
     /// 次のチャンクを取得
     ///
     /// ストリームが終了した場合はNoneを返す
@@ -125,6 +199,8 @@ pub async fn generate_streaming(
     model: &str,
     prompt: &str,
     system: Option<&str>,
+    options: Option<RequestOptions>,
+    keep_alive: Option<&str>,
 ) -> Result<StreamingResponse> {
     let (tx, rx) = mpsc::channel(100);
 
@@ -133,6 +209,8 @@ pub async fn generate_streaming(
         prompt: prompt.to_string(),
         stream: true,
         system: system.map(|s| s.to_string()),
+        options,
+        keep_alive: keep_alive.map(str::to_string),
     };
 
     let response = client
@@ -145,16 +223,17 @@ pub async fn generate_streaming(
     if !response.status().is_success() {
         let status = response.status();
         let body = response.text().await.unwrap_or_default();
+        let message = api_error_message(&body).unwrap_or(body);
         return Err(anyhow::anyhow!(
             "OLLAMAサーバーエラー: {} - {}",
             status,
-            body
+            message
         ));
     }
 
     let mut stream = response.bytes_stream();
 
-    tokio::spawn(async move {
+    let task = tokio::spawn(async move {
         let mut buffer = Vec::new();
 
         while let Some(chunk) = stream.next().await {
@@ -173,20 +252,12 @@ pub async fn generate_streaming(
                         if let Ok(chunk) = serde_json::from_str::<StreamChunk>(trimmed) {
                             let stats = if chunk.done {
                                 // 完了時に統計情報を計算
-                                let eval_count = chunk.eval_count.unwrap_or(0);
-                                let eval_duration = chunk.eval_duration.unwrap_or(1); // 0除算防止
-                                let tokens_per_second = if eval_duration > 0 {
-                                    (eval_count as f64) / (eval_duration as f64 / 1_000_000_000.0)
-                                } else {
-                                    0.0
-                                };
-
-                                Some(StreamStats {
-                                    total_duration: chunk.total_duration.unwrap_or(0),
-                                    prompt_eval_count: chunk.prompt_eval_count.unwrap_or(0),
-                                    eval_count,
-                                    tokens_per_second,
-                                })
+                                Some(StreamStats::from_counts(
+                                    chunk.total_duration,
+                                    chunk.prompt_eval_count,
+                                    chunk.eval_count,
+                                    chunk.eval_duration,
+                                ))
                             } else {
                                 None
                             };
@@ -204,6 +275,10 @@ pub async fn generate_streaming(
                             if chunk.done {
                                 return; // ストリーム完了
                             }
+                        } else if let Some(message) = api_error_message(trimmed) {
+                            // 生成の途中でサーバーが {"error": ...} を返した
+                            tracing::warn!(error = %message, "ストリーミング中にサーバーがエラーを返しました");
+                            return;
                         }
                     }
                 }
@@ -215,10 +290,13 @@ pub async fn generate_streaming(
         }
     });
 
-    Ok(StreamingResponse {
-        receiver: rx,
-        accumulated_text: String::new(),
-    })
+    Ok(StreamingResponse::with_task(rx, task))
+}
+
+impl Drop for StreamingResponse {
+    fn drop(&mut self) {
+        self.abort();
+    }
 }
 
 #[cfg(test)]
@@ -237,6 +315,35 @@ mod tests {
         assert!(chunk.stats.is_none());
     }
 
+    #[tokio::test]
+    async fn test_next_or_cancel_returns_partial_text() {
+        // 完了しないストリーム（送信側を保持したまま）
+        let (tx, rx) = mpsc::channel(10);
+        let mut stream = StreamingResponse::from_receiver(rx);
+        let cancel = CancellationToken::new();
+
+        tx.send(StreamChunkData { text: "partial".to_string(), done: false, stats: None })
+            .await
+            .unwrap();
+        assert!(stream.next_or_cancel(&cancel).await.is_some());
+
+        let canceller = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            canceller.cancel();
+        });
+
+        let next = tokio::time::timeout(
+            std::time::Duration::from_secs(2),
+            stream.next_or_cancel(&cancel),
+        )
+        .await
+        .expect("cancellation should unblock the stream");
+        assert!(next.is_none());
+        assert_eq!(stream.accumulated(), "partial");
+        assert!(tx.is_closed());
+    }
+
     #[test]
     fn test_stream_stats() {
         let stats = StreamStats {
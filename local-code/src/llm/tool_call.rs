@@ -2,6 +2,8 @@ use anyhow::{Result, anyhow};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::ops::Range;
+use std::sync::OnceLock;
 
 /// ツール呼び出しリクエスト
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,24 +14,116 @@ pub struct ToolCall {
     pub params: Value,
 }
 
-/// LLMレスポンスからツール呼び出しを抽出
-pub struct ToolCallParser;
+/// 受け付けるツール呼び出しの書式（`tools.call_format`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolCallFormat {
+    /// JSON と XML の両方
+    #[default]
+    Auto,
+    /// ```json の `{"tool": ..., "params": ...}` のみ
+    Json,
+    /// `<tool_call>` と `<invoke>` のみ
+    Xml,
+}
 
-impl ToolCallParser {
-    /// レスポンステキストからツール呼び出しを抽出
-    pub fn parse(response: &str) -> Result<Vec<ToolCall>> {
-        let mut tool_calls = Vec::new();
+impl ToolCallFormat {
+    fn accepts_json(self) -> bool {
+        self != ToolCallFormat::Xml
+    }
 
-        // ```json ... ``` ブロックを抽出
-        let json_blocks = Self::extract_json_blocks(response);
+    fn accepts_xml(self) -> bool {
+        self != ToolCallFormat::Json
+    }
 
-        for block in json_blocks {
-            if let Ok(call) = Self::parse_tool_call(&block) {
-                tool_calls.push(call);
+    /// システムプロンプトに載せるツールの呼び方
+    pub fn instructions(self) -> &'static str {
+        match self {
+            ToolCallFormat::Auto => {
+                r#"To use a tool, output a JSON block like this:
+```json
+{"tool": "tool_name", "params": {"param1": "value1"}}
+```
+These formats are accepted as well:
+<tool_call>{"name": "tool_name", "arguments": {"param1": "value1"}}</tool_call>
+<invoke name="tool_name"><parameter name="param1">value1</parameter></invoke>"#
+            }
+            ToolCallFormat::Json => {
+                r#"To use a tool, output a JSON block like this:
+```json
+{"tool": "tool_name", "params": {"param1": "value1"}}
+```"#
+            }
+            ToolCallFormat::Xml => {
+                r#"To use a tool, output an XML block like this:
+<invoke name="tool_name">
+<parameter name="param1">value1</parameter>
+</invoke>
+A JSON body inside <tool_call> tags is accepted as well:
+<tool_call>{"name": "tool_name", "arguments": {"param1": "value1"}}</tool_call>"#
             }
         }
+    }
+}
+
+/// LLMレスポンスからツール呼び出しを抽出
+///
+/// コードフェンス内の JSON に加えて、フェンス外に書かれた `{"tool": ...}` や呼び出しの配列も拾う。
+/// 厳密なパースに失敗した候補だけを修復（末尾カンマ・シングルクォート・裸のキー）してから読み直す。
+/// `<tool_call>{"name": ..., "arguments": ...}</tool_call>` と `<invoke name="..."><parameter ...>` も同じ形に揃える
+pub struct ToolCallParser;
+
+/// 応答内で見つかった呼び出し（元テキストのバイト範囲つき）
+struct FoundCalls {
+    range: Range<usize>,
+    calls: Vec<ToolCall>,
+}
+
+fn fence_regex() -> &'static Regex {
+    static FENCE: OnceLock<Regex> = OnceLock::new();
+    FENCE.get_or_init(|| Regex::new(r"```(?:json)?\s*\n?([\s\S]*?)```").unwrap())
+}
+
+fn tool_key_regex() -> &'static Regex {
+    static TOOL_KEY: OnceLock<Regex> = OnceLock::new();
+    TOOL_KEY.get_or_init(|| Regex::new(r#"["']?\btool["']?\s*:"#).unwrap())
+}
+
+fn tool_call_tag_regex() -> &'static Regex {
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    TAG.get_or_init(|| Regex::new(r"<tool_call>([\s\S]*?)</tool_call>").unwrap())
+}
+
+fn invoke_regex() -> &'static Regex {
+    static INVOKE: OnceLock<Regex> = OnceLock::new();
+    INVOKE.get_or_init(|| Regex::new(r#"<invoke\s+name\s*=\s*["']([^"']+)["']\s*>([\s\S]*?)</invoke>"#).unwrap())
+}
+
+fn parameter_regex() -> &'static Regex {
+    static PARAMETER: OnceLock<Regex> = OnceLock::new();
+    PARAMETER.get_or_init(|| {
+        Regex::new(r#"<parameter\s+name\s*=\s*["']([^"']+)["']\s*>([\s\S]*?)</parameter>"#).unwrap()
+    })
+}
+
+/// `<invoke>` を囲むだけのタグ（テキスト部分から取り除く）
+fn wrapper_regex() -> &'static Regex {
+    static WRAPPER: OnceLock<Regex> = OnceLock::new();
+    WRAPPER.get_or_init(|| Regex::new(r"</?function_calls>").unwrap())
+}
+
+impl ToolCallParser {
+    /// レスポンステキストからツール呼び出しを抽出（すべての書式を受け付ける）
+    pub fn parse(response: &str) -> Result<Vec<ToolCall>> {
+        Self::parse_with(response, ToolCallFormat::Auto)
+    }
 
-        Ok(tool_calls)
+    /// 指定した書式のツール呼び出しだけを抽出
+    pub fn parse_with(response: &str, format: ToolCallFormat) -> Result<Vec<ToolCall>> {
+        Ok(Self::find_calls(response, format)
+            .into_iter()
+            .flat_map(|found| found.calls)
+            .collect())
     }
 
     /// 最初のツール呼び出しのみを取得
@@ -38,83 +132,312 @@ impl ToolCallParser {
         Ok(calls.into_iter().next())
     }
 
-    /// JSONブロックを抽出
-    fn extract_json_blocks(text: &str) -> Vec<String> {
-        let re = Regex::new(r"```(?:json)?\s*\n?([\s\S]*?)```").unwrap();
-        let mut blocks = Vec::new();
+    /// 受け付ける書式の呼び出しを出現順に集める
+    fn find_calls(text: &str, format: ToolCallFormat) -> Vec<FoundCalls> {
+        let mut found = Vec::new();
+        if format.accepts_xml() {
+            Self::find_xml(text, &mut found);
+        }
+        if format.accepts_json() {
+            let taken: Vec<Range<usize>> = found.iter().map(|f| f.range.clone()).collect();
+            Self::find_json(text, &taken, &mut found);
+        }
+        found.sort_by_key(|f| f.range.start);
+        found
+    }
 
-        for cap in re.captures_iter(text) {
-            if let Some(content) = cap.get(1) {
-                blocks.push(content.as_str().trim().to_string());
+    /// `<tool_call>` の JSON と `<invoke>` の XML を探す
+    fn find_xml(text: &str, found: &mut Vec<FoundCalls>) {
+        for cap in tool_call_tag_regex().captures_iter(text) {
+            let body = cap.get(1).map_or("", |m| m.as_str()).trim();
+            if let Some(calls) = Self::parse_candidate(body, true) {
+                found.push(FoundCalls { range: cap.get(0).unwrap().range(), calls });
             }
         }
-
-        // ```なしの生JSONも検出
-        if blocks.is_empty() {
-            if let Some(json) = Self::find_raw_json(text) {
-                blocks.push(json);
+        for cap in invoke_regex().captures_iter(text) {
+            let whole = cap.get(0).unwrap();
+            if found.iter().any(|f| overlaps(&f.range, &whole.range())) {
+                continue;
             }
+            let params = parameter_regex()
+                .captures_iter(cap.get(2).map_or("", |m| m.as_str()))
+                .map(|p| (p[1].to_string(), parameter_value(&p[2])))
+                .collect::<serde_json::Map<_, _>>();
+            let call = ToolCall { tool: cap[1].trim().to_string(), params: Value::Object(params) };
+            found.push(FoundCalls { range: whole.range(), calls: vec![call] });
         }
-
-        blocks
     }
 
-    /// 生のJSONオブジェクトを検出
-    fn find_raw_json(text: &str) -> Option<String> {
-        let text = text.trim();
+    /// コードフェンス内とフェンス外の JSON を探す（`taken` の範囲は飛ばす）
+    fn find_json(text: &str, taken: &[Range<usize>], found: &mut Vec<FoundCalls>) {
+        let mut last = 0;
+        for cap in fence_regex().captures_iter(text) {
+            let whole = cap.get(0).unwrap();
+            if taken.iter().any(|t| overlaps(t, &whole.range())) {
+                continue;
+            }
+            Self::scan_bare_outside(text, last..whole.start(), taken, found);
+            last = whole.end();
 
-        // { で始まり } で終わる部分を探す
-        if let Some(start) = text.find('{') {
-            let mut depth = 0;
-            let chars: Vec<char> = text.chars().collect();
+            let content = cap.get(1).map_or("", |m| m.as_str()).trim();
+            // ブロック全体が1つの JSON でなければ、中の行ごとの呼び出しを探す
+            let calls = Self::parse_candidate(content, false).unwrap_or_else(|| {
+                let mut inner = Vec::new();
+                Self::scan_bare(content, 0..content.len(), &mut inner);
+                inner.into_iter().flat_map(|f| f.calls).collect()
+            });
+            if !calls.is_empty() {
+                found.push(FoundCalls { range: whole.range(), calls });
+            }
+        }
+        Self::scan_bare_outside(text, last..text.len(), taken, found);
+    }
 
-            for (i, &c) in chars.iter().enumerate().skip(start) {
-                match c {
-                    '{' => depth += 1,
-                    '}' => {
-                        depth -= 1;
-                        if depth == 0 {
-                            return Some(text[start..=i].to_string());
+    /// `range` のうち `taken` と重ならない部分だけを scan_bare する
+    fn scan_bare_outside(text: &str, range: Range<usize>, taken: &[Range<usize>], found: &mut Vec<FoundCalls>) {
+        let mut start = range.start;
+        let mut inside: Vec<&Range<usize>> = taken.iter().filter(|t| overlaps(t, &range)).collect();
+        inside.sort_by_key(|t| t.start);
+        for t in inside {
+            if t.start > start {
+                Self::scan_bare(text, start..t.start, found);
+            }
+            start = start.max(t.end);
+        }
+        if start < range.end {
+            Self::scan_bare(text, start..range.end, found);
+        }
+    }
+
+    /// フェンス外のテキストから `{"tool": ...}` と `[{...}, ...]` を探す
+    fn scan_bare(text: &str, range: Range<usize>, found: &mut Vec<FoundCalls>) {
+        let mut i = range.start;
+        while i < range.end {
+            let c = text.as_bytes()[i];
+            if c == b'{' || c == b'[' {
+                if let Some(end) = balanced_end(&text[i..range.end]).map(|len| i + len) {
+                    let candidate = &text[i..end];
+                    if tool_key_regex().is_match(candidate) {
+                        if let Some(calls) = Self::parse_candidate(candidate, false) {
+                            found.push(FoundCalls { range: i..end, calls });
+                            i = end;
+                            continue;
                         }
                     }
-                    _ => {}
                 }
             }
+            i += 1;
         }
-        None
     }
 
-    /// JSONをToolCallにパース
-    fn parse_tool_call(json_str: &str) -> Result<ToolCall> {
-        let value: Value = serde_json::from_str(json_str)?;
+    /// 候補を厳密にパースし、失敗したら修復してもう一度試す
+    ///
+    /// `tagged` は `<tool_call>` の本文（`name`/`arguments` のキーも受け付ける）
+    fn parse_candidate(candidate: &str, tagged: bool) -> Option<Vec<ToolCall>> {
+        let value = match serde_json::from_str::<Value>(candidate) {
+            Ok(value) => value,
+            Err(_) => {
+                if !tagged && !tool_key_regex().is_match(candidate) {
+                    return None;
+                }
+                let repaired = repair_json(candidate);
+                let value = serde_json::from_str::<Value>(&repaired).ok()?;
+                tracing::debug!("Repaired tool call JSON:\n  before: {}\n  after:  {}", candidate, repaired);
+                value
+            }
+        };
 
+        let calls: Vec<ToolCall> = match value {
+            Value::Array(items) => items
+                .iter()
+                .map(|item| Self::parse_tool_call(item, tagged))
+                .collect::<Result<_>>()
+                .ok()?,
+            value => vec![Self::parse_tool_call(&value, tagged).ok()?],
+        };
+        (!calls.is_empty()).then_some(calls)
+    }
+
+    /// JSONをToolCallにパース
+    ///
+    /// `tagged` なら `<tool_call>` の本文で使われる `name`/`arguments`（文字列化された JSON も可）も受け付ける
+    fn parse_tool_call(value: &Value, tagged: bool) -> Result<ToolCall> {
         let tool = value.get("tool")
+            .or_else(|| value.get("name").filter(|_| tagged))
             .and_then(|v| v.as_str())
             .ok_or_else(|| anyhow!("Missing 'tool' field"))?
             .to_string();
 
-        let params = value.get("params")
-            .cloned()
-            .unwrap_or(Value::Object(serde_json::Map::new()));
+        let params = match value.get("params").or_else(|| value.get("arguments").filter(|_| tagged)) {
+            Some(Value::String(args)) => serde_json::from_str(args)?,
+            Some(params) => params.clone(),
+            None => Value::Object(serde_json::Map::new()),
+        };
 
         Ok(ToolCall { tool, params })
     }
 
     /// レスポンスにツール呼び出しが含まれるかチェック
     pub fn has_tool_call(response: &str) -> bool {
-        let re = Regex::new(r#"\{\s*"tool"\s*:"#).unwrap();
+        let re = Regex::new(r#"\{\s*["']?tool["']?\s*:"#).unwrap();
         re.is_match(response)
     }
 
     /// ツール呼び出し部分とテキスト部分を分離
+    ///
+    /// テキスト部分からはコードフェンスとフェンス外の呼び出し（XML 形式を含む）を取り除く
     pub fn split_response(response: &str) -> (String, Vec<ToolCall>) {
-        let re = Regex::new(r"```(?:json)?\s*\n?[\s\S]*?```").unwrap();
-        let text_only = re.replace_all(response, "").trim().to_string();
-        let tool_calls = Self::parse(response).unwrap_or_default();
-        (text_only, tool_calls)
+        Self::split_response_with(response, ToolCallFormat::Auto)
+    }
+
+    /// 指定した書式でツール呼び出し部分とテキスト部分を分離
+    pub fn split_response_with(response: &str, format: ToolCallFormat) -> (String, Vec<ToolCall>) {
+        let found = Self::find_calls(response, format);
+        let mut removed: Vec<Range<usize>> = fence_regex()
+            .find_iter(response)
+            .chain(wrapper_regex().find_iter(response))
+            .map(|m| m.range())
+            .chain(found.iter().map(|f| f.range.clone()))
+            .collect();
+        removed.sort_by_key(|r| r.start);
+
+        let mut text_only = String::new();
+        let mut last = 0;
+        for range in removed {
+            if range.start >= last {
+                text_only.push_str(&response[last..range.start]);
+            }
+            last = last.max(range.end);
+        }
+        text_only.push_str(&response[last..]);
+
+        let tool_calls = found.into_iter().flat_map(|f| f.calls).collect();
+        (text_only.trim().to_string(), tool_calls)
     }
 }
 
+fn overlaps(a: &Range<usize>, b: &Range<usize>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+/// `<parameter>` の中身を値にする
+///
+/// 数値・真偽値・配列は JSON として読み、それ以外（オブジェクトを含む）は文字列のまま渡す。
+/// 1行の値は前後の空白を除き、複数行の値は先頭の改行だけを除く
+fn parameter_value(raw: &str) -> Value {
+    let trimmed = raw.trim();
+    if let Ok(value) = serde_json::from_str::<Value>(trimmed) {
+        if matches!(value, Value::Number(_) | Value::Bool(_) | Value::Array(_)) {
+            return value;
+        }
+    }
+    if trimmed.contains('\n') {
+        let text = raw.strip_prefix("\r\n").or_else(|| raw.strip_prefix('\n')).unwrap_or(raw);
+        Value::String(text.to_string())
+    } else {
+        Value::String(trimmed.to_string())
+    }
+}
+
+/// 先頭の括弧に対応する閉じ括弧までのバイト数（文字列内の括弧は数えない）
+fn balanced_end(text: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for (i, c) in text.char_indices() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => quote = Some(c),
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// 小さなモデルが出しがちな崩れた JSON を直す
+///
+/// シングルクォートの文字列・裸のキー・末尾カンマ・文字列内の生の改行・Python の True/False/None を扱う。
+/// 正しい JSON はそのまま返る
+fn repair_json(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let next_significant = |from: usize| chars[from..].iter().copied().find(|c| !c.is_whitespace());
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '"' | '\'' => {
+                out.push('"');
+                i += 1;
+                while i < chars.len() && chars[i] != c {
+                    match chars[i] {
+                        '\\' if i + 1 < chars.len() => {
+                            // シングルクォート内の \' は JSON ではただの '
+                            if chars[i + 1] != '\'' {
+                                out.push('\\');
+                            }
+                            out.push(chars[i + 1]);
+                            i += 2;
+                            continue;
+                        }
+                        '"' => out.push_str("\\\""),
+                        '\n' => out.push_str("\\n"),
+                        '\r' => out.push_str("\\r"),
+                        '\t' => out.push_str("\\t"),
+                        other => out.push(other),
+                    }
+                    i += 1;
+                }
+                out.push('"');
+                i += 1;
+            }
+            ',' if matches!(next_significant(i + 1), Some('}' | ']')) => i += 1,
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '-') {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                if next_significant(i) == Some(':') {
+                    out.push('"');
+                    out.push_str(&word);
+                    out.push('"');
+                } else {
+                    out.push_str(match word.as_str() {
+                        "True" => "true",
+                        "False" => "false",
+                        "None" => "null",
+                        word => word,
+                    });
+                }
+            }
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -153,6 +476,222 @@ Let me also check this:
     #[test]
     fn test_has_tool_call() {
         assert!(ToolCallParser::has_tool_call(r#"{"tool": "read"}"#));
+        assert!(ToolCallParser::has_tool_call("{tool: 'read'}"));
         assert!(!ToolCallParser::has_tool_call("Just a regular message"));
     }
+
+    /// 小さなモデルの実際の出力に見られた崩れ方と、期待する (ツール名, パラメータ)
+    const MALFORMED: &[(&str, &str, &str)] = &[
+        // 末尾カンマ
+        (
+            r#"{"tool": "read", "params": {"file_path": "src/main.rs",},}"#,
+            "read",
+            r#"{"file_path": "src/main.rs"}"#,
+        ),
+        // シングルクォート
+        (
+            "{'tool': 'bash', 'params': {'command': 'ls -la'}}",
+            "bash",
+            r#"{"command": "ls -la"}"#,
+        ),
+        // 裸のキー
+        (
+            r#"{tool: "glob", params: {pattern: "**/*.rs"}}"#,
+            "glob",
+            r#"{"pattern": "**/*.rs"}"#,
+        ),
+        // シングルクォート内のエスケープとダブルクォート
+        (
+            r#"{'tool': 'bash', 'params': {'command': 'echo \'it\'s\' && grep "fn main" src'}}"#,
+            "bash",
+            r#"{"command": "echo 'it's' && grep \"fn main\" src"}"#,
+        ),
+        // Python のリテラル
+        (
+            "{'tool': 'grep', 'params': {'pattern': 'TODO', 'case_insensitive': True, 'path': None}}",
+            "grep",
+            r#"{"pattern": "TODO", "case_insensitive": true, "path": null}"#,
+        ),
+        // 文字列内の生の改行
+        (
+            "{\"tool\": \"write\", \"params\": {\"file_path\": \"a.txt\", \"content\": \"one\ntwo\n\"}}",
+            "write",
+            r#"{"file_path": "a.txt", "content": "one\ntwo\n"}"#,
+        ),
+        // 全部入り
+        (
+            "{tool: 'edit', params: {file_path: 'lib.rs', old_string: 'a', new_string: 'b',},}",
+            "edit",
+            r#"{"file_path": "lib.rs", "old_string": "a", "new_string": "b"}"#,
+        ),
+        // params なし
+        ("{'tool': 'git_status'}", "git_status", "{}"),
+    ];
+
+    #[test]
+    fn test_repairs_malformed_corpus() {
+        for (input, tool, params) in MALFORMED {
+            let expected: Value = serde_json::from_str(params).unwrap();
+            for response in [
+                input.to_string(),
+                format!("Let me do that. {}", input),
+                format!("```json\n{}\n```", input),
+                format!("```\n{}\n```\nDone.", input),
+            ] {
+                let calls = ToolCallParser::parse(&response).unwrap();
+                assert_eq!(calls.len(), 1, "{}", response);
+                assert_eq!(calls[0].tool, *tool, "{}", response);
+                assert_eq!(calls[0].params, expected, "{}", response);
+            }
+        }
+    }
+
+    #[test]
+    fn test_strict_inputs_are_never_altered() {
+        let valid = [
+            r#"{"tool": "read", "params": {"file_path": "src/main.rs"}}"#,
+            r#"{"tool": "bash", "params": {"command": "echo \"a,}\" 'b' it's"}}"#,
+            r#"{"tool": "write", "params": {"content": "fn main() {\n    println!(\"{}\", [1, 2,]);\n}\n"}}"#,
+            r#"{"tool": "grep", "params": {"pattern": "True|None", "limit": -1.5e3, "flag": false, "x": null}}"#,
+            r#"{"tool": "read", "params": {"file_path": "日本語/ファイル.md", "emoji": "\u2603"}}"#,
+            r#"[{"tool": "read", "params": {}}, {"tool": "glob", "params": {"pattern": "*"}}]"#,
+        ];
+        for input in valid {
+            assert_eq!(repair_json(input), input);
+
+            let expected: Value = serde_json::from_str(input).unwrap();
+            let calls = ToolCallParser::parse(input).unwrap();
+            let expected = match expected {
+                Value::Array(items) => items,
+                value => vec![value],
+            };
+            assert_eq!(calls.len(), expected.len());
+            for (call, value) in calls.iter().zip(&expected) {
+                assert_eq!(call.tool, value["tool"].as_str().unwrap());
+                assert_eq!(call.params, value["params"]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_array_of_calls() {
+        let bare = r#"Reading both: [{"tool": "read", "params": {"file_path": "a.rs"}}, {'tool': 'read', 'params': {'file_path': 'b.rs'},}]"#;
+        let calls = ToolCallParser::parse(bare).unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[1].params["file_path"], "b.rs");
+
+        let fenced = "```json\n[\n  {\"tool\": \"glob\", \"params\": {\"pattern\": \"*.rs\"}},\n  {\"tool\": \"git_status\"}\n]\n```";
+        let calls = ToolCallParser::parse(fenced).unwrap();
+        assert_eq!(calls.iter().map(|c| c.tool.as_str()).collect::<Vec<_>>(), ["glob", "git_status"]);
+    }
+
+    #[test]
+    fn test_fenced_and_bare_calls_in_order() {
+        let response = r#"First:
+```json
+{"tool": "glob", "params": {"pattern": "*.toml"}}
+```
+then {"tool": "read", "params": {"file_path": "Cargo.toml"}}"#;
+        let (text, calls) = ToolCallParser::split_response(response);
+        assert_eq!(calls.iter().map(|c| c.tool.as_str()).collect::<Vec<_>>(), ["glob", "read"]);
+        assert_eq!(text, "First:\n\nthen");
+
+        // 1つのフェンスに複数行の呼び出し
+        let lines = "```\n{\"tool\": \"read\", \"params\": {\"file_path\": \"a\"}}\n{tool: 'read', params: {file_path: 'b'}}\n```";
+        assert_eq!(ToolCallParser::parse(lines).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_mixed_formats_in_one_response() {
+        let response = r#"I'll look around first.
+
+<tool_call>{"name": "glob", "arguments": {"pattern": "src/**/*.rs"}}</tool_call>
+
+Then read the entry point:
+<function_calls>
+<invoke name="read">
+<parameter name="file_path">src/main.rs</parameter>
+<parameter name="limit">40</parameter>
+</invoke>
+</function_calls>
+
+```json
+{"tool": "git_status"}
+```
+And finally {'tool': 'grep', 'params': {'pattern': 'TODO'}} to be thorough."#;
+
+        let (text, calls) = ToolCallParser::split_response(response);
+        assert_eq!(
+            calls.iter().map(|c| c.tool.as_str()).collect::<Vec<_>>(),
+            ["glob", "read", "git_status", "grep"]
+        );
+        assert_eq!(calls[0].params, serde_json::json!({"pattern": "src/**/*.rs"}));
+        assert_eq!(calls[1].params, serde_json::json!({"file_path": "src/main.rs", "limit": 40}));
+        assert_eq!(calls[3].params["pattern"], "TODO");
+
+        for fragment in ["<tool_call>", "<invoke", "<parameter", "function_calls", "\"tool\"", "'tool'"] {
+            assert!(!text.contains(fragment), "{} left in {:?}", fragment, text);
+        }
+        assert!(text.starts_with("I'll look around first."));
+        assert!(text.contains("Then read the entry point:"));
+        assert!(text.ends_with("to be thorough."));
+    }
+
+    #[test]
+    fn test_xml_shapes() {
+        // arguments が文字列化された JSON、修復が必要な本文
+        let calls = ToolCallParser::parse(
+            r#"<tool_call>{"name": "bash", "arguments": "{\"command\": \"cargo test\"}"}</tool_call>
+<tool_call>{'name': 'read', 'arguments': {'file_path': 'a.rs',}}</tool_call>"#,
+        )
+        .unwrap();
+        assert_eq!(calls[0].params["command"], "cargo test");
+        assert_eq!(calls[1].params["file_path"], "a.rs");
+
+        // 複数行の値は改行を保ち、オブジェクトは文字列のまま、配列は JSON として読む
+        let calls = ToolCallParser::parse(
+            "<invoke name='write'><parameter name=\"file_path\"> out.json </parameter><parameter name=\"content\">\n{\"a\": 1}\nnext\n</parameter></invoke>\n<invoke name=\"git_add\"><parameter name=\"files\">[\"a.rs\", \"b.rs\"]</parameter></invoke>",
+        )
+        .unwrap();
+        assert_eq!(calls[0].params["file_path"], "out.json");
+        assert_eq!(calls[0].params["content"], "{\"a\": 1}\nnext\n");
+        assert_eq!(calls[1].params["files"], serde_json::json!(["a.rs", "b.rs"]));
+
+        // name/arguments はタグの中だけ（フェンス内の package.json などは呼び出しではない）
+        let fenced = "```json\n{\"name\": \"my-app\", \"version\": \"1.0.0\"}\n```";
+        assert!(ToolCallParser::parse(fenced).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_call_format_restricts_parsing() {
+        let response = "```json\n{\"tool\": \"glob\"}\n```\n<invoke name=\"read\"></invoke>";
+        let tools = |format| {
+            ToolCallParser::parse_with(response, format)
+                .unwrap()
+                .into_iter()
+                .map(|c| c.tool)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(tools(ToolCallFormat::Auto), ["glob", "read"]);
+        assert_eq!(tools(ToolCallFormat::Json), ["glob"]);
+        assert_eq!(tools(ToolCallFormat::Xml), ["read"]);
+
+        assert!(ToolCallFormat::Auto.instructions().contains("<invoke name="));
+        assert!(ToolCallFormat::Auto.instructions().contains("```json"));
+        assert!(!ToolCallFormat::Json.instructions().contains("<tool_call>"));
+        assert!(!ToolCallFormat::Xml.instructions().contains("```json"));
+    }
+
+    #[test]
+    fn test_ignores_text_that_is_not_a_tool_call() {
+        for response in [
+            "Use `{}` as a placeholder and [1, 2, 3] as the list.",
+            r#"The config looks like {"name": "x", "tool": 3}."#,
+            "```rust\nfn main() { let tool = 1; }\n```",
+            "An unbalanced {'tool': 'read' call",
+            "I'd call {tool} with [brackets] later.",
+        ] {
+            assert!(ToolCallParser::parse(response).unwrap().is_empty(), "{}", response);
+        }
+    }
 }
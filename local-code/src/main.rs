@@ -1,25 +1,37 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{CommandFactory, Parser};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::fs;
-use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
 
 use local_code::{
-    config::Config,
+    config::{Config, ConfigSource, LlmProvider, OllamaConfig, StorageConfig},
+    eval::{self, EvalOptions, EvalReport},
     Mode, ModeManager,
-    Command, CommandHandler, CommandResult, Repl,
+    CommandHandler, CommandResult, Repl,
     ToolRegistry,
     SkillRegistry, SkillExecutor,
-    Agent, AgentConfig, CodeVerifier,
-    tools::file::{ReadTool, WriteTool, EditTool},
-    tools::search::{GlobTool, GrepTool},
-    tools::bash::BashTool,
-    tools::git::{GitStatusTool, GitDiffTool, GitAddTool, GitCommitTool, GitLogTool},
-    tools::lsp::{LspClient, LspDefinitionTool, LspReferencesTool, LspDiagnosticsTool},
-    skills::{SkillContext, TriggerDetector, load_superpowers_commands, EmbeddedSuperpowers},
-    cli::{print_startup_banner, print_formatted_block, print_processing, print_separator, OutputPostProcessor},
+    Agent, AgentConfig, VerificationPipeline,
+    agent::{context_warning, AutoApprove, AutoDeny, CodeFixer, ConfirmationPolicy, EnvProber, HistoryManager, MessageOrigin, PassphraseSource, PinStage, PromptDebugger, ReadToolOutputTool, SessionResettable, SessionTransition, StatusProvider, StorageCipher, ToolConfirmation, ToolOutputManager, TurnRecord, VerificationReport},
+    agent::history::autosave_name,
+    agent::init::{agent_md_target, draft_agent_md, write_agent_md, ProjectFacts, AGENT_MD},
+    agent::export::{export_target, write_export, ConversationExporter},
+    tools::file::{ReadTool, WriteTool, WriteConfirmer, EditTool, ApplyPatchTool, MoveTool, DeleteTool, SharedFileObserver},
+    tools::search::{GlobTool, GrepTool, TreeTool},
+    tools::{render_todos, DiskStatus, DryRun, PathPolicy, TodoItem, TodoList, TodoTool, Tool, ToolEffects, Workspace, WriteGuard},
+    tools::external::ExternalTool,
+    tools::web::WebFetchTool,
+    tools::bash::{BashKillTool, BashOutputTool, BashPolicy, BashTool, JobManager},
+    tools::git::{GitStatusTool, GitDiffTool, GitAddTool, GitCommitTool, GitLogTool, GitBranchTool, GitCheckoutTool, GitStashTool, GitShowTool, GitBlameTool, GitPushTool, RepoInfo},
+    tools::lsp::{LspManager, LspRouter, LspDefinitionTool, LspReferencesTool, LspDiagnosticsTool, LspHoverTool, LspSymbolsTool, LspOutlineTool, LspRenameTool, LspStatus},
+    skills::{grant_extra_paths, scaffold, SharedSkillRegistry, SkillContext, TriggerDetector, load_superpowers_commands, EmbeddedSuperpowers},
+    cli::{broadcast, color, watch},
+    cli::{inputs, CommandTable, ConfigAction, DryRunAction, Locale, print_mode, prompt_lint, ask_send_or_edit, InputPreprocessor, PromptLinter, SendChoice},
+    cli::output::print_debug,
+    cli::{configure_pager, PagingMode, print_startup_banner, print_formatted_block, print_dim_block, print_info, print_processing, print_separator, print_status, prompt_passphrase, confirm, ConfirmResult, DialogConfirmation, OutputPostProcessor, Activity},
+    llm::{HealthStatus, RetryEvent},
 };
 
 #[derive(Parser, Debug)]
@@ -43,126 +55,589 @@ struct Args {
     #[arg(long)]
     mode: Option<String>,
 
-    /// プロジェクトルートディレクトリ
-    #[arg(short, long)]
-    project: Option<PathBuf>,
+    /// プロジェクトルートディレクトリ（複数指定でマルチルートのワークスペース、`label=path` でラベルを指定）
+    #[arg(short = 'C', long)]
+    project: Vec<PathBuf>,
+
+    /// ワークスペースファイル（`[[roots]]` に label と path を並べた TOML）
+    #[arg(long, conflicts_with = "project")]
+    workspace: Option<PathBuf>,
+
+    /// 非対話モード: このプロンプトを1回だけ処理して応答を標準出力に書く（パイプ入力は後ろに添付）
+    #[arg(short = 'p', long)]
+    prompt: Option<String>,
+
+    /// 非対話モードで確認が必要なツールを許可する（既定では拒否）
+    #[arg(long)]
+    yes: bool,
+
+    /// 変更系のツールを実行せずに模擬する（書き込みはメモリ上にだけ反映し、終了時に一覧を表示）
+    #[arg(long)]
+    dry_run: bool,
+
+    /// このプロジェクトの直近の自動保存から会話を再開する
+    #[arg(long, visible_alias = "continue")]
+    resume: bool,
+
+    /// 非対話モードの出力形式（json はターンごとにツール呼び出しや統計を含む JSON を1行で出す）
+    #[arg(long, value_enum, default_value = "text")]
+    output: print_mode::OutputFormat,
 
     /// 詳細ログを表示 (INFO level)
     #[arg(long)]
     verbose: bool,
+
+    /// 色やエスケープシーケンスを出力しない（NO_COLOR と同じ）
+    #[arg(long)]
+    no_color: bool,
+
+    /// 送信プロンプトと生レスポンスを .local-code/debug/ に保存 (LOCAL_CODE_DEBUG_PROMPT)
+    #[arg(long)]
+    debug_prompt: bool,
+
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum CliCommand {
+    /// 保存済み会話ファイルの管理
+    Storage {
+        #[command(subcommand)]
+        action: StorageAction,
+    },
+    /// 評価スイートを実行する（フィクスチャごとに一時ディレクトリで実行）
+    Eval(EvalArgs),
+    /// 同じプロジェクトのセッション（`[ui] broadcast = true`）を読み取り専用で表示する
+    Watch {
+        /// プロジェクトルートディレクトリ
+        #[arg(long)]
+        project: Option<PathBuf>,
+    },
+    /// シェルの補完スクリプトを標準出力に書く（bash / zsh / fish / powershell）
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+    /// 設定ファイル
+    Config {
+        #[command(subcommand)]
+        action: ConfigFileAction,
+    },
+    /// スキル
+    Skills {
+        #[command(subcommand)]
+        action: SkillsAction,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum ConfigFileAction {
+    /// 読み込む設定ファイル（/config save の書き出し先）のパスを表示する
+    Path,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum SkillsAction {
+    /// 読み込めるスキルの名前を表示する（REPL は起動しない）
+    List,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
+struct EvalArgs {
+    #[command(subcommand)]
+    action: Option<EvalAction>,
+
+    /// フィクスチャを含むスイートディレクトリ
+    suite: Option<PathBuf>,
+
+    /// 同時に実行するフィクスチャ数
+    #[arg(long, default_value_t = 1)]
+    parallel: usize,
+
+    /// フィクスチャごとのタイムアウト（秒、expect.toml の timeout_secs が優先）
+    #[arg(long, default_value_t = 300)]
+    timeout: u64,
+
+    /// num_ctx（未指定なら設定ファイルの値）
+    #[arg(long)]
+    num_ctx: Option<u32>,
+
+    /// JSONレポートの出力先（既定: .local-code/eval/<時刻>.json）
+    #[arg(long)]
+    report: Option<PathBuf>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum EvalAction {
+    /// 2つのJSONレポートを比較する
+    Diff {
+        before: PathBuf,
+        after: PathBuf,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum StorageAction {
+    /// 平文の会話ファイルを暗号化する
+    EncryptExisting,
+}
+
+/// 設定のパスとSuperpowersのスキルを読み込む（無効にしたスキルは除く）
+async fn load_skill_registry(config: &Config, superpowers_dir: Option<&std::path::Path>) -> Result<SkillRegistry> {
+    let mut skill_registry = SkillRegistry::new();
+    if let Some(custom_path) = &config.skills.custom_path {
+        skill_registry.add_search_path(PathBuf::from(custom_path));
+    }
+    if let Some(dir) = superpowers_dir {
+        skill_registry.add_superpowers_path(dir.join("skills"));
+    }
+    skill_registry.set_disabled(&config.skills.disabled);
+    skill_registry.load_all().await?;
+    Ok(skill_registry)
+}
+
+/// 設定からパスフレーズの取得方法を決める（keyfile > 環境変数 > 入力）
+fn passphrase_source(storage: &StorageConfig) -> PassphraseSource {
+    if let Some(keyfile) = &storage.keyfile {
+        PassphraseSource::File(PathBuf::from(keyfile))
+    } else if let Some(var) = &storage.passphrase_env {
+        PassphraseSource::Env(var.clone())
+    } else {
+        PassphraseSource::Prompt(Arc::new(|| {
+            Ok(prompt_passphrase("Passphrase for saved conversations")?)
+        }))
+    }
+}
+
+/// `local-code eval` を実行（全件合格・回帰なしなら true）
+async fn run_eval(args: &EvalArgs, ollama: OllamaConfig, max_messages: usize) -> Result<bool> {
+    if let Some(EvalAction::Diff { before, after }) = &args.action {
+        let diff = eval::diff_reports(&EvalReport::load(before)?, &EvalReport::load(after)?);
+        println!("{}", diff.to_display());
+        return Ok(diff.regressions.is_empty());
+    }
+
+    let Some(suite) = &args.suite else {
+        anyhow::bail!("Usage: local-code eval <suite-dir> | local-code eval diff <a.json> <b.json>");
+    };
+    let fixtures = eval::load_suite(suite)?;
+    let mut options = EvalOptions::new(ollama)
+        .with_timeout(std::time::Duration::from_secs(args.timeout))
+        .with_parallel(args.parallel);
+    options.max_messages = max_messages;
+
+    print_formatted_block(
+        "INFO",
+        &format!("Running {} fixture(s) with {}", fixtures.len(), options.ollama.model),
+    );
+    let report = eval::run_suite(suite, &fixtures, &options).await;
+    println!("{}", report.to_table());
+
+    let report_path = args.report.clone().unwrap_or_else(|| {
+        PathBuf::from(".local-code/eval")
+            .join(format!("{}.json", chrono::Local::now().format("%Y%m%d-%H%M%S")))
+    });
+    report.save(&report_path)?;
+    print_formatted_block("INFO", &format!("Report saved to {}", report_path.display()));
+
+    Ok(report.all_passed())
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // トレーシング初期化（デフォルトはWARN、--verboseでINFO）
     let args = Args::parse();
+    if let Some(CliCommand::Completions { shell }) = &args.command {
+        clap_complete::generate(*shell, &mut Args::command(), "local-code", &mut std::io::stdout());
+        return Ok(());
+    }
+    color::init(color::ColorMode::detect(args.no_color));
     let default_level = if args.verbose { "info" } else { "warn" };
     tracing_subscriber::fmt()
         .with_env_filter(
             tracing_subscriber::EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(default_level))
         )
+        .with_writer(std::io::stderr)
+        .with_ansi(color::enabled())
         .init();
 
-    // 設定ファイルを読み込み
-    let config = if args.config.exists() {
-        Config::load_from_file(&args.config).unwrap_or_else(|e| {
-            tracing::warn!("Failed to load config file: {}, using defaults", e);
-            Config::default()
-        })
-    } else {
-        Config::load_default().unwrap_or_else(|e| {
-            tracing::warn!("Failed to load default config: {}, using defaults", e);
-            Config::default()
-        })
-    };
+    // プロジェクトルート（複数ルートなら最初のルート。bash と設定ファイルはここを基準にする）
+    let workspace = Arc::new(match (&args.workspace, args.project.as_slice()) {
+        (Some(file), _) => Workspace::load_file(file)?,
+        (None, []) => Workspace::single(std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))),
+        (None, [root]) => Workspace::single(root.clone()),
+        (None, roots) => Workspace::from_paths(roots)?,
+    });
+    let project_root = workspace.primary().path.clone();
+    if workspace.is_multi() {
+        tracing::info!("Workspace roots: {}", workspace.labels().join(", "));
+    }
 
-    // コマンドライン引数で設定を上書き
-    let ollama_url = args.ollama_url.clone().unwrap_or_else(|| config.ollama.url.clone());
-    let model = args.model.clone().unwrap_or_else(|| config.ollama.model.clone());
+    // 設定ファイルを読み込み（基本の設定ファイルにプロジェクトの .local-code/config.toml を重ねる）
+    let explicit_config = args.config.exists().then(|| args.config.clone());
+    // /config save の書き出し先
+    let user_config_path = explicit_config.clone().unwrap_or_else(Config::default_config_path);
+    if let Some(CliCommand::Config { action: ConfigFileAction::Path }) = &args.command {
+        println!("{}", user_config_path.display());
+        return Ok(());
+    }
+    let (mut config, config_errors) = Config::load_for_project(explicit_config.as_deref(), &project_root);
+    for path in &config.files {
+        tracing::info!("Config layer: {}", path.display());
+    }
+    for (key, source) in &config.sources {
+        if let ConfigSource::Environment(var) = source {
+            tracing::info!("Config {} from environment ({})", key, var);
+        }
+    }
+    for error in &config_errors {
+        tracing::warn!("{}", error);
+    }
+
+    // コマンドライン引数で設定を上書き（既定値 < 環境変数 < 設定ファイル < プロジェクトの設定ファイル < LOCAL_CODE_* の環境変数 < 引数）
+    config.override_from_cli("ollama.url", "--ollama-url", args.ollama_url.as_deref());
+    config.override_from_cli("ollama.model", "--model", args.model.as_deref());
+    config.override_from_cli("agent.initial_mode", "--mode", args.mode.as_deref());
+
+    // 会話ファイルの暗号化（暗号化済みファイルは encrypt = false でも読めるよう常に用意）
+    let storage_cipher = Arc::new(StorageCipher::new(passphrase_source(&config.storage)));
+
+    if let Some(CliCommand::Storage { action: StorageAction::EncryptExisting }) = &args.command {
+        let result = HistoryManager::new()
+            .map(|manager| manager.with_cipher(Arc::clone(&storage_cipher), true))
+            .and_then(|manager| manager.encrypt_existing());
+        match result {
+            Ok(count) => print_formatted_block("INFO", &format!("Encrypted {} conversation file(s)", count)),
+            Err(e) => print_formatted_block("ERROR", &format!("Failed to encrypt conversations: {:#}", e)),
+        }
+        return Ok(());
+    }
+
+    if let Some(CliCommand::Skills { action: SkillsAction::List }) = &args.command {
+        let skill_registry = load_skill_registry(&config, find_superpowers_dir().as_deref()).await?;
+        let mut names = skill_registry.names();
+        names.sort();
+        for name in names {
+            println!("{}", name);
+        }
+        return Ok(());
+    }
+
+    if let Some(CliCommand::Eval(eval_args)) = &args.command {
+        let mut ollama = config.ollama.clone();
+        if eval_args.num_ctx.is_some() {
+            ollama.num_ctx = eval_args.num_ctx;
+        }
+        let success = run_eval(eval_args, ollama, config.agent.max_messages).await?;
+        if !success {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(CliCommand::Watch { project }) = &args.command {
+        let project_root = project
+            .clone()
+            .or_else(|| args.project.first().cloned())
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_else(|| PathBuf::from("."));
+        return watch::run_watch(&project_root).await;
+    }
+
+    // 非対話モード（-p またはパイプ入力）
+    let piped_stdin = print_mode::read_piped_stdin().unwrap_or_else(|e| {
+        tracing::warn!("Failed to read stdin: {}", e);
+        None
+    });
+    let print_mode = print_mode::compose_prompt(args.prompt.as_deref(), piped_stdin.as_deref()).is_some();
+    if args.output != print_mode::OutputFormat::Text && !print_mode {
+        eprintln!("Error: --output json requires -p or piped input");
+        std::process::exit(2);
+    }
+    // 設定ファイルと環境変数の問題（問題のある項目は既定値、読めないファイルと環境変数は無視して続ける）
+    for error in &config_errors {
+        let fallback = if matches!(error.source, ConfigSource::Environment(_)) {
+            "Ignoring this variable."
+        } else if error.problems.iter().all(|problem| problem.key().is_some()) {
+            "Using the defaults for these settings."
+        } else {
+            "Ignoring this file."
+        };
+        print_startup_warning(&format!("{}\n{}", error, fallback), print_mode);
+    }
+
+    // OpenAI 互換サーバーを使う場合は [llm] url に接続する（モデル名などは [ollama] と共通）
+    let llm_url = match config.llm.provider {
+        LlmProvider::Ollama => config.ollama.url.clone(),
+        LlmProvider::OpenAi => config.llm.url.clone(),
+    };
+    let model = config.ollama.model.clone();
 
     tracing::info!("local-code v{} starting...", local_code::VERSION);
-    tracing::info!("OLLAMA URL: {}", ollama_url);
+    tracing::info!("LLM: {:?} at {}", config.llm.provider, llm_url);
     tracing::info!("Model: {}", model);
-    let mode_str = args.mode.clone().unwrap_or_else(|| config.agent.initial_mode.clone());
+    let mode_str = config.agent.initial_mode.clone();
     tracing::info!("Mode: {}", mode_str);
     tracing::info!("Connect timeout: {}s", config.ollama.connect_timeout);
     tracing::info!("Read timeout: {}s", config.ollama.read_timeout);
 
-    // 初期モードをパース
-    let initial_mode = Mode::parse_mode(&mode_str).unwrap_or_else(|| {
+    // モードマネージャーを初期化（[agent.modes] の許可リストを反映）
+    let mode_manager = ModeManager::new(Mode::Execute).with_allowlists(&config.agent.modes);
+
+    // 初期モードをパース（設定で定義したモード名も可）
+    let initial_mode = mode_manager.resolve(&mode_str).unwrap_or_else(|| {
         tracing::warn!("Invalid mode '{}', using execute", mode_str);
         Mode::Execute
     });
+    mode_manager.set(initial_mode.clone()).await;
 
-    // モードマネージャーを初期化
-    let mode_manager = ModeManager::new(initial_mode);
-
-    // ツールレジストリを初期化
-    let mut tool_registry = ToolRegistry::new();
-    tool_registry.register(Arc::new(ReadTool::new()));
-    tool_registry.register(Arc::new(WriteTool::new()));
-    tool_registry.register(Arc::new(EditTool::new()));
-    tool_registry.register(Arc::new(GlobTool::new()));
-    tool_registry.register(Arc::new(GrepTool::new()));
-    tool_registry.register(Arc::new(BashTool::with_timeout(config.tools.bash_timeout)));
-    tool_registry.register(Arc::new(GitStatusTool::new()));
-    tool_registry.register(Arc::new(GitDiffTool::new()));
-    tool_registry.register(Arc::new(GitAddTool::new()));
-    tool_registry.register(Arc::new(GitCommitTool::new()));
-    tool_registry.register(Arc::new(GitLogTool::new()));
-    // LSPツール（クライアントは後で初期化）
-    let lsp_client = Arc::new(Mutex::new(None));
-    tool_registry.register(Arc::new(LspDefinitionTool::new(Arc::clone(&lsp_client))));
-    tool_registry.register(Arc::new(LspReferencesTool::new(Arc::clone(&lsp_client))));
-    tool_registry.register(Arc::new(LspDiagnosticsTool::new(Arc::clone(&lsp_client))));
+    // プロジェクトルートを所有するリポジトリ（プロセスのcwdではなく）
+    let project_repo = RepoInfo::resolve(&project_root);
+    let repo_root = project_repo
+        .as_ref()
+        .map(|r| r.root.clone())
+        .unwrap_or_else(|| project_root.clone());
+    if let Some(cwd_repo) = std::env::current_dir().ok().and_then(|cwd| RepoInfo::resolve(&cwd)) {
+        if project_repo.as_ref() != Some(&cwd_repo) {
+            let message = format!(
+                "Current directory belongs to repository {}, but the project root {} belongs to {}. Git tools will use the project's repository.",
+                cwd_repo.root.display(),
+                project_root.display(),
+                project_repo.as_ref().map(|r| r.root.display().to_string()).unwrap_or_else(|| "no repository".to_string())
+            );
+            tracing::warn!("{}", message);
+            print_startup_warning(&message, print_mode);
+        }
+    }
 
-    tracing::info!("Registered {} tools", tool_registry.len());
+    // 言語サーバー（起動は後で。落ちたらツールの呼び出し時に起動し直す。ファイルツールの書き換えも伝える）
+    // ワークスペースのルートごとに用意し、ファイルを含むルートのものに問い合わせる
+    let lsp = Arc::new(LspRouter::for_workspace(Arc::clone(&workspace), |root| {
+        let lsp_command = config
+            .lsp
+            .command
+            .clone()
+            .or_else(|| root.join("Cargo.toml").exists().then(|| "rust-analyzer".to_string()));
+        LspManager::new(lsp_command, config.lsp.args.clone(), root)
+            .with_request_timeout(std::time::Duration::from_secs(config.lsp.request_timeout))
+    }));
 
-    // スキルレジストリを初期化
-    let mut skill_registry = SkillRegistry::new();
-    if let Some(custom_path) = &config.skills.custom_path {
-        skill_registry.add_search_path(PathBuf::from(custom_path));
+    // ツールレジストリを初期化
+    let mut tool_registry = ToolRegistry::new()
+        .with_disabled(config.tools.disabled.iter().cloned())
+        .with_timeouts(&config.tools.timeouts);
+    // ファイルツールはプロジェクト（ワークスペースの各ルート）と extra_allowed_paths の下だけを扱う
+    let path_policy = Arc::new(
+        PathPolicy::new(&project_root)
+            .with_allowed_paths(workspace.roots().iter().map(|r| r.path.clone()))
+            .with_allowed_paths(config.tools.expanded_allowed_paths())
+            .with_read_outside(config.tools.allow_read_outside_project),
+    );
+    // ラベル付きのパス（`backend:src/api.rs`）は複数ルートのときだけ解決する
+    tool_registry.register(Arc::new(ReadTool::new().with_workspace(Arc::clone(&workspace)).with_path_policy(Arc::clone(&path_policy))));
+    // 副作用のあるツールは実行前に確認する（エージェントのループとツール自身の確認で共有）
+    // 対話モードではダイアログで尋ね、a / A でセッション中は確認しない
+    // 非対話モードでは尋ねずに --yes に従う
+    let confirm_policy: Arc<dyn ConfirmationPolicy> = match (print_mode, args.yes) {
+        (false, _) => Arc::new(DialogConfirmation),
+        (true, true) => Arc::new(AutoApprove),
+        (true, false) => Arc::new(AutoDeny),
+    };
+    let confirmation = Arc::new(ToolConfirmation::new(confirm_policy));
+    // 既存ファイルの上書きは差分を、move・delete は対象を、bash はコマンドを、push はコミットを見せて確認する
+    // accept-edits モードではファイル書き込みの確認を省略する
+    let confirmer = |tool: &'static str, effects: ToolEffects| -> WriteConfirmer {
+        let mode_manager = mode_manager.clone();
+        let confirmation = Arc::clone(&confirmation);
+        Arc::new(move |details: &str| {
+            let mode = mode_manager.try_current().unwrap_or_default();
+            confirmation.confirm(tool, effects, &mode, details)
+        })
+    };
+    let write_guard = WriteGuard::new(config.tools.max_write_bytes, config.tools.min_free_bytes);
+    let file_observer: SharedFileObserver = lsp.clone();
+    tool_registry.register(Arc::new(WriteTool::new().with_guard(write_guard.clone()).with_workspace(Arc::clone(&workspace)).with_observer(Arc::clone(&file_observer)).with_confirm(confirmer("write", ToolEffects::Writes)).with_path_policy(Arc::clone(&path_policy))));
+    tool_registry.register(Arc::new(EditTool::new().with_workspace(Arc::clone(&workspace)).with_observer(Arc::clone(&file_observer)).with_path_policy(Arc::clone(&path_policy))));
+    tool_registry.register(Arc::new(
+        ApplyPatchTool::new(project_root.clone())
+            .with_guard(write_guard)
+            .with_workspace(Arc::clone(&workspace))
+            .with_observer(Arc::clone(&file_observer))
+            .with_path_policy(Arc::clone(&path_policy)),
+    ));
+    tool_registry.register(Arc::new(
+        MoveTool::new(project_root.clone())
+            .with_workspace(Arc::clone(&workspace))
+            .with_observer(Arc::clone(&file_observer))
+            .with_confirm(confirmer("move", ToolEffects::Writes))
+            .with_path_policy(Arc::clone(&path_policy)),
+    ));
+    tool_registry.register(Arc::new(
+        DeleteTool::new(project_root.clone())
+            .with_workspace(Arc::clone(&workspace))
+            .with_observer(Arc::clone(&file_observer))
+            .with_confirm(confirmer("delete", ToolEffects::Writes))
+            .with_path_policy(Arc::clone(&path_policy)),
+    ));
+    tool_registry.register(Arc::new(
+        GlobTool::new()
+            .with_include_nested_repos(config.tools.include_nested_repos)
+            .with_workspace(Arc::clone(&workspace))
+            .with_path_policy(Arc::clone(&path_policy)),
+    ));
+    tool_registry.register(Arc::new(
+        GrepTool::new()
+            .with_include_nested_repos(config.tools.include_nested_repos)
+            .with_max_file_size(config.tools.grep_max_file_size)
+            .with_workspace(Arc::clone(&workspace))
+            .with_path_policy(Arc::clone(&path_policy)),
+    ));
+    tool_registry.register(Arc::new(TreeTool::new().with_workspace(Arc::clone(&workspace)).with_path_policy(Arc::clone(&path_policy))));
+    let job_manager = Arc::new(JobManager::new());
+    // bash は許可リストの環境変数だけを渡し、コマンドと環境変数の要約を見せて確認する
+    tool_registry.register(Arc::new(
+        BashTool::with_timeout(config.tools.bash_timeout, project_root.clone())
+            .with_jobs(Arc::clone(&job_manager))
+            .with_policy(BashPolicy::from_config(&config.tools.bash)?)
+            .with_confirm(confirmer("bash", ToolEffects::Executes)),
+    ));
+    tool_registry.register(Arc::new(BashOutputTool::new(Arc::clone(&job_manager))));
+    tool_registry.register(Arc::new(BashKillTool::new(Arc::clone(&job_manager))));
+    let mut tool_output = ToolOutputManager::new(config.tools.max_output_lines, config.tools.max_output_bytes)
+        .with_dir(config.tools.save_full_output.then(ToolOutputManager::default_dir).flatten());
+    if config.storage.encrypt {
+        tool_output = tool_output.with_cipher(Arc::clone(&storage_cipher));
+    }
+    let tool_output = Arc::new(tool_output);
+    tool_registry.register(Arc::new(ReadToolOutputTool::new(Arc::clone(&tool_output))));
+    tool_registry.register(Arc::new(GitStatusTool::new().with_root(repo_root.clone())));
+    tool_registry.register(Arc::new(GitDiffTool::new().with_root(repo_root.clone())));
+    tool_registry.register(Arc::new(GitAddTool::new().with_root(repo_root.clone())));
+    tool_registry.register(Arc::new(GitCommitTool::new().with_root(repo_root.clone())));
+    tool_registry.register(Arc::new(GitLogTool::new().with_root(repo_root.clone())));
+    tool_registry.register(Arc::new(GitShowTool::new().with_root(repo_root.clone())));
+    tool_registry.register(Arc::new(GitBlameTool::new().with_root(repo_root.clone())));
+    tool_registry.register(Arc::new(GitPushTool::new().with_root(repo_root.clone()).with_confirm(confirmer("git_push", ToolEffects::Executes))));
+    tool_registry.register(Arc::new(GitBranchTool::new().with_root(repo_root.clone())));
+    tool_registry.register(Arc::new(GitCheckoutTool::new().with_root(repo_root.clone())));
+    tool_registry.register(Arc::new(GitStashTool::new().with_root(repo_root.clone())));
+    // LSPツール
+    tool_registry.register(Arc::new(LspDefinitionTool::new(Arc::clone(&lsp))));
+    tool_registry.register(Arc::new(LspReferencesTool::new(Arc::clone(&lsp))));
+    tool_registry.register(Arc::new(LspHoverTool::new(Arc::clone(&lsp))));
+    tool_registry.register(Arc::new(LspSymbolsTool::new(Arc::clone(&lsp)).with_max_results(config.lsp.max_symbols)));
+    tool_registry.register(Arc::new(LspOutlineTool::new(Arc::clone(&lsp))));
+    tool_registry.register(Arc::new(
+        LspRenameTool::new(Arc::clone(&lsp))
+            .with_observer(Arc::clone(&file_observer))
+            .with_path_policy(Arc::clone(&path_policy)),
+    ));
+    tool_registry.register(Arc::new(LspDiagnosticsTool::new(Arc::clone(&lsp))));
+    // ToDo リスト（セッション中だけ保持し、REPL が変わるたびに表示する）
+    let todos = TodoList::default();
+    tool_registry.register(Arc::new(TodoTool::new(Arc::clone(&todos))));
+    // Webツール（ネットワークに出るので設定で有効にしたときだけ）
+    if config.tools.enable_web {
+        tool_registry.register(Arc::new(
+            WebFetchTool::new()
+                .with_timeout(std::time::Duration::from_secs(config.tools.web_timeout))
+                .with_max_download_bytes(config.tools.web_max_bytes),
+        ));
     }
 
-    // Superpowersスキルをロード
-    let superpowers_dir = find_superpowers_dir();
-    if let Some(dir) = &superpowers_dir {
-        skill_registry.add_superpowers_path(dir.join("skills"));
+    // 外部コマンドツール
+    for external in &config.tools.external {
+        match ExternalTool::from_config(external, &project_root).await {
+            Ok(tool) if tool_registry.contains(tool.name()) => {
+                print_startup_warning(
+                    &format!("External tool '{}' conflicts with a built-in tool and was skipped", tool.name()),
+                    print_mode,
+                );
+            }
+            Ok(tool) => {
+                tracing::info!("Registered external tool: {}", tool.name());
+                tool_registry.register(Arc::new(tool));
+            }
+            Err(e) => {
+                tracing::warn!("Failed to load external tool '{}': {:#}", external.command, e);
+                print_startup_warning(
+                    &format!("Failed to load external tool '{}': {:#}", external.command, e),
+                    print_mode,
+                );
+            }
+        }
+    }
+    // モードによる可否と確認の要否は各ツールの宣言した副作用で決まる
+    let mode_manager = mode_manager.with_tool_effects(tool_registry.effects());
+    let prompt_linter = config.ui.prompt_lint.then(|| {
+        PromptLinter::new(project_root.clone(), tool_registry.names().into_iter().map(String::from).collect())
+    });
+    let input_preprocessor = InputPreprocessor::new(project_root.clone());
+    for warning in mode_manager.config_warnings() {
+        tracing::warn!("{}", warning);
+        print_startup_warning(&warning, print_mode);
     }
 
-    skill_registry.load_all().await?;
+    tracing::info!("Registered {} tools", tool_registry.len());
+
+    // スキルレジストリを初期化（Superpowersスキルも）
+    let superpowers_dir = find_superpowers_dir();
+    let skill_registry = load_skill_registry(&config, superpowers_dir.as_deref()).await?;
     tracing::info!("Loaded {} skills", skill_registry.len());
     let skill_registry = Arc::new(skill_registry);
+    // /reload で差し替えるので、ループでは毎回ここから取り出す
+    let shared_skills = SharedSkillRegistry::new(Arc::clone(&skill_registry));
 
-    // モードマネージャーを初期化
     // Superpowersコマンドエイリアスをロード（埋め込み + ファイルシステム）
-    let mut command_aliases: HashMap<String, String> = HashMap::new();
-    let mut superpowers_commands: Vec<String> = Vec::new();
-    let commands_dir = superpowers_dir.as_ref().map(|d| d.join("commands")).unwrap_or_default();
-    match load_superpowers_commands(&commands_dir).await {
-        Ok(commands) => {
-            for command in commands {
-                command_aliases.insert(command.name.clone(), command.skill.clone());
-                superpowers_commands.push(command.name);
-            }
-        }
-        Err(e) => tracing::warn!("Failed to load superpowers commands: {}", e),
-    }
+    let (command_aliases, superpowers_commands) = load_command_aliases(superpowers_dir.as_deref(), &skill_registry).await;
+
+    // 使用ツールのバージョンを遅延取得するプローブ
+    let env_prober = Arc::new(EnvProber::from_config(&config.environment));
+
+    // コマンドの別名と /help の言語（別名がコマンドやスキルと重なれば起動しない）
+    let skill_names: Vec<String> = skill_registry.names().into_iter().chain(command_aliases.keys().cloned()).collect();
+    let locale_dir = dirs::home_dir().map(|home| home.join(".local-code").join("locales"));
+    let command_table = Arc::new(CommandTable::load(Locale::resolve(&config.ui.locale), locale_dir.as_deref(), &skill_names)?);
 
     // コマンドハンドラーを初期化
-    let command_handler = CommandHandler::new(mode_manager.clone())
-        .with_skill_aliases(command_aliases);
+    let mut command_handler = match HistoryManager::new() {
+        Ok(manager) => CommandHandler::with_history_manager(
+            mode_manager.clone(),
+            manager.with_cipher(Arc::clone(&storage_cipher), config.storage.encrypt),
+        ),
+        Err(e) => {
+            tracing::warn!("History manager is not available: {}", e);
+            CommandHandler::new(mode_manager.clone())
+        }
+    }
+        .with_skill_aliases(command_aliases)
+        .with_command_table(Arc::clone(&command_table))
+        .with_env_prober(Arc::clone(&env_prober))
+        .with_project_root(project_root.clone())
+        .with_workspace(Arc::clone(&workspace));
 
     // エージェントを初期化（設定ファイルからタイムアウトを取得）
     let agent_config = AgentConfig {
-        ollama_url: ollama_url.clone(),
+        provider: config.llm.provider,
+        ollama_url: llm_url.clone(),
+        api_key: config.llm.api_key(|var| std::env::var(var).ok()),
         model: model.clone(),
         initial_mode,
         max_messages: config.agent.max_messages,
         connect_timeout: config.ollama.connect_timeout,
         read_timeout: config.ollama.read_timeout,
+        num_ctx: config.ollama.num_ctx,
+        max_num_ctx: config.ollama.max_num_ctx,
         retry_config: config.ollama.retry.clone(),
+        compression: config.agent.compression.clone(),
+        context_window: config.agent.context_window,
+        keep_thoughts: config.agent.keep_thoughts,
+        options: config.ollama.options.clone(),
+        keep_alive: Some(config.ollama.keep_alive.clone()).filter(|k| !k.trim().is_empty()),
+        tool_call_format: config.tools.call_format,
     };
     let mut agent = Agent::new(
         agent_config,
@@ -170,87 +645,122 @@ async fn main() -> Result<()> {
         Arc::clone(&skill_registry),
         mode_manager.clone(),
     );
+    let dry_run = Arc::new(DryRun::new(args.dry_run));
+    agent.set_dry_run(Arc::clone(&dry_run));
+    agent.set_tool_output(tool_output);
+    agent.set_confirmation(Arc::clone(&confirmation));
+    agent.register_session_state(Arc::clone(&lsp) as Arc<dyn SessionResettable>);
+
+    // /status で集約するサブシステム（モードはハンドラーが登録済み）
+    let status_registry = command_handler.status_registry().clone();
+    status_registry.register(agent.llm().status_provider());
+    if let Some(context_status) = agent.llm().context_status_provider() {
+        status_registry.register(context_status);
+    }
+    status_registry.register(Arc::new(shared_skills.clone()) as Arc<dyn StatusProvider>);
+    status_registry.register(Arc::new(DiskStatus::new(&project_root, config.tools.min_free_bytes)));
 
     // Superpowersブートストラップをシステムプロンプトに追加
-    // 優先順位: ファイルシステム > 埋め込み
-    let bootstrap_content = if let Some(dir) = &superpowers_dir {
-        let local_bootstrap = dir.join("superpowers-bootstrap.local.md");
-        let codex_bootstrap = dir.join("superpowers-bootstrap.md");
-        let bootstrap_path = if local_bootstrap.exists() {
-            Some(local_bootstrap)
-        } else if codex_bootstrap.exists() {
-            Some(codex_bootstrap)
-        } else {
-            None
-        };
-        if let Some(path) = bootstrap_path {
-            match fs::read_to_string(&path).await {
-                Ok(content) => Some(content),
-                Err(e) => {
-                    tracing::warn!("Failed to read superpowers bootstrap: {}", e);
-                    EmbeddedSuperpowers::bootstrap()
-                }
-            }
-        } else {
-            EmbeddedSuperpowers::bootstrap()
+    let mut bootstrap_content = load_bootstrap(superpowers_dir.as_deref()).await;
+    if let Some(content) = &bootstrap_content {
+        agent.set_system_extra(Some(content.clone()));
+    }
+
+    // 最初のルートの言語サーバーを起動（設定またはCargoプロジェクトの場合のみ。ほかのルートは使うときに起動）
+    status_registry.register(Arc::new(LspStatus::new(Arc::clone(lsp.primary()))));
+    if let Err(e) = lsp.primary().start().await {
+        tracing::warn!("{:#}", e);
+    }
+
+    agent.set_env_prober(Some(env_prober));
+
+    // プロンプトデバッグ出力（--debug-prompt または環境変数）
+    if args.debug_prompt || PromptDebugger::env_enabled() {
+        let mut debugger = PromptDebugger::for_project(&project_root);
+        if config.storage.encrypt {
+            debugger = debugger.with_cipher(Arc::clone(&storage_cipher));
         }
+        tracing::info!("Prompt debugging enabled: {}", debugger.dir().display());
+        agent.set_prompt_debugger(Some(debugger));
+    }
+
+    // ターンの進行とモデルロード中・混雑中の待機状況をスピナーで表示（-p では待機だけを標準エラー出力へ）
+    let (retry_tx, retry_rx) = tokio::sync::mpsc::unbounded_channel();
+    agent.set_retry_status_sender(Some(retry_tx));
+    let mut verification = VerificationPipeline::from_config(&config.verification);
+    let activity = if print_mode {
+        tokio::spawn(log_retry_status(retry_rx, args.output));
+        None
     } else {
-        // ファイルシステムにsuperpowersがない場合は埋め込み版を使用
-        EmbeddedSuperpowers::bootstrap()
+        let (event_tx, event_rx) = tokio::sync::mpsc::unbounded_channel();
+        verification = verification.with_events(event_tx.clone());
+        agent.set_event_sender(Some(event_tx));
+        Some(Activity::new(event_rx, retry_rx))
     };
-    if let Some(content) = bootstrap_content {
-        agent.set_system_extra(Some(content));
-    }
-
-    // プロジェクトコンテキストを読み込み
-    let project_root = args.project
-        .or_else(|| std::env::current_dir().ok())
-        .unwrap_or_else(|| PathBuf::from("."));
-
-    // LSPクライアントを初期化（設定またはCargoプロジェクトの場合のみ）
-    let lsp_command = config
-        .lsp
-        .command
-        .clone()
-        .or_else(|| {
-            if project_root.join("Cargo.toml").exists() {
-                Some("rust-analyzer".to_string())
-            } else {
-                None
-            }
-        });
-    if let Some(command) = lsp_command {
-        let args = config.lsp.args.clone();
-        let arg_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
-        match LspClient::start(&command, &arg_refs).await {
-            Ok(client) => {
-                match client.initialize(&project_root).await {
-                    Ok(_) => {
-                        *lsp_client.lock().await = Some(client);
-                        tracing::info!("LSP initialized: {}", command);
-                    }
-                    Err(e) => {
-                        tracing::warn!("Failed to initialize LSP: {}", e);
-                    }
-                }
-            }
-            Err(e) => {
-                tracing::warn!("Failed to start LSP server '{}': {}", command, e);
-            }
-        }
-    }
 
+    agent.set_workspace(Some(Arc::clone(&workspace)));
     if let Err(e) = agent.load_context(&project_root).await {
         tracing::warn!("Failed to load project context: {}", e);
     } else {
         tracing::info!("Loaded project context from: {}", project_root.display());
     }
 
+    // 前回のセッションの続きから始める（システムプロンプトは今読み込んだもの）
+    if args.resume {
+        resume_autosave(&mut agent, command_handler.history_manager(), &project_root, print_mode);
+    }
+
+    if print_mode {
+        // 存在しないパスやツール名は、確認する代わりにヒントとして書き添える
+        let annotated = args.prompt.as_deref().map(|prompt| match &prompt_linter {
+            Some(linter) => prompt_lint::annotate(prompt, &linter.lint(prompt)),
+            None => prompt.to_string(),
+        });
+        // @path のファイルを添付（添付できなかったものは標準エラー出力に書く）
+        let annotated = annotated.map(|prompt| {
+            let expanded = input_preprocessor.process(&prompt);
+            for (path, reason) in &expanded.skipped {
+                eprintln!("Note: @{} {}; left as is", path, reason);
+            }
+            expanded.message
+        });
+        let input = print_mode::compose_prompt(annotated.as_deref(), piped_stdin.as_deref()).unwrap_or_default();
+        agent.set_deny_confirmations(!args.yes);
+        let code = print_mode::run(&mut agent, &input, args.output, &todos, &verification).await;
+        if dry_run.is_enabled() {
+            eprintln!("{}", dry_run.overlay().report());
+        }
+        shutdown_background(&job_manager, &lsp).await;
+        std::process::exit(code);
+    }
+
+    // ターンごとにプロジェクトの自動保存を上書きする（-p では書かない）
+    if config.storage.autosave {
+        match HistoryManager::new() {
+            Ok(manager) => agent.set_autosave(Some(manager.with_cipher(Arc::clone(&storage_cipher), config.storage.encrypt))),
+            Err(e) => tracing::warn!("Autosave is not available: {}", e),
+        }
+    }
+
+    // 長い応答とツール出力はページャーで表示する（対話セッションだけ）
+    let paging = PagingMode::parse(&config.ui.paging).unwrap_or_else(|| {
+        tracing::warn!("Unknown ui.paging '{}', using auto", config.ui.paging);
+        PagingMode::Auto
+    });
+    configure_pager(paging, config.ui.pager.clone());
+
+    let mut activity = activity.expect("activity is set up for the interactive session");
+
     let mut repl = Repl::new();
     repl.set_skills(skill_registry.names());
     repl.set_superpowers_commands(superpowers_commands.clone());
+    repl.set_localized_commands(command_table.localized_commands());
     repl.set_working_dir(project_root.clone());
+    if let Some(manager) = command_handler.history_manager() {
+        repl.set_conversation_names(manager.names());
+    }
     repl.set_mode(mode_str.clone());
+    repl.set_mode_manager(mode_manager.clone());
     repl.set_model(model.clone());
 
     // Claude Code風の起動バナーを表示
@@ -263,23 +773,74 @@ async fn main() -> Result<()> {
 
     println!("Type /help for commands, /quit to exit\n");
 
+    // サーバーとモデルを確かめ、リトライで待たせる前に対処方法を示す
+    // 問題がなければ、アイドル後の最初のリクエストで待たされないよう先にモデルを読み込んでおく
+    match agent.llm().health_check().await {
+        Some(HealthStatus::Ok) | None => {
+            warm_up_model(&agent, &mut activity).await;
+        }
+        Some(status) => {
+            if let Some(guidance) = status.guidance() {
+                print_formatted_block("WARNING", &guidance);
+                println!();
+            }
+        }
+    }
+
+    // `local-code watch` 向けに表示内容を書き出す（平文なので、会話を暗号化する設定では書き出さない）
+    if config.ui.broadcast && config.storage.encrypt {
+        tracing::warn!("Not broadcasting this session: the stream is plain text and [storage] encrypt = true");
+    } else if config.ui.broadcast {
+        let path = broadcast::stream_path(&project_root);
+        match broadcast::start(&path) {
+            Ok(()) => print_info(&format!("Broadcasting this session; follow it with `local-code watch` ({})", path.display())),
+            Err(e) => tracing::warn!("Failed to start broadcasting to {}: {}", path.display(), e),
+        }
+    }
+
+    // /use で固定した、次のメッセージに添付するツール結果
+    let pin_stage = Arc::new(PinStage::new());
+    agent.register_session_state(Arc::clone(&pin_stage) as Arc<dyn SessionResettable>);
+    // /retry で送り直す入力
+    let mut retry_input: Option<String> = None;
+
     loop {
+        let skill_registry = shared_skills.current();
         let mode = mode_manager.current().await;
         // モードとモデルを更新してプロンプトを自動生成
         repl.set_mode(mode.to_string());
         repl.set_model(agent.llm().model().to_string());
+        let segments: Vec<String> = dry_run
+            .is_enabled()
+            .then(|| "dry-run".to_string())
+            .into_iter()
+            .chain(context_warning(agent.token_breakdown().total(), agent.context_window()))
+            .collect();
+        repl.set_status_segment((!segments.is_empty()).then(|| segments.join(" · ")));
+        if !pin_stage.is_empty() {
+            print_info(&pin_stage.summary());
+        }
         // モードアイコン付きプロンプトを表示
         repl.print_prompt_with_icon(Some(mode.icon()))?;
 
-        let input = repl.read_line_with_history()?;
+        // /retry は取り除いた入力をそのまま次の入力として扱う（スキルや @ファイルも展開し直す）
+        let input = match retry_input.take() {
+            Some(line) => line,
+            None => match repl.read_line_with_history() {
+                Ok(line) => line,
+                // プロンプトでのCtrl+Cは入力の取り消しのみ（REPLは終了しない）
+                Err(e) if e.to_string().contains("interrupted") => continue,
+                Err(e) => return Err(e),
+            },
+        };
         let input = input.trim();
 
         if input.is_empty() {
             continue;
         }
 
-        let command = Command::parse(input);
-        let result = command_handler.handle(&command, &*skill_registry).await;
+        let command = command_table.parse(input);
+        let result = command_handler.handle(&command, &skill_registry).await;
 
         match result {
             CommandResult::Exit => {
@@ -290,25 +851,63 @@ async fn main() -> Result<()> {
             CommandResult::Clear => {
                 // シンプルモードでは画面クリアは行わない（スクロール式のため）
                 println!("\n--- cleared ---\n");
+                agent.reset_session(SessionTransition::Clear);
             }
             CommandResult::Output(msg) => {
                 print_formatted_block("INFO", &msg);
             }
+            CommandResult::Status { statuses, json } => {
+                print_status(&statuses, json);
+            }
             CommandResult::SendToLLM(msg) => {
+                // 存在しないパスやツール名があれば送信前に確認する
+                if let Some(linter) = &prompt_linter {
+                    let issues = linter.lint(&msg);
+                    if !issues.is_empty() && ask_send_or_edit(&prompt_lint::notice(&issues))? == SendChoice::Edit {
+                        repl.set_pending_input(msg);
+                        continue;
+                    }
+                }
                 print_formatted_block("USER", &msg);
+                agent.pin_for_next_turn(pin_stage.take());
+                let expanded = input_preprocessor.process(&msg);
+                for attachment in &expanded.attachments {
+                    let note = if attachment.truncated { ", truncated" } else { "" };
+                    print_debug(&format!("Attached @{} ({} bytes{})", attachment.path, attachment.size, note));
+                }
+                for (path, reason) in &expanded.skipped {
+                    print_debug(&format!("@{} {}; left as is", path, reason));
+                }
                 let detector = TriggerDetector::new(&skill_registry);
                 let matches = detector.detect(&msg);
 
-                // 自動実行スキルがあれば実行
-                if let Some(skill) = matches.iter().find(|s| s.metadata.auto) {
+                // 最上位のスキルが auto でスコアが閾値を超えていれば実行
+                let auto_skill = matches
+                    .first()
+                    .filter(|m| m.skill.metadata.auto && m.score > config.skills.auto_threshold)
+                    .map(|m| m.skill);
+                if let Some(skill) = auto_skill {
                     print_formatted_block("SKILL", &format!("Auto: {}", skill.metadata.name));
                     let skill_executor = SkillExecutor::new(Arc::clone(&skill_registry));
-                    let context = SkillContext::new(Some(msg.clone()));
+                    let context = SkillContext::new(Some(msg.clone())).with_project_root(project_root.clone());
                     match skill_executor.execute(skill, &context).await {
                         Ok(skill_prompt) => {
-                            print_processing("Processing skill prompt...");
-                            match agent.process(&skill_prompt).await {
-                                Ok(response) => print_formatted_block("ASSISTANT", &response),
+                            activity.with_label("Skill");
+                            // extra_paths はこのターンの間だけ許可する（ガードを捨てると元に戻る）
+                            let grants = grant_extra_paths(skill, &project_root, &path_policy, &confirmation);
+                            print_skill_grants(&grants.summary());
+                            // allowed-tools があればこのターンだけツールを絞り込む
+                            mode_manager.restrict_to_skill(&skill.metadata.name, skill.metadata.allowed_tools.as_deref()).await;
+                            agent.tag_next_turn(MessageOrigin::Skill, Some(input.to_string()));
+                            let result = process_interruptible(&mut agent, &mut activity, &todos, &skill_prompt, config.ui.streaming).await;
+                            mode_manager.clear_skill_restriction().await;
+                            drop(grants);
+                            match result {
+                                Ok(record) => {
+                                    if needs_reprint(config.ui.streaming, &record, &record.response) {
+                                        print_formatted_block("ASSISTANT", &record.response);
+                                    }
+                                }
                                 Err(e) => {
                                     tracing::error!("Agent error while processing skill: {}", e);
                                     print_formatted_block("ERROR", &format!("Failed to process skill: {}", e));
@@ -328,142 +927,68 @@ async fn main() -> Result<()> {
 
                 // 関連スキルがあればモデルに通知（強制適用）
                 let enhanced_msg = if !matches.is_empty() {
-                    let skill_names: Vec<_> = matches.iter().map(|s| s.metadata.name.as_str()).collect();
+                    let skill_names: Vec<_> = matches.iter().map(|m| m.skill.metadata.name.as_str()).collect();
                     print_formatted_block("SKILL", &format!("Related: {}", skill_names.join(", ")));
                     format!(
                         "<skill_hint>\nRelevant skills detected: {}. Consider using `/{}` if applicable.\n</skill_hint>\n\n{}",
                         skill_names.join(", "),
                         skill_names.first().unwrap_or(&""),
-                        msg
+                        expanded.message
                     )
                 } else {
-                    msg.clone()
+                    expanded.message
                 };
 
-                // 「only code」キーワードを検出
-                let code_only = {
-                    let lower = msg.to_lowercase();
-                    lower.contains("only code") || lower.contains("code only") || lower.contains("コードのみ")
-                };
+                let code_only = wants_code_only(&msg);
 
                 // エージェントに処理を委譲
-                print_processing("Processing...");
-                match agent.process(&enhanced_msg).await {
-                    Ok(response) => {
-                        // ポストプロセス（THOUGHT除去、オプションでコードのみ抽出）
-                        let mut processed = OutputPostProcessor::process(&response, code_only);
-
-                        // 自己検証ループ
-                        let verifier = CodeVerifier::new();
-                        let code_blocks = CodeVerifier::extract_code_blocks(&processed);
-
-                        for (lang, code) in &code_blocks {
-                            if lang.is_empty() {
-                                continue;
-                            }
-
-                            match verifier.verify(lang, code) {
-                                Ok(result) => {
-                                    if !result.success {
-                                        print_formatted_block("VERIFY", &format!("❌ {} error detected, attempting fix...", lang));
-
-                                        // 修正ループ
-                                        let mut attempts = 0;
-                                        let mut current_code = code.clone();
-                                        let mut last_error = result.error.clone();
-
-                                        while attempts < verifier.max_attempts() {
-                                            let fix_prompt = verifier.create_fix_prompt(&local_code::VerificationResult {
-                                                success: false,
-                                                output: String::new(),
-                                                error: last_error.clone(),
-                                                language: lang.clone(),
-                                                code: current_code.clone(),
-                                            });
-
-                                            print_processing(&format!("Fix attempt {}/{}...", attempts + 1, verifier.max_attempts()));
-
-                                            match agent.process(&fix_prompt).await {
-                                                Ok(fix_response) => {
-                                                    let fixed = OutputPostProcessor::process(&fix_response, true);
-                                                    let fixed_blocks = CodeVerifier::extract_code_blocks(&fixed);
-
-                                                    // フェンスなし応答対応
-                                                    let fixed_code = if let Some((_, code)) = fixed_blocks.first() {
-                                                        code.clone()
-                                                    } else {
-                                                        // フェンスがない場合は応答全体をコードとして扱う
-                                                        let trimmed = fixed.trim();
-                                                        if !trimmed.is_empty() {
-                                                            trimmed.to_string()
-                                                        } else {
-                                                            continue; // 空の応答はスキップ
-                                                        }
-                                                    };
-
-                                                    if !fixed_code.is_empty() {
-                                                        current_code = fixed_code;
-
-                                                        match verifier.verify(lang, &current_code) {
-                                                            Ok(verify_result) => {
-                                                                if verify_result.success {
-                                                                    print_formatted_block("VERIFY", &format!("✅ {} code fixed successfully!", lang));
-                                                                    processed = replace_code_block(&processed, code, &current_code, lang);
-                                                                    break;
-                                                                } else {
-                                                                    last_error = verify_result.error;
-                                                                }
-                                                            }
-                                                            Err(e) => {
-                                                                tracing::warn!("Verification error: {}", e);
-                                                                break;
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                                Err(e) => {
-                                                    tracing::warn!("Fix attempt failed: {}", e);
-                                                    break;
-                                                }
-                                            }
-                                            attempts += 1;
-                                        }
-
-                                        if attempts >= verifier.max_attempts() {
-                                            print_formatted_block("VERIFY", &format!("⚠️ Could not fix {} code after {} attempts", lang, verifier.max_attempts()));
-                                        }
-                                    } else {
-                                        print_formatted_block("VERIFY", &format!("✅ {} code verified", lang));
-                                    }
-                                }
-                                Err(e) => {
-                                    tracing::debug!("Verification skipped: {}", e);
-                                }
-                            }
-                        }
-
-                        print_formatted_block("ASSISTANT", &processed);
-                    }
-                    Err(e) => {
-                        tracing::error!("Agent error: {}", e);
-                        print_formatted_block("ERROR", &format!("Failed to process request: {}", e));
-                    }
-                }
+                agent.tag_next_turn(MessageOrigin::Typed, Some(input.to_string()));
+                respond(&mut agent, &mut activity, &todos, &verification, &enhanced_msg, config.ui.streaming, code_only).await;
+            }
+            CommandResult::Undo => match agent.undo_last_exchange() {
+                Some(exchange) => print_formatted_block(
+                    "INFO",
+                    &format!("Removed {}:\n{}", exchange.summary(), exchange.user_text()),
+                ),
+                None => print_formatted_block("INFO", "Nothing to undo: the conversation has no messages yet."),
+            },
+            CommandResult::Retry { guidance } => {
+                let Some(exchange) = agent.undo_last_exchange() else {
+                    print_formatted_block("INFO", "Nothing to retry: the conversation has no messages yet.");
+                    continue;
+                };
+                print_formatted_block("INFO", &format!("Removed {}; sending again", exchange.summary()));
+                retry_input = Some(exchange.retry_input(guidance.as_deref()));
             }
             CommandResult::Skill { name, args } => {
                 print_formatted_block("SKILL", &format!("Manual: {}", name));
+                agent.pin_for_next_turn(pin_stage.take());
 
                 // SkillExecutorを使用してスキルを実行
                 let skill_executor = SkillExecutor::new(Arc::clone(&skill_registry));
-                let context = SkillContext::new(args);
+                let context = SkillContext::new(args).with_project_root(project_root.clone());
 
                 match skill_executor.execute_by_name(&name, &context).await {
                     Ok(skill_prompt) => {
-                        // 生成されたプロンプトをLLMに送信
-                        print_processing("Processing skill prompt...");
-                        match agent.process(&skill_prompt).await {
-                            Ok(response) => {
-                                print_formatted_block("ASSISTANT", &response);
+                        // 生成されたプロンプトをLLMに送信（allowed-tools があればこのターンだけ絞り込む）
+                        activity.with_label("Skill");
+                        let skill = skill_registry.get(&name);
+                        // extra_paths はこのターンの間だけ許可する（ガードを捨てると元に戻る）
+                        let grants = skill.map(|skill| grant_extra_paths(skill, &project_root, &path_policy, &confirmation));
+                        if let Some(grants) = &grants {
+                            print_skill_grants(&grants.summary());
+                        }
+                        let allowed_tools = skill.and_then(|s| s.metadata.allowed_tools.clone());
+                        mode_manager.restrict_to_skill(&name, allowed_tools.as_deref()).await;
+                        agent.tag_next_turn(MessageOrigin::Skill, Some(input.to_string()));
+                        let result = process_interruptible(&mut agent, &mut activity, &todos, &skill_prompt, config.ui.streaming).await;
+                        mode_manager.clear_skill_restriction().await;
+                        drop(grants);
+                        match result {
+                            Ok(record) => {
+                                if needs_reprint(config.ui.streaming, &record, &record.response) {
+                                    print_formatted_block("ASSISTANT", &record.response);
+                                }
                             }
                             Err(e) => {
                                 tracing::error!("Agent error while processing skill: {}", e);
@@ -479,43 +1004,663 @@ async fn main() -> Result<()> {
             }
             CommandResult::SaveConversation { name } => {
                 match command_handler.history_manager() {
-                    Some(manager) => match manager.save(&name, agent.conversation()) {
-                        Ok(path) => print_formatted_block("INFO", &format!("Saved conversation: {}", path.display())),
+                    Some(manager) => match manager.save_with_metadata(&name, agent.conversation(), agent.session_metadata()) {
+                        Ok(path) => {
+                            repl.set_conversation_names(manager.names());
+                            print_formatted_block("INFO", &format!("Saved conversation: {}", path.display()));
+                        }
                         Err(e) => print_formatted_block("ERROR", &format!("Failed to save conversation: {}", e)),
                     },
                     None => print_formatted_block("ERROR", "History manager is not available."),
                 }
             }
+            CommandResult::DeleteConversation { name, force } => {
+                let Some(manager) = command_handler.history_manager() else {
+                    print_formatted_block("ERROR", "History manager is not available.");
+                    continue;
+                };
+                if !manager.exists(&name) {
+                    print_formatted_block("ERROR", &format!("No saved conversation named '{}'", name));
+                    continue;
+                }
+                let details = format!("'{}' will be removed from {}", name, manager.history_dir().display());
+                let approved = force || matches!(confirm("Delete conversation", details), Ok(ConfirmResult::Approved));
+                if !approved {
+                    print_formatted_block("INFO", &format!("Kept conversation: {}", name));
+                    continue;
+                }
+                match manager.delete(&name) {
+                    Ok(()) => {
+                        repl.set_conversation_names(manager.names());
+                        print_formatted_block("INFO", &format!("Deleted conversation: {}", name));
+                    }
+                    Err(e) => print_formatted_block("ERROR", &format!("Failed to delete conversation: {:#}", e)),
+                }
+            }
+            CommandResult::RenameConversation { from, to } => match command_handler.history_manager() {
+                Some(manager) => match manager.rename(&from, &to) {
+                    Ok(_) => {
+                        repl.set_conversation_names(manager.names());
+                        print_formatted_block("INFO", &format!("Renamed conversation: {} -> {}", from, to));
+                    }
+                    Err(e) => print_formatted_block("ERROR", &format!("Failed to rename conversation: {:#}", e)),
+                },
+                None => print_formatted_block("ERROR", "History manager is not available."),
+            },
             CommandResult::LoadConversation { name } => {
                 match command_handler.history_manager() {
                     Some(manager) => match manager.load(&name) {
                         Ok(conversation) => {
-                            agent.replace_conversation(conversation);
+                            agent.restore_conversation(conversation);
+                            agent.reset_session(SessionTransition::Load);
                             print_formatted_block("INFO", &format!("Loaded conversation: {}", name));
                         }
-                        Err(e) => print_formatted_block("ERROR", &format!("Failed to load conversation: {}", e)),
+                        Err(e) => print_formatted_block("ERROR", &format!("Failed to load conversation: {:#}", e)),
                     },
                     None => print_formatted_block("ERROR", "History manager is not available."),
                 }
             }
+            CommandResult::PinToolResult { id, instruction } => {
+                match agent.resolve_tool_result(&id).await {
+                    Ok(mut pin) => {
+                        pin.instruction = instruction;
+                        pin_stage.add(pin);
+                        print_formatted_block("INFO", &pin_stage.summary());
+                    }
+                    Err(e) => print_formatted_block("ERROR", &format!("{:#}", e)),
+                }
+            }
+            CommandResult::ShowContext => {
+                print_formatted_block("CONTEXT", &agent.context_summary());
+            }
+            CommandResult::Init { force } => {
+                if let Err(e) = agent_md_target(&project_root, force) {
+                    print_formatted_block("ERROR", &format!("{:#}", e));
+                    continue;
+                }
+                let facts = ProjectFacts::gather(&project_root).await;
+                let cancel = CancellationToken::new();
+                let watcher = spawn_ctrl_c_watcher(&cancel);
+                activity.set_streaming(false);
+                activity.with_label("Writing agent.md");
+                let draft = activity.track(draft_agent_md(agent.llm(), &facts), &cancel).await;
+                watcher.abort();
+                let draft = match draft {
+                    Ok(draft) => draft,
+                    Err(e) => {
+                        print_formatted_block("ERROR", &format!("Failed to draft agent.md: {:#}", e));
+                        continue;
+                    }
+                };
+                print_formatted_block("AGENT.MD", &draft);
+                let details = format!("{} ({} lines)", project_root.join(AGENT_MD).display(), draft.lines().count());
+                if !confirm("Write agent.md", details).is_ok_and(|result| result.is_approved()) {
+                    print_formatted_block("INFO", "agent.md was not written.");
+                    continue;
+                }
+                match write_agent_md(&project_root, &draft, force) {
+                    Ok(path) => match agent.load_context(&project_root).await {
+                        Ok(()) => print_formatted_block("INFO", &format!("Wrote {} and loaded it into the context.", path.display())),
+                        Err(e) => print_formatted_block("ERROR", &format!("Wrote {} but could not reload the context: {:#}", path.display(), e)),
+                    },
+                    Err(e) => print_formatted_block("ERROR", &format!("{:#}", e)),
+                }
+            }
+            CommandResult::Export { format, path, include_system } => {
+                let target = export_target(&project_root, &path);
+                if target.is_dir() {
+                    print_formatted_block("ERROR", &format!("{} is a directory; give a file path", target.display()));
+                    continue;
+                }
+                if target.exists() && !confirm("Overwrite file", target.display().to_string()).is_ok_and(|result| result.is_approved()) {
+                    print_formatted_block("INFO", "Nothing was exported.");
+                    continue;
+                }
+                let exporter =
+                    ConversationExporter::new().with_system(include_system).with_metadata(agent.session_metadata());
+                let count = exporter.exported_messages(agent.conversation()).len();
+                match write_export(&target, &exporter.render(agent.conversation(), format)) {
+                    Ok(()) => print_formatted_block(
+                        "INFO",
+                        &format!("Exported {} messages as {} to {}", count, format.label(), target.display()),
+                    ),
+                    Err(e) => print_formatted_block("ERROR", &format!("{:#}", e)),
+                }
+            }
+            CommandResult::Remember { text, scope } => match agent.remember(&text, scope) {
+                Ok(true) => print_formatted_block("INFO", &format!("Remembered ({}): {}", scope.label(), text)),
+                Ok(false) => print_formatted_block("INFO", "Already remembered; nothing added."),
+                Err(e) => print_formatted_block("ERROR", &format!("Failed to remember: {:#}", e)),
+            },
+            CommandResult::ShowMemory => {
+                agent.refresh_memory();
+                print_formatted_block("MEMORY", &agent.memory().list());
+            }
+            CommandResult::Forget { index } => match agent.forget_memory(index) {
+                Ok(entry) => print_formatted_block("INFO", &format!("Forgot ({}): {}", entry.scope.label(), entry.text)),
+                Err(e) => print_formatted_block("ERROR", &format!("{:#}", e)),
+            },
+            CommandResult::Config(ConfigAction::Show) => {
+                print_formatted_block("CONFIG", &config.source_report());
+            }
+            CommandResult::Config(ConfigAction::Get { key }) => match config.get_value(&key) {
+                Ok(value) => print_formatted_block("CONFIG", &format!("{} = {}  ({})", key, value, config.source(&key))),
+                Err(e) => print_formatted_block("ERROR", &e.to_string()),
+            },
+            CommandResult::Config(ConfigAction::Set { key, value }) => {
+                // 生成オプションは /set と同じ範囲で確かめてから変える
+                if let Some(option) = key.strip_prefix("ollama.options.") {
+                    if let Err(e) = agent.llm().options().clone().set(option, &value) {
+                        print_formatted_block("ERROR", &e);
+                        continue;
+                    }
+                }
+                if let Err(e) = config.set_value(&key, &value) {
+                    print_formatted_block("ERROR", &e.to_string());
+                    continue;
+                }
+                // 動いているエージェントに反映できる項目（ほかは次の起動から）
+                let live = match key.as_str() {
+                    "ollama.model" => {
+                        agent.set_model(config.ollama.model.clone());
+                        true
+                    }
+                    "agent.max_messages" => {
+                        agent.set_max_messages(config.agent.max_messages);
+                        true
+                    }
+                    "verification.enabled" => {
+                        verification.set_enabled(config.verification.enabled);
+                        true
+                    }
+                    _ => key
+                        .strip_prefix("ollama.options.")
+                        .is_some_and(|option| agent.set_generation_option(option, &value).is_ok()),
+                };
+                let shown = config.get_value(&key).unwrap_or(value);
+                let note = if live { "" } else { " (takes effect after restart; /config save keeps it)" };
+                print_formatted_block("INFO", &format!("{} = {}{}", key, shown, note));
+            }
+            CommandResult::Config(ConfigAction::Save) => {
+                let details = format!(
+                    "Rewrite {} with its own settings and this session's changes (comments are not kept)",
+                    user_config_path.display()
+                );
+                match confirm("Save config", details) {
+                    Ok(result) if result.is_approved() => match config.save(&user_config_path) {
+                        Ok(()) => print_formatted_block("INFO", &format!("Saved config to {}", user_config_path.display())),
+                        Err(e) => print_formatted_block("ERROR", &format!("{:#}", e)),
+                    },
+                    _ => print_formatted_block("INFO", "Config not saved."),
+                }
+            }
+            CommandResult::ShowInputs { query, page } => {
+                let text = inputs::render(repl.input_history().entries(), query.as_deref(), page);
+                print_formatted_block("INPUTS", &text);
+            }
+            CommandResult::ClearInputs => {
+                let history = repl.input_history();
+                let details = format!("{} inputs in {}", history.entries().len(), history.path().display());
+                match confirm("Clear input history", details) {
+                    Ok(ConfirmResult::Approved) => match repl.clear_input_history() {
+                        Ok(()) => print_formatted_block("INFO", "Input history cleared."),
+                        Err(e) => print_formatted_block("ERROR", &format!("Failed to clear input history: {}", e)),
+                    },
+                    _ => print_formatted_block("INFO", "Input history kept."),
+                }
+            }
+            CommandResult::RootChanged(msg) => {
+                agent.reset_session(SessionTransition::ChangeDirectory);
+                agent.refresh_system_prompt();
+                print_formatted_block("INFO", &msg);
+            }
+            CommandResult::DryRun(action) => {
+                let text = match action {
+                    DryRunAction::On => {
+                        dry_run.set_enabled(true);
+                        "Dry run on: changes are simulated and not written.".to_string()
+                    }
+                    DryRunAction::Off => {
+                        dry_run.set_enabled(false);
+                        "Dry run off: tools run for real. Simulated changes were not applied.".to_string()
+                    }
+                    DryRunAction::Report => dry_run.overlay().report(),
+                    DryRunAction::Status => format!(
+                        "Dry run is {}.",
+                        if dry_run.is_enabled() { "on" } else { "off" }
+                    ),
+                };
+                print_formatted_block("INFO", &text);
+            }
+            CommandResult::Verify { enabled } => {
+                if let Some(enabled) = enabled {
+                    verification.set_enabled(enabled);
+                }
+                print_formatted_block("INFO", &verification.describe());
+            }
+            CommandResult::ShowTokens => {
+                print_formatted_block("TOKENS", &agent.token_breakdown().report(agent.context_window()));
+            }
+            CommandResult::ShowTodos => {
+                print_formatted_block("TODOS", &render_todos(&todos.lock().unwrap()));
+            }
+            CommandResult::ShowStats => {
+                print_formatted_block("STATS", &agent.session_stats().report(agent.context_window()));
+            }
+            CommandResult::ResetStats => {
+                agent.reset_stats();
+                print_formatted_block("INFO", "Tool statistics reset.");
+            }
+            CommandResult::ClearPinnedResults => {
+                pin_stage.clear();
+                print_formatted_block("INFO", "Cleared staged tool results.");
+            }
             CommandResult::ChangeModel { name } => {
                 agent.set_model(name.clone());
+                if let Err(e) = config.set_value("ollama.model", &name) {
+                    tracing::warn!("Failed to record the model in the config: {}", e);
+                }
                 print_formatted_block("INFO", &format!("Model changed to: {}", name));
             }
+            CommandResult::SetOption { key, value } => match agent.set_generation_option(&key, &value) {
+                Ok(()) => {
+                    let current = agent.llm().options().describe().into_iter().find(|(k, _)| *k == key).map(|(_, v)| v);
+                    print_formatted_block("INFO", &format!("{} = {}", key, current.unwrap_or(value)));
+                }
+                Err(e) => print_formatted_block("ERROR", &e),
+            },
+            result @ (CommandResult::Reload | CommandResult::SkillNew { .. }) => {
+                // 雛形を作ったらそのまま読み込み直して /<name> を使えるようにする
+                if let CommandResult::SkillNew { name } = &result {
+                    let Some(dir) = scaffold::skills_dir(config.skills.custom_path.as_deref()) else {
+                        print_formatted_block("ERROR", "Could not determine the skills directory; set [skills] custom_path");
+                        continue;
+                    };
+                    match scaffold::create_skill(&dir, name) {
+                        Ok(path) => print_info(&format!("Created {}\nEdit it, then run /reload to pick up your changes.", path.display())),
+                        Err(e) => {
+                            print_formatted_block("ERROR", &e.to_string());
+                            continue;
+                        }
+                    }
+                }
+                let reloaded = match skill_registry.reload().await {
+                    Ok(registry) => Arc::new(registry),
+                    Err(e) => {
+                        print_formatted_block("ERROR", &format!("Failed to reload skills: {}", e));
+                        continue;
+                    }
+                };
+                let changes = reloaded.changes_from(&skill_registry);
+                shared_skills.replace(Arc::clone(&reloaded));
+                agent.set_skills(Arc::clone(&reloaded));
+
+                let (command_aliases, superpowers_commands) = load_command_aliases(superpowers_dir.as_deref(), &reloaded).await;
+                command_handler.set_skill_aliases(command_aliases);
+                repl.set_skills(reloaded.names());
+                repl.set_superpowers_commands(superpowers_commands);
+
+                let bootstrap = load_bootstrap(superpowers_dir.as_deref()).await;
+                let bootstrap_changed = bootstrap != bootstrap_content;
+                if bootstrap_changed {
+                    agent.set_system_extra(bootstrap.clone());
+                    agent.refresh_system_prompt();
+                    bootstrap_content = bootstrap;
+                }
+
+                let mut text = format!("Reloaded {} skills: {}", reloaded.len(), changes);
+                if bootstrap_changed {
+                    text.push_str("\nSuperpowers bootstrap changed; the system prompt was updated.");
+                }
+                for error in reloaded.load_errors() {
+                    text.push_str(&format!("\nFailed to load {}", error));
+                }
+                print_info(&text);
+            }
+            CommandResult::SetSkillEnabled { name, enabled } => {
+                let mut updated = (*skill_registry).clone();
+                if enabled {
+                    updated.enable(&name);
+                } else {
+                    updated.disable(&name);
+                }
+                let updated = Arc::new(updated);
+                shared_skills.replace(Arc::clone(&updated));
+                agent.set_skills(Arc::clone(&updated));
+                let (_, superpowers_commands) = load_command_aliases(superpowers_dir.as_deref(), &updated).await;
+                repl.set_skills(updated.names());
+                repl.set_superpowers_commands(superpowers_commands);
+                print_info(&format!(
+                    "Skill '{}' {} for this session (use [skills] disabled in the config to make it permanent)",
+                    name,
+                    if enabled { "enabled" } else { "disabled" }
+                ));
+            }
+            CommandResult::WarmUp => {
+                if agent.llm().provider() != "ollama" {
+                    print_formatted_block("INFO", &format!("The {} backend loads models on its own; nothing to warm up.", agent.llm().provider()));
+                } else {
+                    warm_up_model(&agent, &mut activity).await;
+                }
+            }
+            CommandResult::RestartLsp => match lsp.primary().command() {
+                None => print_formatted_block("INFO", "No language server is configured (set [lsp] command)."),
+                Some(command) => match lsp.restart().await {
+                    Ok(reopened) => print_info(&format!(
+                        "Restarted {} ({} file{} reopened)",
+                        command,
+                        reopened,
+                        if reopened == 1 { "" } else { "s" }
+                    )),
+                    Err(e) => print_formatted_block("ERROR", &format!("{:#}", e)),
+                },
+            },
+            CommandResult::ShowOptions => {
+                let lines: Vec<String> = agent
+                    .llm()
+                    .options()
+                    .describe()
+                    .into_iter()
+                    .map(|(key, value)| format!("{:<12} {}", key, value))
+                    .collect();
+                print_formatted_block("OPTIONS", &format!("{}\n\nChange with /set <key> <value> ('default' resets a key).", lines.join("\n")));
+            }
         }
         println!(); // 出力後に空行を追加
     }
 
-    // LSPサーバーをシャットダウン
-    if let Some(client) = lsp_client.lock().await.take() {
-        if let Err(e) = client.shutdown().await {
-            tracing::warn!("Failed to shutdown LSP server: {}", e);
-        }
+    if !dry_run.overlay().is_empty() {
+        print_formatted_block("DRY RUN", &dry_run.overlay().report());
     }
+    broadcast::finish();
+    shutdown_background(&job_manager, &lsp).await;
 
     Ok(())
 }
 
+/// バックグラウンドジョブと LSP サーバーを終了
+async fn shutdown_background(job_manager: &JobManager, lsp: &LspRouter) {
+    job_manager.shutdown().await;
+    lsp.shutdown().await;
+}
+
+/// Superpowers コマンドの別名（コマンド名 → スキル名）とコマンド名の一覧（埋め込み + ファイルシステム）
+///
+/// 無効にしたスキルを呼ぶコマンドは補完用の一覧から外す
+async fn load_command_aliases(
+    superpowers_dir: Option<&std::path::Path>,
+    skill_registry: &SkillRegistry,
+) -> (HashMap<String, String>, Vec<String>) {
+    let mut command_aliases = HashMap::new();
+    let mut superpowers_commands = Vec::new();
+    let commands_dir = superpowers_dir.map(|d| d.join("commands")).unwrap_or_default();
+    match load_superpowers_commands(&commands_dir).await {
+        Ok(commands) => {
+            for command in commands {
+                command_aliases.insert(command.name.clone(), command.skill.clone());
+                if skill_registry.get(&command.skill).is_some() {
+                    superpowers_commands.push(command.name);
+                }
+            }
+        }
+        Err(e) => tracing::warn!("Failed to load superpowers commands: {}", e),
+    }
+    (command_aliases, superpowers_commands)
+}
+
+/// Superpowers ブートストラップの内容
+///
+/// 優先順位: ファイルシステム（.local.md > .md） > 埋め込み
+async fn load_bootstrap(superpowers_dir: Option<&std::path::Path>) -> Option<String> {
+    let Some(dir) = superpowers_dir else {
+        // ファイルシステムにsuperpowersがない場合は埋め込み版を使用
+        return EmbeddedSuperpowers::bootstrap();
+    };
+    let path = [dir.join("superpowers-bootstrap.local.md"), dir.join("superpowers-bootstrap.md")]
+        .into_iter()
+        .find(|path| path.exists());
+    let Some(path) = path else {
+        return EmbeddedSuperpowers::bootstrap();
+    };
+    match fs::read_to_string(&path).await {
+        Ok(content) => Some(content),
+        Err(e) => {
+            tracing::warn!("Failed to read superpowers bootstrap: {}", e);
+            EmbeddedSuperpowers::bootstrap()
+        }
+    }
+}
+
+/// モデルを読み込んでおく（起動時と /warm。失敗しても警告だけで続ける）
+async fn warm_up_model(agent: &Agent, activity: &mut Activity) -> bool {
+    // OpenAI 互換サーバーはモデルの読み込みを自分で行う
+    if agent.llm().provider() != "ollama" {
+        return false;
+    }
+    let model = agent.llm().model().to_string();
+    match activity.loading(&model, agent.warm_up()).await {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            print_formatted_block(
+                "WARNING",
+                &format!("Could not load {} in advance: {:#}\nThe first request may be slow.", model, e),
+            );
+            false
+        }
+    }
+}
+
+/// 起動時の警告（非対話モードでは標準出力に混ぜず標準エラー出力へ）
+fn print_startup_warning(message: &str, print_mode: bool) {
+    if print_mode {
+        eprintln!("Warning: {}", message);
+    } else {
+        print_formatted_block("WARNING", message);
+    }
+}
+
+/// このプロジェクトの自動保存を読み込む（なければその旨を表示して新しい会話で始める）
+fn resume_autosave(agent: &mut Agent, manager: Option<&HistoryManager>, project_root: &std::path::Path, print_mode: bool) {
+    let notify = |message: &str| {
+        if print_mode {
+            eprintln!("{}", message);
+        } else {
+            print_info(message);
+        }
+    };
+    let Some(manager) = manager else {
+        notify("History manager is not available; starting a new session.");
+        return;
+    };
+    let name = autosave_name(project_root);
+    if !manager.exists(&name) {
+        notify("No autosaved session for this project; starting a new session.");
+        return;
+    }
+    match manager.load(&name) {
+        Ok(conversation) => {
+            let count = conversation.len();
+            agent.restore_conversation(conversation);
+            agent.reset_session(SessionTransition::Load);
+            notify(&format!("Resumed the last session for this project ({} messages).", count));
+        }
+        Err(e) => notify(&format!("Failed to resume the last session: {:#}", e)),
+    }
+}
+
+/// 自己検証の修正依頼を REPL から送る（やり取りは流さない）
+struct ReplFixer<'a> {
+    agent: &'a mut Agent,
+    cancel: CancellationToken,
+}
+
+#[async_trait::async_trait]
+impl CodeFixer for ReplFixer<'_> {
+    async fn fix(&mut self, prompt: &str, _attempt: usize, _max_attempts: usize) -> Result<String> {
+        // /undo・/retry では元のやり取りの一部として扱う
+        self.agent.tag_next_turn(MessageOrigin::Verification, None);
+        Ok(self.agent.process_detailed_with_cancel(prompt, &self.cancel).await?.response)
+    }
+
+    fn project_root(&self) -> Option<PathBuf> {
+        self.agent.project_root().map(|root| root.to_path_buf())
+    }
+}
+
+/// Ctrl+C でトークンを取り消すタスク（終わったら abort する）
+fn spawn_ctrl_c_watcher(cancel: &CancellationToken) -> tokio::task::JoinHandle<()> {
+    let cancel = cancel.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            cancel.cancel();
+        }
+    })
+}
+
+/// ターンの後の通知（履歴の圧縮、ToDo リストの変化、中断）
+fn print_turn_notices(agent: &mut Agent, todos: &TodoList, todos_before: &[TodoItem], cancel: &CancellationToken) {
+    if let Some(notice) = agent.take_compression_notice() {
+        print_formatted_block("INFO", &notice.to_string());
+    }
+    let todos_after = todos.lock().unwrap().clone();
+    if todos_after != todos_before {
+        print_dim_block("TODOS", &render_todos(&todos_after));
+    }
+
+    if cancel.is_cancelled() {
+        print_processing("(cancelled)");
+    }
+}
+
+/// Ctrl+Cで中断可能な状態でエージェントに処理させる
+///
+/// 処理中はスピナーで進行（生成中・ツール実行中）を表示し、戻る前に止める。
+/// 生成中にCtrl+Cが押されるとキャンセルトークンを発火し、"(cancelled)" を表示する。
+/// 部分応答は中断済みメッセージとして会話に残る。
+/// streaming なら "ASSISTANT" の見出しの下に応答をトークンごとに書き出す。
+/// ターン中に ToDo リストが変わったら暗い色で表示する
+async fn process_interruptible(
+    agent: &mut Agent,
+    activity: &mut Activity,
+    todos: &TodoList,
+    input: &str,
+    streaming: bool,
+) -> Result<TurnRecord> {
+    let cancel = CancellationToken::new();
+    let watcher = spawn_ctrl_c_watcher(&cancel);
+
+    let todos_before = todos.lock().unwrap().clone();
+    activity.set_streaming(streaming);
+    let result = if streaming {
+        print_formatted_block("ASSISTANT", "");
+        activity.track(agent.process_streaming(input, &cancel), &cancel).await
+    } else {
+        activity.track(agent.process_detailed_with_cancel(input, &cancel), &cancel).await
+    };
+    watcher.abort();
+
+    print_turn_notices(agent, todos, &todos_before, &cancel);
+    result
+}
+
+/// スキルの extra_paths の許可・拒否を表示する
+fn print_skill_grants(lines: &[String]) {
+    if !lines.is_empty() {
+        print_formatted_block("SKILL", &lines.join("\n"));
+    }
+}
+
+/// 「only code」キーワードを検出
+fn wants_code_only(msg: &str) -> bool {
+    let lower = msg.to_lowercase();
+    lower.contains("only code") || lower.contains("code only") || lower.contains("コードのみ")
+}
+
+/// メッセージをエージェントに渡し、応答を整形・検証して表示する
+async fn respond(
+    agent: &mut Agent,
+    activity: &mut Activity,
+    todos: &TodoList,
+    verification: &VerificationPipeline,
+    input: &str,
+    streaming: bool,
+    code_only: bool,
+) {
+    match process_interruptible(agent, activity, todos, input, streaming).await {
+        Ok(record) => {
+            // ポストプロセス（THOUGHT除去、オプションでコードのみ抽出）
+            let mut processed = OutputPostProcessor::process(&record.response, code_only);
+
+            // 自己検証ループ（[verification]、/verify off で止める）
+            if verification.is_enabled() {
+                let report = verify_interruptible(verification, &processed, agent, activity, todos).await;
+                for block in &report.blocks {
+                    print_formatted_block("VERIFY", &block.summary());
+                }
+                processed = report.text;
+            }
+
+            if needs_reprint(streaming, &record, &processed) {
+                print_formatted_block("ASSISTANT", &processed);
+            }
+        }
+        Err(e) => {
+            tracing::error!("Agent error: {}", e);
+            print_formatted_block("ERROR", &format!("Failed to process request: {}", e));
+        }
+    }
+}
+
+/// 応答内のコードを検証し、失敗したブロックの修正を頼む
+///
+/// ブロックごとの検証と修正の進行をスピナーに出す。Ctrl+C で修正の依頼を中断できる
+async fn verify_interruptible(
+    pipeline: &VerificationPipeline,
+    response: &str,
+    agent: &mut Agent,
+    activity: &mut Activity,
+    todos: &TodoList,
+) -> VerificationReport {
+    let cancel = CancellationToken::new();
+    let watcher = spawn_ctrl_c_watcher(&cancel);
+
+    let todos_before = todos.lock().unwrap().clone();
+    activity.set_streaming(false);
+    activity.with_label("Verification");
+    let mut fixer = ReplFixer { agent: &mut *agent, cancel: cancel.clone() };
+    let report = activity
+        .track(async { Ok(pipeline.run(response, &mut fixer).await) }, &cancel)
+        .await
+        .unwrap_or_default();
+    watcher.abort();
+
+    print_turn_notices(agent, todos, &todos_before, &cancel);
+    report
+}
+
+/// 整形後の応答をあらためて表示する必要があるか
+///
+/// ストリーミングで書き出したのは思考を除いた生成テキストだけなので、ツールを実行したターンや
+/// コードのみの抽出・自己検証で中身が変わった場合は表示し直す
+fn needs_reprint(streamed: bool, record: &TurnRecord, processed: &str) -> bool {
+    let shown = OutputPostProcessor::remove_thought_blocks(&record.response);
+    !streamed || !record.tool_calls.is_empty() || OutputPostProcessor::differs_materially(&shown, processed)
+}
+
+/// 非対話モードではリトライ待機の開始を標準エラー出力に1行ずつ書く
+async fn log_retry_status(
+    mut rx: tokio::sync::mpsc::UnboundedReceiver<RetryEvent>,
+    format: print_mode::OutputFormat,
+) {
+    while let Some(event) = rx.recv().await {
+        if let RetryEvent::Waiting(status) = event {
+            if status.is_first_tick() {
+                print_mode::log_retry(&status, format);
+            }
+        }
+    }
+}
+
 fn find_superpowers_dir() -> Option<PathBuf> {
     if let Ok(path) = std::env::var("LOCAL_CODE_SUPERPOWERS") {
         let dir = PathBuf::from(path);
@@ -547,18 +1692,43 @@ fn find_superpowers_dir() -> Option<PathBuf> {
     candidates.into_iter().find(|dir| dir.join("skills").exists())
 }
 
-/// 特定のコードブロックを置換
-fn replace_code_block(content: &str, old_code: &str, new_code: &str, lang: &str) -> String {
-    // 元のブロック（言語タグあり/なし両方をカバー）
-    let old_block_with_lang = format!("```{}\n{}\n```", lang, old_code);
-    let old_block_no_lang = format!("```\n{}\n```", old_code);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if content.contains(&old_block_with_lang) {
-        content.replace(&old_block_with_lang, &format!("```{}\n{}\n```", lang, new_code))
-    } else if content.contains(&old_block_no_lang) {
-        content.replace(&old_block_no_lang, &format!("```{}\n{}\n```", lang, new_code))
-    } else {
-        // マッチしない場合は変更なし
-        content.to_string()
+    #[test]
+    fn test_parse_subcommands() {
+        Args::command().debug_assert();
+
+        let args = Args::try_parse_from(["local-code", "completions", "zsh"]).unwrap();
+        assert!(matches!(args.command, Some(CliCommand::Completions { shell: clap_complete::Shell::Zsh })));
+        assert!(Args::try_parse_from(["local-code", "completions", "tcsh"]).is_err());
+
+        let args = Args::try_parse_from(["local-code", "--config", "my.toml", "config", "path"]).unwrap();
+        assert!(matches!(args.command, Some(CliCommand::Config { action: ConfigFileAction::Path })));
+        assert_eq!(args.config, PathBuf::from("my.toml"));
+
+        let args = Args::try_parse_from(["local-code", "skills", "list"]).unwrap();
+        assert!(matches!(args.command, Some(CliCommand::Skills { action: SkillsAction::List })));
+        assert!(Args::try_parse_from(["local-code", "skills"]).is_err());
+    }
+
+    #[test]
+    fn test_flags_without_subcommand() {
+        let args = Args::try_parse_from(["local-code", "-C", "app", "-m", "qwen2.5-coder", "-p", "explain main.rs"]).unwrap();
+        assert!(args.command.is_none());
+        assert_eq!(args.project, [PathBuf::from("app")]);
+        assert_eq!(args.model.as_deref(), Some("qwen2.5-coder"));
+        assert_eq!(args.prompt.as_deref(), Some("explain main.rs"));
+        assert_eq!(args.config, PathBuf::from("config/default.toml"));
+    }
+
+    #[test]
+    fn test_completion_script_names_subcommands() {
+        let mut script = Vec::new();
+        clap_complete::generate(clap_complete::Shell::Bash, &mut Args::command(), "local-code", &mut script);
+        let script = String::from_utf8(script).unwrap();
+        assert!(script.contains("completions"), "{}", script);
+        assert!(script.contains("--model"), "{}", script);
     }
 }
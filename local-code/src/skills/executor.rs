@@ -1,11 +1,12 @@
-use anyhow::Result;
-use std::path::Path;
+use anyhow::{bail, Result};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::fs;
 
 use super::loader::Skill;
 use super::registry::SkillRegistry;
 use super::embedded::EmbeddedSuperpowers;
+use super::template;
 
 /// スキル実行コンテキスト
 pub struct SkillContext {
@@ -13,6 +14,8 @@ pub struct SkillContext {
     pub args: Option<String>,
     /// 現在の作業ディレクトリ
     pub working_dir: std::path::PathBuf,
+    /// プロジェクトルート（`{{project}}`、None なら作業ディレクトリ）
+    pub project_root: Option<PathBuf>,
 }
 
 impl SkillContext {
@@ -21,6 +24,46 @@ impl SkillContext {
         Self {
             args,
             working_dir: std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from(".")),
+            project_root: None,
+        }
+    }
+
+    pub fn with_project_root(mut self, root: PathBuf) -> Self {
+        self.project_root = Some(root);
+        self
+    }
+
+    /// 空白を除いた引数（空なら None）
+    fn args(&self) -> Option<&str> {
+        self.args.as_deref().map(str::trim).filter(|a| !a.is_empty())
+    }
+
+    /// プレースホルダーの値
+    ///
+    /// `{{args}}`・`{{argN}}`（空白区切り、足りなければ空）・`{{file}}`（存在するファイルを指す最初の引数）・
+    /// `{{cwd}}`・`{{project}}`（プロジェクトのディレクトリ名）・`{{env.NAME}}`
+    fn placeholder(&self, name: &str) -> Option<String> {
+        let args = self.args().unwrap_or("");
+        match name {
+            "args" => Some(args.to_string()),
+            "cwd" => Some(self.working_dir.display().to_string()),
+            "project" => {
+                let root = self.project_root.as_ref().unwrap_or(&self.working_dir);
+                let name = root.file_name().map(|n| n.to_string_lossy().into_owned());
+                Some(name.unwrap_or_else(|| root.display().to_string()))
+            }
+            "file" => args
+                .split_whitespace()
+                .next()
+                .filter(|arg| self.working_dir.join(arg).is_file())
+                .map(str::to_string),
+            _ => {
+                if let Some(var) = name.strip_prefix("env.") {
+                    return std::env::var(var).ok();
+                }
+                let index: usize = name.strip_prefix("arg")?.parse().ok().filter(|&n| n > 0)?;
+                Some(args.split_whitespace().nth(index - 1).unwrap_or("").to_string())
+            }
         }
     }
 }
@@ -45,7 +88,14 @@ impl SkillExecutor {
     }
 
     /// スキルを実行し、プロンプトを生成
+    ///
+    /// 本文のプレースホルダーを引数などで置き換える。`{{args}}` などで引数を使わないスキルには
+    /// 末尾に "User input:" として引数を付ける
     pub async fn execute(&self, skill: &Skill, context: &SkillContext) -> Result<String> {
+        if skill.metadata.args_required && context.args().is_none() {
+            bail!("{}", usage_hint(skill));
+        }
+
         let mut prompt = String::new();
 
         // 親スキルがあれば先に読み込み
@@ -66,8 +116,15 @@ impl SkillExecutor {
             prompt.push_str(&doc);
         }
 
-        // 引数があれば追加
-        if let Some(args) = &context.args {
+        let args_placed = template::uses_args(&prompt);
+        let rendered = template::render(&prompt, |name| context.placeholder(name));
+        for name in &rendered.unknown {
+            tracing::warn!("Unknown placeholder {{{{{}}}}} in skill {}", name, skill.metadata.name);
+        }
+        let mut prompt = rendered.text;
+
+        // 本文で使われていなければ引数を末尾に追加
+        if let Some(args) = context.args.as_ref().filter(|_| !args_placed) {
             prompt.push_str("\n\n---\n\n");
             prompt.push_str(&format!("User input: {}", args));
         }
@@ -147,6 +204,15 @@ impl SkillExecutor {
     }
 }
 
+/// 引数が必要なスキルを引数なしで呼んだときの案内
+fn usage_hint(skill: &Skill) -> String {
+    let mut hint = format!("/{} needs arguments. Usage: /{} <args>", skill.metadata.name, skill.metadata.name);
+    if !skill.metadata.description.is_empty() {
+        hint.push_str(&format!("\n{}", skill.metadata.description));
+    }
+    hint
+}
+
 /// スキル実行結果
 pub struct SkillResult {
     /// 生成されたプロンプト
@@ -166,7 +232,65 @@ mod tests {
         let ctx = SkillContext {
             args: Some("test args".to_string()),
             working_dir: std::path::PathBuf::from("/test"),
+            project_root: None,
         };
         assert_eq!(ctx.args.as_deref(), Some("test args"));
     }
+
+    fn skill(frontmatter: &str, body: &str) -> Skill {
+        let content = format!("---\nname: review\ndescription: Review a file for bugs\n{}---\n{}", frontmatter, body);
+        Skill::load_from_string(&content, "/nonexistent/review/SKILL.md").unwrap()
+    }
+
+    fn context(args: Option<&str>, dir: &Path) -> SkillContext {
+        SkillContext {
+            args: args.map(str::to_string),
+            working_dir: dir.to_path_buf(),
+            project_root: Some(dir.join("my-project")),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_substitutes_placeholders() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "").unwrap();
+        let executor = SkillExecutor::new(Arc::new(SkillRegistry::new()));
+        let skill = skill(
+            "",
+            "Review the following file: {{args}}\nFirst: {{arg1}}, third: [{{arg3}}], file: {{file}}\nIn {{project}} at {{cwd}}, keep {{unknown}}",
+        );
+
+        let prompt = executor.execute(&skill, &context(Some(" lib.rs strict "), dir.path())).await.unwrap();
+        assert!(prompt.starts_with("Review the following file: lib.rs strict\nFirst: lib.rs, third: [], file: lib.rs\n"));
+        assert!(prompt.contains(&format!("In my-project at {}, keep {{{{unknown}}}}", dir.path().display())));
+        // 本文で使ったので末尾には付けない
+        assert!(!prompt.contains("User input:"));
+
+        // {{file}} は存在しないパスなら残す
+        let prompt = executor.execute(&skill, &context(Some("missing.rs"), dir.path())).await.unwrap();
+        assert!(prompt.contains("file: {{file}}"));
+    }
+
+    #[tokio::test]
+    async fn test_execute_requires_args_when_declared() {
+        let dir = tempfile::tempdir().unwrap();
+        let executor = SkillExecutor::new(Arc::new(SkillRegistry::new()));
+        let skill = skill("args_required: true\n", "Review {{args}}");
+
+        for args in [None, Some("  ")] {
+            let err = executor.execute(&skill, &context(args, dir.path())).await.unwrap_err().to_string();
+            assert_eq!(err, "/review needs arguments. Usage: /review <args>\nReview a file for bugs");
+        }
+        assert!(executor.execute(&skill, &context(Some("a.rs"), dir.path())).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_execute_escapes_and_appends_unplaced_args() {
+        let dir = tempfile::tempdir().unwrap();
+        let executor = SkillExecutor::new(Arc::new(SkillRegistry::new()));
+        let skill = skill("", r"Templates look like \{{args}} in SKILL.md");
+
+        let prompt = executor.execute(&skill, &context(Some("explain"), dir.path())).await.unwrap();
+        assert_eq!(prompt, "Templates look like {{args}} in SKILL.md\n\n---\n\nUser input: explain");
+    }
 }
@@ -1,17 +1,18 @@
 use anyhow::Result;
+use regex::{Regex, RegexBuilder};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tokio::fs;
 
 /// スキルのメタデータ（YAML frontmatter）
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SkillMetadata {
     /// スキル名
     pub name: String,
     /// 説明
     #[serde(default)]
     pub description: String,
-    /// トリガーフレーズ
+    /// トリガーフレーズ（語境界で照合。`/regex/` は正規表現）
     #[serde(default)]
     pub triggers: Vec<String>,
     /// 自動実行するか
@@ -20,10 +21,159 @@ pub struct SkillMetadata {
     /// 親スキル名（階層構造用）
     #[serde(default)]
     pub parent: Option<String>,
+    /// プロジェクト外で追加アクセスを要求するパス
+    #[serde(default)]
+    pub extra_paths: Vec<ExtraPath>,
+    /// 引数なしでは実行できない（`{{args}}` などを使うスキル）
+    #[serde(default)]
+    pub args_required: bool,
+    /// スキル実行中に使えるツール（`allowed-tools`、None なら制限しない）
+    #[serde(default, rename = "allowed-tools", deserialize_with = "deserialize_tool_list")]
+    pub allowed_tools: Option<Vec<String>>,
+    /// 複数のスキルがマッチしたときの優先度（大きいほど優先）
+    #[serde(default)]
+    pub priority: i64,
 }
 
-/// スキル定義
+/// `allowed-tools` はリストでもカンマ区切りの文字列でもよい
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ToolListSpec {
+    List(Vec<String>),
+    Text(String),
+}
+
+/// ツール名を揃える（Claude Code の `Read`・`Bash(git diff:*)` は `read`・`bash` として扱う）
+fn deserialize_tool_list<'de, D>(deserializer: D) -> std::result::Result<Option<Vec<String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let names = match ToolListSpec::deserialize(deserializer)? {
+        ToolListSpec::List(names) => names,
+        ToolListSpec::Text(text) => text.split(',').map(str::to_string).collect(),
+    };
+    let names = names
+        .iter()
+        .map(|name| name.split('(').next().unwrap_or("").trim().to_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect();
+    Ok(Some(names))
+}
+
+/// コンパイル済みのトリガー
 #[derive(Debug, Clone)]
+pub enum Trigger {
+    /// キーワード（小文字化済み、英数字の端は語境界で照合）
+    Keyword(String),
+    /// `/regex/` 形式の正規表現（大文字小文字を区別しない）
+    Pattern(Regex),
+}
+
+impl PartialEq for Trigger {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Keyword(a), Self::Keyword(b)) => a == b,
+            (Self::Pattern(a), Self::Pattern(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        }
+    }
+}
+
+impl Trigger {
+    /// frontmatter の1項目をコンパイルする
+    pub fn parse(spec: &str) -> Result<Self, regex::Error> {
+        match spec.strip_prefix('/').and_then(|rest| rest.strip_suffix('/')) {
+            Some(pattern) if !pattern.is_empty() => {
+                RegexBuilder::new(pattern).case_insensitive(true).build().map(Self::Pattern)
+            }
+            _ => Ok(Self::Keyword(spec.to_lowercase())),
+        }
+    }
+
+    /// マッチすればその長さ（文字数、具体性の目安）を返す
+    ///
+    /// `lower` は `input` を小文字化したもの
+    fn find(&self, input: &str, lower: &str) -> Option<usize> {
+        match self {
+            Self::Keyword(keyword) if keyword.is_empty() => None,
+            Self::Keyword(keyword) => lower
+                .match_indices(keyword.as_str())
+                .any(|(start, _)| is_word_bounded(lower, start, start + keyword.len()))
+                .then(|| keyword.chars().count()),
+            Self::Pattern(regex) => regex.find(input).map(|m| m.as_str().chars().count().max(1)),
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// キーワードの端が英数字なら、隣の文字が英数字でないことを確かめる
+///
+/// 日本語のように単語を空白で区切らない言語は部分一致のままにする
+fn is_word_bounded(text: &str, start: usize, end: usize) -> bool {
+    let matched = &text[start..end];
+    let left_ok = !matched.starts_with(is_word_char) || !text[..start].ends_with(is_word_char);
+    let right_ok = !matched.ends_with(is_word_char) || !text[end..].starts_with(is_word_char);
+    left_ok && right_ok
+}
+
+/// スキルが追加でアクセスを要求するパス
+///
+/// frontmatter では文字列（読み取りのみ）または `{path, write}` で指定する
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "ExtraPathSpec")]
+pub struct ExtraPath {
+    /// パス（絶対パス、またはスキルファイル/プロジェクトからの相対パス）
+    pub path: String,
+    /// 書き込みを許可するか（常に確認が必要）
+    pub write: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ExtraPathSpec {
+    Path(String),
+    Detailed {
+        path: String,
+        #[serde(default)]
+        write: bool,
+    },
+}
+
+impl From<ExtraPathSpec> for ExtraPath {
+    fn from(spec: ExtraPathSpec) -> Self {
+        match spec {
+            ExtraPathSpec::Path(path) => Self { path, write: false },
+            ExtraPathSpec::Detailed { path, write } => Self { path, write },
+        }
+    }
+}
+
+impl ExtraPath {
+    /// 絶対パスに解決する
+    ///
+    /// 相対パスはスキルファイルのディレクトリを優先し、
+    /// 存在しなければプロジェクトルートからの相対パスとみなす
+    pub fn resolve(&self, skill_path: &Path, project_root: &Path) -> PathBuf {
+        let path = Path::new(&self.path);
+        if path.is_absolute() {
+            return path.to_path_buf();
+        }
+
+        if let Some(skill_dir) = skill_path.parent() {
+            let candidate = skill_dir.join(path);
+            if candidate.exists() {
+                return candidate;
+            }
+        }
+        project_root.join(path)
+    }
+}
+
+/// スキル定義
+#[derive(Debug, Clone, PartialEq)]
 pub struct Skill {
     /// メタデータ
     pub metadata: SkillMetadata,
@@ -31,6 +181,8 @@ pub struct Skill {
     pub content: String,
     /// ファイルパス
     pub path: std::path::PathBuf,
+    /// 読み込み時にコンパイルしたトリガー（不正な正規表現は除く）
+    pub triggers: Vec<Trigger>,
 }
 
 impl Skill {
@@ -49,11 +201,23 @@ impl Skill {
     fn parse(content: &str, path: std::path::PathBuf) -> Result<Self> {
         // YAML frontmatterを抽出
         let (metadata, body) = Self::extract_frontmatter(content)?;
+        let triggers = metadata
+            .triggers
+            .iter()
+            .filter_map(|spec| match Trigger::parse(spec) {
+                Ok(trigger) => Some(trigger),
+                Err(e) => {
+                    tracing::warn!("Skill '{}': skipping invalid trigger {}: {}", metadata.name, spec, e);
+                    None
+                }
+            })
+            .collect();
 
         Ok(Self {
             metadata,
             content: body,
             path,
+            triggers,
         })
     }
 
@@ -70,6 +234,10 @@ impl Skill {
                     triggers: Vec::new(),
                     auto: false,
                     parent: None,
+                    extra_paths: Vec::new(),
+                    args_required: false,
+                    allowed_tools: None,
+                    priority: 0,
                 },
                 content.to_string(),
             ));
@@ -90,10 +258,13 @@ impl Skill {
 
     /// トリガーフレーズにマッチするか確認
     pub fn matches_trigger(&self, input: &str) -> bool {
-        let input_lower = input.to_lowercase();
-        self.metadata.triggers.iter().any(|trigger| {
-            input_lower.contains(&trigger.to_lowercase())
-        })
+        !self.trigger_hits(input).is_empty()
+    }
+
+    /// マッチしたトリガーそれぞれの長さ（文字数）
+    pub fn trigger_hits(&self, input: &str) -> Vec<usize> {
+        let lower = input.to_lowercase();
+        self.triggers.iter().filter_map(|trigger| trigger.find(input, &lower)).collect()
     }
 }
 
@@ -123,4 +294,86 @@ This is the skill content.
         assert!(metadata.auto);
         assert!(body.contains("# Test Skill"));
     }
+
+    #[test]
+    fn test_parse_extra_paths() {
+        let content = r#"---
+name: deploy-notes
+extra_paths:
+  - ../infra
+  - path: /var/tmp/notes
+    write: true
+---
+
+Body
+"#;
+
+        let (metadata, _) = Skill::extract_frontmatter(content).unwrap();
+        assert_eq!(
+            metadata.extra_paths,
+            vec![
+                ExtraPath { path: "../infra".to_string(), write: false },
+                ExtraPath { path: "/var/tmp/notes".to_string(), write: true },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_allowed_tools() {
+        let parse = |field: &str| {
+            let content = format!("---\nname: review\n{}---\nBody\n", field);
+            Skill::extract_frontmatter(&content).unwrap().0.allowed_tools
+        };
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("allowed-tools:\n  - read\n  - grep\n"), Some(vec!["read".to_string(), "grep".to_string()]));
+        assert_eq!(
+            parse("allowed-tools: Read, Grep, Bash(git diff:*)\n"),
+            Some(vec!["read".to_string(), "grep".to_string(), "bash".to_string()])
+        );
+        assert_eq!(parse("allowed-tools: []\n"), Some(Vec::new()));
+    }
+
+    #[test]
+    fn test_keyword_triggers_match_on_word_boundaries() {
+        let content = "---\nname: test\ntriggers:\n  - test\n  - run tests\n  - テスト\n---\nBody\n";
+        let skill = Skill::load_from_string(content, "/virtual/SKILL.md").unwrap();
+        assert!(skill.matches_trigger("please Test this"));
+        assert!(skill.matches_trigger("test."));
+        assert!(!skill.matches_trigger("testing the parser"));
+        assert!(!skill.matches_trigger("a contest"));
+        assert!(!skill.matches_trigger("my_test_helper"));
+        assert_eq!(skill.trigger_hits("Run tests, then test again"), vec![4, 9]);
+        // 日本語は語の区切りがないので部分一致
+        assert!(skill.matches_trigger("テストを書いて"));
+    }
+
+    #[test]
+    fn test_regex_triggers_compile_once_and_skip_invalid() {
+        let content = "---\nname: fix\ntriggers:\n  - '/fix(es)? #\\d+/'\n  - /unclosed(/\n  - /\n---\nBody\n";
+        let skill = Skill::load_from_string(content, "/virtual/SKILL.md").unwrap();
+        assert_eq!(skill.triggers.len(), 2);
+        assert!(matches!(&skill.triggers[0], Trigger::Pattern(re) if re.as_str() == "fix(es)? #\\d+"));
+        // `/` だけの項目はキーワード扱い
+        assert_eq!(skill.triggers[1], Trigger::Keyword("/".to_string()));
+        assert_eq!(skill.trigger_hits("FIXES #42 please"), vec![9]);
+        assert!(!skill.matches_trigger("fix the bug"));
+    }
+
+    #[test]
+    fn test_resolve_extra_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let skill_dir = dir.path().join("skills").join("deploy");
+        std::fs::create_dir_all(skill_dir.join("templates")).unwrap();
+        let skill_path = skill_dir.join("SKILL.md");
+        let project = dir.path().join("project");
+
+        let near_skill = ExtraPath { path: "templates".to_string(), write: false };
+        assert_eq!(near_skill.resolve(&skill_path, &project), skill_dir.join("templates"));
+
+        let near_project = ExtraPath { path: "../infra".to_string(), write: false };
+        assert_eq!(near_project.resolve(&skill_path, &project), project.join("../infra"));
+
+        let absolute = ExtraPath { path: "/opt/infra".to_string(), write: false };
+        assert_eq!(absolute.resolve(&skill_path, &project), PathBuf::from("/opt/infra"));
+    }
 }
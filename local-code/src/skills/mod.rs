@@ -4,10 +4,14 @@ pub mod trigger;
 pub mod executor;
 pub mod superpowers;
 pub mod embedded;
+pub mod template;
+pub mod scaffold;
+pub mod sandbox;
 
-pub use loader::{Skill, SkillMetadata};
-pub use registry::SkillRegistry;
-pub use trigger::TriggerDetector;
+pub use loader::{ExtraPath, Skill, SkillMetadata, Trigger};
+pub use registry::{SharedSkillRegistry, SkillChanges, SkillOrigin, SkillRegistry};
+pub use trigger::{SkillMatch, TriggerDetector};
 pub use executor::{SkillExecutor, SkillContext, SkillResult};
 pub use superpowers::{SuperpowersCommand, load_superpowers_commands};
 pub use embedded::EmbeddedSuperpowers;
+pub use sandbox::{grant_extra_paths, SkillGrants};
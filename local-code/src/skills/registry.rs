@@ -1,12 +1,16 @@
 use anyhow::Result;
-use std::collections::HashMap;
+use async_trait::async_trait;
+use std::collections::{BTreeSet, HashMap};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 use tokio::fs;
 
 use super::loader::Skill;
 use super::embedded::EmbeddedSuperpowers;
+use crate::agent::status::{StatusProvider, SubsystemStatus};
 
 /// スキルレジストリ - スキルの探索と管理
+#[derive(Clone)]
 pub struct SkillRegistry {
     /// 登録されたスキル（名前 -> スキル）
     skills: HashMap<String, Skill>,
@@ -14,6 +18,12 @@ pub struct SkillRegistry {
     superpowers_skills: HashMap<String, Skill>,
     /// スキル探索パス
     search_paths: Vec<SkillSearchPath>,
+    /// 読み込めなかったスキル（パス: エラー）
+    load_errors: Vec<String>,
+    /// スキルの読み込み元（スキルファイルのパス -> 読み込み元）
+    origins: HashMap<PathBuf, SkillOrigin>,
+    /// 無効にしたスキル名（`name` は同名の superpowers 版も、`superpowers:name` は superpowers 版だけを隠す）
+    disabled: BTreeSet<String>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -28,6 +38,53 @@ struct SkillSearchPath {
     source: SkillSource,
 }
 
+impl SkillSearchPath {
+    fn origin(&self) -> SkillOrigin {
+        match self.source {
+            SkillSource::User => SkillOrigin::User(self.path.clone()),
+            SkillSource::Superpowers => SkillOrigin::Superpowers(self.path.clone()),
+        }
+    }
+}
+
+/// スキルの読み込み元
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkillOrigin {
+    /// バイナリに埋め込んだ superpowers
+    Embedded,
+    /// superpowers ディレクトリ
+    Superpowers(PathBuf),
+    /// ユーザーのスキルディレクトリ（~/.claude/skills、custom_path など）
+    User(PathBuf),
+}
+
+impl SkillOrigin {
+    fn source(&self) -> SkillSource {
+        match self {
+            Self::Embedded | Self::Superpowers(_) => SkillSource::Superpowers,
+            Self::User(_) => SkillSource::User,
+        }
+    }
+
+    /// 一覧用の短い名前
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Embedded => "embedded",
+            Self::Superpowers(_) => "superpowers",
+            Self::User(_) => "user",
+        }
+    }
+}
+
+impl std::fmt::Display for SkillOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Embedded => write!(f, "embedded"),
+            Self::Superpowers(dir) | Self::User(dir) => write!(f, "{} ({})", self.label(), dir.display()),
+        }
+    }
+}
+
 impl SkillRegistry {
     /// 新しいレジストリを作成
     pub fn new() -> Self {
@@ -49,9 +106,67 @@ impl SkillRegistry {
             skills: HashMap::new(),
             superpowers_skills: HashMap::new(),
             search_paths,
+            load_errors: Vec::new(),
+            origins: HashMap::new(),
+            disabled: BTreeSet::new(),
         }
     }
 
+    /// 無効にするスキル名を設定（`[skills] disabled`）
+    pub fn set_disabled(&mut self, names: &[String]) {
+        self.disabled = names.iter().cloned().collect();
+    }
+
+    /// スキルを無効にする（見えなくなれば true）
+    pub fn disable(&mut self, name: &str) -> bool {
+        let visible = self.get(name).is_some();
+        self.disabled.insert(name.to_string());
+        visible && self.get(name).is_none()
+    }
+
+    /// スキルを有効に戻す（見えるようになれば true）
+    ///
+    /// `name` と `superpowers:name` のどちらで無効にしていても戻す
+    pub fn enable(&mut self, name: &str) -> bool {
+        let hidden = self.get(name).is_none();
+        let base = name.strip_prefix("superpowers:").unwrap_or(name);
+        self.disabled.remove(base);
+        self.disabled.remove(&format!("superpowers:{}", base));
+        hidden && self.get(name).is_some()
+    }
+
+    /// スキルの読み込み元
+    pub fn origin(&self, skill: &Skill) -> Option<&SkillOrigin> {
+        self.origins.get(&skill.path)
+    }
+
+    /// 読み込み済みで無効になっているスキル名（/skills 用）
+    pub fn disabled_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .skills
+            .iter()
+            .filter(|(name, skill)| self.is_hidden(name, skill))
+            .map(|(name, _)| name.clone())
+            .chain(
+                self.superpowers_skills
+                    .keys()
+                    .filter(|name| self.is_superpowers_hidden(name))
+                    .map(|name| format!("superpowers:{}", name)),
+            )
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn is_superpowers_hidden(&self, name: &str) -> bool {
+        self.disabled.contains(name) || self.disabled.contains(&format!("superpowers:{}", name))
+    }
+
+    fn is_hidden(&self, name: &str, skill: &Skill) -> bool {
+        self.disabled.contains(name)
+            || (self.superpowers_skills.get(name) == Some(skill) && self.is_superpowers_hidden(name))
+    }
+
     /// カスタム探索パスを追加
     pub fn add_search_path(&mut self, path: PathBuf) {
         self.search_paths.push(SkillSearchPath {
@@ -76,7 +191,7 @@ impl SkillRegistry {
         // 2. ファイルシステムからスキルをロード（オーバーライド可能）
         for entry in &self.search_paths.clone() {
             if entry.path.exists() {
-                self.load_from_directory(&entry.path, entry.source).await?;
+                self.load_from_directory(&entry.path, &entry.origin()).await?;
             }
         }
         Ok(())
@@ -89,10 +204,11 @@ impl SkillRegistry {
                 match Skill::load_from_string(&content, &format!("embedded://{}", path)) {
                     Ok(skill) => {
                         tracing::debug!("Loaded embedded skill: {}", skill.metadata.name);
-                        self.insert_skill(skill, SkillSource::Superpowers);
+                        self.insert_skill(skill, SkillOrigin::Embedded);
                     }
                     Err(e) => {
                         tracing::warn!("Failed to parse embedded skill {}: {}", path, e);
+                        self.load_errors.push(format!("embedded://{}: {}", path, e));
                     }
                 }
             }
@@ -103,7 +219,7 @@ impl SkillRegistry {
     fn load_from_directory<'a>(
         &'a mut self,
         dir: &'a Path,
-        source: SkillSource,
+        origin: &'a SkillOrigin,
     ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send + 'a>> {
         Box::pin(async move {
             let mut entries = fs::read_dir(dir).await?;
@@ -115,15 +231,21 @@ impl SkillRegistry {
                     // ディレクトリの場合、SKILL.mdを探す
                     let skill_file = path.join("SKILL.md");
                     if skill_file.exists() {
-                        if let Ok(skill) = Skill::load_from_file(&skill_file).await {
-                            tracing::info!("Loaded skill: {} from {}", skill.metadata.name, skill_file.display());
-                            self.insert_skill(skill, source);
+                        match Skill::load_from_file(&skill_file).await {
+                            Ok(skill) => {
+                                tracing::info!("Loaded skill: {} from {}", skill.metadata.name, skill_file.display());
+                                self.insert_skill(skill, origin.clone());
+                            }
+                            Err(e) => {
+                                tracing::warn!("Failed to load skill {}: {}", skill_file.display(), e);
+                                self.load_errors.push(format!("{}: {}", skill_file.display(), e));
+                            }
                         }
                     }
 
                     // プラグインキャッシュの場合はさらに深くスキャン
                     if path.to_string_lossy().contains("plugins/cache") {
-                        self.scan_plugin_directory(&path, source).await?;
+                        self.scan_plugin_directory(&path, origin).await?;
                     }
                 }
             }
@@ -136,7 +258,7 @@ impl SkillRegistry {
     fn scan_plugin_directory<'a>(
         &'a mut self,
         plugin_dir: &'a Path,
-        source: SkillSource,
+        origin: &'a SkillOrigin,
     ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send + 'a>> {
         Box::pin(async move {
             let mut entries = fs::read_dir(plugin_dir).await?;
@@ -146,7 +268,7 @@ impl SkillRegistry {
                 if version_dir.is_dir() {
                     let skills_dir = version_dir.join("skills");
                     if skills_dir.exists() {
-                        self.load_from_directory(&skills_dir, source).await?;
+                        self.load_from_directory(&skills_dir, origin).await?;
                     }
                 }
             }
@@ -155,26 +277,73 @@ impl SkillRegistry {
         })
     }
 
+    /// 同じ探索パスから読み込み直した新しいレジストリを作る（/reload）
+    ///
+    /// SKILL.md のパースエラーは中断せず `load_errors` に残る
+    pub async fn reload(&self) -> Result<SkillRegistry> {
+        let mut registry = Self {
+            skills: HashMap::new(),
+            superpowers_skills: HashMap::new(),
+            search_paths: self.search_paths.clone(),
+            load_errors: Vec::new(),
+            origins: HashMap::new(),
+            disabled: self.disabled.clone(),
+        };
+        registry.load_all().await?;
+        Ok(registry)
+    }
+
+    /// `old` からの変化（追加・削除・内容の変わったスキル名）
+    pub fn changes_from(&self, old: &SkillRegistry) -> SkillChanges {
+        let old_names = old.names();
+        let new_names = self.names();
+        SkillChanges {
+            added: new_names.iter().filter(|n| !old_names.contains(n)).cloned().collect(),
+            removed: old_names.iter().filter(|n| !new_names.contains(n)).cloned().collect(),
+            changed: new_names
+                .iter()
+                .filter(|n| old_names.contains(n) && old.get(n) != self.get(n))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// 読み込みに失敗したスキル（パス: エラー）
+    pub fn load_errors(&self) -> &[String] {
+        &self.load_errors
+    }
+
     /// 名前でスキルを取得
     pub fn get(&self, name: &str) -> Option<&Skill> {
+        let superpowers = |name: &str| self.superpowers_skills.get(name).filter(|_| !self.is_superpowers_hidden(name));
         if let Some(stripped) = name.strip_prefix("superpowers:") {
-            return self.superpowers_skills.get(stripped);
+            return superpowers(stripped);
         }
 
         self.skills
             .get(name)
-            .or_else(|| self.superpowers_skills.get(name))
+            .filter(|skill| !self.is_hidden(name, skill))
+            .or_else(|| superpowers(name))
     }
 
-    /// 全スキルのリストを取得
+    /// 全スキルのリストを取得（無効なスキルは除く）
     pub fn list(&self) -> Vec<&Skill> {
-        self.skills.values().collect()
+        self.skills
+            .iter()
+            .filter(|(name, skill)| !self.is_hidden(name, skill))
+            .map(|(_, skill)| skill)
+            .collect()
     }
 
-    /// スキル名一覧を取得
+    /// スキル名一覧を取得（無効なスキルは除く）
     pub fn names(&self) -> Vec<String> {
-        let mut names: Vec<String> = self.skills.keys().cloned().collect();
-        for name in self.superpowers_skills.keys() {
+        let mut names: Vec<String> = self
+            .skills
+            .iter()
+            .filter(|(name, skill)| !self.is_hidden(name, skill))
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in self.superpowers_skills.keys().filter(|name| !self.is_superpowers_hidden(name)) {
             names.push(format!("superpowers:{}", name));
         }
         names.sort();
@@ -184,24 +353,26 @@ impl SkillRegistry {
 
     /// トリガーにマッチするスキルを検索
     pub fn find_by_trigger(&self, input: &str) -> Vec<&Skill> {
-        self.skills
-            .values()
+        self.list()
+            .into_iter()
             .filter(|skill| skill.matches_trigger(input))
             .collect()
     }
 
     /// スキル数を取得
     pub fn len(&self) -> usize {
-        self.skills.len()
+        self.list().len()
     }
 
     /// スキルが空かチェック
     pub fn is_empty(&self) -> bool {
-        self.skills.is_empty()
+        self.list().is_empty()
     }
 
-    fn insert_skill(&mut self, skill: Skill, source: SkillSource) {
+    fn insert_skill(&mut self, skill: Skill, origin: SkillOrigin) {
         let name = skill.metadata.name.clone();
+        let source = origin.source();
+        self.origins.insert(skill.path.clone(), origin);
         match source {
             SkillSource::Superpowers => {
                 self.superpowers_skills.insert(name.clone(), skill.clone());
@@ -220,6 +391,80 @@ impl Default for SkillRegistry {
     }
 }
 
+/// 再読み込みで変わったスキル（名前順）
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SkillChanges {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl SkillChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl std::fmt::Display for SkillChanges {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no changes");
+        }
+        let parts: Vec<String> = [("added", &self.added), ("removed", &self.removed), ("changed", &self.changed)]
+            .into_iter()
+            .filter(|(_, names)| !names.is_empty())
+            .map(|(label, names)| format!("{} {} ({})", names.len(), label, names.join(", ")))
+            .collect();
+        write!(f, "{}", parts.join("; "))
+    }
+}
+
+/// /reload で差し替えられるレジストリ
+///
+/// 使う側はその都度 `current` で取り出す（/status もこれ経由で最新の状態を返す）
+#[derive(Clone)]
+pub struct SharedSkillRegistry {
+    inner: Arc<RwLock<Arc<SkillRegistry>>>,
+}
+
+impl SharedSkillRegistry {
+    pub fn new(registry: Arc<SkillRegistry>) -> Self {
+        Self { inner: Arc::new(RwLock::new(registry)) }
+    }
+
+    /// 現在のレジストリ
+    pub fn current(&self) -> Arc<SkillRegistry> {
+        Arc::clone(&self.inner.read().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// レジストリを差し替える
+    pub fn replace(&self, registry: Arc<SkillRegistry>) {
+        *self.inner.write().unwrap_or_else(|e| e.into_inner()) = registry;
+    }
+}
+
+#[async_trait]
+impl StatusProvider for SharedSkillRegistry {
+    async fn status(&self) -> SubsystemStatus {
+        self.current().status().await
+    }
+}
+
+#[async_trait]
+impl StatusProvider for SkillRegistry {
+    async fn status(&self) -> SubsystemStatus {
+        let loaded = format!("{} skills loaded", self.len());
+        match self.load_errors.first() {
+            None => SubsystemStatus::ok("skills", loaded),
+            Some(first) => SubsystemStatus::degraded(
+                "skills",
+                format!("{}, {} failed ({})", loaded, self.load_errors.len(), first),
+            )
+            .with_hint(Some("Fix the frontmatter of the listed SKILL.md")),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -229,4 +474,141 @@ mod tests {
         let registry = SkillRegistry::new();
         assert!(registry.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_load_errors_are_reported_in_status() {
+        use crate::agent::status::HealthState;
+
+        let dir = tempfile::tempdir().unwrap();
+        let broken = dir.path().join("broken");
+        std::fs::create_dir(&broken).unwrap();
+        std::fs::write(broken.join("SKILL.md"), "---\nname: broken\nnever closed").unwrap();
+
+        let mut registry = SkillRegistry::new();
+        registry.add_search_path(dir.path().to_path_buf());
+        registry.load_all().await.unwrap();
+
+        assert_eq!(registry.load_errors().len(), 1);
+        assert!(registry.load_errors()[0].contains("broken/SKILL.md"));
+        let status = registry.status().await;
+        assert_eq!(status.state, HealthState::Degraded);
+        assert!(status.detail.contains("1 failed"));
+    }
+
+    fn write_skill(dir: &Path, name: &str, body: &str) {
+        let skill_dir = dir.join(name);
+        std::fs::create_dir_all(&skill_dir).unwrap();
+        std::fs::write(skill_dir.join("SKILL.md"), format!("---\nname: {}\ndescription: test\n---\n{}", name, body)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reload_reports_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        write_skill(dir.path(), "keep", "same");
+        write_skill(dir.path(), "edit", "before");
+        write_skill(dir.path(), "drop", "gone soon");
+
+        let mut registry = SkillRegistry::new();
+        registry.add_search_path(dir.path().to_path_buf());
+        registry.load_all().await.unwrap();
+        assert!(registry.get("edit").is_some());
+
+        write_skill(dir.path(), "edit", "after");
+        write_skill(dir.path(), "fresh", "new");
+        std::fs::remove_dir_all(dir.path().join("drop")).unwrap();
+        // パースできない SKILL.md があっても再読み込みは続ける
+        let broken = dir.path().join("broken");
+        std::fs::create_dir(&broken).unwrap();
+        std::fs::write(broken.join("SKILL.md"), "---\nname: broken\nnever closed").unwrap();
+
+        let reloaded = registry.reload().await.unwrap();
+        let changes = reloaded.changes_from(&registry);
+        assert_eq!(changes.added, vec!["fresh"]);
+        assert_eq!(changes.removed, vec!["drop"]);
+        assert_eq!(changes.changed, vec!["edit"]);
+        assert_eq!(changes.to_string(), "1 added (fresh); 1 removed (drop); 1 changed (edit)");
+        assert_eq!(reloaded.get("edit").unwrap().content, "after");
+        assert_eq!(reloaded.load_errors().len(), 1);
+        assert!(reloaded.reload().await.unwrap().changes_from(&reloaded).is_empty());
+
+        // 共有しているハンドルは差し替え後のレジストリを返す
+        let shared = SharedSkillRegistry::new(Arc::new(registry));
+        let handle = shared.clone();
+        shared.replace(Arc::new(reloaded));
+        assert!(handle.current().get("fresh").is_some());
+        assert!(handle.status().await.detail.contains("1 failed"));
+    }
+
+    #[tokio::test]
+    async fn test_disabled_skills_are_hidden() {
+        use crate::skills::TriggerDetector;
+
+        let dir = tempfile::tempdir().unwrap();
+        write_skill(dir.path(), "lint", "Lint");
+        let review = dir.path().join("review");
+        std::fs::create_dir(&review).unwrap();
+        std::fs::write(review.join("SKILL.md"), "---\nname: review\ntriggers: [review]\n---\nReview").unwrap();
+
+        let mut registry = SkillRegistry::new();
+        registry.add_search_path(dir.path().to_path_buf());
+        registry.set_disabled(&["review".to_string(), "superpowers:brainstorming".to_string()]);
+        registry.load_all().await.unwrap();
+
+        assert!(registry.get("review").is_none());
+        assert!(registry.get("brainstorming").is_none());
+        assert!(registry.get("superpowers:brainstorming").is_none());
+        assert!(registry.get("lint").is_some());
+        let names = registry.names();
+        assert!(names.contains(&"lint".to_string()));
+        assert!(!names.iter().any(|n| n == "review" || n.ends_with("brainstorming")));
+        assert_eq!(registry.disabled_names(), vec!["brainstorming", "review", "superpowers:brainstorming"]);
+        assert!(registry.find_by_trigger("please review this").is_empty());
+        assert!(TriggerDetector::new(&registry).detect("please review this").is_empty());
+        // 再読み込みしても無効のまま
+        assert!(registry.reload().await.unwrap().get("review").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_origin_after_override() {
+        let dir = tempfile::tempdir().unwrap();
+        write_skill(dir.path(), "brainstorming", "My own take");
+        let mut registry = SkillRegistry::new();
+        registry.add_search_path(dir.path().to_path_buf());
+        registry.load_all().await.unwrap();
+
+        let own = registry.get("brainstorming").unwrap();
+        assert_eq!(own.content, "My own take");
+        assert_eq!(registry.origin(own), Some(&SkillOrigin::User(dir.path().to_path_buf())));
+        let embedded = registry.get("superpowers:brainstorming").unwrap();
+        assert_eq!(registry.origin(embedded), Some(&SkillOrigin::Embedded));
+        assert_eq!(registry.origin(embedded).unwrap().to_string(), "embedded");
+        assert_eq!(
+            registry.origin(own).unwrap().to_string(),
+            format!("user ({})", dir.path().display())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_enable_disable_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        write_skill(dir.path(), "lint", "Lint");
+        let mut registry = SkillRegistry::new();
+        registry.add_search_path(dir.path().to_path_buf());
+        registry.load_all().await.unwrap();
+        let before = registry.names();
+
+        assert!(registry.disable("lint"));
+        assert!(!registry.disable("lint"));
+        assert!(registry.disable("brainstorming"));
+        assert!(registry.get("superpowers:brainstorming").is_none());
+        assert_eq!(registry.disabled_names(), vec!["brainstorming", "lint", "superpowers:brainstorming"]);
+
+        // superpowers: 付きの名前でも戻せる
+        assert!(registry.enable("superpowers:brainstorming"));
+        assert!(registry.enable("lint"));
+        assert!(!registry.enable("lint"));
+        assert!(!registry.enable("missing"));
+        assert_eq!(registry.names(), before);
+        assert!(registry.disabled_names().is_empty());
+    }
 }
@@ -0,0 +1,186 @@
+//! スキルの `extra_paths`：スキルのターンの間だけパスの制限を広げる
+//!
+//! 要求ごとにユーザーに尋ね（読み取りは「常に許可」を選べる、書き込みは毎回）、
+//! 許可したものだけをガードに入れる。ガードを破棄すると元の制限に戻る
+
+use std::path::Path;
+use std::sync::Arc;
+
+use super::loader::Skill;
+use crate::agent::ToolConfirmation;
+use crate::tools::{PathGrant, PathGrantGuard, PathPolicy};
+
+/// スキルに与えた一時的な許可
+#[derive(Debug)]
+pub struct SkillGrants {
+    /// 許可したパス（ターンが終わるまで持っておく）
+    pub guard: PathGrantGuard,
+    /// 拒否された要求（表示用）
+    pub denied: Vec<String>,
+}
+
+impl SkillGrants {
+    /// 表示用の1行ずつの説明（要求がなければ空）
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self
+            .guard
+            .grants()
+            .iter()
+            .map(|g| format!("granted {} access to {} for this turn", access(g.write), g.root.display()))
+            .collect();
+        lines.extend(self.denied.iter().map(|d| format!("denied {}", d)));
+        lines
+    }
+}
+
+fn access(write: bool) -> &'static str {
+    if write {
+        "read and write"
+    } else {
+        "read"
+    }
+}
+
+/// `skill` の `extra_paths` を尋ね、許可したものをこのターンの間だけ `policy` に加える
+///
+/// すでに制限の内側にあるパスは尋ねない
+pub fn grant_extra_paths(
+    skill: &Skill,
+    project_root: &Path,
+    policy: &Arc<PathPolicy>,
+    confirmation: &ToolConfirmation,
+) -> SkillGrants {
+    let name = &skill.metadata.name;
+    let mut grants = Vec::new();
+    let mut denied = Vec::new();
+    for extra in &skill.metadata.extra_paths {
+        let resolved = extra.resolve(&skill.path, project_root);
+        let inside = if extra.write { policy.check_write(&resolved) } else { policy.check_read(&resolved) };
+        if inside.is_ok() {
+            continue;
+        }
+        let details = format!(
+            "skill '{}' requests {} access to {} ({})",
+            name,
+            access(extra.write),
+            extra.path,
+            resolved.display()
+        );
+        if confirmation.confirm_grant(name, &details, extra.write) {
+            grants.push(PathGrant::new(&resolved, extra.write));
+        } else {
+            denied.push(format!("{} access to {}", access(extra.write), extra.path));
+        }
+    }
+    SkillGrants { guard: policy.grant(grants), denied }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::ConfirmationPolicy;
+    use crate::cli::ConfirmResult;
+    use std::sync::Mutex;
+
+    /// 決まった答えを返し、尋ねられた内容を記録する
+    struct Scripted {
+        answer: ConfirmResult,
+        asked: Mutex<Vec<(String, bool)>>,
+    }
+
+    impl ConfirmationPolicy for Scripted {
+        fn decide(&self, _tool: &str, _details: &str) -> ConfirmResult {
+            unreachable!("grants use decide_grant")
+        }
+
+        fn decide_grant(&self, _skill: &str, details: &str, always_choices: bool) -> ConfirmResult {
+            self.asked.lock().unwrap().push((details.to_string(), always_choices));
+            self.answer
+        }
+    }
+
+    fn setup(answer: ConfirmResult) -> (ToolConfirmation, Arc<Scripted>) {
+        let policy = Arc::new(Scripted { answer, asked: Mutex::new(Vec::new()) });
+        (ToolConfirmation::new(policy.clone()), policy)
+    }
+
+    fn skill(project: &Path, frontmatter: &str) -> Skill {
+        let content = format!("---\nname: deploy-notes\n{}---\nbody\n", frontmatter);
+        Skill::load_from_string(&content, &project.join("skills/deploy-notes/SKILL.md").to_string_lossy()).unwrap()
+    }
+
+    #[test]
+    fn test_grant_read_and_remember_always() {
+        let project = tempfile::tempdir().unwrap();
+        let infra = tempfile::tempdir().unwrap();
+        let policy = Arc::new(PathPolicy::new(project.path()));
+        let (confirmation, asked) = setup(ConfirmResult::AlwaysAllowTool);
+        let skill = skill(project.path(), &format!("extra_paths:\n  - {}\n  - src\n", infra.path().display()));
+        let file = infra.path().join("main.tf");
+
+        let grants = grant_extra_paths(&skill, project.path(), &policy, &confirmation);
+        assert!(policy.check_read(&file).is_ok());
+        assert!(policy.check_write(&file).is_err());
+        assert_eq!(grants.summary().len(), 1, "{:?}", grants.summary());
+        drop(grants);
+        assert!(policy.check_read(&file).is_err());
+
+        // 「常に許可」は次のターンでは尋ねない（プロジェクト内の src は最初から尋ねない）
+        let _grants = grant_extra_paths(&skill, project.path(), &policy, &confirmation);
+        assert!(policy.check_read(&file).is_ok());
+        let asked = asked.asked.lock().unwrap();
+        assert_eq!(asked.len(), 1);
+        assert!(asked[0].0.contains("requests read access to"), "{}", asked[0].0);
+        assert!(asked[0].1);
+    }
+
+    #[test]
+    fn test_denied_and_write_requests() {
+        let project = tempfile::tempdir().unwrap();
+        let infra = tempfile::tempdir().unwrap();
+        let policy = Arc::new(PathPolicy::new(project.path()));
+        let frontmatter = format!("extra_paths:\n  - path: {}\n    write: true\n", infra.path().display());
+        let skill = skill(project.path(), &frontmatter);
+
+        let (confirmation, _) = setup(ConfirmResult::Denied);
+        let grants = grant_extra_paths(&skill, project.path(), &policy, &confirmation);
+        assert!(policy.check_read(&infra.path().join("x")).is_err());
+        assert_eq!(grants.denied.len(), 1);
+
+        // 書き込みは「常に許可」を出さず、毎回尋ねる
+        let (confirmation, asked) = setup(ConfirmResult::Approved);
+        for _ in 0..2 {
+            let _grants = grant_extra_paths(&skill, project.path(), &policy, &confirmation);
+            assert!(policy.check_write(&infra.path().join("x")).is_ok());
+        }
+        let asked = asked.asked.lock().unwrap();
+        assert_eq!(asked.len(), 2);
+        assert!(asked.iter().all(|(_, always)| !always));
+    }
+
+    #[tokio::test]
+    async fn test_reverts_after_mid_turn_failure_and_cancellation() {
+        let project = tempfile::tempdir().unwrap();
+        let infra = tempfile::tempdir().unwrap();
+        let policy = Arc::new(PathPolicy::new(project.path()));
+        let (confirmation, _) = setup(ConfirmResult::Approved);
+        let skill = skill(project.path(), &format!("extra_paths:\n  - {}\n", infra.path().display()));
+        let file = infra.path().join("main.tf");
+
+        let turn = async {
+            let _grants = grant_extra_paths(&skill, project.path(), &policy, &confirmation);
+            assert!(policy.check_read(&file).is_ok());
+            Err::<(), _>(anyhow::anyhow!("tool failed mid-turn"))
+        };
+        assert!(turn.await.is_err());
+        assert!(policy.check_read(&file).is_err());
+
+        // 完了前に捨てられた（Ctrl+C で中断した）ターン
+        let turn = async {
+            let _grants = grant_extra_paths(&skill, project.path(), &policy, &confirmation);
+            std::future::pending::<()>().await;
+        };
+        let _ = tokio::time::timeout(std::time::Duration::from_millis(10), turn).await;
+        assert!(policy.check_read(&file).is_err());
+    }
+}
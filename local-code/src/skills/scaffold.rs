@@ -0,0 +1,118 @@
+//! 新しいスキルの雛形（/skill-new）
+//!
+//! `[skills] custom_path`（未設定なら ~/.claude/skills）に `<name>/SKILL.md` を作る
+
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+/// 雛形を置くディレクトリ
+pub fn skills_dir(custom_path: Option<&str>) -> Option<PathBuf> {
+    match custom_path {
+        Some(path) => Some(PathBuf::from(path)),
+        None => dirs::home_dir().map(|home| home.join(".claude").join("skills")),
+    }
+}
+
+/// スキル名として使えるか（英小文字・数字・`-`・`_`、先頭は英数字）
+pub fn validate_name(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let valid_start = chars.next().is_some_and(|c| c.is_ascii_lowercase() || c.is_ascii_digit());
+    if !valid_start || !chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_') {
+        bail!("Invalid skill name '{}': use lowercase letters, digits, '-' and '_'", name);
+    }
+    Ok(())
+}
+
+/// SKILL.md の雛形
+pub fn template(name: &str) -> String {
+    format!(
+        r#"---
+# Invoked as /{name}
+name: {name}
+# One line shown by /skills (also the usage hint when args_required is set)
+description: Describe what this skill does
+# Phrases that suggest this skill when they appear in your message (case-insensitive)
+triggers: []
+#  - example phrase
+# Run without asking when a trigger matches
+auto: false
+# Fail with a usage hint when called without arguments
+args_required: false
+# Name of a skill whose content is included before this one
+# parent: other-skill
+# Tools the model may use while this skill runs (omit to allow the current mode's tools)
+# allowed-tools:
+#   - read
+#   - grep
+---
+
+# {name}
+
+Write the instructions for the model here.
+{{{{args}}}} is replaced with the text typed after /{name}; see the README for other placeholders.
+"#
+    )
+}
+
+/// `dir/<name>/SKILL.md` を作成してパスを返す（既にあれば上書きしない）
+pub fn create_skill(dir: &Path, name: &str) -> Result<PathBuf> {
+    validate_name(name)?;
+    let skill_dir = dir.join(name);
+    let path = skill_dir.join("SKILL.md");
+    if path.exists() {
+        bail!("Skill '{}' already exists at {}", name, path.display());
+    }
+    std::fs::create_dir_all(&skill_dir).with_context(|| format!("Failed to create {}", skill_dir.display()))?;
+    // 確認と作成の間に作られた場合も上書きしない
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    std::io::Write::write_all(&mut file, template(name).as_bytes())?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skills::Skill;
+
+    #[test]
+    fn test_skills_dir_prefers_custom_path() {
+        assert_eq!(skills_dir(Some("/opt/skills")), Some(PathBuf::from("/opt/skills")));
+        if let Some(home) = dirs::home_dir() {
+            assert_eq!(skills_dir(None), Some(home.join(".claude").join("skills")));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_created_template_parses() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = create_skill(dir.path(), "my-skill").unwrap();
+        assert_eq!(path, dir.path().join("my-skill").join("SKILL.md"));
+
+        let skill = Skill::load_from_file(&path).await.unwrap();
+        assert_eq!(skill.metadata.name, "my-skill");
+        assert!(skill.metadata.triggers.is_empty());
+        assert!(!skill.metadata.auto);
+        assert_eq!(skill.metadata.parent, None);
+        assert_eq!(skill.metadata.allowed_tools, None);
+        assert!(skill.content.contains("{{args}} is replaced with the text typed after /my-skill"));
+    }
+
+    #[test]
+    fn test_refuses_to_overwrite_and_bad_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = create_skill(dir.path(), "review").unwrap();
+        std::fs::write(&path, "edited").unwrap();
+
+        let err = create_skill(dir.path(), "review").unwrap_err();
+        assert!(err.to_string().contains("already exists"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "edited");
+
+        for name in ["", "My-Skill", "-x", "a/b", "../up", "with space"] {
+            assert!(create_skill(dir.path(), name).is_err(), "{}", name);
+        }
+    }
+}
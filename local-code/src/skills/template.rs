@@ -0,0 +1,88 @@
+//! SKILL.md のプレースホルダー置換
+//!
+//! `{{args}}`・`{{arg1}}`… などを値に置き換える。`\{{` はそのまま `{{` として残し、
+//! 値の分からないプレースホルダーは書かれたまま残す
+
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+
+fn placeholder_regex() -> &'static Regex {
+    static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+    PLACEHOLDER.get_or_init(|| Regex::new(r"\\\{\{|\{\{\s*([A-Za-z0-9_.]+)\s*\}\}").unwrap())
+}
+
+/// 置換結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rendered {
+    pub text: String,
+    /// 値が見つからず残したプレースホルダー名
+    pub unknown: Vec<String>,
+}
+
+/// `lookup` で値を引いてプレースホルダーを置き換える
+pub fn render(template: &str, lookup: impl Fn(&str) -> Option<String>) -> Rendered {
+    let mut unknown = Vec::new();
+    let text = placeholder_regex()
+        .replace_all(template, |caps: &Captures| {
+            let Some(name) = caps.get(1) else {
+                // `\{{` はエスケープ
+                return "{{".to_string();
+            };
+            match lookup(name.as_str()) {
+                Some(value) => value,
+                None => {
+                    unknown.push(name.as_str().to_string());
+                    caps[0].to_string()
+                }
+            }
+        })
+        .into_owned();
+    Rendered { text, unknown }
+}
+
+/// 引数を受け取るプレースホルダー（`{{args}}`・`{{argN}}`・`{{file}}`）を含むか
+pub fn uses_args(template: &str) -> bool {
+    placeholder_regex().captures_iter(template).any(|caps| {
+        caps.get(1).is_some_and(|name| {
+            let name = name.as_str();
+            name == "args" || name == "file" || name.strip_prefix("arg").is_some_and(|n| n.parse::<usize>().is_ok())
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "args" => Some("src/main.rs --strict".to_string()),
+            "arg1" => Some("src/main.rs".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_substitutes_known_placeholders() {
+        let rendered = render("Review {{args}} (file: {{ arg1 }})", lookup);
+        assert_eq!(rendered.text, "Review src/main.rs --strict (file: src/main.rs)");
+        assert!(rendered.unknown.is_empty());
+    }
+
+    #[test]
+    fn test_keeps_unknown_placeholders() {
+        let rendered = render("Hello {{name}} and {{args}}", lookup);
+        assert_eq!(rendered.text, "Hello {{name}} and src/main.rs --strict");
+        assert_eq!(rendered.unknown, vec!["name"]);
+    }
+
+    #[test]
+    fn test_escaped_braces_pass_through() {
+        let rendered = render(r"Write \{{args}} literally, then {{args}}. Plain {braces} and {{ not closed", lookup);
+        assert_eq!(rendered.text, "Write {{args}} literally, then src/main.rs --strict. Plain {braces} and {{ not closed");
+        assert!(rendered.unknown.is_empty());
+        assert!(!uses_args(r"only \{{args}}"));
+        assert!(uses_args("see {{arg2}}"));
+        assert!(!uses_args("{{cwd}} {{argument}}"));
+    }
+}
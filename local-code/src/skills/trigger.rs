@@ -1,6 +1,25 @@
 use super::loader::Skill;
 use super::registry::SkillRegistry;
 
+/// 検出されたスキルとそのスコア
+#[derive(Debug, Clone, Copy)]
+pub struct SkillMatch<'a> {
+    pub skill: &'a Skill,
+    pub score: i64,
+}
+
+/// `/skill-name` で明示的に呼ばれたときのスコア
+pub const EXPLICIT_SCORE: i64 = i64::MAX;
+
+/// トリガーのスコア
+///
+/// 優先度 × 1000 + マッチしたトリガー数 × 100 + 最も長いトリガーの文字数（上限 99）。
+/// マッチがなければ None
+pub fn score(skill: &Skill, hits: &[usize]) -> Option<i64> {
+    let longest = *hits.iter().max()?;
+    Some(skill.metadata.priority.saturating_mul(1000) + hits.len() as i64 * 100 + longest.min(99) as i64)
+}
+
 /// トリガー検出器
 pub struct TriggerDetector<'a> {
     registry: &'a SkillRegistry,
@@ -11,30 +30,30 @@ impl<'a> TriggerDetector<'a> {
         Self { registry }
     }
 
-    /// 入力テキストからトリガーにマッチするスキルを検出
-    pub fn detect(&self, input: &str) -> Vec<&Skill> {
+    /// 入力テキストからトリガーにマッチするスキルをスコアの高い順に検出
+    pub fn detect(&self, input: &str) -> Vec<SkillMatch<'a>> {
         let mut matches = Vec::new();
 
         // /skill-name 形式のコマンド検出
         if let Some(stripped) = input.strip_prefix('/') {
             let skill_name = stripped.split_whitespace().next().unwrap_or("");
             if let Some(skill) = self.registry.get(skill_name) {
-                matches.push(skill);
+                matches.push(SkillMatch { skill, score: EXPLICIT_SCORE });
             }
         }
 
         // トリガーフレーズ検出
         for skill in self.registry.list() {
-            if skill.matches_trigger(input)
-                && !matches.iter().any(|s| s.metadata.name == skill.metadata.name)
-            {
-                matches.push(skill);
+            if matches.iter().any(|m: &SkillMatch| m.skill.metadata.name == skill.metadata.name) {
+                continue;
+            }
+            if let Some(score) = score(skill, &skill.trigger_hits(input)) {
+                matches.push(SkillMatch { skill, score });
             }
         }
 
-        // 自動実行スキルを優先
-        matches.sort_by(|a, b| b.metadata.auto.cmp(&a.metadata.auto));
-
+        // 同点は名前順にして結果を安定させる
+        matches.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.skill.metadata.name.cmp(&b.skill.metadata.name)));
         matches
     }
 
@@ -90,6 +109,56 @@ mod tests {
         assert_eq!(TriggerDetector::extract_skill_name("not a command"), None);
     }
 
+    async fn registry(skills: &[&str]) -> SkillRegistry {
+        let dir = tempfile::tempdir().unwrap();
+        for (i, frontmatter) in skills.iter().enumerate() {
+            let skill_dir = dir.path().join(i.to_string());
+            std::fs::create_dir(&skill_dir).unwrap();
+            std::fs::write(skill_dir.join("SKILL.md"), format!("---\n{}---\nBody\n", frontmatter)).unwrap();
+        }
+        let mut registry = SkillRegistry::new();
+        registry.add_search_path(dir.path().to_path_buf());
+        registry.load_all().await.unwrap();
+        registry
+    }
+
+    fn ranked<'a>(registry: &'a SkillRegistry, input: &str) -> Vec<(&'a str, i64)> {
+        TriggerDetector::new(registry)
+            .detect(input)
+            .into_iter()
+            .map(|m| (m.skill.metadata.name.as_str(), m.score))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_detect_ranks_by_score() {
+        let registry = registry(&[
+            "name: tests\ntriggers: [test]\n",
+            "name: coverage\ntriggers: [test, coverage report]\n",
+            "name: release\ntriggers: [release notes]\n",
+            "name: notes\ntriggers: [notes]\n",
+        ])
+        .await;
+        // マッチ数が多いほど上、同数なら長いトリガーが上
+        assert_eq!(ranked(&registry, "test the coverage report"), vec![("coverage", 215), ("tests", 104)]);
+        assert_eq!(ranked(&registry, "write release notes"), vec![("release", 113), ("notes", 105)]);
+        // 部分一致では発火しない
+        assert!(ranked(&registry, "testing").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_detect_priority_and_explicit_command() {
+        let registry = registry(&[
+            "name: tests\ntriggers: [test]\n",
+            "name: tdd\ntriggers: ['/\\btests?\\b/']\npriority: 2\n",
+            "name: lint\ntriggers: [test, lint]\npriority: -1\n",
+        ])
+        .await;
+        assert_eq!(ranked(&registry, "lint and test"), vec![("tdd", 2104), ("tests", 104), ("lint", -796)]);
+        // /skill-name は常に最優先
+        assert_eq!(ranked(&registry, "/lint test")[0], ("lint", EXPLICIT_SCORE));
+    }
+
     #[test]
     fn test_extract_args() {
         assert_eq!(TriggerDetector::extract_args("/commit fix bug"), Some("fix bug"));
@@ -0,0 +1,445 @@
+//! バックグラウンドジョブ
+//!
+//! `run_in_background` で起動したコマンドを管理する。
+//! 出力はバッファに溜め、`bash_output` で前回以降の新しい出力を取得する
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::Command;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use super::policy::BashPolicy;
+use crate::tools::{Tool, ToolEffects, ToolResult};
+
+/// 未読出力の最大バイト数（超えた分は古いものから捨てる）
+const MAX_BUFFERED_BYTES: usize = 1024 * 1024;
+
+/// ジョブの状態
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobStatus {
+    Running,
+    Exited(i32),
+    Killed,
+    Failed(String),
+}
+
+impl std::fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobStatus::Running => write!(f, "running"),
+            JobStatus::Exited(code) => write!(f, "exited with code {}", code),
+            JobStatus::Killed => write!(f, "killed"),
+            JobStatus::Failed(e) => write!(f, "failed: {}", e),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct JobState {
+    /// 未読の出力
+    unread: String,
+    /// 捨てた未読出力のバイト数
+    dropped: usize,
+    status: JobStatus,
+}
+
+impl JobState {
+    fn append(&mut self, text: &str) {
+        self.unread.push_str(text);
+        if self.unread.len() > MAX_BUFFERED_BYTES {
+            let mut cut = self.unread.len() - MAX_BUFFERED_BYTES;
+            while !self.unread.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.unread.drain(..cut);
+            self.dropped += cut;
+        }
+    }
+}
+
+struct Job {
+    command: String,
+    state: Arc<Mutex<JobState>>,
+    kill_tx: Option<oneshot::Sender<()>>,
+    waiter: Option<JoinHandle<()>>,
+}
+
+/// 新しい出力の取得結果
+#[derive(Debug, Clone)]
+pub struct JobOutput {
+    pub output: String,
+    pub dropped: usize,
+    pub status: JobStatus,
+}
+
+/// バックグラウンドジョブのマネージャー
+#[derive(Default)]
+pub struct JobManager {
+    jobs: Mutex<BTreeMap<u32, Job>>,
+    next_id: AtomicU32,
+}
+
+impl JobManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// コマンドをバックグラウンドで起動してジョブIDを返す（環境変数は `policy` の許可リストだけ渡す）
+    pub fn spawn(&self, command: &str, working_dir: &Path, policy: &BashPolicy) -> Result<u32> {
+        let mut cmd = Command::new("bash");
+        policy.apply_env(&mut cmd);
+        let mut child = cmd
+            .arg("-c")
+            .arg(command)
+            .current_dir(working_dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // 子プロセスごと終了できるよう独立したプロセスグループにする
+            .process_group(0)
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let state = Arc::new(Mutex::new(JobState {
+            unread: String::new(),
+            dropped: 0,
+            status: JobStatus::Running,
+        }));
+        let stdout = tokio::spawn(pump(child.stdout.take(), Arc::clone(&state), ""));
+        let stderr = tokio::spawn(pump(child.stderr.take(), Arc::clone(&state), "[stderr] "));
+
+        let (kill_tx, kill_rx) = oneshot::channel::<()>();
+        let waiter = {
+            let state = Arc::clone(&state);
+            tokio::spawn(async move {
+                let status = tokio::select! {
+                    result = child.wait() => match result {
+                        Ok(status) => JobStatus::Exited(status.code().unwrap_or(-1)),
+                        Err(e) => JobStatus::Failed(e.to_string()),
+                    },
+                    _ = kill_rx => {
+                        kill_process_group(&mut child).await;
+                        JobStatus::Killed
+                    }
+                };
+                let _ = stdout.await;
+                let _ = stderr.await;
+                if let Ok(mut state) = state.lock() {
+                    state.status = status;
+                }
+            })
+        };
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        if let Ok(mut jobs) = self.jobs.lock() {
+            jobs.insert(
+                id,
+                Job {
+                    command: command.to_string(),
+                    state,
+                    kill_tx: Some(kill_tx),
+                    waiter: Some(waiter),
+                },
+            );
+        }
+        Ok(id)
+    }
+
+    /// 前回の取得以降の出力と状態を取得
+    pub fn read_new(&self, id: u32) -> Option<JobOutput> {
+        let jobs = self.jobs.lock().ok()?;
+        let job = jobs.get(&id)?;
+        let mut state = job.state.lock().ok()?;
+        Some(JobOutput {
+            output: std::mem::take(&mut state.unread),
+            dropped: std::mem::take(&mut state.dropped),
+            status: state.status.clone(),
+        })
+    }
+
+    /// ジョブを終了させる（終了済みならその状態を返す）
+    pub async fn kill(&self, id: u32) -> Option<JobStatus> {
+        let (kill_tx, waiter, state) = {
+            let mut jobs = self.jobs.lock().ok()?;
+            let job = jobs.get_mut(&id)?;
+            (job.kill_tx.take(), job.waiter.take(), Arc::clone(&job.state))
+        };
+        if let Some(tx) = kill_tx {
+            let _ = tx.send(());
+        }
+        if let Some(waiter) = waiter {
+            let _ = waiter.await;
+        }
+        let status = state.lock().ok()?.status.clone();
+        Some(status)
+    }
+
+    /// ジョブ一覧（ID, コマンド, 状態）
+    pub fn list(&self) -> Vec<(u32, String, JobStatus)> {
+        let Ok(jobs) = self.jobs.lock() else {
+            return Vec::new();
+        };
+        jobs.iter()
+            .map(|(id, job)| {
+                let status = job
+                    .state
+                    .lock()
+                    .map(|s| s.status.clone())
+                    .unwrap_or(JobStatus::Running);
+                (*id, job.command.clone(), status)
+            })
+            .collect()
+    }
+
+    /// 実行中のジョブをすべて終了させる（REPL終了時）
+    pub async fn shutdown(&self) {
+        let ids: Vec<u32> = self
+            .list()
+            .into_iter()
+            .filter(|(_, _, status)| *status == JobStatus::Running)
+            .map(|(id, _, _)| id)
+            .collect();
+        for id in ids {
+            self.kill(id).await;
+        }
+    }
+}
+
+/// 出力をジョブのバッファに流し込む
+async fn pump<R: AsyncRead + Unpin>(reader: Option<R>, state: Arc<Mutex<JobState>>, prefix: &'static str) {
+    let Some(mut reader) = reader else {
+        return;
+    };
+    let mut buf = [0u8; 4096];
+    let mut pending = Vec::new();
+    loop {
+        let n = match reader.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        pending.extend_from_slice(&buf[..n]);
+        // 行単位で追加する（stdout/stderr の行が混ざらないように）
+        if let Some(end) = pending.iter().rposition(|&b| b == b'\n') {
+            let chunk: Vec<u8> = pending.drain(..=end).collect();
+            append_lines(&state, prefix, &chunk);
+        }
+    }
+    if !pending.is_empty() {
+        pending.push(b'\n');
+        append_lines(&state, prefix, &pending);
+    }
+}
+
+fn append_lines(state: &Mutex<JobState>, prefix: &str, chunk: &[u8]) {
+    let text = String::from_utf8_lossy(chunk);
+    let text: String = text.lines().map(|line| format!("{}{}\n", prefix, line)).collect();
+    if let Ok(mut state) = state.lock() {
+        state.append(&text);
+    }
+}
+
+/// プロセスグループごと終了させる
+async fn kill_process_group(child: &mut tokio::process::Child) {
+    if let Some(pid) = child.id() {
+        let _ = Command::new("kill")
+            .args(["-TERM", "--", &format!("-{}", pid)])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await;
+    }
+    let _ = child.kill().await;
+}
+
+/// ジョブIDパラメータを取得
+fn job_id(params: &Value) -> Result<u32> {
+    params
+        .get("job_id")
+        .and_then(|v| v.as_u64())
+        .map(|id| id as u32)
+        .ok_or_else(|| anyhow::anyhow!("Missing job_id parameter"))
+}
+
+/// バックグラウンドジョブの出力取得ツール
+pub struct BashOutputTool {
+    jobs: Arc<JobManager>,
+}
+
+impl BashOutputTool {
+    pub fn new(jobs: Arc<JobManager>) -> Self {
+        Self { jobs }
+    }
+}
+
+#[async_trait]
+impl Tool for BashOutputTool {
+    fn name(&self) -> &str {
+        "bash_output"
+    }
+
+    fn description(&self) -> &str {
+        "Get new output from a background bash job since the last read"
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "job_id": {
+                    "type": "integer",
+                    "description": "Job id returned by bash with run_in_background"
+                }
+            },
+            "required": ["job_id"]
+        })
+    }
+
+    fn effects(&self) -> ToolEffects {
+        // 既存ジョブの出力を読むだけ
+        ToolEffects::ReadOnly
+    }
+
+    async fn execute(&self, params: Value) -> Result<ToolResult> {
+        let id = job_id(&params)?;
+        let Some(output) = self.jobs.read_new(id) else {
+            return Ok(ToolResult::failure(format!("No such job: {}", id)));
+        };
+
+        let mut text = format!("Job {}: {}\n", id, output.status);
+        if output.dropped > 0 {
+            text.push_str(&format!("({} bytes of older output dropped)\n", output.dropped));
+        }
+        if output.output.is_empty() {
+            text.push_str("(no new output)");
+        } else {
+            text.push_str(&output.output);
+        }
+        Ok(ToolResult::success(text))
+    }
+}
+
+/// バックグラウンドジョブの終了ツール
+pub struct BashKillTool {
+    jobs: Arc<JobManager>,
+}
+
+impl BashKillTool {
+    pub fn new(jobs: Arc<JobManager>) -> Self {
+        Self { jobs }
+    }
+}
+
+#[async_trait]
+impl Tool for BashKillTool {
+    fn name(&self) -> &str {
+        "bash_kill"
+    }
+
+    fn description(&self) -> &str {
+        "Terminate a background bash job"
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "job_id": {
+                    "type": "integer",
+                    "description": "Job id to terminate"
+                }
+            },
+            "required": ["job_id"]
+        })
+    }
+
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::Executes
+    }
+
+    async fn execute(&self, params: Value) -> Result<ToolResult> {
+        let id = job_id(&params)?;
+        match self.jobs.kill(id).await {
+            Some(status) => Ok(ToolResult::success(format!("Job {}: {}", id, status))),
+            None => Ok(ToolResult::failure(format!("No such job: {}", id))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    async fn wait_for_output(jobs: &JobManager, id: u32, expected: &str) -> String {
+        let mut collected = String::new();
+        for _ in 0..100 {
+            collected.push_str(&jobs.read_new(id).unwrap().output);
+            if collected.contains(expected) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        collected
+    }
+
+    #[tokio::test]
+    async fn test_poll_and_kill_running_job() {
+        let dir = tempdir().unwrap();
+        let jobs = Arc::new(JobManager::new());
+        let id = jobs.spawn("echo started; echo oops >&2; sleep 30", dir.path(), &BashPolicy::default()).unwrap();
+
+        let output = wait_for_output(&jobs, id, "[stderr] oops").await;
+        assert!(output.contains("started\n"));
+        assert!(output.contains("[stderr] oops\n"));
+        assert_eq!(jobs.read_new(id).unwrap().status, JobStatus::Running);
+        // 既に読んだ出力は返さない
+        assert!(jobs.read_new(id).unwrap().output.is_empty());
+
+        let result = BashKillTool::new(Arc::clone(&jobs))
+            .execute(json!({"job_id": id}))
+            .await
+            .unwrap();
+        assert_eq!(result.output, format!("Job {}: killed", id));
+
+        let result = BashOutputTool::new(Arc::clone(&jobs))
+            .execute(json!({"job_id": id}))
+            .await
+            .unwrap();
+        assert!(result.output.starts_with(&format!("Job {}: killed", id)));
+    }
+
+    #[tokio::test]
+    async fn test_finished_job_reports_exit_code() {
+        let dir = tempdir().unwrap();
+        let jobs = JobManager::new();
+        let id = jobs.spawn("echo done; exit 3", dir.path(), &BashPolicy::default()).unwrap();
+
+        let output = wait_for_output(&jobs, id, "done").await;
+        assert_eq!(output, "done\n");
+        assert_eq!(jobs.kill(id).await, Some(JobStatus::Exited(3)));
+        assert!(jobs.read_new(99).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_reaps_running_jobs() {
+        let dir = tempdir().unwrap();
+        let jobs = JobManager::new();
+        let first = jobs.spawn("sleep 30", dir.path(), &BashPolicy::default()).unwrap();
+        let second = jobs.spawn("sleep 30 | cat", dir.path(), &BashPolicy::default()).unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), jobs.shutdown()).await.unwrap();
+        assert!(jobs
+            .list()
+            .iter()
+            .all(|(id, _, status)| [first, second].contains(id) && *status == JobStatus::Killed));
+    }
+}
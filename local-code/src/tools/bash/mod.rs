@@ -1,3 +1,7 @@
 mod executor;
+mod jobs;
+mod policy;
 
-pub use executor::BashTool;
+pub use executor::{BashTool, CommandConfirmer};
+pub use policy::{BashPolicy, DEFAULT_DENIED_PATTERNS, DEFAULT_ENV_ALLOWLIST};
+pub use jobs::{BashKillTool, BashOutputTool, JobManager, JobStatus};
@@ -0,0 +1,352 @@
+//! ディスク容量の確認
+//!
+//! write/apply_patch は書き込む前に、1回の書き込みの上限（`tools.max_write_bytes`）と
+//! 書き込み先のファイルシステムの空き容量（`tools.min_free_bytes` を残せるか）を確かめ、
+//! 超える場合は書き込まずに理由を返す。空き容量は `DiskSpace` で取得する（テストでは差し替える）。
+
+use async_trait::async_trait;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::agent::status::{StatusProvider, SubsystemStatus};
+
+/// 1回の書き込みの上限の既定値（10MB）
+pub const DEFAULT_MAX_WRITE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// 書き込み後に残す空き容量の既定値（100MB）
+pub const DEFAULT_MIN_FREE_BYTES: u64 = 100 * 1024 * 1024;
+
+/// 空き容量の取得
+pub trait DiskSpace: Send + Sync {
+    /// パスを含むファイルシステムで使える空き容量（バイト）
+    fn available_bytes(&self, path: &Path) -> io::Result<u64>;
+}
+
+/// OS から空き容量を取得する（unix は statvfs、Windows は GetDiskFreeSpaceExW）
+///
+/// まだ存在しないパスは、存在する最も近い親ディレクトリで調べる
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemDiskSpace;
+
+impl DiskSpace for SystemDiskSpace {
+    fn available_bytes(&self, path: &Path) -> io::Result<u64> {
+        let path = std::path::absolute(path)?;
+        let existing = path
+            .ancestors()
+            .find(|p| p.exists())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no existing parent directory"))?;
+        available_bytes(existing)
+    }
+}
+
+#[cfg(unix)]
+fn available_bytes(path: &Path) -> io::Result<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: statvfs は有効な NUL 終端のパスと書き込み先の構造体だけを受け取る
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // 型はプラットフォームで異なる（macOS の f_bavail は u32）
+    #[allow(clippy::useless_conversion)]
+    let (blocks, block_size) = (u64::from(stat.f_bavail), u64::from(stat.f_frsize));
+    Ok(blocks.saturating_mul(block_size))
+}
+
+#[cfg(windows)]
+fn available_bytes(path: &Path) -> io::Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut available = 0u64;
+    // SAFETY: NUL 終端のワイド文字列と、書き込み先の u64 だけを渡す（不要な出力は null）
+    let ok = unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut available, std::ptr::null_mut(), std::ptr::null_mut()) };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(available)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn available_bytes(_path: &Path) -> io::Result<u64> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "free space is not available on this platform"))
+}
+
+/// 書き込み前の大きさと空き容量の確認
+#[derive(Clone)]
+pub struct WriteGuard {
+    /// 1回の書き込みの上限（0 なら無制限）
+    max_write_bytes: u64,
+    /// 書き込み後に残す空き容量
+    min_free_bytes: u64,
+    disk: Arc<dyn DiskSpace>,
+}
+
+impl WriteGuard {
+    pub fn new(max_write_bytes: u64, min_free_bytes: u64) -> Self {
+        Self {
+            max_write_bytes,
+            min_free_bytes,
+            disk: Arc::new(SystemDiskSpace),
+        }
+    }
+
+    /// 空き容量の取得元を差し替える
+    pub fn with_disk(mut self, disk: Arc<dyn DiskSpace>) -> Self {
+        self.disk = disk;
+        self
+    }
+
+    /// `old_len` バイトのファイルを `new_len` バイトで置き換えてよいか（だめなら理由）
+    ///
+    /// 空き容量は増える分だけを比べる。空き容量が取得できない場合は書き込みを止めない
+    pub fn check(&self, path: &Path, new_len: u64, old_len: u64) -> Result<(), String> {
+        if self.max_write_bytes > 0 && new_len > self.max_write_bytes {
+            return Err(format!(
+                "Refusing to write {}: {} exceeds the per-write limit of {} (tools.max_write_bytes). \
+                 Split the content into smaller files or raise the limit.",
+                path.display(),
+                format_size(new_len),
+                format_size(self.max_write_bytes)
+            ));
+        }
+        let growth = new_len.saturating_sub(old_len);
+        if growth == 0 {
+            return Ok(());
+        }
+        let available = match self.disk.available_bytes(path) {
+            Ok(available) => available,
+            Err(e) => {
+                tracing::warn!("Could not read free space for {}: {}", path.display(), e);
+                return Ok(());
+            }
+        };
+        if fits(available, growth, self.min_free_bytes) {
+            return Ok(());
+        }
+        Err(format!(
+            "Refusing to write {}: writing {} would leave {} free on its filesystem ({} available), \
+             below the safety margin of {} (tools.min_free_bytes). Free up disk space first.",
+            path.display(),
+            format_size(growth),
+            format_size(available.saturating_sub(growth)),
+            format_size(available),
+            format_size(self.min_free_bytes)
+        ))
+    }
+}
+
+impl Default for WriteGuard {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_WRITE_BYTES, DEFAULT_MIN_FREE_BYTES)
+    }
+}
+
+/// `growth` バイト増やしても `margin` バイト以上残るか
+fn fits(available: u64, growth: u64, margin: u64) -> bool {
+    available.checked_sub(margin).is_some_and(|usable| growth <= usable)
+}
+
+/// バイト数を読みやすく（1024 単位）
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// ディレクトリ以下のファイルの合計サイズ（読めないものは数えない）
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(t) if t.is_dir() => dir_size(&entry.path()),
+            Ok(t) if t.is_file() => entry.metadata().map(|m| m.len()).unwrap_or(0),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// `.local-code` ディレクトリの大きさと空き容量を報告（/status）
+pub struct DiskStatus {
+    /// 表示名とディレクトリ
+    dirs: Vec<(String, PathBuf)>,
+    min_free_bytes: u64,
+    disk: Arc<dyn DiskSpace>,
+}
+
+impl DiskStatus {
+    /// プロジェクトとホームの `.local-code` を対象にする
+    pub fn new(project_root: &Path, min_free_bytes: u64) -> Self {
+        let mut dirs = vec![("project .local-code".to_string(), project_root.join(".local-code"))];
+        if let Some(home) = dirs::home_dir() {
+            dirs.push(("~/.local-code".to_string(), home.join(".local-code")));
+        }
+        Self {
+            dirs,
+            min_free_bytes,
+            disk: Arc::new(SystemDiskSpace),
+        }
+    }
+
+    /// 空き容量の取得元を差し替える
+    pub fn with_disk(mut self, disk: Arc<dyn DiskSpace>) -> Self {
+        self.disk = disk;
+        self
+    }
+}
+
+#[async_trait]
+impl StatusProvider for DiskStatus {
+    async fn status(&self) -> SubsystemStatus {
+        let dirs = self.dirs.clone();
+        let sizes = tokio::task::spawn_blocking(move || {
+            dirs.into_iter()
+                .map(|(label, dir)| format!("{} {}", label, format_size(dir_size(&dir))))
+                .collect::<Vec<_>>()
+        })
+        .await
+        .unwrap_or_default();
+        let sizes = sizes.join(", ");
+
+        // プロジェクトのファイルシステムで調べる
+        let Some((_, project_dir)) = self.dirs.first() else {
+            return SubsystemStatus::disabled("disk", "nothing to report");
+        };
+        match self.disk.available_bytes(project_dir) {
+            Ok(available) if available < self.min_free_bytes => SubsystemStatus::degraded(
+                "disk",
+                format!("{}; only {} free", sizes, format_size(available)),
+            )
+            .with_hint(Some(format!(
+                "Writes are refused below {} free (tools.min_free_bytes); free up disk space",
+                format_size(self.min_free_bytes)
+            ))),
+            Ok(available) => SubsystemStatus::ok("disk", format!("{}; {} free", sizes, format_size(available))),
+            Err(e) => SubsystemStatus::degraded("disk", format!("{}; free space unknown ({})", sizes, e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::status::HealthState;
+
+    /// 決まった空き容量を返す
+    struct FixedDiskSpace(u64);
+
+    impl DiskSpace for FixedDiskSpace {
+        fn available_bytes(&self, _path: &Path) -> io::Result<u64> {
+            Ok(self.0)
+        }
+    }
+
+    struct FailingDiskSpace;
+
+    impl DiskSpace for FailingDiskSpace {
+        fn available_bytes(&self, _path: &Path) -> io::Result<u64> {
+            Err(io::Error::other("statvfs failed"))
+        }
+    }
+
+    const MB: u64 = 1024 * 1024;
+
+    fn guard(available: u64) -> WriteGuard {
+        WriteGuard::new(10 * MB, 100 * MB).with_disk(Arc::new(FixedDiskSpace(available)))
+    }
+
+    #[test]
+    fn test_margin_math() {
+        assert!(fits(200 * MB, 100 * MB, 100 * MB));
+        assert!(!fits(200 * MB, 100 * MB + 1, 100 * MB));
+        // 空き容量がもともと余裕分より少ない
+        assert!(!fits(50 * MB, 1, 100 * MB));
+        assert!(fits(u64::MAX, u64::MAX - 1, 1));
+    }
+
+    #[test]
+    fn test_check_compares_only_growth() {
+        let path = Path::new("out.bin");
+        assert!(guard(101 * MB).check(path, MB, 0).is_ok());
+        assert!(guard(101 * MB).check(path, 2 * MB, 0).is_err());
+        // 既存ファイルの置き換えは増える分だけ
+        assert!(guard(101 * MB).check(path, 2 * MB, MB + MB / 2).is_ok());
+        // 小さくなる書き込みは空きがなくても通す
+        assert!(guard(0).check(path, MB, 2 * MB).is_ok());
+    }
+
+    #[test]
+    fn test_error_messages() {
+        let path = Path::new("dump.json");
+        let too_big = guard(u64::MAX).check(path, 12 * MB, 0).unwrap_err();
+        assert_eq!(
+            too_big,
+            "Refusing to write dump.json: 12.0 MB exceeds the per-write limit of 10.0 MB (tools.max_write_bytes). \
+             Split the content into smaller files or raise the limit."
+        );
+
+        let no_space = guard(105 * MB).check(path, 6 * MB, 0).unwrap_err();
+        assert_eq!(
+            no_space,
+            "Refusing to write dump.json: writing 6.0 MB would leave 99.0 MB free on its filesystem (105.0 MB available), \
+             below the safety margin of 100.0 MB (tools.min_free_bytes). Free up disk space first."
+        );
+    }
+
+    #[test]
+    fn test_unlimited_and_unknown_free_space() {
+        let guard = WriteGuard::new(0, 100 * MB).with_disk(Arc::new(FailingDiskSpace));
+        assert!(guard.check(Path::new("big"), 1024 * MB, 0).is_ok());
+    }
+
+    #[test]
+    fn test_system_disk_space_uses_existing_parent() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("a/b/c.txt");
+        assert!(SystemDiskSpace.available_bytes(&missing).unwrap() > 0);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(10 * MB), "10.0 MB");
+        assert_eq!(format_size(3 * 1024 * MB), "3.0 GB");
+    }
+
+    #[tokio::test]
+    async fn test_disk_status() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join(".local-code/debug")).unwrap();
+        std::fs::write(dir.path().join(".local-code/debug/a.json"), vec![0u8; 2048]).unwrap();
+        let status = DiskStatus {
+            dirs: vec![("project .local-code".to_string(), dir.path().join(".local-code"))],
+            min_free_bytes: 100 * MB,
+            disk: Arc::new(FixedDiskSpace(5 * 1024 * MB)),
+        };
+        let report = status.status().await;
+        assert_eq!(report.state, HealthState::Ok);
+        assert_eq!(report.detail, "project .local-code 2.0 KB; 5.0 GB free");
+
+        let low = status.with_disk(Arc::new(FixedDiskSpace(MB))).status().await;
+        assert_eq!(low.state, HealthState::Degraded);
+        assert!(low.hint.unwrap().contains("tools.min_free_bytes"));
+    }
+}
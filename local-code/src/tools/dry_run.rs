@@ -0,0 +1,316 @@
+//! ドライラン（`--dry-run` / `/dryrun on`）
+//!
+//! 変更を伴うツール呼び出しを実行せず、各ツールの `Tool::simulate` で結果を模擬する。
+//! 模擬した書き込みはメモリ上のオーバーレイに記録し、同じセッションの読み取りにはその内容を見せる
+//! （モデルが「編集が反映されていない」と繰り返さないように）。オーバーレイにないパスは実際のファイルを読む。
+//! glob/grep などの検索はオーバーレイを見ない。
+
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use super::file::diff::unified_diff;
+use super::{Tool, ToolEffects, ToolResult};
+
+/// 模擬した結果の出力に付ける印
+pub const DRY_RUN_PREFIX: &str = "[dry-run]";
+
+/// 模擬した変更を記録するオーバーレイ（パス → 内容、削除は None）
+///
+/// 初めて触れたパスはディスクの内容を元にする（コピーオンライト）
+#[derive(Debug, Default)]
+pub struct Overlay {
+    files: Mutex<BTreeMap<PathBuf, Option<String>>>,
+    /// 模擬した git_add のパス
+    staged: Mutex<BTreeSet<String>>,
+    /// 実行しなかったコマンドなど（ファイル以外の変更）
+    actions: Mutex<Vec<String>>,
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 内容を読む（オーバーレイになければディスク）
+    pub fn read(&self, path: &Path) -> io::Result<String> {
+        match self.get(path) {
+            Some(Some(content)) => Ok(content),
+            Some(None) => Err(io::Error::new(io::ErrorKind::NotFound, "deleted in dry-run")),
+            None => std::fs::read_to_string(path),
+        }
+    }
+
+    /// オーバーレイ上の状態（触れていなければ None、削除済みなら Some(None)）
+    pub fn get(&self, path: &Path) -> Option<Option<String>> {
+        self.files.lock().ok()?.get(&key(path)).cloned()
+    }
+
+    /// 存在するか（オーバーレイを優先）
+    pub fn exists(&self, path: &Path) -> bool {
+        match self.get(path) {
+            Some(content) => content.is_some(),
+            None => path.exists(),
+        }
+    }
+
+    /// 書き込みを記録
+    pub fn write(&self, path: &Path, content: impl Into<String>) {
+        if let Ok(mut files) = self.files.lock() {
+            files.insert(key(path), Some(content.into()));
+        }
+    }
+
+    /// 削除を記録
+    pub fn remove(&self, path: &Path) {
+        if let Ok(mut files) = self.files.lock() {
+            files.insert(key(path), None);
+        }
+    }
+
+    /// git_add を記録
+    pub fn stage(&self, paths: impl IntoIterator<Item = String>) {
+        if let Ok(mut staged) = self.staged.lock() {
+            staged.extend(paths);
+        }
+    }
+
+    /// 模擬した git_add のパスを取り出す（コミットの模擬で空にする）
+    pub fn take_staged(&self) -> Vec<String> {
+        self.staged
+            .lock()
+            .map(|mut staged| std::mem::take(&mut *staged).into_iter().collect())
+            .unwrap_or_default()
+    }
+
+    /// ファイル以外の変更を記録
+    pub fn record(&self, action: impl Into<String>) {
+        if let Ok(mut actions) = self.actions.lock() {
+            actions.push(action.into());
+        }
+    }
+
+    /// 変更があるか
+    pub fn is_empty(&self) -> bool {
+        let files = self.files.lock().map(|f| f.is_empty()).unwrap_or(true);
+        let actions = self.actions.lock().map(|a| a.is_empty()).unwrap_or(true);
+        files && actions
+    }
+
+    /// 実際に行われていたはずの変更の一覧
+    pub fn report(&self) -> String {
+        let mut lines = Vec::new();
+        if let Ok(files) = self.files.lock() {
+            for (path, content) in files.iter() {
+                let disk = std::fs::read_to_string(path).ok();
+                let line = match (disk, content) {
+                    (None, Some(new)) => format!("  created  {} (+{})", path.display(), new.lines().count()),
+                    (Some(_), None) => format!("  deleted  {}", path.display()),
+                    (Some(old), Some(new)) => {
+                        let (added, removed) = line_counts(&unified_diff(&old, new, ""));
+                        if added == 0 && removed == 0 {
+                            continue;
+                        }
+                        format!("  modified {} (+{} -{})", path.display(), added, removed)
+                    }
+                    (None, None) => continue,
+                };
+                lines.push(line);
+            }
+        }
+        if let Ok(actions) = self.actions.lock() {
+            lines.extend(actions.iter().map(|a| format!("  {}", a)));
+        }
+        if lines.is_empty() {
+            "Dry run: nothing would have changed.".to_string()
+        } else {
+            format!("Dry run: these changes were not made:\n{}", lines.join("\n"))
+        }
+    }
+
+    /// 記録を消す
+    pub fn clear(&self) {
+        if let Ok(mut files) = self.files.lock() {
+            files.clear();
+        }
+        if let Ok(mut staged) = self.staged.lock() {
+            staged.clear();
+        }
+        if let Ok(mut actions) = self.actions.lock() {
+            actions.clear();
+        }
+    }
+}
+
+/// セッション全体のドライランの状態
+#[derive(Debug, Default)]
+pub struct DryRun {
+    enabled: AtomicBool,
+    overlay: Overlay,
+}
+
+impl DryRun {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled: AtomicBool::new(enabled),
+            overlay: Overlay::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// 有効・無効を切り替える（無効にしてもオーバーレイの記録は残す）
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn overlay(&self) -> &Overlay {
+        &self.overlay
+    }
+
+    /// ツールを実行する（有効時は模擬する）
+    ///
+    /// 模擬できない変更系のツールは実行せず、実行しなかったことだけを返す。
+    /// 読み取り系のツールはオーバーレイにないパスなら実際に実行する
+    pub async fn execute(&self, tool: &dyn Tool, params: Value) -> Result<ToolResult> {
+        if !self.is_enabled() {
+            return tool.execute(params).await;
+        }
+        if let Some(result) = tool.simulate(params.clone(), &self.overlay).await? {
+            return Ok(result);
+        }
+        if tool.call_effects(&params) == ToolEffects::ReadOnly {
+            return tool.execute(params).await;
+        }
+        let action = format!("would run {} {}", tool.name(), params);
+        self.overlay.record(action.clone());
+        Ok(ToolResult::success(format!("{} {}", DRY_RUN_PREFIX, capitalize(&action))))
+    }
+}
+
+/// オーバーレイのキー（相対パスは作業ディレクトリから）
+fn key(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// unified diff の追加・削除行数
+fn line_counts(diff: &str) -> (usize, usize) {
+    diff.lines()
+        .filter(|l| !l.starts_with("+++") && !l.starts_with("---"))
+        .fold((0, 0), |(added, removed), line| match line.chars().next() {
+            Some('+') => (added + 1, removed),
+            Some('-') => (added, removed + 1),
+            _ => (added, removed),
+        })
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::bash::BashTool;
+    use crate::tools::file::{ApplyPatchTool, EditTool, ReadTool, WriteTool};
+    use serde_json::json;
+
+    fn path_str(path: &Path) -> String {
+        path.to_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_reads_see_simulated_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("main.rs");
+        std::fs::write(&file, "fn main() {\n    old();\n}\n").unwrap();
+        let dry_run = DryRun::new(true);
+
+        let edit = dry_run
+            .execute(&EditTool::new(), json!({"file_path": path_str(&file), "old_string": "old()", "new_string": "new()"}))
+            .await
+            .unwrap();
+        assert!(edit.success, "{:?}", edit.error);
+        assert!(edit.output.starts_with(DRY_RUN_PREFIX));
+        assert!(edit.output.contains("+    new();"));
+
+        let created = dir.path().join("src/lib.rs");
+        let write = dry_run
+            .execute(&WriteTool::new(), json!({"file_path": path_str(&created), "content": "pub fn lib() {}\n"}))
+            .await
+            .unwrap();
+        assert!(write.output.contains("Would create"));
+
+        // 読み取りは模擬した内容を見る
+        let read = dry_run.execute(&ReadTool::new(), json!({"file_path": path_str(&file)})).await.unwrap();
+        assert!(read.output.contains("new();"), "{}", read.output);
+        let read = dry_run.execute(&ReadTool::new(), json!({"file_path": path_str(&created)})).await.unwrap();
+        assert!(read.output.contains("pub fn lib() {}"));
+
+        // 続けて編集すると模擬した内容が元になる
+        let again = dry_run
+            .execute(&EditTool::new(), json!({"file_path": path_str(&file), "old_string": "new()", "new_string": "newer()"}))
+            .await
+            .unwrap();
+        assert!(again.success, "{:?}", again.error);
+
+        // 実際のファイルは変わらない
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "fn main() {\n    old();\n}\n");
+        assert!(!created.exists());
+        assert!(!dir.path().join("src").exists());
+
+        let report = dry_run.overlay().report();
+        assert!(report.contains(&format!("modified {} (+1 -1)", file.display())), "{}", report);
+        assert!(report.contains(&format!("created  {} (+1)", created.display())));
+    }
+
+    #[tokio::test]
+    async fn test_patch_and_commands_are_not_performed() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "one\ntwo\n").unwrap();
+        let dry_run = DryRun::new(true);
+
+        let patch = "--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n one\n-two\n+three\n";
+        let result = dry_run
+            .execute(&ApplyPatchTool::new(dir.path()), json!({"patch": patch}))
+            .await
+            .unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert!(result.output.contains("Would apply patch to 1 file(s)"));
+        assert_eq!(dry_run.overlay().read(&dir.path().join("a.txt")).unwrap(), "one\nthree\n");
+
+        let marker = dir.path().join("ran");
+        let result = dry_run
+            .execute(&BashTool::new(), json!({"command": format!("touch {}", marker.display())}))
+            .await
+            .unwrap();
+        assert!(result.output.contains("Would run command"));
+        assert!(!marker.exists());
+
+        assert_eq!(std::fs::read_to_string(dir.path().join("a.txt")).unwrap(), "one\ntwo\n");
+        assert!(dry_run.overlay().report().contains("would run command: touch"));
+    }
+
+    #[tokio::test]
+    async fn test_disabled_runs_for_real() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        let dry_run = DryRun::new(false);
+        dry_run
+            .execute(&WriteTool::new(), json!({"file_path": path_str(&file), "content": "real\n"}))
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "real\n");
+        assert!(dry_run.overlay().is_empty());
+    }
+}
@@ -0,0 +1,3 @@
+mod tool;
+
+pub use tool::{validate_schema, ExternalTool};
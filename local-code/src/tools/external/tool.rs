@@ -0,0 +1,451 @@
+//! 外部コマンドツール
+//!
+//! 設定した実行ファイルをツールとして登録する簡易プロトコル。
+//! パラメータのJSONを標準入力に書き込み、標準出力から
+//! `{"success": bool, "output": string, "error": string, "data": any}` を1つ読み取る
+//! （`data` は任意）。
+//! `--describe` で起動するとツール自身が名前・説明・スキーマを返せる
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+
+use crate::config::ExternalToolConfig;
+use crate::tools::{Tool, ToolEffects, ToolResult};
+
+/// 説明用ハンドシェイクの引数
+const DESCRIBE_FLAG: &str = "--describe";
+
+/// ハンドシェイクのタイムアウト
+const DESCRIBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// エラー表示に含める stderr の最大バイト数
+const STDERR_LIMIT: usize = 4096;
+
+/// ツールの応答
+#[derive(Debug, Deserialize)]
+struct ExternalResponse {
+    success: bool,
+    #[serde(default)]
+    output: String,
+    #[serde(default)]
+    error: Option<String>,
+    /// 構造化された結果（任意、そのまま ToolResult.data になる）
+    #[serde(default)]
+    data: Option<serde_json::Value>,
+}
+
+/// --describe の応答
+#[derive(Debug, Default, Deserialize)]
+struct Description {
+    name: Option<String>,
+    description: Option<String>,
+    #[serde(alias = "schema")]
+    parameters: Option<Value>,
+}
+
+/// 外部コマンドツール
+#[derive(Debug)]
+pub struct ExternalTool {
+    name: String,
+    description: String,
+    schema: Value,
+    command: String,
+    args: Vec<String>,
+    working_dir: Option<PathBuf>,
+    timeout: Duration,
+    max_output_bytes: usize,
+}
+
+impl ExternalTool {
+    /// 設定から作成
+    ///
+    /// 名前・説明・スキーマのいずれかが設定にない場合は `--describe` で問い合わせる。
+    /// 設定の値が優先される
+    pub async fn from_config(config: &ExternalToolConfig, base_dir: &Path) -> Result<Self> {
+        let inline_schema = match (&config.schema, &config.schema_file) {
+            (Some(schema), _) => Some(schema.clone()),
+            (None, Some(file)) => Some(load_schema_file(&base_dir.join(file))?),
+            (None, None) => None,
+        };
+
+        let described = if config.name.is_none()
+            || config.description.is_none()
+            || inline_schema.is_none()
+        {
+            describe(&config.command, &config.args, base_dir).await?
+        } else {
+            Description::default()
+        };
+
+        let name = config
+            .name
+            .clone()
+            .or(described.name)
+            .ok_or_else(|| anyhow::anyhow!("External tool '{}' has no name", config.command))?;
+        let schema = inline_schema
+            .or(described.parameters)
+            .unwrap_or_else(|| serde_json::json!({"type": "object", "properties": {}}));
+        validate_schema(&schema).with_context(|| format!("Invalid schema for tool '{}'", name))?;
+
+        Ok(Self {
+            description: config
+                .description
+                .clone()
+                .or(described.description)
+                .unwrap_or_else(|| format!("External tool: {}", config.command)),
+            name,
+            schema,
+            command: config.command.clone(),
+            args: config.args.clone(),
+            working_dir: Some(base_dir.to_path_buf()),
+            timeout: Duration::from_secs(config.timeout_secs),
+            max_output_bytes: config.max_output_bytes,
+        })
+    }
+
+    /// 実行して応答をToolResultに変換
+    async fn run(&self, params: &Value) -> Result<ToolResult, String> {
+        let mut cmd = Command::new(&self.command);
+        cmd.args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(dir) = &self.working_dir {
+            cmd.current_dir(dir);
+        }
+
+        let mut child = cmd.spawn().map_err(|e| format!("failed to start '{}': {}", self.command, e))?;
+
+        let input = serde_json::to_vec(params).map_err(|e| e.to_string())?;
+        let mut stdin = child.stdin.take();
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        let cap = self.max_output_bytes;
+
+        let run = async {
+            // 入力を読まずに終了するスクリプトもあるため書き込みエラーは無視
+            if let Some(stdin) = stdin.as_mut() {
+                let _ = stdin.write_all(&input).await;
+                let _ = stdin.shutdown().await;
+            }
+            drop(stdin);
+            let (stdout, stderr) = tokio::join!(read_capped(stdout, cap), read_capped(stderr, STDERR_LIMIT));
+            let status = child.wait().await;
+            (status, stdout, stderr)
+        };
+
+        let (status, (stdout, stdout_overflow), (stderr, _)) = tokio::time::timeout(self.timeout, run)
+            .await
+            .map_err(|_| format!("timed out after {} seconds", self.timeout.as_secs()))?;
+        let status = status.map_err(|e| e.to_string())?;
+        let stderr = String::from_utf8_lossy(&stderr).trim().to_string();
+        let with_stderr = |message: String| {
+            if stderr.is_empty() {
+                message
+            } else {
+                format!("{}\n[stderr]\n{}", message, stderr)
+            }
+        };
+
+        if !status.success() {
+            return Err(with_stderr(format!(
+                "exited with code {}",
+                status.code().unwrap_or(-1)
+            )));
+        }
+        if stdout_overflow {
+            return Err(with_stderr(format!("output exceeded {} bytes", cap)));
+        }
+
+        let response: ExternalResponse = serde_json::from_slice(&stdout)
+            .map_err(|e| with_stderr(format!("returned malformed JSON: {}", e)))?;
+
+        Ok(if response.success {
+            ToolResult {
+                data: response.data,
+                ..ToolResult::success(response.output)
+            }
+        } else {
+            ToolResult {
+                success: false,
+                output: response.output,
+                error: Some(response.error.unwrap_or_else(|| "Unknown error".to_string())),
+                data: response.data,
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl Tool for ExternalTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters_schema(&self) -> Value {
+        self.schema.clone()
+    }
+
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::Executes
+    }
+
+    async fn execute(&self, params: Value) -> Result<ToolResult> {
+        match self.run(&params).await {
+            Ok(result) => Ok(result),
+            Err(message) => Ok(ToolResult::failure(format!(
+                "External tool '{}' {}",
+                self.name, message
+            ))),
+        }
+    }
+}
+
+/// 最大 cap バイトまで読み取る（超えた場合は true を返す）
+async fn read_capped<R: AsyncRead + Unpin>(reader: Option<R>, cap: usize) -> (Vec<u8>, bool) {
+    let Some(reader) = reader else {
+        return (Vec::new(), false);
+    };
+    let mut buf = Vec::new();
+    let mut limited = reader.take(cap as u64 + 1);
+    let _ = limited.read_to_end(&mut buf).await;
+    let overflow = buf.len() > cap;
+    buf.truncate(cap);
+
+    // パイプを閉じないように残りは読み捨てる
+    let mut rest = limited.into_inner();
+    let _ = tokio::io::copy(&mut rest, &mut tokio::io::sink()).await;
+    (buf, overflow)
+}
+
+/// `--describe` でツール情報を取得
+async fn describe(command: &str, args: &[String], dir: &Path) -> Result<Description> {
+    let output = tokio::time::timeout(
+        DESCRIBE_TIMEOUT,
+        Command::new(command)
+            .args(args)
+            .arg(DESCRIBE_FLAG)
+            .current_dir(dir)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .map_err(|_| anyhow::anyhow!("'{} {}' timed out", command, DESCRIBE_FLAG))?
+    .with_context(|| format!("Failed to start '{}'", command))?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "'{} {}' exited with code {}: {}",
+            command,
+            DESCRIBE_FLAG,
+            output.status.code().unwrap_or(-1),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    serde_json::from_slice(&output.stdout)
+        .with_context(|| format!("'{} {}' returned malformed JSON", command, DESCRIBE_FLAG))
+}
+
+fn load_schema_file(path: &Path) -> Result<Value> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read schema file: {}", path.display()))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse schema file: {}", path.display()))
+}
+
+/// ツールのパラメータスキーマとして妥当か検証
+///
+/// トップレベルが object 型で、required の各項目が properties に存在すること
+pub fn validate_schema(schema: &Value) -> Result<()> {
+    let object = schema
+        .as_object()
+        .ok_or_else(|| anyhow::anyhow!("schema must be a JSON object"))?;
+    if object.get("type").and_then(|v| v.as_str()) != Some("object") {
+        anyhow::bail!("schema must have \"type\": \"object\"");
+    }
+
+    let properties = match object.get("properties") {
+        None => None,
+        Some(Value::Object(props)) => Some(props),
+        Some(_) => anyhow::bail!("\"properties\" must be an object"),
+    };
+    if let Some(props) = properties {
+        for (key, prop) in props {
+            if !prop.is_object() {
+                anyhow::bail!("property '{}' must be an object", key);
+            }
+        }
+    }
+
+    match object.get("required") {
+        None => {}
+        Some(Value::Array(required)) => {
+            for item in required {
+                let key = item
+                    .as_str()
+                    .ok_or_else(|| anyhow::anyhow!("\"required\" must contain strings"))?;
+                if !properties.is_some_and(|p| p.contains_key(key)) {
+                    anyhow::bail!("required property '{}' is not defined in properties", key);
+                }
+            }
+        }
+        Some(_) => anyhow::bail!("\"required\" must be an array"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    fn write_script(dir: &Path, name: &str, body: &str) -> String {
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    fn config(command: String) -> ExternalToolConfig {
+        ExternalToolConfig {
+            name: Some("ext".to_string()),
+            command,
+            args: Vec::new(),
+            description: Some("test tool".to_string()),
+            schema: Some(json!({"type": "object", "properties": {"n": {"type": "integer"}}})),
+            schema_file: None,
+            timeout_secs: 5,
+            max_output_bytes: 1024,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_params_and_result() {
+        let dir = tempdir().unwrap();
+        // 標準入力で受け取ったパラメータを確認して返す
+        let script = write_script(
+            dir.path(),
+            "echo.sh",
+            r#"input=$(cat)
+case "$input" in
+  *'"n":3'*) echo '{"success": true, "output": "got 3"}' ;;
+  *) echo '{"success": false, "error": "unexpected input"}' ;;
+esac"#,
+        );
+        let tool = ExternalTool::from_config(&config(script), dir.path()).await.unwrap();
+
+        let result = tool.execute(json!({"n": 3})).await.unwrap();
+        assert!(result.success, "{:?}", result);
+        assert_eq!(result.output, "got 3");
+    }
+
+    #[tokio::test]
+    async fn test_describe_handshake() {
+        let dir = tempdir().unwrap();
+        let script = write_script(
+            dir.path(),
+            "described.sh",
+            r#"if [ "$1" = "--describe" ]; then
+  echo '{"name": "word_count", "description": "Count words", "parameters": {"type": "object", "properties": {"text": {"type": "string"}}, "required": ["text"]}}'
+  exit 0
+fi
+echo '{"success": true, "output": "2"}'"#,
+        );
+        let config = ExternalToolConfig {
+            name: None,
+            description: None,
+            schema: None,
+            ..config(script)
+        };
+
+        let tool = ExternalTool::from_config(&config, dir.path()).await.unwrap();
+        assert_eq!(tool.name(), "word_count");
+        assert_eq!(tool.description(), "Count words");
+        assert_eq!(tool.parameters_schema()["required"], json!(["text"]));
+        assert_eq!(tool.execute(json!({"text": "a b"})).await.unwrap().output, "2");
+    }
+
+    #[tokio::test]
+    async fn test_misbehaving_tools_fail_clearly() {
+        let dir = tempdir().unwrap();
+
+        let crash = write_script(dir.path(), "crash.sh", "echo 'boom' >&2; exit 3");
+        let tool = ExternalTool::from_config(&config(crash), dir.path()).await.unwrap();
+        let error = tool.execute(json!({})).await.unwrap().error.unwrap();
+        assert!(error.starts_with("External tool 'ext' exited with code 3"), "{}", error);
+        assert!(error.contains("boom"));
+
+        let garbage = write_script(dir.path(), "garbage.sh", "echo 'not json'");
+        let tool = ExternalTool::from_config(&config(garbage), dir.path()).await.unwrap();
+        let error = tool.execute(json!({})).await.unwrap().error.unwrap();
+        assert!(error.contains("returned malformed JSON"), "{}", error);
+
+        let slow = write_script(dir.path(), "slow.sh", "exec /bin/sleep 5");
+        let mut slow_config = config(slow);
+        slow_config.timeout_secs = 1;
+        let tool = ExternalTool::from_config(&slow_config, dir.path()).await.unwrap();
+        let error = tool.execute(json!({})).await.unwrap().error.unwrap();
+        assert_eq!(error, "External tool 'ext' timed out after 1 seconds");
+
+        let chatty = write_script(dir.path(), "chatty.sh", "head -c 5000 /dev/zero | tr '\\0' 'x'");
+        let tool = ExternalTool::from_config(&config(chatty), dir.path()).await.unwrap();
+        let error = tool.execute(json!({})).await.unwrap().error.unwrap();
+        assert!(error.contains("output exceeded 1024 bytes"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_tool_reported_failure() {
+        let dir = tempdir().unwrap();
+        let script = write_script(
+            dir.path(),
+            "fail.sh",
+            r#"echo '{"success": false, "error": "file not found"}'"#,
+        );
+        let tool = ExternalTool::from_config(&config(script), dir.path()).await.unwrap();
+        let result = tool.execute(json!({})).await.unwrap();
+        assert!(!result.success);
+        assert_eq!(result.error.as_deref(), Some("file not found"));
+    }
+
+    #[test]
+    fn test_validate_schema() {
+        assert!(validate_schema(&json!({"type": "object"})).is_ok());
+        assert!(validate_schema(&json!({
+            "type": "object",
+            "properties": {"a": {"type": "string"}},
+            "required": ["a"]
+        }))
+        .is_ok());
+        assert!(validate_schema(&json!([])).is_err());
+        assert!(validate_schema(&json!({"type": "string"})).is_err());
+        assert!(validate_schema(&json!({"type": "object", "properties": {"a": 1}})).is_err());
+        assert!(validate_schema(&json!({"type": "object", "required": ["missing"]})).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_schema_file_is_rejected() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("schema.json"), r#"{"type": "array"}"#).unwrap();
+        let mut config = config("true".to_string());
+        config.schema = None;
+        config.schema_file = Some("schema.json".to_string());
+
+        let error = ExternalTool::from_config(&config, dir.path()).await.unwrap_err();
+        assert!(format!("{:#}", error).contains("Invalid schema for tool 'ext'"));
+    }
+}
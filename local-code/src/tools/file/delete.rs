@@ -0,0 +1,352 @@
+//! ファイル・ディレクトリの削除
+//!
+//! 削除したものは消さずに `~/.local-code/trash/<日時>/` へ移し、間違えても戻せるようにする
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::observer::SharedFileObserver;
+use super::patch::resolve_project_path;
+use super::write::WriteConfirmer;
+use crate::tools::dry_run::DRY_RUN_PREFIX;
+use crate::tools::search::walk_files;
+use crate::tools::{Overlay, PathPolicy, Tool, ToolEffects, ToolResult, Workspace};
+
+/// `from` を `to` へ移す（別のファイルシステムならコピーしてから消す）
+pub(super) fn move_path(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    copy_recursive(from, to)?;
+    if from.is_dir() {
+        std::fs::remove_dir_all(from)
+    } else {
+        std::fs::remove_file(from)
+    }
+}
+
+fn copy_recursive(from: &Path, to: &Path) -> std::io::Result<()> {
+    let metadata = std::fs::symlink_metadata(from)?;
+    if metadata.is_dir() {
+        std::fs::create_dir_all(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else if metadata.file_type().is_symlink() {
+        let target = std::fs::read_link(from)?;
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(target, to)
+        }
+        #[cfg(not(unix))]
+        {
+            std::fs::copy(from, to).map(|_| drop(target))
+        }
+    } else {
+        std::fs::copy(from, to).map(|_| ())
+    }
+}
+
+/// 移動・削除で言語サーバーに伝えるファイル（ディレクトリなら配下のファイルすべて）
+pub(super) fn affected_files(path: &Path) -> Vec<PathBuf> {
+    if path.is_dir() {
+        walk_files(path, true).files
+    } else {
+        vec![path.to_path_buf()]
+    }
+}
+
+/// 削除ツール（ゴミ箱へ移す）
+pub struct DeleteTool {
+    /// プロジェクトルート（この外のものは削除しない）
+    project_root: PathBuf,
+    /// ゴミ箱のディレクトリ（この下に日時ごとのディレクトリを作る）
+    trash_dir: Option<PathBuf>,
+    /// 複数ルートのワークスペース（パスはラベルのルート、なければ現在のルートから）
+    workspace: Option<Arc<Workspace>>,
+    /// 削除したファイルの通知先（言語サーバーへの didClose）
+    observer: Option<SharedFileObserver>,
+    /// 削除前の確認（対話実行時のみ設定）
+    confirm: Option<WriteConfirmer>,
+    /// 扱えるパスの制限
+    policy: Option<Arc<PathPolicy>>,
+}
+
+impl DeleteTool {
+    pub fn new(project_root: impl Into<PathBuf>) -> Self {
+        Self {
+            project_root: project_root.into(),
+            trash_dir: Self::default_trash_dir(),
+            workspace: None,
+            observer: None,
+            confirm: None,
+            policy: None,
+        }
+    }
+
+    /// 既定のゴミ箱（~/.local-code/trash/）
+    pub fn default_trash_dir() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(".local-code").join("trash"))
+    }
+
+    /// ゴミ箱の場所を設定
+    pub fn with_trash_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.trash_dir = Some(dir.into());
+        self
+    }
+
+    /// ワークスペースのルートからパスを解決する
+    pub fn with_workspace(mut self, workspace: Arc<Workspace>) -> Self {
+        self.workspace = Some(workspace);
+        self
+    }
+
+    /// 削除したファイルを知らせる
+    pub fn with_observer(mut self, observer: SharedFileObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// 削除前に確認する
+    pub fn with_confirm(mut self, confirm: WriteConfirmer) -> Self {
+        self.confirm = Some(confirm);
+        self
+    }
+
+    /// 扱えるパスを制限する
+    pub fn with_path_policy(mut self, policy: Arc<PathPolicy>) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    fn resolve(&self, path: &str) -> std::result::Result<PathBuf, String> {
+        resolve_project_path(&self.project_root, self.workspace.as_deref(), self.policy.as_deref(), path)
+    }
+
+    /// プロジェクト（ワークスペースのルート）そのものか
+    fn is_root(&self, path: &Path) -> bool {
+        let mut roots = vec![self.project_root.clone()];
+        if let Some(workspace) = &self.workspace {
+            roots.extend(workspace.roots().iter().map(|r| r.path.clone()));
+        }
+        roots.iter().any(|root| root.canonicalize().is_ok_and(|root| root == path))
+    }
+
+    /// ゴミ箱内の移し先（プロジェクト内の相対パスを保つ）
+    fn trash_destination(&self, trash: &Path, path: &Path) -> PathBuf {
+        let relative = self
+            .project_root
+            .canonicalize()
+            .ok()
+            .and_then(|root| path.strip_prefix(&root).ok().map(Path::to_path_buf))
+            .unwrap_or_else(|| PathBuf::from(path.file_name().unwrap_or_default()));
+
+        let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+        let mut dir = trash.join(&stamp);
+        let mut n = 1;
+        while dir.join(&relative).exists() {
+            n += 1;
+            dir = trash.join(format!("{}-{}", stamp, n));
+        }
+        dir.join(relative)
+    }
+}
+
+#[async_trait]
+impl Tool for DeleteTool {
+    fn name(&self) -> &str {
+        "delete"
+    }
+
+    fn description(&self) -> &str {
+        "Delete a file, or a directory with recursive: true. It is moved to a trash directory (reported in the result) so it can be restored"
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "File or directory to delete (inside the project)"
+                },
+                "recursive": {
+                    "type": "boolean",
+                    "description": "Required to delete a directory and everything in it (default: false)"
+                }
+            },
+            "required": ["path"]
+        })
+    }
+
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::Writes
+    }
+
+    fn confirms_itself(&self) -> bool {
+        self.confirm.is_some()
+    }
+
+    async fn execute(&self, params: Value) -> Result<ToolResult> {
+        let display = params.get("path")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing path parameter"))?;
+        let recursive = params.get("recursive").and_then(|v| v.as_bool()).unwrap_or(false);
+
+        let path = match self.resolve(display) {
+            Ok(path) => path,
+            Err(e) => return Ok(ToolResult::failure(e)),
+        };
+        if std::fs::symlink_metadata(&path).is_err() {
+            return Ok(ToolResult::failure(format!("Not found: {}", display)));
+        }
+        if self.is_root(&path) {
+            return Ok(ToolResult::failure(format!("Refusing to delete the project root: {}", display)));
+        }
+        let is_dir = path.is_dir() && !path.is_symlink();
+        if is_dir && !recursive {
+            return Ok(ToolResult::failure(format!(
+                "{} is a directory; pass recursive: true to delete it and its contents",
+                display
+            )));
+        }
+        let Some(trash) = &self.trash_dir else {
+            return Ok(ToolResult::failure("No trash directory available (home directory not found)"));
+        };
+
+        let kind = if is_dir { "directory" } else { "file" };
+        if let Some(confirm) = &self.confirm {
+            let confirm = Arc::clone(confirm);
+            let files = if is_dir { affected_files(&path).len() } else { 1 };
+            let details = format!("Delete {} {} ({} files, moved to trash)", kind, display, files);
+            let approved = tokio::task::spawn_blocking(move || confirm(&details)).await?;
+            if !approved {
+                return Ok(ToolResult::failure(format!("Deletion of {} was denied by the user", display)));
+            }
+        }
+
+        let removed = affected_files(&path);
+        let destination = self.trash_destination(trash, &path);
+        if let Err(e) = move_path(&path, &destination) {
+            return Ok(ToolResult::failure(format!("Failed to move {} to trash: {}", display, e)));
+        }
+        if let Some(observer) = &self.observer {
+            for file in &removed {
+                observer.file_removed(file).await;
+            }
+        }
+        Ok(ToolResult::success(format!(
+            "Deleted {} {} (moved to trash: {})",
+            kind,
+            display,
+            destination.display()
+        )))
+    }
+
+    async fn simulate(&self, params: Value, overlay: &Overlay) -> Result<Option<ToolResult>> {
+        let Some(display) = params.get("path").and_then(|v| v.as_str()) else {
+            return Ok(None);
+        };
+        let path = match self.resolve(display) {
+            Ok(path) => path,
+            Err(e) => return Ok(Some(ToolResult::failure(e))),
+        };
+        if path.is_file() {
+            overlay.remove(&path);
+        }
+        overlay.record(format!("would delete {} (to trash)", display));
+        Ok(Some(ToolResult::success(format!("{} Would delete {} (to trash)", DRY_RUN_PREFIX, display))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_deleted_files_can_be_restored_from_trash() {
+        let project = tempdir().unwrap();
+        let trash = tempdir().unwrap();
+        std::fs::create_dir_all(project.path().join("src/old")).unwrap();
+        std::fs::write(project.path().join("src/old/mod.rs"), "pub mod a;\n").unwrap();
+        std::fs::write(project.path().join("src/old/a.rs"), "fn a() {}\n").unwrap();
+        let tool = DeleteTool::new(project.path()).with_trash_dir(trash.path());
+
+        let result = tool.execute(json!({"path": "src/old"})).await.unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("recursive: true"));
+
+        let result = tool.execute(json!({"path": "src/old", "recursive": true})).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert!(!project.path().join("src/old").exists());
+
+        // 報告された場所から元に戻せる
+        let location = result.output.rsplit_once("moved to trash: ").unwrap().1.trim_end_matches(')');
+        let location = PathBuf::from(location);
+        assert!(location.starts_with(trash.path()));
+        assert!(location.ends_with("src/old"));
+        std::fs::rename(&location, project.path().join("src/old")).unwrap();
+        assert_eq!(std::fs::read_to_string(project.path().join("src/old/a.rs")).unwrap(), "fn a() {}\n");
+    }
+
+    #[tokio::test]
+    async fn test_same_path_deleted_twice_keeps_both_copies() {
+        let project = tempdir().unwrap();
+        let trash = tempdir().unwrap();
+        let tool = DeleteTool::new(project.path()).with_trash_dir(trash.path());
+        for content in ["first", "second"] {
+            std::fs::write(project.path().join("notes.txt"), content).unwrap();
+            let result = tool.execute(json!({"path": "notes.txt"})).await.unwrap();
+            assert!(result.success, "{:?}", result.error);
+        }
+        let copies = walk_files(trash.path(), true).files;
+        assert_eq!(copies.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_refuses_outside_project_root_and_the_root_itself() {
+        let project = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        std::fs::write(outside.path().join("keep.txt"), "").unwrap();
+        let tool = DeleteTool::new(project.path()).with_trash_dir(tempdir().unwrap().path());
+
+        for path in [
+            outside.path().join("keep.txt").display().to_string(),
+            "../keep.txt".to_string(),
+        ] {
+            let result = tool.execute(json!({"path": path})).await.unwrap();
+            assert!(!result.success);
+        }
+        let result = tool.execute(json!({"path": outside.path().join("keep.txt").to_str().unwrap()})).await.unwrap();
+        assert!(result.error.unwrap().contains("Access denied by path policy"));
+        assert!(outside.path().join("keep.txt").exists());
+
+        let result = tool.execute(json!({"path": ".", "recursive": true})).await.unwrap();
+        assert!(result.error.unwrap().contains("project root"));
+        assert!(project.path().exists());
+    }
+
+    #[tokio::test]
+    async fn test_denied_confirmation_keeps_file() {
+        let project = tempdir().unwrap();
+        std::fs::write(project.path().join("a.txt"), "").unwrap();
+        let tool = DeleteTool::new(project.path())
+            .with_trash_dir(tempdir().unwrap().path())
+            .with_confirm(Arc::new(|details: &str| {
+                assert_eq!(details, "Delete file a.txt (1 files, moved to trash)");
+                false
+            }));
+        let result = tool.execute(json!({"path": "a.txt"})).await.unwrap();
+        assert!(result.error.unwrap().contains("denied by the user"));
+        assert!(project.path().join("a.txt").exists());
+    }
+}
@@ -0,0 +1,186 @@
+//! 行単位の差分生成
+//!
+//! 共通の先頭・末尾を除いた中間部分に LCS をとり、unified diff 形式で出力する
+
+/// ハンク前後に表示する文脈行数
+const CONTEXT_LINES: usize = 3;
+
+/// LCS を計算する中間部分の最大サイズ（行数の積）
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equal,
+    Remove,
+    Add,
+}
+
+/// 変更前後の内容から unified diff を生成（差分がなければ空文字列）
+pub fn unified_diff(old: &str, new: &str, path: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    if old_lines == new_lines {
+        return String::new();
+    }
+
+    let Some(ops) = diff_ops(&old_lines, &new_lines) else {
+        return format!(
+            "--- a/{path}\n+++ b/{path}\n(file too large to diff: {} lines -> {} lines)\n",
+            old_lines.len(),
+            new_lines.len()
+        );
+    };
+
+    let mut out = format!("--- a/{}\n+++ b/{}\n", path, path);
+    for hunk in hunks(&ops) {
+        render_hunk(&mut out, &ops[hunk.0..hunk.1], &old_lines, &new_lines);
+    }
+    out
+}
+
+/// 差分を最大 max_lines 行に切り詰める
+pub fn truncate_diff(diff: &str, max_lines: usize) -> String {
+    let total = diff.lines().count();
+    if total <= max_lines {
+        return diff.to_string();
+    }
+    let mut out: String = diff.lines().take(max_lines).collect::<Vec<_>>().join("\n");
+    out.push_str(&format!("\n... ({} more diff lines)\n", total - max_lines));
+    out
+}
+
+/// 編集操作列（old/new の位置付き）を求める
+fn diff_ops(old: &[&str], new: &[&str]) -> Option<Vec<(Op, usize, usize)>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+    if old_mid.len().saturating_mul(new_mid.len()) > MAX_LCS_CELLS {
+        return None;
+    }
+
+    // lcs[i][j] = old_mid[i..] と new_mid[j..] の LCS 長
+    let (n, m) = (old_mid.len(), new_mid.len());
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old_mid[i] == new_mid[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops: Vec<(Op, usize, usize)> = (0..prefix).map(|k| (Op::Equal, k, k)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old_mid[i] == new_mid[j] {
+            ops.push((Op::Equal, prefix + i, prefix + j));
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            // 同点なら削除を先に出す
+            ops.push((Op::Remove, prefix + i, prefix + j));
+            i += 1;
+        } else {
+            ops.push((Op::Add, prefix + i, prefix + j));
+            j += 1;
+        }
+    }
+    for k in 0..suffix {
+        ops.push((Op::Equal, old.len() - suffix + k, new.len() - suffix + k));
+    }
+    Some(ops)
+}
+
+/// 変更箇所を文脈行込みのハンク範囲（ops の添字）にまとめる
+fn hunks(ops: &[(Op, usize, usize)]) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (idx, (op, _, _)) in ops.iter().enumerate() {
+        if *op == Op::Equal {
+            continue;
+        }
+        let start = idx.saturating_sub(CONTEXT_LINES);
+        let end = (idx + 1 + CONTEXT_LINES).min(ops.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+    ranges
+}
+
+fn render_hunk(out: &mut String, ops: &[(Op, usize, usize)], old: &[&str], new: &[&str]) {
+    let old_count = ops.iter().filter(|(op, _, _)| *op != Op::Add).count();
+    let new_count = ops.iter().filter(|(op, _, _)| *op != Op::Remove).count();
+    let (_, old_start, new_start) = ops[0];
+    // 空範囲の開始行は直前の行番号で表す（diff の慣例）
+    let old_start = if old_count == 0 { old_start } else { old_start + 1 };
+    let new_start = if new_count == 0 { new_start } else { new_start + 1 };
+    out.push_str(&format!(
+        "@@ -{},{} +{},{} @@\n",
+        old_start, old_count, new_start, new_count
+    ));
+    for (op, i, j) in ops {
+        match op {
+            Op::Equal => out.push_str(&format!(" {}\n", old[*i])),
+            Op::Remove => out.push_str(&format!("-{}\n", old[*i])),
+            Op::Add => out.push_str(&format!("+{}\n", new[*j])),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_has_no_diff() {
+        assert_eq!(unified_diff("a\nb\n", "a\nb\n", "f.txt"), "");
+    }
+
+    #[test]
+    fn test_single_line_change_with_context() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n";
+        assert_eq!(
+            unified_diff(old, new, "n.txt"),
+            "--- a/n.txt\n+++ b/n.txt\n@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n"
+        );
+    }
+
+    #[test]
+    fn test_separate_hunks_and_pure_insertion() {
+        let old: String = (1..=20).map(|n| format!("{}\n", n)).collect();
+        let new: String = (1..=20)
+            .map(|n| match n {
+                2 => "two\n".to_string(),
+                18 => "18\ninserted\n".to_string(),
+                _ => format!("{}\n", n),
+            })
+            .collect();
+        let diff = unified_diff(&old, &new, "f");
+        assert_eq!(diff.matches("@@ -").count(), 2);
+        assert!(diff.contains("-2\n+two\n"));
+        assert!(diff.contains("@@ -16,5 +16,6 @@\n 16\n 17\n 18\n+inserted\n"));
+    }
+
+    #[test]
+    fn test_emptied_file() {
+        assert_eq!(unified_diff("a\nb\n", "", "f"), "--- a/f\n+++ b/f\n@@ -1,2 +0,0 @@\n-a\n-b\n");
+    }
+
+    #[test]
+    fn test_truncate_diff() {
+        let diff = "l1\nl2\nl3\nl4\n";
+        assert_eq!(truncate_diff(diff, 10), diff);
+        assert_eq!(truncate_diff(diff, 2), "l1\nl2\n... (2 more diff lines)\n");
+    }
+}
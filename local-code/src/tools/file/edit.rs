@@ -1,17 +1,47 @@
 use anyhow::Result;
 use async_trait::async_trait;
 use serde_json::{json, Value};
-use std::path::Path;
+use std::sync::Arc;
 use tokio::fs;
 
-use crate::tools::{Tool, ToolResult};
+use crate::tools::payload::FileChange;
+use super::diff::{truncate_diff, unified_diff};
+use super::observer::SharedFileObserver;
+use crate::tools::dry_run::DRY_RUN_PREFIX;
+use crate::tools::workspace::resolve_tool_path;
+use crate::tools::{Overlay, PathPolicy, Tool, ToolEffects, ToolResult, Workspace};
 
 /// ファイル編集ツール（部分置換）
-pub struct EditTool;
+pub struct EditTool {
+    /// 複数ルートのワークスペース（`ラベル:パス` を解決する）
+    workspace: Option<Arc<Workspace>>,
+    /// 書き換えたファイルの通知先（言語サーバーへの didChange）
+    observer: Option<SharedFileObserver>,
+    /// 扱えるパスの制限（プロジェクトルートの外を拒否する）
+    policy: Option<Arc<PathPolicy>>,
+}
 
 impl EditTool {
     pub fn new() -> Self {
-        Self
+        Self { workspace: None, observer: None, policy: None }
+    }
+
+    /// ワークスペースのルートからパスを解決する
+    pub fn with_workspace(mut self, workspace: Arc<Workspace>) -> Self {
+        self.workspace = Some(workspace);
+        self
+    }
+
+    /// 書き換えたファイルを知らせる
+    pub fn with_observer(mut self, observer: SharedFileObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// 扱えるパスを制限する
+    pub fn with_path_policy(mut self, policy: Arc<PathPolicy>) -> Self {
+        self.policy = Some(policy);
+        self
     }
 }
 
@@ -56,6 +86,10 @@ impl Tool for EditTool {
         })
     }
 
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::Writes
+    }
+
     async fn execute(&self, params: Value) -> Result<ToolResult> {
         let file_path = params.get("file_path")
             .and_then(|v| v.as_str())
@@ -73,7 +107,21 @@ impl Tool for EditTool {
             .and_then(|v| v.as_bool())
             .unwrap_or(false);
 
-        let path = Path::new(file_path);
+        if old_string.is_empty() {
+            return Ok(ToolResult::failure("old_string must not be empty"));
+        }
+
+        if old_string == new_string {
+            return Ok(ToolResult::failure(
+                "old_string and new_string are identical; nothing to change",
+            ));
+        }
+
+        let (path, file_path) = resolve_tool_path(self.workspace.as_deref(), file_path);
+        let (path, file_path) = (path.as_path(), file_path.as_str());
+        if let Some(Err(e)) = self.policy.as_ref().map(|p| p.check_write(path)) {
+            return Ok(ToolResult::failure(e));
+        }
 
         if !path.exists() {
             return Ok(ToolResult::failure(format!("File not found: {}", file_path)));
@@ -84,43 +132,187 @@ impl Tool for EditTool {
             Err(e) => return Ok(ToolResult::failure(format!("Failed to read file: {}", e))),
         };
 
-        // old_stringの出現回数をカウント
-        let occurrences = content.matches(old_string).count();
-
-        if occurrences == 0 {
-            return Ok(ToolResult::failure(format!(
-                "old_string not found in file: '{}'",
-                if old_string.len() > 50 {
-                    format!("{}...", &old_string[..50])
-                } else {
-                    old_string.to_string()
-                }
-            )));
-        }
-
-        if occurrences > 1 && !replace_all {
-            return Ok(ToolResult::failure(format!(
-                "old_string found {} times. Use replace_all: true to replace all, or provide a more unique string.",
-                occurrences
-            )));
-        }
-
-        let new_content = if replace_all {
-            content.replace(old_string, new_string)
-        } else {
-            content.replacen(old_string, new_string, 1)
+        let (new_content, replaced) = match replace(&content, old_string, new_string, replace_all) {
+            Ok(replaced) => replaced,
+            Err(e) => return Ok(ToolResult::failure(e)),
         };
 
         match fs::write(path, &new_content).await {
             Ok(_) => {
-                let replaced = if replace_all { occurrences } else { 1 };
+                if let Some(observer) = &self.observer {
+                    observer.file_written(path).await;
+                }
                 Ok(ToolResult::success(format!(
                     "Successfully replaced {} occurrence(s) in {}",
                     replaced,
                     file_path
-                )))
+                ))
+                .with_data(FileChange {
+                    path: file_path.to_string(),
+                    bytes_written: new_content.len(),
+                    created: false,
+                }))
             }
             Err(e) => Ok(ToolResult::failure(format!("Failed to write file: {}", e))),
         }
     }
+
+    async fn simulate(&self, params: Value, overlay: &Overlay) -> Result<Option<ToolResult>> {
+        let (Some(file_path), Some(old_string), Some(new_string)) = (
+            params.get("file_path").and_then(|v| v.as_str()),
+            params.get("old_string").and_then(|v| v.as_str()),
+            params.get("new_string").and_then(|v| v.as_str()),
+        ) else {
+            return Ok(None);
+        };
+        let replace_all = params.get("replace_all").and_then(|v| v.as_bool()).unwrap_or(false);
+        if old_string.is_empty() || old_string == new_string {
+            // 入力の誤りは実際の実行と同じメッセージで返す（何も変更しない）
+            return self.execute(params).await.map(Some);
+        }
+
+        let (path, file_path) = resolve_tool_path(self.workspace.as_deref(), file_path);
+        let (path, file_path) = (path.as_path(), file_path.as_str());
+        if let Some(Err(e)) = self.policy.as_ref().map(|p| p.check_write(path)) {
+            return Ok(Some(ToolResult::failure(e)));
+        }
+        let content = match overlay.read(path) {
+            Ok(content) => content,
+            Err(_) => return Ok(Some(ToolResult::failure(format!("File not found: {}", file_path)))),
+        };
+        let (new_content, replaced) = match replace(&content, old_string, new_string, replace_all) {
+            Ok(replaced) => replaced,
+            Err(e) => return Ok(Some(ToolResult::failure(e))),
+        };
+        let diff = truncate_diff(&unified_diff(&content, &new_content, file_path), 200);
+        overlay.write(path, new_content);
+        Ok(Some(ToolResult::success(format!(
+            "{} Would replace {} occurrence(s) in {}\n{}",
+            DRY_RUN_PREFIX, replaced, file_path, diff
+        ))))
+    }
+}
+
+/// old_string を置き換えた内容と置換数（見つからない・曖昧ならエラーメッセージ）
+fn replace(content: &str, old_string: &str, new_string: &str, replace_all: bool) -> Result<(String, usize), String> {
+    // old_stringの出現位置（行番号）を収集
+    let match_lines = match_line_numbers(content, old_string);
+    let occurrences = match_lines.len();
+
+    if occurrences == 0 {
+        return Err(format!("old_string not found in file: '{}'", preview(old_string)));
+    }
+
+    if occurrences > 1 && !replace_all {
+        let lines: Vec<String> = match_lines.iter().map(|n| n.to_string()).collect();
+        return Err(format!(
+            "old_string found {} times, provide more context or set replace_all (matches at lines: {})",
+            occurrences,
+            lines.join(", ")
+        ));
+    }
+
+    if replace_all {
+        Ok((content.replace(old_string, new_string), occurrences))
+    } else {
+        Ok((content.replacen(old_string, new_string, 1), 1))
+    }
+}
+
+/// 各出現箇所の開始行番号（1始まり）を返す
+fn match_line_numbers(content: &str, pattern: &str) -> Vec<usize> {
+    content
+        .match_indices(pattern)
+        .map(|(idx, _)| content[..idx].matches('\n').count() + 1)
+        .collect()
+}
+
+/// エラーメッセージ用に先頭50文字だけ表示
+fn preview(text: &str) -> String {
+    match text.char_indices().nth(50) {
+        Some((idx, _)) => format!("{}...", &text[..idx]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    async fn run_edit(content: &str, params: Value) -> (ToolResult, String) {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        std::fs::write(&path, content).unwrap();
+
+        let mut params = params;
+        params["file_path"] = json!(path.to_str().unwrap());
+        let result = EditTool::new().execute(params).await.unwrap();
+        let after = std::fs::read_to_string(&path).unwrap();
+        (result, after)
+    }
+
+    #[tokio::test]
+    async fn test_zero_matches() {
+        let (result, after) = run_edit("hello\n", json!({"old_string": "bye", "new_string": "x"})).await;
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("not found"));
+        assert_eq!(after, "hello\n");
+    }
+
+    #[tokio::test]
+    async fn test_single_match() {
+        let (result, after) = run_edit("a\nfoo\nb\n", json!({"old_string": "foo", "new_string": "bar"})).await;
+        assert!(result.success);
+        assert!(result.output.contains("replaced 1 occurrence"));
+        assert_eq!(after, "a\nbar\nb\n");
+        let data = result.data.unwrap();
+        assert_eq!(data["bytes_written"], json!(after.len()));
+        assert_eq!(data["created"], json!(false));
+    }
+
+    #[tokio::test]
+    async fn test_multiple_matches_without_replace_all() {
+        let (result, after) = run_edit("foo\nx\nfoo\n", json!({"old_string": "foo", "new_string": "bar"})).await;
+        assert!(!result.success);
+        let error = result.error.unwrap();
+        assert!(error.contains("old_string found 2 times, provide more context or set replace_all"));
+        assert!(error.contains("lines: 1, 3"));
+        assert_eq!(after, "foo\nx\nfoo\n");
+    }
+
+    #[tokio::test]
+    async fn test_multiple_matches_with_replace_all() {
+        let (result, after) = run_edit(
+            "foo\nx\nfoo\n",
+            json!({"old_string": "foo", "new_string": "bar", "replace_all": true}),
+        )
+        .await;
+        assert!(result.success);
+        assert!(result.output.contains("replaced 2 occurrence"));
+        assert_eq!(after, "bar\nx\nbar\n");
+    }
+
+    #[tokio::test]
+    async fn test_multiline_old_string() {
+        let content = "fn a() {\n    1\n}\n\nfn b() {\n    1\n}\n";
+        let (result, _) = run_edit(content, json!({"old_string": "{\n    1\n}", "new_string": "{ 2 }"})).await;
+        assert!(result.error.unwrap().contains("lines: 1, 5"));
+
+        let (result, after) = run_edit(
+            content,
+            json!({"old_string": "fn b() {\n    1\n}", "new_string": "fn b() {\n    2\n}"}),
+        )
+        .await;
+        assert!(result.success);
+        assert!(after.contains("fn b() {\n    2\n}"));
+        assert!(after.contains("fn a() {\n    1\n}"));
+    }
+
+    #[tokio::test]
+    async fn test_identical_strings_rejected() {
+        let (result, _) = run_edit("foo\n", json!({"old_string": "foo", "new_string": "foo"})).await;
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("identical"));
+    }
 }
@@ -1,7 +1,16 @@
 pub mod read;
 pub mod write;
 pub mod edit;
+pub mod patch;
+pub mod diff;
+pub mod observer;
+pub mod move_file;
+pub mod delete;
 
 pub use read::ReadTool;
-pub use write::WriteTool;
+pub use write::{WriteConfirmer, WriteTool};
 pub use edit::EditTool;
+pub use patch::ApplyPatchTool;
+pub use move_file::MoveTool;
+pub use delete::DeleteTool;
+pub use observer::{FileObserver, SharedFileObserver};
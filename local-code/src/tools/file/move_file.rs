@@ -0,0 +1,250 @@
+//! ファイル・ディレクトリの移動（名前の変更）
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::delete::{affected_files, move_path};
+use super::observer::SharedFileObserver;
+use super::patch::resolve_project_path;
+use super::write::WriteConfirmer;
+use crate::tools::dry_run::DRY_RUN_PREFIX;
+use crate::tools::{Overlay, PathPolicy, Tool, ToolEffects, ToolResult, Workspace};
+
+/// 移動ツール
+pub struct MoveTool {
+    /// プロジェクトルート（この外へ・外からは移動しない）
+    project_root: PathBuf,
+    /// 複数ルートのワークスペース（パスはラベルのルート、なければ現在のルートから）
+    workspace: Option<Arc<Workspace>>,
+    /// 移動したファイルの通知先（言語サーバーへの didClose と didOpen）
+    observer: Option<SharedFileObserver>,
+    /// 移動前の確認（対話実行時のみ設定）
+    confirm: Option<WriteConfirmer>,
+    /// 扱えるパスの制限
+    policy: Option<Arc<PathPolicy>>,
+}
+
+impl MoveTool {
+    pub fn new(project_root: impl Into<PathBuf>) -> Self {
+        Self {
+            project_root: project_root.into(),
+            workspace: None,
+            observer: None,
+            confirm: None,
+            policy: None,
+        }
+    }
+
+    /// ワークスペースのルートからパスを解決する
+    pub fn with_workspace(mut self, workspace: Arc<Workspace>) -> Self {
+        self.workspace = Some(workspace);
+        self
+    }
+
+    /// 移動したファイルを知らせる
+    pub fn with_observer(mut self, observer: SharedFileObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// 移動前に確認する
+    pub fn with_confirm(mut self, confirm: WriteConfirmer) -> Self {
+        self.confirm = Some(confirm);
+        self
+    }
+
+    /// 扱えるパスを制限する
+    pub fn with_path_policy(mut self, policy: Arc<PathPolicy>) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    fn resolve(&self, path: &str) -> std::result::Result<PathBuf, String> {
+        resolve_project_path(&self.project_root, self.workspace.as_deref(), self.policy.as_deref(), path)
+    }
+}
+
+#[async_trait]
+impl Tool for MoveTool {
+    fn name(&self) -> &str {
+        "move"
+    }
+
+    fn description(&self) -> &str {
+        "Move or rename a file or directory, creating parent directories. Fails if the destination exists unless overwrite is true"
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "from": {
+                    "type": "string",
+                    "description": "Existing file or directory"
+                },
+                "to": {
+                    "type": "string",
+                    "description": "New path (the full destination path, not a directory to move into)"
+                },
+                "overwrite": {
+                    "type": "boolean",
+                    "description": "Replace an existing destination file (default: false)"
+                }
+            },
+            "required": ["from", "to"]
+        })
+    }
+
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::Writes
+    }
+
+    fn confirms_itself(&self) -> bool {
+        self.confirm.is_some()
+    }
+
+    async fn execute(&self, params: Value) -> Result<ToolResult> {
+        let from_display = params.get("from")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing from parameter"))?;
+        let to_display = params.get("to")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing to parameter"))?;
+        let overwrite = params.get("overwrite").and_then(|v| v.as_bool()).unwrap_or(false);
+
+        let (from, to) = match self.resolve(from_display).and_then(|from| Ok((from, self.resolve(to_display)?))) {
+            Ok(paths) => paths,
+            Err(e) => return Ok(ToolResult::failure(e)),
+        };
+        if std::fs::symlink_metadata(&from).is_err() {
+            return Ok(ToolResult::failure(format!("Not found: {}", from_display)));
+        }
+        if from == to {
+            return Ok(ToolResult::failure(format!("{} and {} are the same path", from_display, to_display)));
+        }
+        if to.starts_with(&from) {
+            return Ok(ToolResult::failure(format!("Cannot move {} into itself", from_display)));
+        }
+        if to.exists() {
+            if to.is_dir() {
+                return Ok(ToolResult::failure(format!(
+                    "Destination {} is an existing directory; give the full new path instead",
+                    to_display
+                )));
+            }
+            if !overwrite {
+                return Ok(ToolResult::failure(format!(
+                    "Destination {} already exists; pass overwrite: true to replace it",
+                    to_display
+                )));
+            }
+        }
+
+        if let Some(confirm) = &self.confirm {
+            let confirm = Arc::clone(confirm);
+            let replaced = if to.exists() { " (replacing the existing file)" } else { "" };
+            let details = format!("Move {} to {}{}", from_display, to_display, replaced);
+            let approved = tokio::task::spawn_blocking(move || confirm(&details)).await?;
+            if !approved {
+                return Ok(ToolResult::failure(format!("Move of {} was denied by the user", from_display)));
+            }
+        }
+
+        let moved = affected_files(&from);
+        if let Err(e) = move_path(&from, &to) {
+            return Ok(ToolResult::failure(format!("Failed to move {} to {}: {}", from_display, to_display, e)));
+        }
+        if let Some(observer) = &self.observer {
+            for file in &moved {
+                observer.file_removed(file).await;
+                if let Ok(relative) = file.strip_prefix(&from) {
+                    observer.file_written(&to.join(relative)).await;
+                }
+            }
+        }
+        Ok(ToolResult::success(format!("Moved {} to {}", from_display, to_display)))
+    }
+
+    async fn simulate(&self, params: Value, overlay: &Overlay) -> Result<Option<ToolResult>> {
+        let (Some(from_display), Some(to_display)) = (
+            params.get("from").and_then(|v| v.as_str()),
+            params.get("to").and_then(|v| v.as_str()),
+        ) else {
+            return Ok(None);
+        };
+        let (from, to) = match self.resolve(from_display).and_then(|from| Ok((from, self.resolve(to_display)?))) {
+            Ok(paths) => paths,
+            Err(e) => return Ok(Some(ToolResult::failure(e))),
+        };
+        if let Ok(content) = overlay.read(&from) {
+            overlay.write(&to, content);
+            overlay.remove(&from);
+        }
+        overlay.record(format!("would move {} to {}", from_display, to_display));
+        Ok(Some(ToolResult::success(format!("{} Would move {} to {}", DRY_RUN_PREFIX, from_display, to_display))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_rename_creates_parent_directories() {
+        let project = tempdir().unwrap();
+        std::fs::write(project.path().join("old.rs"), "fn f() {}\n").unwrap();
+        let tool = MoveTool::new(project.path());
+
+        let result = tool.execute(json!({"from": "old.rs", "to": "src/util/new.rs"})).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert!(!project.path().join("old.rs").exists());
+        assert_eq!(std::fs::read_to_string(project.path().join("src/util/new.rs")).unwrap(), "fn f() {}\n");
+
+        let result = tool.execute(json!({"from": "src/util", "to": "src/helpers"})).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert!(project.path().join("src/helpers/new.rs").exists());
+    }
+
+    #[tokio::test]
+    async fn test_existing_destination_needs_overwrite() {
+        let project = tempdir().unwrap();
+        std::fs::write(project.path().join("a.txt"), "a").unwrap();
+        std::fs::write(project.path().join("b.txt"), "b").unwrap();
+        std::fs::create_dir(project.path().join("dir")).unwrap();
+        let tool = MoveTool::new(project.path());
+
+        let result = tool.execute(json!({"from": "a.txt", "to": "b.txt"})).await.unwrap();
+        assert!(result.error.unwrap().contains("overwrite: true"));
+        assert_eq!(std::fs::read_to_string(project.path().join("b.txt")).unwrap(), "b");
+
+        let result = tool.execute(json!({"from": "a.txt", "to": "dir", "overwrite": true})).await.unwrap();
+        assert!(result.error.unwrap().contains("existing directory"));
+
+        let result = tool.execute(json!({"from": "a.txt", "to": "b.txt", "overwrite": true})).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert!(!project.path().join("a.txt").exists());
+        assert_eq!(std::fs::read_to_string(project.path().join("b.txt")).unwrap(), "a");
+    }
+
+    #[tokio::test]
+    async fn test_refuses_paths_outside_project_root() {
+        let project = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        std::fs::write(project.path().join("a.txt"), "a").unwrap();
+        std::fs::write(outside.path().join("b.txt"), "b").unwrap();
+        let tool = MoveTool::new(project.path());
+
+        let result = tool.execute(json!({"from": "a.txt", "to": "../escaped.txt"})).await.unwrap();
+        assert!(result.error.unwrap().contains("Access denied by path policy"));
+        assert!(project.path().join("a.txt").exists());
+
+        let from = outside.path().join("b.txt");
+        let result = tool.execute(json!({"from": from.to_str().unwrap(), "to": "b.txt"})).await.unwrap();
+        assert!(result.error.unwrap().contains("Access denied by path policy"));
+        assert!(from.exists());
+    }
+}
@@ -0,0 +1,21 @@
+//! ファイルツールが書き換えたファイルの通知
+//!
+//! 言語サーバーは開いたファイルの内容を自分で持っているので、write・edit・apply_patch で
+//! 書き換えたら知らせないと、以降の LSP の結果が古い内容のままになる。
+
+use async_trait::async_trait;
+use std::path::Path;
+use std::sync::Arc;
+
+/// 書き換えたファイルの通知先
+#[async_trait]
+pub trait FileObserver: Send + Sync {
+    /// ファイルを作成・上書きした
+    async fn file_written(&self, path: &Path);
+
+    /// ファイルを削除した
+    async fn file_removed(&self, path: &Path);
+}
+
+/// 共有する通知先
+pub type SharedFileObserver = Arc<dyn FileObserver>;
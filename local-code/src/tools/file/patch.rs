@@ -0,0 +1,670 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs;
+
+use super::observer::SharedFileObserver;
+use crate::tools::payload::PatchedFile;
+use crate::tools::disk::WriteGuard;
+use crate::tools::dry_run::DRY_RUN_PREFIX;
+use crate::tools::{Overlay, PathPolicy, Tool, ToolEffects, ToolResult, Workspace};
+
+/// ハンクの位置ずれを許容する最大行数
+const MAX_OFFSET: usize = 50;
+
+/// unified diff 適用ツール
+pub struct ApplyPatchTool {
+    /// プロジェクトルート（この外のファイルは変更しない）
+    project_root: PathBuf,
+    /// 書き込みの大きさと空き容量の確認
+    guard: WriteGuard,
+    /// 複数ルートのワークスペース（パスはラベルのルート、なければ現在のルートから）
+    workspace: Option<Arc<Workspace>>,
+    /// 書き換え・削除したファイルの通知先（言語サーバーへの didChange・didClose）
+    observer: Option<SharedFileObserver>,
+    /// 扱えるパスの制限
+    policy: Option<Arc<PathPolicy>>,
+}
+
+impl ApplyPatchTool {
+    pub fn new(project_root: impl Into<PathBuf>) -> Self {
+        Self {
+            project_root: project_root.into(),
+            guard: WriteGuard::default(),
+            workspace: None,
+            observer: None,
+            policy: None,
+        }
+    }
+
+    /// 書き換え・削除したファイルを知らせる
+    pub fn with_observer(mut self, observer: SharedFileObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// ワークスペースのルートからパスを解決する
+    pub fn with_workspace(mut self, workspace: Arc<Workspace>) -> Self {
+        self.workspace = Some(workspace);
+        self
+    }
+
+    /// 扱えるパスを制限する
+    pub fn with_path_policy(mut self, policy: Arc<PathPolicy>) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// パッチ内のパスを解決（パスの制限の外は拒否する）
+    fn resolve(&self, display: &str) -> Result<PathBuf, String> {
+        resolve_project_path(&self.project_root, self.workspace.as_deref(), self.policy.as_deref(), display)
+    }
+
+    /// 出力用のパス（複数ルートでラベルがなければ現在のルートのラベルを付ける）
+    fn display(&self, path: &str) -> String {
+        match &self.workspace {
+            Some(workspace) if workspace.is_multi() && workspace.locate(path).is_none() => {
+                format!("{}:{}", workspace.current().label, path)
+            }
+            _ => path.to_string(),
+        }
+    }
+
+    /// 書き込みの上限と空き容量の余裕を設定
+    pub fn with_guard(mut self, guard: WriteGuard) -> Self {
+        self.guard = guard;
+        self
+    }
+}
+
+/// パッチ内の1ファイル分の変更
+#[derive(Debug, Clone, PartialEq)]
+struct FilePatch {
+    /// 変更前のパス（新規作成時は None）
+    old_path: Option<String>,
+    /// 変更後のパス（削除時は None）
+    new_path: Option<String>,
+    hunks: Vec<Hunk>,
+}
+
+impl FilePatch {
+    fn display_path(&self) -> &str {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or("<unknown>")
+    }
+}
+
+/// ハンク
+#[derive(Debug, Clone, PartialEq)]
+struct Hunk {
+    /// ヘッダー行（エラー表示用）
+    header: String,
+    /// 変更前の開始行（1始まり）
+    old_start: usize,
+    lines: Vec<HunkLine>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+impl Hunk {
+    /// 変更前に存在するはずの行
+    fn old_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|l| match l {
+                HunkLine::Context(s) | HunkLine::Remove(s) => Some(s.as_str()),
+                HunkLine::Add(_) => None,
+            })
+            .collect()
+    }
+
+    /// 変更後の行
+    fn new_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|l| match l {
+                HunkLine::Context(s) | HunkLine::Add(s) => Some(s.as_str()),
+                HunkLine::Remove(_) => None,
+            })
+            .collect()
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = self.header.clone();
+        for line in &self.lines {
+            let (prefix, s) = match line {
+                HunkLine::Context(s) => (' ', s),
+                HunkLine::Remove(s) => ('-', s),
+                HunkLine::Add(s) => ('+', s),
+            };
+            text.push('\n');
+            text.push(prefix);
+            text.push_str(s);
+        }
+        text
+    }
+}
+
+/// ファイルごとの適用結果
+#[derive(Debug, Clone, PartialEq)]
+struct AppliedFile {
+    path: PathBuf,
+    display: String,
+    /// 新しい内容（削除時は None）
+    content: Option<String>,
+    /// 新規作成か
+    created: bool,
+    added: usize,
+    removed: usize,
+}
+
+/// diff のパス表記（a/ b/ プレフィックス、タイムスタンプ）を除去
+fn parse_patch_path(raw: &str) -> Option<String> {
+    let path = raw.split('\t').next().unwrap_or(raw).trim();
+    if path == "/dev/null" {
+        return None;
+    }
+    let path = path
+        .strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path);
+    Some(path.to_string())
+}
+
+/// "@@ -10,4 +10,5 @@" から変更前の開始行を取得
+fn parse_hunk_header(line: &str) -> Option<usize> {
+    let rest = line.strip_prefix("@@")?.trim_start();
+    let old = rest.strip_prefix('-')?;
+    let start = old.split([',', ' ']).next()?;
+    start.parse().ok()
+}
+
+/// unified diff をパース
+fn parse_patch(patch: &str) -> Result<Vec<FilePatch>, String> {
+    let lines: Vec<&str> = patch.lines().collect();
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+
+        if line.starts_with("--- ") && lines.get(i + 1).is_some_and(|l| l.starts_with("+++ ")) {
+            files.push(FilePatch {
+                old_path: parse_patch_path(&line[4..]),
+                new_path: parse_patch_path(&lines[i + 1][4..]),
+                hunks: Vec::new(),
+            });
+            i += 2;
+            continue;
+        }
+
+        if line.starts_with("@@") {
+            let file = files
+                .last_mut()
+                .ok_or_else(|| format!("Hunk without file header: {}", line))?;
+            let old_start = parse_hunk_header(line)
+                .ok_or_else(|| format!("Invalid hunk header: {}", line))?;
+
+            let mut hunk = Hunk {
+                header: line.to_string(),
+                old_start,
+                lines: Vec::new(),
+            };
+            i += 1;
+
+            while i < lines.len() {
+                let body = lines[i];
+                if body.starts_with("@@")
+                    || body.starts_with("diff ")
+                    || (body.starts_with("--- ") && lines.get(i + 1).is_some_and(|l| l.starts_with("+++ ")))
+                {
+                    break;
+                }
+                match body.chars().next() {
+                    Some(' ') => hunk.lines.push(HunkLine::Context(body[1..].to_string())),
+                    Some('-') => hunk.lines.push(HunkLine::Remove(body[1..].to_string())),
+                    Some('+') => hunk.lines.push(HunkLine::Add(body[1..].to_string())),
+                    // "\ No newline at end of file"
+                    Some('\\') => {}
+                    // 先頭の空白が落ちた空のコンテキスト行
+                    None => hunk.lines.push(HunkLine::Context(String::new())),
+                    Some(_) => break,
+                }
+                i += 1;
+            }
+
+            file.hunks.push(hunk);
+            continue;
+        }
+
+        // diff --git / index 行などは読み飛ばす
+        i += 1;
+    }
+
+    if files.is_empty() {
+        return Err("No file headers (--- / +++) found in patch".to_string());
+    }
+    Ok(files)
+}
+
+/// 行列が一致するか（行末の空白は無視）
+fn lines_match(content: &[String], at: usize, expected: &[&str]) -> bool {
+    at + expected.len() <= content.len()
+        && expected
+            .iter()
+            .zip(&content[at..at + expected.len()])
+            .all(|(e, c)| e.trim_end() == c.trim_end())
+}
+
+/// 期待位置から近い順に一致箇所を探す
+fn find_hunk(content: &[String], expected: &[&str], hint: usize, min: usize) -> Option<usize> {
+    let hint = hint.max(min);
+    for delta in 0..=MAX_OFFSET {
+        if hint + delta <= content.len() && lines_match(content, hint + delta, expected) {
+            return Some(hint + delta);
+        }
+        if delta > 0 && hint >= min + delta && lines_match(content, hint - delta, expected) {
+            return Some(hint - delta);
+        }
+    }
+    None
+}
+
+/// ハンクを順に適用した内容と追加/削除行数を返す
+fn apply_hunks(original: &str, file: &FilePatch) -> Result<(String, usize, usize), String> {
+    let mut content: Vec<String> = original.lines().map(str::to_string).collect();
+    let trailing_newline = original.is_empty() || original.ends_with('\n');
+    let mut offset: isize = 0;
+    let mut min = 0;
+    let mut added = 0;
+    let mut removed = 0;
+
+    for (index, hunk) in file.hunks.iter().enumerate() {
+        let old_lines = hunk.old_lines();
+        let new_lines = hunk.new_lines();
+        let expected = (hunk.old_start.saturating_sub(1) as isize + offset).max(0) as usize;
+        // 新規ファイルへの追加など、変更前の行が空のハンク
+        let expected = if old_lines.is_empty() && hunk.old_start == 0 { 0 } else { expected };
+
+        let at = find_hunk(&content, &old_lines, expected, min).ok_or_else(|| {
+            format!(
+                "Hunk #{} in {} failed: context does not match\n{}",
+                index + 1,
+                file.display_path(),
+                hunk.to_text()
+            )
+        })?;
+
+        content.splice(at..at + old_lines.len(), new_lines.iter().map(|s| s.to_string()));
+        offset += new_lines.len() as isize - old_lines.len() as isize;
+        min = at + new_lines.len();
+        added += hunk.lines.iter().filter(|l| matches!(l, HunkLine::Add(_))).count();
+        removed += hunk.lines.iter().filter(|l| matches!(l, HunkLine::Remove(_))).count();
+    }
+
+    let mut result = content.join("\n");
+    if trailing_newline && !result.is_empty() {
+        result.push('\n');
+    }
+    Ok((result, added, removed))
+}
+
+/// `..` や `.` を字句的に解決
+fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                result.pop();
+            }
+            Component::CurDir => {}
+            other => result.push(other),
+        }
+    }
+    result
+}
+
+/// ツールに渡されたパスを解決し、書き込んでよいかを PathPolicy で確かめる
+///
+/// ラベル付きのパスはそのルートから、ラベルのない相対パスは現在のルート（なければプロジェクトルート）から解決する。
+/// ラベル付きのパスがそのルートの外を指すことは許さない。
+/// `policy` が未設定なら、プロジェクトルートとワークスペースの各ルートの下だけを許可する
+pub(super) fn resolve_project_path(
+    project_root: &Path,
+    workspace: Option<&Workspace>,
+    policy: Option<&PathPolicy>,
+    path: &str,
+) -> Result<PathBuf, String> {
+    let joined = match workspace.and_then(|w| w.locate(path)) {
+        Some((root, rest)) => {
+            let joined = normalize(&root.path.join(rest));
+            if !joined.starts_with(normalize(&root.path)) {
+                return Err(format!("Refusing to modify {}: the path leaves the '{}' root", path, root.label));
+            }
+            joined
+        }
+        None => {
+            let base = workspace.map_or(project_root, |w| w.current().path.as_path());
+            normalize(&base.join(path))
+        }
+    };
+    match policy {
+        Some(policy) => policy.check_write(&joined),
+        None => PathPolicy::new(project_root)
+            .with_allowed_paths(workspace.into_iter().flat_map(|w| w.roots().iter().map(|r| r.path.clone())))
+            .check_write(&joined),
+    }
+}
+
+impl ApplyPatchTool {
+    /// パッチを検証し、全ファイルの適用結果を計算（書き込みは行わない）
+    async fn prepare(&self, patch: &str) -> Result<Vec<AppliedFile>, String> {
+        self.prepare_with(patch, |path| std::fs::read_to_string(path), |path| path.exists())
+    }
+
+    /// 読み取り元を指定して適用結果を計算（ドライランではオーバーレイから読む）
+    fn prepare_with(
+        &self,
+        patch: &str,
+        read: impl Fn(&Path) -> std::io::Result<String>,
+        exists: impl Fn(&Path) -> bool,
+    ) -> Result<Vec<AppliedFile>, String> {
+        let files = parse_patch(patch)?;
+        let mut applied = Vec::new();
+
+        for file in &files {
+            let path = self.resolve(file.display_path())?;
+            let display = self.display(file.display_path());
+
+            let original = match &file.old_path {
+                Some(_) => read(&path).map_err(|e| format!("Failed to read {}: {}", display, e))?,
+                None => {
+                    if exists(&path) {
+                        return Err(format!("Cannot create {}: file already exists", display));
+                    }
+                    String::new()
+                }
+            };
+
+            let (content, added, removed) = apply_hunks(&original, file)?;
+            if file.new_path.is_some() {
+                self.guard.check(&path, content.len() as u64, original.len() as u64)?;
+            }
+            applied.push(AppliedFile {
+                path,
+                display,
+                content: file.new_path.as_ref().map(|_| content),
+                created: file.old_path.is_none(),
+                added,
+                removed,
+            });
+        }
+
+        Ok(applied)
+    }
+}
+
+#[async_trait]
+impl Tool for ApplyPatchTool {
+    fn name(&self) -> &str {
+        "apply_patch"
+    }
+
+    fn description(&self) -> &str {
+        "Apply a unified diff patch (may contain multiple files) to files in the project"
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "patch": {
+                    "type": "string",
+                    "description": "The patch in unified diff format (--- a/file, +++ b/file, @@ hunks)"
+                }
+            },
+            "required": ["patch"]
+        })
+    }
+
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::Writes
+    }
+
+    async fn execute(&self, params: Value) -> Result<ToolResult> {
+        let patch = params.get("patch")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing patch parameter"))?;
+
+        // 全ハンクを検証してから書き込む（途中で失敗しても部分適用しない）
+        let applied = match self.prepare(patch).await {
+            Ok(applied) => applied,
+            Err(e) => return Ok(ToolResult::failure(e)),
+        };
+
+        let mut summary = Vec::new();
+        let mut payload = Vec::new();
+        for file in &applied {
+            let result = match &file.content {
+                Some(content) => {
+                    if let Some(parent) = file.path.parent() {
+                        fs::create_dir_all(parent).await?;
+                    }
+                    fs::write(&file.path, content).await
+                }
+                None => fs::remove_file(&file.path).await,
+            };
+            if let Err(e) = result {
+                return Ok(ToolResult::failure(format!(
+                    "Failed to write {}: {}",
+                    file.display, e
+                )));
+            }
+            if let Some(observer) = &self.observer {
+                match file.content {
+                    Some(_) => observer.file_written(&file.path).await,
+                    None => observer.file_removed(&file.path).await,
+                }
+            }
+            let action = if file.content.is_none() { " (deleted)" } else { "" };
+            summary.push(format!("{}: +{} -{}{}", file.display, file.added, file.removed, action));
+            payload.push(PatchedFile {
+                path: file.display.clone(),
+                bytes_written: file.content.as_ref().map_or(0, |c| c.len()),
+                created: file.created,
+                deleted: file.content.is_none(),
+                added: file.added,
+                removed: file.removed,
+            });
+        }
+
+        Ok(ToolResult::success(format!(
+            "Applied patch to {} file(s)\n{}",
+            applied.len(),
+            summary.join("\n")
+        ))
+        .with_data(json!({ "files": payload })))
+    }
+
+    async fn simulate(&self, params: Value, overlay: &Overlay) -> Result<Option<ToolResult>> {
+        let Some(patch) = params.get("patch").and_then(|v| v.as_str()) else {
+            return Ok(None);
+        };
+        let applied = match self.prepare_with(patch, |path| overlay.read(path), |path| overlay.exists(path)) {
+            Ok(applied) => applied,
+            Err(e) => return Ok(Some(ToolResult::failure(e))),
+        };
+
+        let mut summary = Vec::new();
+        for file in &applied {
+            match &file.content {
+                Some(content) => overlay.write(&file.path, content.clone()),
+                None => overlay.remove(&file.path),
+            }
+            let action = if file.content.is_none() { " (deleted)" } else { "" };
+            summary.push(format!("{}: +{} -{}{}", file.display, file.added, file.removed, action));
+        }
+        Ok(Some(ToolResult::success(format!(
+            "{} Would apply patch to {} file(s)\n{}",
+            DRY_RUN_PREFIX,
+            applied.len(),
+            summary.join("\n")
+        ))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const ORIGINAL: &str = "fn main() {\n    let a = 1;\n    let b = 2;\n    println!(\"{}\", a + b);\n}\n";
+
+    const CLEAN_PATCH: &str = "\
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -1,5 +1,6 @@
+ fn main() {
+     let a = 1;
+-    let b = 2;
++    let b = 3;
++    let c = 4;
+     println!(\"{}\", a + b);
+ }
+";
+
+    const MULTI_FILE_PATCH: &str = "\
+diff --git a/src/main.rs b/src/main.rs
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -2,1 +2,1 @@
+-    let a = 1;
++    let a = 10;
+--- /dev/null
++++ b/src/new.rs
+@@ -0,0 +1,2 @@
++pub fn added() {}
++pub fn another() {}
+";
+
+    const REJECTED_PATCH: &str = "\
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -1,3 +1,3 @@
+ fn main() {
+-    let x = 1;
++    let x = 2;
+     let b = 2;
+";
+
+    async fn setup(content: &str) -> (tempfile::TempDir, ApplyPatchTool) {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/main.rs"), content).unwrap();
+        let tool = ApplyPatchTool::new(dir.path());
+        (dir, tool)
+    }
+
+    #[tokio::test]
+    async fn test_clean_apply() {
+        let (dir, tool) = setup(ORIGINAL).await;
+        let result = tool.execute(json!({"patch": CLEAN_PATCH})).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert!(result.output.contains("src/main.rs: +2 -1"));
+
+        let after = std::fs::read_to_string(dir.path().join("src/main.rs")).unwrap();
+        assert_eq!(after, "fn main() {\n    let a = 1;\n    let b = 3;\n    let c = 4;\n    println!(\"{}\", a + b);\n}\n");
+    }
+
+    #[tokio::test]
+    async fn test_offset_apply() {
+        // 先頭に行が追加されてハンクの位置がずれている
+        let shifted = format!("// header\n// comment\n\n{}", ORIGINAL);
+        let (dir, tool) = setup(&shifted).await;
+        let result = tool.execute(json!({"patch": CLEAN_PATCH})).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+
+        let after = std::fs::read_to_string(dir.path().join("src/main.rs")).unwrap();
+        assert!(after.starts_with("// header\n// comment\n\nfn main() {"));
+        assert!(after.contains("    let b = 3;\n    let c = 4;\n"));
+    }
+
+    #[tokio::test]
+    async fn test_multi_file_apply() {
+        let (dir, tool) = setup(ORIGINAL).await;
+        let result = tool.execute(json!({"patch": MULTI_FILE_PATCH})).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert!(result.output.contains("src/main.rs: +1 -1"));
+        assert!(result.output.contains("src/new.rs: +2 -0"));
+
+        let new_file = std::fs::read_to_string(dir.path().join("src/new.rs")).unwrap();
+        assert_eq!(new_file, "pub fn added() {}\npub fn another() {}\n");
+
+        let files: Vec<PatchedFile> = serde_json::from_value(result.data.unwrap()["files"].clone()).unwrap();
+        assert_eq!(files.len(), 2);
+        assert!(!files[0].created);
+        assert_eq!((files[0].added, files[0].removed), (1, 1));
+        assert_eq!(files[1].path, "src/new.rs");
+        assert!(files[1].created);
+        assert_eq!(files[1].bytes_written, new_file.len());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_hunk_leaves_files_untouched() {
+        let (dir, tool) = setup(ORIGINAL).await;
+        let result = tool.execute(json!({"patch": REJECTED_PATCH})).await.unwrap();
+        assert!(!result.success);
+        let error = result.error.unwrap();
+        assert!(error.contains("Hunk #1 in src/main.rs failed"));
+        assert!(error.contains("-    let x = 1;"));
+
+        let after = std::fs::read_to_string(dir.path().join("src/main.rs")).unwrap();
+        assert_eq!(after, ORIGINAL);
+    }
+
+    #[tokio::test]
+    async fn test_refuses_outside_project_root() {
+        let (_dir, tool) = setup(ORIGINAL).await;
+        let patch = "--- /dev/null\n+++ b/../escape.txt\n@@ -0,0 +1 @@\n+oops\n";
+        let result = tool.execute(json!({"patch": patch})).await.unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("Access denied by path policy"));
+    }
+
+    #[tokio::test]
+    async fn test_follows_shared_path_policy() {
+        let (dir, tool) = setup(ORIGINAL).await;
+        let shared = dir.path().parent().unwrap().join(format!("shared-{}", std::process::id()));
+        std::fs::create_dir_all(&shared).unwrap();
+        let name = shared.file_name().unwrap().to_string_lossy().to_string();
+        let patch = format!("--- /dev/null\n+++ b/../{}/notes.txt\n@@ -0,0 +1 @@\n+ok\n", name);
+
+        // tools.extra_allowed_paths で許可した場所には書ける
+        let policy = Arc::new(PathPolicy::new(dir.path()).with_allowed_paths([&shared]));
+        let result = tool.with_path_policy(policy).execute(json!({"patch": patch})).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(std::fs::read_to_string(shared.join("notes.txt")).unwrap(), "ok\n");
+        std::fs::remove_dir_all(&shared).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_oversized_file_refuses_whole_patch() {
+        let (dir, tool) = setup(ORIGINAL).await;
+        let tool = tool.with_guard(WriteGuard::new(ORIGINAL.len() as u64, 0));
+        let result = tool.execute(json!({"patch": MULTI_FILE_PATCH})).await.unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("tools.max_write_bytes"));
+        // 上限内のファイルも書き込まない
+        assert_eq!(std::fs::read_to_string(dir.path().join("src/main.rs")).unwrap(), ORIGINAL);
+        assert!(!dir.path().join("src/new.rs").exists());
+    }
+}
@@ -1,17 +1,45 @@
 use anyhow::Result;
 use async_trait::async_trait;
 use serde_json::{json, Value};
-use std::path::Path;
+use std::sync::Arc;
 use tokio::fs;
 
-use crate::tools::{Tool, ToolResult};
+use crate::tools::payload::FileRead;
+use crate::tools::workspace::resolve_tool_path;
+use crate::tools::{Overlay, PathPolicy, Tool, ToolEffects, ToolResult, Workspace};
+
+/// limit 未指定時に読み込む最大行数
+const DEFAULT_LINE_LIMIT: usize = 2000;
+
+/// 1行あたりの最大文字数（超過分は切り詰め）
+const MAX_LINE_CHARS: usize = 2000;
+
+/// バイナリ判定に使う先頭バイト数
+const BINARY_CHECK_BYTES: usize = 8192;
 
 /// ファイル読み込みツール
-pub struct ReadTool;
+pub struct ReadTool {
+    /// 複数ルートのワークスペース（`ラベル:パス` を解決する）
+    workspace: Option<Arc<Workspace>>,
+    /// 扱えるパスの制限（プロジェクトルートの外を拒否する）
+    policy: Option<Arc<PathPolicy>>,
+}
 
 impl ReadTool {
     pub fn new() -> Self {
-        Self
+        Self { workspace: None, policy: None }
+    }
+
+    /// ワークスペースのルートからパスを解決する
+    pub fn with_workspace(mut self, workspace: Arc<Workspace>) -> Self {
+        self.workspace = Some(workspace);
+        self
+    }
+
+    /// 扱えるパスを制限する
+    pub fn with_path_policy(mut self, policy: Arc<PathPolicy>) -> Self {
+        self.policy = Some(policy);
+        self
     }
 }
 
@@ -41,63 +69,255 @@ impl Tool for ReadTool {
                 },
                 "offset": {
                     "type": "integer",
-                    "description": "Line number to start reading from (0-indexed)"
+                    "description": "Line number to start reading from (1-indexed, default 1)"
                 },
                 "limit": {
                     "type": "integer",
-                    "description": "Maximum number of lines to read"
+                    "description": "Maximum number of lines to read (default 2000)"
                 }
             },
             "required": ["file_path"]
         })
     }
 
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::ReadOnly
+    }
+
     async fn execute(&self, params: Value) -> Result<ToolResult> {
         let file_path = params.get("file_path")
             .and_then(|v| v.as_str())
             .ok_or_else(|| anyhow::anyhow!("Missing file_path parameter"))?;
 
+        // offset は1始まりの行番号（0は1とみなす）
         let offset = params.get("offset")
             .and_then(|v| v.as_u64())
-            .unwrap_or(0) as usize;
+            .unwrap_or(1)
+            .max(1) as usize;
 
         let limit = params.get("limit")
             .and_then(|v| v.as_u64())
-            .map(|v| v as usize);
+            .map(|v| v as usize)
+            .unwrap_or(DEFAULT_LINE_LIMIT);
 
-        let path = Path::new(file_path);
+        let (path, file_path) = resolve_tool_path(self.workspace.as_deref(), file_path);
+        let (path, file_path) = (path.as_path(), file_path.as_str());
+        if let Some(Err(e)) = self.policy.as_ref().map(|p| p.check_read(path)) {
+            return Ok(ToolResult::failure(e));
+        }
 
         if !path.exists() {
             return Ok(ToolResult::failure(format!("File not found: {}", file_path)));
         }
 
-        match fs::read_to_string(path).await {
-            Ok(content) => {
-                let lines: Vec<&str> = content.lines().collect();
-                let total_lines = lines.len();
-
-                let selected: Vec<&str> = lines
-                    .into_iter()
-                    .skip(offset)
-                    .take(limit.unwrap_or(usize::MAX))
-                    .collect();
-
-                let numbered: Vec<String> = selected
-                    .iter()
-                    .enumerate()
-                    .map(|(i, line)| format!("{:>6}\t{}", offset + i + 1, line))
-                    .collect();
-
-                let output = format!(
-                    "File: {} ({} lines)\n{}",
-                    file_path,
-                    total_lines,
-                    numbered.join("\n")
-                );
-
-                Ok(ToolResult::success(output))
+        let bytes = match fs::read(path).await {
+            Ok(bytes) => bytes,
+            Err(e) => return Ok(ToolResult::failure(format!("Failed to read file: {}", e))),
+        };
+
+        if is_binary(&bytes) {
+            return Ok(ToolResult::failure(format!(
+                "Cannot read binary file: {} ({} bytes)",
+                file_path,
+                bytes.len()
+            )));
+        }
+
+        let content = match String::from_utf8(bytes) {
+            Ok(content) => content,
+            Err(_) => {
+                return Ok(ToolResult::failure(format!(
+                    "Cannot read file: {} is not valid UTF-8 (binary?)",
+                    file_path
+                )))
             }
-            Err(e) => Ok(ToolResult::failure(format!("Failed to read file: {}", e))),
+        };
+
+        let total_lines = content.lines().count();
+        let (start, end) = line_span(total_lines, offset, limit);
+        Ok(ToolResult::success(format_lines(file_path, &content, offset, limit)).with_data(FileRead {
+            path: file_path.to_string(),
+            start_line: offset,
+            lines: end.saturating_sub(start),
+            total_lines,
+        }))
+    }
+
+    async fn simulate(&self, params: Value, overlay: &Overlay) -> Result<Option<ToolResult>> {
+        let Some(file_path) = params.get("file_path").and_then(|v| v.as_str()) else {
+            return Ok(None);
+        };
+        let (path, file_path) = resolve_tool_path(self.workspace.as_deref(), file_path);
+        let (path, file_path) = (path.as_path(), file_path.as_str());
+        if let Some(Err(e)) = self.policy.as_ref().map(|p| p.check_read(path)) {
+            return Ok(Some(ToolResult::failure(e)));
         }
+        // ドライランで書き換えたファイルだけはオーバーレイから読む
+        let content = match overlay.get(path) {
+            None => return Ok(None),
+            Some(None) => return Ok(Some(ToolResult::failure(format!("File not found: {}", file_path)))),
+            Some(Some(content)) => content,
+        };
+        let offset = params.get("offset").and_then(|v| v.as_u64()).unwrap_or(1).max(1) as usize;
+        let limit = params
+            .get("limit")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as usize)
+            .unwrap_or(DEFAULT_LINE_LIMIT);
+        Ok(Some(ToolResult::success(format_lines(file_path, &content, offset, limit))))
+    }
+}
+
+/// NULバイトを含むファイルをバイナリとみなす
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes.iter().take(BINARY_CHECK_BYTES).any(|&b| b == 0)
+}
+
+/// 返す行の範囲（0始まり、end は含まない）
+fn line_span(total_lines: usize, offset: usize, limit: usize) -> (usize, usize) {
+    let start = offset - 1;
+    (start, start.saturating_add(limit).min(total_lines))
+}
+
+/// 指定範囲の行を行番号付きで整形
+fn format_lines(file_path: &str, content: &str, offset: usize, limit: usize) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let total_lines = lines.len();
+    let (start, end) = line_span(total_lines, offset, limit);
+
+    let mut output = format!("File: {} ({} lines)\n", file_path, total_lines);
+
+    if start >= total_lines && total_lines > 0 {
+        output.push_str(&format!(
+            "(offset {} is past end of file; file has {} lines)",
+            offset, total_lines
+        ));
+        return output;
+    }
+
+    let numbered: Vec<String> = lines[start.min(total_lines)..end]
+        .iter()
+        .enumerate()
+        .map(|(i, line)| format!("{:>6}→{}", offset + i, truncate_line(line)))
+        .collect();
+    output.push_str(&numbered.join("\n"));
+
+    let remaining = total_lines - end;
+    if remaining > 0 {
+        if !numbered.is_empty() {
+            output.push('\n');
+        }
+        output.push_str(&format!(
+            "(truncated, {} more lines; re-read with offset={})",
+            remaining,
+            end + 1
+        ));
+    }
+
+    output
+}
+
+/// 長すぎる行を切り詰める
+fn truncate_line(line: &str) -> String {
+    match line.char_indices().nth(MAX_LINE_CHARS) {
+        Some((idx, _)) => format!(
+            "{}… (line truncated, {} chars total)",
+            &line[..idx],
+            line.chars().count()
+        ),
+        None => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn numbered_file(lines: usize) -> String {
+        (1..=lines).map(|i| format!("line {}", i)).collect::<Vec<_>>().join("\n")
+    }
+
+    #[test]
+    fn test_default_limit_truncates_with_hint() {
+        let output = format_lines("a.txt", &numbered_file(5000), 1, DEFAULT_LINE_LIMIT);
+        assert!(output.contains("     1→line 1"));
+        assert!(output.contains("  2000→line 2000"));
+        assert!(!output.contains("line 2001\n"));
+        assert!(output.ends_with("(truncated, 3000 more lines; re-read with offset=2001)"));
+    }
+
+    #[test]
+    fn test_offset_past_eof() {
+        let output = format_lines("a.txt", &numbered_file(10), 50, 10);
+        assert!(output.contains("offset 50 is past end of file"));
+        assert!(!output.contains("→"));
+    }
+
+    #[test]
+    fn test_limit_zero() {
+        let output = format_lines("a.txt", &numbered_file(10), 1, 0);
+        assert!(!output.contains("→"));
+        assert!(output.contains("(truncated, 10 more lines; re-read with offset=1)"));
+    }
+
+    #[test]
+    fn test_long_line_truncated() {
+        let content = "x".repeat(MAX_LINE_CHARS * 3);
+        let output = format_lines("a.txt", &content, 1, 10);
+        assert!(output.contains("line truncated, 6000 chars total"));
+        assert!(output.len() < MAX_LINE_CHARS + 200);
+    }
+
+    #[tokio::test]
+    async fn test_binary_file_rejected() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("image.bin");
+        std::fs::write(&path, [0x89, b'P', b'N', b'G', 0x00, 0x01]).unwrap();
+
+        let result = ReadTool::new()
+            .execute(json!({"file_path": path.to_str().unwrap()}))
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap_or_default().contains("binary"));
+    }
+
+    #[tokio::test]
+    async fn test_data_matches_rendered_range() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        std::fs::write(&path, numbered_file(10)).unwrap();
+
+        let result = ReadTool::new()
+            .execute(json!({"file_path": path.to_str().unwrap(), "offset": 4, "limit": 3}))
+            .await
+            .unwrap();
+        let data: FileRead = serde_json::from_value(result.data.unwrap()).unwrap();
+        assert_eq!((data.start_line, data.lines, data.total_lines), (4, 3, 10));
+        // テキストの行番号付きの行数と一致する
+        assert_eq!(result.output.lines().filter(|l| l.contains('→')).count(), data.lines);
+        assert!(result.output.contains("     4→"));
+    }
+
+    #[tokio::test]
+    async fn test_path_policy_refuses_files_outside_project() {
+        let project = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        std::fs::write(outside.path().join("secret.txt"), "secret").unwrap();
+        std::fs::write(project.path().join("a.txt"), "ok").unwrap();
+        let policy = Arc::new(PathPolicy::new(project.path()));
+        let tool = ReadTool::new().with_path_policy(Arc::clone(&policy));
+
+        let secret = outside.path().join("secret.txt");
+        let result = tool.execute(json!({"file_path": secret.to_str().unwrap()})).await.unwrap();
+        assert!(result.error.unwrap().contains("Access denied by path policy"));
+        let result = tool.execute(json!({"file_path": project.path().join("a.txt").to_str().unwrap()})).await.unwrap();
+        assert!(result.success);
+
+        // 読み取りだけは設定で外側も許可できる
+        let tool = ReadTool::new().with_path_policy(Arc::new(PathPolicy::new(project.path()).with_read_outside(true)));
+        let result = tool.execute(json!({"file_path": secret.to_str().unwrap()})).await.unwrap();
+        assert!(result.output.contains("secret"));
     }
 }
@@ -2,16 +2,126 @@ use anyhow::Result;
 use async_trait::async_trait;
 use serde_json::{json, Value};
 use std::path::Path;
+use std::sync::Arc;
 use tokio::fs;
 
-use crate::tools::{Tool, ToolResult};
+use super::diff::{truncate_diff, unified_diff};
+use super::observer::SharedFileObserver;
+use crate::tools::disk::WriteGuard;
+use crate::tools::payload::FileChange;
+use crate::tools::dry_run::DRY_RUN_PREFIX;
+use crate::tools::workspace::resolve_tool_path;
+use crate::tools::{Overlay, PathPolicy, Tool, ToolEffects, ToolResult, Workspace};
+
+/// 上書き時に出力する差分の最大行数
+const MAX_DIFF_LINES: usize = 200;
+
+/// 上書き確認のコールバック（差分を含む詳細を受け取り、許可なら true）
+pub type WriteConfirmer = Arc<dyn Fn(&str) -> bool + Send + Sync>;
 
 /// ファイル書き込みツール
-pub struct WriteTool;
+pub struct WriteTool {
+    /// 既存ファイルを上書きする前の確認（対話実行時のみ設定）
+    confirm: Option<WriteConfirmer>,
+    /// 書き込みの大きさと空き容量の確認
+    guard: WriteGuard,
+    /// 複数ルートのワークスペース（`ラベル:パス` を解決する）
+    workspace: Option<Arc<Workspace>>,
+    /// 書き込んだファイルの通知先（言語サーバーへの didChange）
+    observer: Option<SharedFileObserver>,
+    /// 扱えるパスの制限（プロジェクトルートの外を拒否する）
+    policy: Option<Arc<PathPolicy>>,
+}
 
 impl WriteTool {
     pub fn new() -> Self {
-        Self
+        Self {
+            confirm: None,
+            guard: WriteGuard::default(),
+            workspace: None,
+            observer: None,
+            policy: None,
+        }
+    }
+
+    /// 書き込んだファイルを知らせる
+    pub fn with_observer(mut self, observer: SharedFileObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// 扱えるパスを制限する
+    pub fn with_path_policy(mut self, policy: Arc<PathPolicy>) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    async fn notify_written(&self, path: &Path) {
+        if let Some(observer) = &self.observer {
+            observer.file_written(path).await;
+        }
+    }
+
+    /// ワークスペースのルートからパスを解決する
+    pub fn with_workspace(mut self, workspace: Arc<Workspace>) -> Self {
+        self.workspace = Some(workspace);
+        self
+    }
+
+    /// 書き込みの上限と空き容量の余裕を設定
+    pub fn with_guard(mut self, guard: WriteGuard) -> Self {
+        self.guard = guard;
+        self
+    }
+
+    /// 既存ファイルの上書き前に確認する
+    pub fn with_confirm(mut self, confirm: WriteConfirmer) -> Self {
+        self.confirm = Some(confirm);
+        self
+    }
+
+    /// 既存ファイルを上書き（差分を出力に含める）
+    async fn overwrite(&self, path: &Path, file_path: &str, content: &str, force: bool) -> Result<ToolResult> {
+        let old = match fs::read(path).await {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(e) => return Ok(ToolResult::failure(format!("Failed to read existing file: {}", e))),
+        };
+
+        let diff = unified_diff(&old, content, file_path);
+        let diff = if diff.is_empty() {
+            "(no changes)".to_string()
+        } else {
+            truncate_diff(&diff, MAX_DIFF_LINES)
+        };
+
+        if let (Some(confirm), false) = (&self.confirm, force) {
+            let confirm = Arc::clone(confirm);
+            let details = format!("Overwrite {}\n{}", file_path, diff);
+            let approved = tokio::task::spawn_blocking(move || confirm(&details)).await?;
+            if !approved {
+                return Ok(ToolResult::failure(format!(
+                    "Overwrite of {} was denied by the user",
+                    file_path
+                )));
+            }
+        }
+
+        if let Err(e) = fs::write(path, content).await {
+            return Ok(ToolResult::failure(format!("Failed to write file: {}", e)));
+        }
+        self.notify_written(path).await;
+        Ok(ToolResult::success(format!(
+            "Overwrote {} ({} lines, {} bytes)\n{}",
+            file_path,
+            content.lines().count(),
+            content.len(),
+            diff
+        ))
+        .with_data(FileChange {
+            path: file_path.to_string(),
+            bytes_written: content.len(),
+            created: false,
+        }))
     }
 }
 
@@ -28,7 +138,7 @@ impl Tool for WriteTool {
     }
 
     fn description(&self) -> &str {
-        "Write content to a file (creates or overwrites)"
+        "Write content to a file (creates or overwrites; overwrites show a diff)"
     }
 
     fn parameters_schema(&self) -> Value {
@@ -42,12 +152,24 @@ impl Tool for WriteTool {
                 "content": {
                     "type": "string",
                     "description": "The content to write to the file"
+                },
+                "force": {
+                    "type": "boolean",
+                    "description": "Overwrite an existing file without asking for confirmation (default: false)"
                 }
             },
             "required": ["file_path", "content"]
         })
     }
 
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::Writes
+    }
+
+    fn confirms_itself(&self) -> bool {
+        self.confirm.is_some()
+    }
+
     async fn execute(&self, params: Value) -> Result<ToolResult> {
         let file_path = params.get("file_path")
             .and_then(|v| v.as_str())
@@ -57,25 +179,212 @@ impl Tool for WriteTool {
             .and_then(|v| v.as_str())
             .ok_or_else(|| anyhow::anyhow!("Missing content parameter"))?;
 
-        let path = Path::new(file_path);
+        let force = params.get("force").and_then(|v| v.as_bool()).unwrap_or(false);
+
+        let (path, file_path) = resolve_tool_path(self.workspace.as_deref(), file_path);
+        let (path, file_path) = (path.as_path(), file_path.as_str());
+        if let Some(Err(e)) = self.policy.as_ref().map(|p| p.check_write(path)) {
+            return Ok(ToolResult::failure(e));
+        }
+
+        if path.is_dir() {
+            return Ok(ToolResult::failure(format!("{} is a directory", file_path)));
+        }
+        let old_len = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        if let Err(e) = self.guard.check(path, content.len() as u64, old_len) {
+            return Ok(ToolResult::failure(e));
+        }
+        if path.exists() {
+            return self.overwrite(path, file_path, content, force).await;
+        }
 
         // 親ディレクトリが存在しない場合は作成
-        if let Some(parent) = path.parent() {
-            if !parent.exists() {
-                fs::create_dir_all(parent).await?;
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            if let Err(e) = fs::create_dir_all(parent).await {
+                return Ok(ToolResult::failure(format!(
+                    "Failed to create directory {}: {}",
+                    parent.display(),
+                    e
+                )));
             }
         }
 
-        match fs::write(path, content).await {
-            Ok(_) => {
-                let lines = content.lines().count();
-                Ok(ToolResult::success(format!(
-                    "Successfully wrote {} lines to {}",
-                    lines,
-                    file_path
-                )))
-            }
-            Err(e) => Ok(ToolResult::failure(format!("Failed to write file: {}", e))),
+        if let Err(e) = fs::write(path, content).await {
+            return Ok(ToolResult::failure(format!("Failed to write file: {}", e)));
+        }
+        self.notify_written(path).await;
+        Ok(ToolResult::success(format!(
+            "Created {} ({} lines, {} bytes)",
+            file_path,
+            content.lines().count(),
+            content.len()
+        ))
+        .with_data(FileChange {
+            path: file_path.to_string(),
+            bytes_written: content.len(),
+            created: true,
+        }))
+    }
+
+    async fn simulate(&self, params: Value, overlay: &Overlay) -> Result<Option<ToolResult>> {
+        let (Some(file_path), Some(content)) = (
+            params.get("file_path").and_then(|v| v.as_str()),
+            params.get("content").and_then(|v| v.as_str()),
+        ) else {
+            return Ok(None);
+        };
+        let (path, file_path) = resolve_tool_path(self.workspace.as_deref(), file_path);
+        let (path, file_path) = (path.as_path(), file_path.as_str());
+        if let Some(Err(e)) = self.policy.as_ref().map(|p| p.check_write(path)) {
+            return Ok(Some(ToolResult::failure(e)));
+        }
+        if path.is_dir() {
+            return Ok(Some(ToolResult::failure(format!("{} is a directory", file_path))));
         }
+        let old_len = overlay.read(path).map(|old| old.len() as u64).unwrap_or(0);
+        if let Err(e) = self.guard.check(path, content.len() as u64, old_len) {
+            return Ok(Some(ToolResult::failure(e)));
+        }
+
+        let output = if overlay.exists(path) {
+            let old = overlay.read(path).unwrap_or_default();
+            let diff = unified_diff(&old, content, file_path);
+            let diff = if diff.is_empty() {
+                "(no changes)".to_string()
+            } else {
+                truncate_diff(&diff, MAX_DIFF_LINES)
+            };
+            format!("{} Would overwrite {} ({} lines, {} bytes)\n{}", DRY_RUN_PREFIX, file_path, content.lines().count(), content.len(), diff)
+        } else {
+            format!("{} Would create {} ({} lines, {} bytes)", DRY_RUN_PREFIX, file_path, content.lines().count(), content.len())
+        };
+        overlay.write(path, content);
+        Ok(Some(ToolResult::success(output)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tempfile::tempdir;
+
+    fn recording_confirmer(answer: bool) -> (WriteConfirmer, Arc<Mutex<Vec<String>>>) {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&seen);
+        let confirm: WriteConfirmer = Arc::new(move |details: &str| {
+            recorder.lock().unwrap().push(details.to_string());
+            answer
+        });
+        (confirm, seen)
+    }
+
+    #[tokio::test]
+    async fn test_create_reports_size_and_makes_parents() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("a/b/new.txt");
+        let (confirm, seen) = recording_confirmer(false);
+        let tool = WriteTool::new().with_confirm(confirm);
+
+        let result = tool
+            .execute(json!({"file_path": path.to_str().unwrap(), "content": "one\ntwo\n"}))
+            .await
+            .unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert!(result.output.starts_with("Created "));
+        assert!(result.output.ends_with("(2 lines, 8 bytes)"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\ntwo\n");
+        // 構造化された結果もテキストと一致する
+        assert_eq!(
+            result.data,
+            Some(json!({"path": path.to_str().unwrap(), "bytes_written": 8, "created": true}))
+        );
+        // 新規作成では確認しない
+        assert!(seen.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_overwrite_shows_diff_and_asks() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("f.txt");
+        std::fs::write(&path, "keep\nold\n").unwrap();
+        let (confirm, seen) = recording_confirmer(true);
+        let tool = WriteTool::new().with_confirm(confirm);
+
+        let result = tool
+            .execute(json!({"file_path": path.to_str().unwrap(), "content": "keep\nnew\n"}))
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert!(result.output.contains("-old\n+new"));
+        assert_eq!(result.data.as_ref().unwrap()["created"], json!(false));
+        assert_eq!(seen.lock().unwrap().len(), 1);
+        assert!(seen.lock().unwrap()[0].contains("-old\n+new"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep\nnew\n");
+    }
+
+    #[tokio::test]
+    async fn test_denied_overwrite_keeps_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("f.txt");
+        std::fs::write(&path, "original\n").unwrap();
+        let (confirm, _) = recording_confirmer(false);
+        let tool = WriteTool::new().with_confirm(confirm);
+
+        let result = tool
+            .execute(json!({"file_path": path.to_str().unwrap(), "content": "clobbered\n"}))
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("denied"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "original\n");
+    }
+
+    #[tokio::test]
+    async fn test_force_skips_confirmation() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("f.txt");
+        std::fs::write(&path, "original\n").unwrap();
+        let (confirm, seen) = recording_confirmer(false);
+        let tool = WriteTool::new().with_confirm(confirm);
+
+        let result = tool
+            .execute(json!({"file_path": path.to_str().unwrap(), "content": "forced\n", "force": true}))
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert!(seen.lock().unwrap().is_empty());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "forced\n");
+    }
+
+    #[tokio::test]
+    async fn test_oversized_write_is_refused() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("big.txt");
+        let tool = WriteTool::new().with_guard(WriteGuard::new(16, 0));
+
+        let result = tool
+            .execute(json!({"file_path": path.to_str().unwrap(), "content": "x".repeat(17)}))
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("exceeds the per-write limit of 16 B (tools.max_write_bytes)"));
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_path_policy_refuses_writes_outside_project() {
+        let project = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        let policy = PathPolicy::new(project.path()).with_read_outside(true);
+        let tool = WriteTool::new().with_path_policy(Arc::new(policy));
+
+        let target = project.path().join("../escape.txt");
+        let result = tool.execute(json!({"file_path": target.to_str().unwrap(), "content": "x"})).await.unwrap();
+        assert!(result.error.unwrap().contains("Access denied by path policy"));
+        let target = outside.path().join("x.txt");
+        let result = tool.execute(json!({"file_path": target.to_str().unwrap(), "content": "x"})).await.unwrap();
+        assert!(!result.success);
+        assert!(!target.exists());
     }
 }
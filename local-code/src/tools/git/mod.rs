@@ -1,3 +1,7 @@
 mod operations;
+mod push;
+pub mod repo;
 
-pub use operations::{GitStatusTool, GitDiffTool, GitAddTool, GitCommitTool, GitLogTool};
+pub use operations::{GitStatusTool, GitDiffTool, GitAddTool, GitCommitTool, GitLogTool, GitBranchTool, GitCheckoutTool, GitStashTool, GitShowTool, GitBlameTool};
+pub use push::{GitPushTool, PushConfirmer};
+pub use repo::{RepoInfo, find_nested_repos, is_in_nested_repo};
@@ -1,14 +1,17 @@
 use anyhow::Result;
 use async_trait::async_trait;
 use serde_json::{json, Value};
+use std::path::PathBuf;
 use std::process::Stdio;
 use tokio::process::Command;
 use tokio::io::AsyncReadExt;
 
-use crate::tools::{Tool, ToolResult};
+use crate::tools::payload::GitStatus;
+use crate::tools::dry_run::DRY_RUN_PREFIX;
+use crate::tools::{Overlay, Tool, ToolEffects, ToolResult};
 
 /// Git コマンド実行ヘルパー
-async fn run_git_command(args: &[&str], working_dir: Option<&str>) -> Result<(bool, String)> {
+pub(super) async fn run_git_command(args: &[&str], working_dir: Option<&str>) -> Result<(bool, String)> {
     let mut cmd = Command::new("git");
     cmd.args(args)
         .stdout(Stdio::piped())
@@ -41,11 +44,28 @@ async fn run_git_command(args: &[&str], working_dir: Option<&str>) -> Result<(bo
     Ok((status.success(), output.trim().to_string()))
 }
 
+/// path パラメータ、なければプロジェクトのリポジトリルートを返す
+pub(super) fn repo_path(params: &Value, root: &Option<PathBuf>) -> Option<String> {
+    params
+        .get("path")
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+        .or_else(|| root.as_ref().map(|r| r.display().to_string()))
+}
+
 /// Git status ツール
-pub struct GitStatusTool;
+pub struct GitStatusTool {
+    root: Option<PathBuf>,
+}
 
 impl GitStatusTool {
-    pub fn new() -> Self { Self }
+    pub fn new() -> Self { Self { root: None } }
+
+    /// path 未指定時に使うリポジトリルートを設定
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
 }
 
 impl Default for GitStatusTool {
@@ -60,15 +80,21 @@ impl Tool for GitStatusTool {
         json!({
             "type": "object",
             "properties": {
-                "path": { "type": "string", "description": "Repository path" }
+                "path": { "type": "string", "description": "Repository path (defaults to the project repository)" }
             }
         })
     }
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::ReadOnly
+    }
+
     async fn execute(&self, params: Value) -> Result<ToolResult> {
-        let path = params.get("path").and_then(|v| v.as_str());
+        let path = repo_path(&params, &self.root);
+        let path = path.as_deref();
         let (success, output) = run_git_command(&["status", "--short"], path).await?;
         if success {
-            Ok(ToolResult::success(if output.is_empty() { "Working tree clean".to_string() } else { output }))
+            let data = GitStatus::parse(&output);
+            Ok(ToolResult::success(if output.is_empty() { "Working tree clean".to_string() } else { output }).with_data(data))
         } else {
             Ok(ToolResult::failure(output))
         }
@@ -76,10 +102,18 @@ impl Tool for GitStatusTool {
 }
 
 /// Git diff ツール
-pub struct GitDiffTool;
+pub struct GitDiffTool {
+    root: Option<PathBuf>,
+}
 
 impl GitDiffTool {
-    pub fn new() -> Self { Self }
+    pub fn new() -> Self { Self { root: None } }
+
+    /// path 未指定時に使うリポジトリルートを設定
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
 }
 
 impl Default for GitDiffTool {
@@ -94,14 +128,19 @@ impl Tool for GitDiffTool {
         json!({
             "type": "object",
             "properties": {
-                "path": { "type": "string", "description": "Repository path" },
+                "path": { "type": "string", "description": "Repository path (defaults to the project repository)" },
                 "staged": { "type": "boolean", "description": "Show staged changes" },
                 "file": { "type": "string", "description": "Specific file to diff" }
             }
         })
     }
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::ReadOnly
+    }
+
     async fn execute(&self, params: Value) -> Result<ToolResult> {
-        let path = params.get("path").and_then(|v| v.as_str());
+        let path = repo_path(&params, &self.root);
+        let path = path.as_deref();
         let staged = params.get("staged").and_then(|v| v.as_bool()).unwrap_or(false);
         let file = params.get("file").and_then(|v| v.as_str());
 
@@ -119,10 +158,18 @@ impl Tool for GitDiffTool {
 }
 
 /// Git add ツール
-pub struct GitAddTool;
+pub struct GitAddTool {
+    root: Option<PathBuf>,
+}
 
 impl GitAddTool {
-    pub fn new() -> Self { Self }
+    pub fn new() -> Self { Self { root: None } }
+
+    /// path 未指定時に使うリポジトリルートを設定
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
 }
 
 impl Default for GitAddTool {
@@ -137,7 +184,7 @@ impl Tool for GitAddTool {
         json!({
             "type": "object",
             "properties": {
-                "path": { "type": "string", "description": "Repository path" },
+                "path": { "type": "string", "description": "Repository path (defaults to the project repository)" },
                 "files": {
                     "type": "array",
                     "items": { "type": "string" },
@@ -147,8 +194,13 @@ impl Tool for GitAddTool {
             "required": ["files"]
         })
     }
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::Writes
+    }
+
     async fn execute(&self, params: Value) -> Result<ToolResult> {
-        let path = params.get("path").and_then(|v| v.as_str());
+        let path = repo_path(&params, &self.root);
+        let path = path.as_deref();
         let files = params.get("files")
             .and_then(|v| v.as_array())
             .ok_or_else(|| anyhow::anyhow!("Missing files parameter"))?;
@@ -167,13 +219,32 @@ impl Tool for GitAddTool {
             Ok(ToolResult::failure(output))
         }
     }
+
+    async fn simulate(&self, params: Value, overlay: &Overlay) -> Result<Option<ToolResult>> {
+        let Some(files) = params.get("files").and_then(|v| v.as_array()) else {
+            return Ok(None);
+        };
+        let files: Vec<String> = files.iter().filter_map(|v| v.as_str()).map(String::from).collect();
+        let output = format!("{} Would stage {} file(s): {}", DRY_RUN_PREFIX, files.len(), files.join(", "));
+        overlay.record(format!("would stage {}", files.join(", ")));
+        overlay.stage(files);
+        Ok(Some(ToolResult::success(output)))
+    }
 }
 
 /// Git commit ツール
-pub struct GitCommitTool;
+pub struct GitCommitTool {
+    root: Option<PathBuf>,
+}
 
 impl GitCommitTool {
-    pub fn new() -> Self { Self }
+    pub fn new() -> Self { Self { root: None } }
+
+    /// path 未指定時に使うリポジトリルートを設定
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
 }
 
 impl Default for GitCommitTool {
@@ -188,14 +259,20 @@ impl Tool for GitCommitTool {
         json!({
             "type": "object",
             "properties": {
-                "path": { "type": "string", "description": "Repository path" },
+                "path": { "type": "string", "description": "Repository path (defaults to the project repository)" },
                 "message": { "type": "string", "description": "Commit message" }
             },
             "required": ["message"]
         })
     }
+    fn effects(&self) -> ToolEffects {
+        // コミットフックが任意のコマンドを実行しうる
+        ToolEffects::Executes
+    }
+
     async fn execute(&self, params: Value) -> Result<ToolResult> {
-        let path = params.get("path").and_then(|v| v.as_str());
+        let path = repo_path(&params, &self.root);
+        let path = path.as_deref();
         let message = params.get("message")
             .and_then(|v| v.as_str())
             .ok_or_else(|| anyhow::anyhow!("Missing message parameter"))?;
@@ -207,13 +284,42 @@ impl Tool for GitCommitTool {
             Ok(ToolResult::failure(output))
         }
     }
+
+    async fn simulate(&self, params: Value, overlay: &Overlay) -> Result<Option<ToolResult>> {
+        let Some(message) = params.get("message").and_then(|v| v.as_str()) else {
+            return Ok(None);
+        };
+        // 実際にステージ済みのファイルと、ドライランでステージしたファイル
+        let path = repo_path(&params, &self.root);
+        let (_, staged) = run_git_command(&["diff", "--cached", "--name-only"], path.as_deref()).await?;
+        let mut files: Vec<String> = staged.lines().map(String::from).collect();
+        files.extend(overlay.take_staged());
+        files.sort();
+        files.dedup();
+        let subject = message.lines().next().unwrap_or_default();
+        overlay.record(format!("would commit {} file(s): {}", files.len(), subject));
+        Ok(Some(ToolResult::success(format!(
+            "{} Would commit {} file(s) with message: {}",
+            DRY_RUN_PREFIX,
+            files.len(),
+            subject
+        ))))
+    }
 }
 
 /// Git log ツール
-pub struct GitLogTool;
+pub struct GitLogTool {
+    root: Option<PathBuf>,
+}
 
 impl GitLogTool {
-    pub fn new() -> Self { Self }
+    pub fn new() -> Self { Self { root: None } }
+
+    /// path 未指定時に使うリポジトリルートを設定
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
 }
 
 impl Default for GitLogTool {
@@ -228,14 +334,19 @@ impl Tool for GitLogTool {
         json!({
             "type": "object",
             "properties": {
-                "path": { "type": "string", "description": "Repository path" },
+                "path": { "type": "string", "description": "Repository path (defaults to the project repository)" },
                 "count": { "type": "integer", "description": "Number of commits to show (default: 10)" },
                 "oneline": { "type": "boolean", "description": "Show one line per commit" }
             }
         })
     }
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::ReadOnly
+    }
+
     async fn execute(&self, params: Value) -> Result<ToolResult> {
-        let path = params.get("path").and_then(|v| v.as_str());
+        let path = repo_path(&params, &self.root);
+        let path = path.as_deref();
         let count = params.get("count").and_then(|v| v.as_u64()).unwrap_or(10);
         let oneline = params.get("oneline").and_then(|v| v.as_bool()).unwrap_or(true);
 
@@ -251,3 +362,662 @@ impl Tool for GitLogTool {
         }
     }
 }
+
+/// リビジョンとして受け付けるか（オプションと取り違えないよう `-` で始まるものは拒否）
+fn check_revision(rev: &str) -> std::result::Result<(), String> {
+    if rev.trim().is_empty() {
+        Err("Revision is empty".to_string())
+    } else if rev.starts_with('-') {
+        Err(format!("Invalid revision '{}'", rev))
+    } else {
+        Ok(())
+    }
+}
+
+/// Git show ツール
+///
+/// 長い出力はエージェントが他のツール結果と同じく切り詰める（全文は read_tool_output で読める）
+pub struct GitShowTool {
+    root: Option<PathBuf>,
+}
+
+impl GitShowTool {
+    pub fn new() -> Self { Self { root: None } }
+
+    /// path 未指定時に使うリポジトリルートを設定
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+}
+
+impl Default for GitShowTool {
+    fn default() -> Self { Self::new() }
+}
+
+#[async_trait]
+impl Tool for GitShowTool {
+    fn name(&self) -> &str { "git_show" }
+    fn description(&self) -> &str {
+        "Show a commit's message and diff, or with file, the content of that file as of the commit (e.g. ref: HEAD~3, a hash, or a tag)"
+    }
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Repository path (defaults to the project repository)" },
+                "ref": { "type": "string", "description": "Commit, branch or tag to show" },
+                "file": { "type": "string", "description": "File path relative to the repository root; shows its content at ref instead of the commit" }
+            },
+            "required": ["ref"]
+        })
+    }
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::ReadOnly
+    }
+
+    async fn execute(&self, params: Value) -> Result<ToolResult> {
+        let path = repo_path(&params, &self.root);
+        let path = path.as_deref();
+        let rev = params.get("ref")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing ref parameter"))?;
+        if let Err(e) = check_revision(rev) {
+            return Ok(ToolResult::failure(e));
+        }
+
+        let object = params.get("file")
+            .and_then(|v| v.as_str())
+            .map(|file| format!("{}:{}", rev, file.trim_start_matches("./")));
+        let args = match &object {
+            Some(object) => vec!["show", object.as_str()],
+            None => vec!["show", "--stat", "--patch", rev, "--"],
+        };
+        let (success, output) = run_git_command(&args, path).await?;
+        if success {
+            Ok(ToolResult::success(output))
+        } else {
+            Ok(ToolResult::failure(output))
+        }
+    }
+}
+
+/// Git blame ツール
+pub struct GitBlameTool {
+    root: Option<PathBuf>,
+}
+
+impl GitBlameTool {
+    pub fn new() -> Self { Self { root: None } }
+
+    /// path 未指定時に使うリポジトリルートを設定
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+}
+
+impl Default for GitBlameTool {
+    fn default() -> Self { Self::new() }
+}
+
+/// `-L` に渡す行範囲（どちらも省略ならファイル全体）
+fn blame_range(start: Option<u64>, end: Option<u64>) -> std::result::Result<Option<String>, String> {
+    match (start, end) {
+        (None, None) => Ok(None),
+        (Some(0), _) | (_, Some(0)) => Err("Line numbers start at 1".to_string()),
+        (Some(start), Some(end)) if end < start => {
+            Err(format!("end_line {} is before start_line {}", end, start))
+        }
+        (start, end) => Ok(Some(format!(
+            "{},{}",
+            start.unwrap_or(1),
+            end.map(|e| e.to_string()).unwrap_or_default()
+        ))),
+    }
+}
+
+#[async_trait]
+impl Tool for GitBlameTool {
+    fn name(&self) -> &str { "git_blame" }
+    fn description(&self) -> &str {
+        "Show who last changed each line of a file, with short commit hash, author and date. Narrow it with start_line/end_line"
+    }
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Repository path (defaults to the project repository)" },
+                "file": { "type": "string", "description": "File to blame" },
+                "start_line": { "type": "integer", "description": "First line (1-based, default: 1)" },
+                "end_line": { "type": "integer", "description": "Last line (default: end of file)" }
+            },
+            "required": ["file"]
+        })
+    }
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::ReadOnly
+    }
+
+    async fn execute(&self, params: Value) -> Result<ToolResult> {
+        let path = repo_path(&params, &self.root);
+        let path = path.as_deref();
+        let file = params.get("file")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing file parameter"))?;
+        let range = match blame_range(
+            params.get("start_line").and_then(|v| v.as_u64()),
+            params.get("end_line").and_then(|v| v.as_u64()),
+        ) {
+            Ok(range) => range,
+            Err(e) => return Ok(ToolResult::failure(e)),
+        };
+
+        let mut args = vec!["blame", "--date=short"];
+        if let Some(range) = &range {
+            args.extend(["-L", range.as_str()]);
+        }
+        args.extend(["--", file]);
+        let (success, output) = run_git_command(&args, path).await?;
+        if success {
+            Ok(ToolResult::success(output))
+        } else {
+            Ok(ToolResult::failure(output))
+        }
+    }
+}
+
+/// `action` パラメータ（省略時は `default`）
+fn action<'a>(params: &'a Value, default: &'a str) -> &'a str {
+    params.get("action").and_then(|v| v.as_str()).unwrap_or(default)
+}
+
+/// ブランチ名として受け付けるか（オプションと取り違えないよう `-` で始まる名前は拒否）
+fn check_branch_name(name: &str) -> std::result::Result<(), String> {
+    if name.trim().is_empty() {
+        Err("Branch name is empty".to_string())
+    } else if name.starts_with('-') {
+        Err(format!("Invalid branch name '{}'", name))
+    } else {
+        Ok(())
+    }
+}
+
+/// Git branch ツール
+pub struct GitBranchTool {
+    root: Option<PathBuf>,
+}
+
+impl GitBranchTool {
+    pub fn new() -> Self { Self { root: None } }
+
+    /// path 未指定時に使うリポジトリルートを設定
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+}
+
+impl Default for GitBranchTool {
+    fn default() -> Self { Self::new() }
+}
+
+#[async_trait]
+impl Tool for GitBranchTool {
+    fn name(&self) -> &str { "git_branch" }
+    fn description(&self) -> &str { "List branches, show the current branch, or create a branch (without switching to it)" }
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Repository path (defaults to the project repository)" },
+                "action": {
+                    "type": "string",
+                    "enum": ["list", "current", "create"],
+                    "description": "list (default), current, or create"
+                },
+                "name": { "type": "string", "description": "Branch name to create (action: create)" },
+                "start_point": { "type": "string", "description": "Commit or branch the new branch starts from (defaults to HEAD)" }
+            }
+        })
+    }
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::Writes
+    }
+    fn call_effects(&self, params: &Value) -> ToolEffects {
+        match action(params, "list") {
+            "create" => ToolEffects::Writes,
+            _ => ToolEffects::ReadOnly,
+        }
+    }
+
+    async fn execute(&self, params: Value) -> Result<ToolResult> {
+        let path = repo_path(&params, &self.root);
+        let path = path.as_deref();
+        match action(&params, "list") {
+            "list" => {
+                let (success, output) = run_git_command(&["branch", "--list"], path).await?;
+                if success {
+                    Ok(ToolResult::success(if output.is_empty() { "No branches".to_string() } else { output }))
+                } else {
+                    Ok(ToolResult::failure(output))
+                }
+            }
+            "current" => {
+                let (success, output) = run_git_command(&["branch", "--show-current"], path).await?;
+                if !success {
+                    return Ok(ToolResult::failure(output));
+                }
+                if !output.is_empty() {
+                    return Ok(ToolResult::success(output));
+                }
+                let (_, head) = run_git_command(&["rev-parse", "--short", "HEAD"], path).await?;
+                Ok(ToolResult::success(format!("HEAD is detached at {}", head)))
+            }
+            "create" => {
+                let name = params.get("name")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| anyhow::anyhow!("Missing name parameter"))?;
+                if let Err(e) = check_branch_name(name) {
+                    return Ok(ToolResult::failure(e));
+                }
+                let mut args = vec!["branch", name];
+                if let Some(start) = params.get("start_point").and_then(|v| v.as_str()) {
+                    args.push(start);
+                }
+                let (success, output) = run_git_command(&args, path).await?;
+                if success {
+                    Ok(ToolResult::success(format!("Created branch '{}' (use git_checkout to switch to it)", name)))
+                } else {
+                    Ok(ToolResult::failure(output))
+                }
+            }
+            other => Ok(ToolResult::failure(format!("Unknown action '{}': use list, current or create", other))),
+        }
+    }
+
+    async fn simulate(&self, params: Value, overlay: &Overlay) -> Result<Option<ToolResult>> {
+        let Some(name) = params.get("name").and_then(|v| v.as_str()).filter(|_| action(&params, "list") == "create") else {
+            return Ok(None);
+        };
+        overlay.record(format!("would create branch {}", name));
+        Ok(Some(ToolResult::success(format!("{} Would create branch '{}'", DRY_RUN_PREFIX, name))))
+    }
+}
+
+/// Git checkout ツール
+pub struct GitCheckoutTool {
+    root: Option<PathBuf>,
+}
+
+impl GitCheckoutTool {
+    pub fn new() -> Self { Self { root: None } }
+
+    /// path 未指定時に使うリポジトリルートを設定
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+}
+
+impl Default for GitCheckoutTool {
+    fn default() -> Self { Self::new() }
+}
+
+#[async_trait]
+impl Tool for GitCheckoutTool {
+    fn name(&self) -> &str { "git_checkout" }
+    fn description(&self) -> &str {
+        "Switch to a branch, or create and switch with create: true. Refuses when tracked files have uncommitted changes unless force is true"
+    }
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Repository path (defaults to the project repository)" },
+                "branch": { "type": "string", "description": "Branch to switch to" },
+                "create": { "type": "boolean", "description": "Create the branch first (git checkout -b)" },
+                "start_point": { "type": "string", "description": "Commit or branch a created branch starts from (defaults to HEAD)" },
+                "force": { "type": "boolean", "description": "Switch even with uncommitted changes; they are carried over, and git still refuses if they conflict" }
+            },
+            "required": ["branch"]
+        })
+    }
+    fn effects(&self) -> ToolEffects {
+        // 作業ツリーを書き換え、post-checkout フックが任意のコマンドを実行しうる
+        ToolEffects::Executes
+    }
+
+    async fn execute(&self, params: Value) -> Result<ToolResult> {
+        let path = repo_path(&params, &self.root);
+        let path = path.as_deref();
+        let branch = params.get("branch")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing branch parameter"))?;
+        if let Err(e) = check_branch_name(branch) {
+            return Ok(ToolResult::failure(e));
+        }
+        let create = params.get("create").and_then(|v| v.as_bool()).unwrap_or(false);
+        let force = params.get("force").and_then(|v| v.as_bool()).unwrap_or(false);
+
+        if !force {
+            let (success, dirty) = run_git_command(&["status", "--porcelain", "--untracked-files=no"], path).await?;
+            if !success {
+                return Ok(ToolResult::failure(dirty));
+            }
+            if !dirty.is_empty() {
+                return Ok(ToolResult::failure(format!(
+                    "Working tree has uncommitted changes:\n{}\nCommit or stash them (git_stash) first, or pass force: true to carry them over",
+                    dirty
+                )));
+            }
+        }
+
+        let mut args = vec!["checkout"];
+        if create {
+            args.extend(["-b", branch]);
+            if let Some(start) = params.get("start_point").and_then(|v| v.as_str()) {
+                args.push(start);
+            }
+        } else {
+            // ファイル名ではなくブランチとして解釈させる
+            args.extend([branch, "--"]);
+        }
+        let (success, output) = run_git_command(&args, path).await?;
+        if success {
+            Ok(ToolResult::success(output))
+        } else {
+            Ok(ToolResult::failure(output))
+        }
+    }
+
+    async fn simulate(&self, params: Value, overlay: &Overlay) -> Result<Option<ToolResult>> {
+        let Some(branch) = params.get("branch").and_then(|v| v.as_str()) else {
+            return Ok(None);
+        };
+        let create = params.get("create").and_then(|v| v.as_bool()).unwrap_or(false);
+        let action = if create { "create and switch to" } else { "switch to" };
+        overlay.record(format!("would {} branch {}", action, branch));
+        Ok(Some(ToolResult::success(format!("{} Would {} branch '{}'", DRY_RUN_PREFIX, action, branch))))
+    }
+}
+
+/// Git stash ツール
+pub struct GitStashTool {
+    root: Option<PathBuf>,
+}
+
+impl GitStashTool {
+    pub fn new() -> Self { Self { root: None } }
+
+    /// path 未指定時に使うリポジトリルートを設定
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+}
+
+impl Default for GitStashTool {
+    fn default() -> Self { Self::new() }
+}
+
+#[async_trait]
+impl Tool for GitStashTool {
+    fn name(&self) -> &str { "git_stash" }
+    fn description(&self) -> &str { "Stash uncommitted changes (push), list stashes, or restore the latest stash (pop)" }
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Repository path (defaults to the project repository)" },
+                "action": {
+                    "type": "string",
+                    "enum": ["push", "list", "pop"],
+                    "description": "push, list (default), or pop"
+                },
+                "message": { "type": "string", "description": "Description of the stash (action: push)" },
+                "include_untracked": { "type": "boolean", "description": "Also stash untracked files (action: push)" },
+                "index": { "type": "integer", "description": "Stash to pop, as in stash@{index} (defaults to the latest)" }
+            }
+        })
+    }
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::Writes
+    }
+    fn call_effects(&self, params: &Value) -> ToolEffects {
+        match action(params, "list") {
+            "list" => ToolEffects::ReadOnly,
+            _ => ToolEffects::Writes,
+        }
+    }
+
+    async fn execute(&self, params: Value) -> Result<ToolResult> {
+        let path = repo_path(&params, &self.root);
+        let path = path.as_deref();
+        let (success, output) = match action(&params, "list") {
+            "list" => {
+                let (success, output) = run_git_command(&["stash", "list"], path).await?;
+                (success, if output.is_empty() { "No stashes".to_string() } else { output })
+            }
+            "push" => {
+                let mut args = vec!["stash", "push"];
+                if params.get("include_untracked").and_then(|v| v.as_bool()).unwrap_or(false) {
+                    args.push("--include-untracked");
+                }
+                if let Some(message) = params.get("message").and_then(|v| v.as_str()) {
+                    args.extend(["-m", message]);
+                }
+                run_git_command(&args, path).await?
+            }
+            "pop" => {
+                let stash = params.get("index").and_then(|v| v.as_u64()).map(|i| format!("stash@{{{}}}", i));
+                let mut args = vec!["stash", "pop"];
+                if let Some(stash) = &stash {
+                    args.push(stash);
+                }
+                run_git_command(&args, path).await?
+            }
+            other => return Ok(ToolResult::failure(format!("Unknown action '{}': use push, list or pop", other))),
+        };
+        if success {
+            Ok(ToolResult::success(output))
+        } else {
+            Ok(ToolResult::failure(output))
+        }
+    }
+
+    async fn simulate(&self, params: Value, overlay: &Overlay) -> Result<Option<ToolResult>> {
+        let text = match action(&params, "list") {
+            "push" => "stash the uncommitted changes".to_string(),
+            "pop" => match params.get("index").and_then(|v| v.as_u64()) {
+                Some(i) => format!("pop stash@{{{}}}", i),
+                None => "pop the latest stash".to_string(),
+            },
+            _ => return Ok(None),
+        };
+        overlay.record(format!("would {}", text));
+        Ok(Some(ToolResult::success(format!("{} Would {}", DRY_RUN_PREFIX, text))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::payload::GitStatusEntry;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_status_data_matches_text() {
+        let dir = tempdir().unwrap();
+        assert!(std::process::Command::new("git")
+            .args(["init", "-q"])
+            .current_dir(dir.path())
+            .status()
+            .unwrap()
+            .success());
+        std::fs::write(dir.path().join("untracked.txt"), "x").unwrap();
+
+        let result = GitStatusTool::new().with_root(dir.path()).execute(json!({})).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.output.trim(), "?? untracked.txt");
+        let status: GitStatus = serde_json::from_value(result.data.unwrap()).unwrap();
+        assert_eq!(
+            status.entries,
+            vec![GitStatusEntry { path: "untracked.txt".into(), from: None, index: '?', worktree: '?' }]
+        );
+    }
+
+    /// 1コミットある一時リポジトリ
+    fn init_repo() -> tempfile::TempDir {
+        let dir = tempdir().unwrap();
+        let git = |args: &[&str]| {
+            assert!(std::process::Command::new("git")
+                .args(args)
+                .current_dir(dir.path())
+                .status()
+                .unwrap()
+                .success());
+        };
+        git(&["init", "-q", "-b", "main"]);
+        git(&["config", "user.email", "test@example.com"]);
+        git(&["config", "user.name", "Test"]);
+        std::fs::write(dir.path().join("a.txt"), "one\n").unwrap();
+        git(&["add", "a.txt"]);
+        git(&["commit", "-q", "-m", "initial"]);
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_branch_list_current_and_create() {
+        let dir = init_repo();
+        let tool = GitBranchTool::new().with_root(dir.path());
+
+        let result = tool.execute(json!({})).await.unwrap();
+        assert_eq!(result.output, "* main");
+        let result = tool.execute(json!({"action": "current"})).await.unwrap();
+        assert_eq!(result.output, "main");
+
+        let result = tool.execute(json!({"action": "create", "name": "feature"})).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        let result = tool.execute(json!({"action": "list"})).await.unwrap();
+        assert_eq!(result.output, "feature\n* main");
+
+        let result = tool.execute(json!({"action": "create", "name": "--force"})).await.unwrap();
+        assert!(!result.success);
+
+        assert_eq!(tool.call_effects(&json!({})), ToolEffects::ReadOnly);
+        assert_eq!(tool.call_effects(&json!({"action": "create", "name": "x"})), ToolEffects::Writes);
+    }
+
+    #[tokio::test]
+    async fn test_checkout_refuses_dirty_tree_unless_forced() {
+        let dir = init_repo();
+        let checkout = GitCheckoutTool::new().with_root(dir.path());
+        let current = || async { GitBranchTool::new().with_root(dir.path()).execute(json!({"action": "current"})).await.unwrap().output };
+
+        let result = checkout.execute(json!({"branch": "topic", "create": true})).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(current().await, "topic");
+
+        std::fs::write(dir.path().join("a.txt"), "changed\n").unwrap();
+        let result = checkout.execute(json!({"branch": "main"})).await.unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("uncommitted changes"));
+        assert_eq!(current().await, "topic");
+
+        let result = checkout.execute(json!({"branch": "main", "force": true})).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(current().await, "main");
+        assert_eq!(std::fs::read_to_string(dir.path().join("a.txt")).unwrap(), "changed\n");
+    }
+
+    #[tokio::test]
+    async fn test_stash_push_list_pop() {
+        let dir = init_repo();
+        let stash = GitStashTool::new().with_root(dir.path());
+
+        assert_eq!(stash.execute(json!({})).await.unwrap().output, "No stashes");
+        std::fs::write(dir.path().join("a.txt"), "wip\n").unwrap();
+        let result = stash.execute(json!({"action": "push", "message": "wip on a"})).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(std::fs::read_to_string(dir.path().join("a.txt")).unwrap(), "one\n");
+
+        let result = stash.execute(json!({"action": "list"})).await.unwrap();
+        assert!(result.output.starts_with("stash@{0}:"), "{}", result.output);
+        assert!(result.output.contains("wip on a"));
+
+        let result = stash.execute(json!({"action": "pop"})).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(std::fs::read_to_string(dir.path().join("a.txt")).unwrap(), "wip\n");
+        assert_eq!(stash.execute(json!({})).await.unwrap().output, "No stashes");
+
+        assert_eq!(stash.call_effects(&json!({"action": "list"})), ToolEffects::ReadOnly);
+        assert_eq!(stash.call_effects(&json!({"action": "pop"})), ToolEffects::Writes);
+    }
+
+    /// `init_repo` に2つ目のコミット（a.txt を書き換え）を足す
+    fn commit_change(dir: &std::path::Path, content: &str, message: &str) {
+        std::fs::write(dir.join("a.txt"), content).unwrap();
+        for args in [&["add", "a.txt"][..], &["commit", "-q", "-m", message]] {
+            assert!(std::process::Command::new("git").args(args).current_dir(dir).status().unwrap().success());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_show_commit_and_file_at_ref() {
+        let dir = init_repo();
+        commit_change(dir.path(), "one\ntwo\n", "add second line");
+        let tool = GitShowTool::new().with_root(dir.path());
+
+        let result = tool.execute(json!({"ref": "HEAD"})).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert!(result.output.contains("add second line"), "{}", result.output);
+        assert!(result.output.contains("+two"), "{}", result.output);
+
+        let result = tool.execute(json!({"ref": "HEAD~1", "file": "a.txt"})).await.unwrap();
+        assert_eq!(result.output, "one");
+
+        let result = tool.execute(json!({"ref": "no-such-ref"})).await.unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("no-such-ref"));
+        let result = tool.execute(json!({"ref": "--output=x"})).await.unwrap();
+        assert!(!result.success);
+    }
+
+    #[tokio::test]
+    async fn test_show_long_output_gets_truncation_marker() {
+        let dir = init_repo();
+        let content: String = (1..=500).map(|i| format!("line {}\n", i)).collect();
+        commit_change(dir.path(), &content, "long file");
+
+        let result = GitShowTool::new().with_root(dir.path())
+            .execute(json!({"ref": "HEAD", "file": "a.txt"}))
+            .await
+            .unwrap();
+        let shown = crate::agent::ToolOutputManager::new(100, 0).limit("t1", &result.output);
+        assert!(shown.contains("[... 400 lines omitted — full output id=t1"), "{}", shown);
+    }
+
+    #[tokio::test]
+    async fn test_blame_line_range() {
+        let dir = init_repo();
+        commit_change(dir.path(), "one\ntwo\nthree\n", "add lines");
+        let tool = GitBlameTool::new().with_root(dir.path());
+
+        let result = tool.execute(json!({"file": "a.txt", "start_line": 2, "end_line": 3})).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        let lines: Vec<&str> = result.output.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", result.output);
+        assert!(lines[0].contains("(Test ") && lines[0].ends_with(") two"), "{}", lines[0]);
+        // --date=short の日付
+        assert!(lines[0].split_whitespace().any(|w| chrono::NaiveDate::parse_from_str(w, "%Y-%m-%d").is_ok()), "{}", lines[0]);
+
+        let whole = tool.execute(json!({"file": "a.txt"})).await.unwrap();
+        assert_eq!(whole.output.lines().count(), 3);
+
+        let result = tool.execute(json!({"file": "a.txt", "start_line": 3, "end_line": 2})).await.unwrap();
+        assert!(!result.success);
+        let result = tool.execute(json!({"file": "missing.txt"})).await.unwrap();
+        assert!(!result.success);
+    }
+}
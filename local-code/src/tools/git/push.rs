@@ -0,0 +1,359 @@
+//! リモートへの push
+//!
+//! 実行前に push されるコミットの一覧を確認ダイアログに出す。
+//! main/master への強制 push は `allow_protected` を指定しない限り拒否する
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::operations::{repo_path, run_git_command};
+use crate::tools::dry_run::DRY_RUN_PREFIX;
+use crate::tools::{Overlay, Tool, ToolEffects, ToolResult};
+
+/// 強制 push から守るブランチ
+const PROTECTED_BRANCHES: &[&str] = &["main", "master"];
+
+/// 確認に出すコミットの最大数
+const MAX_LISTED_COMMITS: usize = 20;
+
+/// push 前の確認のコールバック（push される内容の要約を受け取り、許可なら true）
+pub type PushConfirmer = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// push の引数
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PushRequest {
+    remote: String,
+    branch: String,
+    set_upstream: bool,
+    force: bool,
+}
+
+impl PushRequest {
+    /// `git` に渡す引数（強制 push は相手側が想定外に進んでいれば失敗する --force-with-lease）
+    fn args(&self) -> Vec<String> {
+        let mut args = vec!["push".to_string()];
+        if self.set_upstream {
+            args.push("--set-upstream".to_string());
+        }
+        if self.force {
+            args.push("--force-with-lease".to_string());
+        }
+        args.push(self.remote.clone());
+        args.push(self.branch.clone());
+        args
+    }
+
+    /// 保護されたブランチへの強制 push なら拒否理由
+    fn protected_branch_error(&self, allow_protected: bool) -> Option<String> {
+        let protected = PROTECTED_BRANCHES.contains(&self.branch.as_str());
+        (self.force && protected && !allow_protected).then(|| {
+            format!(
+                "Refusing to force-push to protected branch '{}'. Ask the user first, then retry with allow_protected: true",
+                self.branch
+            )
+        })
+    }
+}
+
+/// リモート名・ブランチ名として受け付けるか（オプションと取り違えないよう `-` で始まる名前は拒否）
+fn check_name(kind: &str, name: &str) -> std::result::Result<(), String> {
+    if name.trim().is_empty() || name.starts_with('-') {
+        Err(format!("Invalid {} name '{}'", kind, name))
+    } else {
+        Ok(())
+    }
+}
+
+/// Git push ツール
+pub struct GitPushTool {
+    root: Option<PathBuf>,
+    /// push 前の確認（対話実行時と --yes のときに設定）
+    confirm: Option<PushConfirmer>,
+}
+
+impl GitPushTool {
+    pub fn new() -> Self {
+        Self { root: None, confirm: None }
+    }
+
+    /// path 未指定時に使うリポジトリルートを設定
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+
+    /// push 前に確認する
+    pub fn with_confirm(mut self, confirm: PushConfirmer) -> Self {
+        self.confirm = Some(confirm);
+        self
+    }
+
+    /// 確認に出す要約（状態と、push されるコミット）
+    async fn summary(&self, request: &PushRequest, path: Option<&str>) -> Result<String> {
+        let mut lines = vec![format!(
+            "Push {} to {}{}{}",
+            request.branch,
+            request.remote,
+            if request.set_upstream { " (set upstream)" } else { "" },
+            if request.force { " (FORCE, with lease)" } else { "" },
+        )];
+
+        let (_, status) = run_git_command(&["status", "--short", "--branch"], path).await?;
+        if let Some(head) = status.lines().next() {
+            lines.push(head.to_string());
+        }
+        if status.lines().count() > 1 {
+            lines.push("(uncommitted changes are not pushed)".to_string());
+        }
+
+        // 既に upstream があればそこから、なければリモートの同名ブランチからの差分
+        let upstream = format!("{}@{{u}}", request.branch);
+        let remote_branch = format!("{}/{}", request.remote, request.branch);
+        let mut base = None;
+        for candidate in [&upstream, &remote_branch] {
+            let (exists, _) = run_git_command(&["rev-parse", "--verify", "--quiet", candidate], path).await?;
+            if exists {
+                base = Some(candidate.as_str());
+                break;
+            }
+        }
+        let max = format!("-{}", MAX_LISTED_COMMITS);
+        let (range, header) = match base {
+            Some(base) => (format!("{}..{}", base, request.branch), "Commits to push:".to_string()),
+            None => (
+                request.branch.clone(),
+                format!("New branch on {}; latest commits:", request.remote),
+            ),
+        };
+        let (ok, log) = run_git_command(&["log", "--oneline", &max, &range, "--"], path).await?;
+        lines.push(String::new());
+        lines.push(header);
+        lines.push(match (ok, log.is_empty()) {
+            (true, true) => "(none)".to_string(),
+            _ => log,
+        });
+        Ok(lines.join("\n"))
+    }
+}
+
+impl Default for GitPushTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Tool for GitPushTool {
+    fn name(&self) -> &str { "git_push" }
+    fn description(&self) -> &str {
+        "Push a branch to a remote (asks the user first). Force pushes use --force-with-lease and are refused for main/master unless allow_protected is true"
+    }
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Repository path (defaults to the project repository)" },
+                "remote": { "type": "string", "description": "Remote to push to (default: origin)" },
+                "branch": { "type": "string", "description": "Branch to push (default: the current branch)" },
+                "set_upstream": { "type": "boolean", "description": "Set the pushed branch as upstream (use for new branches)" },
+                "force": { "type": "boolean", "description": "Overwrite the remote branch (--force-with-lease)" },
+                "allow_protected": { "type": "boolean", "description": "Allow a force push to main/master; only when the user asked for it" }
+            }
+        })
+    }
+    fn effects(&self) -> ToolEffects {
+        // ネットワークに出て、pre-push フックが任意のコマンドを実行しうる
+        ToolEffects::Executes
+    }
+    fn confirms_itself(&self) -> bool {
+        self.confirm.is_some()
+    }
+
+    async fn execute(&self, params: Value) -> Result<ToolResult> {
+        let path = repo_path(&params, &self.root);
+        let path = path.as_deref();
+        let remote = params.get("remote").and_then(|v| v.as_str()).unwrap_or("origin");
+        let branch = match params.get("branch").and_then(|v| v.as_str()) {
+            Some(branch) => branch.to_string(),
+            None => {
+                let (success, current) = run_git_command(&["branch", "--show-current"], path).await?;
+                if !success {
+                    return Ok(ToolResult::failure(current));
+                }
+                if current.is_empty() {
+                    return Ok(ToolResult::failure("HEAD is detached; pass the branch to push"));
+                }
+                current
+            }
+        };
+        if let Err(e) = check_name("remote", remote).and_then(|_| check_name("branch", &branch)) {
+            return Ok(ToolResult::failure(e));
+        }
+
+        let request = PushRequest {
+            remote: remote.to_string(),
+            branch,
+            set_upstream: params.get("set_upstream").and_then(|v| v.as_bool()).unwrap_or(false),
+            force: params.get("force").and_then(|v| v.as_bool()).unwrap_or(false),
+        };
+        let allow_protected = params.get("allow_protected").and_then(|v| v.as_bool()).unwrap_or(false);
+        if let Some(e) = request.protected_branch_error(allow_protected) {
+            return Ok(ToolResult::failure(e));
+        }
+
+        if let Some(confirm) = &self.confirm {
+            let confirm = Arc::clone(confirm);
+            let details = self.summary(&request, path).await?;
+            let approved = tokio::task::spawn_blocking(move || confirm(&details)).await?;
+            if !approved {
+                return Ok(ToolResult::failure(format!(
+                    "Push of {} to {} was denied by the user",
+                    request.branch, request.remote
+                )));
+            }
+        }
+
+        let args = request.args();
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let (success, output) = run_git_command(&args, path).await?;
+        if success {
+            Ok(ToolResult::success(output))
+        } else {
+            Ok(ToolResult::failure(output))
+        }
+    }
+
+    async fn simulate(&self, params: Value, overlay: &Overlay) -> Result<Option<ToolResult>> {
+        let remote = params.get("remote").and_then(|v| v.as_str()).unwrap_or("origin");
+        let branch = params.get("branch").and_then(|v| v.as_str()).unwrap_or("the current branch");
+        overlay.record(format!("would push {} to {}", branch, remote));
+        Ok(Some(ToolResult::success(format!("{} Would push {} to {}", DRY_RUN_PREFIX, branch, remote))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use std::sync::Mutex;
+    use tempfile::tempdir;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = std::process::Command::new("git").args(args).current_dir(dir).status().unwrap();
+        assert!(status.success(), "git {:?}", args);
+    }
+
+    /// bare リポジトリの origin と、1コミットを push 済みのクローン
+    fn repo_with_origin() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempdir().unwrap();
+        let origin = dir.path().join("origin.git");
+        let work = dir.path().join("work");
+        git(dir.path(), &["init", "-q", "--bare", "-b", "main", origin.to_str().unwrap()]);
+        git(dir.path(), &["init", "-q", "-b", "main", work.to_str().unwrap()]);
+        git(&work, &["config", "user.email", "test@example.com"]);
+        git(&work, &["config", "user.name", "Test"]);
+        git(&work, &["remote", "add", "origin", origin.to_str().unwrap()]);
+        git(&work, &["commit", "-q", "--allow-empty", "-m", "initial"]);
+        git(&work, &["push", "-q", "-u", "origin", "main"]);
+        (dir, work)
+    }
+
+    fn recording_confirmer(answer: bool) -> (PushConfirmer, Arc<Mutex<Vec<String>>>) {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&seen);
+        let confirm: PushConfirmer = Arc::new(move |details: &str| {
+            recorded.lock().unwrap().push(details.to_string());
+            answer
+        });
+        (confirm, seen)
+    }
+
+    #[test]
+    fn test_push_arguments() {
+        let mut request = PushRequest {
+            remote: "origin".to_string(),
+            branch: "feature".to_string(),
+            set_upstream: false,
+            force: false,
+        };
+        assert_eq!(request.args(), ["push", "origin", "feature"]);
+        request.set_upstream = true;
+        request.force = true;
+        assert_eq!(request.args(), ["push", "--set-upstream", "--force-with-lease", "origin", "feature"]);
+    }
+
+    #[test]
+    fn test_protected_branch_guard() {
+        let request = |branch: &str, force: bool| PushRequest {
+            remote: "origin".to_string(),
+            branch: branch.to_string(),
+            set_upstream: false,
+            force,
+        };
+        assert!(request("main", true).protected_branch_error(false).is_some());
+        assert!(request("master", true).protected_branch_error(false).is_some());
+        assert!(request("main", true).protected_branch_error(true).is_none());
+        assert!(request("main", false).protected_branch_error(false).is_none());
+        assert!(request("feature", true).protected_branch_error(false).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_force_push_to_main_is_refused_before_confirmation() {
+        let (_dir, work) = repo_with_origin();
+        let (confirm, seen) = recording_confirmer(true);
+        let tool = GitPushTool::new().with_root(&work).with_confirm(confirm);
+
+        let result = tool.execute(json!({"force": true})).await.unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("protected branch 'main'"));
+        assert!(seen.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_confirmation_lists_commits_to_push() {
+        let (_dir, work) = repo_with_origin();
+        git(&work, &["commit", "-q", "--allow-empty", "-m", "fix the parser"]);
+
+        let (confirm, seen) = recording_confirmer(false);
+        let tool = GitPushTool::new().with_root(&work).with_confirm(confirm);
+        let result = tool.execute(json!({})).await.unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("denied by the user"));
+        let details = seen.lock().unwrap()[0].clone();
+        assert!(details.starts_with("Push main to origin"), "{}", details);
+        assert!(details.contains("Commits to push:\n"), "{}", details);
+        assert!(details.contains("fix the parser"), "{}", details);
+        assert!(!details.contains("initial"), "{}", details);
+
+        let (confirm, _) = recording_confirmer(true);
+        let tool = GitPushTool::new().with_root(&work).with_confirm(confirm);
+        let result = tool.execute(json!({})).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+    }
+
+    #[tokio::test]
+    async fn test_new_branch_and_rejected_push() {
+        let (dir, work) = repo_with_origin();
+        git(&work, &["checkout", "-q", "-b", "topic"]);
+        git(&work, &["commit", "-q", "--allow-empty", "-m", "topic work"]);
+        let (confirm, seen) = recording_confirmer(true);
+        let tool = GitPushTool::new().with_root(&work).with_confirm(confirm);
+        let result = tool.execute(json!({"set_upstream": true})).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert!(seen.lock().unwrap()[0].contains("New branch on origin"));
+
+        // 別のクローンから先に push されていれば non-fast-forward で失敗する
+        let other = dir.path().join("other");
+        git(dir.path(), &["clone", "-q", dir.path().join("origin.git").to_str().unwrap(), other.to_str().unwrap()]);
+        git(&other, &["-c", "user.email=o@example.com", "-c", "user.name=O", "commit", "-q", "--allow-empty", "-m", "other"]);
+        git(&other, &["push", "-q", "origin", "main"]);
+        git(&work, &["checkout", "-q", "main"]);
+        git(&work, &["commit", "-q", "--allow-empty", "-m", "diverged"]);
+        let result = tool.execute(json!({})).await.unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("rejected"));
+    }
+}
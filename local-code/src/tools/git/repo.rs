@@ -0,0 +1,150 @@
+//! リポジトリ解決
+//!
+//! プロセスのカレントディレクトリではなく、プロジェクトルートを所有する
+//! リポジトリを明示的に解決する。リポジトリ内に別のリポジトリが
+//! チェックアウトされている場合（vendor/ 等）はネストしたリポジトリとして検出する
+
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+/// ネストしたリポジトリの探索で降りないディレクトリ
+const SKIP_DIRS: &[&str] = &[".git", "target", "node_modules"];
+
+/// 探索する最大深さ
+const MAX_DEPTH: usize = 8;
+
+/// リポジトリ情報
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepoInfo {
+    /// リポジトリのトップレベル
+    pub root: PathBuf,
+}
+
+impl RepoInfo {
+    /// 指定ディレクトリを所有するリポジトリを解決
+    ///
+    /// `git rev-parse --show-toplevel` を指定ディレクトリで実行する
+    pub fn resolve(dir: &Path) -> Option<Self> {
+        let output = Command::new("git")
+            .args(["rev-parse", "--show-toplevel"])
+            .current_dir(dir)
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+
+        let root = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if root.is_empty() {
+            return None;
+        }
+        let root = PathBuf::from(root);
+        Some(Self {
+            root: root.canonicalize().unwrap_or(root),
+        })
+    }
+}
+
+/// ディレクトリがリポジトリのルートか（.git はディレクトリまたはファイル）
+fn has_git_marker(dir: &Path) -> bool {
+    dir.join(".git").exists()
+}
+
+/// root 配下にあるネストしたリポジトリを探す（root 自身は含まない）
+///
+/// 見つかったリポジトリの中には降りない
+pub fn find_nested_repos(root: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    walk(root, 0, &mut found);
+    found.sort();
+    found
+}
+
+fn walk(dir: &Path, depth: usize, found: &mut Vec<PathBuf>) {
+    if depth >= MAX_DEPTH {
+        return;
+    }
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if !file_type.is_dir() {
+            continue;
+        }
+        let name = entry.file_name();
+        if SKIP_DIRS.iter().any(|s| name == *s) {
+            continue;
+        }
+
+        let path = entry.path();
+        if has_git_marker(&path) {
+            found.push(path);
+        } else {
+            walk(&path, depth + 1, found);
+        }
+    }
+}
+
+/// パスがネストしたリポジトリ配下にあるか
+pub fn is_in_nested_repo(path: &Path, nested: &[PathBuf]) -> bool {
+    let path = without_cur_dir(path);
+    nested.iter().any(|repo| path.starts_with(without_cur_dir(repo)))
+}
+
+/// "./" 表記の違いを吸収する
+fn without_cur_dir(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn git_init(dir: &Path) {
+        std::fs::create_dir_all(dir).unwrap();
+        let status = Command::new("git")
+            .args(["init", "-q"])
+            .current_dir(dir)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    #[test]
+    fn test_resolve_owning_repo() {
+        let dir = tempdir().unwrap();
+        let outer = dir.path().join("mono");
+        let nested = outer.join("vendor").join("foo");
+        git_init(&outer);
+        git_init(&nested);
+        std::fs::create_dir_all(outer.join("src")).unwrap();
+
+        let outer_root = outer.canonicalize().unwrap();
+        let nested_root = nested.canonicalize().unwrap();
+        assert_eq!(RepoInfo::resolve(&outer.join("src")).unwrap().root, outer_root);
+        assert_eq!(RepoInfo::resolve(&nested).unwrap().root, nested_root);
+    }
+
+    #[test]
+    fn test_find_nested_repos() {
+        let dir = tempdir().unwrap();
+        let outer = dir.path().join("mono");
+        git_init(&outer);
+        git_init(&outer.join("vendor").join("foo"));
+        git_init(&outer.join("vendor").join("foo").join("deeper"));
+        std::fs::create_dir_all(outer.join("src")).unwrap();
+
+        let nested = find_nested_repos(&outer);
+        assert_eq!(nested, vec![outer.join("vendor").join("foo")]);
+        assert!(is_in_nested_repo(&outer.join("vendor/foo/lib.rs"), &nested));
+        assert!(!is_in_nested_repo(&outer.join("src/main.rs"), &nested));
+        assert!(is_in_nested_repo(Path::new("vendor/foo/a.rs"), &[PathBuf::from("./vendor/foo")]));
+    }
+}
@@ -4,24 +4,69 @@ use lsp_types::{
     ClientCapabilities, Url, TextDocumentIdentifier,
     Position, GotoDefinitionParams, GotoDefinitionResponse,
     ReferenceParams, ReferenceContext, Location,
-    TextDocumentPositionParams,
+    TextDocumentPositionParams, Hover, HoverParams,
+    WorkspaceSymbolParams, WorkspaceSymbolResponse, DocumentSymbolParams, DocumentSymbolResponse,
+    TextDocumentClientCapabilities, DocumentSymbolClientCapabilities,
+    RenameParams, WorkspaceEdit, WorkspaceClientCapabilities, WorkspaceEditClientCapabilities,
+    Diagnostic, PublishDiagnosticsParams, ServerCapabilities,
 };
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::process::{Child, Command};
-use tokio::sync::Mutex;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::{broadcast, oneshot, Mutex};
+use tokio::task::JoinHandle;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
+/// リクエストの応答を待つ時間の既定値（`[lsp] request_timeout`）
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
 /// LSPクライアント
+///
+/// stdout は起動時に読み取りタスクへ渡し、応答は id で待っているリクエストへ、
+/// 通知は broadcast チャンネルへ振り分ける
 pub struct LspClient {
     process: Mutex<Child>,
-    request_id: Mutex<i64>,
-    #[allow(dead_code)]
-    pending_responses: Mutex<HashMap<i64, tokio::sync::oneshot::Sender<Value>>>,
+    stdin: Arc<Mutex<ChildStdin>>,
+    request_id: AtomicI64,
+    inbox: Arc<Inbox>,
+    reader: JoinHandle<()>,
+    /// didOpen で送った内容（URI -> バージョンと本文）
+    documents: Mutex<HashMap<Url, OpenDocument>>,
+    /// initialize で受け取ったサーバーの機能
+    capabilities: Mutex<Option<ServerCapabilities>>,
+    request_timeout: Duration,
+}
+
+/// 読み取りタスクと共有する状態
+struct Inbox {
+    /// 応答を待っているリクエスト（id -> 送り先）
+    pending_responses: Mutex<HashMap<i64, oneshot::Sender<JsonRpcResponse>>>,
+    /// publishDiagnostics で届いた最新の診断（URI ごと）
+    diagnostics: Mutex<HashMap<Url, Vec<Diagnostic>>>,
+    notifications: broadcast::Sender<LspNotification>,
+    /// パイプが切れた（EOF・書き込み失敗）ら立てる。以降のリクエストはすぐ失敗させる
+    exited: AtomicBool,
+}
+
+/// 言語サーバーからの通知（window/logMessage・$/progress・publishDiagnostics など）
+#[derive(Debug, Clone)]
+pub struct LspNotification {
+    pub method: String,
+    pub params: Value,
+}
+
+/// 言語サーバーに渡したドキュメント
+#[derive(Debug, Clone)]
+pub struct OpenDocument {
+    pub version: i32,
+    pub text: String,
 }
 
 #[derive(Serialize)]
@@ -32,10 +77,18 @@ struct JsonRpcRequest {
     params: Value,
 }
 
+/// 言語サーバーから届いたメッセージ（応答・通知・サーバーからのリクエスト）
 #[derive(Deserialize)]
+struct JsonRpcMessage {
+    id: Option<Value>,
+    method: Option<String>,
+    #[serde(default)]
+    params: Value,
+    result: Option<Value>,
+    error: Option<Value>,
+}
+
 struct JsonRpcResponse {
-    #[allow(dead_code)]
-    id: Option<i64>,
     result: Option<Value>,
     error: Option<Value>,
 }
@@ -43,20 +96,77 @@ struct JsonRpcResponse {
 impl LspClient {
     /// 言語サーバープロセスを起動してクライアントを作成
     pub async fn start(command: &str, args: &[&str]) -> Result<Self> {
-        let process = Command::new(command)
+        let mut process = Command::new(command)
             .args(args)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()?;
 
+        let stdin = process.stdin.take().ok_or_else(|| anyhow::anyhow!("No stdin"))?;
+        let stdout = process.stdout.take().ok_or_else(|| anyhow::anyhow!("No stdout"))?;
+        // 読まずにおくとパイプが詰まってサーバーが止まるので、ログに流しておく
+        if let Some(stderr) = process.stderr.take() {
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    tracing::debug!("lsp stderr: {}", line);
+                }
+            });
+        }
+
+        let stdin = Arc::new(Mutex::new(stdin));
+        let inbox = Arc::new(Inbox {
+            pending_responses: Mutex::new(HashMap::new()),
+            diagnostics: Mutex::new(HashMap::new()),
+            notifications: broadcast::channel(256).0,
+            exited: AtomicBool::new(false),
+        });
+        let reader = tokio::spawn(read_loop(stdout, Arc::clone(&stdin), Arc::clone(&inbox)));
+
         Ok(Self {
             process: Mutex::new(process),
-            request_id: Mutex::new(0),
-            pending_responses: Mutex::new(HashMap::new()),
+            stdin,
+            request_id: AtomicI64::new(0),
+            inbox,
+            reader,
+            documents: Mutex::new(HashMap::new()),
+            capabilities: Mutex::new(None),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
         })
     }
 
+    /// 応答を待つ時間（`[lsp] request_timeout`）
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// 言語サーバープロセスが動作中か（パイプが切れていれば終了扱い）
+    pub async fn is_running(&self) -> bool {
+        !self.inbox.exited.load(Ordering::SeqCst) && matches!(self.process.lock().await.try_wait(), Ok(None))
+    }
+
+    /// サーバーからの通知を受け取る
+    pub fn subscribe(&self) -> broadcast::Receiver<LspNotification> {
+        self.inbox.notifications.subscribe()
+    }
+
+    /// プロセスを強制終了する（応答しなくなったサーバーの再起動用）
+    pub async fn kill(&self) {
+        self.inbox.exited.store(true, Ordering::SeqCst);
+        if let Err(e) = self.process.lock().await.kill().await {
+            tracing::debug!("Failed to kill language server: {}", e);
+        }
+    }
+
+    /// didOpen で開いたファイル（再起動後に開き直す）
+    pub async fn open_paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self.documents.lock().await.keys().filter_map(|uri| uri.to_file_path().ok()).collect();
+        paths.sort();
+        paths
+    }
+
     /// LSPサーバーを初期化
     pub async fn initialize(&self, root_path: &Path) -> Result<InitializeResult> {
         let root_uri = Url::from_file_path(root_path)
@@ -65,11 +175,30 @@ impl LspClient {
         #[allow(deprecated)]
         let params = InitializeParams {
             root_uri: Some(root_uri),
-            capabilities: ClientCapabilities::default(),
+            capabilities: ClientCapabilities {
+                // lsp_rename でバージョン付きの編集を受け取る
+                workspace: Some(WorkspaceClientCapabilities {
+                    workspace_edit: Some(WorkspaceEditClientCapabilities {
+                        document_changes: Some(true),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                // lsp_outline 用に入れ子の DocumentSymbol を受け取る
+                text_document: Some(TextDocumentClientCapabilities {
+                    document_symbol: Some(DocumentSymbolClientCapabilities {
+                        hierarchical_document_symbol_support: Some(true),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
             ..Default::default()
         };
 
         let result: InitializeResult = self.request("initialize", serde_json::to_value(params)?).await?;
+        *self.capabilities.lock().await = Some(result.capabilities.clone());
 
         // initialized通知を送信
         self.notify("initialized", serde_json::to_value(InitializedParams {})?).await?;
@@ -78,22 +207,70 @@ impl LspClient {
     }
 
     /// ドキュメントを開く（didOpen通知）
+    ///
+    /// すでに開いていれば、ディスクの内容が変わったときだけ didChange を送る
     pub async fn did_open(&self, file_path: &Path) -> Result<()> {
+        self.sync_document(file_path, true).await
+    }
+
+    /// 開いているドキュメントの内容をディスクに合わせる（didChange通知、開いていなければ何もしない）
+    pub async fn did_change(&self, file_path: &Path) -> Result<()> {
+        self.sync_document(file_path, false).await
+    }
+
+    /// ドキュメントを閉じる（didClose通知、開いていなければ何もしない）
+    pub async fn did_close(&self, file_path: &Path) -> Result<()> {
+        let uri = Url::from_file_path(file_path)
+            .map_err(|_| anyhow::anyhow!("Invalid path"))?;
+        if self.documents.lock().await.remove(&uri).is_none() {
+            return Ok(());
+        }
+        self.notify("textDocument/didClose", json!({ "textDocument": { "uri": uri } })).await
+    }
+
+    async fn sync_document(&self, file_path: &Path, open: bool) -> Result<()> {
         let uri = Url::from_file_path(file_path)
             .map_err(|_| anyhow::anyhow!("Invalid path"))?;
+        let current = self.documents.lock().await.get(&uri).map(|document| (document.version, document.text.clone()));
+        if current.is_none() && !open {
+            return Ok(());
+        }
         let text = fs::read_to_string(file_path).await?;
-        let language_id = Self::language_id_for_path(file_path);
 
-        let params = json!({
-            "textDocument": {
-                "uri": uri,
-                "languageId": language_id,
-                "version": 1,
-                "text": text,
+        let (method, params, version) = match current {
+            Some((_, old)) if old == text => return Ok(()),
+            // 全文を送る（差分の位置計算はしない）
+            Some((version, _)) => {
+                let version = version + 1;
+                let params = json!({
+                    "textDocument": { "uri": uri, "version": version },
+                    "contentChanges": [{ "text": text }],
+                });
+                ("textDocument/didChange", params, version)
             }
-        });
+            None => {
+                let version = 1;
+                let params = json!({
+                    "textDocument": {
+                        "uri": uri,
+                        "languageId": Self::language_id_for_path(file_path),
+                        "version": version,
+                        "text": text,
+                    }
+                });
+                ("textDocument/didOpen", params, version)
+            }
+        };
 
-        self.notify("textDocument/didOpen", params).await
+        self.notify(method, params).await?;
+        self.documents.lock().await.insert(uri, OpenDocument { version, text });
+        Ok(())
+    }
+
+    /// didOpen で渡した内容（開いていなければ None）
+    pub async fn open_document(&self, file_path: &Path) -> Option<OpenDocument> {
+        let uri = Url::from_file_path(file_path).ok()?;
+        self.documents.lock().await.get(&uri).cloned()
     }
 
     /// 定義ジャンプ
@@ -133,6 +310,95 @@ impl LspClient {
         self.request("textDocument/references", serde_json::to_value(params)?).await
     }
 
+    /// ホバー情報（型・シグネチャ・ドキュメント）
+    pub async fn hover(&self, file_path: &Path, line: u32, character: u32) -> Result<Option<Hover>> {
+        let uri = Url::from_file_path(file_path)
+            .map_err(|_| anyhow::anyhow!("Invalid path"))?;
+
+        let params = HoverParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: Position { line, character },
+            },
+            work_done_progress_params: Default::default(),
+        };
+
+        self.request("textDocument/hover", serde_json::to_value(params)?).await
+    }
+
+    /// ワークスペース全体からシンボルを検索
+    pub async fn workspace_symbols(&self, query: &str) -> Result<Option<WorkspaceSymbolResponse>> {
+        let params = WorkspaceSymbolParams {
+            query: query.to_string(),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+
+        self.request("workspace/symbol", serde_json::to_value(params)?).await
+    }
+
+    /// ファイル内のシンボル
+    pub async fn document_symbols(&self, file_path: &Path) -> Result<Option<DocumentSymbolResponse>> {
+        let uri = Url::from_file_path(file_path)
+            .map_err(|_| anyhow::anyhow!("Invalid path"))?;
+
+        let params = DocumentSymbolParams {
+            text_document: TextDocumentIdentifier { uri },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+
+        self.request("textDocument/documentSymbol", serde_json::to_value(params)?).await
+    }
+
+    /// シンボル名の変更（適用する編集を返すだけで、ファイルは変更しない）
+    pub async fn rename(&self, file_path: &Path, line: u32, character: u32, new_name: &str) -> Result<Option<WorkspaceEdit>> {
+        let uri = Url::from_file_path(file_path)
+            .map_err(|_| anyhow::anyhow!("Invalid path"))?;
+
+        let params = RenameParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: Position { line, character },
+            },
+            new_name: new_name.to_string(),
+            work_done_progress_params: Default::default(),
+        };
+
+        self.request("textDocument/rename", serde_json::to_value(params)?).await
+    }
+
+    /// textDocument/diagnostic（pull diagnostics）に対応したサーバーか
+    pub async fn supports_pull_diagnostics(&self) -> bool {
+        self.capabilities.lock().await.as_ref().is_some_and(|caps| caps.diagnostic_provider.is_some())
+    }
+
+    /// publishDiagnostics で届いた診断（まだ届いていなければ `wait` まで待つ）
+    pub async fn published_diagnostics(&self, file_path: &Path, wait: Duration) -> Option<Vec<Diagnostic>> {
+        let uri = Url::from_file_path(file_path).ok()?;
+        // 先に購読してから確認する（確認と購読の間に届いた通知も取りこぼさない）
+        let mut notifications = self.subscribe();
+        if let Some(diagnostics) = self.inbox.diagnostics.lock().await.get(&uri) {
+            return Some(diagnostics.clone());
+        }
+        let published = async {
+            loop {
+                match notifications.recv().await {
+                    Ok(notification) if notification.method == "textDocument/publishDiagnostics" => {
+                        if let Ok(params) = serde_json::from_value::<PublishDiagnosticsParams>(notification.params) {
+                            if params.uri == uri {
+                                return Some(params.diagnostics);
+                            }
+                        }
+                    }
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        };
+        tokio::time::timeout(wait, published).await.ok().flatten()
+    }
+
     /// 診断情報を取得（pull diagnostics）
     pub async fn document_diagnostics(&self, file_path: &Path) -> Result<Value> {
         let uri = Url::from_file_path(file_path)
@@ -155,11 +421,7 @@ impl LspClient {
     }
 
     async fn request<T: for<'de> Deserialize<'de>>(&self, method: &str, params: Value) -> Result<T> {
-        let id = {
-            let mut id_guard = self.request_id.lock().await;
-            *id_guard += 1;
-            *id_guard
-        };
+        let id = self.request_id.fetch_add(1, Ordering::SeqCst) + 1;
 
         let request = JsonRpcRequest {
             jsonrpc: "2.0",
@@ -169,26 +431,35 @@ impl LspClient {
         };
 
         let content = serde_json::to_string(&request)?;
-        let message = format!("Content-Length: {}\r\n\r\n{}", content.len(), content);
-
-        {
-            let mut process = self.process.lock().await;
-            if let Some(stdin) = process.stdin.as_mut() {
-                stdin.write_all(message.as_bytes()).await?;
-                stdin.flush().await?;
-            }
+        let (sender, receiver) = oneshot::channel();
+        self.inbox.pending_responses.lock().await.insert(id, sender);
+        if let Err(e) = self.send(&content).await {
+            self.inbox.pending_responses.lock().await.remove(&id);
+            return Err(e);
         }
 
-        // レスポンス読み取り（簡略化版）
-        let response = self.read_response().await?;
+        // 応答は読み取りタスクが id で振り分ける（終了すると送り先が落ちる）
+        let response = match tokio::time::timeout(self.request_timeout, receiver).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(_)) => return Err(anyhow::anyhow!("Language server has exited")),
+            Err(_) => {
+                self.inbox.pending_responses.lock().await.remove(&id);
+                return Err(anyhow::anyhow!(
+                    "LSP request '{}' timed out after {:?} (raise [lsp] request_timeout if the server is still indexing)",
+                    method,
+                    self.request_timeout
+                ));
+            }
+        };
 
         if let Some(error) = response.error {
             return Err(anyhow::anyhow!("LSP error: {:?}", error));
         }
 
+        // `"result": null`（該当なし）は Option<T> なら None として受け取れる
         match response.result {
             Some(result) => Ok(serde_json::from_value(result)?),
-            None => Err(anyhow::anyhow!("No result in response")),
+            None => serde_json::from_value(Value::Null).map_err(|_| anyhow::anyhow!("No result in response")),
         }
     }
 
@@ -200,50 +471,19 @@ impl LspClient {
         });
 
         let content = serde_json::to_string(&notification)?;
-        let message = format!("Content-Length: {}\r\n\r\n{}", content.len(), content);
-
-        let mut process = self.process.lock().await;
-        if let Some(stdin) = process.stdin.as_mut() {
-            stdin.write_all(message.as_bytes()).await?;
-            stdin.flush().await?;
-        }
-
-        Ok(())
+        self.send(&content).await
     }
 
-    async fn read_response(&self) -> Result<JsonRpcResponse> {
-        let mut process = self.process.lock().await;
-        let stdout = process.stdout.as_mut().ok_or_else(|| anyhow::anyhow!("No stdout"))?;
-        let mut reader = BufReader::new(stdout);
-
-        // ヘッダー読み取り（ログ行は無視してContent-Lengthを待つ）
-        let mut content_length: Option<usize> = None;
-        loop {
-            let mut line = String::new();
-            let bytes_read = reader.read_line(&mut line).await?;
-            if bytes_read == 0 {
-                return Err(anyhow::anyhow!("LSP response EOF"));
-            }
-
-            let line = line.trim();
-            if line.is_empty() {
-                if content_length.is_some() {
-                    break;
-                }
-                continue;
-            }
-            if let Some(length_str) = line.strip_prefix("Content-Length:") {
-                content_length = Some(length_str.trim().parse()?);
-            }
+    /// メッセージを1つ書き込む（パイプが切れていれば終了扱いにする）
+    async fn send(&self, content: &str) -> Result<()> {
+        if self.inbox.exited.load(Ordering::SeqCst) {
+            return Err(anyhow::anyhow!("Language server has exited"));
         }
-
-        // ボディ読み取り
-        let content_length = content_length.ok_or_else(|| anyhow::anyhow!("Missing Content-Length header"))?;
-        let mut body = vec![0u8; content_length];
-        tokio::io::AsyncReadExt::read_exact(&mut reader, &mut body).await?;
-
-        let response: JsonRpcResponse = serde_json::from_slice(&body)?;
-        Ok(response)
+        let written = write_message(&mut *self.stdin.lock().await, content).await;
+        written.map_err(|e| {
+            self.inbox.exited.store(true, Ordering::SeqCst);
+            anyhow::anyhow!("Language server has exited ({})", e)
+        })
     }
 
     fn language_id_for_path(path: &Path) -> &'static str {
@@ -267,3 +507,282 @@ impl LspClient {
         }
     }
 }
+
+impl Drop for LspClient {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+async fn write_message(stdin: &mut ChildStdin, content: &str) -> std::io::Result<()> {
+    let message = format!("Content-Length: {}\r\n\r\n{}", content.len(), content);
+    stdin.write_all(message.as_bytes()).await?;
+    stdin.flush().await
+}
+
+/// stdout からメッセージを読み続け、応答・通知・サーバーからのリクエストに振り分ける
+async fn read_loop(stdout: ChildStdout, stdin: Arc<Mutex<ChildStdin>>, inbox: Arc<Inbox>) {
+    let mut reader = BufReader::new(stdout);
+    loop {
+        let body = match read_message(&mut reader).await {
+            Ok(Some(body)) => body,
+            Ok(None) => break,
+            Err(e) => {
+                tracing::debug!("Failed to read from language server: {}", e);
+                break;
+            }
+        };
+        let message: JsonRpcMessage = match serde_json::from_slice(&body) {
+            Ok(message) => message,
+            Err(e) => {
+                tracing::debug!("Ignoring malformed LSP message: {}", e);
+                continue;
+            }
+        };
+        match (message.method, message.id) {
+            // サーバーからのリクエストには応答しないとサーバー側が待ち続ける
+            (Some(method), Some(id)) => {
+                let result = match method.as_str() {
+                    "workspace/configuration" => {
+                        let items = message.params.get("items").and_then(|v| v.as_array()).map_or(0, |items| items.len());
+                        Value::Array(vec![Value::Null; items])
+                    }
+                    _ => Value::Null,
+                };
+                let reply = json!({"jsonrpc": "2.0", "id": id, "result": result}).to_string();
+                if let Err(e) = write_message(&mut *stdin.lock().await, &reply).await {
+                    tracing::debug!("Failed to answer {} from language server: {}", method, e);
+                }
+            }
+            (Some(method), None) => {
+                if method == "textDocument/publishDiagnostics" {
+                    if let Ok(params) = serde_json::from_value::<PublishDiagnosticsParams>(message.params.clone()) {
+                        inbox.diagnostics.lock().await.insert(params.uri, params.diagnostics);
+                    }
+                }
+                // 購読者がいなければ捨てる
+                let _ = inbox.notifications.send(LspNotification { method, params: message.params });
+            }
+            (None, Some(id)) => {
+                let sender = match id.as_i64() {
+                    Some(id) => inbox.pending_responses.lock().await.remove(&id),
+                    None => None,
+                };
+                match sender {
+                    Some(sender) => {
+                        let _ = sender.send(JsonRpcResponse { result: message.result, error: message.error });
+                    }
+                    // タイムアウトしたリクエストへの遅れた応答
+                    None => tracing::debug!("Dropping LSP response for unknown id {}", id),
+                }
+            }
+            (None, None) => tracing::debug!("Ignoring LSP message without id or method"),
+        }
+    }
+
+    inbox.exited.store(true, Ordering::SeqCst);
+    // 応答を待っているリクエストは送り先を落として終了を知らせる
+    inbox.pending_responses.lock().await.clear();
+}
+
+/// メッセージの本文を1つ読む（EOF なら None）
+async fn read_message<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+    // ヘッダー読み取り（ログ行は無視してContent-Lengthを待つ）
+    let mut content_length: Option<usize> = None;
+    loop {
+        let mut line = String::new();
+        let bytes_read = reader.read_line(&mut line).await?;
+        if bytes_read == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim();
+        if line.is_empty() {
+            if content_length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some(length_str) = line.strip_prefix("Content-Length:") {
+            content_length = Some(length_str.trim().parse()?);
+        }
+    }
+
+    // ボディ読み取り
+    let content_length = content_length.ok_or_else(|| anyhow::anyhow!("Missing Content-Length header"))?;
+    let mut body = vec![0u8; content_length];
+    tokio::io::AsyncReadExt::read_exact(reader, &mut body).await?;
+    Ok(Some(body))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// 応答の順序を入れ替え、通知やサーバーからのリクエストを挟む言語サーバー
+    ///
+    /// - initialize: logMessage 通知とサーバーからのリクエストを送り、その応答が来てから返す
+    /// - didOpen: そのファイルの publishDiagnostics を送る
+    /// - hover・workspace/symbol: 1件目は保留し、2件目が来たら2件目・$/progress・1件目の順に返す
+    /// - definition: 返さない
+    ///
+    /// 引数にファイルを渡すと、受け取ったメッセージを1行ずつ追記する
+    const FAKE_SERVER: &str = r#"
+send() { printf 'Content-Length: %s\r\n\r\n%s' "${#1}" "$1"; }
+reply() { send "{\"jsonrpc\":\"2.0\",\"id\":$1,\"result\":$2}"; }
+pair() {
+  if [ -z "$held_id" ]; then
+    held_id=$1; held_result=$2
+  else
+    reply "$1" "$2"
+    send '{"jsonrpc":"2.0","method":"$/progress","params":{"token":"index","value":{"kind":"end"}}}'
+    reply "$held_id" "$held_result"
+    held_id=""
+  fi
+}
+held_id=""
+len=0
+while IFS= read -r line; do
+  line=$(printf '%s' "$line" | tr -d '\r')
+  case "$line" in
+    Content-Length:*) len=${line#Content-Length: } ;;
+    "")
+      body=$(dd bs=1 count="$len" 2>/dev/null)
+      [ -n "$1" ] && printf '%s\n' "$body" >> "$1"
+      id=$(printf '%s' "$body" | sed -n 's/.*"id":\([0-9][0-9]*\).*/\1/p')
+      case "$body" in
+        *'"method":"initialize"'*)
+          init_id=$id
+          send '{"jsonrpc":"2.0","method":"window/logMessage","params":{"type":3,"message":"indexing"}}'
+          send '{"jsonrpc":"2.0","id":"progress-1","method":"window/workDoneProgress/create","params":{"token":"index"}}' ;;
+        *'"id":"progress-1"'*) reply "$init_id" '{"capabilities":{}}' ;;
+        *'"method":"textDocument/didOpen"'*)
+          uri=$(printf '%s' "$body" | sed -n 's/.*"uri":"\([^"]*\)".*/\1/p')
+          send "{\"jsonrpc\":\"2.0\",\"method\":\"textDocument/publishDiagnostics\",\"params\":{\"uri\":\"$uri\",\"diagnostics\":[{\"range\":{\"start\":{\"line\":0,\"character\":4},\"end\":{\"line\":0,\"character\":5}},\"severity\":2,\"message\":\"unused variable: x\"}]}}" ;;
+        *'"method":"textDocument/hover"'*) pair "$id" '{"contents":"usize"}' ;;
+        *'"method":"workspace/symbol"'*) pair "$id" '[]' ;;
+        *'"method":"textDocument/definition"'*) ;;
+        *'"method":"shutdown"'*) reply "$id" null ;;
+      esac
+      ;;
+  esac
+done
+"#;
+
+    async fn fake_client(root: &Path) -> LspClient {
+        let client = LspClient::start("sh", &["-c", FAKE_SERVER])
+            .await
+            .unwrap()
+            .with_request_timeout(Duration::from_millis(500));
+        client.initialize(root).await.unwrap();
+        client
+    }
+
+    #[tokio::test]
+    async fn test_out_of_order_responses_are_routed_by_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        std::fs::write(&file, "let x = 1;\n").unwrap();
+        let client = fake_client(dir.path()).await;
+
+        // 先に送った方が後に返ってくる
+        let (hover, symbols) = tokio::join!(client.hover(&file, 0, 4), client.workspace_symbols("x"));
+        assert_eq!(serde_json::to_value(hover.unwrap().unwrap().contents).unwrap(), json!("usize"));
+        assert!(matches!(symbols.unwrap(), Some(WorkspaceSymbolResponse::Flat(symbols)) if symbols.is_empty()));
+
+        let (symbols, hover) = tokio::join!(client.workspace_symbols("y"), client.hover(&file, 0, 4));
+        assert!(symbols.unwrap().is_some());
+        assert!(hover.unwrap().is_some());
+        client.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_notifications_are_broadcast_and_diagnostics_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("main.rs");
+        std::fs::write(&file, "let x = 1;\n").unwrap();
+
+        let client = LspClient::start("sh", &["-c", FAKE_SERVER]).await.unwrap();
+        let mut notifications = client.subscribe();
+        // サーバーからのリクエストに答えなければ initialize は返らない
+        client.initialize(dir.path()).await.unwrap();
+        assert_eq!(notifications.try_recv().unwrap().method, "window/logMessage");
+        assert!(!client.supports_pull_diagnostics().await);
+
+        client.did_open(&file).await.unwrap();
+        let diagnostics = client.published_diagnostics(&file, Duration::from_secs(5)).await.unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "unused variable: x");
+        // 2回目はキャッシュから返る
+        assert_eq!(client.published_diagnostics(&file, Duration::ZERO).await.unwrap().len(), 1);
+        assert!(client.published_diagnostics(&dir.path().join("other.rs"), Duration::from_millis(50)).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_request_times_out_without_blocking_later_requests() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        std::fs::write(&file, "fn f() {}\n").unwrap();
+        let client = fake_client(dir.path()).await;
+
+        let error = client.goto_definition(&file, 0, 3).await.unwrap_err();
+        assert!(error.to_string().contains("LSP request 'textDocument/definition' timed out after 500ms"), "{}", error);
+        assert!(client.is_running().await);
+
+        let (hover, symbols) = tokio::join!(client.hover(&file, 0, 3), client.workspace_symbols("f"));
+        assert!(hover.unwrap().is_some());
+        assert!(symbols.unwrap().is_some());
+    }
+
+    /// 受け取った didOpen・didChange・didClose の (method, version)
+    fn document_notifications(log: &Path) -> Vec<(String, Option<i64>)> {
+        std::fs::read_to_string(log)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .filter(|message| message["method"].as_str().is_some_and(|m| m.starts_with("textDocument/did")))
+            .map(|message| (message["method"].as_str().unwrap().to_string(), message["params"]["textDocument"]["version"].as_i64()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_document_versions_follow_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        let log = dir.path().join("messages.log");
+        std::fs::write(&file, "fn a() {}\n").unwrap();
+        let client = LspClient::start("sh", &["-c", FAKE_SERVER, "fake-lsp", log.to_str().unwrap()]).await.unwrap();
+        client.initialize(dir.path()).await.unwrap();
+
+        // 開いていないファイルの didChange は何も送らない
+        client.did_change(&file).await.unwrap();
+        client.did_open(&file).await.unwrap();
+        // 内容が同じなら開き直さない
+        client.did_open(&file).await.unwrap();
+        assert_eq!(client.open_document(&file).await.unwrap().version, 1);
+
+        std::fs::write(&file, "fn b() {}\n").unwrap();
+        client.did_change(&file).await.unwrap();
+        std::fs::write(&file, "fn c() {}\n").unwrap();
+        // 開いているファイルの didOpen は didChange になる
+        client.did_open(&file).await.unwrap();
+        let document = client.open_document(&file).await.unwrap();
+        assert_eq!((document.version, document.text.as_str()), (3, "fn c() {}\n"));
+
+        client.did_close(&file).await.unwrap();
+        client.did_close(&file).await.unwrap();
+        assert!(client.open_document(&file).await.is_none());
+        // 応答が返れば、それまでの通知はサーバーに届いている
+        client.shutdown().await.unwrap();
+
+        assert_eq!(
+            document_notifications(&log),
+            vec![
+                ("textDocument/didOpen".to_string(), Some(1)),
+                ("textDocument/didChange".to_string(), Some(2)),
+                ("textDocument/didChange".to_string(), Some(3)),
+                ("textDocument/didClose".to_string(), None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,437 @@
+//! 言語サーバーの起動・監視・再起動
+//!
+//! rust-analyzer などは作業中に落ちることがある。ツールは `ensure_alive` を通して
+//! クライアントの共有ハンドルを受け取り、落ちていれば同じコマンドで起動し直して、開いていたファイルを
+//! didOpen し直してから使う。ロックは生存確認と起動し直しの間だけ持ち、リクエストの間は持たない。
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+use super::client::{LspClient, DEFAULT_REQUEST_TIMEOUT};
+use crate::agent::session::{ResetAction, ResetTarget, SessionResettable};
+use crate::tools::file::FileObserver;
+
+/// 言語サーバーのプロセスを管理する
+pub struct LspManager {
+    /// 起動するコマンド（未構成なら None）
+    command: Option<String>,
+    args: Vec<String>,
+    /// initialize に渡すプロジェクトルート
+    root: PathBuf,
+    request_timeout: Duration,
+    client: Mutex<Option<Arc<LspClient>>>,
+    /// 起動し直した回数（/status に表示）
+    restarts: AtomicUsize,
+    /// セッション遷移があった（次に借りるときに開いていたドキュメントを閉じる）
+    stale_documents: AtomicBool,
+}
+
+impl LspManager {
+    pub fn new(command: Option<String>, args: Vec<String>, root: impl Into<PathBuf>) -> Self {
+        Self {
+            command,
+            args,
+            root: root.into(),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            client: Mutex::new(None),
+            restarts: AtomicUsize::new(0),
+            stale_documents: AtomicBool::new(false),
+        }
+    }
+
+    /// リクエストの応答を待つ時間（`[lsp] request_timeout`）
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// 起動するコマンド
+    pub fn command(&self) -> Option<&str> {
+        self.command.as_deref()
+    }
+
+    /// 起動し直した回数
+    pub fn restart_count(&self) -> usize {
+        self.restarts.load(Ordering::SeqCst)
+    }
+
+    /// 言語サーバーが動作中か（一度も起動していなければ None）
+    pub async fn is_running(&self) -> Option<bool> {
+        let client = self.client.lock().await.clone()?;
+        Some(client.is_running().await)
+    }
+
+    /// 言語サーバーを起動して初期化する（構成されていなければ何もしない）
+    pub async fn start(&self) -> Result<()> {
+        let mut guard = self.client.lock().await;
+        if self.command.is_some() {
+            *guard = Some(Arc::new(self.spawn().await?));
+        }
+        Ok(())
+    }
+
+    /// 動作中のクライアントを返す。落ちていれば起動し直す
+    ///
+    /// ロックは生存確認と起動し直しの間だけ持つので、返したハンドルでのリクエスト中も
+    /// 他のツールやファイルの通知はクライアントを使える
+    pub async fn ensure_alive(&self) -> Result<Arc<LspClient>> {
+        let client = {
+            let mut guard = self.client.lock().await;
+            let alive = match guard.as_ref() {
+                Some(client) => client.is_running().await,
+                None => false,
+            };
+            if !alive {
+                self.respawn(&mut guard).await?;
+            }
+            guard.clone().ok_or_else(|| anyhow::anyhow!("LSP client not initialized"))?
+        };
+        self.close_stale_documents(&client).await;
+        Ok(client)
+    }
+
+    /// 動作中のクライアントがあれば返す（起動はしない）
+    async fn running(&self) -> Option<Arc<LspClient>> {
+        let client = self.client.lock().await.clone()?;
+        if !client.is_running().await {
+            return None;
+        }
+        self.close_stale_documents(&client).await;
+        Some(client)
+    }
+
+    /// セッション遷移の後なら、前のセッションで開いたドキュメントを閉じる（didClose）
+    async fn close_stale_documents(&self, client: &LspClient) {
+        if !self.stale_documents.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        for path in client.open_paths().await {
+            if let Err(e) = client.did_close(&path).await {
+                tracing::debug!("Failed to send didClose for {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    /// 動作中かどうかに関わらず起動し直す（/lsp restart）。開き直したファイル数を返す
+    pub async fn restart(&self) -> Result<usize> {
+        let mut guard = self.client.lock().await;
+        self.respawn(&mut guard).await
+    }
+
+    /// 言語サーバーを終了する
+    pub async fn shutdown(&self) {
+        let Some(client) = self.client.lock().await.take() else {
+            return;
+        };
+        if !client.is_running().await {
+            return;
+        }
+        if let Err(e) = client.shutdown().await {
+            tracing::warn!("Failed to shutdown LSP server: {}", e);
+        }
+    }
+
+    /// 古いプロセスを止めて起動し直し、開いていたファイルを didOpen し直す
+    async fn respawn(&self, slot: &mut Option<Arc<LspClient>>) -> Result<usize> {
+        let command = self.command.as_deref().ok_or_else(|| anyhow::anyhow!("No language server configured"))?;
+        // セッション遷移の後なら開き直さない（閉じたのと同じ）
+        let stale = self.stale_documents.swap(false, Ordering::SeqCst);
+        let reopen = match slot.take() {
+            Some(old) => {
+                let paths = if stale { Vec::new() } else { old.open_paths().await };
+                old.kill().await;
+                self.restarts.fetch_add(1, Ordering::SeqCst);
+                tracing::warn!("Restarting language server '{}'", command);
+                paths
+            }
+            None => Vec::new(),
+        };
+
+        let client = self.spawn().await?;
+        let mut reopened = 0;
+        for path in reopen {
+            match client.did_open(&path).await {
+                Ok(()) => reopened += 1,
+                // 削除されたファイルなどは開き直さない
+                Err(e) => tracing::debug!("Failed to reopen {}: {}", path.display(), e),
+            }
+        }
+        *slot = Some(Arc::new(client));
+        Ok(reopened)
+    }
+
+    async fn spawn(&self) -> Result<LspClient> {
+        let command = self.command.as_deref().ok_or_else(|| anyhow::anyhow!("No language server configured"))?;
+        let args: Vec<&str> = self.args.iter().map(|s| s.as_str()).collect();
+        let client = LspClient::start(command, &args)
+            .await
+            .with_context(|| format!("Failed to start language server '{}'", command))?
+            .with_request_timeout(self.request_timeout);
+        client
+            .initialize(&self.root)
+            .await
+            .with_context(|| format!("Failed to initialize language server '{}'", command))?;
+        tracing::info!("LSP initialized: {}", command);
+        Ok(client)
+    }
+}
+
+/// ファイルツールの書き換えを言語サーバーに伝える
+///
+/// 止まっているサーバーは起動しない（起動し直すときにディスクから開き直す）
+#[async_trait]
+impl FileObserver for LspManager {
+    async fn file_written(&self, path: &Path) {
+        let Some(client) = self.running().await else {
+            return;
+        };
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        if let Err(e) = client.did_change(&path).await {
+            tracing::debug!("Failed to send didChange for {}: {}", path.display(), e);
+        }
+    }
+
+    async fn file_removed(&self, path: &Path) {
+        let Some(client) = self.running().await else {
+            return;
+        };
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        if let Err(e) = client.did_close(&path).await {
+            tracing::debug!("Failed to send didClose for {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// セッション遷移で開いていたドキュメントを閉じる
+///
+/// reset は同期的に呼ばれるので印を付けるだけにし、次にクライアントを借りるときに didClose を送る
+impl SessionResettable for LspManager {
+    fn reset_target(&self) -> ResetTarget {
+        ResetTarget::LspDocuments
+    }
+
+    fn reset(&self, _action: ResetAction) {
+        self.stale_documents.store(true, Ordering::SeqCst);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::agent::status::{HealthState, StatusProvider};
+    use crate::tools::lsp::LspStatus;
+
+    /// initialize には空の capabilities、それ以外には null を返し、
+    /// `limit` 件のリクエストに答えたら終了する言語サーバー
+    ///
+    /// 2つ目の引数にファイルを渡すと、受け取ったメッセージを1行ずつ追記する
+    const FAKE_SERVER: &str = r#"
+limit=$1
+log=$2
+count=0
+len=0
+while IFS= read -r line; do
+  line=$(printf '%s' "$line" | tr -d '\r')
+  case "$line" in
+    Content-Length:*) len=${line#Content-Length: } ;;
+    "")
+      body=$(dd bs=1 count="$len" 2>/dev/null)
+      [ -n "$log" ] && printf '%s\n' "$body" >> "$log"
+      id=$(printf '%s' "$body" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+      [ -z "$id" ] && continue
+      case "$body" in
+        *'"method":"initialize"'*) result='{"capabilities":{}}' ;;
+        *) result=null ;;
+      esac
+      reply="{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":$result}"
+      printf 'Content-Length: %s\r\n\r\n%s' "${#reply}" "$reply"
+      count=$((count + 1))
+      [ "$count" -ge "$limit" ] && exit 0
+      ;;
+  esac
+done
+"#;
+
+    fn manager(limit: usize, root: &std::path::Path) -> LspManager {
+        let args = vec!["-c".to_string(), FAKE_SERVER.to_string(), "fake-lsp".to_string(), limit.to_string()];
+        LspManager::new(Some("sh".to_string()), args, root)
+    }
+
+    #[tokio::test]
+    async fn test_restarts_after_server_exits() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        std::fs::write(&file, "fn main() {}\n").unwrap();
+
+        // initialize と workspace/symbol の2件で終了する
+        let lsp = Arc::new(manager(2, dir.path()));
+        let status = LspStatus::new(Arc::clone(&lsp));
+        lsp.start().await.unwrap();
+        assert_eq!(status.status().await.detail, "sh running");
+        {
+            let client = lsp.ensure_alive().await.unwrap();
+            client.did_open(&file).await.unwrap();
+            assert!(client.workspace_symbols("x").await.unwrap().is_none());
+            // 終了したあとのリクエストはパイプのエラーではなく終了として失敗する
+            let error = client.workspace_symbols("x").await.unwrap_err();
+            assert!(error.to_string().contains("Language server has exited"), "{}", error);
+            assert!(!client.is_running().await);
+        }
+        assert_eq!(lsp.is_running().await, Some(false));
+        assert_eq!(status.status().await.state, HealthState::Failed);
+
+        // 次に借りるときに起動し直し、開いていたファイルを開き直す
+        let client = lsp.ensure_alive().await.unwrap();
+        assert!(client.is_running().await);
+        assert!(client.open_document(&file).await.is_some());
+        drop(client);
+        assert_eq!(lsp.restart_count(), 1);
+        // 2件目のリクエストに答えると新しいサーバーも終了するので、状態はその前に見る
+        assert_eq!(status.status().await.detail, "sh running (restarted 1 time)");
+        assert!(lsp.ensure_alive().await.unwrap().workspace_symbols("x").await.unwrap().is_none());
+        lsp.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_handle_does_not_hold_the_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("main.rs");
+        std::fs::write(&file, "fn main() {}\n").unwrap();
+
+        let lsp = Arc::new(manager(100, dir.path()));
+        let client = lsp.ensure_alive().await.unwrap();
+        client.did_open(&file).await.unwrap();
+
+        // ハンドルを持ったままでも、他の呼び出しは待たされない
+        let wait = Duration::from_secs(5);
+        let other = tokio::time::timeout(wait, lsp.ensure_alive()).await.expect("ensure_alive blocked").unwrap();
+        assert!(Arc::ptr_eq(&client, &other));
+        assert_eq!(tokio::time::timeout(wait, lsp.is_running()).await.unwrap(), Some(true));
+        let observer: crate::tools::file::SharedFileObserver = lsp.clone();
+        tokio::time::timeout(wait, observer.file_written(&file)).await.expect("didChange blocked");
+        let (first, second) = tokio::join!(client.workspace_symbols("x"), other.workspace_symbols("y"));
+        assert!(first.unwrap().is_none() && second.unwrap().is_none());
+        lsp.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_restart_reopens_documents() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("main.rs");
+        std::fs::write(&file, "fn main() {}\n").unwrap();
+
+        let lsp = manager(100, dir.path());
+        lsp.ensure_alive().await.unwrap().did_open(&file).await.unwrap();
+        // 一度も落ちていなければ数えない
+        assert_eq!(lsp.restart_count(), 0);
+
+        assert_eq!(lsp.restart().await.unwrap(), 1);
+        assert_eq!(lsp.restart_count(), 1);
+        assert_eq!(lsp.is_running().await, Some(true));
+        lsp.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_session_reset_closes_open_documents() {
+        use crate::agent::{SessionResetHub, SessionTransition};
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("main.rs");
+        let log = dir.path().join("messages.log");
+        std::fs::write(&file, "fn main() {}\n").unwrap();
+        let args = vec!["-c".to_string(), FAKE_SERVER.to_string(), "fake-lsp".to_string(), "100".to_string(), log.to_str().unwrap().to_string()];
+        let lsp = Arc::new(LspManager::new(Some("sh".to_string()), args, dir.path()));
+        let mut hub = SessionResetHub::new();
+        hub.register(lsp.clone());
+        lsp.ensure_alive().await.unwrap().did_open(&file).await.unwrap();
+
+        hub.emit(SessionTransition::ModelChange);
+        assert!(lsp.ensure_alive().await.unwrap().open_document(&file).await.is_some());
+        hub.emit(SessionTransition::ChangeDirectory);
+        let client = lsp.ensure_alive().await.unwrap();
+        assert!(client.open_document(&file).await.is_none());
+        client.workspace_symbols("x").await.unwrap();
+        drop(client);
+        assert!(std::fs::read_to_string(&log).unwrap().contains("textDocument/didClose"));
+
+        // 再起動しても前のセッションのドキュメントは開き直さない
+        lsp.ensure_alive().await.unwrap().did_open(&file).await.unwrap();
+        hub.emit(SessionTransition::Load);
+        assert_eq!(lsp.restart().await.unwrap(), 0);
+        lsp.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_unconfigured_or_missing_server() {
+        let dir = tempfile::tempdir().unwrap();
+        let unconfigured = LspManager::new(None, Vec::new(), dir.path());
+        unconfigured.start().await.unwrap();
+        assert!(unconfigured.ensure_alive().await.is_err());
+        assert_eq!(unconfigured.is_running().await, None);
+
+        let missing = LspManager::new(Some("local-code-no-such-lsp".to_string()), Vec::new(), dir.path());
+        let error = missing.ensure_alive().await.err().unwrap();
+        assert!(format!("{:#}", error).contains("Failed to start language server 'local-code-no-such-lsp'"));
+    }
+
+    #[tokio::test]
+    async fn test_file_tools_send_did_change() {
+        use crate::tools::file::{ApplyPatchTool, EditTool, SharedFileObserver, WriteTool};
+        use crate::tools::Tool;
+        use serde_json::{json, Value};
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        let log = dir.path().join("messages.log");
+        std::fs::write(&file, "fn old() {}\n").unwrap();
+        let args = vec!["-c".to_string(), FAKE_SERVER.to_string(), "fake-lsp".to_string(), "100".to_string(), log.to_str().unwrap().to_string()];
+        let lsp = Arc::new(LspManager::new(Some("sh".to_string()), args, dir.path()));
+        let observer: SharedFileObserver = lsp.clone();
+        let write = WriteTool::new().with_observer(Arc::clone(&observer));
+        let edit = EditTool::new().with_observer(Arc::clone(&observer));
+        let patch = ApplyPatchTool::new(dir.path()).with_observer(observer);
+
+        // 言語サーバーが動いていなければ、書き込みのために起動はしない
+        let path = file.to_str().unwrap();
+        assert!(write.execute(json!({"file_path": path, "content": "fn old() {}\n", "force": true})).await.unwrap().success);
+        assert_eq!(lsp.is_running().await, None);
+
+        // LSP ツールと同じように開いてから書き換える
+        lsp.ensure_alive().await.unwrap().did_open(&file).await.unwrap();
+        assert!(edit.execute(json!({"file_path": path, "old_string": "old", "new_string": "renamed"})).await.unwrap().success);
+        assert!(write.execute(json!({"file_path": path, "content": "fn written() {}\n", "force": true})).await.unwrap().success);
+        let removal = "--- a/lib.rs\n+++ /dev/null\n@@ -1 +0,0 @@\n-fn written() {}\n";
+        let result = patch.execute(json!({"patch": removal})).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        // 応答が返れば、それまでの通知はサーバーに届いている
+        lsp.ensure_alive().await.unwrap().workspace_symbols("x").await.unwrap();
+
+        let messages: Vec<Value> = std::fs::read_to_string(&log)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .filter(|message: &Value| message["method"].as_str().is_some_and(|m| m.starts_with("textDocument/did")))
+            .collect();
+        let sequence: Vec<(&str, Option<i64>)> = messages
+            .iter()
+            .map(|m| (m["method"].as_str().unwrap(), m["params"]["textDocument"]["version"].as_i64()))
+            .collect();
+        assert_eq!(
+            sequence,
+            vec![
+                ("textDocument/didOpen", Some(1)),
+                ("textDocument/didChange", Some(2)),
+                ("textDocument/didChange", Some(3)),
+                ("textDocument/didClose", None),
+            ]
+        );
+        assert_eq!(messages[1]["params"]["contentChanges"][0]["text"], "fn renamed() {}\n");
+        assert_eq!(messages[2]["params"]["contentChanges"][0]["text"], "fn written() {}\n");
+        lsp.shutdown().await;
+    }
+}
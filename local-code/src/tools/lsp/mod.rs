@@ -1,5 +1,12 @@
 pub mod client;
+pub mod manager;
 pub mod operations;
+pub mod router;
+pub mod status;
+pub mod workspace_edit;
 
-pub use client::LspClient;
-pub use operations::{LspDefinitionTool, LspReferencesTool, LspDiagnosticsTool};
+pub use client::{LspClient, LspNotification};
+pub use manager::LspManager;
+pub use router::LspRouter;
+pub use operations::{LspDefinitionTool, LspReferencesTool, LspDiagnosticsTool, LspHoverTool, LspSymbolsTool, LspOutlineTool, LspRenameTool};
+pub use status::LspStatus;
@@ -1,21 +1,31 @@
 use anyhow::Result;
 use async_trait::async_trait;
+use lsp_types::{
+    DocumentSymbol, DocumentSymbolResponse, Hover, HoverContents, Location, MarkedString, OneOf,
+    SymbolKind, WorkspaceSymbolResponse,
+};
 use serde_json::{json, Value};
-use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::Mutex;
 
-use super::client::LspClient;
-use crate::tools::{Tool, ToolResult};
+use super::router::LspRouter;
+use super::workspace_edit;
+use crate::tools::file::SharedFileObserver;
+use crate::tools::{PathPolicy, Tool, ToolEffects, ToolResult};
+
+/// lsp_symbols が返す件数の既定値
+pub const DEFAULT_MAX_SYMBOLS: usize = 50;
+
+/// publishDiagnostics を送ってくるサーバーで、ファイルを開いてから診断を待つ時間
+const PUBLISHED_DIAGNOSTICS_WAIT: std::time::Duration = std::time::Duration::from_secs(3);
 
 /// LSP定義ジャンプツール
 pub struct LspDefinitionTool {
-    client: Arc<Mutex<Option<LspClient>>>,
+    lsp: Arc<LspRouter>,
 }
 
 impl LspDefinitionTool {
-    pub fn new(client: Arc<Mutex<Option<LspClient>>>) -> Self {
-        Self { client }
+    pub fn new(lsp: Arc<LspRouter>) -> Self {
+        Self { lsp }
     }
 }
 
@@ -50,6 +60,10 @@ impl Tool for LspDefinitionTool {
         })
     }
 
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::ReadOnly
+    }
+
     async fn execute(&self, params: Value) -> Result<ToolResult> {
         let file_path = params.get("file_path")
             .and_then(|v| v.as_str())
@@ -61,15 +75,12 @@ impl Tool for LspDefinitionTool {
             .and_then(|v| v.as_u64())
             .ok_or_else(|| anyhow::anyhow!("Missing character"))? as u32;
 
-        let guard = self.client.lock().await;
-        let client = guard.as_ref()
-            .ok_or_else(|| anyhow::anyhow!("LSP client not initialized"))?;
-
-        let path = PathBuf::from(file_path);
+        let path = self.lsp.resolve(file_path);
+        let client = self.lsp.for_path(&path).ensure_alive().await?;
         client.did_open(&path).await?;
         match client.goto_definition(&path, line, character).await {
             Ok(Some(response)) => {
-                Ok(ToolResult::success(serde_json::to_string_pretty(&response)?))
+                Ok(ToolResult::success(serde_json::to_string_pretty(&response)?).with_data(response))
             }
             Ok(None) => {
                 Ok(ToolResult::success("No definition found"))
@@ -83,12 +94,12 @@ impl Tool for LspDefinitionTool {
 
 /// LSP参照検索ツール
 pub struct LspReferencesTool {
-    client: Arc<Mutex<Option<LspClient>>>,
+    lsp: Arc<LspRouter>,
 }
 
 impl LspReferencesTool {
-    pub fn new(client: Arc<Mutex<Option<LspClient>>>) -> Self {
-        Self { client }
+    pub fn new(lsp: Arc<LspRouter>) -> Self {
+        Self { lsp }
     }
 }
 
@@ -123,6 +134,10 @@ impl Tool for LspReferencesTool {
         })
     }
 
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::ReadOnly
+    }
+
     async fn execute(&self, params: Value) -> Result<ToolResult> {
         let file_path = params.get("file_path")
             .and_then(|v| v.as_str())
@@ -134,11 +149,8 @@ impl Tool for LspReferencesTool {
             .and_then(|v| v.as_u64())
             .ok_or_else(|| anyhow::anyhow!("Missing character"))? as u32;
 
-        let guard = self.client.lock().await;
-        let client = guard.as_ref()
-            .ok_or_else(|| anyhow::anyhow!("LSP client not initialized"))?;
-
-        let path = PathBuf::from(file_path);
+        let path = self.lsp.resolve(file_path);
+        let client = self.lsp.for_path(&path).ensure_alive().await?;
         client.did_open(&path).await?;
         match client.find_references(&path, line, character).await {
             Ok(Some(locations)) => {
@@ -154,7 +166,8 @@ impl Tool for LspReferencesTool {
                     "No references found".to_string()
                 } else {
                     output
-                }))
+                })
+                .with_data(locations))
             }
             Ok(None) => {
                 Ok(ToolResult::success("No references found"))
@@ -166,15 +179,540 @@ impl Tool for LspReferencesTool {
     }
 }
 
-/// LSP診断情報ツール（プレースホルダー）
+/// LSPホバーツール（型・シグネチャ・ドキュメント）
+pub struct LspHoverTool {
+    lsp: Arc<LspRouter>,
+}
+
+impl LspHoverTool {
+    pub fn new(lsp: Arc<LspRouter>) -> Self {
+        Self { lsp }
+    }
+}
+
+#[async_trait]
+impl Tool for LspHoverTool {
+    fn name(&self) -> &str {
+        "lsp_hover"
+    }
+
+    fn description(&self) -> &str {
+        "Show the type, signature and documentation of the symbol at the specified position"
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "file_path": {
+                    "type": "string",
+                    "description": "Path to the file"
+                },
+                "line": {
+                    "type": "integer",
+                    "description": "Line number (0-indexed)"
+                },
+                "character": {
+                    "type": "integer",
+                    "description": "Character position (0-indexed)"
+                }
+            },
+            "required": ["file_path", "line", "character"]
+        })
+    }
+
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::ReadOnly
+    }
+
+    async fn execute(&self, params: Value) -> Result<ToolResult> {
+        let file_path = params.get("file_path")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing file_path"))?;
+        let line = params.get("line")
+            .and_then(|v| v.as_u64())
+            .ok_or_else(|| anyhow::anyhow!("Missing line"))? as u32;
+        let character = params.get("character")
+            .and_then(|v| v.as_u64())
+            .ok_or_else(|| anyhow::anyhow!("Missing character"))? as u32;
+
+        let path = self.lsp.resolve(file_path);
+        let client = self.lsp.for_path(&path).ensure_alive().await?;
+        client.did_open(&path).await?;
+        match client.hover(&path, line, character).await {
+            Ok(hover) => Ok(ToolResult::success(hover_text(hover.as_ref()))),
+            Err(e) => Ok(ToolResult::failure(format!("LSP error: {}", e))),
+        }
+    }
+}
+
+/// ホバーの内容をテキストにする
+///
+/// Markdown はそのまま返し、MarkedString の配列は空行で区切ってつなぐ
+fn hover_text(hover: Option<&Hover>) -> String {
+    let marked = |item: &MarkedString| match item {
+        MarkedString::String(text) => text.trim().to_string(),
+        MarkedString::LanguageString(code) => format!("```{}\n{}\n```", code.language, code.value.trim_end()),
+    };
+    let text = match hover.map(|hover| &hover.contents) {
+        None => String::new(),
+        Some(HoverContents::Scalar(item)) => marked(item),
+        Some(HoverContents::Array(items)) => items.iter().map(marked).filter(|text| !text.is_empty()).collect::<Vec<_>>().join("\n\n"),
+        Some(HoverContents::Markup(content)) => content.value.trim().to_string(),
+    };
+    if text.is_empty() {
+        "No hover information".to_string()
+    } else {
+        text
+    }
+}
+
+/// LSPワークスペースシンボル検索ツール
+pub struct LspSymbolsTool {
+    lsp: Arc<LspRouter>,
+    max_results: usize,
+}
+
+impl LspSymbolsTool {
+    pub fn new(lsp: Arc<LspRouter>) -> Self {
+        Self { lsp, max_results: DEFAULT_MAX_SYMBOLS }
+    }
+
+    /// 返す最大件数（`[lsp] max_symbols`）
+    pub fn with_max_results(mut self, max_results: usize) -> Self {
+        self.max_results = max_results.max(1);
+        self
+    }
+}
+
+#[async_trait]
+impl Tool for LspSymbolsTool {
+    fn name(&self) -> &str {
+        "lsp_symbols"
+    }
+
+    fn description(&self) -> &str {
+        "Search the whole workspace for symbols (structs, functions, traits, ...) by name; faster and more precise than grep for finding definitions"
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "Symbol name or part of it (fuzzy matched by the language server)"
+                },
+                "kind": {
+                    "type": "string",
+                    "description": "Only return this kind of symbol, e.g. function, method, struct, enum, trait, class, module, constant"
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::ReadOnly
+    }
+
+    async fn execute(&self, params: Value) -> Result<ToolResult> {
+        let query = params.get("query")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing query"))?;
+        let kind = params.get("kind").and_then(|v| v.as_str()).filter(|k| !k.trim().is_empty());
+
+        let client = self.lsp.for_current().ensure_alive().await?;
+
+        match client.workspace_symbols(query).await {
+            Ok(response) => Ok(ToolResult::success(workspace_symbols_text(response, query, kind, self.max_results))),
+            Err(e) => Ok(ToolResult::failure(format!("LSP error: {}", e))),
+        }
+    }
+}
+
+/// LSPファイルアウトラインツール（documentSymbol）
+pub struct LspOutlineTool {
+    lsp: Arc<LspRouter>,
+}
+
+impl LspOutlineTool {
+    pub fn new(lsp: Arc<LspRouter>) -> Self {
+        Self { lsp }
+    }
+}
+
+#[async_trait]
+impl Tool for LspOutlineTool {
+    fn name(&self) -> &str {
+        "lsp_outline"
+    }
+
+    fn description(&self) -> &str {
+        "Show the symbol tree (types, functions, methods and their line numbers) of a file"
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "file_path": {
+                    "type": "string",
+                    "description": "Path to the file"
+                }
+            },
+            "required": ["file_path"]
+        })
+    }
+
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::ReadOnly
+    }
+
+    async fn execute(&self, params: Value) -> Result<ToolResult> {
+        let file_path = params.get("file_path")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing file_path"))?;
+
+        let path = self.lsp.resolve(file_path);
+        let client = self.lsp.for_path(&path).ensure_alive().await?;
+        client.did_open(&path).await?;
+        match client.document_symbols(&path).await {
+            Ok(response) => Ok(ToolResult::success(outline_text(response))),
+            Err(e) => Ok(ToolResult::failure(format!("LSP error: {}", e))),
+        }
+    }
+}
+
+/// LSPリネームツール（複数ファイルにまたがる WorkspaceEdit を適用する）
+pub struct LspRenameTool {
+    lsp: Arc<LspRouter>,
+    /// 書き換えたファイルの通知先
+    observer: Option<SharedFileObserver>,
+    /// 扱えるパスの制限（プロジェクトルートの外を拒否する）
+    policy: Option<Arc<PathPolicy>>,
+}
+
+impl LspRenameTool {
+    pub fn new(lsp: Arc<LspRouter>) -> Self {
+        Self { lsp, observer: None, policy: None }
+    }
+
+    /// 書き換えたファイルを知らせる
+    pub fn with_observer(mut self, observer: SharedFileObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// 扱えるパスを制限する
+    pub fn with_path_policy(mut self, policy: Arc<PathPolicy>) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+}
+
+#[async_trait]
+impl Tool for LspRenameTool {
+    fn name(&self) -> &str {
+        "lsp_rename"
+    }
+
+    fn description(&self) -> &str {
+        "Rename the symbol at the specified position everywhere it is used, editing all affected files at once"
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "file_path": {
+                    "type": "string",
+                    "description": "Path to the file"
+                },
+                "line": {
+                    "type": "integer",
+                    "description": "Line number (0-indexed)"
+                },
+                "character": {
+                    "type": "integer",
+                    "description": "Character position (0-indexed)"
+                },
+                "new_name": {
+                    "type": "string",
+                    "description": "New name for the symbol"
+                }
+            },
+            "required": ["file_path", "line", "character", "new_name"]
+        })
+    }
+
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::Writes
+    }
+
+    async fn execute(&self, params: Value) -> Result<ToolResult> {
+        let file_path = params.get("file_path")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing file_path"))?;
+        let line = params.get("line")
+            .and_then(|v| v.as_u64())
+            .ok_or_else(|| anyhow::anyhow!("Missing line"))? as u32;
+        let character = params.get("character")
+            .and_then(|v| v.as_u64())
+            .ok_or_else(|| anyhow::anyhow!("Missing character"))? as u32;
+        let new_name = params.get("new_name")
+            .and_then(|v| v.as_str())
+            .filter(|name| !name.trim().is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Missing new_name"))?;
+
+        let path = self.lsp.resolve(file_path);
+        if let Some(Err(e)) = self.policy.as_ref().map(|p| p.check_write(&path)) {
+            return Ok(ToolResult::failure(e));
+        }
+
+        let client = self.lsp.for_path(&path).ensure_alive().await?;
+        client.did_open(&path).await?;
+        let edit = match client.rename(&path, line, character, new_name).await {
+            Ok(Some(edit)) => edit,
+            Ok(None) => return Ok(ToolResult::success("Nothing to rename at this position")),
+            Err(e) => return Ok(ToolResult::failure(format!("LSP error: {}", e))),
+        };
+        let files = match workspace_edit::collect(&edit) {
+            Ok(files) if files.is_empty() => return Ok(ToolResult::success("The rename changes nothing")),
+            Ok(files) => files,
+            Err(e) => return Ok(ToolResult::failure(e.to_string())),
+        };
+
+        // 1つでも書けない場所があれば、どのファイルも書き換えない
+        if let Some(policy) = &self.policy {
+            let denied: Vec<String> = files.iter().filter_map(|file| policy.check_write(&file.path).err()).collect();
+            if !denied.is_empty() {
+                return Ok(ToolResult::failure(format!("Refusing to rename: {}", denied.join("; "))));
+            }
+        }
+
+        // サーバーが見ている内容とディスクが違えば、編集の位置がずれるので適用しない
+        let mut stale = Vec::new();
+        for file in &files {
+            let Some(document) = client.open_document(&file.path).await else {
+                continue;
+            };
+            let disk = tokio::fs::read_to_string(&file.path).await.unwrap_or_default();
+            if disk != document.text || file.version.is_some_and(|version| version != document.version) {
+                stale.push(display_path(&file.path));
+            }
+        }
+        if !stale.is_empty() {
+            return Ok(ToolResult::failure(format!(
+                "Refusing to rename: these files changed since the language server read them: {}",
+                stale.join(", ")
+            )));
+        }
+
+        let planned = match workspace_edit::plan(&files, |path| Ok(std::fs::read_to_string(path)?)) {
+            Ok(planned) => planned,
+            Err(e) => return Ok(ToolResult::failure(format!("{:#}", e))),
+        };
+        if let Err(e) = workspace_edit::write_all(&planned) {
+            return Ok(ToolResult::failure(format!("Rename not applied: {:#}", e)));
+        }
+        // 書き換えた内容を言語サーバーにも渡しておく
+        for file in &planned {
+            if let Err(e) = client.did_open(&file.path).await {
+                tracing::debug!("Failed to reopen {}: {}", file.path.display(), e);
+            }
+            if let Some(observer) = &self.observer {
+                observer.file_written(&file.path).await;
+            }
+        }
+
+        let total: usize = planned.iter().map(|file| file.edit_count).sum();
+        let mut output = format!(
+            "Renamed to `{}`: {} edit{} in {} file{}",
+            new_name,
+            total,
+            if total == 1 { "" } else { "s" },
+            planned.len(),
+            if planned.len() == 1 { "" } else { "s" }
+        );
+        for file in &planned {
+            output.push_str(&format!(
+                "\n  {}: {} edit{}",
+                display_path(&file.path),
+                file.edit_count,
+                if file.edit_count == 1 { "" } else { "s" }
+            ));
+        }
+        Ok(ToolResult::success(output))
+    }
+}
+
+/// 作業ディレクトリからの相対パス（外なら絶対パス）
+fn display_path(path: &std::path::Path) -> String {
+    std::env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok().map(|p| p.display().to_string()))
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// シンボルの種類の表示名
+fn symbol_kind_name(kind: SymbolKind) -> &'static str {
+    match kind {
+        SymbolKind::FILE => "file",
+        SymbolKind::MODULE => "module",
+        SymbolKind::NAMESPACE => "namespace",
+        SymbolKind::PACKAGE => "package",
+        SymbolKind::CLASS => "class",
+        SymbolKind::METHOD => "method",
+        SymbolKind::PROPERTY => "property",
+        SymbolKind::FIELD => "field",
+        SymbolKind::CONSTRUCTOR => "constructor",
+        SymbolKind::ENUM => "enum",
+        SymbolKind::INTERFACE => "interface",
+        SymbolKind::FUNCTION => "function",
+        SymbolKind::VARIABLE => "variable",
+        SymbolKind::CONSTANT => "constant",
+        SymbolKind::STRING => "string",
+        SymbolKind::NUMBER => "number",
+        SymbolKind::BOOLEAN => "boolean",
+        SymbolKind::ARRAY => "array",
+        SymbolKind::OBJECT => "object",
+        SymbolKind::KEY => "key",
+        SymbolKind::NULL => "null",
+        SymbolKind::ENUM_MEMBER => "enum_member",
+        SymbolKind::STRUCT => "struct",
+        SymbolKind::EVENT => "event",
+        SymbolKind::OPERATOR => "operator",
+        SymbolKind::TYPE_PARAMETER => "type_parameter",
+        _ => "symbol",
+    }
+}
+
+/// kind の指定に合うか（rust-analyzer はトレイトを interface として返す）
+fn kind_matches(kind: SymbolKind, filter: &str) -> bool {
+    let filter = filter.trim().to_lowercase();
+    let name = symbol_kind_name(kind);
+    match filter.as_str() {
+        "trait" => name == "interface",
+        "fn" => name == "function" || name == "method",
+        "const" => name == "constant",
+        "mod" => name == "module",
+        "variant" => name == "enum_member",
+        _ => name == filter,
+    }
+}
+
+/// 名前がクエリにどれだけ近いか（小さいほど上位。同じなら言語サーバーの順）
+fn relevance(name: &str, query: &str) -> u8 {
+    let lower = name.to_lowercase();
+    let query_lower = query.to_lowercase();
+    if name == query {
+        0
+    } else if lower == query_lower {
+        1
+    } else if lower.starts_with(&query_lower) {
+        2
+    } else if lower.contains(&query_lower) {
+        3
+    } else {
+        4
+    }
+}
+
+fn location_text(location: &Location) -> String {
+    format!("{}:{}", location.uri.path(), location.range.start.line + 1)
+}
+
+/// workspace/symbol の結果を `name  kind  path:line` の行にする
+fn workspace_symbols_text(response: Option<WorkspaceSymbolResponse>, query: &str, kind: Option<&str>, max_results: usize) -> String {
+    let symbols: Vec<(String, SymbolKind, String)> = match response {
+        None => Vec::new(),
+        Some(WorkspaceSymbolResponse::Flat(symbols)) => symbols
+            .into_iter()
+            .map(|symbol| (symbol.name, symbol.kind, location_text(&symbol.location)))
+            .collect(),
+        Some(WorkspaceSymbolResponse::Nested(symbols)) => symbols
+            .into_iter()
+            .map(|symbol| {
+                let location = match &symbol.location {
+                    OneOf::Left(location) => location_text(location),
+                    OneOf::Right(location) => location.uri.path().to_string(),
+                };
+                (symbol.name, symbol.kind, location)
+            })
+            .collect(),
+    };
+    let mut symbols: Vec<_> = symbols
+        .into_iter()
+        .filter(|(_, symbol_kind, _)| kind.is_none_or(|kind| kind_matches(*symbol_kind, kind)))
+        .collect();
+    if symbols.is_empty() {
+        return "No symbols found".to_string();
+    }
+    symbols.sort_by_key(|(name, _, _)| relevance(name, query));
+
+    let total = symbols.len();
+    let mut lines: Vec<String> = symbols
+        .iter()
+        .take(max_results)
+        .map(|(name, symbol_kind, location)| format!("{}  {}  {}", name, symbol_kind_name(*symbol_kind), location))
+        .collect();
+    if total > max_results {
+        lines.push(format!("... {} more (use a more specific query or kind)", total - max_results));
+    }
+    lines.join("\n")
+}
+
+/// textDocument/documentSymbol の結果を字下げしたツリーにする
+fn outline_text(response: Option<DocumentSymbolResponse>) -> String {
+    fn push_tree(symbols: &[DocumentSymbol], depth: usize, lines: &mut Vec<String>) {
+        for symbol in symbols {
+            lines.push(format!(
+                "{}{}  {}  :{}",
+                "  ".repeat(depth),
+                symbol.name,
+                symbol_kind_name(symbol.kind),
+                symbol.selection_range.start.line + 1
+            ));
+            push_tree(symbol.children.as_deref().unwrap_or_default(), depth + 1, lines);
+        }
+    }
+
+    let mut lines = Vec::new();
+    match response {
+        None => {}
+        Some(DocumentSymbolResponse::Nested(symbols)) => push_tree(&symbols, 0, &mut lines),
+        // 平坦な形では親の名前しか分からないので、親を持つものを1段下げる
+        Some(DocumentSymbolResponse::Flat(symbols)) => {
+            for symbol in symbols {
+                let nested = symbol.container_name.as_deref().is_some_and(|name| !name.is_empty());
+                lines.push(format!(
+                    "{}{}  {}  :{}",
+                    if nested { "  " } else { "" },
+                    symbol.name,
+                    symbol_kind_name(symbol.kind),
+                    symbol.location.range.start.line + 1
+                ));
+            }
+        }
+    }
+    if lines.is_empty() {
+        "No symbols found".to_string()
+    } else {
+        lines.join("\n")
+    }
+}
+
+/// LSP診断情報ツール
+///
+/// pull diagnostics に対応したサーバーには問い合わせ、それ以外は publishDiagnostics で届いたものを返す
 pub struct LspDiagnosticsTool {
-    #[allow(dead_code)]
-    client: Arc<Mutex<Option<LspClient>>>,
+    lsp: Arc<LspRouter>,
 }
 
 impl LspDiagnosticsTool {
-    pub fn new(client: Arc<Mutex<Option<LspClient>>>) -> Self {
-        Self { client }
+    pub fn new(lsp: Arc<LspRouter>) -> Self {
+        Self { lsp }
     }
 }
 
@@ -201,28 +739,302 @@ impl Tool for LspDiagnosticsTool {
         })
     }
 
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::ReadOnly
+    }
+
     async fn execute(&self, params: Value) -> Result<ToolResult> {
         let file_path = params.get("file_path")
             .and_then(|v| v.as_str())
             .ok_or_else(|| anyhow::anyhow!("Missing file_path"))?;
 
-        let guard = self.client.lock().await;
-        let client = guard.as_ref()
-            .ok_or_else(|| anyhow::anyhow!("LSP client not initialized"))?;
-
-        let path = PathBuf::from(file_path);
+        let path = self.lsp.resolve(file_path);
+        let client = self.lsp.for_path(&path).ensure_alive().await?;
         client.did_open(&path).await?;
 
-        match client.document_diagnostics(&path).await {
+        let diagnostics = if client.supports_pull_diagnostics().await {
+            client.document_diagnostics(&path).await
+        } else {
+            let items = client.published_diagnostics(&path, PUBLISHED_DIAGNOSTICS_WAIT).await.unwrap_or_default();
+            Ok(json!({ "items": items }))
+        };
+        match diagnostics {
             Ok(result) => {
                 if let Some(items) = result.get("items").and_then(|v| v.as_array()) {
                     if items.is_empty() {
-                        return Ok(ToolResult::success("No diagnostics found"));
+                        return Ok(ToolResult::success("No diagnostics found").with_data(result));
                     }
                 }
-                Ok(ToolResult::success(serde_json::to_string_pretty(&result)?))
+                Ok(ToolResult::success(serde_json::to_string_pretty(&result)?).with_data(result))
             }
             Err(e) => Ok(ToolResult::failure(format!("LSP error: {}", e))),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hover(payload: Value) -> Hover {
+        serde_json::from_value(payload).unwrap()
+    }
+
+    #[test]
+    fn test_hover_markdown_is_returned_as_is() {
+        // rust-analyzer の textDocument/hover の結果
+        let payload = json!({
+            "contents": {
+                "kind": "markdown",
+                "value": "\n```rust\nlocal_code::tools::lsp::client::LspClient\n```\n\n```rust\npub async fn hover(&self, file_path: &Path, line: u32, character: u32) -> Result<Option<Hover>>\n```\n\n---\n\nホバー情報（型・シグネチャ・ドキュメント）"
+            },
+            "range": {"start": {"line": 10, "character": 4}, "end": {"line": 10, "character": 9}}
+        });
+        let text = hover_text(Some(&hover(payload)));
+        assert!(text.starts_with("```rust\nlocal_code::tools::lsp::client::LspClient\n```"));
+        assert!(text.contains("pub async fn hover(&self"));
+        assert!(text.ends_with("---\n\nホバー情報（型・シグネチャ・ドキュメント）"));
+    }
+
+    #[test]
+    fn test_hover_marked_strings_are_joined() {
+        let payload = json!({
+            "contents": [
+                {"language": "python", "value": "def greet(name: str) -> str"},
+                "Return a greeting for *name*.",
+                ""
+            ]
+        });
+        assert_eq!(
+            hover_text(Some(&hover(payload))),
+            "```python\ndef greet(name: str) -> str\n```\n\nReturn a greeting for *name*."
+        );
+        assert_eq!(hover_text(Some(&hover(json!({"contents": "usize"})))), "usize");
+    }
+
+    /// rust-analyzer の workspace/symbol（SymbolInformation の配列）
+    fn config_symbols() -> Value {
+        json!([
+            {"name": "ConfigSource", "kind": 10, "location": {"uri": "file:///repo/src/config.rs", "range": {"start": {"line": 47, "character": 0}, "end": {"line": 55, "character": 1}}}},
+            {"name": "Config", "kind": 23, "location": {"uri": "file:///repo/src/config.rs", "range": {"start": {"line": 13, "character": 0}, "end": {"line": 45, "character": 1}}}},
+            {"name": "load_config", "kind": 12, "location": {"uri": "file:///repo/src/main.rs", "range": {"start": {"line": 99, "character": 0}, "end": {"line": 120, "character": 1}}}},
+            {"name": "ConfigExt", "kind": 11, "containerName": "config", "location": {"uri": "file:///repo/src/ext.rs", "range": {"start": {"line": 2, "character": 0}, "end": {"line": 9, "character": 1}}}}
+        ])
+    }
+
+    #[test]
+    fn test_workspace_symbols_flat_ranked_and_filtered() {
+        let response: Option<WorkspaceSymbolResponse> = serde_json::from_value(config_symbols()).unwrap();
+        assert_eq!(
+            workspace_symbols_text(response.clone(), "Config", None, 50),
+            "Config  struct  /repo/src/config.rs:14\n\
+             ConfigSource  enum  /repo/src/config.rs:48\n\
+             ConfigExt  interface  /repo/src/ext.rs:3\n\
+             load_config  function  /repo/src/main.rs:100"
+        );
+        assert_eq!(workspace_symbols_text(response.clone(), "Config", Some("trait"), 50), "ConfigExt  interface  /repo/src/ext.rs:3");
+        assert_eq!(workspace_symbols_text(response.clone(), "Config", Some("macro"), 50), "No symbols found");
+        assert_eq!(
+            workspace_symbols_text(response, "Config", None, 2),
+            "Config  struct  /repo/src/config.rs:14\nConfigSource  enum  /repo/src/config.rs:48\n... 2 more (use a more specific query or kind)"
+        );
+        assert_eq!(workspace_symbols_text(None, "x", None, 50), "No symbols found");
+    }
+
+    #[test]
+    fn test_workspace_symbols_nested_shape() {
+        // LSP 3.17 の WorkspaceSymbol（範囲のない location もありうる）
+        let response: Option<WorkspaceSymbolResponse> = serde_json::from_value(json!([
+            {"name": "parse", "kind": 12, "location": {"uri": "file:///repo/src/lib.rs", "range": {"start": {"line": 4, "character": 0}, "end": {"line": 8, "character": 1}}}},
+            {"name": "Parser", "kind": 5, "location": {"uri": "file:///repo/src/parser.rs"}}
+        ]))
+        .unwrap();
+        assert!(matches!(response, Some(WorkspaceSymbolResponse::Nested(_))));
+        assert_eq!(
+            workspace_symbols_text(response, "parse", None, 50),
+            "parse  function  /repo/src/lib.rs:5\nParser  class  /repo/src/parser.rs"
+        );
+    }
+
+    #[test]
+    fn test_outline_nested_and_flat() {
+        let range = |line: u32| json!({"start": {"line": line, "character": 0}, "end": {"line": line + 1, "character": 0}});
+        let nested: Option<DocumentSymbolResponse> = serde_json::from_value(json!([
+            {"name": "LspClient", "kind": 23, "range": range(19), "selectionRange": range(19), "children": [
+                {"name": "process", "kind": 8, "range": range(20), "selectionRange": range(20)}
+            ]},
+            {"name": "impl LspClient", "kind": 19, "range": range(42), "selectionRange": range(42), "children": [
+                {"name": "start", "kind": 12, "range": range(44), "selectionRange": range(44), "children": []}
+            ]}
+        ]))
+        .unwrap();
+        assert_eq!(
+            outline_text(nested),
+            "LspClient  struct  :20\n  process  field  :21\nimpl LspClient  object  :43\n  start  function  :45"
+        );
+
+        let flat: Option<DocumentSymbolResponse> = serde_json::from_value(json!([
+            {"name": "Config", "kind": 23, "location": {"uri": "file:///repo/src/config.rs", "range": range(13)}},
+            {"name": "load", "kind": 6, "containerName": "Config", "location": {"uri": "file:///repo/src/config.rs", "range": range(60)}}
+        ]))
+        .unwrap();
+        assert_eq!(outline_text(flat), "Config  struct  :14\n  load  method  :61");
+        assert_eq!(outline_text(None), "No symbols found");
+    }
+
+    #[test]
+    fn test_hover_without_information() {
+        assert_eq!(hover_text(None), "No hover information");
+        // `"result": null` は None になる
+        let empty: Option<Hover> = serde_json::from_value(Value::Null).unwrap();
+        assert_eq!(hover_text(empty.as_ref()), "No hover information");
+        assert_eq!(hover_text(Some(&hover(json!({"contents": {"kind": "plaintext", "value": "  "}})))), "No hover information");
+    }
+
+    /// initialize には空の capabilities、rename には1つ目の引数のファイルの中身を返す言語サーバー
+    #[cfg(unix)]
+    const RENAME_SERVER: &str = r#"
+edit=$1
+len=0
+while IFS= read -r line; do
+  line=$(printf '%s' "$line" | tr -d '\r')
+  case "$line" in
+    Content-Length:*) len=${line#Content-Length: } ;;
+    "")
+      body=$(dd bs=1 count="$len" 2>/dev/null)
+      id=$(printf '%s' "$body" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+      [ -z "$id" ] && continue
+      case "$body" in
+        *'"method":"initialize"'*) result='{"capabilities":{}}' ;;
+        *'"method":"textDocument/rename"'*) result=$(cat "$edit") ;;
+        *) result=null ;;
+      esac
+      reply="{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":$result}"
+      printf 'Content-Length: %s\r\n\r\n%s' "${#reply}" "$reply"
+      ;;
+  esac
+done
+"#;
+
+    /// 書き換えの通知を覚えておく
+    #[cfg(unix)]
+    #[derive(Default)]
+    struct Recorder(std::sync::Mutex<Vec<std::path::PathBuf>>);
+
+    #[cfg(unix)]
+    #[async_trait]
+    impl crate::tools::file::FileObserver for Recorder {
+        async fn file_written(&self, path: &std::path::Path) {
+            self.0.lock().unwrap().push(path.to_path_buf());
+        }
+
+        async fn file_removed(&self, _path: &std::path::Path) {}
+    }
+
+    /// 各ファイルの `old` を `new` に変える WorkspaceEdit を返す言語サーバー（`root` で起動する）
+    #[cfg(unix)]
+    fn rename_server(root: &std::path::Path, targets: &[&std::path::Path]) -> crate::tools::lsp::LspManager {
+        let changes: serde_json::Map<String, Value> = targets
+            .iter()
+            .map(|path| {
+                let uri = lsp_types::Url::from_file_path(path).unwrap().to_string();
+                let range = json!({"start": {"line": 0, "character": 3}, "end": {"line": 0, "character": 6}});
+                (uri, json!([{"range": range, "newText": "new"}]))
+            })
+            .collect();
+        let edit = root.join("edit.json");
+        std::fs::write(&edit, json!({"changes": changes}).to_string()).unwrap();
+        let args = vec!["-c".to_string(), RENAME_SERVER.to_string(), "fake-lsp".to_string(), edit.display().to_string()];
+        crate::tools::lsp::LspManager::new(Some("sh".to_string()), args, root)
+    }
+
+    /// rename_server につないだ rename ツール
+    #[cfg(unix)]
+    fn rename_tool(project: &std::path::Path, targets: &[&std::path::Path]) -> (Arc<crate::tools::lsp::LspManager>, LspRenameTool) {
+        let lsp = Arc::new(rename_server(project, targets));
+        (Arc::clone(&lsp), LspRenameTool::new(Arc::new(LspRouter::single(lsp))))
+    }
+
+    #[cfg(unix)]
+    fn rename_params(path: &std::path::Path) -> Value {
+        json!({"file_path": path.to_str().unwrap(), "line": 0, "character": 4, "new_name": "new"})
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_rename_writes_every_target_and_notifies() {
+        let project = tempfile::tempdir().unwrap();
+        let (lib, main) = (project.path().join("lib.rs"), project.path().join("main.rs"));
+        std::fs::write(&lib, "fn old() {}\n").unwrap();
+        std::fs::write(&main, "fn old() {}\n").unwrap();
+        let recorder = Arc::new(Recorder::default());
+        let (lsp, tool) = rename_tool(project.path(), &[&lib, &main]);
+        let tool = tool
+            .with_observer(recorder.clone())
+            .with_path_policy(Arc::new(PathPolicy::new(project.path())));
+
+        let result = tool.execute(rename_params(&lib)).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(std::fs::read_to_string(&lib).unwrap(), "fn new() {}\n");
+        assert_eq!(std::fs::read_to_string(&main).unwrap(), "fn new() {}\n");
+        assert_eq!(*recorder.0.lock().unwrap(), vec![lib, main]);
+        lsp.shutdown().await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_rename_outside_the_policy_writes_nothing() {
+        let project = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let (lib, other) = (project.path().join("lib.rs"), outside.path().join("other.rs"));
+        std::fs::write(&lib, "fn old() {}\n").unwrap();
+        std::fs::write(&other, "fn old() {}\n").unwrap();
+        let policy = Arc::new(PathPolicy::new(project.path()).with_read_outside(true));
+
+        // 編集先の1つがルートの外なら、中のファイルも書き換えない
+        let (lsp, tool) = rename_tool(project.path(), &[&lib, &other]);
+        let tool = tool.with_path_policy(Arc::clone(&policy));
+        let result = tool.execute(rename_params(&lib)).await.unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("Access denied by path policy"));
+        assert_eq!(std::fs::read_to_string(&lib).unwrap(), "fn old() {}\n");
+        assert_eq!(std::fs::read_to_string(&other).unwrap(), "fn old() {}\n");
+        lsp.shutdown().await;
+
+        // 指定したファイル自体がルートの外なら、言語サーバーに尋ねる前に断る
+        let (lsp, tool) = rename_tool(project.path(), &[&other]);
+        let result = tool.with_path_policy(policy).execute(rename_params(&other)).await.unwrap();
+        assert!(result.error.unwrap().contains("Access denied by path policy"));
+        assert_eq!(lsp.is_running().await, None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_rename_asks_the_server_of_the_root_that_owns_the_file() {
+        use crate::tools::Workspace;
+
+        let dir = tempfile::tempdir().unwrap();
+        let (api, web) = (dir.path().join("api"), dir.path().join("web"));
+        std::fs::create_dir_all(&api).unwrap();
+        std::fs::create_dir_all(&web).unwrap();
+        let file = web.join("app.ts");
+        std::fs::write(&file, "fn old() {}\n").unwrap();
+        // 最初のルートには言語サーバーがないので、web のサーバーに届かなければ失敗する
+        let workspace = Arc::new(Workspace::from_paths(&[api.clone(), web.clone()]).unwrap());
+        let router = Arc::new(LspRouter::for_workspace(workspace, |root| {
+            if root == web {
+                rename_server(root, &[&file])
+            } else {
+                crate::tools::lsp::LspManager::new(None, Vec::new(), root)
+            }
+        }));
+        let tool = LspRenameTool::new(Arc::clone(&router));
+
+        let params = json!({"file_path": "web:app.ts", "line": 0, "character": 4, "new_name": "new"});
+        let result = tool.execute(params).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "fn new() {}\n");
+        assert_eq!(router.primary().is_running().await, None);
+        router.shutdown().await;
+    }
+}
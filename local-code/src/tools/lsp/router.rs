@@ -0,0 +1,150 @@
+//! ワークスペースのルートごとの言語サーバー
+//!
+//! 複数ルートのワークスペースでは、ルートごとに LspManager を持ち、ファイルを含むルートの
+//! 言語サーバーに問い合わせる。最初のルート以外の言語サーバーは初めて使うときに起動する。
+
+use anyhow::Result;
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::manager::LspManager;
+use crate::agent::session::{ResetAction, ResetTarget, SessionResettable};
+use crate::tools::file::FileObserver;
+use crate::tools::Workspace;
+
+/// ルートのパスをキーにした言語サーバー
+pub struct LspRouter {
+    /// ルートと言語サーバー（先頭が最初のルート）
+    managers: Vec<(PathBuf, Arc<LspManager>)>,
+    /// `ラベル:パス` の解決に使う
+    workspace: Option<Arc<Workspace>>,
+}
+
+impl LspRouter {
+    /// 言語サーバー1つだけ（すべてのパスをこれに送る）
+    pub fn single(manager: Arc<LspManager>) -> Self {
+        Self { managers: vec![(PathBuf::new(), manager)], workspace: None }
+    }
+
+    /// ワークスペースの各ルートに言語サーバーを用意する（`make` はルートから LspManager を作る）
+    pub fn for_workspace(workspace: Arc<Workspace>, make: impl Fn(&Path) -> LspManager) -> Self {
+        let managers = workspace
+            .roots()
+            .iter()
+            .map(|root| (absolute(&root.path), Arc::new(make(&root.path))))
+            .collect();
+        Self { managers, workspace: Some(workspace) }
+    }
+
+    /// 最初のルートの言語サーバー（/status、/lsp restart）
+    pub fn primary(&self) -> &Arc<LspManager> {
+        &self.managers[0].1
+    }
+
+    /// すべての言語サーバー
+    pub fn managers(&self) -> impl Iterator<Item = &Arc<LspManager>> {
+        self.managers.iter().map(|(_, manager)| manager)
+    }
+
+    /// ツールに渡されたパスを解決する（`ラベル:パス`、ラベルのない相対パスは現在のルートから）
+    pub fn resolve(&self, path: &str) -> PathBuf {
+        match &self.workspace {
+            Some(workspace) => workspace.resolve(path),
+            None => PathBuf::from(path),
+        }
+    }
+
+    /// パスを含むルートの言語サーバー（入れ子なら深い方、どのルートにもなければ最初のルート）
+    pub fn for_path(&self, path: &Path) -> &Arc<LspManager> {
+        let path = absolute(path);
+        self.managers
+            .iter()
+            .filter(|(root, _)| !root.as_os_str().is_empty() && path.starts_with(root))
+            .max_by_key(|(root, _)| root.components().count())
+            .map_or_else(|| self.primary(), |(_, manager)| manager)
+    }
+
+    /// 現在のルートの言語サーバー（ファイルを指定しない lsp_symbols）
+    pub fn for_current(&self) -> &Arc<LspManager> {
+        match &self.workspace {
+            Some(workspace) => self.for_path(&workspace.current().path),
+            None => self.primary(),
+        }
+    }
+
+    /// 最初のルートと、起動したことのあるほかのルートの言語サーバーを起動し直す（/lsp restart）
+    ///
+    /// 開き直したファイル数の合計を返す
+    pub async fn restart(&self) -> Result<usize> {
+        let mut reopened = self.primary().restart().await?;
+        for manager in self.managers().skip(1) {
+            if manager.command().is_some() && manager.is_running().await.is_some() {
+                reopened += manager.restart().await?;
+            }
+        }
+        Ok(reopened)
+    }
+
+    /// すべての言語サーバーを終了する
+    pub async fn shutdown(&self) {
+        for manager in self.managers() {
+            manager.shutdown().await;
+        }
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// 書き換えたファイルを含むルートの言語サーバーに伝える
+#[async_trait]
+impl FileObserver for LspRouter {
+    async fn file_written(&self, path: &Path) {
+        self.for_path(path).file_written(path).await;
+    }
+
+    async fn file_removed(&self, path: &Path) {
+        self.for_path(path).file_removed(path).await;
+    }
+}
+
+/// セッション遷移ではすべてのルートで開いていたドキュメントを閉じる
+impl SessionResettable for LspRouter {
+    fn reset_target(&self) -> ResetTarget {
+        ResetTarget::LspDocuments
+    }
+
+    fn reset(&self, action: ResetAction) {
+        for manager in self.managers() {
+            manager.reset(action);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paths_go_to_the_root_that_owns_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let (api, web) = (dir.path().join("api"), dir.path().join("web"));
+        std::fs::create_dir_all(&api).unwrap();
+        std::fs::create_dir_all(&web).unwrap();
+        let workspace = Arc::new(Workspace::from_paths(&[api.clone(), web.clone()]).unwrap());
+        let router = LspRouter::for_workspace(workspace, |root| LspManager::new(None, Vec::new(), root));
+        let root_of = |manager: &Arc<LspManager>| {
+            router.managers().position(|m| Arc::ptr_eq(m, manager)).unwrap()
+        };
+
+        assert_eq!(root_of(router.for_path(&web.join("src/app.ts"))), 1);
+        assert_eq!(root_of(router.for_path(&api.join("src/main.rs"))), 0);
+        assert_eq!(root_of(router.for_path(&router.resolve("web:src/app.ts"))), 1);
+        // ラベルのない相対パスは現在のルート、どのルートにもないパスは最初のルート
+        assert_eq!(router.resolve("src/main.rs"), api.join("src/main.rs"));
+        assert_eq!(root_of(router.for_path(Path::new("/elsewhere/x.rs"))), 0);
+        assert_eq!(root_of(router.for_current()), 0);
+    }
+}
@@ -0,0 +1,55 @@
+//! 言語サーバーの状態報告（/status）
+
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use super::manager::LspManager;
+use crate::agent::status::{StatusProvider, SubsystemStatus};
+
+/// 言語サーバーの起動状態を報告
+pub struct LspStatus {
+    lsp: Arc<LspManager>,
+}
+
+impl LspStatus {
+    pub fn new(lsp: Arc<LspManager>) -> Self {
+        Self { lsp }
+    }
+}
+
+#[async_trait]
+impl StatusProvider for LspStatus {
+    async fn status(&self) -> SubsystemStatus {
+        let Some(command) = self.lsp.command() else {
+            return SubsystemStatus::disabled("lsp", "no language server configured");
+        };
+        match self.lsp.is_running().await {
+            None => SubsystemStatus::failed("lsp", format!("{} failed to start", command))
+                .with_hint(Some(format!("Check that `{}` is installed and on PATH, or set [lsp] command", command))),
+            Some(false) => SubsystemStatus::failed("lsp", format!("{} has exited", command))
+                .with_hint(Some("It is restarted on the next LSP tool call, or run /lsp restart")),
+            Some(true) => match self.lsp.restart_count() {
+                0 => SubsystemStatus::ok("lsp", format!("{} running", command)),
+                n => SubsystemStatus::ok("lsp", format!("{} running (restarted {} time{})", command, n, if n == 1 { "" } else { "s" })),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::status::HealthState;
+
+    #[tokio::test]
+    async fn test_lsp_status_states() {
+        let unconfigured = Arc::new(LspManager::new(None, Vec::new(), "."));
+        assert_eq!(LspStatus::new(unconfigured).status().await.state, HealthState::Disabled);
+
+        let missing = Arc::new(LspManager::new(Some("local-code-no-such-lsp".to_string()), Vec::new(), "."));
+        assert!(missing.start().await.is_err());
+        let failed = LspStatus::new(missing).status().await;
+        assert_eq!(failed.state, HealthState::Failed);
+        assert!(failed.detail.ends_with("failed to start"));
+    }
+}
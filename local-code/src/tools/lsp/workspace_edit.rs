@@ -0,0 +1,346 @@
+//! WorkspaceEdit のファイルへの適用（lsp_rename）
+//!
+//! 位置は LSP 既定の UTF-16 単位で数える。ファイルごとに全編集を計算してから書き込むので、
+//! どれか1つでも適用できなければ何も書き換えない。書き込みも同じディレクトリの一時ファイルに
+//! すべて書けてから置き換えるので、途中で失敗しても一部のファイルだけが変わることはない
+
+use anyhow::{bail, Context, Result};
+use lsp_types::{DocumentChangeOperation, DocumentChanges, OneOf, Position, TextEdit, Url, WorkspaceEdit};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// 1ファイル分の編集
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileEdits {
+    pub path: PathBuf,
+    /// サーバーが前提にしたドキュメントのバージョン（`documentChanges` のときだけ）
+    pub version: Option<i32>,
+    pub edits: Vec<TextEdit>,
+}
+
+/// 適用後の内容
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedEdit {
+    pub path: PathBuf,
+    pub text: String,
+    pub edit_count: usize,
+}
+
+fn file_path(uri: &Url) -> Result<PathBuf> {
+    uri.to_file_path().map_err(|_| anyhow::anyhow!("Not a file URI: {}", uri))
+}
+
+/// WorkspaceEdit をファイルごとの編集にまとめる（パス順）
+///
+/// `documentChanges` があればそちらを使う。ファイルの作成・名前変更・削除は扱わない
+pub fn collect(edit: &WorkspaceEdit) -> Result<Vec<FileEdits>> {
+    let mut files: BTreeMap<PathBuf, FileEdits> = BTreeMap::new();
+    let mut add = |path: PathBuf, version: Option<i32>, edits: Vec<TextEdit>| {
+        let entry = files.entry(path.clone()).or_insert_with(|| FileEdits { path, version: None, edits: Vec::new() });
+        entry.version = entry.version.or(version);
+        entry.edits.extend(edits);
+    };
+
+    match &edit.document_changes {
+        Some(changes) => {
+            let document_edits = match changes {
+                DocumentChanges::Edits(edits) => edits.iter().collect::<Vec<_>>(),
+                DocumentChanges::Operations(operations) => operations
+                    .iter()
+                    .map(|operation| match operation {
+                        DocumentChangeOperation::Edit(edit) => Ok(edit),
+                        DocumentChangeOperation::Op(_) => bail!("The edit creates, renames or deletes files, which is not supported"),
+                    })
+                    .collect::<Result<Vec<_>>>()?,
+            };
+            for document in document_edits {
+                let edits = document
+                    .edits
+                    .iter()
+                    .map(|edit| match edit {
+                        OneOf::Left(edit) => edit.clone(),
+                        OneOf::Right(annotated) => annotated.text_edit.clone(),
+                    })
+                    .collect();
+                add(file_path(&document.text_document.uri)?, document.text_document.version, edits);
+            }
+        }
+        None => {
+            for (uri, edits) in edit.changes.iter().flatten() {
+                add(file_path(uri)?, None, edits.clone());
+            }
+        }
+    }
+    Ok(files.into_values().filter(|file| !file.edits.is_empty()).collect())
+}
+
+/// 行頭のバイト位置
+fn line_starts(text: &str) -> Vec<usize> {
+    std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect()
+}
+
+/// LSP の位置をバイト位置にする（行末を越える文字位置は行末に丸める）
+fn offset(text: &str, starts: &[usize], position: Position) -> Result<usize> {
+    let line = position.line as usize;
+    let Some(&start) = starts.get(line) else {
+        // 末尾の次の行の先頭は EOF を指す
+        if line == starts.len() && position.character == 0 {
+            return Ok(text.len());
+        }
+        bail!("Line {} is past the end of the file", line + 1);
+    };
+    let end = starts.get(line + 1).map(|next| next - 1).unwrap_or(text.len());
+    let content = text[start..end].strip_suffix('\r').unwrap_or(&text[start..end]);
+
+    let mut units = 0;
+    for (i, c) in content.char_indices() {
+        if units >= position.character as usize {
+            return Ok(start + i);
+        }
+        units += c.len_utf16();
+    }
+    Ok(start + content.len())
+}
+
+/// テキストに編集を適用する
+///
+/// 重なる編集は拒否し、後ろの範囲から順に置き換えてオフセットがずれないようにする
+pub fn apply_text_edits(text: &str, edits: &[TextEdit]) -> Result<String> {
+    let starts = line_starts(text);
+    let mut ranges = edits
+        .iter()
+        .enumerate()
+        .map(|(index, edit)| {
+            let start = offset(text, &starts, edit.range.start)?;
+            let end = offset(text, &starts, edit.range.end)?;
+            if start > end {
+                bail!("Edit range ends before it starts at line {}", edit.range.start.line + 1);
+            }
+            Ok((start, end, index))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    ranges.sort();
+    for pair in ranges.windows(2) {
+        if pair[0].1 > pair[1].0 {
+            bail!("Overlapping edits at byte {}", pair[1].0);
+        }
+    }
+
+    let mut result = text.to_string();
+    // 同じ位置への挿入は元の順に並ぶよう、後ろの編集から適用する
+    for &(start, end, index) in ranges.iter().rev() {
+        result.replace_range(start..end, &edits[index].new_text);
+    }
+    Ok(result)
+}
+
+/// すべてのファイルの適用後の内容を計算する（書き込みはしない）
+pub fn plan(files: &[FileEdits], read: impl Fn(&Path) -> Result<String>) -> Result<Vec<PlannedEdit>> {
+    files
+        .iter()
+        .map(|file| {
+            let original = read(&file.path).with_context(|| format!("Failed to read {}", file.path.display()))?;
+            let text = apply_text_edits(&original, &file.edits).with_context(|| format!("Cannot apply the edit to {}", file.path.display()))?;
+            Ok(PlannedEdit { path: file.path.clone(), text, edit_count: file.edits.len() })
+        })
+        .collect()
+}
+
+/// 書き込み途中の内容を置く一時ファイル（置き換えが同じファイルシステム内で済むよう隣に置く）
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{}.local-code-rename", name))
+}
+
+/// 計算した内容をまとめて書き込む（すべて書けるか、どれも変えないか）
+///
+/// まず全ファイルを一時ファイルに書き、すべて成功してから元のファイルと置き換える。
+/// 置き換えの途中で失敗したら、置き換え済みのファイルを元の内容に戻す
+pub fn write_all(planned: &[PlannedEdit]) -> Result<()> {
+    let mut staged = Vec::with_capacity(planned.len());
+    let staging = planned.iter().try_for_each(|file| -> Result<()> {
+        let original = std::fs::read_to_string(&file.path).with_context(|| format!("Failed to read {}", file.path.display()))?;
+        let temp = temp_path(&file.path);
+        if let Err(e) = std::fs::write(&temp, &file.text) {
+            let _ = std::fs::remove_file(&temp);
+            return Err(e).with_context(|| format!("Failed to write {}", file.path.display()));
+        }
+        staged.push((file, temp.clone(), original));
+        std::fs::set_permissions(&temp, std::fs::metadata(&file.path)?.permissions())?;
+        Ok(())
+    });
+    if let Err(e) = staging {
+        for (_, temp, _) in &staged {
+            let _ = std::fs::remove_file(temp);
+        }
+        return Err(e);
+    }
+
+    for (index, (file, temp, _)) in staged.iter().enumerate() {
+        if let Err(e) = std::fs::rename(temp, &file.path) {
+            for (file, _, original) in &staged[..index] {
+                if let Err(e) = std::fs::write(&file.path, original) {
+                    tracing::warn!("Failed to restore {}: {}", file.path.display(), e);
+                }
+            }
+            for (_, temp, _) in &staged[index..] {
+                let _ = std::fs::remove_file(temp);
+            }
+            return Err(e).with_context(|| format!("Failed to replace {}", file.path.display()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::Range;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn edit(start: (u32, u32), end: (u32, u32), new_text: &str) -> TextEdit {
+        TextEdit {
+            range: Range { start: Position::new(start.0, start.1), end: Position::new(end.0, end.1) },
+            new_text: new_text.to_string(),
+        }
+    }
+
+    fn files() -> HashMap<PathBuf, String> {
+        HashMap::from([
+            (PathBuf::from("/repo/src/config.rs"), "pub struct Config {}\n\nimpl Config {\n    fn new() -> Config { Config {} }\n}\n".to_string()),
+            (PathBuf::from("/repo/src/main.rs"), "use config::Config;\r\nfn main() { let _c = Config::new(); }\r\n".to_string()),
+        ])
+    }
+
+    fn read(files: &HashMap<PathBuf, String>) -> impl Fn(&Path) -> Result<String> + '_ {
+        |path| files.get(path).cloned().ok_or_else(|| anyhow::anyhow!("no such file"))
+    }
+
+    #[test]
+    fn test_multiple_edits_per_file_apply_bottom_up() {
+        let text = "pub struct Config {}\n\nimpl Config {\n    fn new() -> Config { Config {} }\n}\n";
+        // 並びが前後していても、後ろから適用すれば位置はずれない
+        let edits = [
+            edit((3, 25), (3, 31), "Settings"),
+            edit((0, 11), (0, 17), "Settings"),
+            edit((3, 16), (3, 22), "Settings"),
+            edit((2, 5), (2, 11), "Settings"),
+        ];
+        assert_eq!(
+            apply_text_edits(text, &edits).unwrap(),
+            "pub struct Settings {}\n\nimpl Settings {\n    fn new() -> Settings { Settings {} }\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_positions_count_utf16_and_clamp_to_line_end() {
+        // 😀 は UTF-16 で2単位
+        let text = "let 名前 = \"😀x\";\r\nnext";
+        assert_eq!(apply_text_edits(text, &[edit((0, 4), (0, 6), "name")]).unwrap(), "let name = \"😀x\";\r\nnext");
+        assert_eq!(apply_text_edits(text, &[edit((0, 12), (0, 13), "y")]).unwrap(), "let 名前 = \"😀y\";\r\nnext");
+        // 行末を越える位置は \r の手前に丸める
+        assert_eq!(apply_text_edits(text, &[edit((0, 99), (0, 99), " // ok")]).unwrap(), "let 名前 = \"😀x\"; // ok\r\nnext");
+        // 同じ位置への挿入は元の順
+        assert_eq!(apply_text_edits("b", &[edit((0, 0), (0, 0), "a1"), edit((0, 0), (0, 0), "a2")]).unwrap(), "a1a2b");
+        assert!(apply_text_edits(text, &[edit((0, 0), (0, 5), "x"), edit((0, 3), (0, 6), "y")]).is_err());
+        assert!(apply_text_edits(text, &[edit((5, 0), (5, 1), "x")]).is_err());
+    }
+
+    #[test]
+    fn test_plan_from_changes_map() {
+        let edit: WorkspaceEdit = serde_json::from_value(json!({
+            "changes": {
+                "file:///repo/src/main.rs": [
+                    {"range": {"start": {"line": 0, "character": 12}, "end": {"line": 0, "character": 18}}, "newText": "Settings"},
+                    {"range": {"start": {"line": 1, "character": 21}, "end": {"line": 1, "character": 27}}, "newText": "Settings"}
+                ],
+                "file:///repo/src/config.rs": [
+                    {"range": {"start": {"line": 0, "character": 11}, "end": {"line": 0, "character": 17}}, "newText": "Settings"}
+                ]
+            }
+        }))
+        .unwrap();
+        let collected = collect(&edit).unwrap();
+        assert_eq!(collected.iter().map(|f| f.path.clone()).collect::<Vec<_>>(), vec![PathBuf::from("/repo/src/config.rs"), PathBuf::from("/repo/src/main.rs")]);
+        assert!(collected.iter().all(|f| f.version.is_none()));
+
+        let fixtures = files();
+        let planned = plan(&collected, read(&fixtures)).unwrap();
+        assert_eq!(planned[0].edit_count, 1);
+        assert!(planned[0].text.starts_with("pub struct Settings {}"));
+        assert_eq!(planned[1].edit_count, 2);
+        assert_eq!(planned[1].text, "use config::Settings;\r\nfn main() { let _c = Settings::new(); }\r\n");
+    }
+
+    #[test]
+    fn test_versioned_document_changes() {
+        let workspace: WorkspaceEdit = serde_json::from_value(json!({
+            "documentChanges": [
+                {
+                    "textDocument": {"uri": "file:///repo/src/config.rs", "version": 3},
+                    "edits": [
+                        {"range": {"start": {"line": 0, "character": 11}, "end": {"line": 0, "character": 17}}, "newText": "Settings"},
+                        {"range": {"start": {"line": 2, "character": 5}, "end": {"line": 2, "character": 11}}, "newText": "Settings", "annotationId": "rename"}
+                    ]
+                },
+                {
+                    "textDocument": {"uri": "file:///repo/src/main.rs", "version": null},
+                    "edits": [{"range": {"start": {"line": 0, "character": 12}, "end": {"line": 0, "character": 18}}, "newText": "Settings"}]
+                }
+            ],
+            // documentChanges があれば changes は使わない
+            "changes": {"file:///repo/src/other.rs": []}
+        }))
+        .unwrap();
+        let collected = collect(&workspace).unwrap();
+        assert_eq!(collected.len(), 2);
+        assert_eq!(collected[0].version, Some(3));
+        assert_eq!(collected[0].edits.len(), 2);
+        assert_eq!(collected[1].version, None);
+
+        let fixtures = files();
+        let planned = plan(&collected, read(&fixtures)).unwrap();
+        assert_eq!(planned[0].text, "pub struct Settings {}\n\nimpl Settings {\n    fn new() -> Config { Config {} }\n}\n");
+        assert!(planned[1].text.starts_with("use config::Settings;"));
+
+        // 読めないファイルがあれば何も計画しない
+        let missing = FileEdits { path: PathBuf::from("/repo/src/gone.rs"), version: None, edits: vec![edit((0, 0), (0, 0), "x")] };
+        assert!(plan(&[collected[0].clone(), missing], read(&fixtures)).is_err());
+    }
+
+    #[test]
+    fn test_resource_operations_are_refused() {
+        let edit: WorkspaceEdit = serde_json::from_value(json!({
+            "documentChanges": [
+                {"kind": "rename", "oldUri": "file:///repo/src/config.rs", "newUri": "file:///repo/src/settings.rs"}
+            ]
+        }))
+        .unwrap();
+        assert!(collect(&edit).unwrap_err().to_string().contains("not supported"));
+    }
+
+    #[test]
+    fn test_write_all_changes_nothing_when_one_target_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let (config, main) = (dir.path().join("config.rs"), dir.path().join("main.rs"));
+        std::fs::write(&config, "struct Config;\n").unwrap();
+        std::fs::write(&main, "use Config;\n").unwrap();
+        let planned = |path: &Path, text: &str| PlannedEdit { path: path.to_path_buf(), text: text.to_string(), edit_count: 1 };
+        let edits = [planned(&config, "struct Settings;\n"), planned(&main, "use Settings;\n")];
+
+        // 2つ目の一時ファイルを置けなくすると、1つ目も書き換えない
+        std::fs::create_dir(temp_path(&main)).unwrap();
+        assert!(write_all(&edits).unwrap_err().to_string().contains("main.rs"));
+        assert_eq!(std::fs::read_to_string(&config).unwrap(), "struct Config;\n");
+        assert_eq!(std::fs::read_to_string(&main).unwrap(), "use Config;\n");
+        assert!(!temp_path(&config).exists());
+
+        std::fs::remove_dir(temp_path(&main)).unwrap();
+        write_all(&edits).unwrap();
+        assert_eq!(std::fs::read_to_string(&config).unwrap(), "struct Settings;\n");
+        assert_eq!(std::fs::read_to_string(&main).unwrap(), "use Settings;\n");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+}
@@ -4,6 +4,14 @@ pub mod search;
 pub mod bash;
 pub mod git;
 pub mod lsp;
+pub mod external;
+pub mod web;
+pub mod todo;
+pub mod payload;
+pub mod dry_run;
+pub mod disk;
+pub mod workspace;
+pub mod path_policy;
 
 use anyhow::Result;
 use async_trait::async_trait;
@@ -19,6 +27,9 @@ pub struct ToolResult {
     pub output: String,
     /// エラーメッセージ（失敗時）
     pub error: Option<String>,
+    /// 構造化された結果（形は payload モジュールを参照）。会話には output だけを渡す
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
 }
 
 impl ToolResult {
@@ -27,6 +38,7 @@ impl ToolResult {
             success: true,
             output: output.into(),
             error: None,
+            data: None,
         }
     }
 
@@ -35,6 +47,42 @@ impl ToolResult {
             success: false,
             output: String::new(),
             error: Some(error.into()),
+            data: None,
+        }
+    }
+
+    /// 構造化された結果を付ける（シリアライズできなければ付けない）
+    pub fn with_data(mut self, data: impl Serialize) -> Self {
+        self.data = serde_json::to_value(data).ok();
+        self
+    }
+}
+
+/// ツールの副作用
+///
+/// モードごとの許可と実行前確認の要否はこの宣言だけで決まる
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ToolEffects {
+    /// 読み取りのみ
+    ReadOnly,
+    /// ファイルやリポジトリを変更する
+    Writes,
+    /// 任意のコマンドを実行する
+    Executes,
+}
+
+impl ToolEffects {
+    /// 実行前にユーザー確認が必要か
+    pub fn requires_confirmation(&self) -> bool {
+        *self != ToolEffects::ReadOnly
+    }
+
+    /// 副作用の説明（拒否メッセージ用）
+    pub fn describe(&self) -> &'static str {
+        match self {
+            ToolEffects::ReadOnly => "only reads",
+            ToolEffects::Writes => "modifies files",
+            ToolEffects::Executes => "runs commands",
         }
     }
 }
@@ -62,9 +110,33 @@ pub trait Tool: Send + Sync {
     /// パラメータスキーマを取得（JSON Schema）
     fn parameters_schema(&self) -> Value;
 
+    /// ツールの副作用（未宣言のツールは最も制限の強い Executes 扱い）
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::Executes
+    }
+
+    /// この呼び出しの副作用（一覧表示など、引数によって読み取りだけで済む呼び出しは弱めて返す）
+    ///
+    /// `effects` はすべての呼び出しのうち最も強いものを宣言する
+    fn call_effects(&self, _params: &Value) -> ToolEffects {
+        self.effects()
+    }
+
+    /// 実行中にツール自身が確認するか（差分やコミット一覧を見せるもの。エージェントは実行前に確認しない）
+    fn confirms_itself(&self) -> bool {
+        false
+    }
+
     /// ツールを実行
     async fn execute(&self, params: Value) -> Result<ToolResult>;
 
+    /// ドライランでの模擬実行（実際の変更はせず、結果をオーバーレイに記録する）
+    ///
+    /// None なら模擬しない。読み取り系はそのまま実行され、変更系は「実行しなかった」とだけ返る
+    async fn simulate(&self, _params: Value, _overlay: &Overlay) -> Result<Option<ToolResult>> {
+        Ok(None)
+    }
+
     /// ツール定義を取得
     fn definition(&self) -> ToolDefinition {
         ToolDefinition {
@@ -76,3 +148,52 @@ pub trait Tool: Send + Sync {
 }
 
 pub use registry::ToolRegistry;
+pub use dry_run::{DryRun, Overlay};
+pub use disk::{DiskStatus, WriteGuard};
+pub use workspace::{Workspace, WorkspaceRoot};
+pub use path_policy::{PathGrant, PathGrantGuard, PathPolicy};
+pub use todo::{render_todos, TodoItem, TodoList, TodoStatus, TodoTool};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{Mode, ModeManager};
+    use std::sync::Arc;
+
+    /// effects を宣言し忘れたツール
+    struct Forgetful;
+
+    #[async_trait]
+    impl Tool for Forgetful {
+        fn name(&self) -> &str {
+            "forgetful"
+        }
+
+        fn description(&self) -> &str {
+            "does not declare effects"
+        }
+
+        fn parameters_schema(&self) -> Value {
+            serde_json::json!({"type": "object"})
+        }
+
+        async fn execute(&self, _params: Value) -> Result<ToolResult> {
+            Ok(ToolResult::success(""))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_undeclared_effects_default_to_denied_path() {
+        assert_eq!(Forgetful.effects(), ToolEffects::Executes);
+        assert!(Forgetful.effects().requires_confirmation());
+
+        let mut registry = ToolRegistry::new();
+        registry.register(Arc::new(Forgetful));
+        let manager = ModeManager::new(Mode::Plan).with_tool_effects(registry.effects());
+        assert!(!manager.is_tool_allowed("forgetful").await);
+        assert!(manager.requires_confirmation("forgetful"));
+
+        manager.to_execute().await;
+        assert!(manager.is_tool_allowed("forgetful").await);
+    }
+}
@@ -0,0 +1,260 @@
+//! ファイルツールが扱えるパスの制限
+//!
+//! プロジェクトルートと `[tools] extra_allowed_paths` の下だけを許可する。
+//! パスは `..` とシンボリックリンクを解決してから判定するので、リンク経由の脱出も拒否する。
+//! 読み取りだけは `allow_read_outside_project` で外側も許可できるが、書き込みは常に制限する。
+//! スキルの `extra_paths` は `grant` のガードが生きている間（そのスキルのターン）だけ許可に加わる
+
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// 一時的に許可するルート
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathGrant {
+    pub root: PathBuf,
+    /// 書き込みも許可するか
+    pub write: bool,
+}
+
+impl PathGrant {
+    pub fn new(root: impl AsRef<Path>, write: bool) -> Self {
+        Self { root: canonical_root(root.as_ref()), write }
+    }
+}
+
+/// 許可するルートとパスの判定
+#[derive(Debug)]
+pub struct PathPolicy {
+    /// 許可するルート（実体パス、先頭がプロジェクトルート）
+    roots: Vec<PathBuf>,
+    /// ルートの外の読み取りを許可するか
+    allow_read_outside: bool,
+    /// 一時的に許可しているルート（`grant` のガードが外す）
+    grants: RwLock<Vec<PathGrant>>,
+}
+
+impl Clone for PathPolicy {
+    fn clone(&self) -> Self {
+        Self {
+            roots: self.roots.clone(),
+            allow_read_outside: self.allow_read_outside,
+            grants: RwLock::new(self.grants.read().map(|g| g.clone()).unwrap_or_default()),
+        }
+    }
+}
+
+impl PathPolicy {
+    pub fn new(project_root: impl AsRef<Path>) -> Self {
+        Self {
+            roots: vec![canonical_root(project_root.as_ref())],
+            allow_read_outside: false,
+            grants: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// プロジェクトルート以外に許可するディレクトリを追加
+    pub fn with_allowed_paths<I, P>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        self.roots.extend(paths.into_iter().map(|p| canonical_root(p.as_ref())));
+        self
+    }
+
+    /// ルートの外の読み取りを許可する
+    pub fn with_read_outside(mut self, allow: bool) -> Self {
+        self.allow_read_outside = allow;
+        self
+    }
+
+    /// ガードを破棄するまで `grants` を許可に加える
+    ///
+    /// ガードはエラーで抜けたときや中断で future ごと捨てられたときにも破棄されるので、必ず元に戻る
+    pub fn grant(self: &Arc<Self>, grants: Vec<PathGrant>) -> PathGrantGuard {
+        if let Ok(mut current) = self.grants.write() {
+            current.extend(grants.iter().cloned());
+        }
+        PathGrantGuard { policy: Arc::clone(self), grants }
+    }
+
+    /// 読み取ってよいか（実体パスを返す）
+    pub fn check_read(&self, path: &Path) -> Result<PathBuf, String> {
+        let resolved = resolve(path)?;
+        if self.allow_read_outside || self.is_allowed(&resolved) || self.is_granted(&resolved, false) {
+            Ok(resolved)
+        } else {
+            Err(format!(
+                "Access denied by path policy: {} is outside the project root \
+                 (allow it with [tools] extra_allowed_paths, or allow_read_outside_project = true for reads)",
+                path.display()
+            ))
+        }
+    }
+
+    /// 書き込んでよいか（実体パスを返す）
+    pub fn check_write(&self, path: &Path) -> Result<PathBuf, String> {
+        let resolved = resolve(path)?;
+        if self.is_allowed(&resolved) || self.is_granted(&resolved, true) {
+            Ok(resolved)
+        } else {
+            Err(format!(
+                "Access denied by path policy: {} is outside the project root \
+                 (writes are only allowed under it and [tools] extra_allowed_paths)",
+                path.display()
+            ))
+        }
+    }
+
+    fn is_allowed(&self, resolved: &Path) -> bool {
+        self.roots.iter().any(|root| resolved.starts_with(root))
+    }
+
+    fn is_granted(&self, resolved: &Path, write: bool) -> bool {
+        self.grants
+            .read()
+            .is_ok_and(|grants| grants.iter().any(|g| (g.write || !write) && resolved.starts_with(&g.root)))
+    }
+}
+
+/// 一時的な許可を持つガード（破棄すると許可を外す）
+#[derive(Debug)]
+pub struct PathGrantGuard {
+    policy: Arc<PathPolicy>,
+    grants: Vec<PathGrant>,
+}
+
+impl PathGrantGuard {
+    pub fn grants(&self) -> &[PathGrant] {
+        &self.grants
+    }
+}
+
+impl Drop for PathGrantGuard {
+    fn drop(&mut self) {
+        if let Ok(mut current) = self.policy.grants.write() {
+            for grant in &self.grants {
+                if let Some(i) = current.iter().position(|g| g == grant) {
+                    current.remove(i);
+                }
+            }
+        }
+    }
+}
+
+/// ルートの実体パス（存在しなければ絶対パスのまま）
+fn canonical_root(path: &Path) -> PathBuf {
+    path.canonicalize()
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// 実際にアクセスされるパスに解決する
+///
+/// 存在する部分は OS に解決させ（`link/..` もリンク先の親になる）、
+/// まだ存在しない残りだけを字面で `..` を畳んで連結する
+fn resolve(path: &Path) -> Result<PathBuf, String> {
+    let absolute = std::path::absolute(path).map_err(|e| format!("Invalid path {}: {}", path.display(), e))?;
+    let components: Vec<Component> = absolute.components().collect();
+
+    let mut existing = components.len();
+    let mut prefix: PathBuf = components.iter().collect();
+    while existing > 0 && std::fs::symlink_metadata(&prefix).is_err() {
+        existing -= 1;
+        prefix = components[..existing].iter().collect();
+    }
+    let mut resolved = prefix.canonicalize().map_err(|_| {
+        // 存在するのに解決できないのはリンク先のないシンボリックリンク
+        format!("Access denied by path policy: {} is a dangling symlink", prefix.display())
+    })?;
+
+    for component in &components[existing..] {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(name) => resolved.push(name),
+            _ => {}
+        }
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parent_traversal_is_rejected() {
+        let project = tempdir().unwrap();
+        std::fs::create_dir(project.path().join("src")).unwrap();
+        let policy = PathPolicy::new(project.path());
+
+        assert!(policy.check_write(&project.path().join("src/new/file.rs")).is_ok());
+        assert!(policy.check_write(&project.path().join("src/../Cargo.toml")).is_ok());
+        for escape in ["src/../../escape.txt", "missing/../../escape.txt", "../escape.txt"] {
+            let error = policy.check_write(&project.path().join(escape)).unwrap_err();
+            assert!(error.contains("path policy"), "{}", error);
+        }
+        assert!(policy.check_read(Path::new("/etc/passwd")).is_err());
+        assert!(policy.clone().with_read_outside(true).check_read(Path::new("/etc/passwd")).is_ok());
+        assert!(policy.with_read_outside(true).check_write(Path::new("/etc/passwd")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_escapes_are_rejected() {
+        let project = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        std::fs::write(outside.path().join("secret"), "").unwrap();
+        std::os::unix::fs::symlink(outside.path(), project.path().join("link")).unwrap();
+        std::os::unix::fs::symlink(outside.path().join("gone"), project.path().join("dangling")).unwrap();
+        std::os::unix::fs::symlink(project.path().join("src.rs"), project.path().join("inside")).unwrap();
+        std::fs::write(project.path().join("src.rs"), "").unwrap();
+        let policy = PathPolicy::new(project.path());
+
+        assert!(policy.check_read(&project.path().join("link/secret")).is_err());
+        assert!(policy.check_write(&project.path().join("link/new.txt")).is_err());
+        // リンク先の親に解決される
+        assert!(policy.check_write(&project.path().join("link/../x")).is_err());
+        assert!(policy.check_write(&project.path().join("dangling")).unwrap_err().contains("dangling"));
+        assert!(policy.check_read(&project.path().join("inside")).is_ok());
+    }
+
+    #[test]
+    fn test_grant_lasts_until_the_guard_is_dropped() {
+        let project = tempdir().unwrap();
+        let infra = tempdir().unwrap();
+        let policy = Arc::new(PathPolicy::new(project.path()));
+        let file = infra.path().join("main.tf");
+
+        let guard = policy.grant(vec![PathGrant::new(infra.path(), false)]);
+        assert!(policy.check_read(&file).is_ok());
+        assert!(policy.check_write(&file).is_err());
+        {
+            let _write = policy.grant(vec![PathGrant::new(infra.path(), true)]);
+            assert!(policy.check_write(&file).is_ok());
+        }
+        // 内側のガードを外しても、外側の読み取りの許可は残る
+        assert!(policy.check_write(&file).is_err());
+        assert!(policy.check_read(&file).is_ok());
+        drop(guard);
+        assert!(policy.check_read(&file).is_err());
+    }
+
+    #[test]
+    fn test_extra_allowed_paths() {
+        let project = tempdir().unwrap();
+        let shared = tempdir().unwrap();
+        let policy = PathPolicy::new(project.path()).with_allowed_paths([shared.path()]);
+
+        assert!(policy.check_write(&shared.path().join("notes.md")).is_ok());
+        assert!(policy.check_read(&shared.path().join("sub/a.rs")).is_ok());
+        let sibling = shared.path().with_file_name(format!(
+            "{}-other",
+            shared.path().file_name().unwrap().to_string_lossy()
+        ));
+        assert!(policy.check_write(&sibling.join("x")).is_err());
+    }
+}
@@ -0,0 +1,120 @@
+//! ツール結果の構造化ペイロード
+//!
+//! `ToolResult.data` に入る JSON の形。会話には `output` のテキストだけを渡し、
+//! 構造化された形は `/use` のツール結果ストアなどプログラム側で使う。
+//!
+//! | ツール | `data` |
+//! |--------|--------|
+//! | `write` / `edit` | [`FileChange`] |
+//! | `apply_patch` | `{"files": [`[`PatchedFile`]`]}` |
+//! | `read` | [`FileRead`] |
+//! | `grep` | `{"matches": [`[`GrepMatch`]`], "truncated": bool}` |
+//! | `glob` | `{"paths": [string]}` |
+//! | `git_status` | [`GitStatus`] |
+//! | `lsp_*` | LSP の応答そのまま |
+//! | 外部ツール | 応答の `data`（任意） |
+//!
+//! その他のツールは `data` を持たない
+
+use serde::{Deserialize, Serialize};
+
+/// ファイルの書き込み結果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileChange {
+    pub path: String,
+    /// 書き込んだバイト数（ファイル全体）
+    pub bytes_written: usize,
+    /// 新規作成したか
+    pub created: bool,
+}
+
+/// パッチを適用したファイル
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PatchedFile {
+    pub path: String,
+    /// 書き込んだバイト数（削除時は 0）
+    pub bytes_written: usize,
+    pub created: bool,
+    pub deleted: bool,
+    pub added: usize,
+    pub removed: usize,
+}
+
+/// ファイルの読み込み範囲
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileRead {
+    pub path: String,
+    /// 最初の行番号（1始まり）
+    pub start_line: usize,
+    /// 返した行数
+    pub lines: usize,
+    /// ファイル全体の行数
+    pub total_lines: usize,
+}
+
+/// grep の一致行
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GrepMatch {
+    pub path: String,
+    /// 行番号（1始まり）
+    pub line: usize,
+    pub text: String,
+}
+
+/// git status の結果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitStatus {
+    pub entries: Vec<GitStatusEntry>,
+}
+
+/// git status --short の1行
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitStatusEntry {
+    pub path: String,
+    /// リネーム元（リネーム時のみ）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    /// インデックス側の状態（`M`、`A`、`?` など。変更なしは空白）
+    pub index: char,
+    /// 作業ツリー側の状態
+    pub worktree: char,
+}
+
+/// `git status --short` の状態文字（警告など他の行は無視する）
+const STATUS_CODES: &str = " MTADRCU?!";
+
+impl GitStatus {
+    /// `git status --short` の出力を解析
+    pub fn parse(output: &str) -> Self {
+        let entries = output
+            .lines()
+            .filter_map(|line| {
+                let mut chars = line.chars();
+                let index = chars.next().filter(|c| STATUS_CODES.contains(*c))?;
+                let worktree = chars.next().filter(|c| STATUS_CODES.contains(*c))?;
+                let rest = line.get(3..).filter(|rest| !rest.is_empty())?;
+                let (from, path) = match rest.split_once(" -> ") {
+                    Some((from, to)) => (Some(from.to_string()), to.to_string()),
+                    None => (None, rest.to_string()),
+                };
+                Some(GitStatusEntry { path, from, index, worktree })
+            })
+            .collect();
+        Self { entries }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_git_status_short() {
+        let status = GitStatus::parse(" M src/main.rs\nA  new.rs\nR  old.rs -> renamed.rs\n?? notes.txt\n");
+        assert_eq!(status.entries.len(), 4);
+        assert_eq!(status.entries[0], GitStatusEntry { path: "src/main.rs".into(), from: None, index: ' ', worktree: 'M' });
+        assert_eq!(status.entries[2].from.as_deref(), Some("old.rs"));
+        assert_eq!(status.entries[2].path, "renamed.rs");
+        assert_eq!((status.entries[3].index, status.entries[3].worktree), ('?', '?'));
+    }
+}
@@ -1,32 +1,92 @@
-use std::collections::HashMap;
+use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::future::Future;
 use std::sync::Arc;
+use std::time::Duration;
 
-use super::{Tool, ToolDefinition};
+use super::{Tool, ToolDefinition, ToolEffects, ToolResult};
 
 /// ツールレジストリ - ツールの登録と検索
+///
+/// 一覧は常に名前順（登録順に依存せず、システムプロンプトが実行ごとに同じになる）
 pub struct ToolRegistry {
-    tools: HashMap<String, Arc<dyn Tool>>,
+    tools: BTreeMap<String, Arc<dyn Tool>>,
+    /// 設定で無効化されたツール名（登録しない）
+    disabled: BTreeSet<String>,
+    /// ツール名ごとの実行タイムアウト
+    timeouts: HashMap<String, Duration>,
 }
 
 impl ToolRegistry {
     /// 新しいレジストリを作成
     pub fn new() -> Self {
         Self {
-            tools: HashMap::new(),
+            tools: BTreeMap::new(),
+            disabled: BTreeSet::new(),
+            timeouts: HashMap::new(),
         }
     }
 
-    /// ツールを登録
+    /// 無効化するツール名を設定（以降の register で無視される）
+    pub fn with_disabled<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.disabled = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// ツール名ごとの実行タイムアウト（秒）を設定
+    pub fn with_timeouts(mut self, timeouts: &HashMap<String, u64>) -> Self {
+        self.timeouts = timeouts
+            .iter()
+            .map(|(name, secs)| (name.clone(), Duration::from_secs(*secs)))
+            .collect();
+        self
+    }
+
+    /// ツールを登録（無効化されたツールは登録しない）
     pub fn register(&mut self, tool: Arc<dyn Tool>) {
+        if self.disabled.contains(tool.name()) {
+            tracing::debug!("Tool '{}' is disabled by configuration", tool.name());
+            return;
+        }
         self.tools.insert(tool.name().to_string(), tool);
     }
 
+    /// 設定で無効化されたツールか
+    pub fn is_disabled(&self, name: &str) -> bool {
+        self.disabled.contains(name)
+    }
+
+    /// ツールの実行タイムアウト（未設定なら None）
+    pub fn timeout(&self, name: &str) -> Option<Duration> {
+        self.timeouts.get(name).copied()
+    }
+
+    /// ツールの実行を設定されたタイムアウトと競争させる
+    ///
+    /// 時間切れなら実行を打ち切り、失敗結果を返す
+    pub async fn execute_with_timeout<F>(&self, name: &str, execution: F) -> Result<ToolResult>
+    where
+        F: Future<Output = Result<ToolResult>>,
+    {
+        let Some(limit) = self.timeout(name) else {
+            return execution.await;
+        };
+        match tokio::time::timeout(limit, execution).await {
+            Ok(result) => result,
+            Err(_) => Ok(ToolResult::failure(format!("timed out after {}s", limit.as_secs()))),
+        }
+    }
+
     /// 名前でツールを取得
     pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
         self.tools.get(name).cloned()
     }
 
-    /// ツール名一覧を取得
+    /// ツール名一覧を取得（名前順）
     pub fn names(&self) -> Vec<&str> {
         self.tools.keys().map(|s| s.as_str()).collect()
     }
@@ -36,6 +96,14 @@ impl ToolRegistry {
         self.tools.values().map(|t| t.definition()).collect()
     }
 
+    /// ツール名ごとの副作用
+    pub fn effects(&self) -> HashMap<String, ToolEffects> {
+        self.tools
+            .iter()
+            .map(|(name, tool)| (name.clone(), tool.effects()))
+            .collect()
+    }
+
     /// ツールが存在するかチェック
     pub fn contains(&self, name: &str) -> bool {
         self.tools.contains_key(name)
@@ -60,7 +128,7 @@ impl ToolRegistry {
             .collect()
     }
 
-    /// LLMに送信するためのツール定義JSON
+    /// LLMに送信するためのツール定義JSON（名前順）
     pub fn to_prompt_format(&self) -> String {
         let mut output = String::from("Available tools:\n\n");
 
@@ -91,4 +159,75 @@ mod tests {
         let registry = ToolRegistry::new();
         assert!(registry.is_empty());
     }
+
+    #[test]
+    fn test_prompt_format_does_not_depend_on_registration_order() {
+        use crate::tools::file::{EditTool, ReadTool, WriteTool};
+        use crate::tools::search::GlobTool;
+
+        let tools: Vec<Arc<dyn Tool>> = vec![
+            Arc::new(WriteTool::new()),
+            Arc::new(GlobTool::new()),
+            Arc::new(ReadTool::new()),
+            Arc::new(EditTool::new()),
+        ];
+        let mut forward = ToolRegistry::new();
+        let mut reverse = ToolRegistry::new();
+        for tool in &tools {
+            forward.register(Arc::clone(tool));
+        }
+        for tool in tools.iter().rev() {
+            reverse.register(Arc::clone(tool));
+        }
+
+        assert_eq!(forward.names(), vec!["edit", "glob", "read", "write"]);
+        assert_eq!(forward.to_prompt_format(), reverse.to_prompt_format());
+        assert_eq!(forward.to_prompt_format(), forward.to_prompt_format());
+    }
+
+    #[test]
+    fn test_disabled_tools_are_not_registered_or_prompted() {
+        use crate::tools::file::{ReadTool, WriteTool};
+
+        let mut registry = ToolRegistry::new().with_disabled(["write"]);
+        registry.register(Arc::new(ReadTool::new()));
+        registry.register(Arc::new(WriteTool::new()));
+
+        assert_eq!(registry.names(), vec!["read"]);
+        assert!(registry.get("write").is_none());
+        assert!(registry.is_disabled("write"));
+        assert!(!registry.is_disabled("read"));
+        let prompt = registry.to_prompt_format();
+        assert!(prompt.contains("## read"));
+        assert!(!prompt.contains("## write"));
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_timeout_fails_slow_tools() {
+        let timeouts = HashMap::from([("slow".to_string(), 1)]);
+        let registry = ToolRegistry::new().with_timeouts(&timeouts);
+
+        let slow = async {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            Ok(ToolResult::success("done"))
+        };
+        let result = tokio::time::timeout(
+            Duration::from_secs(10),
+            registry.execute_with_timeout("slow", slow),
+        )
+        .await
+        .expect("timeout should fire first")
+        .unwrap();
+        assert!(!result.success);
+        assert_eq!(result.error.as_deref(), Some("timed out after 1s"));
+
+        // タイムアウト未設定のツールはそのまま完了を待つ
+        let quick = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Ok(ToolResult::success("done"))
+        };
+        let result = registry.execute_with_timeout("quick", quick).await.unwrap();
+        assert!(result.success);
+        assert_eq!(result.output, "done");
+    }
 }
@@ -1,17 +1,49 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use glob::glob as glob_pattern;
+use glob::{MatchOptions, Pattern};
 use serde_json::{json, Value};
 use std::path::PathBuf;
+use std::sync::Arc;
 
-use crate::tools::{Tool, ToolResult};
+use super::walk::walk_files;
+use crate::tools::git::{find_nested_repos, is_in_nested_repo};
+use crate::tools::{PathPolicy, Tool, ToolEffects, ToolResult, Workspace};
 
 /// Globパターン検索ツール
-pub struct GlobTool;
+pub struct GlobTool {
+    /// ネストしたリポジトリ内のファイルも含めるか
+    include_nested_repos: bool,
+    /// 複数ルートのワークスペース（既定の検索先と出力のパス表記）
+    workspace: Option<Arc<Workspace>>,
+    /// 検索先の制限（プロジェクトルートの外を拒否する）
+    policy: Option<Arc<PathPolicy>>,
+}
 
 impl GlobTool {
     pub fn new() -> Self {
-        Self
+        Self {
+            include_nested_repos: false,
+            workspace: None,
+            policy: None,
+        }
+    }
+
+    /// ワークスペースのルートを検索先にする
+    pub fn with_workspace(mut self, workspace: Arc<Workspace>) -> Self {
+        self.workspace = Some(workspace);
+        self
+    }
+
+    /// 検索できるパスを制限する
+    pub fn with_path_policy(mut self, policy: Arc<PathPolicy>) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// ネストしたリポジトリ内のファイルも含めるか設定
+    pub fn with_include_nested_repos(mut self, include: bool) -> Self {
+        self.include_nested_repos = include;
+        self
     }
 }
 
@@ -32,7 +64,7 @@ impl Tool for GlobTool {
     }
 
     fn parameters_schema(&self) -> Value {
-        json!({
+        let mut schema = json!({
             "type": "object",
             "properties": {
                 "pattern": {
@@ -42,10 +74,25 @@ impl Tool for GlobTool {
                 "path": {
                     "type": "string",
                     "description": "Base directory to search in (defaults to current directory)"
+                },
+                "include_ignored": {
+                    "type": "boolean",
+                    "description": "Include files matched by .gitignore/.ignore (default: false)"
                 }
             },
             "required": ["pattern"]
-        })
+        });
+        if self.workspace.as_ref().is_some_and(|w| w.is_multi()) {
+            schema["properties"]["root"] = json!({
+                "type": "string",
+                "description": "Workspace root label to search, or \"all\" (defaults to the current root; ignored when path is given)"
+            });
+        }
+        schema
+    }
+
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::ReadOnly
     }
 
     async fn execute(&self, params: Value) -> Result<ToolResult> {
@@ -53,35 +100,174 @@ impl Tool for GlobTool {
             .and_then(|v| v.as_str())
             .ok_or_else(|| anyhow::anyhow!("Missing pattern parameter"))?;
 
-        let base_path = params.get("path")
-            .and_then(|v| v.as_str())
-            .map(PathBuf::from)
-            .unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
+        let bases = match self.bases(&params) {
+            Ok(bases) => bases,
+            Err(e) => return Ok(ToolResult::failure(e)),
+        };
+
+        let include_ignored = params.get("include_ignored")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
 
-        let full_pattern = base_path.join(pattern);
-        let pattern_str = full_pattern.to_string_lossy();
+        let pattern = match Pattern::new(pattern) {
+            Ok(p) => p,
+            Err(e) => return Ok(ToolResult::failure(format!("Invalid glob pattern: {}", e))),
+        };
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::default()
+        };
 
         let mut matches: Vec<String> = Vec::new();
+        let mut excluded = 0;
+        let mut ignored = 0;
+        for base_path in &bases {
+            let nested = if self.include_nested_repos {
+                Vec::new()
+            } else {
+                find_nested_repos(base_path)
+            };
+            let walked = walk_files(base_path, include_ignored);
+            ignored += walked.ignored;
 
-        match glob_pattern(&pattern_str) {
-            Ok(paths) => {
-                for entry in paths.flatten() {
-                    matches.push(entry.display().to_string());
+            for entry in walked.files {
+                let relative = entry.strip_prefix(base_path).unwrap_or(&entry);
+                if !pattern.matches_path_with(relative, options) {
+                    continue;
                 }
-            }
-            Err(e) => {
-                return Ok(ToolResult::failure(format!("Invalid glob pattern: {}", e)));
+                if is_in_nested_repo(&entry, &nested) {
+                    excluded += 1;
+                    continue;
+                }
+                matches.push(match &self.workspace {
+                    Some(workspace) => workspace.display(&entry),
+                    None => entry.display().to_string(),
+                });
             }
         }
 
+        let mut note = String::new();
+        if excluded > 0 {
+            note.push_str(&format!("\n({} files in nested repositories excluded)", excluded));
+        }
+        if ignored > 0 {
+            note.push_str(&format!(
+                "\n({} paths skipped by .gitignore/.ignore rules; set include_ignored to include them)",
+                ignored
+            ));
+        }
+
+        let data = json!({ "paths": &matches });
         if matches.is_empty() {
-            Ok(ToolResult::success("No files found matching the pattern"))
+            Ok(ToolResult::success(format!("No files found matching the pattern{}", note)).with_data(data))
         } else {
             Ok(ToolResult::success(format!(
-                "Found {} files:\n{}",
+                "Found {} files:\n{}{}",
                 matches.len(),
-                matches.join("\n")
-            )))
+                matches.join("\n"),
+                note
+            ))
+            .with_data(data))
+        }
+    }
+}
+
+impl GlobTool {
+    /// 検索の起点（path があればそこ、複数ルートなら root の指定、なければ作業ディレクトリ）
+    fn bases(&self, params: &Value) -> Result<Vec<PathBuf>, String> {
+        let paths = self.requested_bases(params)?;
+        self.check_policy(&paths)?;
+        Ok(paths)
+    }
+
+    /// 指定された検索先（制限は確認しない）
+    fn requested_bases(&self, params: &Value) -> Result<Vec<PathBuf>, String> {
+        let path = params.get("path").and_then(|v| v.as_str());
+        let root = params.get("root").and_then(|v| v.as_str());
+        match (&self.workspace, path) {
+            (Some(workspace), Some(path)) => Ok(vec![workspace.resolve(path)]),
+            (Some(workspace), None) if workspace.is_multi() => Ok(workspace
+                .search_roots(root)?
+                .into_iter()
+                .map(|r| r.path.clone())
+                .collect()),
+            (_, Some(path)) => Ok(vec![PathBuf::from(path)]),
+            (_, None) => Ok(vec![std::env::current_dir().unwrap_or_default()]),
         }
     }
+
+    /// 検索先が制限に反していればエラー
+    fn check_policy(&self, paths: &[PathBuf]) -> Result<(), String> {
+        match &self.policy {
+            Some(policy) => paths.iter().try_for_each(|path| policy.check_read(path).map(|_| ())),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_nested_repo_excluded_by_default() {
+        let dir = tempdir().unwrap();
+        let nested = dir.path().join("vendor").join("foo");
+        std::fs::create_dir_all(nested.join(".git")).unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/main.rs"), "").unwrap();
+        std::fs::write(nested.join("lib.rs"), "").unwrap();
+
+        let params = json!({"pattern": "**/*.rs", "path": dir.path().to_str().unwrap()});
+        let result = GlobTool::new().execute(params.clone()).await.unwrap();
+        assert!(result.output.contains("main.rs"));
+        assert!(!result.output.contains("lib.rs"));
+        assert!(result.output.contains("1 files in nested repositories excluded"));
+
+        let result = GlobTool::new()
+            .with_include_nested_repos(true)
+            .execute(params)
+            .await
+            .unwrap();
+        assert!(result.output.contains("lib.rs"));
+    }
+
+    #[tokio::test]
+    async fn test_respects_gitignore() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "node_modules/\n").unwrap();
+        std::fs::create_dir_all(dir.path().join("node_modules/pkg")).unwrap();
+        std::fs::write(dir.path().join("node_modules/pkg/index.js"), "").unwrap();
+        std::fs::write(dir.path().join("app.js"), "").unwrap();
+
+        let params = json!({"pattern": "**/*.js", "path": dir.path().to_str().unwrap()});
+        let result = GlobTool::new().execute(params).await.unwrap();
+        assert!(result.output.contains("Found 1 files"));
+        assert!(result.output.contains("app.js"));
+        assert!(!result.output.contains("index.js"));
+        assert!(result.output.contains("1 paths skipped by .gitignore/.ignore rules"));
+
+        let params = json!({
+            "pattern": "**/*.js",
+            "path": dir.path().to_str().unwrap(),
+            "include_ignored": true
+        });
+        let result = GlobTool::new().execute(params).await.unwrap();
+        assert!(result.output.contains("index.js"));
+    }
+
+    #[tokio::test]
+    async fn test_pattern_is_relative_to_base() {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("top.rs"), "").unwrap();
+        std::fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+
+        // "*" はディレクトリ区切りを跨がない
+        let params = json!({"pattern": "*.rs", "path": dir.path().to_str().unwrap()});
+        let result = GlobTool::new().execute(params).await.unwrap();
+        assert!(result.output.contains("top.rs"));
+        assert!(!result.output.contains("lib.rs"));
+    }
 }
@@ -1,19 +1,149 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use regex::Regex;
+use regex::{Regex, RegexBuilder};
 use serde_json::{json, Value};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::fs;
-use glob::glob as glob_pattern;
+use glob::{MatchOptions, Pattern};
 
-use crate::tools::{Tool, ToolResult};
+use super::walk::walk_files;
+use crate::tools::git::{find_nested_repos, is_in_nested_repo};
+use crate::tools::payload::GrepMatch;
+use crate::tools::{PathPolicy, Tool, ToolEffects, ToolResult, Workspace};
+
+/// 検索対象とするファイルサイズのデフォルト上限（バイト）
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// バイナリ判定で調べる先頭バイト数
+const BINARY_CHECK_BYTES: usize = 8000;
+
+/// 最大マッチ数のデフォルト
+const DEFAULT_MAX_MATCHES: usize = 200;
 
 /// 内容検索ツール
-pub struct GrepTool;
+pub struct GrepTool {
+    /// ネストしたリポジトリ内のファイルも検索するか
+    include_nested_repos: bool,
+    /// 検索対象とするファイルサイズの上限（バイト）
+    max_file_size: u64,
+    /// 複数ルートのワークスペース（既定の検索先と出力のパス表記）
+    workspace: Option<Arc<Workspace>>,
+    /// 検索先の制限（プロジェクトルートの外を拒否する）
+    policy: Option<Arc<PathPolicy>>,
+}
+
+/// 検索結果の蓄積
+struct Matches {
+    /// 出力行（マッチは "path:line:"、前後の行は "path-line-"）
+    lines: Vec<String>,
+    /// マッチした行（構造化された結果用）
+    matches: Vec<GrepMatch>,
+    max_matches: usize,
+    before: usize,
+    after: usize,
+    /// 上限を超えるマッチがあったか
+    truncated: bool,
+}
+
+impl Matches {
+    fn new(max_matches: usize, before: usize, after: usize) -> Self {
+        Self {
+            lines: Vec::new(),
+            matches: Vec::new(),
+            max_matches,
+            before,
+            after,
+            truncated: false,
+        }
+    }
+
+    /// 1ファイル分のマッチを追加（前後の行の範囲が重なる場合はまとめる）
+    fn add_file(&mut self, path: &str, content: &str, regex: &Regex) {
+        let lines: Vec<&str> = content.lines().collect();
+        let mut hits = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            if regex.is_match(line) {
+                if self.matches.len() + hits.len() >= self.max_matches {
+                    self.truncated = true;
+                    break;
+                }
+                hits.push(i);
+            }
+        }
+        if hits.is_empty() {
+            return;
+        }
+        self.matches.extend(hits.iter().map(|&i| GrepMatch {
+            path: path.to_string(),
+            line: i + 1,
+            text: lines[i].to_string(),
+        }));
+
+        let with_context = self.before > 0 || self.after > 0;
+        let mut printed_until: Option<usize> = None;
+        for &hit in &hits {
+            let start = hit.saturating_sub(self.before);
+            let end = (hit + self.after).min(lines.len() - 1);
+            let start = match printed_until {
+                Some(last) if start <= last + 1 => last + 1,
+                _ => {
+                    // 連続しない範囲の間（ファイル間を含む）に区切りを入れる
+                    if with_context && !self.lines.is_empty() {
+                        self.lines.push("--".to_string());
+                    }
+                    start
+                }
+            };
+            for (i, line) in lines.iter().enumerate().take(end + 1).skip(start) {
+                let sep = if hits.binary_search(&i).is_ok() { ':' } else { '-' };
+                self.lines.push(format!("{}{}{}{}{}", path, sep, i + 1, sep, line));
+            }
+            printed_until = Some(end.max(printed_until.unwrap_or(0)));
+        }
+    }
+}
+
+/// 検索しなかったファイルの件数
+#[derive(Debug, Default)]
+struct Skipped {
+    ignored: usize,
+    binary: usize,
+    too_large: usize,
+}
 
 impl GrepTool {
     pub fn new() -> Self {
-        Self
+        Self {
+            include_nested_repos: false,
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            workspace: None,
+            policy: None,
+        }
+    }
+
+    /// ワークスペースのルートを検索先にする
+    pub fn with_workspace(mut self, workspace: Arc<Workspace>) -> Self {
+        self.workspace = Some(workspace);
+        self
+    }
+
+    /// 検索できるパスを制限する
+    pub fn with_path_policy(mut self, policy: Arc<PathPolicy>) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// 検索対象とするファイルサイズの上限を設定
+    pub fn with_max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = bytes;
+        self
+    }
+
+    /// ネストしたリポジトリ内のファイルも検索するか設定
+    pub fn with_include_nested_repos(mut self, include: bool) -> Self {
+        self.include_nested_repos = include;
+        self
     }
 }
 
@@ -34,7 +164,7 @@ impl Tool for GrepTool {
     }
 
     fn parameters_schema(&self) -> Value {
-        json!({
+        let mut schema = json!({
             "type": "object",
             "properties": {
                 "pattern": {
@@ -48,10 +178,45 @@ impl Tool for GrepTool {
                 "glob": {
                     "type": "string",
                     "description": "Glob pattern to filter files (e.g., '*.rs')"
+                },
+                "include_ignored": {
+                    "type": "boolean",
+                    "description": "Also search files matched by .gitignore/.ignore (default: false)"
+                },
+                "ignore_case": {
+                    "type": "boolean",
+                    "description": "Case-insensitive search (default: false)"
+                },
+                "context": {
+                    "type": "integer",
+                    "description": "Lines of context to show before and after each match"
+                },
+                "context_before": {
+                    "type": "integer",
+                    "description": "Lines of context before each match (overrides context)"
+                },
+                "context_after": {
+                    "type": "integer",
+                    "description": "Lines of context after each match (overrides context)"
+                },
+                "max_matches": {
+                    "type": "integer",
+                    "description": "Maximum number of matches to return (default: 200)"
                 }
             },
             "required": ["pattern"]
-        })
+        });
+        if self.workspace.as_ref().is_some_and(|w| w.is_multi()) {
+            schema["properties"]["root"] = json!({
+                "type": "string",
+                "description": "Workspace root label to search, or \"all\" (defaults to the current root; ignored when path is given)"
+            });
+        }
+        schema
+    }
+
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::ReadOnly
     }
 
     async fn execute(&self, params: Value) -> Result<ToolResult> {
@@ -59,69 +224,352 @@ impl Tool for GrepTool {
             .and_then(|v| v.as_str())
             .ok_or_else(|| anyhow::anyhow!("Missing pattern parameter"))?;
 
-        let search_path = params.get("path")
-            .and_then(|v| v.as_str())
-            .unwrap_or(".");
-
         let file_glob = params.get("glob")
             .and_then(|v| v.as_str());
 
-        let regex = match Regex::new(pattern) {
+        let ignore_case = params.get("ignore_case")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let regex = match RegexBuilder::new(pattern).case_insensitive(ignore_case).build() {
             Ok(r) => r,
             Err(e) => return Ok(ToolResult::failure(format!("Invalid regex: {}", e))),
         };
 
-        let mut results: Vec<String> = Vec::new();
-        let path = Path::new(search_path);
+        let count_param = |name: &str| {
+            params.get(name)
+                .and_then(|v| v.as_u64())
+                .map(|n| n as usize)
+        };
+        let context = count_param("context").unwrap_or(0);
+        let before = count_param("context_before").unwrap_or(context);
+        let after = count_param("context_after").unwrap_or(context);
+        let max_matches = count_param("max_matches").unwrap_or(DEFAULT_MAX_MATCHES).max(1);
 
-        if path.is_file() {
-            // 単一ファイル検索
-            if let Ok(content) = fs::read_to_string(path).await {
-                for (i, line) in content.lines().enumerate() {
-                    if regex.is_match(line) {
-                        results.push(format!("{}:{}:{}", path.display(), i + 1, line));
-                    }
-                }
-            }
-        } else if path.is_dir() {
-            // ディレクトリ検索
-            let glob_pattern_str = if let Some(g) = file_glob {
-                format!("{}/{}", search_path, g)
-            } else {
-                format!("{}/**/*", search_path)
-            };
+        let include_ignored = params.get("include_ignored")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
 
-            if let Ok(entries) = glob_pattern(&glob_pattern_str) {
-                for entry in entries.flatten() {
-                    if entry.is_file() {
-                        if let Ok(content) = fs::read_to_string(&entry).await {
-                            for (i, line) in content.lines().enumerate() {
-                                if regex.is_match(line) {
-                                    results.push(format!("{}:{}:{}", entry.display(), i + 1, line));
-                                    if results.len() >= 100 {
-                                        break;
-                                    }
-                                }
-                            }
+        let file_filter = match file_glob.map(Pattern::new).transpose() {
+            Ok(f) => f,
+            Err(e) => return Ok(ToolResult::failure(format!("Invalid glob pattern: {}", e))),
+        };
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::default()
+        };
+
+        let targets = match self.targets(&params) {
+            Ok(targets) => targets,
+            Err(e) => return Ok(ToolResult::failure(e)),
+        };
+
+        let mut results = Matches::new(max_matches, before, after);
+        let mut skipped = Skipped::default();
+
+        for path in &targets {
+            let path = path.as_path();
+            if path.is_file() {
+                // 単一ファイル検索
+                self.search_file(path, &regex, &mut results, &mut skipped).await;
+            } else if path.is_dir() {
+                // ディレクトリ検索
+                let nested = if self.include_nested_repos {
+                    Vec::new()
+                } else {
+                    find_nested_repos(path)
+                };
+
+                let walked = walk_files(path, include_ignored);
+                skipped.ignored += walked.ignored;
+
+                for entry in walked.files {
+                    if let Some(filter) = &file_filter {
+                        let relative = entry.strip_prefix(path).unwrap_or(&entry);
+                        if !filter.matches_path_with(relative, options) {
+                            continue;
                         }
                     }
-                    if results.len() >= 100 {
+                    if is_in_nested_repo(&entry, &nested) {
+                        continue;
+                    }
+                    self.search_file(&entry, &regex, &mut results, &mut skipped).await;
+                    if results.truncated {
                         break;
                     }
                 }
             }
+            if results.truncated {
+                break;
+            }
         }
 
-        if results.is_empty() {
-            Ok(ToolResult::success("No matches found"))
+        let note = skipped.note(self.max_file_size);
+        let data = json!({ "matches": &results.matches, "truncated": results.truncated });
+        if results.matches.is_empty() {
+            Ok(ToolResult::success(format!("No matches found{}", note)).with_data(data))
         } else {
-            let truncated = if results.len() >= 100 { " (truncated)" } else { "" };
+            let truncated = if results.truncated {
+                format!(
+                    "\n(results truncated at {} matches; narrow the search or raise max_matches)",
+                    results.max_matches
+                )
+            } else {
+                String::new()
+            };
             Ok(ToolResult::success(format!(
-                "Found {} matches{}:\n{}",
-                results.len(),
+                "Found {} matches:\n{}{}{}",
+                results.matches.len(),
+                results.lines.join("\n"),
                 truncated,
-                results.join("\n")
-            )))
+                note
+            ))
+            .with_data(data))
+        }
+    }
+}
+
+impl GrepTool {
+    /// 検索先（path があればそこ、複数ルートなら root の指定、なければ作業ディレクトリ）
+    fn targets(&self, params: &Value) -> Result<Vec<PathBuf>, String> {
+        let paths = self.requested_targets(params)?;
+        self.check_policy(&paths)?;
+        Ok(paths)
+    }
+
+    /// 指定された検索先（制限は確認しない）
+    fn requested_targets(&self, params: &Value) -> Result<Vec<PathBuf>, String> {
+        let path = params.get("path").and_then(|v| v.as_str());
+        let root = params.get("root").and_then(|v| v.as_str());
+        match (&self.workspace, path) {
+            (Some(workspace), Some(path)) => Ok(vec![workspace.resolve(path)]),
+            (Some(workspace), None) if workspace.is_multi() => Ok(workspace
+                .search_roots(root)?
+                .into_iter()
+                .map(|r| r.path.clone())
+                .collect()),
+            (_, path) => Ok(vec![PathBuf::from(path.unwrap_or("."))]),
+        }
+    }
+
+    /// 検索先が制限に反していればエラー
+    fn check_policy(&self, paths: &[PathBuf]) -> Result<(), String> {
+        match &self.policy {
+            Some(policy) => paths.iter().try_for_each(|path| policy.check_read(path).map(|_| ())),
+            None => Ok(()),
+        }
+    }
+
+    /// 1ファイルを検索（大きすぎるファイルとバイナリはスキップ）
+    async fn search_file(
+        &self,
+        path: &Path,
+        regex: &Regex,
+        results: &mut Matches,
+        skipped: &mut Skipped,
+    ) {
+        let Ok(metadata) = fs::metadata(path).await else {
+            return;
+        };
+        if metadata.len() > self.max_file_size {
+            skipped.too_large += 1;
+            return;
+        }
+        let Ok(bytes) = fs::read(path).await else {
+            return;
+        };
+        if is_binary(&bytes) {
+            skipped.binary += 1;
+            return;
+        }
+        let Ok(content) = String::from_utf8(bytes) else {
+            skipped.binary += 1;
+            return;
+        };
+
+        let shown = match &self.workspace {
+            Some(workspace) => workspace.display(path),
+            None => path.display().to_string(),
+        };
+        results.add_file(&shown, &content, regex);
+    }
+}
+
+impl Skipped {
+    /// 結果に添える注記（スキップがなければ空）
+    fn note(&self, max_file_size: u64) -> String {
+        let mut parts = Vec::new();
+        if self.ignored > 0 {
+            parts.push(format!(
+                "{} paths by .gitignore/.ignore rules (set include_ignored to search them)",
+                self.ignored
+            ));
+        }
+        if self.binary > 0 {
+            parts.push(format!("{} binary files", self.binary));
+        }
+        if self.too_large > 0 {
+            parts.push(format!("{} files larger than {} bytes", self.too_large, max_file_size));
+        }
+        if parts.is_empty() {
+            String::new()
+        } else {
+            format!("\n(skipped {})", parts.join(", "))
         }
     }
 }
+
+/// 先頭にNULバイトを含むならバイナリとみなす
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes.iter().take(BINARY_CHECK_BYTES).any(|&b| b == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_nested_repo_excluded_by_default() {
+        let dir = tempdir().unwrap();
+        let nested = dir.path().join("vendor").join("foo");
+        std::fs::create_dir_all(nested.join(".git")).unwrap();
+        std::fs::write(dir.path().join("main.rs"), "needle here").unwrap();
+        std::fs::write(nested.join("lib.rs"), "needle there").unwrap();
+
+        let params = json!({"pattern": "needle", "path": dir.path().to_str().unwrap()});
+        let result = GrepTool::new().execute(params.clone()).await.unwrap();
+        assert!(result.output.contains("needle here"));
+        assert!(!result.output.contains("needle there"));
+
+        let result = GrepTool::new()
+            .with_include_nested_repos(true)
+            .execute(params)
+            .await
+            .unwrap();
+        assert!(result.output.contains("needle there"));
+    }
+
+    #[tokio::test]
+    async fn test_skips_ignored_large_and_binary_files() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        std::fs::write(root.join("target/out.rs"), "needle ignored").unwrap();
+        std::fs::write(root.join("src.rs"), "needle src").unwrap();
+        std::fs::write(root.join("big.txt"), format!("needle big\n{}", "x".repeat(2048))).unwrap();
+        std::fs::write(root.join("blob.bin"), b"needle\0\x01\x02").unwrap();
+
+        let params = json!({"pattern": "needle", "path": root.to_str().unwrap()});
+        let result = GrepTool::new()
+            .with_max_file_size(1024)
+            .execute(params)
+            .await
+            .unwrap();
+        assert!(result.output.contains("Found 1 matches"));
+        assert!(result.output.contains("needle src"));
+        assert!(result.output.contains("1 paths by .gitignore/.ignore rules"));
+        assert!(result.output.contains("1 binary files"));
+        assert!(result.output.contains("1 files larger than 1024 bytes"));
+
+        let params = json!({"pattern": "needle", "path": root.to_str().unwrap(), "include_ignored": true});
+        let result = GrepTool::new().execute(params).await.unwrap();
+        assert!(result.output.contains("needle ignored"));
+        assert!(result.output.contains("needle big"));
+        assert!(!result.output.contains("skipped 1 paths"));
+    }
+
+    #[tokio::test]
+    async fn test_no_matches_explains_skipped() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join(".ignore"), "vendor/\n").unwrap();
+        std::fs::create_dir_all(dir.path().join("vendor")).unwrap();
+        std::fs::write(dir.path().join("vendor/lib.rs"), "needle").unwrap();
+
+        let params = json!({"pattern": "needle", "path": dir.path().to_str().unwrap()});
+        let result = GrepTool::new().execute(params).await.unwrap();
+        assert!(result.output.starts_with("No matches found"));
+        assert!(result.output.contains("skipped 1 paths by .gitignore/.ignore rules"));
+    }
+
+    #[tokio::test]
+    async fn test_overlapping_context_is_merged() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        let content = (1..=12).map(|n| format!("line{}", n)).collect::<Vec<_>>().join("\n");
+        std::fs::write(&file, content.replace("line3", "hit3").replace("line5", "hit5").replace("line11", "hit11")).unwrap();
+
+        let params = json!({"pattern": "hit", "path": file.to_str().unwrap(), "context": 1});
+        let result = GrepTool::new().execute(params).await.unwrap();
+        let path = file.display().to_string();
+        let expected = [
+            format!("{}-2-line2", path),
+            format!("{}:3:hit3", path),
+            format!("{}-4-line4", path),
+            format!("{}:5:hit5", path),
+            format!("{}-6-line6", path),
+            "--".to_string(),
+            format!("{}-10-line10", path),
+            format!("{}:11:hit11", path),
+            format!("{}-12-line12", path),
+        ]
+        .join("\n");
+        assert_eq!(result.output, format!("Found 3 matches:\n{}", expected));
+
+        // 構造化された結果はマッチ行だけを持ち、テキストの "path:line:text" と一致する
+        let matches: Vec<GrepMatch> = serde_json::from_value(result.data.unwrap()["matches"].clone()).unwrap();
+        assert_eq!(matches.len(), 3);
+        for m in &matches {
+            assert!(result.output.contains(&format!("{}:{}:{}", m.path, m.line, m.text)));
+        }
+        assert_eq!(matches[2], GrepMatch { path: path.clone(), line: 11, text: "hit11".into() });
+
+        // context_before/context_after は個別に指定できる
+        let params = json!({"pattern": "hit11", "path": file.to_str().unwrap(), "context_before": 2, "context_after": 0});
+        let result = GrepTool::new().execute(params).await.unwrap();
+        assert!(result.output.contains("-9-line9"));
+        assert!(!result.output.contains("-12-line12"));
+    }
+
+    #[tokio::test]
+    async fn test_ignore_case_and_truncation_marker() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        std::fs::write(&file, "Needle\nneedle\nNEEDLE\nhay").unwrap();
+
+        let params = json!({"pattern": "needle", "path": file.to_str().unwrap()});
+        let result = GrepTool::new().execute(params).await.unwrap();
+        assert!(result.output.starts_with("Found 1 matches"));
+
+        let params = json!({"pattern": "needle", "path": file.to_str().unwrap(), "ignore_case": true, "max_matches": 2});
+        let result = GrepTool::new().execute(params).await.unwrap();
+        assert!(result.output.starts_with("Found 2 matches"));
+        assert!(result.output.contains(":1:Needle"));
+        assert!(!result.output.contains("NEEDLE"));
+        assert!(result.output.contains("(results truncated at 2 matches"));
+        assert_eq!(result.data.unwrap()["truncated"], json!(true));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_regex_is_tool_failure() {
+        let params = json!({"pattern": "(unclosed", "path": "."});
+        let result = GrepTool::new().execute(params).await.unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().starts_with("Invalid regex:"));
+    }
+
+    #[tokio::test]
+    async fn test_path_policy_refuses_search_outside_project() {
+        let project = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        std::fs::write(outside.path().join("a.txt"), "needle").unwrap();
+        let tool = GrepTool::new().with_path_policy(Arc::new(PathPolicy::new(project.path())));
+
+        let result = tool
+            .execute(json!({"pattern": "needle", "path": outside.path().to_str().unwrap()}))
+            .await
+            .unwrap();
+        assert!(result.error.unwrap().contains("Access denied by path policy"));
+    }
+}
@@ -1,5 +1,9 @@
 pub mod glob;
 pub mod grep;
+pub mod tree;
+pub mod walk;
 
 pub use glob::GlobTool;
 pub use grep::GrepTool;
+pub use tree::TreeTool;
+pub use walk::{walk_files, WalkResult};
@@ -0,0 +1,292 @@
+//! ディレクトリ構成をツリーで表示する
+//!
+//! glob を何度も呼ばずにプロジェクトの全体像をつかむためのツール。
+//! ignore ルールを尊重し、深さと表示件数に上限を設ける
+
+use anyhow::Result;
+use async_trait::async_trait;
+use ignore::gitignore::Gitignore;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::walk::{is_ignored, load_matcher, parent_matchers, walk_files};
+use crate::tools::workspace::resolve_tool_path;
+use crate::tools::{PathPolicy, Tool, ToolEffects, ToolResult, Workspace};
+
+/// 既定の深さ
+const DEFAULT_MAX_DEPTH: usize = 3;
+
+/// 既定の表示件数の上限
+const DEFAULT_MAX_ENTRIES: usize = 500;
+
+/// ツリーの組み立て中の状態
+struct TreeBuilder {
+    lines: Vec<String>,
+    max_depth: usize,
+    /// 残りの表示件数
+    remaining: usize,
+    /// 上限で省略したエントリ数
+    omitted: usize,
+    /// ignore ルールでスキップしたパス数
+    ignored: usize,
+    matchers: Vec<Gitignore>,
+}
+
+impl TreeBuilder {
+    /// `dir` の中身を `depth`（1始まり）の字下げで追加する
+    fn add_dir(&mut self, dir: &Path, abs_dir: &Path, depth: usize) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        let pushed = match load_matcher(abs_dir) {
+            Some(matcher) => {
+                self.matchers.push(matcher);
+                true
+            }
+            None => false,
+        };
+
+        let mut children: Vec<(String, bool)> = Vec::new();
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name == ".git" {
+                continue;
+            }
+            // シンボリックリンクのディレクトリは辿らない（ループ回避）
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            if is_ignored(&self.matchers, &abs_dir.join(&name), is_dir) {
+                self.ignored += 1;
+                continue;
+            }
+            children.push((name, is_dir));
+        }
+        // ディレクトリを先に、それぞれ名前順
+        children.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let indent = "  ".repeat(depth);
+        for (i, (name, is_dir)) in children.iter().enumerate() {
+            if self.remaining == 0 {
+                let rest = children.len() - i;
+                self.omitted += rest;
+                self.lines.push(format!("{}… {} more", indent, rest));
+                break;
+            }
+            self.remaining -= 1;
+            if !is_dir {
+                self.lines.push(format!("{}{}", indent, name));
+                continue;
+            }
+            let path = dir.join(name);
+            if depth >= self.max_depth {
+                let files = walk_files(&path, false).files.len();
+                self.lines.push(format!("{}{}/ ({} {})", indent, name, files, if files == 1 { "file" } else { "files" }));
+            } else {
+                self.lines.push(format!("{}{}/", indent, name));
+                self.add_dir(&path, &abs_dir.join(name), depth + 1);
+            }
+        }
+
+        if pushed {
+            self.matchers.pop();
+        }
+    }
+}
+
+/// ディレクトリツリー表示ツール
+pub struct TreeTool {
+    /// 複数ルートのワークスペース（既定の表示先とパスの解決）
+    workspace: Option<Arc<Workspace>>,
+    /// 表示できるパスの制限（プロジェクトルートの外を拒否する）
+    policy: Option<Arc<PathPolicy>>,
+}
+
+impl TreeTool {
+    pub fn new() -> Self {
+        Self { workspace: None, policy: None }
+    }
+
+    /// ワークスペースの現在のルートを既定の表示先にする
+    pub fn with_workspace(mut self, workspace: Arc<Workspace>) -> Self {
+        self.workspace = Some(workspace);
+        self
+    }
+
+    /// 表示できるパスを制限する
+    pub fn with_path_policy(mut self, policy: Arc<PathPolicy>) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// 表示するディレクトリと見出し
+    fn base(&self, params: &Value) -> (PathBuf, String) {
+        match (params.get("path").and_then(|v| v.as_str()), &self.workspace) {
+            (Some(path), workspace) => resolve_tool_path(workspace.as_deref(), path),
+            (None, Some(workspace)) => {
+                let path = workspace.current().path.clone();
+                let shown = workspace.display(&path);
+                (path, shown)
+            }
+            (None, None) => (std::env::current_dir().unwrap_or_default(), ".".to_string()),
+        }
+    }
+}
+
+impl Default for TreeTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Tool for TreeTool {
+    fn name(&self) -> &str {
+        "tree"
+    }
+
+    fn description(&self) -> &str {
+        "Show the directory tree (directories first, ending in /) for a project overview. Prefer this over many glob calls when exploring an unfamiliar layout"
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Directory to show (defaults to the current directory)"
+                },
+                "max_depth": {
+                    "type": "integer",
+                    "description": format!("Levels to expand; deeper directories show only their file count (default: {})", DEFAULT_MAX_DEPTH)
+                },
+                "max_entries": {
+                    "type": "integer",
+                    "description": format!("Maximum entries to list (default: {})", DEFAULT_MAX_ENTRIES)
+                }
+            }
+        })
+    }
+
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::ReadOnly
+    }
+
+    async fn execute(&self, params: Value) -> Result<ToolResult> {
+        let max_depth = params.get("max_depth")
+            .and_then(|v| v.as_u64())
+            .map(|n| n as usize)
+            .unwrap_or(DEFAULT_MAX_DEPTH)
+            .max(1);
+        let max_entries = params.get("max_entries")
+            .and_then(|v| v.as_u64())
+            .map(|n| n as usize)
+            .unwrap_or(DEFAULT_MAX_ENTRIES)
+            .max(1);
+
+        let (base, shown) = self.base(&params);
+        if let Some(Err(e)) = self.policy.as_ref().map(|p| p.check_read(&base)) {
+            return Ok(ToolResult::failure(e));
+        }
+        if !base.is_dir() {
+            return Ok(ToolResult::failure(format!("Not a directory: {}", shown)));
+        }
+        let abs_base = base.canonicalize().unwrap_or_else(|_| base.clone());
+
+        let mut tree = TreeBuilder {
+            lines: vec![format!("{}/", shown.trim_end_matches('/'))],
+            max_depth,
+            remaining: max_entries,
+            omitted: 0,
+            ignored: 0,
+            matchers: parent_matchers(&abs_base),
+        };
+        tree.add_dir(&base, &abs_base, 1);
+
+        let mut output = tree.lines.join("\n");
+        if tree.omitted > 0 {
+            output.push_str(&format!(
+                "\n\n(entry limit of {} reached; {} entries not shown. Narrow path or raise max_entries)",
+                max_entries, tree.omitted
+            ));
+        }
+        if tree.ignored > 0 {
+            output.push_str(&format!("\n\n({} paths skipped by .gitignore/.ignore rules)", tree.ignored));
+        }
+        Ok(ToolResult::success(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    /// src/{main.rs, agent/{core.rs, deep/{a.rs, b.rs}}}, README.md, target/ (ignored)
+    fn project() -> tempfile::TempDir {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::create_dir_all(root.join("src/agent/deep")).unwrap();
+        std::fs::create_dir_all(root.join("target/debug")).unwrap();
+        std::fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        for file in ["README.md", "src/main.rs", "src/agent/core.rs", "src/agent/deep/a.rs", "src/agent/deep/b.rs", "target/debug/app"] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+        dir
+    }
+
+    async fn tree(root: &Path, params: Value) -> String {
+        let mut params = params;
+        params["path"] = json!(root.to_str().unwrap());
+        let result = TreeTool::new().execute(params).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        result.output.replace(root.to_str().unwrap(), "ROOT")
+    }
+
+    #[tokio::test]
+    async fn test_depth_cutoff_shows_file_counts() {
+        let dir = project();
+        let output = tree(dir.path(), json!({"max_depth": 2})).await;
+        assert_eq!(
+            output,
+            "ROOT/\n  src/\n    agent/ (3 files)\n    main.rs\n  .gitignore\n  README.md\n\n(1 paths skipped by .gitignore/.ignore rules)"
+        );
+
+        let output = tree(dir.path(), json!({})).await;
+        assert!(output.contains("\n      deep/ (2 files)\n      core.rs\n"), "{}", output);
+    }
+
+    #[tokio::test]
+    async fn test_respects_ignore_rules() {
+        let dir = project();
+        std::fs::write(dir.path().join("src/.ignore"), "main.rs\n").unwrap();
+        let output = tree(dir.path(), json!({"max_depth": 5})).await;
+        assert!(!output.contains("target"), "{}", output);
+        assert!(!output.contains("main.rs"), "{}", output);
+        assert!(output.contains("        a.rs\n"), "{}", output);
+        assert!(output.ends_with("(2 paths skipped by .gitignore/.ignore rules)"), "{}", output);
+    }
+
+    #[tokio::test]
+    async fn test_entry_cap_annotates_each_directory() {
+        let dir = project();
+        let output = tree(dir.path(), json!({"max_entries": 2})).await;
+        assert_eq!(
+            output,
+            "ROOT/\n  src/\n    agent/\n      … 2 more\n    … 1 more\n  … 2 more\n\n\
+             (entry limit of 2 reached; 5 entries not shown. Narrow path or raise max_entries)\n\n\
+             (1 paths skipped by .gitignore/.ignore rules)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejects_missing_directory() {
+        let dir = tempdir().unwrap();
+        let result = TreeTool::new()
+            .execute(json!({"path": dir.path().join("missing").to_str().unwrap()}))
+            .await
+            .unwrap();
+        assert!(!result.success);
+    }
+}
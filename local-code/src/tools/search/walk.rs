@@ -0,0 +1,205 @@
+//! ignoreルールを考慮したファイル走査
+//!
+//! .gitignore / .ignore に一致するパスには降りずにスキップし、件数を数える。
+//! 検索ディレクトリより上のルールはリポジトリのルートまで遡って読み込む
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::{Path, PathBuf};
+
+/// 読み込むignoreファイル（後のものが優先）
+const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore"];
+
+/// 走査する最大深さ
+const MAX_DEPTH: usize = 64;
+
+/// 走査結果
+#[derive(Debug, Default)]
+pub struct WalkResult {
+    /// 見つかったファイル（ディレクトリごとに名前順）
+    pub files: Vec<PathBuf>,
+    /// ignoreルールでスキップしたパス数（ディレクトリは1件として数える）
+    pub ignored: usize,
+}
+
+/// base 配下のファイルを列挙
+///
+/// `include_ignored` が true の場合はignoreルールを適用しない（.git は常に除外）
+pub fn walk_files(base: &Path, include_ignored: bool) -> WalkResult {
+    let abs_base = base.canonicalize().unwrap_or_else(|_| base.to_path_buf());
+    let mut matchers = if include_ignored {
+        Vec::new()
+    } else {
+        parent_matchers(&abs_base)
+    };
+
+    let mut result = WalkResult::default();
+    walk_dir(base, &abs_base, include_ignored, &mut matchers, 0, &mut result);
+    result
+}
+
+fn walk_dir(
+    dir: &Path,
+    abs_dir: &Path,
+    include_ignored: bool,
+    matchers: &mut Vec<Gitignore>,
+    depth: usize,
+    result: &mut WalkResult,
+) {
+    if depth >= MAX_DEPTH {
+        return;
+    }
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    let pushed = if include_ignored {
+        false
+    } else if let Some(matcher) = load_matcher(abs_dir) {
+        matchers.push(matcher);
+        true
+    } else {
+        false
+    };
+
+    let mut entries: Vec<_> = entries.flatten().collect();
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let name = entry.file_name();
+        if name == ".git" {
+            continue;
+        }
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let path = dir.join(&name);
+        let abs = abs_dir.join(&name);
+        // シンボリックリンクはファイルのみ辿る（ループ回避）
+        let is_dir = file_type.is_dir();
+        let is_file = file_type.is_file() || (file_type.is_symlink() && path.is_file());
+
+        if !include_ignored && is_ignored(matchers, &abs, is_dir) {
+            result.ignored += 1;
+            continue;
+        }
+
+        if is_dir {
+            walk_dir(&path, &abs, include_ignored, matchers, depth + 1, result);
+        } else if is_file {
+            result.files.push(path);
+        }
+    }
+
+    if pushed {
+        matchers.pop();
+    }
+}
+
+/// 深い階層のルールから順に判定（否定パターンも考慮）
+pub(super) fn is_ignored(matchers: &[Gitignore], path: &Path, is_dir: bool) -> bool {
+    for matcher in matchers.iter().rev() {
+        let matched = matcher.matched(path, is_dir);
+        if matched.is_ignore() {
+            return true;
+        }
+        if matched.is_whitelist() {
+            return false;
+        }
+    }
+    false
+}
+
+/// ディレクトリのignoreファイルを読み込む（なければ None）
+pub(super) fn load_matcher(dir: &Path) -> Option<Gitignore> {
+    let files: Vec<PathBuf> = IGNORE_FILES
+        .iter()
+        .map(|name| dir.join(name))
+        .filter(|path| path.is_file())
+        .collect();
+    if files.is_empty() {
+        return None;
+    }
+
+    let mut builder = GitignoreBuilder::new(dir);
+    for file in &files {
+        if let Some(e) = builder.add(file) {
+            tracing::debug!("Failed to parse {}: {}", file.display(), e);
+        }
+    }
+    builder.build().ok()
+}
+
+/// base より上のディレクトリのルール（リポジトリのルートまで、浅い順）
+pub(super) fn parent_matchers(abs_base: &Path) -> Vec<Gitignore> {
+    let mut dirs = Vec::new();
+    let mut found_repo_root = abs_base.join(".git").exists();
+    for ancestor in abs_base.ancestors().skip(1) {
+        if found_repo_root {
+            break;
+        }
+        dirs.push(ancestor);
+        found_repo_root = ancestor.join(".git").exists();
+    }
+    // リポジトリ外では上位のルールを読まない
+    if !found_repo_root {
+        return Vec::new();
+    }
+    dirs.iter().rev().filter_map(|dir| load_matcher(dir)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_skips_ignored_directories() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join(".gitignore"), "target/\n*.log\n!keep.log\n").unwrap();
+        std::fs::write(root.join(".ignore"), "generated/\n").unwrap();
+        for sub in ["src", "target/debug", "generated"] {
+            std::fs::create_dir_all(root.join(sub)).unwrap();
+        }
+        std::fs::write(root.join("src/main.rs"), "").unwrap();
+        std::fs::write(root.join("target/debug/out"), "").unwrap();
+        std::fs::write(root.join("generated/a.rs"), "").unwrap();
+        std::fs::write(root.join("debug.log"), "").unwrap();
+        std::fs::write(root.join("keep.log"), "").unwrap();
+
+        let result = walk_files(root, false);
+        let names: Vec<String> = result
+            .files
+            .iter()
+            .map(|p| p.strip_prefix(root).unwrap().display().to_string())
+            .collect();
+        assert_eq!(names, vec![".gitignore", ".ignore", "keep.log", "src/main.rs"]);
+        assert_eq!(result.ignored, 3);
+
+        let result = walk_files(root, true);
+        assert_eq!(result.files.len(), 7);
+        assert_eq!(result.ignored, 0);
+    }
+
+    #[test]
+    fn test_reads_rules_above_search_dir_within_repo() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::write(root.join(".gitignore"), "*.tmp\n").unwrap();
+        std::fs::create_dir_all(root.join("src/nested")).unwrap();
+        std::fs::write(root.join("src/nested/.gitignore"), "local.rs\n").unwrap();
+        std::fs::write(root.join("src/a.rs"), "").unwrap();
+        std::fs::write(root.join("src/b.tmp"), "").unwrap();
+        std::fs::write(root.join("src/nested/local.rs"), "").unwrap();
+
+        let result = walk_files(&root.join("src"), false);
+        let names: Vec<String> = result
+            .files
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, vec!["a.rs", ".gitignore"]);
+        assert_eq!(result.ignored, 2);
+    }
+}
@@ -0,0 +1,213 @@
+//! 作業の計画用の ToDo リスト
+//!
+//! モデルが複数手順の作業を始めるときにリストを作り、進むたびに状態を更新する。
+//! リストはセッション中だけ保持し、REPL は変わるたびに表示する
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+use super::{Tool, ToolEffects, ToolResult};
+
+/// ToDo の状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoStatus {
+    Pending,
+    InProgress,
+    Completed,
+}
+
+impl TodoStatus {
+    /// リスト表示のチェックボックス
+    fn checkbox(&self) -> &'static str {
+        match self {
+            TodoStatus::Pending => "[ ]",
+            TodoStatus::InProgress => "[~]",
+            TodoStatus::Completed => "[x]",
+        }
+    }
+}
+
+/// ToDo 1件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TodoItem {
+    pub content: String,
+    pub status: TodoStatus,
+}
+
+/// セッション中の ToDo リスト（main.rs で作り、ツールと REPL で共有する）
+pub type TodoList = Arc<Mutex<Vec<TodoItem>>>;
+
+/// チェックボックス付きで表示（最後に完了数）
+pub fn render_todos(items: &[TodoItem]) -> String {
+    if items.is_empty() {
+        return "No todos.".to_string();
+    }
+    let mut lines: Vec<String> = items
+        .iter()
+        .map(|item| format!("{} {}", item.status.checkbox(), item.content))
+        .collect();
+    let completed = items.iter().filter(|i| i.status == TodoStatus::Completed).count();
+    lines.push(format!("({}/{} completed)", completed, items.len()));
+    lines.join("\n")
+}
+
+/// ToDo リストを置き換えるツール
+pub struct TodoTool {
+    todos: TodoList,
+}
+
+impl TodoTool {
+    pub fn new(todos: TodoList) -> Self {
+        Self { todos }
+    }
+}
+
+/// 渡されたリストを検証（空の内容と、進行中が2件以上あるものは受け付けない）
+fn parse_items(value: &Value) -> std::result::Result<Vec<TodoItem>, String> {
+    let items: Vec<TodoItem> =
+        serde_json::from_value(value.clone()).map_err(|e| format!("Invalid todos: {}", e))?;
+    if let Some(i) = items.iter().position(|item| item.content.trim().is_empty()) {
+        return Err(format!("Todo {} has empty content", i + 1));
+    }
+    let in_progress = items.iter().filter(|i| i.status == TodoStatus::InProgress).count();
+    if in_progress > 1 {
+        return Err(format!(
+            "{} todos are in_progress; keep exactly one task in progress at a time",
+            in_progress
+        ));
+    }
+    Ok(items)
+}
+
+#[async_trait]
+impl Tool for TodoTool {
+    fn name(&self) -> &str {
+        "todo"
+    }
+
+    fn description(&self) -> &str {
+        "Plan multi-step work with a todo list. Pass the full list each time (it replaces the previous one); mark one item in_progress while working on it and completed as soon as it is done"
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "todos": {
+                    "type": "array",
+                    "description": "The complete todo list (replaces the current one)",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "content": {
+                                "type": "string",
+                                "description": "What to do"
+                            },
+                            "status": {
+                                "type": "string",
+                                "enum": ["pending", "in_progress", "completed"]
+                            }
+                        },
+                        "required": ["content", "status"]
+                    }
+                }
+            },
+            "required": ["todos"]
+        })
+    }
+
+    fn effects(&self) -> ToolEffects {
+        // ファイルは書かないので Plan モードでも使える
+        ToolEffects::ReadOnly
+    }
+
+    async fn execute(&self, params: Value) -> Result<ToolResult> {
+        let value = params.get("todos").ok_or_else(|| anyhow::anyhow!("Missing todos parameter"))?;
+        let items = match parse_items(value) {
+            Ok(items) => items,
+            Err(e) => return Ok(ToolResult::failure(e)),
+        };
+        let rendered = render_todos(&items);
+        *self.todos.lock().unwrap() = items;
+        Ok(ToolResult::success(rendered))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(content: &str, status: TodoStatus) -> TodoItem {
+        TodoItem {
+            content: content.to_string(),
+            status,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_set_replaces_list_as_work_progresses() {
+        let todos = TodoList::default();
+        let tool = TodoTool::new(Arc::clone(&todos));
+
+        tool.execute(json!({"todos": [
+            {"content": "Read the parser", "status": "in_progress"},
+            {"content": "Fix the bug", "status": "pending"},
+        ]}))
+        .await
+        .unwrap();
+        assert_eq!(todos.lock().unwrap()[0].status, TodoStatus::InProgress);
+
+        let result = tool
+            .execute(json!({"todos": [
+                {"content": "Read the parser", "status": "completed"},
+                {"content": "Fix the bug", "status": "in_progress"},
+            ]}))
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert_eq!(
+            *todos.lock().unwrap(),
+            vec![
+                item("Read the parser", TodoStatus::Completed),
+                item("Fix the bug", TodoStatus::InProgress),
+            ]
+        );
+
+        tool.execute(json!({"todos": []})).await.unwrap();
+        assert!(todos.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_lists_leave_current_list_unchanged() {
+        let todos: TodoList = Arc::new(Mutex::new(vec![item("keep", TodoStatus::Pending)]));
+        let tool = TodoTool::new(Arc::clone(&todos));
+
+        for todos in [
+            json!([{"content": "a", "status": "in_progress"}, {"content": "b", "status": "in_progress"}]),
+            json!([{"content": "  ", "status": "pending"}]),
+            json!([{"content": "a", "status": "done"}]),
+        ] {
+            let result = tool.execute(json!({"todos": todos})).await.unwrap();
+            assert!(!result.success);
+        }
+        assert_eq!(*todos.lock().unwrap(), vec![item("keep", TodoStatus::Pending)]);
+    }
+
+    #[test]
+    fn test_render_todos() {
+        assert_eq!(render_todos(&[]), "No todos.");
+        let items = [
+            item("Write tests", TodoStatus::Completed),
+            item("Implement", TodoStatus::InProgress),
+            item("Update docs", TodoStatus::Pending),
+        ];
+        assert_eq!(
+            render_todos(&items),
+            "[x] Write tests\n[~] Implement\n[ ] Update docs\n(1/3 completed)"
+        );
+    }
+}
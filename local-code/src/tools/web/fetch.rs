@@ -0,0 +1,319 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Url;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use super::html::html_to_text;
+use crate::llm::client::build_http_client;
+use crate::tools::{Tool, ToolEffects, ToolResult};
+
+/// 既定のタイムアウト（秒）
+pub const DEFAULT_WEB_TIMEOUT: u64 = 30;
+/// 既定のダウンロード上限（バイト）
+pub const DEFAULT_WEB_MAX_BYTES: usize = 2 * 1024 * 1024;
+/// max_bytes を省略したときに返すテキストの上限（バイト）
+const DEFAULT_TEXT_BYTES: usize = 50_000;
+
+/// URL の内容を取得してテキストで返すツール
+///
+/// HTML は読みやすいテキストに変換する。http/https 以外の URL は拒否し、
+/// タイムアウトとダウンロード量の上限を超えたら打ち切る
+pub struct WebFetchTool {
+    /// 接続から読み終わりまでのタイムアウト
+    timeout: Duration,
+    /// ダウンロードする本文の上限（バイト）
+    max_download_bytes: usize,
+}
+
+impl WebFetchTool {
+    pub fn new() -> Self {
+        Self {
+            timeout: Duration::from_secs(DEFAULT_WEB_TIMEOUT),
+            max_download_bytes: DEFAULT_WEB_MAX_BYTES,
+        }
+    }
+
+    /// タイムアウトを設定
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// ダウンロードする本文の上限を設定
+    pub fn with_max_download_bytes(mut self, bytes: usize) -> Self {
+        self.max_download_bytes = bytes;
+        self
+    }
+
+    /// 本文を上限まで読む（上限を超えたら true）
+    async fn download(&self, url: &Url) -> std::result::Result<(Url, Option<String>, Vec<u8>, bool), String> {
+        let secs = self.timeout.as_secs().max(1);
+        let client = build_http_client(url.as_str(), secs, secs);
+        let mut response = client
+            .get(url.clone())
+            .timeout(self.timeout)
+            .send()
+            .await
+            .map_err(|e| describe_error(url, &e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("HTTP {} for {}", status, response.url()));
+        }
+        let final_url = response.url().clone();
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_ascii_lowercase());
+
+        let mut body = Vec::new();
+        let mut truncated = false;
+        while let Some(chunk) = response.chunk().await.map_err(|e| describe_error(url, &e))? {
+            let room = self.max_download_bytes - body.len();
+            if chunk.len() > room {
+                body.extend_from_slice(&chunk[..room]);
+                truncated = true;
+                break;
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok((final_url, content_type, body, truncated))
+    }
+}
+
+impl Default for WebFetchTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// http/https の URL だけを受け付ける
+fn parse_url(url: &str) -> std::result::Result<Url, String> {
+    let parsed = Url::parse(url.trim()).map_err(|e| format!("Invalid URL '{}': {}", url, e))?;
+    match parsed.scheme() {
+        "http" | "https" => Ok(parsed),
+        scheme => Err(format!("Unsupported URL scheme '{}': only http and https are allowed", scheme)),
+    }
+}
+
+fn describe_error(url: &Url, error: &reqwest::Error) -> String {
+    if error.is_timeout() {
+        format!("Request to {} timed out", url)
+    } else if error.is_connect() {
+        format!("Could not connect to {}: {}", url, error)
+    } else {
+        format!("Request to {} failed: {}", url, error)
+    }
+}
+
+/// HTML として扱うか（Content-Type がなければ先頭を見る）
+fn is_html(content_type: Option<&str>, body: &str) -> bool {
+    match content_type {
+        Some(ct) if ct.contains("html") => true,
+        Some(ct) if ct.starts_with("text/") || ct.contains("json") || ct.contains("xml") => false,
+        _ => {
+            let head = body.trim_start().get(..15).unwrap_or("").to_ascii_lowercase();
+            head.starts_with("<!doctype html") || head.starts_with("<html")
+        }
+    }
+}
+
+/// テキストとして読めない Content-Type か
+fn is_binary(content_type: Option<&str>) -> bool {
+    content_type.is_some_and(|ct| {
+        ct.starts_with("image/")
+            || ct.starts_with("audio/")
+            || ct.starts_with("video/")
+            || ct.starts_with("font/")
+            || ct.contains("octet-stream")
+            || ct.contains("zip")
+            || ct.contains("pdf")
+    })
+}
+
+/// 文字の境界で切り詰める
+fn truncate_to(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+#[async_trait]
+impl Tool for WebFetchTool {
+    fn name(&self) -> &str {
+        "web_fetch"
+    }
+
+    fn description(&self) -> &str {
+        "Fetch a web page (http/https) and return its readable text. Use it for crate docs, API references or upstream issues"
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": {
+                    "type": "string",
+                    "description": "http or https URL to fetch"
+                },
+                "max_bytes": {
+                    "type": "integer",
+                    "description": format!("Maximum bytes of text to return (default: {})", DEFAULT_TEXT_BYTES)
+                }
+            },
+            "required": ["url"]
+        })
+    }
+
+    fn effects(&self) -> ToolEffects {
+        ToolEffects::ReadOnly
+    }
+
+    async fn execute(&self, params: Value) -> Result<ToolResult> {
+        let url = params.get("url")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Missing url parameter"))?;
+        let max_bytes = params.get("max_bytes")
+            .and_then(|v| v.as_u64())
+            .map(|n| n as usize)
+            .unwrap_or(DEFAULT_TEXT_BYTES)
+            .clamp(1, self.max_download_bytes.max(1));
+
+        let url = match parse_url(url) {
+            Ok(url) => url,
+            Err(e) => return Ok(ToolResult::failure(e)),
+        };
+        let (final_url, content_type, body, cut_off) = match self.download(&url).await {
+            Ok(downloaded) => downloaded,
+            Err(e) => return Ok(ToolResult::failure(e)),
+        };
+        if is_binary(content_type.as_deref()) {
+            return Ok(ToolResult::failure(format!(
+                "{} is not a text page (Content-Type: {})",
+                final_url,
+                content_type.unwrap_or_default()
+            )));
+        }
+
+        let body = String::from_utf8_lossy(&body);
+        let text = if is_html(content_type.as_deref(), &body) {
+            html_to_text(&body, Some(&final_url))
+        } else {
+            body.trim().to_string()
+        };
+
+        let shown = truncate_to(&text, max_bytes);
+        let mut output = format!("URL: {}\n\n{}", final_url, shown);
+        if shown.len() < text.len() {
+            output.push_str(&format!(
+                "\n\n[truncated: showing {} of {} bytes of text; call again with a larger max_bytes to read more]",
+                shown.len(),
+                text.len()
+            ));
+        }
+        if cut_off {
+            output.push_str(&format!(
+                "\n\n[download stopped at {} bytes; the rest of the page was not fetched]",
+                self.max_download_bytes
+            ));
+        }
+        Ok(ToolResult::success(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::mock_server::spawn_mock_handler;
+
+    fn html_response(body: &str) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+    }
+
+    #[tokio::test]
+    async fn test_fetches_html_as_text() {
+        let url = spawn_mock_handler(|_| {
+            html_response("<html><body><h1>Title</h1><script>alert(1)</script><p>Body <a href=\"/next\">next</a></p></body></html>")
+        })
+        .await;
+
+        let result = WebFetchTool::new()
+            .execute(json!({"url": format!("{}/docs", url)}))
+            .await
+            .unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(
+            result.output,
+            format!("URL: {}/docs\n\n# Title\n\nBody next ({}/next)", url, url)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejects_non_http_schemes() {
+        let tool = WebFetchTool::new();
+        for url in ["file:///etc/passwd", "ftp://example.com/a", "not a url"] {
+            let result = tool.execute(json!({"url": url})).await.unwrap();
+            assert!(!result.success);
+            let error = result.error.unwrap();
+            assert!(error.contains("Unsupported URL scheme") || error.contains("Invalid URL"), "{}", error);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_caps_text_and_download_size() {
+        let url = spawn_mock_handler(|_| html_response(&format!("<p>{}</p>", "word ".repeat(2000)))).await;
+
+        let result = WebFetchTool::new()
+            .execute(json!({"url": url.clone(), "max_bytes": 100}))
+            .await
+            .unwrap();
+        assert!(result.output.contains("[truncated: showing 100 of 9999 bytes of text"), "{}", result.output);
+        assert!(!result.output.contains("download stopped"));
+
+        let result = WebFetchTool::new()
+            .with_max_download_bytes(1000)
+            .execute(json!({"url": url}))
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert!(result.output.contains("[download stopped at 1000 bytes"), "{}", result.output);
+    }
+
+    #[tokio::test]
+    async fn test_times_out_and_reports_http_errors() {
+        // 接続は受け付けるが応答しないサーバー
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+        let result = WebFetchTool::new()
+            .with_timeout(Duration::from_secs(1))
+            .execute(json!({"url": format!("http://{}/", addr)}))
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("timed out"));
+
+        let url = spawn_mock_handler(|_| {
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+        })
+        .await;
+        let result = WebFetchTool::new().execute(json!({"url": url})).await.unwrap();
+        assert!(result.error.unwrap().starts_with("HTTP 404 Not Found"));
+    }
+}
@@ -0,0 +1,324 @@
+//! HTML を読みやすいテキストに変換する
+//!
+//! スクリプト・スタイルを捨て、空白をまとめ、見出しは `#`、リンクは `テキスト (URL)` として残す。
+//! 正確なパーサーではなく、ドキュメントのページをモデルに読ませるための簡易な変換
+
+use reqwest::Url;
+
+/// 中身ごと捨てる要素
+const SKIPPED_ELEMENTS: &[&str] = &["script", "style", "noscript", "template", "svg", "iframe"];
+
+/// 前後に空行を入れる要素
+const PARAGRAPH_ELEMENTS: &[&str] = &["p", "pre", "blockquote", "table", "ul", "ol", "dl", "figure", "hr"];
+
+/// 前後で改行する要素
+const LINE_ELEMENTS: &[&str] = &[
+    "br", "div", "section", "article", "header", "footer", "nav", "main", "aside", "tr", "dt", "dd", "title",
+    "form", "details", "summary", "caption",
+];
+
+/// HTML をテキストに変換（相対リンクは `base` で解決する）
+pub fn html_to_text(html: &str, base: Option<&Url>) -> String {
+    let mut out = TextBuilder::default();
+    let mut rest = html;
+    let mut link: Option<String> = None;
+    let mut pre_depth = 0usize;
+
+    while let Some(lt) = rest.find('<') {
+        out.push_text(&decode_entities(&rest[..lt]), pre_depth > 0);
+        rest = &rest[lt..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some(gt) = tag_end(rest) else {
+            // 閉じていない `<` は文字として扱う
+            out.push_text(&decode_entities(rest), pre_depth > 0);
+            rest = "";
+            break;
+        };
+        let tag = &rest[1..gt];
+        rest = &rest[gt + 1..];
+
+        let closing = tag.starts_with('/');
+        let name: String = tag
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        if name.is_empty() {
+            // `<!DOCTYPE>` や `<?xml?>`
+            continue;
+        }
+
+        if SKIPPED_ELEMENTS.contains(&name.as_str()) {
+            if !closing && !tag.ends_with('/') {
+                rest = skip_element(rest, &name);
+            }
+            continue;
+        }
+
+        match name.as_str() {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                out.paragraph();
+                if !closing {
+                    let level = name[1..].parse::<usize>().unwrap_or(1);
+                    out.push_raw(&format!("{} ", "#".repeat(level)));
+                }
+            }
+            "li" => {
+                out.newline();
+                if !closing {
+                    out.push_raw("- ");
+                }
+            }
+            "td" | "th" if !closing => out.push_text(" ", false),
+            "a" if !closing => link = attribute(tag, "href").and_then(|href| resolve_link(&href, base)),
+            "a" => {
+                if let Some(href) = link.take() {
+                    out.push_text(&format!(" ({})", href), false);
+                }
+            }
+            "pre" => {
+                out.paragraph();
+                if closing {
+                    pre_depth = pre_depth.saturating_sub(1);
+                } else {
+                    pre_depth += 1;
+                }
+            }
+            name if PARAGRAPH_ELEMENTS.contains(&name) => out.paragraph(),
+            name if LINE_ELEMENTS.contains(&name) => out.newline(),
+            _ => {}
+        }
+    }
+    out.push_text(&decode_entities(rest), pre_depth > 0);
+    out.finish()
+}
+
+/// タグの終わりの `>` の位置（属性値の引用符の中は飛ばす）
+fn tag_end(s: &str) -> Option<usize> {
+    let mut quote: Option<char> = None;
+    for (i, c) in s.char_indices().skip(1) {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `</name>` の後ろまで飛ばす（閉じタグがなければ残りをすべて捨てる）
+fn skip_element<'a>(rest: &'a str, name: &str) -> &'a str {
+    let lower = rest.to_ascii_lowercase();
+    let closing = format!("</{}", name);
+    match lower.find(&closing) {
+        Some(start) => rest[start..].find('>').map_or("", |end| &rest[start + end + 1..]),
+        None => "",
+    }
+}
+
+/// タグ内の属性値（引用符なしの値にも対応）
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut search = 0;
+    while let Some(found) = lower[search..].find(name) {
+        let start = search + found;
+        search = start + name.len();
+        // 別の属性名の一部（data-href など）は除く
+        if !lower[..start].ends_with(char::is_whitespace) {
+            continue;
+        }
+        let after = tag[search..].trim_start();
+        let Some(value) = after.strip_prefix('=') else {
+            continue;
+        };
+        let value = value.trim_start();
+        let value = match value.chars().next() {
+            Some(q @ ('"' | '\'')) => value[1..].split(q).next().unwrap_or(""),
+            _ => value.split(|c: char| c.is_whitespace() || c == '>').next().unwrap_or(""),
+        };
+        return Some(decode_entities(value));
+    }
+    None
+}
+
+/// リンク先を絶対 URL にする（ページ内リンクと javascript: は残さない）
+fn resolve_link(href: &str, base: Option<&Url>) -> Option<String> {
+    let href = href.trim();
+    if href.is_empty() || href.starts_with('#') || href.to_ascii_lowercase().starts_with("javascript:") {
+        return None;
+    }
+    match base.and_then(|base| base.join(href).ok()) {
+        Some(url) => Some(url.to_string()),
+        None => Some(href.to_string()),
+    }
+}
+
+/// 文字参照を展開（知らない名前はそのまま残す）
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest[1..].find(';').filter(|&end| end <= 10).and_then(|end| {
+            let entity = &rest[1..end + 1];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ => match entity.strip_prefix('#') {
+                    Some(hex) if hex.starts_with(['x', 'X']) => u32::from_str_radix(&hex[1..], 16).ok().and_then(char::from_u32),
+                    Some(dec) => dec.parse::<u32>().ok().and_then(char::from_u32),
+                    None => None,
+                },
+            };
+            c.map(|c| (c, end + 2))
+        });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// 空白をまとめながらテキストを組み立てる
+#[derive(Default)]
+struct TextBuilder {
+    out: String,
+    /// 次の語の前に空白を入れるか
+    space: bool,
+}
+
+impl TextBuilder {
+    fn at_line_start(&self) -> bool {
+        self.out.is_empty() || self.out.ends_with('\n') || self.out.ends_with(' ')
+    }
+
+    fn push_text(&mut self, text: &str, preformatted: bool) {
+        if preformatted {
+            self.out.push_str(text);
+            self.space = false;
+            return;
+        }
+        if text.starts_with(char::is_whitespace) {
+            self.space = true;
+        }
+        for (i, word) in text.split_whitespace().enumerate() {
+            if (i > 0 || self.space) && !self.at_line_start() {
+                self.out.push(' ');
+            }
+            self.space = false;
+            self.out.push_str(word);
+        }
+        if text.ends_with(char::is_whitespace) {
+            self.space = true;
+        }
+    }
+
+    fn push_raw(&mut self, text: &str) {
+        self.out.push_str(text);
+        self.space = false;
+    }
+
+    fn newline(&mut self) {
+        let trimmed = self.out.trim_end_matches([' ', '\t']).len();
+        self.out.truncate(trimmed);
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+        self.space = false;
+    }
+
+    fn paragraph(&mut self) {
+        self.newline();
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    /// 行末の空白を除き、3行以上の空行を1行にまとめる
+    fn finish(self) -> String {
+        let mut text = String::with_capacity(self.out.len());
+        let mut blank = 0;
+        for line in self.out.lines() {
+            let line = line.trim_end();
+            if line.trim().is_empty() {
+                blank += 1;
+                if blank > 1 {
+                    continue;
+                }
+            } else {
+                blank = 0;
+            }
+            text.push_str(line);
+            text.push('\n');
+        }
+        text.trim().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strips_scripts_and_collapses_whitespace() {
+        let html = r#"<!DOCTYPE html>
+<html><head><title>Docs</title><style>body { color: red; }</style>
+<script>var x = "<p>not text</p>";</script></head>
+<body>
+  <!-- navigation -->
+  <h1>Crate   <code>foo</code></h1>
+  <p>First
+     paragraph &amp; more&nbsp;text.</p>
+  <ul><li>one</li><li>two</li></ul>
+</body></html>"#;
+        assert_eq!(
+            html_to_text(html, None),
+            "Docs\n\n# Crate foo\n\nFirst paragraph & more text.\n\n- one\n- two"
+        );
+    }
+
+    #[test]
+    fn test_keeps_links_and_preformatted_text() {
+        let base = Url::parse("https://docs.rs/foo/1.0/foo/index.html").unwrap();
+        let html = r#"<h2>See <a href="struct.Bar.html">Bar</a> and <a href='#top'>top</a></h2>
+<pre>fn main() {
+    println!("&lt;hi&gt;");
+}</pre><p>Done</p>"#;
+        assert_eq!(
+            html_to_text(html, Some(&base)),
+            "## See Bar (https://docs.rs/foo/1.0/foo/struct.Bar.html) and top\n\nfn main() {\n    println!(\"<hi>\");\n}\n\nDone"
+        );
+    }
+
+    #[test]
+    fn test_entities_and_attributes() {
+        assert_eq!(decode_entities("a &lt;b&gt; &#65;&#x42; &unknown; &"), "a <b> AB &unknown; &");
+        assert_eq!(attribute(r#"a data-href="x" href="y""#, "href").as_deref(), Some("y"));
+        assert_eq!(attribute("a href=/z title=t", "href").as_deref(), Some("/z"));
+        assert_eq!(attribute(r#"a title="x > y" href="q""#, "href").as_deref(), Some("q"));
+        assert_eq!(html_to_text(r#"<a title="x > y" href="https://e.com">e</a>"#, None), "e (https://e.com)");
+    }
+}
@@ -0,0 +1,5 @@
+pub mod fetch;
+pub mod html;
+
+pub use fetch::{WebFetchTool, DEFAULT_WEB_MAX_BYTES, DEFAULT_WEB_TIMEOUT};
+pub use html::html_to_text;
@@ -0,0 +1,366 @@
+//! 複数ルートのワークスペース
+//!
+//! `--project` を複数指定するか、ワークスペースファイルでルートとラベルを並べる。
+//! ファイル系ツールは `ラベル:相対パス`（例: `backend:src/api.rs`）を受け付け、
+//! 出力のパスも同じ形に揃える。ラベルのない相対パスは現在のルートから解決する。
+//! ルートが1つのときはパスを一切書き換えない（従来どおり）。
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// glob/grep で全ルートを検索する指定
+pub const ALL_ROOTS: &str = "all";
+
+/// ワークスペースの1ルート
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkspaceRoot {
+    /// パスの前置きに使うラベル
+    pub label: String,
+    pub path: PathBuf,
+}
+
+/// ワークスペースファイルの形式
+#[derive(Debug, Deserialize)]
+struct WorkspaceFile {
+    #[serde(default)]
+    roots: Vec<RootEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RootEntry {
+    #[serde(default)]
+    label: Option<String>,
+    path: PathBuf,
+}
+
+/// ワークスペース（最初のルートが主ルート）
+#[derive(Debug)]
+pub struct Workspace {
+    roots: Vec<WorkspaceRoot>,
+    /// 現在のルート（ラベルのない相対パスの基準）
+    current: AtomicUsize,
+}
+
+impl Workspace {
+    /// ルート1つのワークスペース
+    pub fn single(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let label = derive_label(&path);
+        Self::from_roots(vec![WorkspaceRoot { label, path }])
+    }
+
+    /// パスの一覧から作る（`label=path` でラベルを指定、省略時はディレクトリ名）
+    pub fn from_paths(paths: &[PathBuf]) -> Result<Self> {
+        if paths.is_empty() {
+            bail!("no project roots given");
+        }
+        let mut roots = Vec::new();
+        for path in paths {
+            let text = path.to_string_lossy();
+            let (label, path) = match text.split_once('=') {
+                Some((label, rest)) if is_valid_label(label) => (Some(label.to_string()), PathBuf::from(rest)),
+                _ => (None, path.clone()),
+            };
+            push_root(&mut roots, label, path)?;
+        }
+        Ok(Self::from_roots(roots))
+    }
+
+    /// ワークスペースファイル（TOML の `[[roots]]`）を読む。相対パスはファイルの場所から
+    pub fn load_file(file: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(file)
+            .with_context(|| format!("failed to read workspace file {}", file.display()))?;
+        let parsed: WorkspaceFile = toml::from_str(&content)
+            .with_context(|| format!("invalid workspace file {}", file.display()))?;
+        if parsed.roots.is_empty() {
+            bail!("workspace file {} lists no [[roots]]", file.display());
+        }
+        let base = file.parent().unwrap_or(Path::new("."));
+        let mut roots = Vec::new();
+        for entry in parsed.roots {
+            let path = if entry.path.is_absolute() { entry.path } else { base.join(entry.path) };
+            push_root(&mut roots, entry.label, path)?;
+        }
+        Ok(Self::from_roots(roots))
+    }
+
+    fn from_roots(roots: Vec<WorkspaceRoot>) -> Self {
+        Self {
+            roots,
+            current: AtomicUsize::new(0),
+        }
+    }
+
+    /// ルートが複数あるか
+    pub fn is_multi(&self) -> bool {
+        self.roots.len() > 1
+    }
+
+    pub fn roots(&self) -> &[WorkspaceRoot] {
+        &self.roots
+    }
+
+    /// 主ルート（LSP・bash・agent.md の基準）
+    pub fn primary(&self) -> &WorkspaceRoot {
+        &self.roots[0]
+    }
+
+    /// 現在のルート
+    pub fn current(&self) -> &WorkspaceRoot {
+        &self.roots[self.current.load(Ordering::Relaxed).min(self.roots.len() - 1)]
+    }
+
+    /// 現在のルートを切り替える
+    pub fn set_current(&self, label: &str) -> Result<&WorkspaceRoot> {
+        let index = self
+            .roots
+            .iter()
+            .position(|r| r.label == label)
+            .with_context(|| format!("unknown root '{}' (roots: {})", label, self.labels().join(", ")))?;
+        self.current.store(index, Ordering::Relaxed);
+        Ok(&self.roots[index])
+    }
+
+    pub fn get(&self, label: &str) -> Option<&WorkspaceRoot> {
+        self.roots.iter().find(|r| r.label == label)
+    }
+
+    pub fn labels(&self) -> Vec<&str> {
+        self.roots.iter().map(|r| r.label.as_str()).collect()
+    }
+
+    /// `ラベル:残り` ならそのルートと残りの相対パス（ルートが1つなら常に None）
+    pub fn locate<'a>(&self, path: &'a str) -> Option<(&WorkspaceRoot, &'a str)> {
+        if !self.is_multi() {
+            return None;
+        }
+        let (label, rest) = path.split_once(':')?;
+        let root = self.get(label)?;
+        Some((root, rest.trim_start_matches('/')))
+    }
+
+    /// ツールに渡されたパスを実際のパスに解決する
+    ///
+    /// ラベルのない相対パスは現在のルートから（ラベル付きは複数ルートのときだけ、そのルートから）
+    pub fn resolve(&self, path: &str) -> PathBuf {
+        if let Some((root, rest)) = self.locate(path) {
+            return if rest.is_empty() { root.path.clone() } else { root.path.join(rest) };
+        }
+        let path = Path::new(path);
+        if path.is_relative() {
+            self.current().path.join(path)
+        } else {
+            path.to_path_buf()
+        }
+    }
+
+    /// 出力用のパス（複数ルートのときは `ラベル:相対パス`）
+    pub fn display(&self, path: &Path) -> String {
+        if !self.is_multi() {
+            return path.display().to_string();
+        }
+        let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        let found = self
+            .roots
+            .iter()
+            .filter_map(|root| {
+                let base = std::path::absolute(&root.path).unwrap_or_else(|_| root.path.clone());
+                absolute.strip_prefix(&base).ok().map(|rel| (root, rel))
+            })
+            // 入れ子のルートは深い方を優先
+            .max_by_key(|(root, _)| root.path.components().count());
+        match found {
+            Some((root, rel)) => format!("{}:{}", root.label, slash_path(rel)),
+            None => path.display().to_string(),
+        }
+    }
+
+    /// glob/grep の検索先（None は現在のルート、"all" は全ルート、それ以外はラベル）
+    pub fn search_roots(&self, root: Option<&str>) -> Result<Vec<&WorkspaceRoot>, String> {
+        match root {
+            None => Ok(vec![self.current()]),
+            Some(ALL_ROOTS) => Ok(self.roots.iter().collect()),
+            Some(label) => self
+                .get(label)
+                .map(|r| vec![r])
+                .ok_or_else(|| format!("Unknown root '{}' (roots: {})", label, self.labels().join(", "))),
+        }
+    }
+}
+
+/// ツールのパス引数を解決し、出力用の表記と組にする（ワークスペースなしなら与えられたまま）
+pub fn resolve_tool_path(workspace: Option<&Workspace>, path: &str) -> (PathBuf, String) {
+    match workspace {
+        Some(workspace) => {
+            let resolved = workspace.resolve(path);
+            // 単一ルートでは与えられた表記のまま見せる
+            let shown = if workspace.is_multi() { workspace.display(&resolved) } else { path.to_string() };
+            (resolved, shown)
+        }
+        None => (PathBuf::from(path), path.to_string()),
+    }
+}
+
+/// ラベルに使える文字か（Windows のドライブ文字と区別するため2文字以上）
+fn is_valid_label(label: &str) -> bool {
+    label.chars().count() >= 2
+        && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// ディレクトリ名からラベルを作る
+fn derive_label(path: &Path) -> String {
+    let name = std::path::absolute(path)
+        .ok()
+        .and_then(|p| {
+            p.components().rev().find_map(|c| match c {
+                Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                _ => None,
+            })
+        })
+        .unwrap_or_default();
+    let label: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect();
+    if is_valid_label(&label) { label } else { "root".to_string() }
+}
+
+/// ルートを追加（ラベルの重複は自動ラベルなら番号を付け、明示ならエラー）
+fn push_root(roots: &mut Vec<WorkspaceRoot>, label: Option<String>, path: PathBuf) -> Result<()> {
+    if !path.is_dir() {
+        bail!("project root {} is not a directory", path.display());
+    }
+    let label = match label {
+        Some(label) => {
+            if !is_valid_label(&label) {
+                bail!("invalid root label '{}' (use letters, digits, '-' or '_')", label);
+            }
+            if roots.iter().any(|r| r.label == label) {
+                bail!("duplicate root label '{}'", label);
+            }
+            label
+        }
+        None => {
+            let base = derive_label(&path);
+            let mut label = base.clone();
+            let mut n = 2;
+            while roots.iter().any(|r| r.label == label) {
+                label = format!("{}-{}", base, n);
+                n += 1;
+            }
+            label
+        }
+    };
+    roots.push(WorkspaceRoot { label, path });
+    Ok(())
+}
+
+/// 区切りを `/` に揃えた相対パス
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn two_roots() -> (tempfile::TempDir, Workspace) {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("backend/src")).unwrap();
+        std::fs::create_dir_all(dir.path().join("web")).unwrap();
+        let ws = Workspace::from_paths(&[
+            dir.path().join("backend"),
+            PathBuf::from(format!("frontend={}", dir.path().join("web").display())),
+        ])
+        .unwrap();
+        (dir, ws)
+    }
+
+    #[test]
+    fn test_labels_from_names_and_explicit() {
+        let (_dir, ws) = two_roots();
+        assert!(ws.is_multi());
+        assert_eq!(ws.labels(), vec!["backend", "frontend"]);
+        assert_eq!(ws.primary().label, "backend");
+    }
+
+    #[test]
+    fn test_duplicate_names_are_numbered() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("a/app")).unwrap();
+        std::fs::create_dir_all(dir.path().join("b/app")).unwrap();
+        let ws = Workspace::from_paths(&[dir.path().join("a/app"), dir.path().join("b/app")]).unwrap();
+        assert_eq!(ws.labels(), vec!["app", "app-2"]);
+
+        let err = Workspace::from_paths(&[
+            PathBuf::from(format!("x1={}", dir.path().join("a").display())),
+            PathBuf::from(format!("x1={}", dir.path().join("b").display())),
+        ])
+        .unwrap_err();
+        assert!(err.to_string().contains("duplicate"));
+    }
+
+    #[test]
+    fn test_resolve_and_display() {
+        let (dir, ws) = two_roots();
+        let api = dir.path().join("backend/src/api.rs");
+        assert_eq!(ws.resolve("backend:src/api.rs"), api);
+        assert_eq!(ws.resolve("frontend:"), dir.path().join("web"));
+        // ラベルのない相対パスは現在のルートから
+        assert_eq!(ws.resolve("src/api.rs"), api);
+        ws.set_current("frontend").unwrap();
+        assert_eq!(ws.resolve("app.ts"), dir.path().join("web/app.ts"));
+        assert_eq!(ws.resolve("/etc/hosts"), PathBuf::from("/etc/hosts"));
+
+        assert_eq!(ws.display(&api), "backend:src/api.rs");
+        assert_eq!(ws.display(Path::new("/elsewhere/x")), "/elsewhere/x");
+        assert!(ws.set_current("nope").is_err());
+    }
+
+    #[test]
+    fn test_single_root_resolves_from_root_without_labels() {
+        let dir = tempfile::tempdir().unwrap();
+        let ws = Workspace::single(dir.path());
+        assert!(!ws.is_multi());
+        assert_eq!(ws.resolve("src/main.rs"), dir.path().join("src/main.rs"));
+        assert_eq!(ws.resolve("/elsewhere/x"), PathBuf::from("/elsewhere/x"));
+        let label = ws.primary().label.clone();
+        assert_eq!(ws.resolve(&format!("{}:x", label)), dir.path().join(format!("{}:x", label)));
+        let file = dir.path().join("src/main.rs");
+        assert_eq!(ws.display(&file), file.display().to_string());
+        assert_eq!(resolve_tool_path(Some(&ws), "src/main.rs"), (file, "src/main.rs".to_string()));
+    }
+
+    #[test]
+    fn test_search_roots() {
+        let (_dir, ws) = two_roots();
+        assert_eq!(ws.search_roots(None).unwrap()[0].label, "backend");
+        assert_eq!(ws.search_roots(Some("all")).unwrap().len(), 2);
+        assert_eq!(ws.search_roots(Some("frontend")).unwrap()[0].label, "frontend");
+        assert!(ws.search_roots(Some("docs")).unwrap_err().contains("Unknown root"));
+    }
+
+    #[test]
+    fn test_load_file_relative_to_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("svc")).unwrap();
+        std::fs::create_dir_all(dir.path().join("ui")).unwrap();
+        let file = dir.path().join("local-code.workspace.toml");
+        std::fs::write(
+            &file,
+            "[[roots]]\nlabel = \"api\"\npath = \"svc\"\n\n[[roots]]\npath = \"ui\"\n",
+        )
+        .unwrap();
+        let ws = Workspace::load_file(&file).unwrap();
+        assert_eq!(ws.labels(), vec!["api", "ui"]);
+        assert_eq!(ws.roots()[0].path, dir.path().join("svc"));
+
+        std::fs::write(&file, "[[roots]]\npath = \"missing\"\n").unwrap();
+        assert!(Workspace::load_file(&file).is_err());
+    }
+}
@@ -0,0 +1,2 @@
+# 応答はファイルとして渡される
+grep -qi paris "$LOCAL_CODE_EVAL_RESPONSE"
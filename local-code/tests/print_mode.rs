@@ -0,0 +1,239 @@
+//! 非対話モード（`local-code -p`）の結合テスト
+//!
+//! モックの OLLAMA サーバーに向けてバイナリを起動し、標準出力と終了コードを確かめる。
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// `/api/generate` に固定の応答を返し、受け取ったボディを記録するモックサーバー
+struct MockOllama {
+    url: String,
+    bodies: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockOllama {
+    fn start(status: &'static str, response: &str) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&bodies);
+        let body = serde_json::json!({
+            "model": "mock",
+            "response": response,
+            "done": true,
+            "prompt_eval_count": 12,
+            "eval_count": 8,
+            "eval_duration": 400_000_000u64,
+        })
+        .to_string();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { break };
+                let (path, request_body) = read_request(&mut stream);
+                let (status, body) = if path == "/api/generate" {
+                    recorded.lock().unwrap().push(request_body);
+                    (status, body.as_str())
+                } else {
+                    ("404 Not Found", "{}")
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+            }
+        });
+        Self { url, bodies }
+    }
+
+    fn bodies(&self) -> Vec<String> {
+        self.bodies.lock().unwrap().clone()
+    }
+}
+
+fn read_request(stream: &mut TcpStream) -> (String, String) {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        let n = stream.read(&mut chunk).unwrap_or(0);
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        let text = String::from_utf8_lossy(&buf);
+        if let Some(end) = text.find("\r\n\r\n") {
+            let length = text[..end]
+                .lines()
+                .find_map(|l| {
+                    let (name, value) = l.split_once(':')?;
+                    name.eq_ignore_ascii_case("content-length").then(|| value.trim().parse::<usize>().ok())?
+                })
+                .unwrap_or(0);
+            if buf.len() >= end + 4 + length {
+                let path = text.split_whitespace().nth(1).unwrap_or_default().to_string();
+                return (path, text[end + 4..].to_string());
+            }
+        }
+    }
+    (String::new(), String::new())
+}
+
+/// 一時ディレクトリのプロジェクトと設定でバイナリを実行
+fn run(dir: &Path, url: &str, args: &[&str], stdin: Option<&str>) -> Output {
+    let project = dir.join("project");
+    std::fs::create_dir_all(&project).unwrap();
+    let config = dir.join("config.toml");
+    std::fs::write(
+        &config,
+        format!("[ollama]\nurl = \"{}\"\nmodel = \"mock\"\n\n[ollama.retry]\nmax_retries = 0\n\n[agent]\n\n[tools]\n", url),
+    )
+    .unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_local-code"))
+        .arg("--config")
+        .arg(&config)
+        .arg("--project")
+        .arg(&project)
+        .args(args)
+        .current_dir(&project)
+        .env("HOME", dir)
+        .env_remove("OLLAMA_HOST")
+        .env_remove("LOCAL_CODE_CONFIG")
+        .env_remove("LOCAL_CODE_SUPERPOWERS")
+        .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    if let Some(input) = stdin {
+        child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    }
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_prints_only_the_response() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockOllama::start("200 OK", "src/main.rs prints a greeting.");
+
+    let output = run(dir.path(), &server.url, &["-p", "explain src/main.rs"], None);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(stdout, "src/main.rs prints a greeting.\n");
+    assert!(!stdout.contains('\x1b'));
+    assert!(server.bodies()[0].contains("explain src/main.rs"));
+}
+
+#[test]
+fn test_piped_stdin_is_appended() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockOllama::start("200 OK", "A null pointer.");
+
+    let output = run(dir.path(), &server.url, &["-p", "what caused this?"], Some("panic: null pointer at foo.rs:3\n"));
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "A null pointer.\n");
+    let body = &server.bodies()[0];
+    assert!(body.contains("what caused this?"), "{}", body);
+    assert!(body.contains("<stdin>\\npanic: null pointer at foo.rs:3\\n</stdin>"), "{}", body);
+}
+
+#[test]
+fn test_error_exits_non_zero() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockOllama::start("400 Bad Request", "");
+
+    let output = run(dir.path(), &server.url, &["-p", "hello"], None);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty(), "{}", String::from_utf8_lossy(&output.stdout));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Error"));
+}
+
+#[test]
+fn test_tool_confirmation_denied_without_yes() {
+    let dir = tempfile::tempdir().unwrap();
+    let call = "```json\n{\"tool\": \"bash\", \"params\": {\"command\": \"touch created.txt\"}}\n```";
+    let server = MockOllama::start("200 OK", call);
+
+    let output = run(dir.path(), &server.url, &["-p", "create a file"], None);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("requires confirmation"));
+    assert!(!dir.path().join("project/created.txt").exists());
+
+    let output = run(dir.path(), &server.url, &["-p", "create a file", "--yes"], None);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert!(dir.path().join("project/created.txt").exists());
+}
+
+#[test]
+fn test_json_output_is_one_document_per_turn() {
+    let dir = tempfile::tempdir().unwrap();
+    let call = "Creating it.\n```json\n{\"tool\": \"bash\", \"params\": {\"command\": \"echo made\"}}\n```";
+    let server = MockOllama::start("200 OK", call);
+
+    let output = run(dir.path(), &server.url, &["-p", "make something", "--output", "json", "--yes"], None);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 1, "{}", stdout);
+    let turn: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(turn["prompt"], "make something");
+    assert_eq!(turn["assistant"], "Creating it.");
+    assert_eq!(turn["tool_calls"][0]["tool"], "bash");
+    assert_eq!(turn["tool_calls"][0]["params"]["command"], "echo made");
+    assert_eq!(turn["tool_calls"][0]["success"], true);
+    assert!(turn["tool_calls"][0]["output"].as_str().unwrap().contains("made"));
+    assert!(turn["tool_calls"][0].get("data").is_none(), "{}", stdout);
+    assert_eq!(turn["stats"]["eval_count"], 8);
+    assert_eq!(turn["stats"]["tokens_per_second"], 20.0);
+}
+
+#[test]
+fn test_json_output_includes_tool_data() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("project")).unwrap();
+    std::fs::write(dir.path().join("project/notes.txt"), "one\ntwo\n").unwrap();
+    let call = "```json\n{\"tool\": \"read\", \"params\": {\"file_path\": \"notes.txt\"}}\n```";
+    let server = MockOllama::start("200 OK", call);
+
+    let output = run(dir.path(), &server.url, &["-p", "read the notes", "--output", "json"], None);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let turn: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let data = &turn["tool_calls"][0]["data"];
+    assert_eq!(data["path"], "notes.txt", "{}", turn);
+    assert_eq!(data["total_lines"], 2);
+}
+
+#[test]
+fn test_json_output_reports_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockOllama::start("400 Bad Request", "");
+
+    let output = run(dir.path(), &server.url, &["-p", "hello", "--output", "json"], None);
+    assert!(!output.status.success());
+    let error: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(error["prompt"], "hello");
+    assert!(error["error"].is_string());
+}
+
+#[test]
+fn test_extra_roots_are_described_in_the_system_prompt() {
+    let dir = tempfile::tempdir().unwrap();
+    let web = dir.path().join("web");
+    std::fs::create_dir_all(web.join("src")).unwrap();
+    std::fs::write(web.join("package.json"), "{}").unwrap();
+    let server = MockOllama::start("200 OK", "ok");
+
+    let output = run(dir.path(), &server.url, &["--project", web.to_str().unwrap(), "-p", "hi"], None);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let body = &server.bodies()[0];
+    assert!(body.contains("# Workspace"), "{}", body);
+    assert!(body.contains("- project: "));
+    assert!(body.contains("- web: "));
+    assert!(body.contains("(Node.js)"));
+    assert!(body.contains("# Current Root\\nproject"), "{}", body);
+}
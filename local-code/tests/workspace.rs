@@ -0,0 +1,112 @@
+//! 複数ルートのワークスペースの結合テスト
+//!
+//! 2つの一時プロジェクトをルートにして、ラベル付きパスでの読み書きと検索を確かめる。
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use local_code::tools::file::{ApplyPatchTool, EditTool, ReadTool};
+use local_code::tools::search::{GlobTool, GrepTool};
+use local_code::tools::{Tool, Workspace};
+use serde_json::json;
+
+/// backend（Rust）と frontend（Node.js）の2ルート。現在のルートは backend
+fn two_projects() -> (tempfile::TempDir, Arc<Workspace>) {
+    let dir = tempfile::tempdir().unwrap();
+    let backend = dir.path().join("backend");
+    let web = dir.path().join("web");
+    std::fs::create_dir_all(backend.join("src")).unwrap();
+    std::fs::create_dir_all(web.join("src")).unwrap();
+    std::fs::write(backend.join("Cargo.toml"), "[package]\nname = \"backend\"\n").unwrap();
+    std::fs::write(backend.join("src/api.rs"), "pub fn fetch_user() -> User {\n    todo!()\n}\n").unwrap();
+    std::fs::write(web.join("package.json"), "{}\n").unwrap();
+    std::fs::write(web.join("src/app.ts"), "const user = fetchUser();\n").unwrap();
+    let workspace = Workspace::from_paths(&[backend, PathBuf::from(format!("frontend={}", web.display()))]).unwrap();
+    (dir, Arc::new(workspace))
+}
+
+#[tokio::test]
+async fn test_read_and_edit_across_roots() {
+    let (dir, workspace) = two_projects();
+    let read = ReadTool::new().with_workspace(Arc::clone(&workspace));
+    let edit = EditTool::new().with_workspace(Arc::clone(&workspace));
+
+    let result = read.execute(json!({"file_path": "frontend:src/app.ts"})).await.unwrap();
+    assert!(result.success, "{:?}", result.error);
+    assert!(result.output.starts_with("File: frontend:src/app.ts"), "{}", result.output);
+    assert!(result.output.contains("fetchUser()"));
+
+    // ラベルのないパスは現在のルート（backend）から
+    let result = read.execute(json!({"file_path": "src/api.rs"})).await.unwrap();
+    assert!(result.output.starts_with("File: backend:src/api.rs"), "{}", result.output);
+
+    let result = edit
+        .execute(json!({"file_path": "frontend:src/app.ts", "old_string": "fetchUser()", "new_string": "fetchUser(id)"}))
+        .await
+        .unwrap();
+    assert!(result.success, "{:?}", result.error);
+    assert!(result.output.contains("frontend:src/app.ts"));
+    assert_eq!(std::fs::read_to_string(dir.path().join("web/src/app.ts")).unwrap(), "const user = fetchUser(id);\n");
+
+    // 現在のルートを切り替えるとラベルのないパスの基準も変わる
+    workspace.set_current("frontend").unwrap();
+    let result = read.execute(json!({"file_path": "src/app.ts"})).await.unwrap();
+    assert!(result.output.contains("fetchUser(id)"), "{}", result.output);
+}
+
+#[tokio::test]
+async fn test_patch_targets_the_labelled_root() {
+    let (dir, workspace) = two_projects();
+    let patch_tool = ApplyPatchTool::new(dir.path().join("backend")).with_workspace(Arc::clone(&workspace));
+
+    let patch = "--- a/frontend:src/app.ts\n+++ b/frontend:src/app.ts\n@@ -1 +1 @@\n-const user = fetchUser();\n+const user = await fetchUser();\n";
+    let result = patch_tool.execute(json!({"patch": patch})).await.unwrap();
+    assert!(result.success, "{:?}", result.error);
+    assert!(result.output.contains("frontend:src/app.ts"), "{}", result.output);
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("web/src/app.ts")).unwrap(),
+        "const user = await fetchUser();\n"
+    );
+
+    let escape = "--- a/frontend:../backend/Cargo.toml\n+++ b/frontend:../backend/Cargo.toml\n@@ -1 +1 @@\n-[package]\n+[oops]\n";
+    let result = patch_tool.execute(json!({"patch": escape})).await.unwrap();
+    assert!(!result.success);
+}
+
+#[tokio::test]
+async fn test_search_defaults_to_current_root() {
+    let (_dir, workspace) = two_projects();
+    let glob = GlobTool::new().with_workspace(Arc::clone(&workspace));
+    let grep = GrepTool::new().with_workspace(Arc::clone(&workspace));
+    assert!(glob.parameters_schema()["properties"]["root"].is_object());
+
+    let result = glob.execute(json!({"pattern": "src/*"})).await.unwrap();
+    assert_eq!(result.data.unwrap()["paths"], json!(["backend:src/api.rs"]));
+
+    let result = glob.execute(json!({"pattern": "src/*", "root": "all"})).await.unwrap();
+    assert_eq!(result.data.unwrap()["paths"], json!(["backend:src/api.rs", "frontend:src/app.ts"]));
+
+    let result = grep.execute(json!({"pattern": "(?i)fetch_?user", "root": "frontend"})).await.unwrap();
+    assert!(result.output.contains("frontend:src/app.ts:1:const user"), "{}", result.output);
+    assert!(!result.output.contains("backend:"));
+
+    let result = grep.execute(json!({"pattern": "x", "root": "docs"})).await.unwrap();
+    assert!(!result.success);
+}
+
+#[tokio::test]
+async fn test_single_root_is_unchanged() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("a.txt"), "hello\n").unwrap();
+    let workspace = Arc::new(Workspace::single(dir.path()));
+    let glob = GlobTool::new().with_workspace(Arc::clone(&workspace));
+    assert!(glob.parameters_schema()["properties"].get("root").is_none());
+
+    let file = dir.path().join("a.txt");
+    let result = ReadTool::new()
+        .with_workspace(workspace)
+        .execute(json!({"file_path": file.to_str().unwrap()}))
+        .await
+        .unwrap();
+    assert!(result.output.starts_with(&format!("File: {}", file.display())));
+}